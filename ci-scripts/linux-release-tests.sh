@@ -23,6 +23,10 @@ python script/headless.py --precache --use-unoptimized-shaders test_init
 python script/headless.py reftest
 python script/headless.py rawtest
 python script/headless.py test_invalidation
+python script/headless.py test_yaml_round_trip
 CXX=clang++ cargo run ${CARGOFLAGS} --release --features=software -- \
   --software --headless reftest
+# HDR falls back to SDR with SWGL, so this must render the same as without it.
+CXX=clang++ cargo run ${CARGOFLAGS} --release --features=software -- \
+  --software --headless --hdr clip reftest reftests/image/yuv-pq.yaml
 popd
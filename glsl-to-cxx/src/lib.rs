@@ -241,6 +241,9 @@ fn translate_shader(
             write!(state, " return this;\n}}\n");
             write!(state, "const char* get_name() const override {{ return \"{}\"; }}\n", name);
             write!(state, "static ProgramImpl* loader() {{ return new {}_program; }}\n", name);
+            write!(state, "ProgramImpl* clone() const override {{ return new {}_program(*this); }}\n", name);
+            write!(state, "void copy_from(const ProgramImpl* other) override {{\n");
+            write!(state, " *this = *static_cast<const {}_program*>(other);\n}}\n", name);
             write!(state, "}};\n\n");
         }
 
@@ -587,6 +590,53 @@ fn write_store_outputs(state: &mut OutputState, outputs: &[hir::SymRef]) {
     write!(state, "    dest_ptr += stride;\n");
     write!(state, "  }}\n");
     write!(state, "}}\n");
+
+    write_flat_outputs(state, outputs);
+}
+
+/// Flat outputs aren't interpolated, but stored in the shader itself for the
+/// fragment shader to read. They can be saved and restored, so that primitives
+/// can be rasterized after the vertex shader ran for others.
+fn write_flat_outputs(state: &mut OutputState, outputs: &[hir::SymRef]) {
+    let flat_outputs: Vec<_> = outputs
+        .iter()
+        .filter(|i| match &state.hir.sym(**i).decl {
+            hir::SymDecl::Global(_, _, _, run_class) => *run_class == hir::RunClass::Scalar,
+            _ => panic!(),
+        })
+        .cloned()
+        .collect();
+
+    let is_scalar = state.is_scalar.replace(true);
+    write!(state, "public:\nstruct FlatOutputs {{\n");
+    for i in &flat_outputs {
+        let sym = state.hir.sym(*i);
+        match &sym.decl {
+            hir::SymDecl::Global(_, _, ty, _) => {
+                show_type(state, ty);
+                write!(state, " {};\n", sym.name.as_str());
+            }
+            _ => panic!(),
+        }
+    }
+    write!(state, "}};\nprivate:\n");
+    state.is_scalar.set(is_scalar);
+
+    write!(state, "static void store_flat_outputs(Self* self, char* dest_ptr) {{\n");
+    write!(state, "  auto* dest = reinterpret_cast<FlatOutputs*>(dest_ptr);\n");
+    for i in &flat_outputs {
+        let name = state.hir.sym(*i).name.as_str();
+        write!(state, "  dest->{0} = self->{0};\n", name);
+    }
+    write!(state, "}}\n");
+
+    write!(state, "static void load_flat_outputs(Self* self, const char* src_ptr) {{\n");
+    write!(state, "  auto* src = reinterpret_cast<const FlatOutputs*>(src_ptr);\n");
+    for i in &flat_outputs {
+        let name = state.hir.sym(*i).name.as_str();
+        write!(state, "  self->{0} = src->{0};\n", name);
+    }
+    write!(state, "}}\n");
 }
 
 fn write_read_inputs(state: &mut OutputState, inputs: &[hir::SymRef]) {
@@ -3643,6 +3693,9 @@ fn write_abi(state: &mut OutputState) {
             state.write(" init_batch_func = (InitBatchFunc)&init_batch;\n");
             state.write(" load_attribs_func = (LoadAttribsFunc)&load_attribs;\n");
             state.write(" run_primitive_func = (RunPrimitiveFunc)&run;\n");
+            state.write(" store_flat_outputs_func = (StoreFlatOutputsFunc)&store_flat_outputs;\n");
+            state.write(" load_flat_outputs_func = (LoadFlatOutputsFunc)&load_flat_outputs;\n");
+            state.write(" flat_outputs_size = sizeof(FlatOutputs);\n");
             if state.hir.used_clip_dist != 0 {
                 state.write(" enable_clip_distance();\n");
             }
@@ -332,15 +332,17 @@ enum SWGLClipFlag {
   SWGL_CLIP_FLAG_AA = 1 << 1,
   SWGL_CLIP_FLAG_BLEND_OVERRIDE = 1 << 2,
 };
-static int swgl_ClipFlags = 0;
-static BlendKey swgl_BlendOverride = BLEND_KEY_NONE;
-static WideRGBA8 swgl_BlendColorRGBA8 = {0};
-static WideRGBA8 swgl_BlendAlphaRGBA8 = {0};
+// The clip and blend state is set up per primitive, and is thread-local so
+// that draw calls can be rasterized on multiple threads.
+static thread_local int swgl_ClipFlags = 0;
+static thread_local BlendKey swgl_BlendOverride = BLEND_KEY_NONE;
+static thread_local WideRGBA8 swgl_BlendColorRGBA8 = {0};
+static thread_local WideRGBA8 swgl_BlendAlphaRGBA8 = {0};
 
 // A pointer into the color buffer for the start of the span.
-static void* swgl_SpanBuf = nullptr;
+static thread_local void* swgl_SpanBuf = nullptr;
 // A pointer into the clip mask for the start of the span.
-static uint8_t* swgl_ClipMaskBuf = nullptr;
+static thread_local uint8_t* swgl_ClipMaskBuf = nullptr;
 
 static ALWAYS_INLINE WideR8 expand_mask(UNUSED uint8_t* buf, WideR8 mask) {
   return mask;
@@ -378,14 +380,14 @@ static ALWAYS_INLINE void restore_clip_mask() {
 }
 
 // A pointer to the start of the opaque destination region of the span for AA.
-static const uint8_t* swgl_OpaqueStart = nullptr;
+static thread_local const uint8_t* swgl_OpaqueStart = nullptr;
 // The size, in bytes, of the opaque region.
-static uint32_t swgl_OpaqueSize = 0;
+static thread_local uint32_t swgl_OpaqueSize = 0;
 // AA coverage distance offsets for the left and right edges.
-static Float swgl_LeftAADist = 0.0f;
-static Float swgl_RightAADist = 0.0f;
+static thread_local Float swgl_LeftAADist = 0.0f;
+static thread_local Float swgl_RightAADist = 0.0f;
 // AA coverage slope values used for accumulating coverage for each step.
-static Float swgl_AASlope = 0.0f;
+static thread_local Float swgl_AASlope = 0.0f;
 
 // Get the amount of pixels we need to process before the start of the opaque
 // region.
@@ -414,6 +414,8 @@ struct Texture {
   // to be cleared later and save the value they need to be cleared with so
   // that we can clear these rows individually when they are touched by draws.
   // This currently only works for 2D textures, but not on texture arrays.
+  // Draw calls rasterized in bands on multiple threads update the count of
+  // rows left to clear atomically, see prepare_row.
   int delay_clear = 0;
   uint32_t clear_val = 0;
   uint32_t* cleared_rows = nullptr;
@@ -726,6 +728,14 @@ struct ObjectStore {
   O** end() const { return &objects[size]; }
 };
 
+// Runs count jobs in parallel on the given workers, and returns once they all
+// finished.
+typedef void (*RunRasterizeJobsFunc)(void* workers, int count,
+                                     void (*job)(void* data, int index),
+                                     void* data);
+// Releases the workers once the context no longer uses them.
+typedef void (*ReleaseRasterizeWorkersFunc)(void* workers);
+
 struct Context {
   int32_t references = 1;
 
@@ -791,6 +801,19 @@ struct Context {
   GLuint read_framebuffer_binding = 0;
   GLuint unknown_binding = 0;
 
+  // Optional worker threads that large draw calls are split across in bands
+  // of rows, see SetRasterizeWorkers.
+  void* rasterize_workers = nullptr;
+  int num_rasterize_threads = 0;
+  RunRasterizeJobsFunc run_rasterize_jobs = nullptr;
+  ReleaseRasterizeWorkersFunc release_rasterize_workers = nullptr;
+
+  ~Context() {
+    if (release_rasterize_workers) {
+      release_rasterize_workers(rasterize_workers);
+    }
+  }
+
   GLuint& get_binding(GLenum name) {
     switch (name) {
       case GL_PIXEL_PACK_BUFFER:
@@ -844,9 +867,12 @@ struct Context {
   }
 };
 static Context* ctx = nullptr;
-static VertexShaderImpl* vertex_shader = nullptr;
-static FragmentShaderImpl* fragment_shader = nullptr;
-static BlendKey blend_key = BLEND_KEY_NONE;
+// The shaders and blend state used for rasterization are thread-local, so that
+// worker threads can rasterize parts of a draw call with their own copy of the
+// program.
+static thread_local VertexShaderImpl* vertex_shader = nullptr;
+static thread_local FragmentShaderImpl* fragment_shader = nullptr;
+static thread_local BlendKey blend_key = BLEND_KEY_NONE;
 
 static void prepare_texture(Texture& t, const IntRect* skip = nullptr);
 
@@ -1592,6 +1618,12 @@ static GLenum remap_internal_format(GLenum format) {
       return GL_RG8;
     case GL_RGB_422_APPLE:
       return GL_RGB_RAW_422_APPLE;
+    // There is no native storage for half-float or 10-bit formats. They are
+    // converted on upload to RGBA32F, which holds them without loss.
+    case GL_RGBA16F:
+    case GL_R16F:
+    case GL_RGB10_A2:
+      return GL_RGBA32F;
     default:
       return format;
   }
@@ -1600,15 +1632,84 @@ static GLenum remap_internal_format(GLenum format) {
 }  // extern "C"
 
 static bool format_requires_conversion(GLenum external_format,
-                                       GLenum internal_format) {
+                                       GLenum internal_format,
+                                       GLenum ty = GL_UNSIGNED_BYTE) {
   switch (external_format) {
     case GL_RGBA:
-      return internal_format == GL_RGBA8;
+      return internal_format == GL_RGBA8 ||
+             (internal_format == GL_RGBA32F &&
+              (ty == GL_HALF_FLOAT || ty == GL_UNSIGNED_INT_2_10_10_10_REV));
+    case GL_RED:
+      return internal_format == GL_RGBA32F && ty == GL_HALF_FLOAT;
     default:
       return false;
   }
 }
 
+// Returns the number of bytes per pixel of client data with the given format
+// and type, when that differs from the size of the internal format.
+static int bytes_for_data(GLenum format, GLenum ty) {
+  switch (ty) {
+    case GL_HALF_FLOAT:
+      return format == GL_RED ? 2 : 4 * 2;
+    case GL_UNSIGNED_INT_2_10_10_10_REV:
+      return 4;
+    default:
+      return bytes_for_internal_format(format);
+  }
+}
+
+static inline float half_to_float(uint16_t h) {
+  uint32_t sign = uint32_t(h & 0x8000) << 16;
+  uint32_t exponent = (h >> 10) & 0x1F;
+  uint32_t mantissa = h & 0x3FF;
+  uint32_t bits;
+  if (exponent == 0x1F) {
+    // Infinity or NaN
+    bits = sign | 0x7F800000 | (mantissa << 13);
+  } else if (exponent != 0) {
+    bits = sign | ((exponent + 112) << 23) | (mantissa << 13);
+  } else if (mantissa != 0) {
+    // Denormals are exactly representable as normal floats.
+    float f = float(mantissa) * (1.0f / (1 << 24));
+    return sign ? -f : f;
+  } else {
+    bits = sign;
+  }
+  float f;
+  memcpy(&f, &bits, sizeof(f));
+  return f;
+}
+
+static inline void copy_rgba16f_to_rgba32f(float* dest, const uint16_t* src,
+                                           int width) {
+  for (int i = 0; i < width * 4; i++) {
+    dest[i] = half_to_float(src[i]);
+  }
+}
+
+// Single channel textures sample as (r, 0, 0, 1).
+static inline void copy_r16f_to_rgba32f(float* dest, const uint16_t* src,
+                                        int width) {
+  for (; width > 0; width--, dest += 4, src++) {
+    dest[0] = half_to_float(*src);
+    dest[1] = 0.0f;
+    dest[2] = 0.0f;
+    dest[3] = 1.0f;
+  }
+}
+
+static inline void copy_rgb10a2_to_rgba32f(float* dest, const uint32_t* src,
+                                           int width) {
+  for (; width > 0; width--, dest += 4, src++) {
+    uint32_t p = *src;
+    dest[0] = float(p & 0x3FF) * (1.0f / 1023.0f);
+    dest[1] = float((p >> 10) & 0x3FF) * (1.0f / 1023.0f);
+    dest[2] = float((p >> 20) & 0x3FF) * (1.0f / 1023.0f);
+    dest[3] = float(p >> 30) * (1.0f / 3.0f);
+  }
+}
+
 static inline void copy_bgra8_to_rgba8(uint32_t* dest, const uint32_t* src,
                                        int width) {
   for (; width >= 4; width -= 4, dest += 4, src += 4) {
@@ -1626,9 +1727,29 @@ static inline void copy_bgra8_to_rgba8(uint32_t* dest, const uint32_t* src,
 static void convert_copy(GLenum external_format, GLenum internal_format,
                          uint8_t* dst_buf, size_t dst_stride,
                          const uint8_t* src_buf, size_t src_stride,
-                         size_t width, size_t height) {
+                         size_t width, size_t height,
+                         GLenum ty = GL_UNSIGNED_BYTE) {
   switch (external_format) {
     case GL_RGBA:
+      if (internal_format == GL_RGBA32F &&
+          ty == GL_UNSIGNED_INT_2_10_10_10_REV) {
+        for (; height; height--) {
+          copy_rgb10a2_to_rgba32f((float*)dst_buf, (const uint32_t*)src_buf,
+                                  width);
+          dst_buf += dst_stride;
+          src_buf += src_stride;
+        }
+        return;
+      }
+      if (internal_format == GL_RGBA32F && ty == GL_HALF_FLOAT) {
+        for (; height; height--) {
+          copy_rgba16f_to_rgba32f((float*)dst_buf, (const uint16_t*)src_buf,
+                                  width);
+          dst_buf += dst_stride;
+          src_buf += src_stride;
+        }
+        return;
+      }
       if (internal_format == GL_RGBA8) {
         for (; height; height--) {
           copy_bgra8_to_rgba8((uint32_t*)dst_buf, (const uint32_t*)src_buf,
@@ -1639,6 +1760,17 @@ static void convert_copy(GLenum external_format, GLenum internal_format,
         return;
       }
       break;
+    case GL_RED:
+      if (internal_format == GL_RGBA32F && ty == GL_HALF_FLOAT) {
+        for (; height; height--) {
+          copy_r16f_to_rgba32f((float*)dst_buf, (const uint16_t*)src_buf,
+                               width);
+          dst_buf += dst_stride;
+          src_buf += src_stride;
+        }
+        return;
+      }
+      break;
     default:
       break;
   }
@@ -1714,6 +1846,12 @@ GLenum internal_format_for_data(GLenum format, GLenum ty) {
     return GL_RGB_RAW_422_APPLE;
   } else if (format == GL_RED && ty == GL_UNSIGNED_SHORT) {
     return GL_R16;
+  } else if (format == GL_RGBA && ty == GL_HALF_FLOAT) {
+    return GL_RGBA32F;
+  } else if (format == GL_RED && ty == GL_HALF_FLOAT) {
+    return GL_RGBA32F;
+  } else if (format == GL_RGBA && ty == GL_UNSIGNED_INT_2_10_10_10_REV) {
+    return GL_RGBA32F;
   } else {
     debugf("unknown internal format for format %x, type %x\n", format, ty);
     assert(false);
@@ -1765,13 +1903,13 @@ void TexSubImage2D(GLenum target, GLint level, GLint xoffset, GLint yoffset,
   GLsizei row_length =
       ctx->unpack_row_length != 0 ? ctx->unpack_row_length : width;
   assert(t.internal_format == internal_format_for_data(format, ty));
-  int src_bpp = format_requires_conversion(format, t.internal_format)
-                    ? bytes_for_internal_format(format)
+  int src_bpp = format_requires_conversion(format, t.internal_format, ty)
+                    ? bytes_for_data(format, ty)
                     : t.bpp();
   if (!src_bpp || !t.buf) return;
   convert_copy(format, t.internal_format,
                (uint8_t*)t.sample_ptr(xoffset, yoffset), t.stride(),
-               (const uint8_t*)data, row_length * src_bpp, width, height);
+               (const uint8_t*)data, row_length * src_bpp, width, height, ty);
 }
 
 void TexImage2D(GLenum target, GLint level, GLint internal_format,
@@ -2534,7 +2672,7 @@ void ReadPixels(GLint x, GLint y, GLsizei width, GLsizei height, GLenum format,
     return;
   }
   // Only support readback conversions that are reversible
-  assert(!format_requires_conversion(format, t.internal_format) ||
+  assert(!format_requires_conversion(format, t.internal_format, type) ||
          bytes_for_internal_format(format) == t.bpp());
   uint8_t* dest = (uint8_t*)data;
   size_t destStride = width * t.bpp();
@@ -2698,43 +2836,9 @@ void DrawElementsInstanced(GLenum mode, GLsizei count, GLenum type,
 
   vertex_shader->init_batch();
 
-  switch (type) {
-    case GL_UNSIGNED_SHORT:
-      assert(mode == GL_TRIANGLES);
-      draw_elements<uint16_t>(count, instancecount, offset, v, colortex,
-                              depthtex);
-      break;
-    case GL_UNSIGNED_INT:
-      assert(mode == GL_TRIANGLES);
-      draw_elements<uint32_t>(count, instancecount, offset, v, colortex,
-                              depthtex);
-      break;
-    case GL_NONE:
-      // Non-standard GL extension - if element type is GL_NONE, then we don't
-      // use any element buffer and behave as if DrawArrays was called instead.
-      for (GLsizei instance = 0; instance < instancecount; instance++) {
-        switch (mode) {
-          case GL_LINES:
-            for (GLsizei i = 0; i + 2 <= count; i += 2) {
-              vertex_shader->load_attribs(v.attribs, offset + i, instance, 2);
-              draw_quad(2, colortex, depthtex);
-            }
-            break;
-          case GL_TRIANGLES:
-            for (GLsizei i = 0; i + 3 <= count; i += 3) {
-              vertex_shader->load_attribs(v.attribs, offset + i, instance, 3);
-              draw_quad(3, colortex, depthtex);
-            }
-            break;
-          default:
-            assert(false);
-            break;
-        }
-      }
-      break;
-    default:
-      assert(false);
-      break;
+  DrawBatch batch = {mode, count, type, offset, instancecount};
+  if (!draw_batch_in_bands(batch, v, colortex, depthtex)) {
+    draw_batch(batch, v, colortex, depthtex);
   }
 
   if (ctx->samples_passed_query) {
@@ -2762,13 +2866,14 @@ void Finish() {
 }
 
 void MakeCurrent(Context* c) {
-  if (ctx == c) {
-    return;
-  }
   ctx = c;
+  // The bound shaders are thread-local, so set them up even if the context was
+  // already current, as that may have been on another thread.
   setup_program(ctx ? ctx->current_program : 0);
 }
 
+Context* GetCurrentContext() { return ctx; }
+
 Context* CreateContext() { return new Context; }
 
 void ReferenceContext(Context* c) {
@@ -2793,6 +2898,21 @@ void DestroyContext(Context* c) {
   delete c;
 }
 
+void SetRasterizeWorkers(Context* c, void* workers, int num_threads,
+                         RunRasterizeJobsFunc run,
+                         ReleaseRasterizeWorkersFunc release) {
+  if (!c) {
+    return;
+  }
+  if (c->release_rasterize_workers) {
+    c->release_rasterize_workers(c->rasterize_workers);
+  }
+  c->rasterize_workers = workers;
+  c->num_rasterize_threads = workers ? num_threads : 0;
+  c->run_rasterize_jobs = run;
+  c->release_rasterize_workers = release;
+}
+
 size_t ReportMemory(Context *ctx, size_t (*size_of_op)(void*)) {
   size_t size = 0;
   if (ctx) {
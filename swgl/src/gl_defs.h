@@ -36,6 +36,9 @@ typedef intptr_t GLintptr;
 #define GL_RGBA32I 0x8D82
 #define GL_BGRA8 0x93A1
 #define GL_RG8 0x822B
+#define GL_RGBA16F 0x881A
+#define GL_R16F 0x822D
+#define GL_RGB10_A2 0x8059
 
 #define GL_BYTE 0x1400
 #define GL_UNSIGNED_BYTE 0x1401
@@ -45,6 +48,7 @@ typedef intptr_t GLintptr;
 #define GL_UNSIGNED_INT 0x1405
 #define GL_FLOAT 0x1406
 #define GL_DOUBLE 0x1408
+#define GL_HALF_FLOAT 0x140B
 
 #define GL_RED 0x1903
 #define GL_GREEN 0x1904
@@ -190,6 +194,7 @@ typedef intptr_t GLintptr;
 #define GL_QUADS 0x0007
 
 #define GL_UNSIGNED_INT_8_8_8_8_REV 0x8367
+#define GL_UNSIGNED_INT_2_10_10_10_REV 0x8368
 
 #define GL_RGB_422_APPLE 0x8A1F
 #define GL_UNSIGNED_SHORT_8_8_APPLE 0x85BA
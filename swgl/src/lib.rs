@@ -6,6 +6,7 @@
 #![crate_type = "lib"]
 
 extern crate gleam;
+extern crate rayon;
 
 mod swgl_fns;
 
@@ -30,6 +30,14 @@ struct ProgramImpl {
   virtual VertexShaderImpl* get_vertex_shader() = 0;
   virtual FragmentShaderImpl* get_fragment_shader() = 0;
   virtual const char* get_name() const = 0;
+  // Copies the program along with its uniform state, so that the copy can be
+  // used to rasterize on another thread. Samplers must be rebound with
+  // init_batch before the copy is used.
+  virtual ProgramImpl* clone() const = 0;
+  // Copies the uniform state of another instance of the same program into
+  // this one, without allocating a new copy. Samplers must be rebound with
+  // init_batch afterwards.
+  virtual void copy_from(const ProgramImpl* other) = 0;
 };
 
 typedef ProgramImpl* (*ProgramLoader)();
@@ -48,6 +56,8 @@ struct VertexShaderImpl {
                                   uint32_t start, int instance, int count);
   typedef void (*RunPrimitiveFunc)(VertexShaderImpl*, char* interps,
                                    size_t interp_stride);
+  typedef void (*StoreFlatOutputsFunc)(VertexShaderImpl*, char* dest);
+  typedef void (*LoadFlatOutputsFunc)(VertexShaderImpl*, const char* src);
 
   SetUniform1iFunc set_uniform_1i_func = nullptr;
   SetUniform4fvFunc set_uniform_4fv_func = nullptr;
@@ -55,6 +65,12 @@ struct VertexShaderImpl {
   InitBatchFunc init_batch_func = nullptr;
   LoadAttribsFunc load_attribs_func = nullptr;
   RunPrimitiveFunc run_primitive_func = nullptr;
+  StoreFlatOutputsFunc store_flat_outputs_func = nullptr;
+  LoadFlatOutputsFunc load_flat_outputs_func = nullptr;
+
+  // The size of the flat outputs of the vertex shader, which the fragment
+  // shader reads from the shader itself rather than from the interpolants.
+  size_t flat_outputs_size = 0;
 
   enum FLAGS {
     CLIP_DISTANCE = 1 << 0,
@@ -90,6 +106,16 @@ struct VertexShaderImpl {
   ALWAYS_INLINE void run_primitive(char* interps, size_t interp_stride) {
     (*run_primitive_func)(this, interps, interp_stride);
   }
+
+  // Saves the flat outputs of the last primitive, so that it can be
+  // rasterized after the vertex shader ran for other primitives.
+  ALWAYS_INLINE void store_flat_outputs(char* dest) {
+    (*store_flat_outputs_func)(this, dest);
+  }
+
+  ALWAYS_INLINE void load_flat_outputs(const char* src) {
+    (*load_flat_outputs_func)(this, src);
+  }
 };
 
 // The number of pixels in a step.
@@ -397,6 +397,11 @@ ALWAYS_INLINE void discard_output<false>(uint8_t* buf, int span) {
   store_span(buf, pack(r), span);
 }
 
+// The rows and pixels shaded by the current thread, which are accumulated into
+// the context after each draw call.
+static thread_local int thread_shaded_rows = 0;
+static thread_local int thread_shaded_pixels = 0;
+
 struct ClipRect {
   float x0;
   float y0;
@@ -706,6 +711,11 @@ static ALWAYS_INLINE void draw_span(P* buf, DepthRun* depth, int span, Z z) {
   }
 }
 
+// Whether the color buffer has rows left to clear, see prepare_row.
+static ALWAYS_INLINE bool has_delayed_clear(const Texture& colortex) {
+  return __atomic_load_n(&colortex.delay_clear, __ATOMIC_RELAXED) != 0;
+}
+
 // Called during rasterization to forcefully clear a row on which delayed clear
 // has been enabled. If we know that we are going to completely overwrite a part
 // of the row, then we only need to clear the row outside of that part. However,
@@ -716,12 +726,14 @@ template <typename P>
 static inline void prepare_row(Texture& colortex, int y, int startx, int endx,
                                bool use_discard, DepthRun* depth,
                                uint32_t z = 0, DepthCursor* cursor = nullptr) {
-  assert(colortex.delay_clear > 0);
-  // Delayed clear is enabled for the color buffer. Check if needs clear.
+  assert(has_delayed_clear(colortex));
+  // Delayed clear is enabled for the color buffer. Check if needs clear. Bands
+  // rasterized on other threads never share words of the row mask, but they
+  // do share the count of rows left to clear.
   uint32_t& mask = colortex.cleared_rows[y / 32];
   if ((mask & (1 << (y & 31))) == 0) {
     mask |= 1 << (y & 31);
-    colortex.delay_clear--;
+    __atomic_fetch_sub(&colortex.delay_clear, 1, __ATOMIC_RELAXED);
     if (blend_key || use_discard) {
       // If depth test, blending, or discard is used, old color values
       // might be sampled, so we need to clear the entire row to fill it.
@@ -951,8 +963,8 @@ static inline void draw_quad_spans(int nump, Point2D p[4], uint32_t z,
         span = span.intersect(clip_distance_range(left, right));
         if (span.len() <= 0) goto next_span;
       }
-      ctx->shaded_rows++;
-      ctx->shaded_pixels += span.len();
+      thread_shaded_rows++;
+      thread_shaded_pixels += span.len();
       // Advance color/depth buffer pointers to the start of the span.
       P* buf = fbuf + span.start;
       // Check if we will need to use depth-buffer or discard on this span.
@@ -991,7 +1003,7 @@ static inline void draw_quad_spans(int nump, Point2D p[4], uint32_t z,
         }
       }
 
-      if (colortex.delay_clear) {
+      if (has_delayed_clear(colortex)) {
         // Delayed clear is enabled for the color buffer. Check if needs clear.
         prepare_row<P>(colortex, int(y), span.start, span.end, use_discard,
                        depth, z, &cursor);
@@ -1201,8 +1213,8 @@ static inline void draw_perspective_spans(int nump, Point3D* p,
         span = span.intersect(clip_distance_range(left, right));
         if (span.len() <= 0) goto next_span;
       }
-      ctx->shaded_rows++;
-      ctx->shaded_pixels += span.len();
+      thread_shaded_rows++;
+      thread_shaded_pixels += span.len();
       // Advance color/depth buffer pointers to the start of the span.
       P* buf = fbuf + span.start;
       // Check if the we will need to use depth-buffer or discard on this span.
@@ -1219,7 +1231,7 @@ static inline void draw_perspective_spans(int nump, Point3D* p,
         // Advance to the depth sample at the start of the span.
         depth += span.start;
       }
-      if (colortex.delay_clear) {
+      if (has_delayed_clear(colortex)) {
         // Delayed clear is enabled for the color buffer. Check if needs clear.
         prepare_row<P>(colortex, int(y), span.start, span.end, use_discard,
                        depth);
@@ -1415,45 +1427,102 @@ static int clip_side(int nump, Point3D* p, Interpolants* interp, Point3D* outP,
   return numClip;
 }
 
-// Helper function to dispatch to perspective span drawing with points that
-// have already been transformed and clipped.
-static inline void draw_perspective_clipped(int nump, Point3D* p_clip,
-                                            Interpolants* interp_clip,
-                                            Texture& colortex,
-                                            Texture& depthtex) {
-  // If polygon is ouside clip rect, nothing to draw.
-  ClipRect clipRect(colortex);
-  if (!clipRect.overlaps(nump, p_clip)) {
-    return;
+// A primitive that was transformed to screen space and clipped, along with
+// the state the vertex shader set up for rasterizing it. This allows the
+// primitive to be rasterized later, in bands on multiple threads at once.
+struct ScreenPrimitive {
+  int nump = 0;
+  // Whether the primitive needs perspective-correct rasterization, in which
+  // case its points are in p3, and otherwise in p2.
+  bool perspective = false;
+  Point2D p2[4];
+  // Clipping can expand the points by 1 for each of 6 view frustum planes.
+  Point3D p3[4 + 6];
+  Interpolants interp[4 + 6];
+  // The depth of a 2D primitive, which is constant across it, along with the
+  // Z and W of its fragments.
+  uint32_t z = 0;
+  float frag_z = 0.0f;
+  float frag_w = 0.0f;
+  ClipRect clip_rect = ClipRect(IntRect());
+  // The clip and blend state set by the vertex shader and resolved by
+  // ClipRect, see save_state.
+  BlendKey blend = BLEND_KEY_NONE;
+  int clip_flags = 0;
+  BlendKey blend_override = BLEND_KEY_NONE;
+  WideRGBA8 blend_color = {0};
+  WideRGBA8 blend_alpha = {0};
+  sampler2D clip_mask = nullptr;
+  IntPoint clip_mask_offset = {0, 0};
+  IntRect clip_mask_bounds = {0, 0, 0, 0};
+  int aa_edge_mask = 0;
+
+  void save_state() {
+    blend = blend_key;
+    clip_flags = swgl_ClipFlags;
+    blend_override = swgl_BlendOverride;
+    blend_color = swgl_BlendColorRGBA8;
+    blend_alpha = swgl_BlendAlphaRGBA8;
+    clip_mask = swgl_ClipMask;
+    clip_mask_offset = swgl_ClipMaskOffset;
+    clip_mask_bounds = swgl_ClipMaskBounds;
+    aa_edge_mask = swgl_AAEdgeMask;
   }
 
-  // Finally draw perspective-correct spans for the polygon.
-  if (colortex.internal_format == GL_RGBA8) {
-    draw_perspective_spans<uint32_t>(nump, p_clip, interp_clip, colortex,
-                                     depthtex, clipRect);
-  } else if (colortex.internal_format == GL_R8) {
-    draw_perspective_spans<uint8_t>(nump, p_clip, interp_clip, colortex,
-                                    depthtex, clipRect);
-  } else {
-    assert(false);
+  void restore_state() const {
+    blend_key = blend;
+    swgl_ClipFlags = clip_flags;
+    swgl_BlendOverride = blend_override;
+    swgl_BlendColorRGBA8 = blend_color;
+    swgl_BlendAlphaRGBA8 = blend_alpha;
+    swgl_ClipMask = clip_mask;
+    swgl_ClipMaskOffset = clip_mask_offset;
+    swgl_ClipMaskBounds = clip_mask_bounds;
+    swgl_AAEdgeMask = aa_edge_mask;
+  }
+
+  // Whether the primitive may cover any rows within its clip rect.
+  bool overlaps_clip_rect() const {
+    return perspective ? clip_rect.overlaps(nump, p3)
+                       : clip_rect.overlaps(nump, p2);
   }
-}
 
-// Draws a perspective-correct 3D primitive with varying Z value, as opposed
-// to a simple 2D planar primitive with a constant Z value that could be
-// trivially Z rejected. This requires clipping the primitive against the near
-// and far planes to ensure it stays within the valid Z-buffer range. The Z
-// and W of each fragment of the primitives are interpolated across the
+  // The range of rows that the points of the primitive span.
+  template <typename P>
+  static void row_range(int nump, const P* p, float& y0, float& y1) {
+    y0 = y1 = p[0].y;
+    for (int i = 1; i < nump; i++) {
+      y0 = min(y0, p[i].y);
+      y1 = max(y1, p[i].y);
+    }
+  }
+
+  void row_range(float& y0, float& y1) const {
+    if (perspective) {
+      row_range(nump, p3, y0, y1);
+    } else {
+      row_range(nump, p2, y0, y1);
+    }
+  }
+};
+
+// Transforms a perspective-correct 3D primitive with varying Z value, as
+// opposed to a simple 2D planar primitive with a constant Z value that could
+// be trivially Z rejected. This requires clipping the primitive against the
+// near and far planes to ensure it stays within the valid Z-buffer range. The
+// Z and W of each fragment of the primitives are interpolated across the
 // generated spans and then depth-tested as appropriate.
 // Additionally, vertex attributes must be interpolated with perspective-
 // correction by dividing by W before interpolation, and then later multiplied
 // by W again to produce the final correct attribute value for each fragment.
 // This process is expensive and should be avoided if possible for primitive
 // batches that are known ahead of time to not need perspective-correction.
-static void draw_perspective(int nump, Interpolants interp_outs[4],
-                             Texture& colortex, Texture& depthtex) {
+// Returns false if there is nothing to draw.
+static bool setup_perspective(int nump, Texture& colortex,
+                              ScreenPrimitive& prim) {
   // Lines are not supported with perspective.
   assert(nump >= 3);
+  prim.perspective = true;
   // Convert output of vertex shader to screen space.
   vec4 pos = vertex_shader->gl_Position;
   vec3_scalar scale =
@@ -1461,6 +1530,8 @@ static void draw_perspective(int nump, Interpolants interp_outs[4],
   vec3_scalar offset =
       make_vec3(make_vec2(ctx->viewport.origin() - colortex.offset), 0.0f) +
       scale;
+  Point3D* p_clip = prim.p3;
+  Interpolants* interp_clip = prim.interp;
   // Verify if point is between near and far planes, rejecting NaN.
   if (test_all(pos.z > -pos.w && pos.z < pos.w)) {
     // No points cross the near or far planes, so no clipping required.
@@ -1469,11 +1540,10 @@ static void draw_perspective(int nump, Interpolants interp_outs[4],
     // otherwise fail the test_none condition.
     Float w = 1.0f / pos.w;
     vec3 screen = pos.sel(X, Y, Z) * w * scale + offset;
-    Point3D p[4] = {{screen.x.x, screen.y.x, screen.z.x, w.x},
-                    {screen.x.y, screen.y.y, screen.z.y, w.y},
-                    {screen.x.z, screen.y.z, screen.z.z, w.z},
-                    {screen.x.w, screen.y.w, screen.z.w, w.w}};
-    draw_perspective_clipped(nump, p, interp_outs, colortex, depthtex);
+    p_clip[0] = {screen.x.x, screen.y.x, screen.z.x, w.x};
+    p_clip[1] = {screen.x.y, screen.y.y, screen.z.y, w.y};
+    p_clip[2] = {screen.x.z, screen.y.z, screen.z.z, w.z};
+    p_clip[3] = {screen.x.w, screen.y.w, screen.z.w, w.w};
   } else {
     // Points cross the near or far planes, so we need to clip.
     // Start with the original 3 or 4 points...
@@ -1481,15 +1551,14 @@ static void draw_perspective(int nump, Interpolants interp_outs[4],
                     {pos.x.y, pos.y.y, pos.z.y, pos.w.y},
                     {pos.x.z, pos.y.z, pos.z.z, pos.w.z},
                     {pos.x.w, pos.y.w, pos.z.w, pos.w.w}};
-    // Clipping can expand the points by 1 for each of 6 view frustum planes.
-    Point3D p_clip[4 + 6];
-    Interpolants interp_clip[4 + 6];
+    Interpolants interp_outs[4] = {prim.interp[0], prim.interp[1],
+                                   prim.interp[2], prim.interp[3]};
     // Clip against near and far Z planes.
     nump = clip_side<Z>(nump, p, interp_outs, p_clip, interp_clip,
                         swgl_AAEdgeMask);
     // If no points are left inside the view frustum, there's nothing to draw.
     if (nump < 3) {
-      return;
+      return false;
     }
     // After clipping against only the near and far planes, we might still
     // produce points where W = 0, exactly at the camera plane. OpenGL specifies
@@ -1513,10 +1582,10 @@ static void draw_perspective(int nump, Interpolants interp_outs[4],
         Interpolants interp_tmp[4 + 6];
         nump = clip_side<X>(nump, p_clip, interp_clip, p_tmp, interp_tmp,
                             swgl_AAEdgeMask);
-        if (nump < 3) return;
+        if (nump < 3) return false;
         nump = clip_side<Y>(nump, p_tmp, interp_tmp, p_clip, interp_clip,
                             swgl_AAEdgeMask);
-        if (nump < 3) return;
+        if (nump < 3) return false;
         // After clipping against X and Y planes, there's still points left
         // to draw, so proceed to trying projection now...
         break;
@@ -1532,22 +1601,23 @@ static void draw_perspective(int nump, Interpolants interp_outs[4],
       if (!isfinite(w)) w = 0.0f;
       p_clip[i] = Point3D(p_clip[i].sel(X, Y, Z) * w * scale + offset, w);
     }
-    draw_perspective_clipped(nump, p_clip, interp_clip, colortex, depthtex);
   }
+  prim.nump = nump;
+
+  // If polygon is ouside clip rect, nothing to draw.
+  prim.clip_rect = ClipRect(colortex);
+  return prim.clip_rect.overlaps(nump, p_clip);
 }
 
-static void draw_quad(int nump, Texture& colortex, Texture& depthtex) {
-  // Run vertex shader once for the primitive's vertices.
-  // Reserve space for 6 sets of interpolants, in case we need to clip against
-  // near and far planes in the perspective case.
-  Interpolants interp_outs[4];
-  swgl_ClipFlags = 0;
-  vertex_shader->run_primitive((char*)interp_outs, sizeof(Interpolants));
+// Transforms the primitive output by the vertex shader, whose interpolants
+// are already stored in prim, to screen space. Returns false if there is
+// nothing to draw.
+static bool setup_primitive(int nump, Texture& colortex,
+                            ScreenPrimitive& prim) {
   vec4 pos = vertex_shader->gl_Position;
   // Check if any vertex W is different from another. If so, use perspective.
   if (test_any(pos.w != pos.w.x)) {
-    draw_perspective(nump, interp_outs, colortex, depthtex);
-    return;
+    return setup_perspective(nump, colortex, prim);
   }
 
   // Convert output of vertex shader to screen space.
@@ -1561,28 +1631,27 @@ static void draw_quad(int nump, Texture& colortex, Texture& depthtex) {
   vec2 screen = (pos.sel(X, Y) * w + 1) * 0.5f *
                     vec2_scalar(ctx->viewport.width(), ctx->viewport.height()) +
                 make_vec2(ctx->viewport.origin() - colortex.offset);
-  Point2D p[4] = {{screen.x.x, screen.y.x},
-                  {screen.x.y, screen.y.y},
-                  {screen.x.z, screen.y.z},
-                  {screen.x.w, screen.y.w}};
+  Point2D* p = prim.p2;
+  p[0] = {screen.x.x, screen.y.x};
+  p[1] = {screen.x.y, screen.y.y};
+  p[2] = {screen.x.z, screen.y.z};
+  p[3] = {screen.x.w, screen.y.w};
 
   // If quad is ouside clip rect, nothing to draw.
-  ClipRect clipRect(colortex);
-  if (!clipRect.overlaps(nump, p)) {
-    return;
+  prim.clip_rect = ClipRect(colortex);
+  if (!prim.clip_rect.overlaps(nump, p)) {
+    return false;
   }
 
   // Since the quad is assumed 2D, Z is constant across the quad.
   float screenZ = (pos.z.x * w + 1) * 0.5f;
   if (screenZ < 0 || screenZ > 1) {
     // Z values would cross the near or far plane, so just bail.
-    return;
+    return false;
   }
-  // Since Z doesn't need to be interpolated, just set the fragment shader's
-  // Z and W values here, once and for all fragment shader invocations.
-  uint32_t z = uint32_t(MAX_DEPTH_VALUE * screenZ);
-  fragment_shader->gl_FragCoord.z = screenZ;
-  fragment_shader->gl_FragCoord.w = w;
+  prim.z = uint32_t(MAX_DEPTH_VALUE * screenZ);
+  prim.frag_z = screenZ;
+  prim.frag_w = w;
 
   // If supplied a line, adjust it so that it is a quad at least 1 pixel thick.
   // Assume that for a line that all 4 SIMD lanes were actually filled with
@@ -1608,20 +1677,180 @@ static void draw_quad(int nump, Texture& colortex, Texture& depthtex) {
     // Pretend that it's a quad now...
     nump = 4;
   }
+  prim.nump = nump;
+  return true;
+}
 
-  // Finally draw 2D spans for the quad. Currently only supports drawing to
-  // RGBA8 and R8 color buffers.
+// Rasterizes a primitive within its clip rect. Currently only supports drawing
+// to RGBA8 and R8 color buffers.
+static void draw_primitive(ScreenPrimitive& prim, Texture& colortex,
+                           Texture& depthtex) {
+  if (prim.perspective) {
+    // Draw perspective-correct spans for the polygon.
+    if (colortex.internal_format == GL_RGBA8) {
+      draw_perspective_spans<uint32_t>(prim.nump, prim.p3, prim.interp,
+                                       colortex, depthtex, prim.clip_rect);
+    } else if (colortex.internal_format == GL_R8) {
+      draw_perspective_spans<uint8_t>(prim.nump, prim.p3, prim.interp,
+                                      colortex, depthtex, prim.clip_rect);
+    } else {
+      assert(false);
+    }
+    return;
+  }
+
+  // Since Z doesn't need to be interpolated, just set the fragment shader's
+  // Z and W values here, once and for all fragment shader invocations.
+  fragment_shader->gl_FragCoord.z = prim.frag_z;
+  fragment_shader->gl_FragCoord.w = prim.frag_w;
+
+  // Draw 2D spans for the quad.
   if (colortex.internal_format == GL_RGBA8) {
-    draw_quad_spans<uint32_t>(nump, p, z, interp_outs, colortex, depthtex,
-                              clipRect);
+    draw_quad_spans<uint32_t>(prim.nump, prim.p2, prim.z, prim.interp,
+                              colortex, depthtex, prim.clip_rect);
   } else if (colortex.internal_format == GL_R8) {
-    draw_quad_spans<uint8_t>(nump, p, z, interp_outs, colortex, depthtex,
-                             clipRect);
+    draw_quad_spans<uint8_t>(prim.nump, prim.p2, prim.z, prim.interp,
+                             colortex, depthtex, prim.clip_rect);
   } else {
     assert(false);
   }
 }
 
+// Grows a buffer allocated with malloc to hold at least size items. Returns
+// false if it can't be allocated.
+template <typename T>
+static bool reserve_items(T*& buf, size_t& capacity, size_t size) {
+  if (size <= capacity) {
+    return true;
+  }
+  size_t new_capacity = capacity * 2 > size ? capacity * 2 : size;
+  T* new_buf = (T*)realloc((void*)buf, new_capacity * sizeof(T));
+  if (!new_buf) {
+    return false;
+  }
+  buf = new_buf;
+  capacity = new_capacity;
+  return true;
+}
+
+// The primitives of a draw call that is rasterized in bands, which are
+// transformed once on the calling thread and then binned by the bands they
+// overlap, see draw_batch_in_bands. The buffers are kept between draw calls.
+struct PrimitiveBins {
+  ScreenPrimitive* prims = nullptr;
+  size_t num_prims = 0;
+  size_t max_prims = 0;
+  // The flat outputs of the vertex shader for each primitive.
+  char* flat_outputs = nullptr;
+  size_t flat_outputs_stride = 0;
+  size_t max_flat_outputs = 0;
+  // The indices of the primitives overlapping each band, where the ones of
+  // band i start at band_starts[i] and end at band_starts[i + 1].
+  int* indices = nullptr;
+  size_t max_indices = 0;
+  int* band_starts = nullptr;
+  size_t max_band_starts = 0;
+  // Set if a primitive couldn't be stored.
+  bool failed = false;
+
+  ~PrimitiveBins() {
+    free(prims);
+    free(flat_outputs);
+    free(indices);
+    free(band_starts);
+  }
+
+  void clear(size_t flat_outputs_size) {
+    num_prims = 0;
+    // Keep the flat outputs of each primitive aligned for any of their types.
+    flat_outputs_stride = (flat_outputs_size + 15) & ~size_t(15);
+    failed = false;
+  }
+
+  void add(const ScreenPrimitive& prim) {
+    if (failed || !reserve_items(prims, max_prims, num_prims + 1) ||
+        !reserve_items(flat_outputs, max_flat_outputs,
+                       (num_prims + 1) * flat_outputs_stride)) {
+      failed = true;
+      return;
+    }
+    prims[num_prims] = prim;
+    prims[num_prims].save_state();
+    vertex_shader->store_flat_outputs(flat_outputs +
+                                      num_prims * flat_outputs_stride);
+    num_prims++;
+  }
+
+  // Sorts the primitives into the bands of rows they overlap, which start at
+  // row y0 and are band_rows high. Returns false if they can't be allocated.
+  bool sort(int y0, int band_rows, int num_bands) {
+    if (!reserve_items(band_starts, max_band_starts, size_t(num_bands) + 1)) {
+      return false;
+    }
+    memset(band_starts, 0, (num_bands + 1) * sizeof(int));
+    // Count the primitives of each band, offset by one so that the counts
+    // turn into the starts of the bands below.
+    auto get_bands = [&](const ScreenPrimitive& prim, int& first, int& last) {
+      float prim_y0, prim_y1;
+      prim.row_range(prim_y0, prim_y1);
+      prim_y0 = max(prim_y0, prim.clip_rect.y0);
+      prim_y1 = min(prim_y1, prim.clip_rect.y1);
+      first = clamp((int(floor(prim_y0)) - y0) / band_rows, 0, num_bands - 1);
+      last = clamp((int(ceil(prim_y1)) - y0) / band_rows, 0, num_bands - 1);
+    };
+    size_t num_indices = 0;
+    for (size_t i = 0; i < num_prims; i++) {
+      int first, last;
+      get_bands(prims[i], first, last);
+      for (int band = first; band <= last; band++) {
+        band_starts[band + 1]++;
+      }
+      num_indices += last - first + 1;
+    }
+    if (!reserve_items(indices, max_indices, num_indices)) {
+      return false;
+    }
+    for (int band = 0; band < num_bands; band++) {
+      band_starts[band + 1] += band_starts[band];
+    }
+    // Fill in the indices, using the starts of the bands as cursors, which
+    // leaves each one at the end of its band. Shift them back afterwards.
+    for (size_t i = 0; i < num_prims; i++) {
+      int first, last;
+      get_bands(prims[i], first, last);
+      for (int band = first; band <= last; band++) {
+        indices[band_starts[band]++] = int(i);
+      }
+    }
+    for (int band = num_bands; band > 0; band--) {
+      band_starts[band] = band_starts[band - 1];
+    }
+    band_starts[0] = 0;
+    return true;
+  }
+};
+
+// The primitives of the draw call being rasterized in bands by this thread.
+static thread_local PrimitiveBins primitive_bins;
+// Set while transforming the primitives of a draw call that is rasterized in
+// bands, so that they are binned instead of drawn.
+static thread_local bool binning_primitives = false;
+
+static void draw_quad(int nump, Texture& colortex, Texture& depthtex) {
+  // Run vertex shader once for the primitive's vertices.
+  ScreenPrimitive prim;
+  swgl_ClipFlags = 0;
+  vertex_shader->run_primitive((char*)prim.interp, sizeof(Interpolants));
+  if (!setup_primitive(nump, colortex, prim)) {
+    return;
+  }
+  if (binning_primitives) {
+    primitive_bins.add(prim);
+  } else {
+    draw_primitive(prim, colortex, depthtex);
+  }
+}
+
 template <typename INDEX>
 static inline void draw_elements(GLsizei count, GLsizei instancecount,
                                  size_t offset, VertexArray& v,
@@ -1668,3 +1897,196 @@ static inline void draw_elements(GLsizei count, GLsizei instancecount,
     }
   }
 }
+
+// The parameters of a draw call, see DrawElementsInstanced.
+struct DrawBatch {
+  GLenum mode;
+  GLsizei count;
+  GLenum type;
+  GLintptr offset;
+  GLsizei instancecount;
+};
+
+// Accumulates the rows and pixels shaded by the current thread into the
+// context.
+static void flush_shaded_counts() {
+  __atomic_fetch_add(&ctx->shaded_rows, thread_shaded_rows, __ATOMIC_RELAXED);
+  __atomic_fetch_add(&ctx->shaded_pixels, thread_shaded_pixels,
+                     __ATOMIC_RELAXED);
+  thread_shaded_rows = 0;
+  thread_shaded_pixels = 0;
+}
+
+static void draw_batch(const DrawBatch& batch, VertexArray& v,
+                       Texture& colortex, Texture& depthtex) {
+
+  switch (batch.type) {
+    case GL_UNSIGNED_SHORT:
+      assert(batch.mode == GL_TRIANGLES);
+      draw_elements<uint16_t>(batch.count, batch.instancecount, batch.offset,
+                              v, colortex, depthtex);
+      break;
+    case GL_UNSIGNED_INT:
+      assert(batch.mode == GL_TRIANGLES);
+      draw_elements<uint32_t>(batch.count, batch.instancecount, batch.offset,
+                              v, colortex, depthtex);
+      break;
+    case GL_NONE:
+      // Non-standard GL extension - if element type is GL_NONE, then we don't
+      // use any element buffer and behave as if DrawArrays was called instead.
+      for (GLsizei instance = 0; instance < batch.instancecount; instance++) {
+        switch (batch.mode) {
+          case GL_LINES:
+            for (GLsizei i = 0; i + 2 <= batch.count; i += 2) {
+              vertex_shader->load_attribs(v.attribs, batch.offset + i,
+                                          instance, 2);
+              draw_quad(2, colortex, depthtex);
+            }
+            break;
+          case GL_TRIANGLES:
+            for (GLsizei i = 0; i + 3 <= batch.count; i += 3) {
+              vertex_shader->load_attribs(v.attribs, batch.offset + i,
+                                          instance, 3);
+              draw_quad(3, colortex, depthtex);
+            }
+            break;
+          default:
+            assert(false);
+            break;
+        }
+      }
+      break;
+    default:
+      assert(false);
+      break;
+  }
+
+  flush_shaded_counts();
+}
+
+// Bands start on multiples of this many rows, so that no two bands share a
+// word of the delayed clear mask of the color buffer.
+constexpr int RASTERIZE_BAND_ALIGN = 32;
+// Draw calls are not split into bands that would be shorter than this, as
+// binning the primitives and setting up the workers would outweigh the
+// savings.
+constexpr int MIN_RASTERIZE_BAND_ROWS = 64;
+
+// A copy of a program that a worker thread rasterizes bands with. It is kept
+// between draw calls, so that it only needs allocating when the program
+// changes.
+struct BandProgram {
+  ProgramImpl* program = nullptr;
+
+  ~BandProgram() { delete program; }
+};
+static thread_local BandProgram band_program;
+
+// Gets a copy of the program for the current thread, with the uniform state of
+// the given program. Programs of the same type share their name, so the copy
+// can be reused for any program with the same name.
+static ProgramImpl* get_band_program(const ProgramImpl* source) {
+  ProgramImpl*& program = band_program.program;
+  if (program && program->get_name() == source->get_name()) {
+    program->copy_from(source);
+  } else {
+    delete program;
+    program = source->clone();
+  }
+  return program;
+}
+
+struct RasterizeBandJob {
+  const PrimitiveBins* bins;
+  const ProgramImpl* program;
+  Texture* colortex;
+  Texture* depthtex;
+  int y0;
+  int band_rows;
+};
+
+static void rasterize_band(void* data, int band) {
+  const RasterizeBandJob& job = *(const RasterizeBandJob*)data;
+  const PrimitiveBins& bins = *job.bins;
+  int start = bins.band_starts[band];
+  int end = bins.band_starts[band + 1];
+  if (start >= end) {
+    return;
+  }
+
+  // Shaders store the state of the primitive being rasterized, so every
+  // thread needs its own copy of the program.
+  ProgramImpl* program = get_band_program(job.program);
+  VertexShaderImpl* prev_vertex_shader = vertex_shader;
+  FragmentShaderImpl* prev_fragment_shader = fragment_shader;
+  vertex_shader = program->get_vertex_shader();
+  fragment_shader = program->get_fragment_shader();
+  vertex_shader->init_batch();
+
+  float band_y0 = float(job.y0 + band * job.band_rows);
+  float band_y1 = band_y0 + job.band_rows;
+  for (int i = start; i < end; i++) {
+    int index = bins.indices[i];
+    // Rasterization may modify the primitive, and other bands rasterize it
+    // concurrently, so work on a copy.
+    ScreenPrimitive prim = bins.prims[index];
+    // Only rasterize the rows of the band.
+    prim.clip_rect.y0 = max(prim.clip_rect.y0, band_y0);
+    prim.clip_rect.y1 = min(prim.clip_rect.y1, band_y1);
+    if (!prim.overlaps_clip_rect()) {
+      continue;
+    }
+    prim.restore_state();
+    vertex_shader->load_flat_outputs(bins.flat_outputs +
+                                     index * bins.flat_outputs_stride);
+    draw_primitive(prim, *job.colortex, *job.depthtex);
+  }
+  flush_shaded_counts();
+
+  vertex_shader = prev_vertex_shader;
+  fragment_shader = prev_fragment_shader;
+}
+
+// Splits a draw call into horizontal bands that are rasterized in parallel on
+// the rasterize workers of the context, if any. The primitives are transformed
+// by the vertex shader once on the current thread, and binned by the bands
+// they overlap. Each band then only rasterizes the rows of its primitives
+// within it, so that bands never touch the same pixels. Returns false if the
+// draw call is too small to be worth splitting, or the primitives couldn't be
+// binned, and it should be drawn on the current thread instead.
+static bool draw_batch_in_bands(const DrawBatch& batch, VertexArray& v,
+                                Texture& colortex, Texture& depthtex) {
+  if (!ctx->run_rasterize_jobs || ctx->num_rasterize_threads < 2) {
+    return false;
+  }
+  IntRect bounds = ctx->apply_scissor(colortex);
+  int y0 = bounds.y0 & ~(RASTERIZE_BAND_ALIGN - 1);
+  int rows = bounds.y1 - y0;
+  int num_bands =
+      min(ctx->num_rasterize_threads, rows / MIN_RASTERIZE_BAND_ROWS);
+  if (num_bands < 2) {
+    return false;
+  }
+  int band_rows = (rows + num_bands - 1) / num_bands;
+  band_rows = (band_rows + RASTERIZE_BAND_ALIGN - 1) &
+              ~(RASTERIZE_BAND_ALIGN - 1);
+  num_bands = (rows + band_rows - 1) / band_rows;
+
+  PrimitiveBins& bins = primitive_bins;
+  bins.clear(vertex_shader->flat_outputs_size);
+  binning_primitives = true;
+  draw_batch(batch, v, colortex, depthtex);
+  binning_primitives = false;
+  if (bins.failed || !bins.sort(y0, band_rows, num_bands)) {
+    return false;
+  }
+  if (!bins.num_prims) {
+    return true;
+  }
+
+  RasterizeBandJob job = {&bins, ctx->programs[ctx->current_program].impl,
+                          &colortex, &depthtex, y0, band_rows};
+  ctx->run_rasterize_jobs(ctx->rasterize_workers, num_bands, rasterize_band,
+                          &job);
+  return true;
+}
@@ -4,10 +4,13 @@
 #![allow(unused_variables)]
 
 use gleam::gl::*;
+use rayon::prelude::*;
+use rayon::ThreadPool;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int, c_void};
 use std::ptr;
 use std::str;
+use std::sync::Arc;
 
 #[allow(unused)]
 macro_rules! debug {
@@ -316,7 +319,39 @@ extern "C" {
     fn ReferenceContext(ctx: *mut c_void);
     fn DestroyContext(ctx: *mut c_void);
     fn MakeCurrent(ctx: *mut c_void);
+    fn GetCurrentContext() -> *mut c_void;
     fn ReportMemory(ctx: *mut c_void, size_of_op: unsafe extern "C" fn(ptr: *const c_void) -> usize) -> usize;
+    fn SetRasterizeWorkers(
+        ctx: *mut c_void,
+        workers: *mut c_void,
+        num_threads: c_int,
+        run: Option<RunRasterizeJobsFunc>,
+        release: Option<ReleaseRasterizeWorkersFunc>,
+    );
+}
+
+type RasterizeJobFunc = unsafe extern "C" fn(data: *mut c_void, index: c_int);
+type RunRasterizeJobsFunc =
+    unsafe extern "C" fn(workers: *mut c_void, count: c_int, job: RasterizeJobFunc, data: *mut c_void);
+type ReleaseRasterizeWorkersFunc = unsafe extern "C" fn(workers: *mut c_void);
+
+unsafe extern "C" fn run_rasterize_jobs(
+    workers: *mut c_void,
+    count: c_int,
+    job: RasterizeJobFunc,
+    data: *mut c_void,
+) {
+    let workers = &*(workers as *const ThreadPool);
+    // Raw pointers can't be sent across threads, but the job data outlives
+    // the jobs since install blocks until they all finished.
+    let data = data as usize;
+    workers.install(|| {
+        (0 .. count).into_par_iter().for_each(|index| job(data as *mut c_void, index));
+    });
+}
+
+unsafe extern "C" fn release_rasterize_workers(workers: *mut c_void) {
+    drop(Arc::from_raw(workers as *const ThreadPool));
 }
 
 #[derive(Clone, Copy)]
@@ -345,6 +380,16 @@ impl Context {
         }
     }
 
+    /// The context that was last made current, if any.
+    pub fn current() -> Option<Self> {
+        let ctx = unsafe { GetCurrentContext() };
+        if ctx.is_null() {
+            None
+        } else {
+            Some(Context(ctx))
+        }
+    }
+
     pub fn init_default_framebuffer(
         &self,
         x: i32,
@@ -451,6 +496,26 @@ impl Context {
         }
     }
 
+    /// Sets the thread pool that large draw calls are rasterized on in bands
+    /// of rows, or rasterizes all draw calls on the current thread if None.
+    pub fn set_rasterize_workers(&self, workers: Option<Arc<ThreadPool>>) {
+        unsafe {
+            match workers {
+                Some(workers) => {
+                    let num_threads = workers.current_num_threads() as c_int;
+                    SetRasterizeWorkers(
+                        self.0,
+                        Arc::into_raw(workers) as *mut c_void,
+                        num_threads,
+                        Some(run_rasterize_jobs),
+                        Some(release_rasterize_workers),
+                    );
+                }
+                None => SetRasterizeWorkers(self.0, ptr::null_mut(), 0, None, None),
+            }
+        }
+    }
+
     pub fn report_memory(&self, size_of_op: unsafe extern "C" fn(ptr: *const c_void) -> usize) -> usize {
         unsafe { ReportMemory(self.0, size_of_op) }
     }
@@ -4,7 +4,7 @@
 
 #define VECS_PER_SPECIFIC_BRUSH 3
 
-#include shared,prim_shared,brush
+#include shared,prim_shared,brush,color_space
 
 // Interpolated UV coordinates to sample.
 varying vec2 v_uv;
@@ -15,6 +15,11 @@ flat varying vec2 v_mask_swizzle;
 flat varying vec2 v_tile_repeat;
 #endif
 
+// The color spaces of the image and of the output. No conversion is needed
+// when both are the same, so the conversion matrix is only looked up by the
+// fragment shader rather than taking up varyings.
+flat varying ivec2 v_color_spaces;
+
 // Normalized bounds of the source image in the texture.
 flat varying vec4 v_uv_bounds;
 // Normalized bounds of the source image in the texture, adjusted to avoid
@@ -188,6 +193,9 @@ void brush_vs(
 
     vec2 f = (vi.local_pos - local_rect.p0) / rect_size(local_rect);
 
+    // See encode_color_space_conversion in color_space.rs.
+    v_color_spaces = ivec2(prim_user_data.w & 0xff, (prim_user_data.w >> 8) & 0xff);
+
 #ifdef WR_FEATURE_ALPHA_PASS
     int color_mode = prim_user_data.x & 0xffff;
     int blend_mode = prim_user_data.x >> 16;
@@ -330,6 +338,10 @@ Fragment brush_fs() {
     vec2 uv = clamp(repeated_uv, v_uv_sample_bounds.xy, v_uv_sample_bounds.zw);
 
     vec4 texel = TEX_SAMPLE(sColor0, uv);
+    if (v_color_spaces.x != v_color_spaces.y) {
+        mat3 conversion = get_color_space_conversion(v_color_spaces.x, v_color_spaces.y);
+        texel = convert_color_space(texel, conversion, v_color_spaces.x, v_color_spaces.y);
+    }
 
     Fragment frag;
 
@@ -362,6 +374,11 @@ void swgl_drawSpanRGBA8() {
         return;
     }
 
+    // Color space conversions are done by the fragment shader.
+    if (v_color_spaces.x != v_color_spaces.y) {
+        return;
+    }
+
     #ifdef WR_FEATURE_ALPHA_PASS
         if (v_mask_swizzle != vec2(1.0, 0.0)) {
             return;
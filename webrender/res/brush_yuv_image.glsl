@@ -4,7 +4,7 @@
 
 #define VECS_PER_SPECIFIC_BRUSH 1
 
-#include shared,prim_shared,brush,yuv
+#include shared,prim_shared,brush,yuv,hdr
 
 varying vec2 vUv_Y;
 flat varying vec4 vUvBounds_Y;
@@ -18,6 +18,8 @@ flat varying vec4 vUvBounds_V;
 YUV_PRECISION flat varying vec3 vYcbcrBias;
 YUV_PRECISION flat varying mat3 vRgbFromDebiasedYcbcr;
 flat varying int vFormat;
+// The reference white in nits when the image is PQ encoded, zero otherwise.
+flat varying float vPqReferenceWhite;
 
 #ifdef SWGL_DRAW_SPAN
 flat varying int vRescaleFactor;
@@ -31,6 +33,7 @@ YuvPrimitive fetch_yuv_primitive(int address) {
     int channel_bit_depth = int(data.x);
     int color_space = int(data.y);
     int yuv_format = int(data.z);
+    vPqReferenceWhite = data.w;
     return YuvPrimitive(channel_bit_depth, color_space, yuv_format);
 }
 
@@ -104,6 +107,10 @@ Fragment brush_fs() {
         vUvBounds_V
     );
 
+    if (vPqReferenceWhite > 0.0) {
+        color.rgb = pq_to_extended_srgb(color.rgb, vPqReferenceWhite);
+    }
+
 #ifdef WR_FEATURE_ALPHA_PASS
     color *= antialias_brush();
 #endif
@@ -116,6 +123,11 @@ Fragment brush_fs() {
 
 #ifdef SWGL_DRAW_SPAN
 void swgl_drawSpanRGBA8() {
+    // PQ decoding is done by the fragment shader.
+    if (vPqReferenceWhite > 0.0) {
+        return;
+    }
+
     if (vFormat == YUV_FORMAT_PLANAR) {
         swgl_commitTextureLinearYUV(sColor0, vUv_Y, vUvBounds_Y,
                                     sColor1, vUv_U, vUvBounds_U,
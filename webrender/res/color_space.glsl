@@ -0,0 +1,143 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Conversions between RGB color spaces, see color_space.rs.
+//
+// The matrix converting linear colors from the source to the destination
+// gamut comes from constant tables, and the fragment shader applies it along
+// with the transfer functions. Shaders short on varyings can look it up in
+// the fragment shader rather than the vertex shader. This file must remain
+// compatible with ESSL 1.
+
+// `RgbColorSpace`
+#define COLOR_SPACE_SRGB       0
+#define COLOR_SPACE_DISPLAY_P3 1
+#define COLOR_SPACE_ADOBE_RGB  2
+#define COLOR_SPACE_REC2020    3
+
+// The matrices are stored in column-major.
+const mat3 XyzFromSrgb = mat3(
+    0.4123908,  0.2126390,  0.0193308,
+    0.3575843,  0.7151687,  0.1191948,
+    0.1804808,  0.0721923,  0.9505322
+);
+const mat3 XyzFromDisplayP3 = mat3(
+    0.4865709,  0.2289746,  0.0000000,
+    0.2656677,  0.6917385,  0.0451134,
+    0.1982173,  0.0792869,  1.0439444
+);
+const mat3 XyzFromAdobeRgb = mat3(
+    0.5766690,  0.2973450,  0.0270314,
+    0.1855582,  0.6273636,  0.0706889,
+    0.1882286,  0.0752915,  0.9913375
+);
+const mat3 XyzFromRec2020 = mat3(
+    0.6369580,  0.2627002,  0.0000000,
+    0.1446169,  0.6779981,  0.0280727,
+    0.1688810,  0.0593017,  1.0609851
+);
+
+const mat3 SrgbFromXyz = mat3(
+    3.2409697, -0.9692435,  0.0556302,
+  -1.5373828,  1.8759673, -0.2039770,
+  -0.4986108,  0.0415551,  1.0569714
+);
+const mat3 DisplayP3FromXyz = mat3(
+    2.4934973, -0.8294893,  0.0358459,
+  -0.9313838,  1.7626642, -0.0761724,
+  -0.4027109,  0.0236248,  0.9568845
+);
+const mat3 AdobeRgbFromXyz = mat3(
+    2.0415880, -0.9692437,  0.0134443,
+  -0.5650068,  1.8759674, -0.1183625,
+  -0.3447313,  0.0415550,  1.0151750
+);
+const mat3 Rec2020FromXyz = mat3(
+    1.7166513, -0.6666843,  0.0176399,
+  -0.3556708,  1.6164812, -0.0427706,
+  -0.2533663,  0.0157686,  0.9421031
+);
+
+mat3 xyz_from_rgb(int color_space) {
+    if (color_space == COLOR_SPACE_DISPLAY_P3) {
+        return XyzFromDisplayP3;
+    } else if (color_space == COLOR_SPACE_ADOBE_RGB) {
+        return XyzFromAdobeRgb;
+    } else if (color_space == COLOR_SPACE_REC2020) {
+        return XyzFromRec2020;
+    }
+    return XyzFromSrgb;
+}
+
+mat3 rgb_from_xyz(int color_space) {
+    if (color_space == COLOR_SPACE_DISPLAY_P3) {
+        return DisplayP3FromXyz;
+    } else if (color_space == COLOR_SPACE_ADOBE_RGB) {
+        return AdobeRgbFromXyz;
+    } else if (color_space == COLOR_SPACE_REC2020) {
+        return Rec2020FromXyz;
+    }
+    return SrgbFromXyz;
+}
+
+// The matrix converting linear colors from the `src` to the `dst` color space.
+mat3 get_color_space_conversion(int src, int dst) {
+    return rgb_from_xyz(dst) * xyz_from_rgb(src);
+}
+
+#ifdef WR_FRAGMENT_SHADER
+
+#define ADOBE_RGB_GAMMA 2.19921875
+#define REC2020_ALPHA   1.09929682680944
+#define REC2020_BETA    0.018053968510807
+
+vec3 color_space_to_linear(vec3 c, int color_space) {
+    c = max(c, vec3(0.0));
+    if (color_space == COLOR_SPACE_ADOBE_RGB) {
+        return pow(c, vec3(ADOBE_RGB_GAMMA));
+    } else if (color_space == COLOR_SPACE_REC2020) {
+        return mix(
+            c / 4.5,
+            pow((c + vec3(REC2020_ALPHA - 1.0)) / REC2020_ALPHA, vec3(1.0 / 0.45)),
+            step(vec3(4.5 * REC2020_BETA), c)
+        );
+    }
+    return mix(
+        c / 12.92,
+        pow((c + vec3(0.055)) / 1.055, vec3(2.4)),
+        step(vec3(0.04045), c)
+    );
+}
+
+vec3 color_space_from_linear(vec3 c, int color_space) {
+    c = clamp(c, vec3(0.0), vec3(1.0));
+    if (color_space == COLOR_SPACE_ADOBE_RGB) {
+        return pow(c, vec3(1.0 / ADOBE_RGB_GAMMA));
+    } else if (color_space == COLOR_SPACE_REC2020) {
+        return mix(
+            c * 4.5,
+            REC2020_ALPHA * pow(c, vec3(0.45)) - vec3(REC2020_ALPHA - 1.0),
+            step(vec3(REC2020_BETA), c)
+        );
+    }
+    return mix(
+        c * 12.92,
+        1.055 * pow(c, vec3(1.0 / 2.4)) - vec3(0.055),
+        step(vec3(0.0031308), c)
+    );
+}
+
+// Convert a premultiplied color with the matrix returned by
+// get_color_space_conversion. Colors outside of the destination gamut
+// are clamped.
+vec4 convert_color_space(vec4 color, mat3 conversion, int src, int dst) {
+    if (color.a <= 0.0) {
+        return color;
+    }
+    vec3 rgb = color_space_to_linear(color.rgb / color.a, src);
+    rgb = color_space_from_linear(conversion * rgb, dst);
+    return vec4(rgb * color.a, color.a);
+}
+
+#endif
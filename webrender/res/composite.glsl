@@ -13,6 +13,12 @@
 
 #ifdef WR_FEATURE_YUV
 #include yuv
+#else
+#include color_space
+#endif
+
+#ifdef WR_FEATURE_TONE_MAP
+#include hdr
 #endif
 
 #ifdef WR_FEATURE_YUV
@@ -34,10 +40,19 @@ varying vec2 vUv;
 #ifndef WR_FEATURE_FAST_PATH
 flat varying vec4 vColor;
 flat varying vec4 vUVBounds;
+// The source and destination color spaces, and the matrix converting
+// between them. No conversion is needed when both are the same.
+flat varying vec2 vColorSpaces;
+flat varying mat3 vColorSpaceConversion;
 #endif
 #ifdef WR_FEATURE_TEXTURE_EXTERNAL_ESSL1
 uniform vec2 uTextureSize;
 #endif
+#ifdef WR_FEATURE_TONE_MAP
+// From HdrOptions::shader_params: the operator, the reference white, the
+// display peak and the content peak, in nits.
+uniform vec4 uToneMap;
+#endif
 #endif
 
 #ifdef WR_VERTEX_SHADER
@@ -158,6 +173,14 @@ void main(void) {
     vUVBounds = uvBounds;
     // Pass through color
     vColor = aColor;
+
+    // From CompositeInstance::with_color_space_conversion, the source color
+    // space is in the low byte and the destination in the next one.
+    int color_spaces = int(aParams.z);
+    int dst_color_space = color_spaces / 256;
+    int src_color_space = color_spaces - dst_color_space * 256;
+    vColorSpaces = vec2(float(src_color_space), float(dst_color_space));
+    vColorSpaceConversion = get_color_space_conversion(src_color_space, dst_color_space);
 #endif
 #endif
 
@@ -191,13 +214,25 @@ void main(void) {
 #ifdef WR_FEATURE_FAST_PATH
     vec4 color = texel;
 #else
+    if (vColorSpaces.x != vColorSpaces.y) {
+        texel = convert_color_space(
+            texel,
+            vColorSpaceConversion,
+            int(vColorSpaces.x + 0.5),
+            int(vColorSpaces.y + 0.5)
+        );
+    }
     vec4 color = vColor * texel;
 #endif
+#ifdef WR_FEATURE_TONE_MAP
+    color = tone_map_premultiplied(color, uToneMap);
+#endif
 #endif
     write_output(color);
 }
 
-#ifdef SWGL_DRAW_SPAN
+// Tone mapping is done by the fragment shader.
+#if defined(SWGL_DRAW_SPAN) && !defined(WR_FEATURE_TONE_MAP)
 void swgl_drawSpanRGBA8() {
 #ifdef WR_FEATURE_YUV
     if (vYuvFormat == YUV_FORMAT_PLANAR) {
@@ -228,6 +263,10 @@ void swgl_drawSpanRGBA8() {
     vec4 uvBounds = vec4(0.0, 0.0, 1.0, 1.0);
 #endif
 #else
+    // Color space conversions are done by the fragment shader.
+    if (vColorSpaces.x != vColorSpaces.y) {
+        return;
+    }
     vec4 color = vColor;
     vec4 uvBounds = vUVBounds;
 #endif
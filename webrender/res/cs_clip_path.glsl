@@ -0,0 +1,69 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#include shared,clip_shared,path
+
+varying vec4 vLocalPos;
+
+// The origin of the clip rect, which the path edges are relative to.
+flat varying vec2 vClipOrigin;
+flat varying HIGHP_FS_ADDRESS int vEdgesAddress;
+flat varying int vEdgeCount;
+flat varying int vFillRule;
+
+#ifdef WR_VERTEX_SHADER
+
+PER_INSTANCE in vec4 aClipLocalRect;
+PER_INSTANCE in int aEdgesAddress;
+PER_INSTANCE in int aEdgeCount;
+PER_INSTANCE in int aFillRule;
+
+void main(void) {
+    ClipMaskInstanceCommon cmi = fetch_clip_item_common();
+    Transform clip_transform = fetch_transform(cmi.clip_transform_id);
+    Transform prim_transform = fetch_transform(cmi.prim_transform_id);
+    RectWithEndpoint local_rect = RectWithEndpoint(aClipLocalRect.xy, aClipLocalRect.zw);
+
+    ClipVertexInfo vi = write_clip_tile_vertex(
+        local_rect,
+        prim_transform,
+        clip_transform,
+        cmi.sub_rect,
+        cmi.task_origin,
+        cmi.screen_origin,
+        cmi.device_pixel_scale
+    );
+
+    vLocalPos = vi.local_pos;
+    vClipOrigin = local_rect.p0;
+    vEdgesAddress = aEdgesAddress;
+    vEdgeCount = aEdgeCount;
+    vFillRule = aFillRule;
+}
+#endif
+
+#ifdef WR_FRAGMENT_SHADER
+
+void main(void) {
+    vec2 local_pos = vLocalPos.xy / vLocalPos.w;
+
+    // Approximate the footprint of the device pixel in the local space of
+    // the clip by an axis-aligned box.
+    vec2 pixel_size = max(fwidth(local_pos), vec2(1.0e-6));
+
+    float area = compute_path_area(
+        local_pos - vClipOrigin,
+        pixel_size,
+        vEdgesAddress,
+        vEdgeCount
+    );
+    float alpha = path_coverage(area, vFillRule);
+
+    // Anything outside of the clip rect is clipped out.
+    alpha *= init_transform_fs(local_pos);
+
+    float final_alpha = vLocalPos.w > 0.0 ? alpha : 0.0;
+    oFragColor = vec4(final_alpha, 0.0, 0.0, 1.0);
+}
+#endif
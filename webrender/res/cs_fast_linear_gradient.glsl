@@ -4,9 +4,19 @@
 
 #include shared
 
+// Must match interpolation_space_as_int in prim_store/gradient/interpolation.rs
+#define INTERPOLATION_SPACE_SRGB        0
+#define INTERPOLATION_SPACE_SRGB_LINEAR 1
+#define INTERPOLATION_SPACE_OKLAB       2
+#define INTERPOLATION_SPACE_OKLCH       3
+#define INTERPOLATION_SPACE_HSL         4
+
 varying float vPos;
+// The colors are in the interpolation space, with premultiplied alpha (except
+// for the hue of polar spaces).
 flat varying vec4 vColor0;
 flat varying vec4 vColor1;
+flat varying int vInterpolationSpace;
 
 #ifdef WR_VERTEX_SHADER
 
@@ -14,19 +24,86 @@ PER_INSTANCE in vec4 aTaskRect;
 PER_INSTANCE in vec4 aColor0;
 PER_INSTANCE in vec4 aColor1;
 PER_INSTANCE in float aAxisSelect;
+PER_INSTANCE in int aInterpolationSpace;
 
 void main(void) {
     vPos = mix(0.0, 1.0, mix(aPosition.x, aPosition.y, aAxisSelect));
 
     vColor0 = aColor0;
     vColor1 = aColor1;
+    vInterpolationSpace = aInterpolationSpace;
 
     gl_Position = uTransform * vec4(mix(aTaskRect.xy, aTaskRect.zw, aPosition.xy), 0.0, 1.0);
 }
 #endif
 
 #ifdef WR_FRAGMENT_SHADER
+vec3 linear_to_srgb(vec3 color) {
+    vec3 c = abs(color);
+    vec3 srgb = mix(
+        1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055,
+        c * 12.92,
+        lessThanEqual(c, vec3(0.0031308))
+    );
+    return sign(color) * srgb;
+}
+
+vec3 oklab_to_linear_srgb(vec3 lab) {
+    vec3 lms = vec3(
+        lab.x + 0.3963377774 * lab.y + 0.2158037573 * lab.z,
+        lab.x - 0.1055613458 * lab.y - 0.0638541728 * lab.z,
+        lab.x - 0.0894841775 * lab.y - 1.2914855480 * lab.z
+    );
+    lms = lms * lms * lms;
+    return vec3(
+        4.0767416621 * lms.x - 3.3077115913 * lms.y + 0.2309699292 * lms.z,
+        -1.2684380046 * lms.x + 2.6097574011 * lms.y - 0.3413193965 * lms.z,
+        -0.0041960863 * lms.x - 0.7034186147 * lms.y + 1.7076147010 * lms.z
+    );
+}
+
+vec3 hsl_to_srgb(vec3 hsl) {
+    float a = hsl.y * min(hsl.z, 1.0 - hsl.z);
+    vec3 k = mod(vec3(0.0, 8.0, 4.0) + hsl.x / 30.0, 12.0);
+    return hsl.z - a * clamp(min(k - 3.0, 9.0 - k), -1.0, 1.0);
+}
+
+// Convert a color from the interpolation space to premultiplied sRGB.
+vec4 to_premultiplied_srgb(vec4 color) {
+    if (vInterpolationSpace == INTERPOLATION_SPACE_SRGB) {
+        return color;
+    }
+
+    if (color.a <= 0.0) {
+        return vec4(0.0);
+    }
+
+    vec3 rgb;
+    switch (vInterpolationSpace) {
+        case INTERPOLATION_SPACE_SRGB_LINEAR:
+            rgb = linear_to_srgb(color.rgb / color.a);
+            break;
+        case INTERPOLATION_SPACE_OKLAB:
+            rgb = linear_to_srgb(oklab_to_linear_srgb(color.rgb / color.a));
+            break;
+        case INTERPOLATION_SPACE_OKLCH: {
+            // The hue is in degrees and isn't premultiplied.
+            vec2 lc = color.xy / color.a;
+            float hue = radians(color.z);
+            rgb = linear_to_srgb(oklab_to_linear_srgb(vec3(lc.x, lc.y * cos(hue), lc.y * sin(hue))));
+            break;
+        }
+        case INTERPOLATION_SPACE_HSL:
+        default:
+            rgb = hsl_to_srgb(vec3(color.x, color.yz / color.a));
+            break;
+    }
+
+    float alpha = min(color.a, 1.0);
+    return vec4(clamp(rgb, 0.0, 1.0) * alpha, alpha);
+}
+
 void main(void) {
-    oFragColor = mix(vColor0, vColor1, vPos);
+    oFragColor = to_premultiplied_srgb(mix(vColor0, vColor1, vPos));
 }
 #endif
@@ -0,0 +1,124 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#include shared,rect,render_task,gpu_cache,gradient,path
+
+#define PI                  3.141592653589793
+
+#define PATH_BRUSH_COLOR            0
+#define PATH_BRUSH_LINEAR_GRADIENT  1
+#define PATH_BRUSH_RADIAL_GRADIENT  2
+#define PATH_BRUSH_CONIC_GRADIENT   3
+
+// Fragment position in the local space of the path, relative to the origin
+// of its bounds.
+varying vec2 v_local_pos;
+
+// The size of a device pixel in local space.
+flat varying vec2 v_pixel_size;
+
+flat varying vec4 v_brush_params0;
+flat varying vec4 v_brush_params1;
+flat varying HIGHP_FS_ADDRESS int v_edges_address;
+flat varying int v_edge_count;
+flat varying int v_fill_rule;
+flat varying int v_brush_kind;
+
+#ifdef WR_VERTEX_SHADER
+
+#define EXTEND_MODE_REPEAT 1
+
+// The rect of the render task we're drawing into, in device pixels.
+PER_INSTANCE in vec4 aTaskRect;
+
+// The local size of the path bounds covered by the task.
+PER_INSTANCE in vec2 aLocalSize;
+
+// Brush specific parameters, see PathBrushKey::params.
+PER_INSTANCE in vec4 aBrushParams0;
+PER_INSTANCE in vec4 aBrushParams1;
+
+// The flattened edges of the path, one [from, to] pair per GPU cache block.
+PER_INSTANCE in int aEdgesAddress;
+PER_INSTANCE in int aEdgeCount;
+
+// A FILL_RULE_* value.
+PER_INSTANCE in int aFillRule;
+
+// A PATH_BRUSH_* value, indicating how the covered area is painted.
+PER_INSTANCE in int aBrushKind;
+PER_INSTANCE in int aExtendMode;
+PER_INSTANCE in int aGradientStopsAddress;
+
+void main(void) {
+    vec2 pos = mix(aTaskRect.xy, aTaskRect.zw, aPosition.xy);
+    gl_Position = uTransform * vec4(pos, 0.0, 1.0);
+
+    v_local_pos = aPosition.xy * aLocalSize;
+    v_pixel_size = aLocalSize / max(aTaskRect.zw - aTaskRect.xy, vec2(1.0));
+
+    v_brush_params0 = aBrushParams0;
+    v_brush_params1 = aBrushParams1;
+    v_edges_address = aEdgesAddress;
+    v_edge_count = aEdgeCount;
+    v_fill_rule = aFillRule;
+    v_brush_kind = aBrushKind;
+
+    v_gradient_repeat = float(aExtendMode == EXTEND_MODE_REPEAT);
+    v_gradient_address = aGradientStopsAddress;
+}
+#endif
+
+#ifdef WR_FRAGMENT_SHADER
+
+void main(void) {
+    float area = compute_path_area(
+        v_local_pos,
+        v_pixel_size,
+        v_edges_address,
+        v_edge_count
+    );
+    float alpha = path_coverage(area, v_fill_rule);
+
+    vec4 color;
+    switch (v_brush_kind) {
+        case PATH_BRUSH_LINEAR_GRADIENT: {
+            vec2 start = v_brush_params0.xy;
+            vec2 dir = v_brush_params0.zw - start;
+            float len_sq = dot(dir, dir);
+            float offset = len_sq != 0.0 ? dot(v_local_pos - start, dir) / len_sq : 0.0;
+            color = sample_gradient(offset);
+            break;
+        }
+        case PATH_BRUSH_RADIAL_GRADIENT: {
+            vec2 radius = v_brush_params0.zw;
+            float d = v_brush_params1.y - v_brush_params1.x;
+            vec2 current = v_local_pos - v_brush_params0.xy;
+            // Scale the y axis so that the ellipse becomes a circle.
+            current.y *= radius.y != 0.0 ? radius.x / radius.y : 0.0;
+            float dist = radius.x != 0.0 ? length(current) / radius.x : 0.0;
+            float offset = d != 0.0 ? (dist - v_brush_params1.x) / d : 0.0;
+            color = sample_gradient(offset);
+            break;
+        }
+        case PATH_BRUSH_CONIC_GRADIENT: {
+            vec2 current = v_local_pos - v_brush_params0.xy;
+            float d = v_brush_params1.y - v_brush_params1.x;
+            float angle = atan(current.y, current.x) + PI / 2.0 - v_brush_params0.z;
+            float offset = fract(angle / (2.0 * PI));
+            offset = d != 0.0 ? (offset - v_brush_params1.x) / d : 0.0;
+            color = sample_gradient(offset);
+            break;
+        }
+        default: {
+            // Solid paths are rendered as an alpha mask, and tinted by the
+            // primitive color when the task is composited.
+            color = vec4(1.0);
+            break;
+        }
+    }
+
+    oFragColor = color * alpha;
+}
+#endif
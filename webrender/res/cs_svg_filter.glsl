@@ -26,6 +26,11 @@ flat varying float vFloat0;
 flat varying mat4 vColorMat;
 flat varying ivec4 vFuncs;
 
+// The position of the fragment in the filter task, in device pixels. This is
+// only used by filters with fewer than two inputs, so it shares the varying
+// of the second input.
+#define vTaskPos vInput2Uv
+
 #define FILTER_BLEND                0
 #define FILTER_FLOOD                1
 #define FILTER_LINEAR_TO_SRGB       2
@@ -37,6 +42,14 @@ flat varying ivec4 vFuncs;
 #define FILTER_COMPONENT_TRANSFER   8
 #define FILTER_IDENTITY             9
 #define FILTER_COMPOSITE            10
+#define FILTER_TURBULENCE           11
+#define FILTER_MORPHOLOGY           12
+#define FILTER_CONVOLVE_MATRIX      13
+#define FILTER_DISPLACEMENT_MAP     14
+#define FILTER_DIFFUSE_LIGHTING     15
+#define FILTER_SPECULAR_LIGHTING    16
+#define FILTER_TILE                 17
+#define FILTER_IMAGE                18
 
 #define COMPOSITE_OVER       0
 #define COMPOSITE_IN         1
@@ -46,6 +59,17 @@ flat varying ivec4 vFuncs;
 #define COMPOSITE_LIGHTER    5
 #define COMPOSITE_ARITHMETIC 6
 
+#define MORPHOLOGY_ERODE  0
+#define MORPHOLOGY_DILATE 1
+
+#define EDGE_MODE_DUPLICATE 0
+#define EDGE_MODE_WRAP      1
+#define EDGE_MODE_NONE      2
+
+#define LIGHT_DISTANT 0
+#define LIGHT_POINT   1
+#define LIGHT_SPOT    2
+
 #ifdef WR_VERTEX_SHADER
 
 PER_INSTANCE in int aFilterRenderTaskAddress;
@@ -158,6 +182,87 @@ void main(void) {
               vFilterData0 = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress);
             }
             break;
+        case FILTER_TURBULENCE: {
+            vTaskPos = aPosition.xy * (target_rect.p1 - target_rect.p0);
+            // The base frequency and origin of the noise.
+            vFilterData0 = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress);
+            // The stitching parameters.
+            vFilterData1 = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress + ivec2(1, 0));
+            vec4 turbulence_params = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress + ivec2(2, 0));
+            // The address of the lattice and gradient tables.
+            vec4 tables_address = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress + ivec2(3, 0));
+            vData = ivec4(
+                ivec2(tables_address.xy),
+                int(turbulence_params.x),
+                int(turbulence_params.y) | (int(turbulence_params.z) << 1)
+            );
+            break;
+        }
+        case FILTER_MORPHOLOGY: {
+            vec2 morphology_texture_size = vec2(TEX_SIZE(sColor0).xy);
+            vec2 radius = filter_task.user_data.xy;
+            vData = ivec4(aFilterGenericInt, int(max(radius.x, radius.y)), 0, 0);
+            vFilterData0 = vec4(sign(radius) / morphology_texture_size, vec2(0.0));
+            vFilterData1 = vec4(input_1_task.p0, input_1_task.p1) / morphology_texture_size.xyxy;
+            break;
+        }
+        case FILTER_CONVOLVE_MATRIX: {
+            vec2 convolve_texture_size = vec2(TEX_SIZE(sColor0).xy);
+            // The order and target of the kernel.
+            vFilterData0 = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress);
+            // The divisor, bias, edge mode and whether to preserve alpha.
+            vFilterData1 = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress + ivec2(1, 0));
+            // The address of the kernel values.
+            vData = ivec4(aFilterExtraDataAddress + ivec2(2, 0), 0, 0);
+            vColorMat = mat4(
+                vec4(input_1_task.p0, input_1_task.p1) / convolve_texture_size.xyxy,
+                vec4(vec2(1.0) / convolve_texture_size, vec2(0.0)),
+                vec4(0.0),
+                vec4(0.0)
+            );
+            break;
+        }
+        case FILTER_DISPLACEMENT_MAP: {
+            vec2 displacement_texture_size = vec2(TEX_SIZE(sColor0).xy);
+            vData = ivec4(aFilterGenericInt & 0xf, (aFilterGenericInt >> 4) & 0xf, 0, 0);
+            vFilterData0 = vec4(vec2(filter_task.user_data.x) / displacement_texture_size, vec2(0.0));
+            vFilterData1 = vec4(input_1_task.p0, input_1_task.p1) / displacement_texture_size.xyxy;
+            break;
+        }
+        case FILTER_DIFFUSE_LIGHTING:
+        case FILTER_SPECULAR_LIGHTING: {
+            vec2 lighting_texture_size = vec2(TEX_SIZE(sColor0).xy);
+            vTaskPos = aPosition.xy * (target_rect.p1 - target_rect.p0);
+            vData = ivec4(aFilterGenericInt, 0, 0, 0);
+            // The surface scale, lighting constant and specular exponent.
+            vFilterData0 = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress);
+            // The lighting color.
+            vFilterData1 = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress + ivec2(1, 0));
+            vec4 light_data[2] = fetch_from_gpu_cache_2_direct(aFilterExtraDataAddress + ivec2(2, 0));
+            vColorMat = mat4(
+                light_data[0],
+                light_data[1],
+                vec4(vec2(1.0) / lighting_texture_size, vec2(0.0)),
+                vec4(0.0)
+            );
+            break;
+        }
+        case FILTER_TILE: {
+            vec2 tile_texture_size = vec2(TEX_SIZE(sColor0).xy);
+            vec4 tile_rect = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress);
+            vFilterData0 = (input_1_task.p0.xyxy + tile_rect) / tile_texture_size.xyxy;
+            break;
+        }
+        case FILTER_IMAGE: {
+            vec2 image_texture_size = vec2(TEX_SIZE(sColor0).xy);
+            vec4 image_rect = fetch_from_gpu_cache_1_direct(aFilterExtraDataAddress);
+            // Map the task onto the image, so that the image covers image_rect.
+            vec2 task_pos = aPosition.xy * (target_rect.p1 - target_rect.p0);
+            vec2 f = (task_pos - image_rect.xy) / (image_rect.zw - image_rect.xy);
+            vInput1Uv = mix(input_1_task.p0, input_1_task.p1, f) / image_texture_size;
+            vFilterData1 = vec4(input_1_task.p0, input_1_task.p1) / image_texture_size.xyxy;
+            break;
+        }
         default:
             break;
     }
@@ -516,6 +621,212 @@ vec4 sampleInUvRect(sampler2D sampler, vec2 uv, vec4 uvRect) {
     return texture(sampler, clamped);
 }
 
+// Turbulence Filter
+
+#define TURBULENCE_LATTICE_BLOCKS   64
+#define TURBULENCE_PERLIN_N         4096
+#define TURBULENCE_BM               0xff
+
+// The lattice and gradient tables are stored in their own GPU cache entry,
+// shared across frames. Only the first half of the tables described by the SVG
+// specification is stored, so indices are wrapped.
+int turbulence_lattice(int index) {
+    index = index & TURBULENCE_BM;
+    vec4 texel = fetch_from_gpu_cache_1_direct(vData.xy + ivec2(index / 4, 0));
+    return int(texel[index % 4]);
+}
+
+vec2 turbulence_gradient(int channel, int index) {
+    ivec2 offset = ivec2(TURBULENCE_LATTICE_BLOCKS + channel * 128 + index / 2, 0);
+    vec4 texel = fetch_from_gpu_cache_1_direct(vData.xy + offset);
+    return (index % 2) == 0 ? texel.xy : texel.zw;
+}
+
+// The noise2 function from the SVG specification, for all four channels at once.
+vec4 turbulence_noise(vec2 point, bool stitch, ivec4 stitch_info) {
+    vec2 t = point + float(TURBULENCE_PERLIN_N);
+    ivec2 b0 = ivec2(t);
+    ivec2 b1 = b0 + 1;
+    vec2 r0 = t - vec2(b0);
+    vec2 r1 = r0 - 1.0;
+
+    // If stitching, adjust the lattice points accordingly.
+    if (stitch) {
+        if (b0.x >= stitch_info.z) b0.x -= stitch_info.x;
+        if (b1.x >= stitch_info.z) b1.x -= stitch_info.x;
+        if (b0.y >= stitch_info.w) b0.y -= stitch_info.y;
+        if (b1.y >= stitch_info.w) b1.y -= stitch_info.y;
+    }
+
+    b0 = b0 & ivec2(TURBULENCE_BM);
+    b1 = b1 & ivec2(TURBULENCE_BM);
+
+    int i = turbulence_lattice(b0.x);
+    int j = turbulence_lattice(b1.x);
+    int b00 = turbulence_lattice(i + b0.y);
+    int b10 = turbulence_lattice(j + b0.y);
+    int b01 = turbulence_lattice(i + b1.y);
+    int b11 = turbulence_lattice(j + b1.y);
+
+    vec2 s = r0 * r0 * (3.0 - 2.0 * r0);
+
+    vec4 result;
+    for (int channel = 0; channel < 4; channel++) {
+        float u = dot(r0, turbulence_gradient(channel, b00));
+        float v = dot(vec2(r1.x, r0.y), turbulence_gradient(channel, b10));
+        float a = mix(u, v, s.x);
+        u = dot(vec2(r0.x, r1.y), turbulence_gradient(channel, b01));
+        v = dot(r1, turbulence_gradient(channel, b11));
+        float b = mix(u, v, s.x);
+        result[channel] = mix(a, b, s.y);
+    }
+    return result;
+}
+
+vec4 Turbulence() {
+    bool fractal_noise = (vData.w & 1) == 0;
+    bool stitch = (vData.w & 2) != 0;
+    ivec4 stitch_info = ivec4(vFilterData1);
+
+    vec2 point = (floor(vTaskPos) + vFilterData0.zw) * vFilterData0.xy;
+    vec4 sum = vec4(0.0);
+    float ratio = 1.0;
+    for (int octave = 0; octave < vData.z; octave++) {
+        vec4 noise = turbulence_noise(point, stitch, stitch_info);
+        sum += (fractal_noise ? noise : abs(noise)) / ratio;
+        point *= 2.0;
+        ratio *= 2.0;
+        stitch_info.xy *= 2;
+        stitch_info.zw = 2 * stitch_info.zw - TURBULENCE_PERLIN_N;
+    }
+
+    vec4 result = fractal_noise ? (sum + 1.0) * 0.5 : sum;
+    return clamp(result, vec4(0.0), vec4(1.0));
+}
+
+// Morphology Filter
+
+// A single pass of the morphology filter, along one axis. The input and the
+// result are premultiplied.
+vec4 Morphology(int op, int radius, vec2 uv_step) {
+    vec4 result = op == MORPHOLOGY_DILATE ? vec4(0.0) : vec4(1.0);
+    for (int i = -radius; i <= radius; i++) {
+        vec2 uv = vInput1Uv + float(i) * uv_step;
+        // Pixels outside of the input are transparent black.
+        vec4 texel = sampleInUvRect(sColor0, uv, vInput1UvRect) *
+            point_inside_rect(uv, vFilterData1.xy, vFilterData1.zw);
+        result = op == MORPHOLOGY_DILATE ? max(result, texel) : min(result, texel);
+    }
+    return result;
+}
+
+// Convolve Matrix Filter
+
+// Returns a premultiplied result.
+vec4 ConvolveMatrix(float source_alpha) {
+    ivec2 order = ivec2(vFilterData0.xy);
+    ivec2 target = ivec2(vFilterData0.zw);
+    float divisor = vFilterData1.x;
+    float bias = vFilterData1.y;
+    int edge_mode = int(vFilterData1.z);
+    bool preserve_alpha = vFilterData1.w != 0.0;
+    vec4 uv_rect = vColorMat[0];
+    vec2 texel_size = vColorMat[1].xy;
+
+    vec4 sum = vec4(0.0);
+    for (int y = 0; y < order.y; y++) {
+        for (int x = 0; x < order.x; x++) {
+            // The kernel is rotated by 180 degrees relative to the source.
+            int k = (order.y - 1 - y) * order.x + (order.x - 1 - x);
+            float weight = fetch_from_gpu_cache_1_direct(vData.xy + ivec2(k / 4, 0))[k % 4];
+
+            vec2 uv = vInput1Uv + vec2(x - target.x, y - target.y) * texel_size;
+            if (edge_mode == EDGE_MODE_WRAP) {
+                uv = uv_rect.xy + mod(uv - uv_rect.xy, uv_rect.zw - uv_rect.xy);
+            }
+            // Clamping to the input rect duplicates the edge pixels.
+            vec4 texel = sampleInUvRect(sColor0, uv, vInput1UvRect);
+            if (edge_mode == EDGE_MODE_NONE) {
+                texel *= point_inside_rect(uv, uv_rect.xy, uv_rect.zw);
+            }
+            if (preserve_alpha && texel.a != 0.0) {
+                texel.rgb /= texel.a;
+            }
+            sum += texel * weight;
+        }
+    }
+
+    vec4 result;
+    if (preserve_alpha) {
+        result.rgb = clamp(sum.rgb / divisor + bias, 0.0, 1.0);
+        result.a = source_alpha;
+        result.rgb *= result.a;
+    } else {
+        result.a = clamp(sum.a / divisor + bias, 0.0, 1.0);
+        result.rgb = clamp(sum.rgb / divisor + bias * result.a, vec3(0.0), vec3(result.a));
+    }
+    return result;
+}
+
+// Lighting Filters
+
+float alpha_at(vec2 offset, vec2 texel_size) {
+    return sampleInUvRect(sColor0, vInput1Uv + offset * texel_size, vInput1UvRect).a;
+}
+
+// Returns an unpremultiplied result.
+vec4 Lighting(bool specular) {
+    vec2 texel_size = vColorMat[2].xy;
+    float surface_scale = vFilterData0.x;
+    float lighting_constant = vFilterData0.y;
+    float specular_exponent = vFilterData0.z;
+
+    // Compute the surface normal from the alpha channel with a Sobel
+    // filter. Edge pixels are duplicated outside of the input.
+    float a00 = alpha_at(vec2(-1.0, -1.0), texel_size);
+    float a10 = alpha_at(vec2( 0.0, -1.0), texel_size);
+    float a20 = alpha_at(vec2( 1.0, -1.0), texel_size);
+    float a01 = alpha_at(vec2(-1.0,  0.0), texel_size);
+    float a11 = alpha_at(vec2( 0.0,  0.0), texel_size);
+    float a21 = alpha_at(vec2( 1.0,  0.0), texel_size);
+    float a02 = alpha_at(vec2(-1.0,  1.0), texel_size);
+    float a12 = alpha_at(vec2( 0.0,  1.0), texel_size);
+    float a22 = alpha_at(vec2( 1.0,  1.0), texel_size);
+
+    vec2 n = -surface_scale * 0.25 * vec2(
+        (a20 + 2.0 * a21 + a22) - (a00 + 2.0 * a01 + a02),
+        (a02 + 2.0 * a12 + a22) - (a00 + 2.0 * a10 + a20)
+    );
+    vec3 normal = normalize(vec3(n, 1.0));
+
+    vec3 surface = vec3(floor(vTaskPos), surface_scale * a11);
+    vec3 color = vFilterData1.rgb;
+    vec3 light;
+    if (vData.x == LIGHT_DISTANT) {
+        light = vColorMat[0].xyz;
+    } else {
+        light = normalize(vColorMat[0].xyz - surface);
+        if (vData.x == LIGHT_SPOT) {
+            float minus_l_dot_s = -dot(light, vColorMat[1].xyz);
+            float spot_exponent = vColorMat[0].w;
+            float cos_cone_angle = vColorMat[1].w;
+            color *= minus_l_dot_s >= cos_cone_angle ? pow(max(minus_l_dot_s, 0.0), spot_exponent) : 0.0;
+        }
+    }
+
+    vec4 result;
+    if (specular) {
+        vec3 halfway = normalize(light + vec3(0.0, 0.0, 1.0));
+        float factor = lighting_constant * pow(max(dot(normal, halfway), 0.0), specular_exponent);
+        result.rgb = clamp(factor * color, vec3(0.0), vec3(1.0));
+        result.a = max(result.r, max(result.g, result.b));
+    } else {
+        float factor = lighting_constant * dot(normal, light);
+        result = vec4(clamp(factor * color, vec3(0.0), vec3(1.0)), 1.0);
+    }
+    return result;
+}
+
 void main(void) {
     vec4 Ca = vec4(0.0, 0.0, 0.0, 0.0);
     vec4 Cb = vec4(0.0, 0.0, 0.0, 0.0);
@@ -582,6 +893,47 @@ void main(void) {
         case FILTER_COMPOSITE:
             result = composite(Ca, Cb, vData.x);
             needsPremul = false;
+            break;
+        case FILTER_TURBULENCE:
+            result = Turbulence();
+            break;
+        case FILTER_MORPHOLOGY:
+            result = Morphology(vData.x, vData.y, vFilterData0.xy);
+            needsPremul = false;
+            break;
+        case FILTER_CONVOLVE_MATRIX:
+            result = ConvolveMatrix(Ca.a);
+            needsPremul = false;
+            break;
+        case FILTER_DISPLACEMENT_MAP: {
+            // Cb is the displacement map, which is used unpremultiplied.
+            // Dynamically indexing a vector is buggy on some devices, so use a temporary array.
+            float[4] map = float[4](Cb.r, Cb.g, Cb.b, Cb.a);
+            vec2 displacement = vec2(map[vData.x], map[vData.y]) - 0.5;
+            vec2 uv = vInput1Uv + displacement * vFilterData0.xy;
+            result = sampleInUvRect(sColor0, uv, vInput1UvRect);
+            result *= point_inside_rect(uv, vFilterData1.xy, vFilterData1.zw);
+            needsPremul = false;
+            break;
+        }
+        case FILTER_DIFFUSE_LIGHTING:
+            result = Lighting(false);
+            break;
+        case FILTER_SPECULAR_LIGHTING:
+            result = Lighting(true);
+            break;
+        case FILTER_TILE: {
+            vec2 tile_size = vFilterData0.zw - vFilterData0.xy;
+            vec2 uv = vFilterData0.xy + mod(vInput1Uv - vFilterData0.xy, tile_size);
+            result = sampleInUvRect(sColor0, uv, vInput1UvRect);
+            needsPremul = false;
+            break;
+        }
+        case FILTER_IMAGE:
+            result = sampleInUvRect(sColor0, vInput1Uv, vInput1UvRect);
+            result *= point_inside_rect(vInput1Uv, vFilterData1.xy, vFilterData1.zw);
+            needsPremul = false;
+            break;
         default:
             break;
     }
@@ -0,0 +1,53 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Draws the coverage of a glyph from the glyph cache into a text mask, see
+// TextMaskTask. Glyphs that overlap are combined with max blending.
+
+#include shared,rect,gpu_cache
+
+varying vec2 v_uv;
+flat varying vec4 v_uv_bounds;
+
+// Non-zero if the coverage is in the alpha channel of color glyphs, rather
+// than the red channel.
+flat varying int v_alpha_channel;
+
+#ifdef WR_VERTEX_SHADER
+
+// The rect of the glyph in the render task, in device pixels.
+PER_INSTANCE in vec4 aTaskRect;
+
+// The UV rect of the glyph in the glyph cache.
+PER_INSTANCE in ivec2 aResourceAddress;
+
+PER_INSTANCE in int aAlphaChannel;
+
+void main(void) {
+    ImageSource res = fetch_image_source_direct(aResourceAddress);
+
+    vec2 pos = mix(aTaskRect.xy, aTaskRect.zw, aPosition.xy);
+    gl_Position = uTransform * vec4(pos, 0.0, 1.0);
+
+    vec2 texture_size = vec2(TEX_SIZE(sColor0));
+    vec4 uv_rect = vec4(res.uv_rect.p0, res.uv_rect.p1);
+    v_uv = mix(uv_rect.xy, uv_rect.zw, aPosition.xy) / texture_size;
+
+    // Apply a half-texel offset to the UV bounds to prevent linear samples
+    // from outside of the glyph.
+    v_uv_bounds = (uv_rect + vec4(0.5, 0.5, -0.5, -0.5)) / texture_size.xyxy;
+
+    v_alpha_channel = aAlphaChannel;
+}
+#endif
+
+#ifdef WR_FRAGMENT_SHADER
+
+void main(void) {
+    vec4 texel = texture(sColor0, clamp(v_uv, v_uv_bounds.xy, v_uv_bounds.zw));
+    float alpha = v_alpha_channel != 0 ? texel.a : texel.r;
+    oFragColor = vec4(alpha);
+}
+
+#endif
@@ -0,0 +1,115 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// HDR transfer functions and tone mapping, see tone_map.rs.
+//
+// HDR content is stored as extended sRGB, where 1.0 is the reference white.
+// This file must remain compatible with ESSL 1.
+
+// `ToneMapOperator`
+#define TONE_MAP_CLIP     0
+#define TONE_MAP_REINHARD 1
+#define TONE_MAP_BT2390   2
+
+#ifdef WR_FRAGMENT_SHADER
+
+#define PQ_M1 0.1593017578125
+#define PQ_M2 78.84375
+#define PQ_C1 0.8359375
+#define PQ_C2 18.8515625
+#define PQ_C3 18.6875
+#define PQ_MAX_NITS 10000.0
+
+// The SMPTE ST 2084 EOTF, from PQ code values to nits.
+float pq_to_nits(float e) {
+    float p = pow(clamp(e, 0.0, 1.0), 1.0 / PQ_M2);
+    return PQ_MAX_NITS * pow(max(p - PQ_C1, 0.0) / (PQ_C2 - PQ_C3 * p), 1.0 / PQ_M1);
+}
+
+float nits_to_pq(float nits) {
+    float y = pow(clamp(nits / PQ_MAX_NITS, 0.0, 1.0), PQ_M1);
+    return pow((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y), PQ_M2);
+}
+
+// The sRGB transfer functions, mirrored for negative values.
+vec3 extended_srgb_to_linear(vec3 c) {
+    vec3 a = abs(c);
+    vec3 lo = a / 12.92;
+    vec3 hi = pow((a + 0.055) / 1.055, vec3(2.4));
+    return sign(c) * mix(lo, hi, step(0.04045, a));
+}
+
+vec3 linear_to_extended_srgb(vec3 c) {
+    vec3 a = abs(c);
+    vec3 lo = a * 12.92;
+    vec3 hi = 1.055 * pow(a, vec3(1.0 / 2.4)) - 0.055;
+    return sign(c) * mix(lo, hi, step(0.0031308, a));
+}
+
+// Linear Rec. 2020 to linear sRGB, in column-major.
+const mat3 SrgbFromRec2020 = mat3(
+    1.6604910, -0.1245505, -0.0181508,
+  -0.5876411,  1.1328999, -0.1005789,
+  -0.0728499, -0.0083494,  1.1187297
+);
+
+// Decode PQ encoded Rec. 2020 values to extended sRGB.
+vec3 pq_to_extended_srgb(vec3 pq, float reference_white_nits) {
+    vec3 nits = vec3(pq_to_nits(pq.r), pq_to_nits(pq.g), pq_to_nits(pq.b));
+    return linear_to_extended_srgb(SrgbFromRec2020 * (nits / reference_white_nits));
+}
+
+// Tone map a linear color relative to the reference white. The parameters
+// come from HdrOptions::shader_params.
+vec3 tone_map(vec3 color, vec4 params) {
+    int op = int(params.x + 0.5);
+    float reference_white = params.y;
+    float display_peak = params.z / reference_white;
+    float content_peak = max(params.w / reference_white, display_peak);
+
+    if (op == TONE_MAP_CLIP) {
+        return min(color, vec3(display_peak));
+    }
+
+    // The other operators scale the color by the ratio the brightest
+    // channel is mapped with, to preserve the hue.
+    float max_channel = max(max(color.r, color.g), color.b);
+    if (max_channel <= 0.0) {
+        return color;
+    }
+
+    float mapped;
+    if (op == TONE_MAP_REINHARD) {
+        float x = max_channel / display_peak;
+        float white = content_peak / display_peak;
+        mapped = x * (1.0 + x / (white * white)) / (1.0 + x) * display_peak;
+    } else {
+        float content_max_pq = nits_to_pq(content_peak * reference_white);
+        float max_lum = nits_to_pq(params.z) / content_max_pq;
+        float knee = max(1.5 * max_lum - 0.5, 0.0);
+        float e = min(nits_to_pq(max_channel * reference_white) / content_max_pq, 1.0);
+        if (e > knee && max_lum < 1.0) {
+            float t = (e - knee) / (1.0 - knee);
+            float t2 = t * t;
+            float t3 = t2 * t;
+            e = (2.0 * t3 - 3.0 * t2 + 1.0) * knee +
+                (t3 - 2.0 * t2 + t) * (1.0 - knee) +
+                (-2.0 * t3 + 3.0 * t2) * max_lum;
+        }
+        mapped = pq_to_nits(e * content_max_pq) / reference_white;
+    }
+
+    return color * (min(mapped, display_peak) / max_channel);
+}
+
+// Tone map a premultiplied extended sRGB color.
+vec4 tone_map_premultiplied(vec4 color, vec4 params) {
+    if (color.a <= 0.0) {
+        return color;
+    }
+    vec3 linear = extended_srgb_to_linear(color.rgb / color.a);
+    return vec4(linear_to_extended_srgb(tone_map(linear, params)) * color.a, color.a);
+}
+
+#endif
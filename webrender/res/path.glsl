@@ -0,0 +1,57 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#include gpu_cache
+
+#define FILL_RULE_NONZERO   1
+#define FILL_RULE_EVENODD   2
+
+#ifdef WR_FRAGMENT_SHADER
+
+// Compute the winding number of a path, integrated over the area of the
+// pixel of size `pixel_size` centered on `pos`. The edges are stored as one
+// [from, to] pair per GPU cache block. Each edge contributes the signed area
+// of the pixel that lies to its left, which gives analytic anti-aliasing
+// without having to deal with interior edges (e.g. where stroke segments
+// overlap).
+float compute_path_area(vec2 pos, vec2 pixel_size, HIGHP_FS_ADDRESS int edges_address, int edge_count) {
+    float area = 0.0;
+
+    for (int i = 0; i < edge_count; i++) {
+        vec4 edge = fetch_from_gpu_cache_1(edges_address + i);
+
+        // Move the edge into pixel space, centered on the fragment.
+        vec2 from = (edge.xy - pos) / pixel_size;
+        vec2 to = (edge.zw - pos) / pixel_size;
+
+        // Clip the edge to the vertical extent of the pixel.
+        float y0 = clamp(from.y, -0.5, 0.5);
+        float y1 = clamp(to.y, -0.5, 0.5);
+        float dy = y1 - y0;
+        if (dy == 0.0) {
+            continue;
+        }
+
+        float inv_height = 1.0 / (to.y - from.y);
+        float x0 = mix(from.x, to.x, (y0 - from.y) * inv_height);
+        float x1 = mix(from.x, to.x, (y1 - from.y) * inv_height);
+        float x = clamp(0.5 * (x0 + x1), -0.5, 0.5);
+
+        area += dy * (x + 0.5);
+    }
+
+    return area;
+}
+
+// Convert the winding area of a pixel into coverage, according to a
+// FILL_RULE_* value.
+float path_coverage(float area, int fill_rule) {
+    area = abs(area);
+    if (fill_rule == FILL_RULE_EVENODD) {
+        return 1.0 - abs(1.0 - mod(area, 2.0));
+    }
+    return min(area, 1.0);
+}
+
+#endif
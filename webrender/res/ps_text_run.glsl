@@ -17,6 +17,15 @@ varying vec2 v_uv;
 varying vec4 v_uv_clip;
 #endif
 
+#ifdef WR_FEATURE_SDF
+// The distance, in texels, that distance field glyphs cover on each side of
+// the outline. Must match SDF_RANGE in sdf.rs.
+#define SDF_RANGE 4.0
+
+// The number of device pixels covered by a texel of the distance field.
+flat varying float v_sdf_scale;
+#endif
+
 #ifdef WR_VERTEX_SHADER
 
 #define VECS_PER_TEXT_RUN           2
@@ -193,7 +202,18 @@ void main() {
     // - The transform has perspective or does not have a 2d inverse (Screen or local space).
     // - The transform's scale will result in result in very large rasterized glyphs and
     //   we clamped the size. This will imply local raster space.
+#ifdef WR_FEATURE_SDF
+    // Distance field glyphs look the same at any subpixel offset, so they aren't
+    // snapped, which keeps animated text from wiggling.
+    vec2 raster_glyph_offset = glyph.offset * glyph_raster_scale / res.scale;
+
+    mat2 local_transform = mat2(transform.m);
+    float local_scale = sqrt(abs(local_transform[0][0] * local_transform[1][1] -
+                                 local_transform[0][1] * local_transform[1][0]));
+    v_sdf_scale = glyph_scale_inv * local_scale * task.device_pixel_scale;
+#else
     vec2 raster_glyph_offset = floor(glyph.offset * glyph_raster_scale + snap_bias) / res.scale;
+#endif
 
     // Compute the glyph rect in local space.
     //
@@ -295,6 +315,13 @@ Fragment text_fs(void) {
 
     vec2 tc = clamp(v_uv, v_uv_bounds.xy, v_uv_bounds.zw);
     vec4 mask = texture(sColor0, tc);
+    #ifdef WR_FEATURE_SDF
+        // The median of the three channels is the distance to the outline, where
+        // 0.5 is on it. Scale it to device pixels for a one pixel wide edge.
+        float dist = max(min(mask.r, mask.g), min(max(mask.r, mask.g), mask.b));
+        float coverage = clamp((dist - 0.5) * 2.0 * SDF_RANGE * v_sdf_scale + 0.5, 0.0, 1.0);
+        mask = vec4(coverage);
+    #endif
     // v_mask_swizzle.z != 0 means we are using an R8 texture as alpha,
     // and therefore must swizzle from the r channel to all channels.
     mask = mix(mask, mask.rrrr, bvec4(v_mask_swizzle.z != 0.0));
@@ -332,7 +359,7 @@ void main() {
     #endif
 }
 
-#if defined(SWGL_DRAW_SPAN) && defined(SWGL_BLEND) && defined(SWGL_CLIP_DIST)
+#if defined(SWGL_DRAW_SPAN) && defined(SWGL_BLEND) && defined(SWGL_CLIP_DIST) && !defined(WR_FEATURE_SDF)
 void swgl_drawSpanRGBA8() {
     // Only support simple swizzles for now. More complex swizzles must either
     // be handled by blend overrides or the slow path.
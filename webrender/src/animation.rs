@@ -0,0 +1,837 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Keyframe animations of property bindings.
+//!
+//! Each document owns an `AnimationStore`, which the render backend samples
+//! every time a frame is generated. The sampled values are handed to the
+//! `SceneProperties` of the document, where they take precedence over the
+//! values supplied through `DynamicProperties`.
+
+use api::{AnimatedProperty, Animation, AnimationDirection, AnimationFillMode, AnimationIterations};
+use api::{ColorF, DynamicProperties, Keyframe, PropertyBindingId, StepPosition, TimingFunction};
+use api::units::LayoutTransform;
+use crate::internal_types::FastHashMap;
+
+/// The precision used when solving cubic bezier timing functions.
+const BEZIER_EPSILON: f32 = 1.0e-5;
+
+struct AnimationState {
+    animation: Animation,
+    /// The time at which the animation was registered, or the equivalent
+    /// time once it has been paused, resumed or seeked.
+    start_time_ns: u64,
+    /// The local time of the animation in milliseconds, if it is paused.
+    paused_time: Option<f32>,
+    /// Whether the completion of the animation has been reported.
+    finished: bool,
+}
+
+impl AnimationState {
+    fn local_time(&self, now_ns: u64) -> f32 {
+        match self.paused_time {
+            Some(time) => time,
+            None => now_ns.saturating_sub(self.start_time_ns) as f32 / 1_000_000.0,
+        }
+    }
+}
+
+/// The animations of a document, keyed by the property binding they drive.
+pub struct AnimationStore {
+    animations: FastHashMap<PropertyBindingId, AnimationState>,
+}
+
+impl AnimationStore {
+    pub fn new() -> Self {
+        AnimationStore {
+            animations: FastHashMap::default(),
+        }
+    }
+
+    /// Register an animation, replacing any animation of the same binding.
+    pub fn add(&mut self, mut animation: Animation, now_ns: u64) {
+        let keyframes_are_empty = match animation.property {
+            AnimatedProperty::Float(_, ref mut keyframes) => sort_keyframes(keyframes),
+            AnimatedProperty::Color(_, ref mut keyframes) => sort_keyframes(keyframes),
+            AnimatedProperty::Transform(_, ref mut keyframes) => sort_keyframes(keyframes),
+        };
+
+        if keyframes_are_empty {
+            warn!("Ignoring animation of {:?} without keyframes", animation.id());
+            return;
+        }
+
+        self.animations.insert(animation.id(), AnimationState {
+            animation,
+            start_time_ns: now_ns,
+            paused_time: None,
+            finished: false,
+        });
+    }
+
+    pub fn pause(&mut self, id: PropertyBindingId, now_ns: u64) {
+        if let Some(state) = self.animations.get_mut(&id) {
+            if state.paused_time.is_none() {
+                state.paused_time = Some(state.local_time(now_ns));
+            }
+        }
+    }
+
+    pub fn resume(&mut self, id: PropertyBindingId, now_ns: u64) {
+        if let Some(state) = self.animations.get_mut(&id) {
+            if let Some(time) = state.paused_time.take() {
+                state.start_time_ns = now_ns.saturating_sub((time * 1_000_000.0) as u64);
+            }
+        }
+    }
+
+    /// Move an animation to the given local time, in milliseconds.
+    pub fn seek(&mut self, id: PropertyBindingId, time: f32, now_ns: u64) {
+        if let Some(state) = self.animations.get_mut(&id) {
+            let time = time.max(0.0);
+            match state.paused_time {
+                Some(ref mut paused_time) => *paused_time = time,
+                None => state.start_time_ns = now_ns.saturating_sub((time * 1_000_000.0) as u64),
+            }
+            // Seeking back into the animation makes it report its completion again.
+            state.finished = false;
+        }
+    }
+
+    pub fn cancel(&mut self, id: PropertyBindingId) {
+        self.animations.remove(&id);
+    }
+
+    /// Returns true if any animation will produce a different value at a
+    /// later sample, i.e. it is neither paused nor finished.
+    pub fn is_active(&self) -> bool {
+        self.animations.values().any(|state| !state.finished && state.paused_time.is_none())
+    }
+
+    /// Sample the animations at the given time. Returns the values of the
+    /// animated properties, and appends the animations that finished since
+    /// the last sample to `finished`. Finished animations that don't fill
+    /// forwards are removed from the store.
+    pub fn sample(
+        &mut self,
+        now_ns: u64,
+        finished: &mut Vec<PropertyBindingId>,
+    ) -> DynamicProperties {
+        let mut properties = DynamicProperties::default();
+
+        self.animations.retain(|id, state| {
+            let local_time = state.local_time(now_ns);
+            let animation = &state.animation;
+
+            if is_after(animation, local_time) && !state.finished {
+                state.finished = true;
+                finished.push(*id);
+            }
+
+            if let Some(progress) = iteration_progress(animation, local_time) {
+                let progress = apply_timing_function(
+                    &animation.timing_function,
+                    progress,
+                    animation.duration,
+                );
+
+                match animation.property {
+                    AnimatedProperty::Float(key, ref keyframes) => {
+                        properties.floats.push(key.with(interpolate_keyframes(keyframes, progress)));
+                    }
+                    AnimatedProperty::Color(key, ref keyframes) => {
+                        properties.colors.push(key.with(interpolate_keyframes(keyframes, progress)));
+                    }
+                    AnimatedProperty::Transform(key, ref keyframes) => {
+                        properties.transforms.push(key.with(interpolate_keyframes(keyframes, progress)));
+                    }
+                }
+            }
+
+            // Keep the animation around while it can still produce a value.
+            !state.finished || fills_forwards(animation.fill_mode)
+        });
+
+        properties
+    }
+}
+
+/// Sort the keyframes by offset, and return true if there are none.
+fn sort_keyframes<T>(keyframes: &mut Vec<Keyframe<T>>) -> bool {
+    keyframes.sort_by(|a, b| a.offset.partial_cmp(&b.offset).unwrap_or(std::cmp::Ordering::Equal));
+    keyframes.is_empty()
+}
+
+fn fills_forwards(fill_mode: AnimationFillMode) -> bool {
+    match fill_mode {
+        AnimationFillMode::Forwards | AnimationFillMode::Both => true,
+        AnimationFillMode::None | AnimationFillMode::Backwards => false,
+    }
+}
+
+fn fills_backwards(fill_mode: AnimationFillMode) -> bool {
+    match fill_mode {
+        AnimationFillMode::Backwards | AnimationFillMode::Both => true,
+        AnimationFillMode::None | AnimationFillMode::Forwards => false,
+    }
+}
+
+fn iteration_count(animation: &Animation) -> f32 {
+    match animation.iterations {
+        AnimationIterations::Count(count) => count.max(0.0),
+        AnimationIterations::Infinite => std::f32::INFINITY,
+    }
+}
+
+fn active_duration(animation: &Animation) -> f32 {
+    let iterations = iteration_count(animation);
+    if animation.duration <= 0.0 || iterations == 0.0 {
+        0.0
+    } else {
+        animation.duration * iterations
+    }
+}
+
+fn is_after(animation: &Animation, local_time: f32) -> bool {
+    local_time - animation.delay >= active_duration(animation)
+}
+
+/// Compute the directed progress within the current iteration, or None if the
+/// animation doesn't apply at the given local time.
+///
+/// Specification: https://drafts.csswg.org/web-animations-1/#calculating-progress-within-an-iteration
+fn iteration_progress(animation: &Animation, local_time: f32) -> Option<f32> {
+    let active_time = local_time - animation.delay;
+    let active_duration = active_duration(animation);
+    let iterations = iteration_count(animation);
+
+    let overall_progress = if active_time < 0.0 {
+        if !fills_backwards(animation.fill_mode) {
+            return None;
+        }
+        0.0
+    } else if active_time >= active_duration {
+        if !fills_forwards(animation.fill_mode) {
+            return None;
+        }
+        iterations
+    } else {
+        active_time / animation.duration
+    };
+
+    // An infinite animation with a zero duration ends straight away, and
+    // holds the end of its last iteration.
+    if overall_progress.is_infinite() {
+        let reversed = animation.direction == AnimationDirection::Reverse;
+        return Some(if reversed { 0.0 } else { 1.0 });
+    }
+
+    let mut simple_progress = overall_progress.fract();
+    let mut current_iteration = overall_progress.floor();
+    // The end of an iteration is its progress of 1, not the start of the next one.
+    if simple_progress == 0.0 && overall_progress != 0.0 && active_time >= active_duration {
+        simple_progress = 1.0;
+        current_iteration -= 1.0;
+    }
+
+    let odd_iteration = current_iteration % 2.0 == 1.0;
+    let reversed = match animation.direction {
+        AnimationDirection::Normal => false,
+        AnimationDirection::Reverse => true,
+        AnimationDirection::Alternate => odd_iteration,
+        AnimationDirection::AlternateReverse => !odd_iteration,
+    };
+
+    Some(if reversed { 1.0 - simple_progress } else { simple_progress })
+}
+
+/// Apply a timing function to the progress of an iteration. `duration` is the
+/// duration of an iteration in milliseconds, which drives spring simulations.
+pub fn apply_timing_function(function: &TimingFunction, progress: f32, duration: f32) -> f32 {
+    match *function {
+        TimingFunction::Linear => progress,
+        TimingFunction::CubicBezier { x1, y1, x2, y2 } => {
+            cubic_bezier(x1, y1, x2, y2, progress)
+        }
+        TimingFunction::Steps { count, position } => steps(count, position, progress),
+        TimingFunction::Spring { mass, stiffness, damping, initial_velocity } => {
+            if progress >= 1.0 {
+                1.0
+            } else {
+                spring(mass, stiffness, damping, initial_velocity, progress * duration / 1000.0)
+            }
+        }
+    }
+}
+
+/// Evaluate a cubic bezier timing function.
+///
+/// Specification: https://drafts.csswg.org/css-easing/#cubic-bezier-easing-functions
+fn cubic_bezier(x1: f32, y1: f32, x2: f32, y2: f32, x: f32) -> f32 {
+    // Outside of the [0, 1] range the curve is extended linearly along the
+    // tangent at its end points.
+    if x <= 0.0 {
+        let slope = if x1 > 0.0 { y1 / x1 } else if y1 == 0.0 && x2 > 0.0 { y2 / x2 } else { 0.0 };
+        return slope * x;
+    }
+    if x >= 1.0 {
+        let slope = if x2 < 1.0 {
+            (y2 - 1.0) / (x2 - 1.0)
+        } else if y2 == 1.0 && x1 < 1.0 {
+            (y1 - 1.0) / (x1 - 1.0)
+        } else {
+            0.0
+        };
+        return 1.0 + slope * (x - 1.0);
+    }
+
+    // Polynomial coefficients of the curve, with the end points at 0 and 1.
+    let cx = 3.0 * x1;
+    let bx = 3.0 * (x2 - x1) - cx;
+    let ax = 1.0 - cx - bx;
+    let cy = 3.0 * y1;
+    let by = 3.0 * (y2 - y1) - cy;
+    let ay = 1.0 - cy - by;
+
+    let sample_x = |t: f32| ((ax * t + bx) * t + cx) * t;
+    let sample_dx = |t: f32| (3.0 * ax * t + 2.0 * bx) * t + cx;
+    let sample_y = |t: f32| ((ay * t + by) * t + cy) * t;
+
+    // Newton's method usually converges quickly...
+    let mut t = x;
+    for _ in 0 .. 8 {
+        let error = sample_x(t) - x;
+        if error.abs() < BEZIER_EPSILON {
+            return sample_y(t);
+        }
+        let dx = sample_dx(t);
+        if dx.abs() < BEZIER_EPSILON {
+            break;
+        }
+        t -= error / dx;
+    }
+
+    // ...but fall back to bisection when the derivative gets too small.
+    let (mut lo, mut hi) = (0.0, 1.0);
+    t = x;
+    while lo < hi {
+        let value = sample_x(t);
+        if (value - x).abs() < BEZIER_EPSILON {
+            break;
+        }
+        if x > value {
+            lo = t;
+        } else {
+            hi = t;
+        }
+        let mid = (hi - lo) * 0.5 + lo;
+        if mid == t {
+            break;
+        }
+        t = mid;
+    }
+
+    sample_y(t)
+}
+
+/// Evaluate a step timing function.
+///
+/// Specification: https://drafts.csswg.org/css-easing/#step-easing-algo
+fn steps(count: u32, position: StepPosition, progress: f32) -> f32 {
+    let count = match position {
+        StepPosition::JumpNone => count.max(2),
+        _ => count.max(1),
+    } as f32;
+    let jumps = match position {
+        StepPosition::JumpStart | StepPosition::JumpEnd => count,
+        StepPosition::JumpNone => count - 1.0,
+        StepPosition::JumpBoth => count + 1.0,
+    };
+
+    let mut current_step = (progress * count).floor();
+    if let StepPosition::JumpStart | StepPosition::JumpBoth = position {
+        current_step += 1.0;
+    }
+    if progress >= 0.0 && current_step < 0.0 {
+        current_step = 0.0;
+    }
+    if progress <= 1.0 && current_step > jumps {
+        current_step = jumps;
+    }
+
+    current_step / jumps
+}
+
+/// The position of a damped spring going from 0 to 1, `time` seconds after
+/// it was released.
+pub fn spring(mass: f32, stiffness: f32, damping: f32, initial_velocity: f32, time: f32) -> f32 {
+    if mass <= 0.0 || stiffness <= 0.0 {
+        return 1.0;
+    }
+
+    let omega = (stiffness / mass).sqrt();
+    let zeta = damping.max(0.0) / (2.0 * (stiffness * mass).sqrt());
+
+    if zeta < 1.0 {
+        // Under-damped: oscillates around the final position.
+        let omega_d = omega * (1.0 - zeta * zeta).sqrt();
+        let envelope = (-zeta * omega * time).exp();
+        1.0 - envelope * (
+            (omega_d * time).cos() +
+            (zeta * omega - initial_velocity) / omega_d * (omega_d * time).sin()
+        )
+    } else if zeta == 1.0 {
+        // Critically damped.
+        1.0 - (-omega * time).exp() * (1.0 + (omega - initial_velocity) * time)
+    } else {
+        // Over-damped.
+        let root = (zeta * zeta - 1.0).sqrt();
+        let r1 = -omega * (zeta - root);
+        let r2 = -omega * (zeta + root);
+        let c1 = (initial_velocity + r2) / (r1 - r2);
+        let c2 = -1.0 - c1;
+        1.0 + c1 * (r1 * time).exp() + c2 * (r2 * time).exp()
+    }
+}
+
+/// A value that keyframe animations can interpolate.
+trait Interpolate: Copy {
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Interpolate for ColorF {
+    /// Colors are interpolated in premultiplied sRGB, as CSS does.
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let from = self.premultiplied();
+        let to = other.premultiplied();
+        let a = from.a.interpolate(&to.a, t).max(0.0).min(1.0);
+        if a <= 0.0 {
+            return ColorF::TRANSPARENT;
+        }
+
+        let component = |from: f32, to: f32| (from.interpolate(&to, t) / a).max(0.0).min(1.0);
+        ColorF::new(
+            component(from.r, to.r),
+            component(from.g, to.g),
+            component(from.b, to.b),
+            a,
+        )
+    }
+}
+
+impl Interpolate for LayoutTransform {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        match (DecomposedTransform::new(self), DecomposedTransform::new(other)) {
+            (Some(from), Some(to)) => from.interpolate(&to, t).recompose(),
+            // Transforms that can't be decomposed switch half way.
+            _ => if t < 0.5 { *self } else { *other },
+        }
+    }
+}
+
+/// Interpolate the keyframes at the given progress. Progress outside of the
+/// [0, 1] range, which cubic bezier timing functions can produce, extrapolates
+/// the first or last interval.
+///
+/// Specification: https://drafts.csswg.org/web-animations-1/#the-effect-value-of-a-keyframe-animation-effect
+fn interpolate_keyframes<T: Interpolate>(keyframes: &[Keyframe<T>], progress: f32) -> T {
+    if keyframes.len() == 1 {
+        return keyframes[0].value;
+    }
+
+    let index = keyframes[1 ..]
+        .iter()
+        .position(|keyframe| progress < keyframe.offset)
+        .map_or(keyframes.len() - 1, |index| index + 1);
+    let from = &keyframes[index - 1];
+    let to = &keyframes[index];
+
+    let interval = to.offset - from.offset;
+    let t = if interval > 0.0 {
+        (progress - from.offset) / interval
+    } else if progress < from.offset {
+        0.0
+    } else {
+        1.0
+    };
+
+    from.value.interpolate(&to.value, t)
+}
+
+type Matrix = [[f32; 4]; 4];
+type Vector = [f32; 3];
+
+fn dot(a: Vector, b: Vector) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: Vector, b: Vector) -> Vector {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn combine(a: Vector, b: Vector, scale_a: f32, scale_b: f32) -> Vector {
+    [
+        a[0] * scale_a + b[0] * scale_b,
+        a[1] * scale_a + b[1] * scale_b,
+        a[2] * scale_a + b[2] * scale_b,
+    ]
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 4]; 4];
+    for i in 0 .. 4 {
+        for j in 0 .. 4 {
+            for k in 0 .. 4 {
+                result[i][j] += a[i][k] * b[k][j];
+            }
+        }
+    }
+    result
+}
+
+/// A 3D transform decomposed into the components that are interpolated.
+///
+/// Specification: https://drafts.csswg.org/css-transforms-2/#interpolation-of-3d-matrices
+#[derive(Clone, Copy, Debug)]
+struct DecomposedTransform {
+    translation: Vector,
+    scale: Vector,
+    skew: Vector,
+    perspective: [f32; 4],
+    quaternion: [f32; 4],
+}
+
+impl DecomposedTransform {
+    fn new(transform: &LayoutTransform) -> Option<Self> {
+        let mut matrix = transform.to_arrays();
+        if matrix[3][3] == 0.0 {
+            return None;
+        }
+
+        // Normalize the matrix.
+        let w = matrix[3][3];
+        for row in matrix.iter_mut() {
+            for value in row.iter_mut() {
+                *value /= w;
+            }
+        }
+
+        // The perspective matrix is used to solve for perspective, and to test
+        // for the singularity of the upper 3x3 component.
+        let mut perspective_matrix = matrix;
+        for row in perspective_matrix.iter_mut().take(3) {
+            row[3] = 0.0;
+        }
+        perspective_matrix[3][3] = 1.0;
+        let perspective_matrix = LayoutTransform::from_arrays(perspective_matrix);
+        if perspective_matrix.determinant() == 0.0 {
+            return None;
+        }
+
+        let perspective = if matrix[0][3] != 0.0 || matrix[1][3] != 0.0 || matrix[2][3] != 0.0 {
+            let rhs = [matrix[0][3], matrix[1][3], matrix[2][3], matrix[3][3]];
+            let inverse = perspective_matrix.inverse()?.to_arrays();
+            // Multiply by the transposed inverse perspective matrix.
+            let mut perspective = [0.0; 4];
+            for (i, value) in perspective.iter_mut().enumerate() {
+                *value = (0 .. 4).map(|j| rhs[j] * inverse[i][j]).sum();
+            }
+            perspective
+        } else {
+            [0.0, 0.0, 0.0, 1.0]
+        };
+
+        let translation = [matrix[3][0], matrix[3][1], matrix[3][2]];
+
+        let mut rows = [
+            [matrix[0][0], matrix[0][1], matrix[0][2]],
+            [matrix[1][0], matrix[1][1], matrix[1][2]],
+            [matrix[2][0], matrix[2][1], matrix[2][2]],
+        ];
+        let mut scale = [0.0; 3];
+        let mut skew = [0.0; 3];
+
+        scale[0] = dot(rows[0], rows[0]).sqrt();
+        rows[0] = combine(rows[0], rows[0], 1.0 / scale[0], 0.0);
+
+        // XY shear.
+        skew[0] = dot(rows[0], rows[1]);
+        rows[1] = combine(rows[1], rows[0], 1.0, -skew[0]);
+
+        scale[1] = dot(rows[1], rows[1]).sqrt();
+        rows[1] = combine(rows[1], rows[1], 1.0 / scale[1], 0.0);
+        skew[0] /= scale[1];
+
+        // XZ and YZ shears.
+        skew[1] = dot(rows[0], rows[2]);
+        rows[2] = combine(rows[2], rows[0], 1.0, -skew[1]);
+        skew[2] = dot(rows[1], rows[2]);
+        rows[2] = combine(rows[2], rows[1], 1.0, -skew[2]);
+
+        scale[2] = dot(rows[2], rows[2]).sqrt();
+        rows[2] = combine(rows[2], rows[2], 1.0 / scale[2], 0.0);
+        skew[1] /= scale[2];
+        skew[2] /= scale[2];
+
+        // Negate the scale and rows if the coordinate system is flipped.
+        if dot(rows[0], cross(rows[1], rows[2])) < 0.0 {
+            for i in 0 .. 3 {
+                scale[i] = -scale[i];
+                rows[i] = combine(rows[i], rows[i], -1.0, 0.0);
+            }
+        }
+
+        let mut quaternion = [
+            0.5 * (1.0 + rows[0][0] - rows[1][1] - rows[2][2]).max(0.0).sqrt(),
+            0.5 * (1.0 - rows[0][0] + rows[1][1] - rows[2][2]).max(0.0).sqrt(),
+            0.5 * (1.0 - rows[0][0] - rows[1][1] + rows[2][2]).max(0.0).sqrt(),
+            0.5 * (1.0 + rows[0][0] + rows[1][1] + rows[2][2]).max(0.0).sqrt(),
+        ];
+        if rows[2][1] > rows[1][2] {
+            quaternion[0] = -quaternion[0];
+        }
+        if rows[0][2] > rows[2][0] {
+            quaternion[1] = -quaternion[1];
+        }
+        if rows[1][0] > rows[0][1] {
+            quaternion[2] = -quaternion[2];
+        }
+
+        Some(DecomposedTransform {
+            translation,
+            scale,
+            skew,
+            perspective,
+            quaternion,
+        })
+    }
+
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let lerp3 = |a: Vector, b: Vector| combine(a, b, 1.0 - t, t);
+        let lerp4 = |a: [f32; 4], b: [f32; 4]| {
+            [
+                a[0].interpolate(&b[0], t),
+                a[1].interpolate(&b[1], t),
+                a[2].interpolate(&b[2], t),
+                a[3].interpolate(&b[3], t),
+            ]
+        };
+
+        DecomposedTransform {
+            translation: lerp3(self.translation, other.translation),
+            scale: lerp3(self.scale, other.scale),
+            skew: lerp3(self.skew, other.skew),
+            perspective: lerp4(self.perspective, other.perspective),
+            quaternion: slerp(self.quaternion, other.quaternion, t),
+        }
+    }
+
+    fn recompose(&self) -> LayoutTransform {
+        let mut matrix: Matrix = [
+            [1.0, 0.0, 0.0, self.perspective[0]],
+            [0.0, 1.0, 0.0, self.perspective[1]],
+            [0.0, 0.0, 1.0, self.perspective[2]],
+            [0.0, 0.0, 0.0, self.perspective[3]],
+        ];
+
+        for i in 0 .. 4 {
+            for j in 0 .. 3 {
+                matrix[3][i] += self.translation[j] * matrix[j][i];
+            }
+        }
+
+        let [x, y, z, w] = self.quaternion;
+        let rotation = [
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + z * w), 2.0 * (x * z - y * w), 0.0],
+            [2.0 * (x * y - z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + x * w), 0.0],
+            [2.0 * (x * z + y * w), 2.0 * (y * z - x * w), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        matrix = multiply(&rotation, &matrix);
+
+        let mut skew_matrix = [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ];
+        if self.skew[2] != 0.0 {
+            skew_matrix[2][1] = self.skew[2];
+            matrix = multiply(&skew_matrix, &matrix);
+        }
+        if self.skew[1] != 0.0 {
+            skew_matrix[2][1] = 0.0;
+            skew_matrix[2][0] = self.skew[1];
+            matrix = multiply(&skew_matrix, &matrix);
+        }
+        if self.skew[0] != 0.0 {
+            skew_matrix[2][0] = 0.0;
+            skew_matrix[1][0] = self.skew[0];
+            matrix = multiply(&skew_matrix, &matrix);
+        }
+
+        for i in 0 .. 3 {
+            for j in 0 .. 4 {
+                matrix[i][j] *= self.scale[i];
+            }
+        }
+
+        LayoutTransform::from_arrays(matrix)
+    }
+}
+
+/// Spherical linear interpolation of two quaternions.
+fn slerp(from: [f32; 4], to: [f32; 4], t: f32) -> [f32; 4] {
+    let mut product = from[0] * to[0] + from[1] * to[1] + from[2] * to[2] + from[3] * to[3];
+    // Take the shortest path between the two rotations.
+    let to = if product < 0.0 {
+        product = -product;
+        [-to[0], -to[1], -to[2], -to[3]]
+    } else {
+        to
+    };
+    let product = product.min(1.0);
+
+    if product == 1.0 {
+        return from;
+    }
+
+    let theta = product.acos();
+    let w = (t * theta).sin() / (1.0 - product * product).sqrt();
+    let scale_from = (t * theta).cos() - product * w;
+
+    [
+        from[0] * scale_from + to[0] * w,
+        from[1] * scale_from + to[1] * w,
+        from[2] * scale_from + to[2] * w,
+        from[3] * scale_from + to[3] * w,
+    ]
+}
+
+#[cfg(test)]
+fn assert_transform_eq(a: &LayoutTransform, b: &LayoutTransform) {
+    let (a, b) = (a.to_array(), b.to_array());
+    for i in 0 .. 16 {
+        assert!((a[i] - b[i]).abs() < 0.001, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn test_timing_functions() {
+    let ease = TimingFunction::EASE;
+    assert_eq!(apply_timing_function(&ease, 0.0, 1000.0), 0.0);
+    assert!((apply_timing_function(&ease, 0.5, 1000.0) - 0.8024).abs() < 0.001);
+    assert!((apply_timing_function(&ease, 1.0, 1000.0) - 1.0).abs() < 0.001);
+
+    let steps = TimingFunction::Steps { count: 4, position: StepPosition::JumpEnd };
+    assert_eq!(apply_timing_function(&steps, 0.3, 1000.0), 0.25);
+    assert_eq!(apply_timing_function(&steps, 1.0, 1000.0), 1.0);
+    let steps = TimingFunction::Steps { count: 4, position: StepPosition::JumpStart };
+    assert_eq!(apply_timing_function(&steps, 0.0, 1000.0), 0.25);
+    let steps = TimingFunction::Steps { count: 3, position: StepPosition::JumpNone };
+    assert_eq!(apply_timing_function(&steps, 0.5, 1000.0), 0.5);
+
+    let spring = TimingFunction::Spring { mass: 1.0, stiffness: 100.0, damping: 10.0, initial_velocity: 0.0 };
+    assert_eq!(apply_timing_function(&spring, 0.0, 1000.0), 0.0);
+    assert!(apply_timing_function(&spring, 0.4, 1000.0) > 1.0, "under-damped springs overshoot");
+    assert_eq!(apply_timing_function(&spring, 1.0, 1000.0), 1.0);
+}
+
+#[test]
+fn test_iterations_and_direction() {
+    use api::PropertyBindingKey;
+
+    let key = PropertyBindingKey::new(1);
+    let property = AnimatedProperty::Float(key, vec![Keyframe::new(0.0, 0.0), Keyframe::new(1.0, 1.0)]);
+    let mut animation = Animation::new(property, 100.0);
+    animation.delay = 50.0;
+    animation.iterations = AnimationIterations::Count(2.0);
+    animation.direction = AnimationDirection::Alternate;
+
+    assert_eq!(iteration_progress(&animation, 0.0), None);
+    assert_eq!(iteration_progress(&animation, 75.0), Some(0.25));
+    assert_eq!(iteration_progress(&animation, 175.0), Some(0.75));
+    assert_eq!(iteration_progress(&animation, 250.0), None);
+
+    animation.fill_mode = AnimationFillMode::Both;
+    assert_eq!(iteration_progress(&animation, 0.0), Some(0.0));
+    assert_eq!(iteration_progress(&animation, 250.0), Some(0.0));
+
+    let mut store = AnimationStore::new();
+    let mut finished = Vec::new();
+    store.add(animation, 0);
+    let properties = store.sample(175_000_000, &mut finished);
+    assert_eq!(properties.floats, vec![key.with(0.75)]);
+    assert!(finished.is_empty());
+    let properties = store.sample(300_000_000, &mut finished);
+    assert_eq!(finished, vec![key.id]);
+    assert_eq!(properties.floats, vec![key.with(0.0)], "animations filling forwards are kept");
+}
+
+#[test]
+fn test_zero_duration() {
+    use api::PropertyBindingKey;
+
+    let key = PropertyBindingKey::new(1);
+    let property = AnimatedProperty::Float(key, vec![Keyframe::new(0.0, 0.0), Keyframe::new(1.0, 1.0)]);
+    let mut animation = Animation::new(property, 0.0);
+    animation.iterations = AnimationIterations::Infinite;
+    animation.fill_mode = AnimationFillMode::Forwards;
+
+    assert_eq!(iteration_progress(&animation, 0.0), Some(1.0));
+    animation.direction = AnimationDirection::Reverse;
+    assert_eq!(iteration_progress(&animation, 0.0), Some(0.0));
+    animation.direction = AnimationDirection::Alternate;
+
+    let mut store = AnimationStore::new();
+    let mut finished = Vec::new();
+    store.add(animation, 0);
+    assert!(store.is_active());
+    let properties = store.sample(10_000_000, &mut finished);
+    assert_eq!(properties.floats, vec![key.with(1.0)]);
+    assert_eq!(finished, vec![key.id]);
+    assert!(!store.is_active(), "finished animations don't need more frames");
+}
+
+#[test]
+fn test_keyframe_interpolation() {
+    let keyframes = [
+        Keyframe::new(0.0, 0.0),
+        Keyframe::new(0.5, 10.0),
+        Keyframe::new(1.0, 0.0),
+    ];
+    assert_eq!(interpolate_keyframes(&keyframes, 0.25), 5.0);
+    assert_eq!(interpolate_keyframes(&keyframes, 0.75), 5.0);
+    assert_eq!(interpolate_keyframes(&keyframes, -0.1), -2.0);
+    assert_eq!(interpolate_keyframes(&keyframes, 1.0), 0.0);
+}
+
+#[test]
+fn test_transform_interpolation() {
+    use euclid::Angle;
+
+    let from = LayoutTransform::translation(10.0, 20.0, 0.0);
+    let to = LayoutTransform::rotation(0.0, 0.0, 1.0, Angle::degrees(90.0))
+        .then_scale(2.0, 2.0, 1.0);
+
+    assert_transform_eq(&from.interpolate(&to, 0.0), &from);
+    assert_transform_eq(&from.interpolate(&to, 1.0), &to);
+
+    // Rotations are interpolated as rotations, not component-wise.
+    let half = LayoutTransform::identity().interpolate(
+        &LayoutTransform::rotation(0.0, 0.0, 1.0, Angle::degrees(90.0)),
+        0.5,
+    );
+    assert_transform_eq(&half, &LayoutTransform::rotation(0.0, 0.0, 1.0, Angle::degrees(45.0)));
+}
@@ -157,7 +157,8 @@ impl ApiResources {
                         r.delete_font_instance(key);
                     }
                 }
-                ResourceUpdate::DeleteImage(..) => {
+                ResourceUpdate::DeleteImage(..) |
+                ResourceUpdate::DeleteSnapshotImage(..) => {
                     transaction.use_scene_builder_thread = true;
                 }
                 _ => {}
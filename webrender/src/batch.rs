@@ -4,8 +4,10 @@
 
 use api::{AlphaType, ClipMode, ImageRendering, ImageBufferKind};
 use api::{FontInstanceFlags, YuvColorSpace, YuvFormat, ColorDepth, ColorRange, PremultipliedColorF};
+use api::RgbColorSpace;
 use api::units::*;
-use crate::clip::{ClipDataStore, ClipNodeFlags, ClipNodeRange, ClipItemKind, ClipStore};
+use crate::clip::{ClipDataStore, ClipNodeFlags, ClipNodeRange, ClipItemKind, ClipPathDataStore, ClipStore};
+use crate::color_space::encode_color_space_conversion;
 use crate::spatial_tree::{SpatialTree, ROOT_SPATIAL_NODE_INDEX, SpatialNodeIndex, CoordinateSystemId};
 use crate::composite::{CompositeState};
 use crate::glyph_rasterizer::{GlyphFormat, SubpixelDirection};
@@ -16,6 +18,7 @@ use crate::gpu_types::{PrimitiveInstanceData, RasterizationSpace, GlyphInstance}
 use crate::gpu_types::{PrimitiveHeader, PrimitiveHeaderIndex, TransformPaletteId, TransformPalette};
 use crate::gpu_types::{ImageBrushData, get_shader_opacity, BoxShadowData};
 use crate::gpu_types::{ClipMaskInstanceCommon, ClipMaskInstanceImage, ClipMaskInstanceRect, ClipMaskInstanceBoxShadow};
+use crate::gpu_types::ClipMaskInstancePath;
 use crate::internal_types::{FastHashMap, Swizzle, TextureSource, Filter};
 use crate::picture::{ClusterFlags, Picture3DContext, PictureCompositeMode, PicturePrimitive, SubSliceIndex};
 use crate::prim_store::{DeferredResolve, PrimitiveInstanceKind, ClipData};
@@ -24,7 +27,7 @@ use crate::prim_store::{BrushSegment, ClipMaskKind, ClipTaskIndex};
 use crate::prim_store::VECS_PER_SEGMENT;
 use crate::render_target::RenderTargetContext;
 use crate::render_task_graph::{RenderTaskId, RenderTaskGraph};
-use crate::render_task::RenderTaskAddress;
+use crate::render_task::{RenderTaskAddress, RenderTaskKind};
 use crate::renderer::{BlendMode, ShaderColorMode};
 use crate::renderer::MAX_VERTEX_TEXTURE_WIDTH;
 use crate::resource_cache::{GlyphFetchResult, ImageProperties, ImageRequest, ResourceCache};
@@ -1304,7 +1307,8 @@ impl BatchBuilder {
                             }
                             GlyphFormat::Alpha |
                             GlyphFormat::TransformedAlpha |
-                            GlyphFormat::Bitmap => {
+                            GlyphFormat::Bitmap |
+                            GlyphFormat::Sdf => {
                                 (
                                     BlendMode::PremultipliedAlpha,
                                     ShaderColorMode::Alpha,
@@ -1386,7 +1390,12 @@ impl BatchBuilder {
                                 for glyph in glyphs {
                                     let glyph_offset = prim_data.glyphs[glyph.index_in_text_run as usize].point + prim_header.local_rect.min.to_vector();
                                     let glyph_scale = LayoutToDeviceScale::new(glyph_raster_scale / glyph.scale);
-                                    let raster_glyph_offset = (glyph_offset * LayoutToDeviceScale::new(glyph_raster_scale) + snap_bias).floor() / glyph.scale;
+                                    let mut raster_glyph_offset = glyph_offset * LayoutToDeviceScale::new(glyph_raster_scale);
+                                    // Distance field glyphs aren't snapped.
+                                    if glyph_format != GlyphFormat::Sdf {
+                                        raster_glyph_offset = (raster_glyph_offset + snap_bias).floor();
+                                    }
+                                    let raster_glyph_offset = raster_glyph_offset / glyph.scale;
                                     let local_glyph_rect = LayoutRect::from_origin_and_size(
                                         (glyph.offset + raster_glyph_offset.to_vector()) / glyph_scale + text_offset,
                                         glyph.size.to_f32() / glyph_scale,
@@ -2424,12 +2433,17 @@ impl BatchBuilder {
                 let image_data = &ctx.data_stores.image[data_handle].kind;
                 let common_data = &ctx.data_stores.image[data_handle].common;
                 let image_instance = &ctx.prim_store.images[image_instance_index];
+                let color_space = ctx.resource_cache
+                    .get_image_properties(image_data.key)
+                    .map_or(RgbColorSpace::Srgb, |properties| properties.descriptor.color_space);
                 let prim_user_data = ImageBrushData {
                     color_mode: ShaderColorMode::Image,
                     alpha_type: image_data.alpha_type,
                     raster_space: RasterizationSpace::Local,
                     opacity: 1.0,
-                }.encode();
+                }.encode_with_color_space_conversion(
+                    encode_color_space_conversion(color_space, ctx.output_color_space),
+                );
 
                 let blend_mode = if !common_data.opacity.is_opaque ||
                     prim_info.clip_task_index != ClipTaskIndex::INVALID ||
@@ -3016,6 +3030,69 @@ impl BatchBuilder {
                     }
                 }
             }
+            PrimitiveInstanceKind::Path { data_handle, .. } => {
+                let prim_data = &ctx.data_stores.path[data_handle];
+                let common_data = &prim_data.common;
+
+                let (uv_rect_address, texture_source) = match prim_data.src_color {
+                    Some(task_id) => render_tasks.resolve_location(task_id, gpu_cache).unwrap(),
+                    None => {
+                        return;
+                    }
+                };
+
+                let (clip_task_address, clip_mask_texture_id) = ctx.get_prim_clip_task_and_texture(
+                    prim_info.clip_task_index,
+                    render_tasks,
+                ).unwrap();
+
+                let textures = BatchTextures::prim_textured(
+                    texture_source,
+                    clip_mask_texture_id,
+                );
+
+                let prim_header = PrimitiveHeader {
+                    local_rect: prim_rect,
+                    local_clip_rect: prim_info.combined_local_clip_rect,
+                    specific_prim_address: gpu_cache.get_address(&common_data.gpu_cache_handle),
+                    transform_id,
+                };
+
+                // Solid paths are cached as an alpha mask, and tinted by the color
+                // stored in the primitive's GPU blocks.
+                let prim_user_data = ImageBrushData {
+                    color_mode: ShaderColorMode::Image,
+                    alpha_type: AlphaType::PremultipliedAlpha,
+                    raster_space: RasterizationSpace::Local,
+                    opacity: 1.0,
+                }.encode();
+
+                let prim_header_index = prim_headers.push(
+                    &prim_header,
+                    z_id,
+                    prim_user_data,
+                );
+
+                let batch_key = BatchKey {
+                    blend_mode: BlendMode::PremultipliedAlpha,
+                    kind: BatchKind::Brush(BrushBatchKind::Image(texture_source.image_buffer_kind())),
+                    textures,
+                };
+
+                self.add_brush_instance_to_batches(
+                    batch_key,
+                    batch_features,
+                    bounding_rect,
+                    z_id,
+                    INVALID_SEGMENT_INDEX,
+                    EdgeAaSegmentMask::all(),
+                    clip_task_address,
+                    BrushFlags::PERSPECTIVE_INTERPOLATION,
+                    prim_header_index,
+                    uv_rect_address.as_int(),
+                    &batch_filter,
+                );
+            }
             PrimitiveInstanceKind::Backdrop { data_handle } => {
                 let prim_data = &ctx.data_stores.backdrop[data_handle];
                 let backdrop_pic_index = prim_data.kind.pic_index;
@@ -3312,6 +3389,8 @@ pub struct ClipBatchList {
     /// Image draws apply the image masking.
     pub images: FastHashMap<(TextureSource, Option<DeviceIntRect>), Vec<ClipMaskInstanceImage>>,
     pub box_shadows: FastHashMap<TextureSource, Vec<ClipMaskInstanceBoxShadow>>,
+    /// Path draws rasterize the coverage of the path.
+    pub paths: Vec<ClipMaskInstancePath>,
 }
 
 impl ClipBatchList {
@@ -3321,6 +3400,7 @@ impl ClipBatchList {
             fast_rectangles: Vec::new(),
             images: FastHashMap::default(),
             box_shadows: FastHashMap::default(),
+            paths: Vec::new(),
         }
     }
 }
@@ -3480,6 +3560,66 @@ impl ClipBatcher {
         }
     }
 
+    /// Add an instance that samples a mask image, or a tile of it, from a
+    /// texture, as image masks and text clips do.
+    fn add_image_mask(
+        &mut self,
+        texture: TextureSource,
+        resource_address: GpuCacheAddress,
+        rect: LayoutRect,
+        tile_rect: LayoutRect,
+        sub_rect: DeviceRect,
+        map_local_to_raster: &SpaceMapper<LayoutPixel, WorldPixel>,
+        common: &ClipMaskInstanceCommon,
+        actual_rect: DeviceRect,
+        surface_device_pixel_scale: DevicePixelScale,
+        is_first_clip: bool,
+    ) {
+        // If the clip transform is axis-aligned, we can skip any need for scissoring
+        // by clipping the local clip rect with the backwards transformed target bounds.
+        // If it is not axis-aligned, then we pass the local clip rect through unmodified
+        // to the shader and also set up a scissor rect for the overall target bounds to
+        // ensure nothing is drawn outside the target. If for some reason we can't map the
+        // rect back to local space, we also fall back to just using a scissor rectangle.
+        let raster_rect =
+            sub_rect.translate(actual_rect.min.to_vector()) / surface_device_pixel_scale;
+        let (clip_transform_id, local_rect, scissor) = match map_local_to_raster.unmap(&raster_rect) {
+            Some(local_rect)
+                if common.clip_transform_id.transform_kind() == TransformedRectKind::AxisAligned &&
+                   !map_local_to_raster.get_transform().has_perspective_component() => {
+                    match local_rect.intersection(&rect) {
+                        Some(local_rect) => (common.clip_transform_id, local_rect, None),
+                        None => return,
+                    }
+            }
+            _ => {
+                // If for some reason inverting the transform failed, then don't consider
+                // the transform to be axis-aligned if it was.
+                (common.clip_transform_id.override_transform_kind(TransformedRectKind::Complex),
+                 rect,
+                 Some(common.sub_rect
+                    .translate(common.task_origin.to_vector())
+                    .round_out()
+                    .to_i32()))
+            }
+        };
+
+        self.get_batch_list(is_first_clip)
+            .images
+            .entry((texture, scissor))
+            .or_insert_with(Vec::new)
+            .push(ClipMaskInstanceImage {
+                common: ClipMaskInstanceCommon {
+                    sub_rect,
+                    clip_transform_id,
+                    ..*common
+                },
+                resource_address,
+                tile_rect,
+                local_rect,
+            });
+    }
+
     pub fn add(
         &mut self,
         clip_node_range: ClipNodeRange,
@@ -3491,6 +3631,7 @@ impl ClipBatcher {
         spatial_tree: &SpatialTree,
         transforms: &mut TransformPalette,
         clip_data_store: &ClipDataStore,
+        clip_path_store: &ClipPathDataStore,
         actual_rect: DeviceRect,
         world_rect: &WorldRect,
         surface_device_pixel_scale: DevicePixelScale,
@@ -3516,7 +3657,7 @@ impl ClipBatcher {
             // the local image bounds, rather than backwards transform the target bounds
             // as in done in write_clip_tile_vertex.
             let prim_transform_id = match clip_node.item.kind {
-                ClipItemKind::Image { .. } => {
+                ClipItemKind::Image { .. } | ClipItemKind::Text { .. } => {
                     transforms.get_id(
                         clip_instance.spatial_node_index,
                         root_spatial_node_index,
@@ -3566,49 +3707,18 @@ impl ClipBatcher {
                             }
                         };
 
-                        // If the clip transform is axis-aligned, we can skip any need for scissoring
-                        // by clipping the local clip rect with the backwards transformed target bounds.
-                        // If it is not axis-aligned, then we pass the local clip rect through unmodified
-                        // to the shader and also set up a scissor rect for the overall target bounds to
-                        // ensure nothing is drawn outside the target. If for some reason we can't map the
-                        // rect back to local space, we also fall back to just using a scissor rectangle.
-                        let raster_rect =
-                            sub_rect.translate(actual_rect.min.to_vector()) / surface_device_pixel_scale;
-                        let (clip_transform_id, local_rect, scissor) = match map_local_to_raster.unmap(&raster_rect) {
-                            Some(local_rect)
-                                if clip_transform_id.transform_kind() == TransformedRectKind::AxisAligned &&
-                                   !map_local_to_raster.get_transform().has_perspective_component() => {
-                                    match local_rect.intersection(&rect) {
-                                        Some(local_rect) => (clip_transform_id, local_rect, None),
-                                        None => return,
-                                    }
-                            }
-                            _ => {
-                                // If for some reason inverting the transform failed, then don't consider
-                                // the transform to be axis-aligned if it was.
-                                (clip_transform_id.override_transform_kind(TransformedRectKind::Complex),
-                                 rect,
-                                 Some(common.sub_rect
-                                    .translate(task_origin.to_vector())
-                                    .round_out()
-                                    .to_i32()))
-                            }
-                        };
-
-                        self.get_batch_list(is_first_clip)
-                            .images
-                            .entry((cache_item.texture_id, scissor))
-                            .or_insert_with(Vec::new)
-                            .push(ClipMaskInstanceImage {
-                                common: ClipMaskInstanceCommon {
-                                    sub_rect,
-                                    clip_transform_id,
-                                    ..common
-                                },
-                                resource_address: gpu_cache.get_address(&cache_item.uv_rect_handle),
-                                tile_rect,
-                                local_rect,
-                            });
+                        self.add_image_mask(
+                            cache_item.texture_id,
+                            gpu_cache.get_address(&cache_item.uv_rect_handle),
+                            rect,
+                            tile_rect,
+                            sub_rect,
+                            &map_local_to_raster,
+                            &common,
+                            actual_rect,
+                            surface_device_pixel_scale,
+                            is_first_clip,
+                        );
                     };
 
                     let clip_spatial_node = &spatial_tree.spatial_nodes[clip_instance.spatial_node_index.0 as usize];
@@ -3731,6 +3841,77 @@ impl ClipBatcher {
                         batch_list.slow_rectangles.push(instance);
                     }
 
+                    true
+                }
+                ClipItemKind::Text { rect, ref source, .. } => {
+                    let task_id = source
+                        .as_ref()
+                        .and_then(|source| source.render_task)
+                        .expect("bug: render task handle not allocated");
+                    let (uv_rect_address, texture) = render_tasks.resolve_location(task_id, gpu_cache).unwrap();
+                    let mask_scale = match render_tasks[task_id].kind {
+                        RenderTaskKind::TextMask(ref task_info) => task_info.scale,
+                        _ => unreachable!("bug: not a text mask"),
+                    };
+
+                    // The text mask is rounded up to whole device pixels, so it
+                    // can cover a little more than the clip rect.
+                    let mask_rect = LayoutRect::from_origin_and_size(
+                        rect.min,
+                        render_tasks[task_id].get_target_rect().size().to_f32() / LayoutToDeviceScale::new(mask_scale),
+                    );
+
+                    let map_local_to_raster = SpaceMapper::new_with_target(
+                        root_spatial_node_index,
+                        clip_instance.spatial_node_index,
+                        WorldRect::max_rect(),
+                        spatial_tree,
+                    );
+
+                    self.add_image_mask(
+                        texture,
+                        uv_rect_address,
+                        rect,
+                        mask_rect,
+                        common.sub_rect,
+                        &map_local_to_raster,
+                        &common,
+                        actual_rect,
+                        surface_device_pixel_scale,
+                        is_first_clip,
+                    );
+
+                    // Like image masks, make sure that pixels outside of the text
+                    // clip rect are initialized if this is the first clip.
+                    let clip_spatial_node = &spatial_tree.spatial_nodes[clip_instance.spatial_node_index.0 as usize];
+                    let clip_is_axis_aligned = clip_spatial_node.coordinate_system_id == CoordinateSystemId::root();
+                    if is_first_clip &&
+                        (!clip_is_axis_aligned ||
+                         !(map_local_to_raster.map(&rect).expect("bug: should always map as axis-aligned")
+                            * surface_device_pixel_scale).contains_box(&actual_rect)) {
+                        clear_to_one = true;
+                    }
+                    true
+                }
+                ClipItemKind::Path { rect, handle } => {
+                    // A path without edges clips everything out, and has
+                    // nothing to fetch from the GPU cache.
+                    let path = &clip_path_store[handle];
+                    let edges_address = if path.edges.is_empty() {
+                        0
+                    } else {
+                        gpu_cache.get_address(&path.edges_handle).as_int()
+                    };
+                    self.get_batch_list(is_first_clip)
+                        .paths
+                        .push(ClipMaskInstancePath {
+                            common,
+                            local_rect: rect,
+                            edges_address,
+                            edge_count: path.edges.len() as i32,
+                            fill_rule: path.fill_rule as i32,
+                        });
+
                     true
                 }
             };
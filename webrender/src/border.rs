@@ -222,6 +222,11 @@ impl<'a> SceneBuilder<'a> {
         let mut border = *border;
         ensure_no_corner_overlap(&mut border.radius, info.rect.size());
 
+        border.left.color = self.output_color(border.left.color);
+        border.right.color = self.output_color(border.right.color);
+        border.top.color = self.output_color(border.top.color);
+        border.bottom.color = self.output_color(border.bottom.color);
+
         self.add_primitive(
             spatial_node_index,
             clip_chain_id,
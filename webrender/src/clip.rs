@@ -93,20 +93,24 @@
 //!
 
 use api::{BorderRadius, ClipMode, ComplexClipRegion, ImageMask};
-use api::{BoxShadowClipMode, ClipId, FillRule, ImageKey, ImageRendering, PipelineId};
+use api::{BoxShadowClipMode, ClipId, FillRule, FontInstanceFlags, GlyphIndex, ImageKey, ImageRendering, PipelineId};
 use api::units::*;
 use crate::image_tiling::{self, Repetition};
 use crate::border::{ensure_no_corner_overlap, BorderRadiusAu};
 use crate::box_shadow::{BLUR_SAMPLE_SCALE, BoxShadowClipSource, BoxShadowCacheKey};
 use crate::spatial_tree::{ROOT_SPATIAL_NODE_INDEX, SpatialTree, SpatialNodeIndex, CoordinateSystemId};
 use crate::ellipse::Ellipse;
-use crate::gpu_cache::GpuCache;
+use crate::gpu_cache::{GpuCache, GpuCacheHandle};
+use crate::glyph_rasterizer::{FontInstance, FontTransform, GlyphKey, SubpixelDirection, FONT_SIZE_LIMIT};
 use crate::gpu_types::{BoxShadowStretchMode};
 use crate::intern::{self, ItemUid};
 use crate::internal_types::{FastHashMap, FastHashSet};
+use crate::path::{self, PathEdge};
+use crate::prim_store::path::PathCommandKey;
 use crate::prim_store::{VisibleMaskImageTile};
 use crate::prim_store::{PointKey, SizeKey, RectangleKey, PolygonKey};
 use crate::render_task_cache::to_cache_size;
+use crate::render_task_graph::RenderTaskId;
 use crate::resource_cache::{ImageRequest, ResourceCache};
 use crate::space::SpaceMapper;
 use crate::util::{clamp_to_scale_factor, MaxRect, extract_inner_rect_safe, project_rect, ScaleOffset, VecHelper};
@@ -427,6 +431,19 @@ impl From<ClipItemKey> for ClipNode {
                     clip_mode,
                 )
             }
+            ClipItemKeyKind::Path(rect, handle) => {
+                ClipItemKind::Path {
+                    rect: rect.into(),
+                    handle,
+                }
+            }
+            ClipItemKeyKind::Text(rect, handle) => {
+                ClipItemKind::Text {
+                    rect: rect.into(),
+                    handle,
+                    source: None,
+                }
+            }
         };
 
         ClipNode {
@@ -602,12 +619,15 @@ struct ClipNodeInfo {
 impl ClipNodeInfo {
     fn create_instance(
         &self,
-        node: &ClipNode,
+        node: &mut ClipNode,
         clipped_rect: &LayoutRect,
         gpu_cache: &mut GpuCache,
         resource_cache: &mut ResourceCache,
+        clip_path_store: &mut ClipPathDataStore,
+        clip_text_store: &ClipTextDataStore,
         mask_tiles: &mut Vec<VisibleMaskImageTile>,
         spatial_tree: &SpatialTree,
+        device_pixel_scale: DevicePixelScale,
         request_resources: bool,
     ) -> Option<ClipNodeInstance> {
         // Calculate some flags that are required for the segment
@@ -691,6 +711,36 @@ impl ClipNodeInfo {
             }
         }
 
+        if let ClipItemKind::Path { handle, .. } = node.item.kind {
+            clip_path_store[handle].update(gpu_cache);
+        }
+
+        if let ClipItemKind::Text { rect, handle, ref mut source } = node.item.kind {
+            let text = &clip_text_store[handle];
+            let (scale_x, scale_y) = spatial_tree
+                .get_relative_transform(self.spatial_node_index, ROOT_SPATIAL_NODE_INDEX)
+                .scale_factors();
+            let scale = text.raster_scale(&rect, device_pixel_scale.0 * scale_x.max(scale_y));
+            if source.as_ref().map_or(true, |source| source.scale != scale) {
+                // Keep the text mask of an earlier instance of the clip this
+                // frame, which has its own copy of the font it was drawn with.
+                let render_task = source.as_ref().and_then(|source| source.render_task);
+                *source = Some(TextClipSource {
+                    render_task,
+                    ..TextClipSource::new(text, scale)
+                });
+            }
+
+            // The glyphs are requested regardless of request_resources, since
+            // the scale may differ between the clip chain instances of a frame.
+            let source = source.as_ref().unwrap();
+            resource_cache.request_glyphs(
+                source.font.clone(),
+                &source.glyph_keys,
+                gpu_cache,
+            );
+        }
+
         Some(ClipNodeInstance {
             handle: self.handle,
             flags,
@@ -708,7 +758,9 @@ impl ClipNode {
         match self.item.kind {
             ClipItemKind::Image { .. } |
             ClipItemKind::Rectangle { .. } |
-            ClipItemKind::RoundedRectangle { .. } => {}
+            ClipItemKind::RoundedRectangle { .. } |
+            ClipItemKind::Path { .. } |
+            ClipItemKind::Text { .. } => {}
 
             ClipItemKind::BoxShadow { ref mut source } => {
                 // Quote from https://drafts.csswg.org/css-backgrounds-3/#shadow-blur
@@ -1243,6 +1295,8 @@ impl ClipStore {
         device_pixel_scale: DevicePixelScale,
         world_rect: &WorldRect,
         clip_data_store: &mut ClipDataStore,
+        clip_path_store: &mut ClipPathDataStore,
+        clip_text_store: &ClipTextDataStore,
         request_resources: bool,
         is_chased: bool,
     ) -> Option<ClipChainInstance> {
@@ -1316,8 +1370,11 @@ impl ClipStore {
                         &local_bounding_rect,
                         gpu_cache,
                         resource_cache,
+                        clip_path_store,
+                        clip_text_store,
                         &mut self.mask_tiles,
                         spatial_tree,
+                        device_pixel_scale,
                         request_resources,
                     ) {
                         // As a special case, a partial accept of a clip rect that is
@@ -1330,7 +1387,9 @@ impl ClipStore {
                             ClipItemKind::Rectangle { mode: ClipMode::ClipOut, .. } |
                             ClipItemKind::RoundedRectangle { .. } |
                             ClipItemKind::Image { .. } |
-                            ClipItemKind::BoxShadow { .. } => {
+                            ClipItemKind::BoxShadow { .. } |
+                            ClipItemKind::Path { .. } |
+                            ClipItemKind::Text { .. } => {
                                 true
                             }
 
@@ -1426,6 +1485,8 @@ pub enum ClipItemKeyKind {
     RoundedRectangle(RectangleKey, BorderRadiusAu, ClipMode),
     ImageMask(RectangleKey, ImageKey, bool, Option<PolygonDataHandle>),
     BoxShadow(PointKey, SizeKey, BorderRadiusAu, RectangleKey, Au, BoxShadowClipMode),
+    Path(RectangleKey, ClipPathDataHandle),
+    Text(RectangleKey, ClipTextDataHandle),
 }
 
 impl ClipItemKeyKind {
@@ -1480,6 +1541,14 @@ impl ClipItemKeyKind {
         )
     }
 
+    pub fn path(rect: LayoutRect, handle: ClipPathDataHandle) -> Self {
+        ClipItemKeyKind::Path(rect.into(), handle)
+    }
+
+    pub fn text(rect: LayoutRect, handle: ClipTextDataHandle) -> Self {
+        ClipItemKeyKind::Text(rect.into(), handle)
+    }
+
     pub fn node_kind(&self) -> ClipNodeKind {
         match *self {
             ClipItemKeyKind::Rectangle(_, ClipMode::Clip) => ClipNodeKind::Rectangle,
@@ -1487,7 +1556,9 @@ impl ClipItemKeyKind {
             ClipItemKeyKind::Rectangle(_, ClipMode::ClipOut) |
             ClipItemKeyKind::RoundedRectangle(..) |
             ClipItemKeyKind::ImageMask(..) |
-            ClipItemKeyKind::BoxShadow(..) => ClipNodeKind::Complex,
+            ClipItemKeyKind::BoxShadow(..) |
+            ClipItemKeyKind::Path(..) |
+            ClipItemKeyKind::Text(..) => ClipNodeKind::Complex,
         }
     }
 }
@@ -1539,6 +1610,15 @@ pub enum ClipItemKind {
     BoxShadow {
         source: BoxShadowClipSource,
     },
+    Path {
+        rect: LayoutRect,
+        handle: ClipPathDataHandle,
+    },
+    Text {
+        rect: LayoutRect,
+        handle: ClipTextDataHandle,
+        source: Option<TextClipSource>,
+    },
 }
 
 #[derive(Debug, MallocSizeOf)]
@@ -1711,7 +1791,9 @@ impl ClipItemKind {
         match *self {
             ClipItemKind::Rectangle { .. } |
             ClipItemKind::Image { .. } |
-            ClipItemKind::BoxShadow { .. } => {
+            ClipItemKind::BoxShadow { .. } |
+            ClipItemKind::Path { .. } |
+            ClipItemKind::Text { .. } => {
                 false
             }
             ClipItemKind::RoundedRectangle { ref radius, .. } => {
@@ -1740,6 +1822,8 @@ impl ClipItemKind {
                 }
             }
             ClipItemKind::BoxShadow { .. } => None,
+            ClipItemKind::Path { rect, .. } |
+            ClipItemKind::Text { rect, .. } => Some(rect),
         }
     }
 
@@ -1762,7 +1846,9 @@ impl ClipItemKind {
                 let inner_clip_rect = extract_inner_rect_safe(&rect, radius);
                 (rect, inner_clip_rect, mode)
             }
-            ClipItemKind::Image { rect, repeat: false, .. } => {
+            ClipItemKind::Image { rect, repeat: false, .. } |
+            ClipItemKind::Path { rect, .. } |
+            ClipItemKind::Text { rect, .. } => {
                 (rect, None, ClipMode::Clip)
             }
             ClipItemKind::Image { repeat: true, .. } |
@@ -1887,6 +1973,17 @@ impl ClipItemKind {
             ClipItemKind::BoxShadow { .. } => {
                 ClipResult::Partial
             }
+            ClipItemKind::Path { rect, .. } |
+            ClipItemKind::Text { rect, .. } => {
+                match rect.intersection(prim_rect) {
+                    Some(..) => {
+                        ClipResult::Partial
+                    }
+                    None => {
+                        ClipResult::Reject
+                    }
+                }
+            }
         }
     }
 }
@@ -2043,6 +2140,25 @@ pub fn polygon_contains_point(
     }
 }
 
+pub fn path_contains_point(
+    point: &LayoutPoint,
+    rect: &LayoutRect,
+    fill_rule: FillRule,
+    edges: &[PathEdge],
+) -> bool {
+    if !rect.contains(*point) {
+        return false;
+    }
+
+    // The edges are relative to the origin of the clip rect.
+    let winding_number = path::winding_number(edges, *point - rect.min.to_vector());
+
+    match fill_rule {
+        FillRule::Nonzero => winding_number != 0,
+        FillRule::Evenodd => winding_number.abs() % 2 == 1,
+    }
+}
+
 pub fn projected_rect_contains(
     source_rect: &LayoutRect,
     transform: &LayoutToWorldTransform,
@@ -2181,6 +2297,40 @@ mod tests {
             "Empty rectangle is considered to include a non-empty!"
         );
     }
+
+    #[test]
+    fn test_path_contains_point() {
+        use api::{FillRule, PathCommand};
+        use api::units::{LayoutPoint, LayoutRect, LayoutVector2D};
+        use crate::prim_store::path::PathCommandKey;
+        use super::{path_contains_point, ClipPathKey};
+
+        // Two nested squares, relative to the origin of the clip rect.
+        let commands = [
+            (0.0, 0.0, 100.0, 100.0),
+            (25.0, 25.0, 75.0, 75.0),
+        ].iter().flat_map(|&(x0, y0, x1, y1)| vec![
+            PathCommand::MoveTo(LayoutPoint::new(x0, y0)),
+            PathCommand::LineTo(LayoutPoint::new(x1, y0)),
+            PathCommand::LineTo(LayoutPoint::new(x1, y1)),
+            PathCommand::LineTo(LayoutPoint::new(x0, y1)),
+            PathCommand::Close,
+        ]).map(|command| {
+            PathCommandKey::new(command, LayoutVector2D::zero())
+        }).collect::<Vec<_>>();
+
+        let rect = LayoutRect::new(LayoutPoint::new(10.0, 10.0), LayoutPoint::new(110.0, 110.0));
+        let inner = LayoutPoint::new(60.0, 60.0);
+        let outer = LayoutPoint::new(20.0, 20.0);
+        let outside = LayoutPoint::new(5.0, 60.0);
+
+        for &(fill_rule, inner_hit) in &[(FillRule::Nonzero, true), (FillRule::Evenodd, false)] {
+            let edges = ClipPathKey { commands: commands.clone(), fill_rule }.build_edges();
+            assert_eq!(path_contains_point(&inner, &rect, fill_rule, &edges), inner_hit);
+            assert!(path_contains_point(&outer, &rect, fill_rule, &edges));
+            assert!(!path_contains_point(&outside, &rect, fill_rule, &edges));
+        }
+    }
 }
 
 /// PolygonKeys get interned, because it's a convenient way to move the data
@@ -2203,3 +2353,223 @@ impl intern::Internable for PolygonIntern {
     type InternData = PolygonKey;
     const PROFILE_COUNTER: usize = crate::profiler::INTERNED_POLYGONS;
 }
+
+/// Identifying key for a clip path. The commands are relative to the origin
+/// of the clip rect.
+#[derive(Clone, Debug, Hash, MallocSizeOf, PartialEq, Eq)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct ClipPathKey {
+    pub commands: Vec<PathCommandKey>,
+    pub fill_rule: FillRule,
+}
+
+impl ClipPathKey {
+    /// Flatten the path into edges, relative to the origin of the clip rect.
+    pub fn build_edges(&self) -> Vec<PathEdge> {
+        path::fit_edges(|tolerance| {
+            let polylines = path::flatten_path(
+                self.commands.iter().map(|command| (*command).into()),
+                tolerance,
+            );
+            let mut edges = Vec::new();
+            path::fill_edges(&polylines, &mut edges);
+            edges
+        })
+    }
+}
+
+impl intern::InternDebug for ClipPathKey {}
+
+/// The flattened edges of a clip path, uploaded to the GPU cache by the
+/// frame builder to rasterize the clip mask.
+#[derive(Debug, MallocSizeOf)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct ClipPathTemplate {
+    pub fill_rule: FillRule,
+    pub edges: Vec<PathEdge>,
+    pub edges_handle: GpuCacheHandle,
+}
+
+impl From<ClipPathKey> for ClipPathTemplate {
+    fn from(key: ClipPathKey) -> Self {
+        ClipPathTemplate {
+            fill_rule: key.fill_rule,
+            edges: key.build_edges(),
+            edges_handle: GpuCacheHandle::new(),
+        }
+    }
+}
+
+impl ClipPathTemplate {
+    /// Upload the edges to the GPU cache, if they are not already there.
+    pub fn update(&mut self, gpu_cache: &mut GpuCache) {
+        if self.edges.is_empty() {
+            return;
+        }
+
+        if let Some(mut request) = gpu_cache.request(&mut self.edges_handle) {
+            for edge in &self.edges {
+                request.push([edge.from.x, edge.from.y, edge.to.x, edge.to.y]);
+            }
+        }
+    }
+}
+
+/// The part of a clip path the scene builder keeps for hit testing.
+#[derive(Debug, MallocSizeOf)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct ClipPathInternData {
+    pub fill_rule: FillRule,
+    pub edges: Vec<PathEdge>,
+}
+
+impl From<&ClipPathKey> for ClipPathInternData {
+    fn from(key: &ClipPathKey) -> Self {
+        ClipPathInternData {
+            fill_rule: key.fill_rule,
+            edges: key.build_edges(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash, MallocSizeOf, PartialEq, Eq)]
+#[cfg_attr(any(feature = "serde"), derive(Deserialize, Serialize))]
+pub enum ClipPathIntern {}
+
+pub type ClipPathDataStore = intern::DataStore<ClipPathIntern>;
+pub type ClipPathDataHandle = intern::Handle<ClipPathIntern>;
+
+impl intern::Internable for ClipPathIntern {
+    type Key = ClipPathKey;
+    type StoreData = ClipPathTemplate;
+    type InternData = ClipPathInternData;
+    const PROFILE_COUNTER: usize = crate::profiler::INTERNED_CLIP_PATHS;
+}
+
+/// A glyph of a text clip, relative to the origin of the clip rect.
+#[derive(Clone, Copy, Debug, Hash, MallocSizeOf, PartialEq, Eq)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct ClipGlyphKey {
+    pub index: GlyphIndex,
+    pub point: PointKey,
+}
+
+/// Identifying key for the glyphs of a text clip.
+#[derive(Clone, Debug, Hash, MallocSizeOf, PartialEq, Eq)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct ClipTextKey {
+    pub font: FontInstance,
+    pub glyphs: Vec<ClipGlyphKey>,
+}
+
+impl intern::InternDebug for ClipTextKey {}
+
+/// The font and glyphs of a text clip. Every frame, the glyphs are drawn
+/// into a text mask at the scale the clip is rendered at, see
+/// `TextClipSource`, which the clip mask then samples.
+#[derive(Debug, MallocSizeOf)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct ClipTextTemplate {
+    pub font: FontInstance,
+    pub glyphs: Vec<ClipGlyphKey>,
+}
+
+impl From<ClipTextKey> for ClipTextTemplate {
+    fn from(key: ClipTextKey) -> Self {
+        ClipTextTemplate {
+            font: key.font,
+            glyphs: key.glyphs,
+        }
+    }
+}
+
+/// The largest size of a text mask, in device pixels.
+const MAX_TEXT_MASK_SIZE: f32 = 4096.0;
+
+impl ClipTextTemplate {
+    /// Get the scale to rasterize the glyphs at, given the scale from the
+    /// local space of the clip to device pixels. Glyphs or text masks that
+    /// would be too large use a lower scale, and are stretched by the clip
+    /// mask instead.
+    fn raster_scale(&self, rect: &LayoutRect, scale: f32) -> f32 {
+        // Round like text runs do, so that small floating point errors don't
+        // cause the glyphs to be rasterized again.
+        let scale = (scale * 100.0).round() / 100.0;
+        scale
+            .min(FONT_SIZE_LIMIT / self.font.size.to_f32_px())
+            .min(MAX_TEXT_MASK_SIZE / rect.width().max(rect.height()))
+            .max(0.01)
+    }
+}
+
+/// The glyphs of a text clip, as they are rasterized for the current frame.
+#[derive(Debug, MallocSizeOf)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct TextClipSource {
+    /// The number of device pixels per local pixel of the glyphs, and of
+    /// the text mask they are drawn into.
+    pub scale: f32,
+    /// The font the glyphs are rasterized with, in grayscale and without
+    /// a transform.
+    pub font: FontInstance,
+    #[ignore_malloc_size_of = "GlyphKey is a plain u32"]
+    pub glyph_keys: Vec<GlyphKey>,
+    /// The most recent text mask of the clip, which the clip masks sample.
+    pub render_task: Option<RenderTaskId>,
+}
+
+impl TextClipSource {
+    fn new(text: &ClipTextTemplate, scale: f32) -> Self {
+        let mut font = FontInstance {
+            transform: FontTransform::identity(),
+            size: (text.font.size.to_f32_px() * scale).into(),
+            ..text.font.clone()
+        };
+        // Masks only need the coverage of the glyphs, and the glyphs are
+        // snapped to whole pixels of the text mask.
+        font.disable_subpixel_aa();
+        font.disable_subpixel_position();
+        font.flags.remove(FontInstanceFlags::DISTANCE_FIELD);
+
+        let glyph_keys = text.glyphs
+            .iter()
+            .map(|glyph| GlyphKey::new(glyph.index, DevicePoint::zero(), SubpixelDirection::None))
+            .collect();
+
+        TextClipSource {
+            scale,
+            font,
+            glyph_keys,
+            render_task: None,
+        }
+    }
+
+    /// The size of the text mask for a clip rect.
+    pub fn task_size(&self, rect: &LayoutRect) -> DeviceIntSize {
+        (rect.size() * LayoutToDeviceScale::new(self.scale))
+            .ceil()
+            .to_i32()
+            .max(DeviceIntSize::new(1, 1))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Hash, MallocSizeOf, PartialEq, Eq)]
+#[cfg_attr(any(feature = "serde"), derive(Deserialize, Serialize))]
+pub enum ClipTextIntern {}
+
+pub type ClipTextDataStore = intern::DataStore<ClipTextIntern>;
+pub type ClipTextDataHandle = intern::Handle<ClipTextIntern>;
+
+impl intern::Internable for ClipTextIntern {
+    type Key = ClipTextKey;
+    type StoreData = ClipTextTemplate;
+    type InternData = ();
+    const PROFILE_COUNTER: usize = crate::profiler::INTERNED_CLIP_TEXTS;
+}
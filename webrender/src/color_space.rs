@@ -0,0 +1,245 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Conversions between RGB color spaces.
+//!
+//! Colors are decoded to linear light, converted to the destination gamut
+//! through CIE XYZ, and encoded with the transfer function of the destination.
+//! All supported color spaces share the D65 white point, so no chromatic
+//! adaptation is needed.
+//!
+//! Constant colors are converted on the CPU while building the scene. Images
+//! are converted in the shaders, see color_space.glsl.
+
+use api::{ColorF, RgbColorSpace};
+
+type Matrix = [[f32; 3]; 3];
+
+const SRGB_TO_XYZ: Matrix = [
+    [0.4123908, 0.3575843, 0.1804808],
+    [0.2126390, 0.7151687, 0.0721923],
+    [0.0193308, 0.1191948, 0.9505322],
+];
+
+const XYZ_TO_SRGB: Matrix = [
+    [3.2409697, -1.5373828, -0.4986108],
+    [-0.9692435, 1.8759673, 0.0415551],
+    [0.0556302, -0.2039770, 1.0569714],
+];
+
+const DISPLAY_P3_TO_XYZ: Matrix = [
+    [0.4865709, 0.2656677, 0.1982173],
+    [0.2289746, 0.6917385, 0.0792869],
+    [0.0000000, 0.0451134, 1.0439444],
+];
+
+const XYZ_TO_DISPLAY_P3: Matrix = [
+    [2.4934973, -0.9313838, -0.4027109],
+    [-0.8294893, 1.7626642, 0.0236248],
+    [0.0358459, -0.0761724, 0.9568845],
+];
+
+const ADOBE_RGB_TO_XYZ: Matrix = [
+    [0.5766690, 0.1855582, 0.1882286],
+    [0.2973450, 0.6273636, 0.0752915],
+    [0.0270314, 0.0706889, 0.9913375],
+];
+
+const XYZ_TO_ADOBE_RGB: Matrix = [
+    [2.0415880, -0.5650068, -0.3447313],
+    [-0.9692437, 1.8759674, 0.0415550],
+    [0.0134443, -0.1183625, 1.0151750],
+];
+
+const REC2020_TO_XYZ: Matrix = [
+    [0.6369580, 0.1446169, 0.1688810],
+    [0.2627002, 0.6779981, 0.0593017],
+    [0.0000000, 0.0280727, 1.0609851],
+];
+
+const XYZ_TO_REC2020: Matrix = [
+    [1.7166513, -0.3556708, -0.2533663],
+    [-0.6666843, 1.6164812, 0.0157686],
+    [0.0176399, -0.0427706, 0.9421031],
+];
+
+const ADOBE_RGB_GAMMA: f32 = 563.0 / 256.0;
+const REC2020_ALPHA: f32 = 1.09929682680944;
+const REC2020_BETA: f32 = 0.018053968510807;
+
+/// The color space, as understood by the shaders. Must match the
+/// COLOR_SPACE_* defines in color_space.glsl.
+pub fn color_space_as_int(color_space: RgbColorSpace) -> i32 {
+    match color_space {
+        RgbColorSpace::Srgb => 0,
+        RgbColorSpace::DisplayP3 => 1,
+        RgbColorSpace::AdobeRgb => 2,
+        RgbColorSpace::Rec2020 => 3,
+    }
+}
+
+/// Pack a conversion between two color spaces for the shaders. The source
+/// color space is in the low byte and the destination in the next one, so
+/// that zero means no conversion.
+pub fn encode_color_space_conversion(src: RgbColorSpace, dst: RgbColorSpace) -> i32 {
+    if src == dst {
+        return 0;
+    }
+
+    color_space_as_int(src) | (color_space_as_int(dst) << 8)
+}
+
+fn to_xyz(color_space: RgbColorSpace) -> &'static Matrix {
+    match color_space {
+        RgbColorSpace::Srgb => &SRGB_TO_XYZ,
+        RgbColorSpace::DisplayP3 => &DISPLAY_P3_TO_XYZ,
+        RgbColorSpace::AdobeRgb => &ADOBE_RGB_TO_XYZ,
+        RgbColorSpace::Rec2020 => &REC2020_TO_XYZ,
+    }
+}
+
+fn from_xyz(color_space: RgbColorSpace) -> &'static Matrix {
+    match color_space {
+        RgbColorSpace::Srgb => &XYZ_TO_SRGB,
+        RgbColorSpace::DisplayP3 => &XYZ_TO_DISPLAY_P3,
+        RgbColorSpace::AdobeRgb => &XYZ_TO_ADOBE_RGB,
+        RgbColorSpace::Rec2020 => &XYZ_TO_REC2020,
+    }
+}
+
+fn to_linear(c: f32, color_space: RgbColorSpace) -> f32 {
+    match color_space {
+        RgbColorSpace::Srgb | RgbColorSpace::DisplayP3 => {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        RgbColorSpace::AdobeRgb => c.max(0.0).powf(ADOBE_RGB_GAMMA),
+        RgbColorSpace::Rec2020 => {
+            if c < 4.5 * REC2020_BETA {
+                c / 4.5
+            } else {
+                ((c + REC2020_ALPHA - 1.0) / REC2020_ALPHA).powf(1.0 / 0.45)
+            }
+        }
+    }
+}
+
+fn from_linear(c: f32, color_space: RgbColorSpace) -> f32 {
+    match color_space {
+        RgbColorSpace::Srgb | RgbColorSpace::DisplayP3 => {
+            if c <= 0.0031308 {
+                c * 12.92
+            } else {
+                1.055 * c.powf(1.0 / 2.4) - 0.055
+            }
+        }
+        RgbColorSpace::AdobeRgb => c.max(0.0).powf(1.0 / ADOBE_RGB_GAMMA),
+        RgbColorSpace::Rec2020 => {
+            if c < REC2020_BETA {
+                c * 4.5
+            } else {
+                REC2020_ALPHA * c.powf(0.45) - (REC2020_ALPHA - 1.0)
+            }
+        }
+    }
+}
+
+fn transform(m: &Matrix, c: [f32; 3]) -> [f32; 3] {
+    [
+        m[0][0] * c[0] + m[0][1] * c[1] + m[0][2] * c[2],
+        m[1][0] * c[0] + m[1][1] * c[1] + m[1][2] * c[2],
+        m[2][0] * c[0] + m[2][1] * c[1] + m[2][2] * c[2],
+    ]
+}
+
+/// Convert an unpremultiplied color between two color spaces. Colors outside
+/// of the destination gamut are clamped.
+pub fn convert_color(color: ColorF, src: RgbColorSpace, dst: RgbColorSpace) -> ColorF {
+    if src == dst {
+        return color;
+    }
+
+    let linear = [
+        to_linear(color.r, src),
+        to_linear(color.g, src),
+        to_linear(color.b, src),
+    ];
+    let xyz = transform(to_xyz(src), linear);
+    let [r, g, b] = transform(from_xyz(dst), xyz);
+
+    ColorF::new(
+        from_linear(r.max(0.0).min(1.0), dst),
+        from_linear(g.max(0.0).min(1.0), dst),
+        from_linear(b.max(0.0).min(1.0), dst),
+        color.a,
+    )
+}
+
+#[cfg(test)]
+fn assert_color_eq(result: ColorF, expected: ColorF) {
+    assert!((result.r - expected.r).abs() < 0.001, "{:?} != {:?}", result, expected);
+    assert!((result.g - expected.g).abs() < 0.001, "{:?} != {:?}", result, expected);
+    assert!((result.b - expected.b).abs() < 0.001, "{:?} != {:?}", result, expected);
+    assert!((result.a - expected.a).abs() < 0.001, "{:?} != {:?}", result, expected);
+}
+
+#[test]
+fn test_known_conversions() {
+    use RgbColorSpace::*;
+
+    let red = ColorF::new(1.0, 0.0, 0.0, 1.0);
+    let green = ColorF::new(0.0, 1.0, 0.0, 1.0);
+    let blue = ColorF::new(0.0, 0.0, 1.0, 1.0);
+
+    assert_color_eq(convert_color(red, Srgb, DisplayP3), ColorF::new(0.91749, 0.20029, 0.13856, 1.0));
+    assert_color_eq(convert_color(green, Srgb, Rec2020), ColorF::new(0.56754, 0.95928, 0.26897, 1.0));
+    assert_color_eq(convert_color(blue, Srgb, AdobeRgb), ColorF::new(0.0, 0.0, 0.98107, 1.0));
+    assert_color_eq(
+        convert_color(ColorF::new(0.2, 0.6, 0.9, 0.5), Srgb, DisplayP3),
+        ColorF::new(0.32046, 0.59181, 0.87591, 0.5),
+    );
+
+    // Colors outside of the sRGB gamut are clamped.
+    assert_color_eq(convert_color(red, DisplayP3, Srgb), red);
+}
+
+#[test]
+fn test_round_trip() {
+    let spaces = [
+        RgbColorSpace::Srgb,
+        RgbColorSpace::DisplayP3,
+        RgbColorSpace::AdobeRgb,
+        RgbColorSpace::Rec2020,
+    ];
+    let colors = [
+        ColorF::new(0.2, 0.6, 0.9, 1.0),
+        ColorF::new(0.5, 0.5, 0.5, 0.25),
+        ColorF::new(0.01, 0.02, 0.03, 1.0),
+    ];
+
+    for &src in &spaces {
+        for &dst in &spaces {
+            for &color in &colors {
+                // Neutral colors stay neutral.
+                let gray = convert_color(ColorF::new(color.r, color.r, color.r, color.a), src, dst);
+                assert_color_eq(gray, ColorF::new(gray.r, gray.r, gray.r, color.a));
+
+                // sRGB colors fit in all of the other gamuts.
+                let converted = convert_color(convert_color(color, RgbColorSpace::Srgb, dst), dst, src);
+                let expected = convert_color(color, RgbColorSpace::Srgb, src);
+                assert_color_eq(converted, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_conversion_encoding() {
+    assert_eq!(encode_color_space_conversion(RgbColorSpace::Rec2020, RgbColorSpace::Rec2020), 0);
+    assert_eq!(encode_color_space_conversion(RgbColorSpace::DisplayP3, RgbColorSpace::Srgb), 1);
+    assert_eq!(encode_color_space_conversion(RgbColorSpace::Srgb, RgbColorSpace::Rec2020), 3 << 8);
+}
@@ -4,7 +4,7 @@
 
 use api::{ColorF, YuvRangedColorSpace, YuvFormat, ImageRendering, ExternalImageId, ImageBufferKind};
 use api::units::*;
-use api::ColorDepth;
+use api::{ColorDepth, RgbColorSpace};
 use crate::image_source::resolve_image;
 use euclid::{Box2D, Transform3D};
 use crate::gpu_cache::GpuCache;
@@ -161,6 +161,7 @@ pub enum ExternalSurfaceDependency {
     },
     Rgb {
         image_dependency: ImageDependency,
+        color_space: RgbColorSpace,
     },
 }
 
@@ -227,6 +228,7 @@ pub enum ResolvedExternalSurfaceColorData {
     Rgb {
         image_dependency: ImageDependency,
         plane: ExternalPlaneDescriptor,
+        color_space: RgbColorSpace,
     },
 }
 
@@ -868,13 +870,14 @@ impl CompositeState {
                     update_params,
                 });
             },
-            ExternalSurfaceDependency::Rgb { .. } => {
+            ExternalSurfaceDependency::Rgb { color_space, .. } => {
                 let image_buffer_kind = planes[0].texture.image_buffer_kind();
 
                 self.external_surfaces.push(ResolvedExternalSurface {
                     color_data: ResolvedExternalSurfaceColorData::Rgb {
                         image_dependency: image_dependencies[0],
                         plane: planes[0],
+                        color_space,
                     },
                     image_buffer_kind,
                     update_params,
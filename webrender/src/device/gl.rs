@@ -551,6 +551,7 @@ pub struct Program {
     u_transform: gl::GLint,
     u_mode: gl::GLint,
     u_texture_size: gl::GLint,
+    u_tone_map: gl::GLint,
     source_info: ProgramSourceInfo,
     is_initialized: bool,
 }
@@ -1824,6 +1825,27 @@ impl Device {
         &self.capabilities
     }
 
+    /// Returns true if the color buffer of the currently bound draw
+    /// framebuffer stores floating point values, so that it can hold values
+    /// above 1.0.
+    pub fn draw_framebuffer_is_float(&self) -> bool {
+        let mut fbo = [0];
+        unsafe {
+            self.gl.get_integer_v(gl::DRAW_FRAMEBUFFER_BINDING, &mut fbo);
+        }
+        let attachment = match (fbo[0], self.gl.get_type()) {
+            (0, gl::GlType::Gl) => gl::BACK_LEFT,
+            (0, gl::GlType::Gles) => gl::BACK,
+            _ => gl::COLOR_ATTACHMENT0,
+        };
+        let component_type = self.gl.get_framebuffer_attachment_parameter_iv(
+            gl::DRAW_FRAMEBUFFER,
+            attachment,
+            gl::FRAMEBUFFER_ATTACHMENT_COMPONENT_TYPE,
+        );
+        component_type as gl::GLenum == gl::FLOAT
+    }
+
     pub fn preferred_color_formats(&self) -> TextureFormatPair<ImageFormat> {
         self.color_formats.clone()
     }
@@ -2364,6 +2386,7 @@ impl Device {
         program.u_transform = self.gl.get_uniform_location(program.id, "uTransform");
         program.u_mode = self.gl.get_uniform_location(program.id, "uMode");
         program.u_texture_size = self.gl.get_uniform_location(program.id, "uTextureSize");
+        program.u_tone_map = self.gl.get_uniform_location(program.id, "uToneMap");
 
         Ok(())
     }
@@ -2870,6 +2893,7 @@ impl Device {
             u_transform: 0,
             u_mode: 0,
             u_texture_size: 0,
+            u_tone_map: 0,
             source_info,
             is_initialized: false,
         };
@@ -2952,6 +2976,22 @@ impl Device {
         }
     }
 
+    /// Sets the uToneMap uniform of the composite shaders that apply tone
+    /// mapping. See `HdrOptions::shader_params`.
+    pub fn set_shader_tone_map(
+        &self,
+        program: &Program,
+        params: [f32; 4],
+    ) {
+        debug_assert!(self.inside_frame);
+        #[cfg(debug_assertions)]
+        debug_assert!(self.shader_is_ready);
+
+        if program.u_tone_map != -1 {
+            self.gl.uniform_4f(program.u_tone_map, params[0], params[1], params[2], params[3]);
+        }
+    }
+
     pub fn create_pbo(&mut self) -> PBO {
         let id = self.gl.gen_buffers(1)[0];
         PBO {
@@ -3950,6 +3990,24 @@ impl Device {
                 read: gl::RG,
                 pixel_type: gl::UNSIGNED_SHORT,
             },
+            ImageFormat::RGBA16F => FormatDesc {
+                internal: gl::RGBA16F,
+                external: gl::RGBA,
+                read: gl::RGBA,
+                pixel_type: gl::HALF_FLOAT,
+            },
+            ImageFormat::R16F => FormatDesc {
+                internal: gl::R16F,
+                external: gl::RED,
+                read: gl::RED,
+                pixel_type: gl::HALF_FLOAT,
+            },
+            ImageFormat::RGB10A2 => FormatDesc {
+                internal: gl::RGB10_A2,
+                external: gl::RGBA,
+                read: gl::RGBA,
+                pixel_type: gl::UNSIGNED_INT_2_10_10_10_REV,
+            },
         }
     }
 
@@ -4591,6 +4649,9 @@ impl<'a> TextureUploader<'a> {
             ImageFormat::RG16 => (gl::RG, 4, gl::UNSIGNED_SHORT),
             ImageFormat::RGBAF32 => (gl::RGBA, 16, gl::FLOAT),
             ImageFormat::RGBAI32 => (gl::RGBA_INTEGER, 16, gl::INT),
+            ImageFormat::RGBA16F => (gl::RGBA, 8, gl::HALF_FLOAT),
+            ImageFormat::R16F => (gl::RED, 2, gl::HALF_FLOAT),
+            ImageFormat::RGB10A2 => (gl::RGBA, 4, gl::UNSIGNED_INT_2_10_10_10_REV),
         };
 
         let row_length = match chunk.stride {
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{ColorF, DebugFlags, FontRenderMode, PremultipliedColorF};
+use api::{ColorF, DebugFlags, FontRenderMode, ImageFormat, PremultipliedColorF, RgbColorSpace};
 use api::units::*;
 use crate::batch::{BatchBuilder, AlphaBatchBuilder, AlphaBatchContainer};
 use crate::clip::{ClipStore, ClipChainStack};
@@ -30,6 +30,7 @@ use crate::resource_cache::{ResourceCache};
 use crate::scene::{BuiltScene, SceneProperties};
 use crate::space::SpaceMapper;
 use crate::segment::SegmentBuilder;
+use crate::tone_map::HdrOptions;
 use std::{f32, mem};
 use crate::util::{VecHelper, Recycler, Preallocator};
 use crate::visibility::{update_primitive_visibility, FrameVisibilityState, FrameVisibilityContext};
@@ -76,6 +77,22 @@ pub struct FrameBuilderConfig {
     pub force_invalidation: bool,
     pub is_software: bool,
     pub low_quality_pinch_zoom: bool,
+    /// The color space colors and images are converted to.
+    pub output_color_space: RgbColorSpace,
+    /// Set when rendering in HDR mode, see `RendererOptions::hdr`.
+    pub hdr: Option<HdrOptions>,
+}
+
+impl FrameBuilderConfig {
+    /// The format of color render targets and picture cache tiles. HDR
+    /// content needs to go above 1.0.
+    pub fn color_target_format(&self) -> ImageFormat {
+        if self.hdr.is_some() {
+            ImageFormat::RGBA16F
+        } else {
+            ImageFormat::RGBA8
+        }
+    }
 }
 
 /// A set of common / global resources that are retained between
@@ -498,6 +515,7 @@ impl FrameBuilder {
                     prim_list,
                     pic_context,
                     pic_state,
+                    &frame_context,
                     &mut frame_state,
                 );
             }
@@ -597,6 +615,7 @@ impl FrameBuilder {
             resource_cache,
             gpu_cache,
             &mut deferred_resolves,
+            scene.config.color_target_format(),
         );
 
         let mut passes = Vec::new();
@@ -622,6 +641,7 @@ impl FrameBuilder {
                     use_advanced_blending: scene.config.gpu_supports_advanced_blend,
                     break_advanced_blend_batches: !scene.config.advanced_blend_is_coherent,
                     batch_lookback_count: scene.config.batch_lookback_count,
+                    output_color_space: scene.config.output_color_space,
                     spatial_tree: &scene.spatial_tree,
                     data_stores,
                     surfaces: &scratch.frame.surfaces,
@@ -644,6 +664,7 @@ impl FrameBuilder {
                     &mut z_generator,
                     &mut composite_state,
                     scene.config.gpu_supports_fast_clears,
+                    scene.config.color_target_format(),
                 );
 
                 has_texture_cache_tasks |= !pass.texture_cache.is_empty();
@@ -660,6 +681,7 @@ impl FrameBuilder {
                 use_advanced_blending: scene.config.gpu_supports_advanced_blend,
                 break_advanced_blend_batches: !scene.config.advanced_blend_is_coherent,
                 batch_lookback_count: scene.config.batch_lookback_count,
+                output_color_space: scene.config.output_color_space,
                 spatial_tree: &scene.spatial_tree,
                 data_stores,
                 surfaces: &scratch.frame.surfaces,
@@ -771,6 +793,7 @@ pub fn build_render_pass(
     z_generator: &mut ZBufferIdGenerator,
     composite_state: &mut CompositeState,
     gpu_supports_fast_clears: bool,
+    color_target_format: ImageFormat,
 ) -> RenderPass {
     profile_scope!("build_render_pass");
 
@@ -783,7 +806,7 @@ pub fn build_render_pass(
     // This allows us to only walk that picture root once, adding the
     // primitives to all relevant batches at the same time.
     let mut picture_cache_tasks = FastHashMap::default();
-    let mut pass = RenderPass::new(src_pass);
+    let mut pass = RenderPass::new(src_pass, color_target_format);
 
     for sub_pass in &src_pass.sub_passes {
         match sub_pass.surface {
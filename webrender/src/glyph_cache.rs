@@ -2,11 +2,21 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::glyph_rasterizer::{FontInstance, GlyphFormat, GlyphKey, GlyphRasterizer};
+use api::{FontKey, FontTemplate, NativeFontHandle};
+use api::channel::{single_msg_channel, Receiver};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crate::glyph_rasterizer::{FontInstance, GlyphFormat, GlyphKey, GlyphRasterizer, RasterizedGlyph};
 use crate::internal_types::FastHashMap;
+use crate::platform::font::FontContext;
 use crate::render_backend::{FrameId, FrameStamp};
 use crate::resource_cache::ResourceClassCache;
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::io::{self, BufWriter, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 use crate::texture_cache::{EvictionNotice, TextureCache};
 use crate::texture_cache::TextureCacheHandle;
 
@@ -89,8 +99,12 @@ impl GlyphKeyCache {
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct GlyphCache {
+    // Glyph keys only identify a glyph within a font instance, so filled and
+    // stroked glyphs, or glyphs of different sizes, have separate key caches.
     glyph_key_caches: FastHashMap<FontInstance, GlyphKeyCache>,
     current_frame: FrameId,
+    #[cfg_attr(any(feature = "capture", feature = "replay"), serde(skip))]
+    disk_cache: Option<GlyphDiskCache>,
 }
 
 impl GlyphCache {
@@ -98,10 +112,29 @@ impl GlyphCache {
         GlyphCache {
             glyph_key_caches: FastHashMap::default(),
             current_frame: Default::default(),
+            disk_cache: None,
+        }
+    }
+
+    /// Create a glyph cache backed by a persistent glyph cache, which starts
+    /// loading the glyphs stored by previous runs in the background.
+    pub fn with_disk_cache(options: &GlyphDiskCacheOptions, can_use_r8_format: bool) -> Self {
+        GlyphCache {
+            disk_cache: Some(GlyphDiskCache::load(options, can_use_r8_format)),
+            ..GlyphCache::new()
         }
     }
 
     pub fn get_glyph_key_cache_for_font_mut(&mut self, font: FontInstance) -> &mut GlyphKeyCache {
+        self.get_glyph_key_and_disk_caches_mut(font).0
+    }
+
+    /// Like get_glyph_key_cache_for_font_mut, but also returns the persistent
+    /// glyph cache if there is one.
+    pub fn get_glyph_key_and_disk_caches_mut(
+        &mut self,
+        font: FontInstance,
+    ) -> (&mut GlyphKeyCache, Option<&mut GlyphDiskCache>) {
         let cache = self.glyph_key_caches
                         .entry(font)
                         .or_insert_with(GlyphKeyCache::new);
@@ -109,7 +142,7 @@ impl GlyphCache {
         {
             cache.user_data.last_frame_used = self.current_frame;
         }
-        cache
+        (cache, self.disk_cache.as_mut())
     }
 
     pub fn get_glyph_key_cache_for_font(&self, font: &FontInstance) -> &GlyphKeyCache {
@@ -152,10 +185,14 @@ impl GlyphCache {
 
     /// If possible, remove entirely any empty glyph key caches.
     fn clear_empty_caches(&mut self, glyph_rasterizer: &mut GlyphRasterizer) {
+        let mut disk_cache = self.disk_cache.as_mut();
         self.glyph_key_caches.retain(|key, cache| {
             // Discard the glyph key cache if it has no valid glyphs.
             if cache.is_empty() {
                 glyph_rasterizer.delete_font_instance(key);
+                if let Some(ref mut disk_cache) = disk_cache {
+                    disk_cache.delete_font_instance(key);
+                }
                 false
             } else {
                 true
@@ -175,5 +212,640 @@ impl GlyphCache {
         // Clearing evicted glyphs and pruning excess usage might have produced empty caches,
         // so get rid of them if possible.
         self.clear_empty_caches(glyph_rasterizer);
+        if let Some(ref mut disk_cache) = self.disk_cache {
+            disk_cache.begin_frame();
+        }
+    }
+
+    pub fn add_font(&mut self, font_key: FontKey, template: &FontTemplate) {
+        if let Some(ref mut disk_cache) = self.disk_cache {
+            disk_cache.add_font(font_key, template);
+        }
+    }
+
+    pub fn delete_font(&mut self, font_key: FontKey) {
+        if let Some(ref mut disk_cache) = self.disk_cache {
+            disk_cache.delete_font(font_key);
+        }
+    }
+
+    /// Write the persistent glyph cache to disk, if there is one.
+    pub fn save_disk_cache(&mut self) {
+        if let Some(ref mut disk_cache) = self.disk_cache {
+            if let Err(err) = disk_cache.save() {
+                warn!("Failed to save the glyph cache: {}", err);
+            }
+        }
+    }
+}
+
+/// Configuration of the persistent glyph cache, see `RendererOptions::glyph_disk_cache`.
+#[derive(Clone, Debug)]
+pub struct GlyphDiskCacheOptions {
+    /// The directory the cache is stored in. It is created if it doesn't exist.
+    pub path: PathBuf,
+    /// The maximum size of the stored glyphs in bytes. The least recently used
+    /// glyphs are discarded when it is exceeded.
+    pub max_size: usize,
+}
+
+const DISK_CACHE_FILE_NAME: &str = "glyphs.bin";
+const DISK_CACHE_MAGIC: &[u8; 4] = b"WRGC";
+// Bump this when the file layout, or the way glyphs are rasterized, changes.
+const DISK_CACHE_FORMAT_VERSION: u32 = 2;
+// How often the glyphs are written to disk while running, if they changed.
+const DISK_CACHE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+// The size of a glyph record in the file, excluding its pixels.
+const DISK_GLYPH_HEADER_SIZE: usize = 41;
+
+/// A rasterized glyph, as uploaded to the texture cache and stored in the
+/// persistent glyph cache.
+#[derive(Clone)]
+pub struct DiskGlyph {
+    pub left: f32,
+    pub top: f32,
+    pub width: i32,
+    pub height: i32,
+    pub scale: f32,
+    pub format: GlyphFormat,
+    /// The pixels, in the glyph format's image format.
+    pub bytes: Arc<Vec<u8>>,
+    // The clock of the persistent glyph cache when the glyph was last used.
+    last_used: u64,
+}
+
+impl DiskGlyph {
+    fn size(&self) -> usize {
+        DISK_GLYPH_HEADER_SIZE + self.bytes.len()
+    }
+}
+
+impl From<RasterizedGlyph> for DiskGlyph {
+    fn from(glyph: RasterizedGlyph) -> Self {
+        DiskGlyph {
+            left: glyph.left,
+            top: glyph.top,
+            width: glyph.width,
+            height: glyph.height,
+            scale: glyph.scale,
+            format: glyph.format,
+            bytes: Arc::new(glyph.bytes),
+            last_used: 0,
+        }
+    }
+}
+
+fn glyph_format_to_u8(format: GlyphFormat) -> u8 {
+    match format {
+        GlyphFormat::Alpha => 0,
+        GlyphFormat::TransformedAlpha => 1,
+        GlyphFormat::Subpixel => 2,
+        GlyphFormat::TransformedSubpixel => 3,
+        GlyphFormat::Bitmap => 4,
+        GlyphFormat::ColorBitmap => 5,
+        GlyphFormat::Sdf => 6,
+    }
+}
+
+fn glyph_format_from_u8(format: u8) -> Option<GlyphFormat> {
+    Some(match format {
+        0 => GlyphFormat::Alpha,
+        1 => GlyphFormat::TransformedAlpha,
+        2 => GlyphFormat::Subpixel,
+        3 => GlyphFormat::TransformedSubpixel,
+        4 => GlyphFormat::Bitmap,
+        5 => GlyphFormat::ColorBitmap,
+        6 => GlyphFormat::Sdf,
+        _ => return None,
+    })
+}
+
+/// The glyphs of a persistent glyph cache, grouped by the serialized
+/// parameters of their font instance, see GlyphDiskCache::instance_key.
+type DiskGlyphMap = FastHashMap<Vec<u8>, FastHashMap<GlyphKey, DiskGlyph>>;
+
+struct LoadedGlyphs {
+    clock: u64,
+    glyphs: DiskGlyphMap,
+}
+
+/// A persistent glyph cache, which keeps the glyphs rasterized by previous
+/// runs on disk so that they don't need rasterizing again on startup.
+///
+/// Glyphs are identified by a hash of the font data, the parameters of the
+/// font instance that affect rasterization and the glyph key, since font and
+/// instance keys are only meaningful within a run. The file is loaded on a
+/// background thread, glyphs requested before it is loaded are rasterized as
+/// usual. The file records the versions of WebRender and of the platform's
+/// font rasterizer that wrote it, so that glyphs rasterized by different
+/// versions are discarded.
+///
+/// The glyphs are written to disk periodically on a background thread, and
+/// when the render backend shuts down.
+///
+/// The cache is bounded by a size limit. It keeps a clock that advances with
+/// every frame and is stored in the file, and discards the glyphs that were
+/// used the longest ago when the limit is exceeded.
+pub struct GlyphDiskCache {
+    file_path: PathBuf,
+    /// The versions of WebRender and the font rasterizer, see `cache_version`.
+    version: String,
+    max_size: usize,
+    can_use_r8_format: bool,
+    /// Receives the glyphs of the file while it is being loaded.
+    loader: Option<Receiver<LoadedGlyphs>>,
+    /// Receives the result of writing the file in the background.
+    saver: Option<Receiver<io::Result<()>>>,
+    last_save: Instant,
+    glyphs: DiskGlyphMap,
+    /// The total size of the glyphs, as stored in the file.
+    size: usize,
+    clock: u64,
+    /// Whether the glyphs changed since they were loaded or saved.
+    dirty: bool,
+    /// The hashes of the data of the fonts that may be cached.
+    font_hashes: FastHashMap<FontKey, u64>,
+    /// The serialized parameters of font instances, or None for instances of
+    /// fonts that aren't cached.
+    instance_keys: FastHashMap<FontInstance, Option<Vec<u8>>>,
+}
+
+impl GlyphDiskCache {
+    fn load(options: &GlyphDiskCacheOptions, can_use_r8_format: bool) -> Self {
+        let file_path = options.path.join(DISK_CACHE_FILE_NAME);
+        let version = cache_version();
+
+        let (tx, rx) = single_msg_channel();
+        let loader_path = file_path.clone();
+        let loader_version = version.clone();
+        let spawned = thread::Builder::new().name("WRGlyphCacheLoader".to_string()).spawn(move || {
+            let glyphs = match read_disk_glyphs(&loader_path, &loader_version, can_use_r8_format) {
+                Ok(glyphs) => glyphs,
+                Err(err) => {
+                    if err.kind() != io::ErrorKind::NotFound {
+                        info!("Discarding the glyph cache {:?}: {}", loader_path, err);
+                    }
+                    LoadedGlyphs { clock: 0, glyphs: FastHashMap::default() }
+                }
+            };
+            tx.send(glyphs).ok();
+        });
+
+        GlyphDiskCache {
+            file_path,
+            version,
+            max_size: options.max_size,
+            can_use_r8_format,
+            loader: spawned.ok().map(|_| rx),
+            saver: None,
+            last_save: Instant::now(),
+            glyphs: FastHashMap::default(),
+            size: 0,
+            clock: 0,
+            dirty: false,
+            font_hashes: FastHashMap::default(),
+            instance_keys: FastHashMap::default(),
+        }
+    }
+
+    fn add_font(&mut self, font_key: FontKey, template: &FontTemplate) {
+        let hash = match *template {
+            FontTemplate::Raw(ref data, index) => fxhash::hash64(&(&data[..], index)),
+            FontTemplate::Native(ref handle) => match native_font_hash(handle) {
+                Some(hash) => hash,
+                None => return,
+            },
+        };
+        self.font_hashes.insert(font_key, hash);
+    }
+
+    fn delete_font(&mut self, font_key: FontKey) {
+        self.font_hashes.remove(&font_key);
+        self.instance_keys.retain(|font, _| font.font_key != font_key);
+    }
+
+    fn delete_font_instance(&mut self, font: &FontInstance) {
+        self.instance_keys.remove(font);
+    }
+
+    /// Get the serialized parameters of a font instance that affect how its
+    /// glyphs are rasterized, which identify it between runs.
+    fn instance_key<'a>(
+        instance_keys: &'a mut FastHashMap<FontInstance, Option<Vec<u8>>>,
+        font_hashes: &FastHashMap<FontKey, u64>,
+        font: &FontInstance,
+    ) -> Option<&'a [u8]> {
+        if !instance_keys.contains_key(font) {
+            let key = font_hashes.get(&font.font_key).and_then(|font_hash| {
+                let transform = &font.transform;
+                bincode::serialize(&(
+                    font_hash,
+                    (transform.scale_x, transform.skew_x, transform.skew_y, transform.scale_y),
+                    font.render_mode,
+                    font.flags,
+                    font.color,
+                    font.size,
+                    font.stroke_width,
+                    font.base.size,
+                    font.base.bg_color,
+                    font.base.render_mode,
+                    font.base.flags,
+                    font.base.synthetic_italics,
+                    font.base.platform_options,
+                    &font.base.variations,
+                )).ok()
+            });
+            instance_keys.insert(font.clone(), key);
+        }
+        instance_keys[font].as_ref().map(|key| &key[..])
+    }
+
+    /// Look up a glyph stored by a previous run.
+    pub fn get(&mut self, font: &FontInstance, key: &GlyphKey) -> Option<DiskGlyph> {
+        if self.loader.is_some() {
+            return None;
+        }
+        let instance_key = Self::instance_key(&mut self.instance_keys, &self.font_hashes, font)?;
+        let glyph = self.glyphs.get_mut(instance_key)?.get_mut(key)?;
+        if glyph.last_used != self.clock {
+            glyph.last_used = self.clock;
+            self.dirty = true;
+        }
+        Some(glyph.clone())
+    }
+
+    /// Store a newly rasterized glyph.
+    pub fn insert(&mut self, font: &FontInstance, key: GlyphKey, mut glyph: DiskGlyph) {
+        let instance_key = match Self::instance_key(&mut self.instance_keys, &self.font_hashes, font) {
+            Some(instance_key) => instance_key,
+            None => return,
+        };
+        if !self.glyphs.contains_key(instance_key) {
+            self.glyphs.insert(instance_key.to_vec(), FastHashMap::default());
+        }
+
+        glyph.last_used = self.clock;
+        self.size += glyph.size();
+        if let Some(old_glyph) = self.glyphs.get_mut(instance_key).unwrap().insert(key, glyph) {
+            self.size -= old_glyph.size();
+        }
+        self.dirty = true;
+    }
+
+    fn begin_frame(&mut self) {
+        self.clock += 1;
+
+        let loaded = match self.loader {
+            Some(ref loader) => loader.try_recv().ok(),
+            None => None,
+        };
+        if let Some(loaded) = loaded {
+            self.loader = None;
+            self.merge(loaded);
+        }
+
+        // Leave some headroom so that we don't prune on every frame once
+        // the cache is full.
+        if self.size > self.max_size {
+            self.prune(self.max_size / 4 * 3);
+        }
+
+        if self.last_save.elapsed() >= DISK_CACHE_SAVE_INTERVAL {
+            self.save_in_background();
+        }
+    }
+
+    /// Write the glyphs to disk on a background thread if they changed, so
+    /// that they aren't lost if the process doesn't shut down cleanly.
+    fn save_in_background(&mut self) {
+        if let Some(ref saver) = self.saver {
+            match saver.try_recv() {
+                Ok(Err(err)) => warn!("Failed to save the glyph cache: {}", err),
+                Ok(Ok(())) => {}
+                // The previous save is still running.
+                Err(..) => return,
+            }
+            self.saver = None;
+        }
+        self.last_save = Instant::now();
+
+        // Don't overwrite the glyphs of previous runs if they haven't
+        // finished loading yet.
+        if !self.dirty || self.loader.is_some() {
+            return;
+        }
+        self.prune(self.max_size);
+
+        // The pixels are shared, so this only copies the glyph records.
+        let glyphs = self.glyphs.clone();
+        let (path, version) = (self.file_path.clone(), self.version.clone());
+        let (can_use_r8_format, clock) = (self.can_use_r8_format, self.clock);
+        let (tx, rx) = single_msg_channel();
+        let spawned = thread::Builder::new().name("WRGlyphCacheSaver".to_string()).spawn(move || {
+            tx.send(write_disk_glyphs(&path, &version, can_use_r8_format, clock, &glyphs)).ok();
+        });
+        if spawned.is_ok() {
+            self.saver = Some(rx);
+            self.dirty = false;
+        }
+    }
+
+    /// Merge the glyphs loaded from the file with the ones rasterized while
+    /// it was being loaded.
+    fn merge(&mut self, loaded: LoadedGlyphs) {
+        // Continue the clock of the run that wrote the file, so that the glyphs
+        // of this run are the most recently used ones.
+        self.clock += loaded.clock;
+        for glyphs in self.glyphs.values_mut() {
+            for glyph in glyphs.values_mut() {
+                glyph.last_used += loaded.clock;
+            }
+        }
+
+        for (instance_key, loaded_glyphs) in loaded.glyphs {
+            let glyphs = self.glyphs.entry(instance_key).or_default();
+            for (key, glyph) in loaded_glyphs {
+                if let Entry::Vacant(entry) = glyphs.entry(key) {
+                    self.size += glyph.size();
+                    entry.insert(glyph);
+                }
+            }
+        }
+    }
+
+    /// Discard the least recently used glyphs until the cache fits in size.
+    fn prune(&mut self, size: usize) {
+        if self.size <= size {
+            return;
+        }
+
+        let mut glyphs: Vec<(u64, &[u8], GlyphKey)> = self.glyphs
+            .iter()
+            .flat_map(|(instance_key, glyphs)| {
+                glyphs.iter().map(move |(key, glyph)| (glyph.last_used, &instance_key[..], *key))
+            })
+            .collect();
+        glyphs.sort_by_key(|&(last_used, _, _)| last_used);
+
+        let mut excess = self.size - size;
+        let mut evicted = Vec::new();
+        for (_, instance_key, key) in glyphs {
+            if excess == 0 {
+                break;
+            }
+            let glyph_size = self.glyphs[instance_key][&key].size();
+            excess = excess.saturating_sub(glyph_size);
+            self.size -= glyph_size;
+            evicted.push((instance_key.to_vec(), key));
+        }
+
+        for (instance_key, key) in evicted {
+            let glyphs = self.glyphs.get_mut(&instance_key).unwrap();
+            glyphs.remove(&key);
+            if glyphs.is_empty() {
+                self.glyphs.remove(&instance_key);
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Write the glyphs to disk if they changed.
+    fn save(&mut self) -> io::Result<()> {
+        // Don't overwrite the glyphs of previous runs if they haven't finished
+        // loading yet.
+        if let Some(loader) = self.loader.take() {
+            if let Ok(loaded) = loader.recv() {
+                self.merge(loaded);
+            }
+        }
+        // Nor race a background save for the file.
+        if let Some(saver) = self.saver.take() {
+            if let Ok(Err(err)) = saver.recv() {
+                warn!("Failed to save the glyph cache: {}", err);
+            }
+        }
+
+        if !self.dirty {
+            return Ok(());
+        }
+        self.prune(self.max_size);
+
+        write_disk_glyphs(&self.file_path, &self.version, self.can_use_r8_format, self.clock, &self.glyphs)?;
+        self.dirty = false;
+
+        Ok(())
+    }
+}
+
+/// The versions that glyphs rasterized by another run must have been
+/// rasterized with to be reused. Besides WebRender itself, the platform's
+/// font rasterizer can change how glyphs look, e.g. with a FreeType update.
+fn cache_version() -> String {
+    format!("{} {}", env!("CARGO_PKG_VERSION"), FontContext::rasterizer_version())
+}
+
+/// Identify a native font by its handle and the file it refers to, so that
+/// glyphs aren't reused if the font installed at the path is updated.
+#[cfg(not(target_os = "macos"))]
+fn native_font_hash(handle: &NativeFontHandle) -> Option<u64> {
+    let metadata = fs::metadata(&handle.path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(fxhash::hash64(&(&handle.path, handle.index, metadata.len(), modified)))
+}
+
+/// Identify a native font by its name and its head table, which records a
+/// checksum of the whole font and its modification date, so that glyphs
+/// aren't reused if the font installed under the name is updated.
+#[cfg(target_os = "macos")]
+fn native_font_hash(handle: &NativeFontHandle) -> Option<u64> {
+    let head = handle.0.copy_table_for_tag(u32::from_be_bytes(*b"head"))?;
+    Some(fxhash::hash64(&(handle.0.postscript_name().to_string(), head.bytes())))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads `len` bytes, checking that the file has them before allocating, as
+/// the length comes from a file that may be truncated or corrupt.
+fn read_bytes(reader: &mut Cursor<&[u8]>, len: usize) -> io::Result<Vec<u8>> {
+    let remaining = reader.get_ref().len() as u64 - reader.position();
+    if len as u64 > remaining {
+        return Err(invalid_data("truncated glyph cache"));
+    }
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_disk_glyphs(path: &Path, expected_version: &str, can_use_r8_format: bool) -> io::Result<LoadedGlyphs> {
+    let data = fs::read(path)?;
+    let mut reader = Cursor::new(&data[..]);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != DISK_CACHE_MAGIC {
+        return Err(invalid_data("not a glyph cache"));
+    }
+    let format_version = reader.read_u32::<LittleEndian>()?;
+    let version_len = reader.read_u32::<LittleEndian>()? as usize;
+    let version = read_bytes(&mut reader, version_len)?;
+    if format_version != DISK_CACHE_FORMAT_VERSION || version != expected_version.as_bytes() {
+        return Err(invalid_data("written by a different version"));
+    }
+    // Glyphs are stored in their texture format, which depends on R8 support.
+    if (reader.read_u8()? != 0) != can_use_r8_format {
+        return Err(invalid_data("written for different texture formats"));
+    }
+    let clock = reader.read_u64::<LittleEndian>()?;
+
+    let mut glyphs = FastHashMap::default();
+    let instance_count = reader.read_u32::<LittleEndian>()?;
+    for _ in 0 .. instance_count {
+        let instance_key_len = reader.read_u32::<LittleEndian>()? as usize;
+        let instance_key = read_bytes(&mut reader, instance_key_len)?;
+
+        let glyph_count = reader.read_u32::<LittleEndian>()?;
+        let mut instance_glyphs = FastHashMap::default();
+        for _ in 0 .. glyph_count {
+            let key = GlyphKey::from_bits(reader.read_u32::<LittleEndian>()?);
+            let format = glyph_format_from_u8(reader.read_u8()?)
+                .ok_or_else(|| invalid_data("unknown glyph format"))?;
+            let left = reader.read_f32::<LittleEndian>()?;
+            let top = reader.read_f32::<LittleEndian>()?;
+            let width = reader.read_i32::<LittleEndian>()?;
+            let height = reader.read_i32::<LittleEndian>()?;
+            let scale = reader.read_f32::<LittleEndian>()?;
+            let last_used = reader.read_u64::<LittleEndian>()?;
+
+            let len = reader.read_u32::<LittleEndian>()? as usize;
+            let bpp = format.image_format(can_use_r8_format).bytes_per_pixel() as usize;
+            let expected_len = if width > 0 && height > 0 {
+                (width as usize).checked_mul(height as usize).and_then(|area| area.checked_mul(bpp))
+            } else {
+                None
+            };
+            if expected_len != Some(len) {
+                return Err(invalid_data("invalid glyph size"));
+            }
+            let bytes = read_bytes(&mut reader, len)?;
+
+            instance_glyphs.insert(key, DiskGlyph {
+                left,
+                top,
+                width,
+                height,
+                scale,
+                format,
+                bytes: Arc::new(bytes),
+                last_used,
+            });
+        }
+        glyphs.insert(instance_key, instance_glyphs);
+    }
+
+    Ok(LoadedGlyphs { clock, glyphs })
+}
+
+fn write_disk_glyphs(
+    path: &Path,
+    version: &str,
+    can_use_r8_format: bool,
+    clock: u64,
+    glyphs: &DiskGlyphMap,
+) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    // Write to a temporary file first, so that a partially written cache is
+    // never loaded.
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+
+        writer.write_all(DISK_CACHE_MAGIC)?;
+        writer.write_u32::<LittleEndian>(DISK_CACHE_FORMAT_VERSION)?;
+        let version = version.as_bytes();
+        writer.write_u32::<LittleEndian>(version.len() as u32)?;
+        writer.write_all(version)?;
+        writer.write_u8(can_use_r8_format as u8)?;
+        writer.write_u64::<LittleEndian>(clock)?;
+
+        writer.write_u32::<LittleEndian>(glyphs.len() as u32)?;
+        for (instance_key, instance_glyphs) in glyphs {
+            writer.write_u32::<LittleEndian>(instance_key.len() as u32)?;
+            writer.write_all(instance_key)?;
+
+            writer.write_u32::<LittleEndian>(instance_glyphs.len() as u32)?;
+            for (key, glyph) in instance_glyphs {
+                writer.write_u32::<LittleEndian>(key.to_bits())?;
+                writer.write_u8(glyph_format_to_u8(glyph.format))?;
+                writer.write_f32::<LittleEndian>(glyph.left)?;
+                writer.write_f32::<LittleEndian>(glyph.top)?;
+                writer.write_i32::<LittleEndian>(glyph.width)?;
+                writer.write_i32::<LittleEndian>(glyph.height)?;
+                writer.write_f32::<LittleEndian>(glyph.scale)?;
+                writer.write_u64::<LittleEndian>(glyph.last_used)?;
+                writer.write_u32::<LittleEndian>(glyph.bytes.len() as u32)?;
+                writer.write_all(&glyph.bytes)?;
+            }
+        }
+
+        writer.flush()?;
     }
+
+    fs::rename(&temp_path, path)
+}
+
+#[test]
+fn test_disk_glyphs_round_trip() {
+    // A directory per process, so that concurrent test runs don't race.
+    let dir = std::env::temp_dir().join(format!("wr-test-glyph-cache-{}", std::process::id()));
+    let path = dir.join(DISK_CACHE_FILE_NAME);
+    let version = "0.61.0 FreeType 2.10.4";
+
+    let mut instance_glyphs = FastHashMap::default();
+    instance_glyphs.insert(GlyphKey::from_bits(0x4000_0021), DiskGlyph {
+        left: -1.0,
+        top: 7.0,
+        width: 2,
+        height: 3,
+        scale: 1.0,
+        format: GlyphFormat::Subpixel,
+        bytes: Arc::new((0 .. 24).collect()),
+        last_used: 5,
+    });
+    let mut glyphs = FastHashMap::default();
+    glyphs.insert(vec![1, 2, 3], instance_glyphs);
+    write_disk_glyphs(&path, version, true, 9, &glyphs).unwrap();
+
+    let loaded = read_disk_glyphs(&path, version, true).unwrap();
+    assert_eq!(loaded.clock, 9);
+    let glyph = &loaded.glyphs[&vec![1, 2, 3]][&GlyphKey::from_bits(0x4000_0021)];
+    assert_eq!((glyph.left, glyph.top, glyph.width, glyph.height), (-1.0, 7.0, 2, 3));
+    assert_eq!(glyph.format, GlyphFormat::Subpixel);
+    assert_eq!(glyph.last_used, 5);
+    assert_eq!(*glyph.bytes, (0 .. 24).collect::<Vec<u8>>());
+
+    // Glyphs stored in a different texture format, or rasterized by a
+    // different version, are discarded.
+    assert!(read_disk_glyphs(&path, "0.61.0 FreeType 2.11.0", true).is_err());
+    assert!(read_disk_glyphs(&path, version, false).is_err());
+
+    // A truncated file is rejected without allocating the lengths it claims.
+    let data = fs::read(&path).unwrap();
+    for len in 0 .. data.len() {
+        fs::write(&path, &data[.. len]).unwrap();
+        assert!(read_disk_glyphs(&path, version, true).is_err());
+    }
+
+    // As are corrupt lengths, here the length of the instance key.
+    let mut corrupt = data.clone();
+    let offset = 4 + 4 + 4 + version.len() + 1 + 8 + 4;
+    corrupt[offset .. offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    fs::write(&path, &corrupt).unwrap();
+    assert!(read_disk_glyphs(&path, version, true).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
 }
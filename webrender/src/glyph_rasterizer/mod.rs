@@ -5,14 +5,14 @@
 use api::{FontInstanceFlags, FontSize, BaseFontInstance};
 use api::{FontKey, FontRenderMode, FontTemplate};
 use api::{ColorU, GlyphIndex, GlyphDimensions, SyntheticItalics};
-use api::channel::{unbounded_channel, Receiver, Sender};
+use api::channel::{single_msg_channel, unbounded_channel, Receiver, Sender};
 use api::units::*;
-use api::{ImageDescriptor, ImageDescriptorFlags, ImageFormat, DirtyRect};
+use api::{ImageDescriptor, ImageDescriptorFlags, ImageFormat, DirtyRect, RgbColorSpace};
 use crate::internal_types::ResourceCacheError;
 use crate::platform::font::FontContext;
 use crate::device::TextureFilter;
 use crate::gpu_types::UvRectKind;
-use crate::glyph_cache::{GlyphCache, CachedGlyphInfo, GlyphCacheEntry};
+use crate::glyph_cache::{GlyphCache, GlyphKeyCache, CachedGlyphInfo, GlyphCacheEntry, DiskGlyph};
 use crate::internal_types::FastHashMap;
 use crate::resource_cache::CachedImageData;
 use crate::texture_cache::{TextureCache, TextureCacheHandle, Eviction, TargetShader};
@@ -32,6 +32,10 @@ use std::ops::Deref;
 use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 use std::sync::atomic::{AtomicBool, Ordering};
 
+mod sdf;
+
+pub use self::sdf::SDF_REFERENCE_SIZE;
+
 pub static GLYPH_FLASHING: AtomicBool = AtomicBool::new(false);
 
 impl FontContexts {
@@ -58,6 +62,20 @@ fn random() -> u32 {
     })
 }
 
+/// Rasterize a glyph into a distance field from its outline. Text runs only
+/// request distance fields for fonts that have outlines, so the plain glyph
+/// fallback is only used for the odd glyph without one.
+fn rasterize_distance_field_glyph(
+    context: &mut FontContext,
+    font: &FontInstance,
+    key: &GlyphKey,
+) -> GlyphRasterResult {
+    match context.get_glyph_outline(font, key) {
+        Some(outline) => Ok(sdf::rasterize_distance_field(&outline)),
+        None => context.rasterize_glyph(font, key),
+    }
+}
+
 impl GlyphRasterizer {
     pub fn request_glyphs(
         &mut self,
@@ -73,7 +91,12 @@ impl GlyphRasterizer {
                 .has_font(&font.font_key)
         );
 
-        let glyph_key_cache = glyph_cache.get_glyph_key_cache_for_font_mut(font.clone());
+        let (glyph_key_cache, mut disk_cache) =
+            glyph_cache.get_glyph_key_and_disk_caches_mut(font.clone());
+        // Flashing glyphs are meant to show which glyphs get rasterized.
+        if GLYPH_FLASHING.load(Ordering::Relaxed) {
+            disk_cache = None;
+        }
 
         // select glyphs that have not been requested yet.
         for key in glyph_keys {
@@ -93,6 +116,13 @@ impl GlyphRasterizer {
                 }
             }
 
+            // Upload the glyph directly if a previous run rasterized it.
+            if let Some(glyph) = disk_cache.as_mut().and_then(|cache| cache.get(&font, key)) {
+                let entry = self.upload_glyph(&glyph, glyph_key_cache, texture_cache, gpu_cache);
+                glyph_key_cache.add_glyph(*key, entry);
+                continue;
+            }
+
             // Increment the total number of glyphs that are pending. This is used to determine
             // later whether to use worker threads for the remaining glyphs during resolve time.
             self.pending_glyph_count += 1;
@@ -153,7 +183,11 @@ impl GlyphRasterizer {
             let mut context = font_contexts.lock_current_context();
             let mut job = GlyphRasterJob {
                 key: key.clone(),
-                result: context.rasterize_glyph(&font, key),
+                result: if font.use_distance_field() {
+                    rasterize_distance_field_glyph(&mut context, font, key)
+                } else {
+                    context.rasterize_glyph(&font, key)
+                },
             };
 
             if let Ok(ref mut glyph) = job.result {
@@ -279,7 +313,11 @@ impl GlyphRasterizer {
             // that text runs get associated with by the texture cache allocator.
             jobs.sort_by(|a, b| a.key.cmp(&b.key));
 
-            let glyph_key_cache = glyph_cache.get_glyph_key_cache_for_font_mut(font);
+            let (glyph_key_cache, mut disk_cache) =
+                glyph_cache.get_glyph_key_and_disk_caches_mut(font.clone());
+            if GLYPH_FLASHING.load(Ordering::Relaxed) {
+                disk_cache = None;
+            }
 
             for GlyphRasterJob { key, result } in jobs {
                 let glyph_info = match result {
@@ -288,31 +326,12 @@ impl GlyphRasterizer {
                         GlyphCacheEntry::Blank
                     }
                     Ok(glyph) => {
-                        let mut texture_cache_handle = TextureCacheHandle::invalid();
-                        texture_cache.request(&texture_cache_handle, gpu_cache);
-                        texture_cache.update(
-                            &mut texture_cache_handle,
-                            ImageDescriptor {
-                                size: size2(glyph.width, glyph.height),
-                                stride: None,
-                                format: glyph.format.image_format(self.can_use_r8_format),
-                                flags: ImageDescriptorFlags::empty(),
-                                offset: 0,
-                            },
-                            TextureFilter::Linear,
-                            Some(CachedImageData::Raw(Arc::new(glyph.bytes))),
-                            [glyph.left, -glyph.top, glyph.scale, 0.0],
-                            DirtyRect::All,
-                            gpu_cache,
-                            Some(glyph_key_cache.eviction_notice()),
-                            UvRectKind::Rect,
-                            Eviction::Auto,
-                            TargetShader::Text,
-                        );
-                        GlyphCacheEntry::Cached(CachedGlyphInfo {
-                            texture_cache_handle,
-                            format: glyph.format,
-                        })
+                        let glyph = DiskGlyph::from(glyph);
+                        let entry = self.upload_glyph(&glyph, glyph_key_cache, texture_cache, gpu_cache);
+                        if let Some(ref mut disk_cache) = disk_cache {
+                            disk_cache.insert(&font, key, glyph);
+                        }
+                        entry
                     }
                 };
                 glyph_key_cache.insert(key, glyph_info);
@@ -325,6 +344,42 @@ impl GlyphRasterizer {
 
         profile.end_time(profiler::GLYPH_RESOLVE_TIME);
     }
+
+    /// Upload a rasterized glyph to the texture cache.
+    fn upload_glyph(
+        &self,
+        glyph: &DiskGlyph,
+        glyph_key_cache: &GlyphKeyCache,
+        texture_cache: &mut TextureCache,
+        gpu_cache: &mut GpuCache,
+    ) -> GlyphCacheEntry {
+        let mut texture_cache_handle = TextureCacheHandle::invalid();
+        texture_cache.request(&texture_cache_handle, gpu_cache);
+        texture_cache.update(
+            &mut texture_cache_handle,
+            ImageDescriptor {
+                size: size2(glyph.width, glyph.height),
+                stride: None,
+                format: glyph.format.image_format(self.can_use_r8_format),
+                flags: ImageDescriptorFlags::empty(),
+                offset: 0,
+                color_space: RgbColorSpace::Srgb,
+            },
+            TextureFilter::Linear,
+            Some(CachedImageData::Raw(Arc::clone(&glyph.bytes))),
+            [glyph.left, -glyph.top, glyph.scale, 0.0],
+            DirtyRect::All,
+            gpu_cache,
+            Some(glyph_key_cache.eviction_notice()),
+            UvRectKind::Rect,
+            Eviction::Auto,
+            TargetShader::Text,
+        );
+        GlyphCacheEntry::Cached(CachedGlyphInfo {
+            texture_cache_handle,
+            format: glyph.format,
+        })
+    }
 }
 
 #[derive(Clone, Copy, Debug, MallocSizeOf, PartialEq, PartialOrd)]
@@ -496,6 +551,10 @@ pub struct FontInstance {
     // The font size is in *device/raster* pixels, not logical pixels.
     // It is stored as an f32 since we need sub-pixel sizes.
     pub size: FontSize,
+    // The width of the stroke the outlines of the glyphs are rasterized with,
+    // relative to the font size, or zero if the glyphs are filled. Being
+    // relative, it doesn't need adjusting when the size is.
+    pub stroke_width: FontSize,
 }
 
 impl Hash for FontInstance {
@@ -508,6 +567,7 @@ impl Hash for FontInstance {
         self.flags.hash(state);
         self.color.hash(state);
         self.size.hash(state);
+        self.stroke_width.hash(state);
     }
 }
 
@@ -533,6 +593,7 @@ impl FontInstance {
             transform: FontTransform::identity(),
             color,
             size: base.size,
+            stroke_width: FontSize::zero(),
             base,
             render_mode,
             flags,
@@ -546,6 +607,7 @@ impl FontInstance {
             transform: FontTransform::identity(),
             color: ColorU::new(0, 0, 0, 255),
             size: base.size,
+            stroke_width: FontSize::zero(),
             render_mode: base.render_mode,
             flags: base.flags,
             base,
@@ -560,6 +622,20 @@ impl FontInstance {
         self.flags.contains(FontInstanceFlags::TRANSFORM_GLYPHS)
     }
 
+    pub fn use_distance_field(&self) -> bool {
+        // Strokes are rasterized as plain glyphs at the reference size.
+        self.flags.contains(FontInstanceFlags::DISTANCE_FIELD) && !self.is_stroked()
+    }
+
+    pub fn is_stroked(&self) -> bool {
+        self.stroke_width > FontSize::zero()
+    }
+
+    /// The width of the stroke in pixels, before the transform is applied.
+    pub fn get_stroke_width(&self) -> f64 {
+        self.stroke_width.to_f64_px() * self.size.to_f64_px()
+    }
+
     pub fn get_alpha_glyph_format(&self) -> GlyphFormat {
         if self.use_transform_glyphs() { GlyphFormat::TransformedAlpha } else { GlyphFormat::Alpha }
     }
@@ -649,7 +725,8 @@ impl SubpixelDirection {
     pub fn limit_by(self, glyph_format: GlyphFormat) -> Self {
         match glyph_format {
             GlyphFormat::Bitmap |
-            GlyphFormat::ColorBitmap => SubpixelDirection::None,
+            GlyphFormat::ColorBitmap |
+            GlyphFormat::Sdf => SubpixelDirection::None,
             _ => self,
         }
     }
@@ -736,6 +813,16 @@ impl GlyphKey {
         self.0 & 0x0FFFFFFF
     }
 
+    /// The glyph index and subpixel offsets packed into a u32, for storing
+    /// the key in the persistent glyph cache.
+    pub fn to_bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u32) -> Self {
+        GlyphKey(bits)
+    }
+
     fn subpixel_offset(&self) -> (SubpixelOffset, SubpixelOffset) {
         let x = (self.0 >> 28) as u8 & 3;
         let y = (self.0 >> 30) as u8 & 3;
@@ -756,6 +843,8 @@ pub enum GlyphFormat {
     TransformedSubpixel,
     Bitmap,
     ColorBitmap,
+    /// A multi-channel signed distance field, see sdf.rs.
+    Sdf,
 }
 
 impl GlyphFormat {
@@ -775,7 +864,8 @@ impl GlyphFormat {
             }
             GlyphFormat::Subpixel |
             GlyphFormat::TransformedSubpixel |
-            GlyphFormat::ColorBitmap => ImageFormat::BGRA8,
+            GlyphFormat::ColorBitmap |
+            GlyphFormat::Sdf => ImageFormat::BGRA8,
         }
     }
 }
@@ -986,6 +1076,16 @@ pub struct GlyphRasterizer {
 
     // Whether glyphs can be rasterized in r8 format when it makes sense.
     can_use_r8_format: bool,
+
+    // Whether fonts have color layers that may be painted with the text color.
+    color_fonts: Mutex<FastHashMap<FontKey, ColorLayers>>,
+}
+
+/// Whether a font has color layers. Native fonts are probed on a worker, as
+/// this reads the font file, and the result is only waited for on first use.
+enum ColorLayers {
+    Pending(Receiver<bool>),
+    Known(bool),
 }
 
 impl GlyphRasterizer {
@@ -1022,16 +1122,52 @@ impl GlyphRasterizer {
             enable_multithreading: true,
             pending_glyph_requests: FastHashMap::default(),
             can_use_r8_format,
+            color_fonts: Mutex::new(FastHashMap::default()),
         })
     }
 
     pub fn add_font(&mut self, font_key: FontKey, template: FontTemplate) {
+        let color_layers = match template {
+            FontTemplate::Raw(..) => ColorLayers::Known(FontContext::has_color_layers(&template)),
+            FontTemplate::Native(..) => {
+                let (tx, rx) = single_msg_channel();
+                let template = template.clone();
+                self.workers.spawn(move || {
+                    let _ = tx.send(FontContext::has_color_layers(&template));
+                });
+                ColorLayers::Pending(rx)
+            }
+        };
+        self.color_fonts.lock().unwrap().insert(font_key, color_layers);
+
         self.font_contexts.async_for_each(move |mut context| {
             context.add_font(&font_key, &template);
         });
     }
 
+    /// Whether glyphs of the font can be rasterized into distance fields. This
+    /// needs outlines, which some platforms and color fonts don't provide.
+    pub fn can_rasterize_distance_field(&self, font_key: &FontKey) -> bool {
+        FontContext::has_glyph_outlines() && !self.has_color_layers(font_key)
+    }
+
+    /// Whether the font has color layers, waiting for its probe if needed.
+    fn has_color_layers(&self, font_key: &FontKey) -> bool {
+        let mut color_fonts = self.color_fonts.lock().unwrap();
+        match color_fonts.get_mut(font_key) {
+            Some(state) => {
+                if let ColorLayers::Pending(ref rx) = *state {
+                    let has_color_layers = rx.recv().unwrap_or(false);
+                    *state = ColorLayers::Known(has_color_layers);
+                }
+                matches!(*state, ColorLayers::Known(true))
+            }
+            None => false,
+        }
+    }
+
     pub fn delete_font(&mut self, font_key: FontKey) {
+        self.color_fonts.lock().unwrap().remove(&font_key);
         self.fonts_to_remove.push(font_key);
     }
 
@@ -1040,8 +1176,15 @@ impl GlyphRasterizer {
     }
 
     pub fn prepare_font(&self, font: &mut FontInstance) {
+        let color = font.color;
         FontContext::prepare_font(font);
 
+        // Keep the text color of color fonts, as their layers may use it. Its
+        // alpha is applied when drawing the glyphs.
+        if self.has_color_layers(&font.font_key) {
+            font.color = ColorU { a: 255, ..color };
+        }
+
         // Quantize the transform to minimize thrashing of the glyph cache, but
         // only quantize the transform when preparing to access the glyph cache.
         // This way, the glyph subpixel positions, which are calculated before
@@ -1302,6 +1445,59 @@ mod test_glyph_rasterizer {
         );
     }
 
+    #[test]
+    fn test_stroke_width_cache_key() {
+        // Stroked glyphs are cached separately from filled glyphs of the same
+        // font, but scaling a font keeps its relative stroke width.
+        use crate::glyph_cache::{GlyphCache, GlyphCacheEntry};
+        use crate::glyph_rasterizer::{FontInstance, BaseFontInstance, GlyphKey};
+        use api::{FontKey, FontInstanceKey, FontSize, FontRenderMode, IdNamespace, ColorU};
+        use api::units::DevicePoint;
+        use std::sync::Arc;
+
+        let filled = FontInstance::from_base(Arc::new(BaseFontInstance {
+            instance_key: FontInstanceKey(IdNamespace(0), 0),
+            font_key: FontKey::new(IdNamespace(0), 0),
+            size: FontSize::from_f32_px(20.0),
+            bg_color: ColorU::new(0, 0, 0, 0),
+            render_mode: FontRenderMode::Alpha,
+            flags: Default::default(),
+            synthetic_italics: Default::default(),
+            platform_options: None,
+            variations: Vec::new(),
+        }));
+        let stroked = FontInstance {
+            stroke_width: FontSize::from_f32_px(0.1),
+            ..filled.clone()
+        };
+        assert!(!filled.is_stroked());
+        assert!(stroked.is_stroked());
+        assert_ne!(filled, stroked);
+        assert!((stroked.get_stroke_width() - 2.0).abs() < 0.01);
+
+        let scaled = FontInstance {
+            size: FontSize::from_f32_px(40.0),
+            ..stroked.clone()
+        };
+        assert_ne!(stroked, scaled);
+        assert!((scaled.get_stroke_width() - 4.0).abs() < 0.01);
+
+        let key = GlyphKey::new(1, DevicePoint::zero(), filled.get_subpx_dir());
+        let mut glyph_cache = GlyphCache::new();
+        glyph_cache.get_glyph_key_cache_for_font_mut(filled.clone()).add_glyph(key, GlyphCacheEntry::Blank);
+        assert!(glyph_cache.get_glyph_key_cache_for_font_mut(filled.clone()).try_get(&key).is_some());
+        assert!(glyph_cache.get_glyph_key_cache_for_font_mut(stroked.clone()).try_get(&key).is_none());
+        assert!(glyph_cache.get_glyph_key_cache_for_font_mut(scaled).try_get(&key).is_none());
+
+        // A stroke of the same relative width shares the key cache.
+        let same_stroke = FontInstance {
+            stroke_width: FontSize::from_f32_px(0.1),
+            ..filled.clone()
+        };
+        glyph_cache.get_glyph_key_cache_for_font_mut(stroked).add_glyph(key, GlyphCacheEntry::Blank);
+        assert!(glyph_cache.get_glyph_key_cache_for_font_mut(same_stroke).try_get(&key).is_some());
+    }
+
     #[test]
     fn test_subpx_quantize() {
         use crate::glyph_rasterizer::SubpixelOffset;
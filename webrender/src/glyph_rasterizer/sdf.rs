@@ -0,0 +1,506 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Multi-channel signed distance field glyphs.
+//!
+//! Glyphs of font instances with `FontInstanceFlags::DISTANCE_FIELD` are
+//! rasterized once at `SDF_REFERENCE_SIZE` into a distance field, which the
+//! text shader scales to any size and transform without re-rasterizing them.
+//!
+//! The edges of each contour are split into three channels at the corners of
+//! the outline, as described in "Shape Decomposition for Multi-channel
+//! Distance Fields" (V. Chlumský). Each channel holds the distance to the
+//! nearest edge of its color, and the median of the three channels keeps the
+//! corners sharp, where a single distance would round them off. The alpha
+//! channel holds the true distance to the outline.
+
+use api::PathCommand;
+use api::units::{LayoutPoint, LayoutVector2D};
+use crate::glyph_rasterizer::{GlyphFormat, RasterizedGlyph};
+use crate::path::{flatten_path, winding_number, PathEdge};
+
+/// The font size, in pixels, that distance field glyphs are rasterized at.
+pub const SDF_REFERENCE_SIZE: f32 = 32.0;
+
+/// The distance, in texels, that the field covers on each side of the outline.
+/// Must match SDF_RANGE in ps_text_run.glsl.
+pub const SDF_RANGE: f32 = 4.0;
+
+/// The flattening tolerance of the curves of the outline, in texels.
+const SDF_TOLERANCE: f32 = 0.05;
+
+/// Edges meeting at an angle larger than this, given as its sine, are corners.
+const CORNER_CROSS_THRESHOLD: f32 = 0.141;
+
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const CYAN: u8 = GREEN | BLUE;
+const WHITE: u8 = RED | GREEN | BLUE;
+const BLACK: u8 = 0;
+
+/// A command of the outline, flattened into a polyline.
+struct Edge {
+    points: Vec<LayoutPoint>,
+    /// The direction of the edge at its start and at its end.
+    start_dir: LayoutVector2D,
+    end_dir: LayoutVector2D,
+    color: u8,
+}
+
+impl Edge {
+    fn reverse(&mut self) {
+        self.points.reverse();
+        let start_dir = self.start_dir;
+        self.start_dir = -self.end_dir;
+        self.end_dir = -start_dir;
+    }
+}
+
+/// A line segment of an edge.
+struct Segment {
+    from: LayoutPoint,
+    to: LayoutPoint,
+    color: u8,
+    /// Whether the segment starts or ends its edge, in which case the
+    /// distance to it extends along its direction past that end.
+    starts_edge: bool,
+    ends_edge: bool,
+}
+
+fn normalize(v: LayoutVector2D) -> LayoutVector2D {
+    let length = v.length();
+    if length > 0.0 { v / length } else { v }
+}
+
+/// The first of `vectors` that is not zero.
+fn direction(vectors: &[LayoutVector2D]) -> LayoutVector2D {
+    normalize(vectors.iter().cloned().find(|v| *v != LayoutVector2D::zero()).unwrap_or_else(LayoutVector2D::zero))
+}
+
+fn make_edge(from: LayoutPoint, command: PathCommand) -> Option<Edge> {
+    let (to, start_dir, end_dir) = match command {
+        PathCommand::LineTo(to) => {
+            (to, direction(&[to - from]), direction(&[to - from]))
+        }
+        PathCommand::QuadTo(ctrl, to) => {
+            (to, direction(&[ctrl - from, to - from]), direction(&[to - ctrl, to - from]))
+        }
+        PathCommand::CubicTo(ctrl1, ctrl2, to) => {
+            (
+                to,
+                direction(&[ctrl1 - from, ctrl2 - from, to - from]),
+                direction(&[to - ctrl2, to - ctrl1, to - from]),
+            )
+        }
+        PathCommand::MoveTo(..) | PathCommand::Close => return None,
+    };
+    if from == to && start_dir == LayoutVector2D::zero() {
+        return None;
+    }
+
+    let points = flatten_path(vec![PathCommand::MoveTo(from), command], SDF_TOLERANCE)
+        .pop()
+        .map_or_else(Vec::new, |polyline| polyline.points);
+    if points.len() < 2 {
+        return None;
+    }
+    Some(Edge { points, start_dir, end_dir, color: WHITE })
+}
+
+/// Split an outline into closed contours of edges.
+fn build_contours(commands: &[PathCommand]) -> Vec<Vec<Edge>> {
+    let mut contours = Vec::new();
+    let mut contour = Vec::new();
+    let mut start = LayoutPoint::zero();
+    let mut pen = LayoutPoint::zero();
+
+    fn close(contours: &mut Vec<Vec<Edge>>, contour: &mut Vec<Edge>, pen: LayoutPoint, start: LayoutPoint) {
+        if pen != start {
+            contour.extend(make_edge(pen, PathCommand::LineTo(start)));
+        }
+        if !contour.is_empty() {
+            contours.push(std::mem::take(contour));
+        }
+    }
+
+    for &command in commands {
+        match command {
+            PathCommand::MoveTo(to) => {
+                close(&mut contours, &mut contour, pen, start);
+                start = to;
+                pen = to;
+            }
+            PathCommand::Close => {
+                close(&mut contours, &mut contour, pen, start);
+                pen = start;
+            }
+            PathCommand::LineTo(to) |
+            PathCommand::QuadTo(_, to) |
+            PathCommand::CubicTo(_, _, to) => {
+                contour.extend(make_edge(pen, command));
+                pen = to;
+            }
+        }
+    }
+    close(&mut contours, &mut contour, pen, start);
+
+    contours
+}
+
+/// The signed area of the contours, positive when they are counter-clockwise
+/// with y pointing up.
+fn signed_area(contours: &[Vec<Edge>]) -> f32 {
+    let mut area = 0.0;
+    for edge in contours.iter().flatten() {
+        for pair in edge.points.windows(2) {
+            area += pair[0].to_vector().cross(pair[1].to_vector());
+        }
+    }
+    area * 0.5
+}
+
+/// The next color of the cycle cyan, magenta, yellow, avoiding the channels
+/// of `banned` if the color would otherwise only differ from it in one channel.
+fn switch_color(color: u8, banned: u8) -> u8 {
+    let combined = color & banned;
+    if combined == RED || combined == GREEN || combined == BLUE {
+        return combined ^ WHITE;
+    }
+    if color == BLACK || color == WHITE {
+        return CYAN;
+    }
+    let shifted = color << 1;
+    (shifted | shifted >> 3) & WHITE
+}
+
+/// Assign channels to the edges of a contour, so that the two edges meeting
+/// at each corner share only one channel.
+fn color_edges(contour: &mut [Edge]) {
+    let count = contour.len();
+    let corners: Vec<usize> = (0 .. count).filter(|&i| {
+        let a = contour[(i + count - 1) % count].end_dir;
+        let b = contour[i].start_dir;
+        a.dot(b) <= 0.0 || a.cross(b).abs() > CORNER_CROSS_THRESHOLD
+    }).collect();
+
+    match corners.len() {
+        0 => {
+            // A smooth contour has the same distance in all channels.
+            for edge in contour.iter_mut() {
+                edge.color = WHITE;
+            }
+        }
+        1 => {
+            // A teardrop is split into three parts, only the first and last
+            // of which meet at the corner.
+            let first = switch_color(WHITE, BLACK);
+            let colors = [first, WHITE, switch_color(first, BLACK)];
+            for i in 0 .. count {
+                let part = if count > 1 { 3 * i / count } else { 0 };
+                contour[(corners[0] + i) % count].color = colors[part];
+            }
+        }
+        corner_count => {
+            let mut color = switch_color(WHITE, BLACK);
+            let initial = color;
+            let mut spline = 0;
+            for i in 0 .. count {
+                let index = (corners[0] + i) % count;
+                if spline + 1 < corner_count && corners[spline + 1] == index {
+                    spline += 1;
+                    let banned = if spline == corner_count - 1 { initial } else { BLACK };
+                    color = switch_color(color, banned);
+                }
+                contour[index].color = color;
+            }
+        }
+    }
+}
+
+/// The distance from a point to a segment, with the measure of how parallel
+/// the direction to the point is to the segment, to pick the best segment
+/// when several are at the same distance.
+#[derive(Copy, Clone)]
+struct SignedDistance {
+    distance: f32,
+    dot: f32,
+}
+
+impl SignedDistance {
+    const FAR: SignedDistance = SignedDistance { distance: f32::MAX, dot: 1.0 };
+
+    fn closer_than(&self, other: &SignedDistance) -> bool {
+        let (a, b) = (self.distance.abs(), other.distance.abs());
+        a < b - 1e-5 || (a <= b + 1e-5 && self.dot < other.dot)
+    }
+}
+
+/// The signed distance from `point` to a segment, positive on its left, and
+/// the position of the nearest point along the segment.
+fn segment_distance(segment: &Segment, point: LayoutPoint) -> (SignedDistance, f32) {
+    let ab = segment.to - segment.from;
+    let ap = point - segment.from;
+    let t = ap.dot(ab) / ab.square_length();
+    let nearest = segment.from + ab * t.max(0.0).min(1.0);
+    let to_point = point - nearest;
+    let distance = to_point.length();
+    let sign = if ab.cross(ap) >= 0.0 { 1.0 } else { -1.0 };
+    let dot = if t > 0.0 && t < 1.0 {
+        0.0
+    } else {
+        normalize(ab).dot(normalize(to_point)).abs()
+    };
+    (SignedDistance { distance: sign * distance, dot }, t)
+}
+
+/// Extend the distance to the nearest segment of a channel past the ends of
+/// its edge, along the direction of the edge, so that the channels of the
+/// edges meeting at a corner keep intersecting past the corner.
+fn pseudo_distance(segment: &Segment, point: LayoutPoint, distance: f32, t: f32) -> f32 {
+    let dir = normalize(segment.to - segment.from);
+    let extended = if t < 0.0 && segment.starts_edge {
+        let ap = point - segment.from;
+        if ap.dot(dir) < 0.0 { Some(dir.cross(ap)) } else { None }
+    } else if t > 1.0 && segment.ends_edge {
+        let bp = point - segment.to;
+        if bp.dot(dir) > 0.0 { Some(dir.cross(bp)) } else { None }
+    } else {
+        None
+    };
+    match extended {
+        Some(pseudo) if pseudo.abs() <= distance.abs() => pseudo,
+        _ => distance,
+    }
+}
+
+fn median(a: f32, b: f32, c: f32) -> f32 {
+    a.min(b).max(a.max(b).min(c))
+}
+
+fn encode(distance: f32) -> u8 {
+    ((0.5 + distance / (2.0 * SDF_RANGE)).max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+/// Rasterize an outline, in pixels at the reference size with y pointing up,
+/// into a distance field glyph. Positive distances are inside of the outline,
+/// and 0.5 is on it.
+pub fn rasterize_distance_field(commands: &[PathCommand]) -> RasterizedGlyph {
+    let mut contours = build_contours(commands);
+
+    // Orient the contours so that the filled area is on the left of the edges,
+    // which the sign of the distances relies on. Holes wind the other way.
+    if signed_area(&contours) < 0.0 {
+        for contour in &mut contours {
+            contour.reverse();
+            for edge in contour.iter_mut() {
+                edge.reverse();
+            }
+        }
+    }
+
+    let mut segments = Vec::new();
+    let mut fill = Vec::new();
+    let mut min = LayoutPoint::new(f32::MAX, f32::MAX);
+    let mut max = LayoutPoint::new(f32::MIN, f32::MIN);
+    for contour in &mut contours {
+        color_edges(contour);
+        for edge in contour.iter() {
+            let last = edge.points.len() - 2;
+            for (i, pair) in edge.points.windows(2).enumerate() {
+                segments.push(Segment {
+                    from: pair[0],
+                    to: pair[1],
+                    color: edge.color,
+                    starts_edge: i == 0,
+                    ends_edge: i == last,
+                });
+                fill.push(PathEdge { from: pair[0], to: pair[1] });
+            }
+            for p in &edge.points {
+                min = min.min(*p);
+                max = max.max(*p);
+            }
+        }
+    }
+
+    if segments.is_empty() {
+        return RasterizedGlyph {
+            left: 0.0,
+            top: 0.0,
+            width: 0,
+            height: 0,
+            scale: 1.0,
+            format: GlyphFormat::Sdf,
+            bytes: Vec::new(),
+        };
+    }
+
+    let left = (min.x - SDF_RANGE).floor() as i32;
+    let bottom = (min.y - SDF_RANGE).floor() as i32;
+    let right = (max.x + SDF_RANGE).ceil() as i32;
+    let top = (max.y + SDF_RANGE).ceil() as i32;
+    let (width, height) = (right - left, top - bottom);
+
+    let mut bytes = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0 .. height {
+        for x in 0 .. width {
+            let point = LayoutPoint::new(
+                (left + x) as f32 + 0.5,
+                (top - y) as f32 - 0.5,
+            );
+
+            let mut nearest = SignedDistance::FAR;
+            let mut channels = [(SignedDistance::FAR, 0.0, None); 3];
+            for segment in &segments {
+                let (distance, t) = segment_distance(segment, point);
+                if distance.closer_than(&nearest) {
+                    nearest = distance;
+                }
+                for (channel, (best, best_t, best_segment)) in channels.iter_mut().enumerate() {
+                    if segment.color & (1 << channel) != 0 && distance.closer_than(best) {
+                        *best = distance;
+                        *best_t = t;
+                        *best_segment = Some(segment);
+                    }
+                }
+            }
+
+            let mut field = [0.0; 3];
+            for (value, &(best, t, segment)) in field.iter_mut().zip(channels.iter()) {
+                *value = match segment {
+                    Some(segment) => pseudo_distance(segment, point, best.distance, t),
+                    None => -SDF_RANGE,
+                };
+            }
+
+            // Trust the winding of the outline over the channels where they
+            // disagree on whether the point is inside.
+            let inside = winding_number(&fill, point) != 0;
+            let distance = if inside { nearest.distance.abs() } else { -nearest.distance.abs() };
+            if (median(field[0], field[1], field[2]) > 0.0) != inside {
+                field = [distance; 3];
+            }
+
+            bytes.extend_from_slice(&[
+                encode(field[2]),
+                encode(field[1]),
+                encode(field[0]),
+                encode(distance),
+            ]);
+        }
+    }
+
+    RasterizedGlyph {
+        left: left as f32,
+        top: top as f32,
+        width,
+        height,
+        scale: 1.0,
+        format: GlyphFormat::Sdf,
+        bytes,
+    }
+}
+
+#[cfg(test)]
+fn square(x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<PathCommand> {
+    vec![
+        PathCommand::MoveTo(LayoutPoint::new(x0, y0)),
+        PathCommand::LineTo(LayoutPoint::new(x1, y0)),
+        PathCommand::LineTo(LayoutPoint::new(x1, y1)),
+        PathCommand::LineTo(LayoutPoint::new(x0, y1)),
+        PathCommand::Close,
+    ]
+}
+
+#[cfg(test)]
+fn texel(glyph: &RasterizedGlyph, x: i32, y: i32) -> [u8; 4] {
+    let i = (((glyph.top as i32 - 1 - y) * glyph.width + x - glyph.left as i32) * 4) as usize;
+    [glyph.bytes[i], glyph.bytes[i + 1], glyph.bytes[i + 2], glyph.bytes[i + 3]]
+}
+
+#[test]
+fn test_edge_coloring() {
+    let mut contours = build_contours(&square(0.0, 0.0, 10.0, 10.0));
+    assert_eq!(contours.len(), 1);
+    let contour = &mut contours[0];
+    color_edges(contour);
+
+    // Edges meeting at a corner share exactly one channel.
+    for i in 0 .. 4 {
+        let shared = contour[i].color & contour[(i + 1) % 4].color;
+        assert!(shared.count_ones() == 1, "{:?}", contour.iter().map(|e| e.color).collect::<Vec<_>>());
+    }
+
+    // Smooth contours use all of the channels.
+    let mut circle = build_contours(&[
+        PathCommand::MoveTo(LayoutPoint::new(0.0, 5.0)),
+        PathCommand::CubicTo(LayoutPoint::new(0.0, 12.0), LayoutPoint::new(10.0, 12.0), LayoutPoint::new(10.0, 5.0)),
+        PathCommand::CubicTo(LayoutPoint::new(10.0, -2.0), LayoutPoint::new(0.0, -2.0), LayoutPoint::new(0.0, 5.0)),
+        PathCommand::Close,
+    ]);
+    color_edges(&mut circle[0]);
+    assert!(circle[0].iter().all(|edge| edge.color == WHITE));
+}
+
+#[test]
+fn test_square_field() {
+    let glyph = rasterize_distance_field(&square(0.0, 0.0, 16.0, 8.0));
+    assert_eq!((glyph.left, glyph.top, glyph.width, glyph.height), (-4.0, 12.0, 24, 16));
+    assert_eq!(glyph.format, GlyphFormat::Sdf);
+
+    let median_at = |x, y| {
+        let [b, g, r, _] = texel(&glyph, x, y);
+        median(r as f32, g as f32, b as f32) / 255.0
+    };
+
+    // The center of the texel at (2, 4) is 2.5 texels inside of the left
+    // edge, and the one at (-2, 4) is 1.5 texels outside of it.
+    assert!((median_at(2, 4) - (0.5 + 2.5 / 8.0)).abs() < 0.01);
+    assert!((median_at(-2, 4) - (0.5 - 1.5 / 8.0)).abs() < 0.01);
+
+    // The corners stay sharp: just outside of a corner is outside, just
+    // inside of it is inside.
+    assert!(median_at(16, 8) < 0.5);
+    assert!(median_at(15, 7) > 0.5);
+
+    // Far from the outline the true distance is clamped.
+    assert_eq!(texel(&glyph, -4, -4)[3], 0);
+    assert!(median_at(-4, -4) < 0.5);
+}
+
+#[test]
+fn test_orientation() {
+    // The field doesn't depend on which way the contours wind.
+    let clockwise = rasterize_distance_field(&[
+        PathCommand::MoveTo(LayoutPoint::new(0.0, 0.0)),
+        PathCommand::LineTo(LayoutPoint::new(0.0, 8.0)),
+        PathCommand::LineTo(LayoutPoint::new(16.0, 8.0)),
+        PathCommand::LineTo(LayoutPoint::new(16.0, 0.0)),
+        PathCommand::Close,
+    ]);
+    let counter_clockwise = rasterize_distance_field(&square(0.0, 0.0, 16.0, 8.0));
+    for y in -4 .. 12 {
+        for x in -4 .. 20 {
+            let a = texel(&clockwise, x, y);
+            let b = texel(&counter_clockwise, x, y);
+            assert_eq!(a[3], b[3]);
+            let inside = |t: [u8; 4]| median(t[0] as f32, t[1] as f32, t[2] as f32) > 127.5;
+            assert_eq!(inside(a), inside(b));
+        }
+    }
+
+    // Holes are outside.
+    let mut ring = square(0.0, 0.0, 16.0, 16.0);
+    ring.extend(vec![
+        PathCommand::MoveTo(LayoutPoint::new(4.0, 4.0)),
+        PathCommand::LineTo(LayoutPoint::new(4.0, 12.0)),
+        PathCommand::LineTo(LayoutPoint::new(12.0, 12.0)),
+        PathCommand::LineTo(LayoutPoint::new(12.0, 4.0)),
+        PathCommand::Close,
+    ]);
+    let glyph = rasterize_distance_field(&ring);
+    assert!(texel(&glyph, 8, 8)[3] < 128);
+    assert!(texel(&glyph, 2, 8)[3] > 128);
+}
@@ -103,6 +103,20 @@ pub struct ScalingInstance {
     pub source_rect: DeviceRect,
 }
 
+/// A glyph drawn into a text mask.
+///
+/// Must match the TEXT_MASK instance description in renderer/vertex.rs.
+#[derive(Clone, Debug)]
+#[repr(C)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct TextMaskInstance {
+    pub target_rect: DeviceRect,
+    pub resource_address: GpuCacheAddress,
+    /// Non-zero if the coverage is in the alpha channel of the glyph.
+    pub alpha_channel: i32,
+}
+
 #[derive(Clone, Debug)]
 #[repr(C)]
 #[cfg_attr(feature = "capture", derive(Serialize))]
@@ -203,6 +217,18 @@ pub struct ClipMaskInstanceBoxShadow {
     pub shadow_data: BoxShadowData,
 }
 
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[repr(C)]
+pub struct ClipMaskInstancePath {
+    pub common: ClipMaskInstanceCommon,
+    pub local_rect: LayoutRect,
+    pub edges_address: i32,
+    pub edge_count: i32,
+    pub fill_rule: i32,
+}
+
 /// A clipping primitive drawn into the clipping mask.
 /// Could be an image or a rectangle, which defines the
 /// way `address` is treated.
@@ -285,7 +311,8 @@ pub struct CompositeInstance {
     z_id: f32,
     color_space_or_uv_type: f32, // YuvColorSpace for YUV;
                                  // UV coordinate space for RGB
-    yuv_format: f32,            // YuvFormat
+    yuv_format_or_color_spaces: f32, // YuvFormat for YUV;
+                                     // color space conversion for RGB
     yuv_channel_bit_depth: f32,
 
     // UV rectangles (pixel space) for color / yuv texture planes
@@ -310,7 +337,7 @@ impl CompositeInstance {
             color,
             z_id: z_id.0 as f32,
             color_space_or_uv_type: pack_as_float(UV_TYPE_NORMALIZED),
-            yuv_format: 0.0,
+            yuv_format_or_color_spaces: 0.0,
             yuv_channel_bit_depth: 0.0,
             uv_rects: [uv, uv, uv],
             transform,
@@ -331,13 +358,22 @@ impl CompositeInstance {
             color,
             z_id: z_id.0 as f32,
             color_space_or_uv_type: pack_as_float(UV_TYPE_UNNORMALIZED),
-            yuv_format: 0.0,
+            yuv_format_or_color_spaces: 0.0,
             yuv_channel_bit_depth: 0.0,
             uv_rects: [uv_rect, uv_rect, uv_rect],
             transform,
         }
     }
 
+    /// Convert an RGB surface to another color space when compositing it,
+    /// see `encode_color_space_conversion`.
+    pub fn with_color_space_conversion(mut self, conversion: i32) -> Self {
+        if conversion != 0 {
+            self.yuv_format_or_color_spaces = pack_as_float(conversion as u32);
+        }
+        self
+    }
+
     pub fn new_yuv(
         rect: PictureRect,
         clip_rect: DeviceRect,
@@ -354,7 +390,7 @@ impl CompositeInstance {
             color: PremultipliedColorF::WHITE,
             z_id: z_id.0 as f32,
             color_space_or_uv_type: pack_as_float(yuv_color_space as u32),
-            yuv_format: pack_as_float(yuv_format as u32),
+            yuv_format_or_color_spaces: pack_as_float(yuv_format as u32),
             yuv_channel_bit_depth: pack_as_float(yuv_channel_bit_depth),
             uv_rects,
             transform,
@@ -374,7 +410,8 @@ impl CompositeInstance {
             features |= CompositeFeatures::NO_UV_CLAMP;
         }
 
-        if self.color == PremultipliedColorF::WHITE {
+        // The fast path doesn't convert between color spaces.
+        if self.color == PremultipliedColorF::WHITE && self.yuv_format_or_color_spaces == 0.0 {
             features |= CompositeFeatures::NO_COLOR_MODULATION
         }
 
@@ -606,6 +643,15 @@ impl ImageBrushData {
             0,
         ]
     }
+
+    /// Encode the data of an image which is converted to another color space
+    /// when it is drawn, see `encode_color_space_conversion`.
+    #[inline]
+    pub fn encode_with_color_space_conversion(&self, conversion: i32) -> [i32; 4] {
+        let mut data = self.encode();
+        data[3] = conversion;
+        data
+    }
 }
 
 // Represents the information about a transform palette
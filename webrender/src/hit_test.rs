@@ -2,13 +2,16 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{BorderRadius, ClipMode, HitTestItem, HitTestResult, ItemTag, PrimitiveFlags};
+use api::{BorderRadius, ClipMode, FillRule, HitTestItem, HitTestResult, ItemTag, PrimitiveFlags};
+use api::{ExternalScrollId, HitTestCoverage, HitTestRegionItem, HitTestRegionResult};
 use api::{PipelineId, ApiHitTester, ClipId};
 use api::units::*;
 use crate::clip::{ClipItemKind, ClipStore, ClipNode, rounded_rectangle_contains_point};
-use crate::clip::{polygon_contains_point};
+use crate::clip::{polygon_contains_point, path_contains_point};
+use crate::path::PathEdge;
 use crate::prim_store::PolygonKey;
 use crate::scene_builder_thread::Interners;
+use crate::spatial_node::SpatialNodeType;
 use crate::spatial_tree::{SpatialNodeIndex, SpatialTree};
 use crate::internal_types::{FastHashMap, FastHashSet, LayoutPrimitiveInfo};
 use std::ops;
@@ -48,6 +51,13 @@ impl ApiHitTester for SharedHitTester {
     ) -> HitTestResult {
         self.get_ref().hit_test(HitTest::new(pipeline_id, point))
     }
+
+    fn hit_test_polygon(&self,
+        pipeline_id: Option<PipelineId>,
+        points: &[WorldPoint],
+    ) -> HitTestRegionResult {
+        self.get_ref().hit_test_region(pipeline_id, points)
+    }
 }
 
 /// A copy of important spatial node data to use during hit testing. This a copy of
@@ -66,6 +76,13 @@ struct HitTestSpatialNode {
 
     /// The accumulated external scroll offset for this spatial node.
     external_scroll_offset: LayoutVector2D,
+
+    /// The parent of this node, if any.
+    parent: Option<SpatialNodeIndex>,
+
+    /// The external id of this node, if it is a scroll frame.
+    #[ignore_malloc_size_of = "simple"]
+    scroll_id: Option<ExternalScrollId>,
 }
 
 #[derive(MallocSizeOf)]
@@ -99,6 +116,14 @@ impl HitTestClipNode {
                     HitTestRegion::Rectangle(rect, ClipMode::Clip)
                 }
             }
+            ClipItemKind::Path { rect, handle } => {
+                let path = &interners.clip_path[handle];
+                HitTestRegion::Path(rect, path.fill_rule, path.edges.clone())
+            }
+            // Like image masks, text clips are hit tested by their bounds.
+            ClipItemKind::Text { rect, .. } => {
+                HitTestRegion::Rectangle(rect, ClipMode::Clip)
+            }
             ClipItemKind::BoxShadow { .. } => HitTestRegion::Invalid,
         };
 
@@ -298,6 +323,7 @@ enum HitTestRegion {
     Rectangle(LayoutRect, ClipMode),
     RoundedRectangle(LayoutRect, BorderRadius, ClipMode),
     Polygon(LayoutRect, PolygonKey),
+    Path(LayoutRect, FillRule, Vec<PathEdge>),
 }
 
 impl HitTestRegion {
@@ -313,6 +339,8 @@ impl HitTestRegion {
                 !rounded_rectangle_contains_point(point, &rect, &radii),
             HitTestRegion::Polygon(rect, polygon) =>
                 polygon_contains_point(point, &rect, &polygon),
+            HitTestRegion::Path(ref rect, fill_rule, ref edges) =>
+                path_contains_point(point, rect, fill_rule, edges),
             HitTestRegion::Invalid => true,
         }
     }
@@ -375,6 +403,11 @@ impl HitTester {
                     .get_world_viewport_transform(index)
                     .into_fast_transform(),
                 external_scroll_offset: spatial_tree.external_scroll_offset(index),
+                parent: node.parent,
+                scroll_id: match node.node_type {
+                    SpatialNodeType::ScrollFrame(ref info) => Some(info.external_id),
+                    _ => None,
+                },
             });
         }
     }
@@ -477,6 +510,208 @@ impl HitTester {
         result.items.dedup();
         result
     }
+
+    /// Find the items intersecting a polygon in world space. Clips are
+    /// approximated by their bounding rect, which keeps the clipped shape of
+    /// each item convex.
+    pub fn hit_test_region(
+        &self,
+        pipeline_id: Option<PipelineId>,
+        polygon: &[WorldPoint],
+    ) -> HitTestRegionResult {
+        let mut result = HitTestRegionResult::default();
+        if polygon.len() < 3 || signed_area(polygon) == 0.0 {
+            return result;
+        }
+
+        // The index of the result of each tag, to merge items sharing a tag.
+        let mut tag_indices = FastHashMap::default();
+
+        for item in self.scene.items.iter().rev() {
+            let scroll_node = &self.spatial_nodes[item.spatial_node_index.0 as usize];
+            let item_pipeline_id = scroll_node.pipeline_id;
+            match (pipeline_id, item_pipeline_id) {
+                (Some(id), node_id) if node_id != id => continue,
+                _ => {},
+            }
+
+            // Don't hit items with backface-visibility:hidden if they are facing the back.
+            if !item.is_backface_visible && scroll_node.world_content_transform.is_backface_visible() {
+                continue;
+            }
+
+            let local_rect = match item.rect.intersection(&item.clip_rect) {
+                Some(rect) => rect,
+                None => continue,
+            };
+            let mut shape = match world_quad(&local_rect, &scroll_node.world_content_transform) {
+                Some(quad) => quad.to_vec(),
+                None => continue,
+            };
+
+            let clip_nodes = &self.scene.clip_nodes[item.clip_nodes_range.start.0 as usize .. item.clip_nodes_range.end.0 as usize];
+            for clip_node in clip_nodes {
+                let clip_rect = match clip_node.region {
+                    HitTestRegion::Rectangle(rect, ClipMode::Clip) |
+                    HitTestRegion::RoundedRectangle(rect, _, ClipMode::Clip) |
+                    HitTestRegion::Polygon(rect, _) |
+                    HitTestRegion::Path(rect, ..) => rect,
+                    // Clip outs can't be approximated without making the
+                    // shape concave, so they are ignored.
+                    HitTestRegion::Rectangle(_, ClipMode::ClipOut) |
+                    HitTestRegion::RoundedRectangle(_, _, ClipMode::ClipOut) |
+                    HitTestRegion::Invalid => continue,
+                };
+
+                let transform = &self.spatial_nodes[clip_node.spatial_node_index.0 as usize].world_content_transform;
+                if let Some(quad) = world_quad(&clip_rect, transform) {
+                    shape = clip_convex_polygon(&shape, &quad);
+                }
+            }
+
+            if shape.len() < 3 {
+                continue;
+            }
+
+            let intersects_edges = polygon_edges_intersect(&shape, polygon);
+            let coverage = if !intersects_edges && shape.iter().all(|point| polygon_contains(polygon, point)) {
+                HitTestCoverage::Full
+            } else if intersects_edges ||
+                      polygon_contains(polygon, &shape[0]) ||
+                      polygon_contains(&shape, &polygon[0]) {
+                HitTestCoverage::Partial
+            } else {
+                continue;
+            };
+
+            let world_bounds = WorldRect::from_points(&shape);
+
+            match tag_indices.get(&(item_pipeline_id, item.tag)) {
+                Some(&index) => {
+                    let merged: &mut HitTestRegionItem = &mut result.items[index];
+                    merged.world_bounds = merged.world_bounds.union(&world_bounds);
+                    if coverage == HitTestCoverage::Partial {
+                        merged.coverage = HitTestCoverage::Partial;
+                    }
+                }
+                None => {
+                    tag_indices.insert((item_pipeline_id, item.tag), result.items.len());
+                    result.items.push(HitTestRegionItem {
+                        pipeline: item_pipeline_id,
+                        tag: item.tag,
+                        coverage,
+                        world_bounds,
+                        scroll_ids: self.scroll_ids(item.spatial_node_index),
+                    });
+                }
+            }
+        }
+
+        result
+    }
+
+    /// The external ids of the scroll frames enclosing a spatial node, from the
+    /// innermost to the outermost.
+    fn scroll_ids(&self, index: SpatialNodeIndex) -> Vec<ExternalScrollId> {
+        let mut scroll_ids = Vec::new();
+        let mut current = Some(index);
+        while let Some(index) = current {
+            let node = &self.spatial_nodes[index.0 as usize];
+            scroll_ids.extend(node.scroll_id);
+            current = node.parent;
+        }
+
+        scroll_ids
+    }
+}
+
+/// The corners of a rect in world space, or None if the transform can't map
+/// all of them.
+fn world_quad(
+    rect: &LayoutRect,
+    transform: &LayoutToWorldFastTransform,
+) -> Option<[WorldPoint; 4]> {
+    Some([
+        transform.transform_point2d(rect.min)?,
+        transform.transform_point2d(LayoutPoint::new(rect.max.x, rect.min.y))?,
+        transform.transform_point2d(rect.max)?,
+        transform.transform_point2d(LayoutPoint::new(rect.min.x, rect.max.y))?,
+    ])
+}
+
+fn cross(origin: &WorldPoint, a: &WorldPoint, b: &WorldPoint) -> f32 {
+    (*a - *origin).cross(*b - *origin)
+}
+
+/// Twice the signed area of a polygon, positive if its points go clockwise in
+/// world space (where y points down).
+fn signed_area(polygon: &[WorldPoint]) -> f32 {
+    polygon.iter().zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| a.to_vector().cross(b.to_vector()))
+        .sum()
+}
+
+/// Clip a polygon by a convex polygon, using the Sutherland-Hodgman algorithm.
+fn clip_convex_polygon(subject: &[WorldPoint], clip: &[WorldPoint]) -> Vec<WorldPoint> {
+    // The sign of the area tells which side of the clip edges is inside.
+    let area = signed_area(clip);
+    if area == 0.0 {
+        return Vec::new();
+    }
+
+    let mut output = subject.to_vec();
+    for (a, b) in clip.iter().zip(clip.iter().cycle().skip(1)) {
+        let input = std::mem::replace(&mut output, Vec::new());
+        let inside = |point: &WorldPoint| cross(a, b, point) * area >= 0.0;
+
+        for (i, current) in input.iter().enumerate() {
+            let previous = &input[(i + input.len() - 1) % input.len()];
+            if inside(current) != inside(previous) {
+                let d0 = cross(a, b, previous);
+                let d1 = cross(a, b, current);
+                output.push(previous.lerp(*current, d0 / (d0 - d1)));
+            }
+            if inside(current) {
+                output.push(*current);
+            }
+        }
+
+        if output.is_empty() {
+            break;
+        }
+    }
+
+    output
+}
+
+/// Whether a point is inside a polygon, using the even-odd rule.
+fn polygon_contains(polygon: &[WorldPoint], point: &WorldPoint) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y) &&
+           point.x < a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y) {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+/// Whether any edge of a polygon crosses an edge of the other.
+fn polygon_edges_intersect(p0: &[WorldPoint], p1: &[WorldPoint]) -> bool {
+    for (a, b) in p0.iter().zip(p0.iter().cycle().skip(1)) {
+        for (c, d) in p1.iter().zip(p1.iter().cycle().skip(1)) {
+            let d0 = cross(a, b, c);
+            let d1 = cross(a, b, d);
+            let d2 = cross(c, d, a);
+            let d3 = cross(c, d, b);
+            if d0 * d1 < 0.0 && d2 * d3 < 0.0 {
+                return true;
+            }
+        }
+    }
+
+    false
 }
 
 #[derive(MallocSizeOf)]
@@ -536,3 +771,209 @@ fn add_clips(
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use api::{ClipMode, HitTestCoverage, HitTestRegionResult, PipelineId};
+    use api::units::*;
+    use crate::internal_types::FastHashMap;
+    use crate::spatial_tree::SpatialNodeIndex;
+    use crate::util::LayoutToWorldFastTransform;
+    use std::sync::Arc;
+    use super::{ClipNodeIndex, HitTestClipNode, HitTestRegion, HitTestSpatialNode, HitTester};
+    use super::{HitTestingItem, HitTestingScene, HitTestingSceneStats};
+    use super::{clip_convex_polygon, polygon_contains, polygon_edges_intersect};
+
+    fn point(x: f32, y: f32) -> WorldPoint {
+        WorldPoint::new(x, y)
+    }
+
+    fn rect(x0: f32, y0: f32, x1: f32, y1: f32) -> LayoutRect {
+        LayoutRect {
+            min: LayoutPoint::new(x0, y0),
+            max: LayoutPoint::new(x1, y1),
+        }
+    }
+
+    fn quad(x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<WorldPoint> {
+        vec![point(x0, y0), point(x1, y0), point(x1, y1), point(x0, y1)]
+    }
+
+    /// A U shape, open at the bottom, with the notch spanning x from 100 to
+    /// 200 and y from 100 to 300.
+    fn u_shape() -> Vec<WorldPoint> {
+        vec![
+            point(0.0, 0.0), point(300.0, 0.0), point(300.0, 300.0), point(200.0, 300.0),
+            point(200.0, 100.0), point(100.0, 100.0), point(100.0, 300.0), point(0.0, 300.0),
+        ]
+    }
+
+    /// A hit tester for items in the root spatial node of one pipeline, tagged
+    /// with their index.
+    fn hit_tester(items: Vec<(LayoutRect, Vec<HitTestRegion>)>) -> HitTester {
+        let pipeline_id = PipelineId(0, 0);
+        let root = SpatialNodeIndex::new(0);
+        let mut scene = HitTestingScene::new(&HitTestingSceneStats::empty());
+        for (index, (rect, clips)) in items.into_iter().enumerate() {
+            let start = ClipNodeIndex(scene.clip_nodes.len() as u32);
+            for region in clips {
+                scene.clip_nodes.push(HitTestClipNode {
+                    region,
+                    spatial_node_index: root,
+                });
+            }
+            let end = ClipNodeIndex(scene.clip_nodes.len() as u32);
+            scene.items.push(HitTestingItem {
+                rect,
+                clip_rect: rect,
+                tag: (index as u64, 0),
+                is_backface_visible: true,
+                spatial_node_index: root,
+                clip_nodes_range: start .. end,
+            });
+        }
+
+        let mut pipeline_root_nodes = FastHashMap::default();
+        pipeline_root_nodes.insert(pipeline_id, root);
+        HitTester {
+            scene: Arc::new(scene),
+            spatial_nodes: vec![HitTestSpatialNode {
+                pipeline_id,
+                world_content_transform: LayoutToWorldFastTransform::identity(),
+                world_viewport_transform: LayoutToWorldFastTransform::identity(),
+                external_scroll_offset: LayoutVector2D::zero(),
+                parent: None,
+                scroll_id: None,
+            }],
+            pipeline_root_nodes,
+        }
+    }
+
+    /// The tags and coverage of the hit items, from front to back.
+    fn hits(result: HitTestRegionResult) -> Vec<(u64, HitTestCoverage)> {
+        result.items.iter().map(|item| (item.tag.0, item.coverage)).collect()
+    }
+
+    #[test]
+    fn test_polygon_contains() {
+        let u = u_shape();
+        assert!(polygon_contains(&u, &point(50.0, 200.0)));
+        assert!(polygon_contains(&u, &point(150.0, 50.0)));
+        assert!(!polygon_contains(&u, &point(150.0, 200.0)));
+        assert!(!polygon_contains(&u, &point(350.0, 50.0)));
+
+        // A polygon with no area contains nothing, not even its own points.
+        let line = [point(0.0, 0.0), point(50.0, 50.0), point(100.0, 100.0)];
+        assert!(!polygon_contains(&line, &point(50.0, 50.0)));
+        assert!(!polygon_contains(&line, &point(25.0, 20.0)));
+    }
+
+    #[test]
+    fn test_polygon_edges_intersect() {
+        let square = quad(0.0, 0.0, 100.0, 100.0);
+        assert!(polygon_edges_intersect(&square, &quad(50.0, 50.0, 150.0, 150.0)));
+        // Nested and disjoint polygons have no crossing edges.
+        assert!(!polygon_edges_intersect(&square, &quad(25.0, 25.0, 75.0, 75.0)));
+        assert!(!polygon_edges_intersect(&square, &quad(200.0, 0.0, 300.0, 100.0)));
+        // Neither do polygons that only touch along an edge or at a corner.
+        assert!(!polygon_edges_intersect(&square, &quad(100.0, 0.0, 200.0, 100.0)));
+        assert!(!polygon_edges_intersect(&square, &quad(100.0, 100.0, 200.0, 200.0)));
+        // Concave polygons cross the edges of the polygons in their notch.
+        assert!(polygon_edges_intersect(&u_shape(), &quad(50.0, 150.0, 150.0, 250.0)));
+        assert!(!polygon_edges_intersect(&u_shape(), &quad(120.0, 150.0, 180.0, 250.0)));
+    }
+
+    #[test]
+    fn test_clip_convex_polygon() {
+        let clipped = clip_convex_polygon(&quad(0.0, 0.0, 100.0, 100.0), &quad(50.0, 50.0, 150.0, 150.0));
+        assert_eq!(WorldRect::from_points(&clipped), WorldRect::new(point(50.0, 50.0), point(100.0, 100.0)));
+
+        // The winding of the clip doesn't matter.
+        let mut clip = quad(50.0, 50.0, 150.0, 150.0);
+        clip.reverse();
+        let clipped = clip_convex_polygon(&quad(0.0, 0.0, 100.0, 100.0), &clip);
+        assert_eq!(WorldRect::from_points(&clipped), WorldRect::new(point(50.0, 50.0), point(100.0, 100.0)));
+
+        assert!(clip_convex_polygon(&quad(0.0, 0.0, 100.0, 100.0), &quad(200.0, 0.0, 300.0, 100.0)).is_empty());
+        // A clip with no area clips everything out.
+        let line = [point(0.0, 0.0), point(50.0, 50.0), point(100.0, 100.0)];
+        assert!(clip_convex_polygon(&quad(0.0, 0.0, 100.0, 100.0), &line).is_empty());
+    }
+
+    #[test]
+    fn test_hit_test_region() {
+        let hit_tester = hit_tester(vec![
+            (rect(0.0, 0.0, 100.0, 100.0), Vec::new()),
+            (rect(200.0, 0.0, 300.0, 100.0), Vec::new()),
+        ]);
+
+        // Results are ordered from front to back.
+        let all = quad(-10.0, -10.0, 310.0, 110.0);
+        assert_eq!(
+            hits(hit_tester.hit_test_region(None, &all)),
+            vec![(1, HitTestCoverage::Full), (0, HitTestCoverage::Full)],
+        );
+        assert_eq!(
+            hits(hit_tester.hit_test_region(None, &quad(50.0, 50.0, 150.0, 150.0))),
+            vec![(0, HitTestCoverage::Partial)],
+        );
+        // A polygon inside an item partially covers it.
+        assert_eq!(
+            hits(hit_tester.hit_test_region(None, &quad(20.0, 20.0, 30.0, 30.0))),
+            vec![(0, HitTestCoverage::Partial)],
+        );
+
+        // Rects that only touch the edge of an item don't hit it.
+        assert!(hit_tester.hit_test_region(None, &quad(100.0, 0.0, 200.0, 100.0)).items.is_empty());
+        assert!(hit_tester.hit_test_region(None, &quad(100.0, 100.0, 200.0, 200.0)).items.is_empty());
+
+        // Nor do polygons with no area, or too few points.
+        let line = [point(10.0, 10.0), point(50.0, 50.0), point(90.0, 90.0)];
+        assert!(hit_tester.hit_test_region(None, &line).items.is_empty());
+        assert!(hit_tester.hit_test_region(None, &all[.. 2]).items.is_empty());
+
+        // Other pipelines are filtered out.
+        assert!(hit_tester.hit_test_region(Some(PipelineId(1, 0)), &all).items.is_empty());
+    }
+
+    #[test]
+    fn test_hit_test_concave_region() {
+        let hit_tester = hit_tester(vec![
+            // In the notch of the U.
+            (rect(120.0, 150.0, 180.0, 250.0), Vec::new()),
+            // Across its left arm.
+            (rect(50.0, 150.0, 150.0, 250.0), Vec::new()),
+            // Inside its left arm.
+            (rect(20.0, 150.0, 80.0, 250.0), Vec::new()),
+        ]);
+
+        assert_eq!(
+            hits(hit_tester.hit_test_region(None, &u_shape())),
+            vec![(2, HitTestCoverage::Full), (1, HitTestCoverage::Partial)],
+        );
+    }
+
+    #[test]
+    fn test_hit_test_region_clips() {
+        let hit_tester = hit_tester(vec![
+            (rect(0.0, 0.0, 100.0, 100.0), vec![HitTestRegion::Rectangle(rect(0.0, 0.0, 50.0, 50.0), ClipMode::Clip)]),
+            // Clip outs are ignored, so this item is hit although it is
+            // entirely clipped out.
+            (rect(200.0, 0.0, 300.0, 100.0), vec![HitTestRegion::Rectangle(rect(200.0, 0.0, 300.0, 100.0), ClipMode::ClipOut)]),
+        ]);
+
+        assert!(hit_tester.hit_test_region(None, &quad(60.0, 60.0, 90.0, 90.0)).items.is_empty());
+        // The clipped item is fully covered once the clip is applied.
+        assert_eq!(
+            hits(hit_tester.hit_test_region(None, &quad(-10.0, -10.0, 60.0, 60.0))),
+            vec![(0, HitTestCoverage::Full)],
+        );
+        let result = hit_tester.hit_test_region(None, &quad(-10.0, -10.0, 60.0, 60.0));
+        assert_eq!(result.items[0].world_bounds, WorldRect::new(point(0.0, 0.0), point(50.0, 50.0)));
+
+        assert_eq!(
+            hits(hit_tester.hit_test_region(None, &quad(250.0, 50.0, 260.0, 60.0))),
+            vec![(1, HitTestCoverage::Partial)],
+        );
+    }
+}
@@ -396,6 +396,9 @@ macro_rules! enumerate_interners {
             filter_data: FilterDataIntern,
             backdrop: Backdrop,
             polygon: PolygonIntern,
+            path: PathPrim,
+            clip_path: ClipPathIntern,
+            clip_text: ClipTextIntern,
         }
     }
 }
@@ -77,12 +77,14 @@ extern crate svg_fmt;
 #[macro_use]
 mod profiler;
 
+mod animation;
 mod batch;
 mod border;
 mod box_shadow;
 #[cfg(any(feature = "capture", feature = "replay"))]
 mod capture;
 mod clip;
+mod color_space;
 mod space;
 mod spatial_tree;
 mod composite;
@@ -104,6 +106,7 @@ mod gpu_types;
 mod hit_test;
 mod internal_types;
 mod lru_cache;
+mod path;
 mod picture;
 mod prepare;
 mod prim_store;
@@ -124,6 +127,7 @@ mod spatial_node;
 mod texture_pack;
 mod texture_cache;
 mod tile_cache;
+mod tone_map;
 mod util;
 mod visibility;
 mod api_resources;
@@ -154,6 +158,7 @@ mod platform {
     }
     #[cfg(any(target_os = "android", all(unix, not(target_os = "macos"))))]
     pub mod unix {
+        mod colr;
         pub mod font;
     }
     #[cfg(target_os = "windows")]
@@ -218,6 +223,8 @@ pub use crate::hit_test::SharedHitTester;
 pub use crate::internal_types::FastHashMap;
 pub use crate::screen_capture::{AsyncScreenshotHandle, RecordedFrameHandle};
 pub use crate::texture_cache::TextureCacheConfig;
+pub use crate::tone_map::{HdrOptions, ToneMapOperator};
+pub use crate::glyph_cache::GlyphDiskCacheOptions;
 pub use api as webrender_api;
 pub use webrender_build::shader::ProgramSourceDigest;
 pub use crate::picture::{TileDescriptor, TileId, InvalidationReason};
@@ -225,6 +232,8 @@ pub use crate::picture::{PrimitiveCompareResult, PrimitiveCompareResultDetail, C
 pub use crate::picture::{TileNode, TileNodeKind, TileSerializer, TileCacheInstanceSerializer, TileOffset, TileCacheLoggerUpdateLists};
 pub use crate::intern::ItemUid;
 pub use crate::render_api::*;
+#[cfg(feature = "replay")]
+pub use crate::render_backend::read_capture_scenes;
 pub use crate::tile_cache::{PictureCacheDebugInfo, DirtyTileDebugInfo, TileDebugInfo, SliceDebugInfo};
 
 #[cfg(feature = "sw_compositor")]
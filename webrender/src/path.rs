@@ -0,0 +1,578 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Vector path geometry.
+//!
+//! Paths are rasterized on the GPU from a flat list of line edges, by
+//! computing the winding number of each pixel. This module turns the
+//! commands of a path into such a list: curves are flattened into polylines,
+//! and strokes (including joins, caps and dashes) are converted into a set of
+//! polygons which, filled with the nonzero rule, cover the stroked area.
+
+use api::{LineCap, LineJoin, PathCommand, PathStroke};
+use api::units::*;
+use std::f32::consts::PI;
+
+/// The maximum number of edges of a single path. This is bounded by the
+/// number of blocks a single GPU cache allocation can hold.
+pub const MAX_PATH_EDGES: usize = 1024;
+
+/// The default flattening tolerance, in local pixels.
+pub const PATH_TOLERANCE: f32 = 0.1;
+
+/// Maximum number of line segments a single curve is flattened into.
+const MAX_CURVE_SEGMENTS: usize = 256;
+
+/// The number of times the flattening tolerance is doubled when trying to
+/// fit a path within MAX_PATH_EDGES.
+const MAX_TOLERANCE_STEPS: usize = 6;
+
+/// The most dashes a stroke is split into. Each dash adds at least four
+/// edges, so a path with more dashes wouldn't fit within MAX_PATH_EDGES.
+const MAX_DASH_COUNT: usize = MAX_PATH_EDGES / 4;
+
+/// A single straight edge of a flattened path.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Copy, Clone, Debug, MallocSizeOf, PartialEq)]
+pub struct PathEdge {
+    pub from: LayoutPoint,
+    pub to: LayoutPoint,
+}
+
+/// A flattened sub-path.
+#[derive(Clone, Debug, Default)]
+pub struct Polyline {
+    pub points: Vec<LayoutPoint>,
+    pub closed: bool,
+}
+
+impl Polyline {
+    fn push(&mut self, point: LayoutPoint) {
+        // Skip degenerate segments, they have no direction to stroke along.
+        if self.points.last() != Some(&point) {
+            self.points.push(point);
+        }
+    }
+
+    /// The length of the polyline, including the closing segment if closed.
+    fn length(&self) -> f32 {
+        let mut length: f32 = self.points.windows(2).map(|s| (s[1] - s[0]).length()).sum();
+        if self.closed && self.points.len() > 1 {
+            length += (self.points[0] - *self.points.last().unwrap()).length();
+        }
+        length
+    }
+}
+
+/// Flatten the commands of a path into polylines, approximating curves
+/// with line segments no further than `tolerance` from the curve.
+pub fn flatten_path<I>(commands: I, tolerance: f32) -> Vec<Polyline>
+where
+    I: IntoIterator<Item = PathCommand>,
+{
+    let mut polylines = Vec::new();
+    let mut current = Polyline::default();
+    let mut start = LayoutPoint::zero();
+    let mut pen = LayoutPoint::zero();
+    // Whether the current sub-path has any segments. A lone move-to is not
+    // drawn, but a zero length segment still gets caps when stroked.
+    let mut has_segments = false;
+
+    fn finish(polylines: &mut Vec<Polyline>, current: &mut Polyline, has_segments: &mut bool) {
+        let polyline = std::mem::take(current);
+        if *has_segments && !polyline.points.is_empty() {
+            polylines.push(polyline);
+        }
+        *has_segments = false;
+    }
+
+    for command in commands {
+        match command {
+            PathCommand::MoveTo(to) => {
+                finish(&mut polylines, &mut current, &mut has_segments);
+                current.push(to);
+                start = to;
+                pen = to;
+            }
+            PathCommand::LineTo(to) => {
+                if current.points.is_empty() {
+                    current.push(pen);
+                }
+                has_segments = true;
+                current.push(to);
+                pen = to;
+            }
+            PathCommand::QuadTo(ctrl, to) => {
+                if current.points.is_empty() {
+                    current.push(pen);
+                }
+                has_segments = true;
+                let dd = (pen.to_vector() - ctrl.to_vector() * 2.0 + to.to_vector()).length();
+                let count = curve_segment_count(dd / (4.0 * tolerance));
+                for i in 1 ..= count {
+                    let t = i as f32 / count as f32;
+                    let mt = 1.0 - t;
+                    let p = pen.to_vector() * (mt * mt) +
+                        ctrl.to_vector() * (2.0 * mt * t) +
+                        to.to_vector() * (t * t);
+                    current.push(p.to_point());
+                }
+                pen = to;
+            }
+            PathCommand::CubicTo(ctrl1, ctrl2, to) => {
+                if current.points.is_empty() {
+                    current.push(pen);
+                }
+                has_segments = true;
+                let dd1 = (pen.to_vector() - ctrl1.to_vector() * 2.0 + ctrl2.to_vector()).length();
+                let dd2 = (ctrl1.to_vector() - ctrl2.to_vector() * 2.0 + to.to_vector()).length();
+                let count = curve_segment_count(3.0 * dd1.max(dd2) / (4.0 * tolerance));
+                for i in 1 ..= count {
+                    let t = i as f32 / count as f32;
+                    let mt = 1.0 - t;
+                    let p = pen.to_vector() * (mt * mt * mt) +
+                        ctrl1.to_vector() * (3.0 * mt * mt * t) +
+                        ctrl2.to_vector() * (3.0 * mt * t * t) +
+                        to.to_vector() * (t * t * t);
+                    current.push(p.to_point());
+                }
+                pen = to;
+            }
+            PathCommand::Close => {
+                if !current.points.is_empty() {
+                    current.closed = true;
+                    has_segments = true;
+                    finish(&mut polylines, &mut current, &mut has_segments);
+                }
+                // A new sub-path implicitly starts at the start of the closed one.
+                pen = start;
+            }
+        }
+    }
+
+    finish(&mut polylines, &mut current, &mut has_segments);
+
+    polylines
+}
+
+/// The number of segments required to flatten a curve, given the square of
+/// the ideal (fractional) count.
+fn curve_segment_count(count_squared: f32) -> usize {
+    if !count_squared.is_finite() {
+        return 1;
+    }
+    (count_squared.sqrt().ceil() as usize).max(1).min(MAX_CURVE_SEGMENTS)
+}
+
+/// Map the points of a path command.
+pub fn map_command<F>(command: PathCommand, map: F) -> PathCommand
+where
+    F: Fn(LayoutPoint) -> LayoutPoint,
+{
+    match command {
+        PathCommand::MoveTo(p) => PathCommand::MoveTo(map(p)),
+        PathCommand::LineTo(p) => PathCommand::LineTo(map(p)),
+        PathCommand::QuadTo(a, b) => PathCommand::QuadTo(map(a), map(b)),
+        PathCommand::CubicTo(a, b, c) => PathCommand::CubicTo(map(a), map(b), map(c)),
+        PathCommand::Close => PathCommand::Close,
+    }
+}
+
+/// Collect the edges of a set of polylines for filling. Every polyline is
+/// implicitly closed.
+pub fn fill_edges(polylines: &[Polyline], edges: &mut Vec<PathEdge>) {
+    for polyline in polylines {
+        let points = &polyline.points;
+        if points.len() < 2 {
+            continue;
+        }
+        for i in 0 .. points.len() {
+            let from = points[i];
+            let to = points[(i + 1) % points.len()];
+            if from != to {
+                edges.push(PathEdge { from, to });
+            }
+        }
+    }
+}
+
+/// Convert the outline of a set of polylines into polygons covering the
+/// stroked area, and collect their edges. The polygons all have the same
+/// orientation, so the result must be filled with the nonzero rule.
+pub fn stroke_edges(
+    polylines: &[Polyline],
+    stroke: &PathStroke,
+    tolerance: f32,
+    edges: &mut Vec<PathEdge>,
+) {
+    let half_width = 0.5 * stroke.width;
+    if !half_width.is_finite() || half_width <= 0.0 {
+        return;
+    }
+
+    let mut stroker = Stroker {
+        half_width,
+        stroke,
+        tolerance,
+        edges,
+    };
+
+    // Paths with too many dashes are stroked solid, rather than creating a
+    // polygon for each dash.
+    let dashes = stroke.dashes();
+    let dash_length: f32 = dashes.iter().sum();
+    let path_length: f32 = polylines.iter().map(Polyline::length).sum();
+    let dash_count = path_length / dash_length * 0.5 * dashes.len() as f32;
+    if dash_length > 0.0 && dashes.iter().all(|d| *d >= 0.0) && dash_count <= MAX_DASH_COUNT as f32 {
+        for polyline in polylines {
+            for dash in apply_dashes(polyline, dashes, stroke.dash_offset) {
+                stroker.stroke_polyline(&dash);
+            }
+        }
+    } else {
+        for polyline in polylines {
+            stroker.stroke_polyline(polyline);
+        }
+    }
+}
+
+/// Split a polyline into the open polylines of each "on" dash interval.
+fn apply_dashes(polyline: &Polyline, dashes: &[f32], dash_offset: f32) -> Vec<Polyline> {
+    // An odd number of dash entries is repeated to yield an even number,
+    // as in SVG.
+    let pattern: Vec<f32> = if dashes.len() % 2 == 1 {
+        dashes.iter().chain(dashes.iter()).cloned().collect()
+    } else {
+        dashes.to_vec()
+    };
+    let period: f32 = pattern.iter().sum();
+
+    let mut index = 0;
+    let mut remaining = pattern[0];
+    let mut offset = dash_offset % period;
+    if offset < 0.0 {
+        offset += period;
+    }
+    while offset > 0.0 {
+        if offset >= remaining {
+            offset -= remaining;
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        } else {
+            remaining -= offset;
+            offset = 0.0;
+        }
+    }
+
+    let mut points = polyline.points.clone();
+    if polyline.closed && points.len() > 1 {
+        points.push(points[0]);
+    }
+
+    let mut result = Vec::new();
+    let mut current = Polyline::default();
+    if index % 2 == 0 && !points.is_empty() {
+        current.push(points[0]);
+    }
+
+    for segment in points.windows(2) {
+        let (mut from, to) = (segment[0], segment[1]);
+        let mut length = (to - from).length();
+        let dir = (to - from) / length;
+
+        while length > remaining {
+            from += dir * remaining;
+            length -= remaining;
+            if index % 2 == 0 {
+                current.push(from);
+                result.push(std::mem::take(&mut current));
+            } else {
+                current.push(from);
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+
+        remaining -= length;
+        if index % 2 == 0 {
+            current.push(to);
+        }
+    }
+
+    if index % 2 == 0 && current.points.len() > 1 {
+        result.push(current);
+    }
+
+    result
+}
+
+struct Stroker<'a> {
+    half_width: f32,
+    stroke: &'a PathStroke,
+    tolerance: f32,
+    edges: &'a mut Vec<PathEdge>,
+}
+
+impl<'a> Stroker<'a> {
+    fn stroke_polyline(&mut self, polyline: &Polyline) {
+        let points = &polyline.points;
+
+        if points.len() == 1 {
+            // A zero length sub-path is only visible with round or square caps.
+            self.add_cap(points[0], LayoutVector2D::new(1.0, 0.0), true);
+            self.add_cap(points[0], LayoutVector2D::new(-1.0, 0.0), true);
+            return;
+        }
+
+        let segment_count = if polyline.closed { points.len() } else { points.len() - 1 };
+        for i in 0 .. segment_count {
+            let from = points[i];
+            let to = points[(i + 1) % points.len()];
+            let normal = self.normal(from, to);
+            self.add_polygon(&[from + normal, to + normal, to - normal, from - normal]);
+        }
+
+        let join_range = if polyline.closed { 0 .. points.len() } else { 1 .. points.len() - 1 };
+        for i in join_range {
+            let prev = points[(i + points.len() - 1) % points.len()];
+            let next = points[(i + 1) % points.len()];
+            self.add_join(prev, points[i], next);
+        }
+
+        if !polyline.closed {
+            let last = points.len() - 1;
+            self.add_cap(points[0], (points[0] - points[1]).normalize(), false);
+            self.add_cap(points[last], (points[last] - points[last - 1]).normalize(), false);
+        }
+    }
+
+    fn normal(&self, from: LayoutPoint, to: LayoutPoint) -> LayoutVector2D {
+        let dir = (to - from).normalize();
+        LayoutVector2D::new(-dir.y, dir.x) * self.half_width
+    }
+
+    fn add_join(&mut self, prev: LayoutPoint, point: LayoutPoint, next: LayoutPoint) {
+        let n0 = self.normal(prev, point);
+        let n1 = self.normal(point, next);
+        let cross = (point - prev).cross(next - point);
+        if cross == 0.0 && n0.dot(n1) > 0.0 {
+            // Collinear segments need no join.
+            return;
+        }
+
+        match self.stroke.line_join {
+            LineJoin::Round => {
+                self.add_circle(point);
+            }
+            LineJoin::Miter | LineJoin::Bevel => {
+                // The join is on the outer side of the turn.
+                let side = if cross > 0.0 { -1.0 } else { 1.0 };
+                let a = point + n0 * side;
+                let b = point + n1 * side;
+                // Intersection of the outer edges of both segments.
+                let hw2 = self.half_width * self.half_width;
+                let miter = (n0 + n1) * (hw2 / (hw2 + n0.dot(n1)));
+                let miter_ratio = miter.length() / self.half_width;
+                if self.stroke.line_join == LineJoin::Miter &&
+                   miter_ratio.is_finite() &&
+                   miter_ratio <= self.stroke.miter_limit {
+                    self.add_polygon(&[point, a, point + miter * side, b]);
+                } else {
+                    self.add_polygon(&[point, a, b]);
+                }
+            }
+        }
+    }
+
+    /// Add the cap at the end of an open sub-path, where `dir` points
+    /// outwards from the sub-path.
+    fn add_cap(&mut self, point: LayoutPoint, dir: LayoutVector2D, zero_length: bool) {
+        match self.stroke.line_cap {
+            LineCap::Butt => {}
+            LineCap::Round => {
+                // Degenerate sub-paths are called once per side, so only
+                // add the dot once.
+                if !zero_length || dir.x > 0.0 {
+                    self.add_circle(point);
+                }
+            }
+            LineCap::Square => {
+                let extent = dir * self.half_width;
+                let normal = LayoutVector2D::new(-extent.y, extent.x);
+                self.add_polygon(&[
+                    point + normal,
+                    point + normal + extent,
+                    point - normal + extent,
+                    point - normal,
+                ]);
+            }
+        }
+    }
+
+    fn add_circle(&mut self, center: LayoutPoint) {
+        let radius = self.half_width;
+        let max_angle = 2.0 * (1.0 - (self.tolerance / radius).min(1.0)).acos();
+        let count = ((2.0 * PI / max_angle).ceil() as usize).max(8).min(64);
+        let points: Vec<LayoutPoint> = (0 .. count).map(|i| {
+            let angle = 2.0 * PI * i as f32 / count as f32;
+            center + LayoutVector2D::new(angle.cos(), angle.sin()) * radius
+        }).collect();
+        self.add_polygon(&points);
+    }
+
+    /// Add the edges of a polygon, reversing it as needed so that all the
+    /// polygons of a stroke have a positive winding.
+    fn add_polygon(&mut self, points: &[LayoutPoint]) {
+        let mut area: f32 = 0.0;
+        for i in 0 .. points.len() {
+            area += points[i].to_vector().cross(points[(i + 1) % points.len()].to_vector());
+        }
+        if area == 0.0 || !area.is_finite() {
+            return;
+        }
+
+        for i in 0 .. points.len() {
+            let (from, to) = if area > 0.0 {
+                (points[i], points[(i + 1) % points.len()])
+            } else {
+                (points[(i + 1) % points.len()], points[i])
+            };
+            self.edges.push(PathEdge { from, to });
+        }
+    }
+}
+
+/// Build the edges of a path with `build`, coarsening the flattening
+/// tolerance until they fit within MAX_PATH_EDGES. Paths that still don't
+/// fit produce no edges, and so are not drawn.
+pub fn fit_edges<F>(mut build: F) -> Vec<PathEdge>
+where
+    F: FnMut(f32) -> Vec<PathEdge>,
+{
+    let mut tolerance = PATH_TOLERANCE;
+    let mut edges = Vec::new();
+    for _ in 0 .. MAX_TOLERANCE_STEPS {
+        edges = build(tolerance);
+        if edges.len() <= MAX_PATH_EDGES {
+            return edges;
+        }
+        tolerance *= 2.0;
+    }
+
+    warn!("Path has too many edges ({}), skipping", edges.len());
+    Vec::new()
+}
+
+/// Compute the winding number of `point` with respect to a set of edges.
+pub fn winding_number(edges: &[PathEdge], point: LayoutPoint) -> i32 {
+    let mut winding = 0;
+    for edge in edges {
+        let (a, b) = (edge.from, edge.to);
+        if a.y <= point.y {
+            if b.y > point.y && (b - a).cross(point - a) > 0.0 {
+                winding += 1;
+            }
+        } else if b.y <= point.y && (b - a).cross(point - a) < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+#[cfg(test)]
+fn square(x0: f32, y0: f32, x1: f32, y1: f32) -> Vec<PathCommand> {
+    vec![
+        PathCommand::MoveTo(LayoutPoint::new(x0, y0)),
+        PathCommand::LineTo(LayoutPoint::new(x1, y0)),
+        PathCommand::LineTo(LayoutPoint::new(x1, y1)),
+        PathCommand::LineTo(LayoutPoint::new(x0, y1)),
+        PathCommand::Close,
+    ]
+}
+
+#[test]
+fn flatten_curves() {
+    let polylines = flatten_path(vec![
+        PathCommand::MoveTo(LayoutPoint::new(0.0, 0.0)),
+        PathCommand::QuadTo(LayoutPoint::new(50.0, 100.0), LayoutPoint::new(100.0, 0.0)),
+        PathCommand::CubicTo(
+            LayoutPoint::new(100.0, 50.0),
+            LayoutPoint::new(0.0, 50.0),
+            LayoutPoint::new(0.0, 0.0),
+        ),
+    ], PATH_TOLERANCE);
+
+    assert_eq!(polylines.len(), 1);
+    assert!(!polylines[0].closed);
+    assert!(polylines[0].points.len() > 10);
+    assert_eq!(polylines[0].points[0], LayoutPoint::new(0.0, 0.0));
+    assert_eq!(*polylines[0].points.last().unwrap(), LayoutPoint::new(0.0, 0.0));
+}
+
+#[test]
+fn fill_winding() {
+    let mut commands = square(0.0, 0.0, 10.0, 10.0);
+    commands.extend(square(2.0, 2.0, 8.0, 8.0));
+    let polylines = flatten_path(commands, PATH_TOLERANCE);
+    assert_eq!(polylines.len(), 2);
+    assert!(polylines.iter().all(|p| p.closed));
+
+    let mut edges = Vec::new();
+    fill_edges(&polylines, &mut edges);
+    assert_eq!(edges.len(), 8);
+
+    assert_eq!(winding_number(&edges, LayoutPoint::new(1.0, 5.0)).abs(), 1);
+    assert_eq!(winding_number(&edges, LayoutPoint::new(5.0, 5.0)).abs(), 2);
+    assert_eq!(winding_number(&edges, LayoutPoint::new(12.0, 5.0)), 0);
+}
+
+#[test]
+fn stroke_coverage() {
+    let polylines = flatten_path(vec![
+        PathCommand::MoveTo(LayoutPoint::new(0.0, 0.0)),
+        PathCommand::LineTo(LayoutPoint::new(10.0, 0.0)),
+        PathCommand::LineTo(LayoutPoint::new(10.0, 10.0)),
+    ], PATH_TOLERANCE);
+
+    let mut stroke = PathStroke::solid(2.0);
+    let mut edges = Vec::new();
+    stroke_edges(&polylines, &stroke, PATH_TOLERANCE, &mut edges);
+
+    assert!(winding_number(&edges, LayoutPoint::new(5.0, 0.5)) > 0);
+    assert!(winding_number(&edges, LayoutPoint::new(10.5, 5.0)) > 0);
+    // Miter join covers the outer corner.
+    assert!(winding_number(&edges, LayoutPoint::new(10.8, -0.8)) > 0);
+    assert_eq!(winding_number(&edges, LayoutPoint::new(5.0, 5.0)), 0);
+    // Butt caps don't extend past the end points.
+    assert_eq!(winding_number(&edges, LayoutPoint::new(-0.5, 0.0)), 0);
+
+    stroke.dash_array[0] = 2.0;
+    stroke.dash_array[1] = 2.0;
+    stroke.dash_count = 2;
+    edges.clear();
+    stroke_edges(&polylines, &stroke, PATH_TOLERANCE, &mut edges);
+    assert!(winding_number(&edges, LayoutPoint::new(1.0, 0.0)) > 0);
+    assert_eq!(winding_number(&edges, LayoutPoint::new(3.0, 0.0)), 0);
+}
+
+#[test]
+fn stroke_too_many_dashes() {
+    let polylines = flatten_path(vec![
+        PathCommand::MoveTo(LayoutPoint::new(0.0, 0.0)),
+        PathCommand::LineTo(LayoutPoint::new(1000.0, 0.0)),
+    ], PATH_TOLERANCE);
+
+    // A million dashes fall back to a solid stroke.
+    let mut stroke = PathStroke::solid(2.0);
+    stroke.dash_array[0] = 0.0005;
+    stroke.dash_array[1] = 0.0005;
+    stroke.dash_count = 2;
+    let mut edges = Vec::new();
+    stroke_edges(&polylines, &stroke, PATH_TOLERANCE, &mut edges);
+
+    let mut solid_edges = Vec::new();
+    stroke_edges(&polylines, &PathStroke::solid(2.0), PATH_TOLERANCE, &mut solid_edges);
+    assert_eq!(edges.len(), solid_edges.len());
+}
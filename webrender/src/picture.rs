@@ -94,10 +94,11 @@
 //! blend the overlay tile (this is not always optimal right now, but will be
 //! improved as a follow up).
 
-use api::{MixBlendMode, PremultipliedColorF, FilterPrimitiveKind};
+use api::{MixBlendMode, PremultipliedColorF, FilterPrimitiveKind, MorphologyOperator};
 use api::{PropertyBinding, PropertyBindingId, FilterPrimitive};
-use api::{DebugFlags, ImageKey, ColorF, ColorU, PrimitiveFlags};
-use api::{ImageRendering, ColorDepth, YuvRangedColorSpace, YuvFormat, AlphaType};
+use api::{DebugFlags, ImageKey, ColorF, ColorU, PrimitiveFlags, SnapshotImageKey};
+use api::{ImageRendering, ColorDepth, YuvRangedColorSpace, YuvFormat, AlphaType, RgbColorSpace};
+use api::YuvTransferFunction;
 use api::units::*;
 use crate::batch::BatchFilter;
 use crate::box_shadow::BLUR_SAMPLE_SCALE;
@@ -148,7 +149,7 @@ use crate::scene_builder_thread::InternerUpdates;
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::intern::{Internable, UpdateList};
 #[cfg(any(feature = "capture", feature = "replay"))]
-use crate::clip::{ClipIntern, PolygonIntern};
+use crate::clip::{ClipIntern, ClipPathIntern, ClipTextIntern, PolygonIntern};
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::filterdata::FilterDataIntern;
 #[cfg(any(feature = "capture", feature = "replay"))]
@@ -164,6 +165,8 @@ use crate::prim_store::image::{Image, YuvImage};
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::prim_store::line_dec::LineDecoration;
 #[cfg(any(feature = "capture", feature = "replay"))]
+use crate::prim_store::path::PathPrim;
+#[cfg(any(feature = "capture", feature = "replay"))]
 use crate::prim_store::picture::Picture;
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::prim_store::text_run::TextRun;
@@ -2550,6 +2553,8 @@ impl TileCacheInstance {
                 frame_context.global_device_pixel_scale,
                 &frame_context.global_screen_world_rect,
                 &mut frame_state.data_stores.clip,
+                &mut frame_state.data_stores.clip_path,
+                &frame_state.data_stores.clip_text,
                 true,
                 false,
             );
@@ -3041,8 +3046,19 @@ impl TileCacheInstance {
             gpu_cache,
         );
 
-        let is_opaque = resource_cache.get_image_properties(api_key)
-            .map_or(false, |properties| properties.descriptor.is_opaque());
+        let properties = resource_cache.get_image_properties(api_key);
+        let is_opaque = properties.map_or(false, |properties| properties.descriptor.is_opaque());
+        let color_space = properties.map_or(RgbColorSpace::Srgb, |properties| properties.descriptor.color_space);
+
+        // External images are attached to native compositor surfaces as is,
+        // so they can't be converted to the output color space.
+        if color_space != frame_context.config.output_color_space {
+            if let CompositorKind::Native { .. } = composite_state.compositor_kind {
+                if properties.map_or(false, |properties| properties.external_image.is_some()) {
+                    return false;
+                }
+            }
+        }
 
         self.setup_compositor_surfaces_impl(
             sub_slice_index,
@@ -3054,6 +3070,7 @@ impl TileCacheInstance {
             frame_context,
             ExternalSurfaceDependency::Rgb {
                 image_dependency,
+                color_space,
             },
             &api_keys,
             resource_cache,
@@ -3586,6 +3603,12 @@ impl TileCacheInstance {
                     SurfacePromotionResult::Success => true,
                 };
 
+                // The composite shaders don't decode PQ, so draw these into the
+                // picture cache tiles instead.
+                if prim_data.kind.transfer_function != YuvTransferFunction::Sdr {
+                    promote_to_surface = false;
+                }
+
                 // TODO(gw): When we support RGBA images for external surfaces, we also
                 //           need to check if opaque (YUV images are implicitly opaque).
 
@@ -3692,6 +3715,7 @@ impl TileCacheInstance {
                 }
             }
             PrimitiveInstanceKind::LineDecoration { .. } |
+            PrimitiveInstanceKind::Path { .. } |
             PrimitiveInstanceKind::NormalBorder { .. } |
             PrimitiveInstanceKind::TextRun { .. } |
             PrimitiveInstanceKind::Backdrop { .. } => {
@@ -4271,6 +4295,8 @@ bitflags! {
         const PRESERVE3D = 4;
         /// A backdrop that is reused which requires a surface.
         const BACKDROP = 8;
+        /// The surface is copied into a snapshot image.
+        const SNAPSHOT = 16;
     }
 }
 
@@ -4354,7 +4380,43 @@ impl PictureCompositeMode {
                             input_rect.translate(primitive.offset * Scale::new(1.0))
                         },
 
-                        FilterPrimitiveKind::Flood(..) => picture_rect,
+                        FilterPrimitiveKind::Morphology(ref primitive) => {
+                            let input = primitive.input.to_index(cur_index).map(|index| output_rects[index]).unwrap_or(picture_rect);
+                            match primitive.operator {
+                                // Dilation grows the content by the radius, erosion only shrinks it.
+                                // The radii were clamped when the display list was sanitized.
+                                MorphologyOperator::Dilate => {
+                                    input.inflate(primitive.radius_x.ceil(), primitive.radius_y.ceil())
+                                }
+                                MorphologyOperator::Erode => input,
+                            }
+                        }
+                        FilterPrimitiveKind::ConvolveMatrix(ref primitive) => {
+                            // The kernel can spread content by up to its size in either direction.
+                            let input = primitive.input.to_index(cur_index).map(|index| output_rects[index]).unwrap_or(picture_rect);
+                            input.inflate(primitive.order_x as f32, primitive.order_y as f32)
+                        }
+                        FilterPrimitiveKind::DisplacementMap(ref primitive) => {
+                            // Pixels move by at most half the scale in each direction.
+                            let input = primitive.input1.to_index(cur_index).map(|index| output_rects[index]).unwrap_or(picture_rect);
+                            let inflation_factor = (primitive.scale.abs() * 0.5).ceil();
+                            input.inflate(inflation_factor, inflation_factor)
+                        }
+                        FilterPrimitiveKind::Merge(ref primitive) => {
+                            primitive.inputs().iter().fold(PictureRect::zero(), |rect, input| {
+                                rect.union(&input.to_index(cur_index).map(|index| output_rects[index]).unwrap_or(picture_rect))
+                            })
+                        }
+                        FilterPrimitiveKind::Image(ref primitive) => {
+                            picture_rect.union(&layout_rect_as_picture_rect(&primitive.rect))
+                        }
+
+                        // These fill the whole filter region, regardless of their input.
+                        FilterPrimitiveKind::Flood(..) |
+                        FilterPrimitiveKind::Turbulence(..) |
+                        FilterPrimitiveKind::DiffuseLighting(..) |
+                        FilterPrimitiveKind::SpecularLighting(..) |
+                        FilterPrimitiveKind::Tile(..) => picture_rect,
                     };
                     output_rects.push(output_rect);
                     result_rect = result_rect.union(&output_rect);
@@ -4602,12 +4664,16 @@ pub struct PictureOptions {
     /// If true, WR should inflate the bounding rect of primitives when
     /// using a filter effect that requires inflation.
     pub inflate_if_required: bool,
+    /// If set, the surface of this picture is copied into the given
+    /// snapshot image each time it is rendered.
+    pub snapshot: Option<SnapshotImageKey>,
 }
 
 impl Default for PictureOptions {
     fn default() -> Self {
         PictureOptions {
             inflate_if_required: true,
+            snapshot: None,
         }
     }
 }
@@ -5879,6 +5945,10 @@ impl PicturePrimitive {
                             uv_rect_kind,
                             picture_task_id,
                             device_pixel_scale,
+                            clipped.min,
+                            frame_state.resource_cache,
+                            frame_state.gpu_cache,
+                            &mut self.extra_gpu_data_handles,
                         );
 
                         primary_render_task_id = Some(filter_task_id);
@@ -6042,6 +6112,7 @@ impl PicturePrimitive {
         prim_list: PrimitiveList,
         context: PictureContext,
         state: PictureState,
+        frame_context: &FrameBuildingContext,
         frame_state: &mut FrameBuildingState,
     ) {
         // Pop any dirty regions this picture set
@@ -6049,6 +6120,37 @@ impl PicturePrimitive {
             frame_state.pop_dirty_region();
         }
 
+        // Copy the surface into the snapshot image once the primitives it
+        // contains are prepared, so that they can't depend on the snapshot.
+        // The snapshot is taken before any filters of the surface. Snapshotted
+        // stacking contexts are never promoted to tile caches during scene
+        // building, so a tiled surface is skipped rather than snapshotted.
+        if let Some(snapshot) = self.options.snapshot {
+            if let Some(RasterConfig { surface_index, .. }) = self.raster_config {
+                let task_id = match frame_state.surfaces[surface_index.0].render_tasks {
+                    Some(SurfaceRenderTasks::Simple(task_id)) => Some(task_id),
+                    Some(SurfaceRenderTasks::Chained { port_task_id, .. }) => Some(port_task_id),
+                    Some(SurfaceRenderTasks::Tiled(..)) => {
+                        warn!("Dropping snapshot {:?} of a tiled surface", snapshot);
+                        None
+                    }
+                    None => None,
+                };
+
+                if let Some(task_id) = task_id {
+                    let size = frame_state.rg_builder.get_task(task_id).location.size();
+                    frame_state.resource_cache.render_as_image(
+                        snapshot.as_image(),
+                        size,
+                        frame_context.fb_config.output_color_space,
+                        task_id,
+                        frame_state.rg_builder,
+                        frame_state.gpu_cache,
+                    );
+                }
+            }
+        }
+
         self.prim_list = prim_list;
         self.state = Some(state);
     }
@@ -6321,13 +6423,37 @@ impl PicturePrimitive {
                     }
                     PictureCompositeMode::SvgFilter(ref primitives, _) => {
                         let mut max = 0.0;
+                        let mut max_sample_distance: f32 = 0.0;
                         for primitive in primitives {
-                            if let FilterPrimitiveKind::Blur(ref blur) = primitive.kind {
-                                max = f32::max(max, blur.width);
-                                max = f32::max(max, blur.height);
+                            match primitive.kind {
+                                FilterPrimitiveKind::Blur(ref blur) => {
+                                    max = f32::max(max, blur.width);
+                                    max = f32::max(max, blur.height);
+                                }
+                                // These primitives read neighbouring pixels of their input, so the
+                                // content around the visible area must be drawn too.
+                                FilterPrimitiveKind::Morphology(ref morphology) => {
+                                    max_sample_distance = max_sample_distance
+                                        .max(morphology.radius_x)
+                                        .max(morphology.radius_y);
+                                }
+                                FilterPrimitiveKind::ConvolveMatrix(ref convolve) => {
+                                    max_sample_distance = max_sample_distance
+                                        .max(convolve.order_x as f32)
+                                        .max(convolve.order_y as f32);
+                                }
+                                FilterPrimitiveKind::DisplacementMap(ref displacement) => {
+                                    max_sample_distance = max_sample_distance.max(displacement.scale.abs() * 0.5);
+                                }
+                                FilterPrimitiveKind::DiffuseLighting(..) |
+                                FilterPrimitiveKind::SpecularLighting(..) => {
+                                    max_sample_distance = max_sample_distance.max(1.0);
+                                }
+                                _ => {}
                             }
                         }
-                        inflation_factor = clamp_blur_radius(max, scale_factors) * BLUR_SAMPLE_SCALE;
+                        inflation_factor = (clamp_blur_radius(max, scale_factors) * BLUR_SAMPLE_SCALE)
+                            .max(max_sample_distance.ceil());
                     }
                     PictureCompositeMode::Filter(Filter::DropShadows(ref shadows)) => {
                         // TODO(gw): This is incorrect, since we don't consider the drop shadow
@@ -7530,3 +7656,76 @@ fn get_relative_scale_offset(
 
     scale_offset
 }
+
+#[cfg(test)]
+fn svg_filter_rect(kinds: Vec<FilterPrimitiveKind>, picture_rect: PictureRect) -> PictureRect {
+    let primitives = kinds.into_iter().map(|kind| {
+        let mut primitive = FilterPrimitive { kind, ..FilterPrimitive::default() };
+        primitive.sanitize();
+        primitive
+    }).collect();
+    PictureCompositeMode::SvgFilter(primitives, Vec::new()).inflate_picture_rect(picture_rect, (1.0, 1.0))
+}
+
+#[test]
+fn test_svg_filter_morphology_rect() {
+    use api::{FilterPrimitiveInput, MorphologyPrimitive};
+
+    let picture_rect = PictureRect::new(PicturePoint::new(200.0, 200.0), PicturePoint::new(210.0, 210.0));
+    let morphology = |operator, radius| FilterPrimitiveKind::Morphology(MorphologyPrimitive {
+        input: FilterPrimitiveInput::Original,
+        radius_x: radius,
+        radius_y: radius,
+        operator,
+    });
+
+    assert_eq!(
+        svg_filter_rect(vec![morphology(MorphologyOperator::Dilate, 2.5)], picture_rect),
+        picture_rect.inflate(3.0, 3.0),
+    );
+    assert_eq!(svg_filter_rect(vec![morphology(MorphologyOperator::Erode, 20.0)], picture_rect), picture_rect);
+
+    // Huge, negative and NaN radii are clamped when the primitive is sanitized.
+    assert_eq!(
+        svg_filter_rect(vec![morphology(MorphologyOperator::Dilate, 100000.0)], picture_rect),
+        picture_rect.inflate(100.0, 100.0),
+    );
+    assert_eq!(svg_filter_rect(vec![morphology(MorphologyOperator::Dilate, -10.0)], picture_rect), picture_rect);
+    assert_eq!(svg_filter_rect(vec![morphology(MorphologyOperator::Dilate, f32::NAN)], picture_rect), picture_rect);
+}
+
+#[test]
+fn test_svg_filter_lighting_rect() {
+    use api::{DiffuseLightingPrimitive, FilterPrimitiveInput, OffsetPrimitive, SpecularLightingPrimitive};
+
+    let picture_rect = PictureRect::new(PicturePoint::new(0.0, 0.0), PicturePoint::new(100.0, 100.0));
+    let offset = |input, x, y| FilterPrimitiveKind::Offset(OffsetPrimitive {
+        input,
+        offset: LayoutVector2D::new(x, y),
+    });
+
+    // Both lighting primitives fill the filter region, even when their input
+    // was moved out of it, so offsetting the light by the same amount again
+    // lands on the moved input rather than past it.
+    let lights = [
+        FilterPrimitiveKind::DiffuseLighting(DiffuseLightingPrimitive {
+            input: FilterPrimitiveInput::OutputOfPrimitiveIndex(0),
+            ..DiffuseLightingPrimitive::default()
+        }),
+        FilterPrimitiveKind::SpecularLighting(SpecularLightingPrimitive {
+            input: FilterPrimitiveInput::OutputOfPrimitiveIndex(0),
+            ..SpecularLightingPrimitive::default()
+        }),
+    ];
+    for light in lights.iter() {
+        let rect = svg_filter_rect(
+            vec![
+                offset(FilterPrimitiveInput::Original, 200.0, 200.0),
+                *light,
+                offset(FilterPrimitiveInput::Previous, 200.0, 200.0),
+            ],
+            picture_rect,
+        );
+        assert_eq!(rect, PictureRect::new(PicturePoint::new(0.0, 0.0), PicturePoint::new(300.0, 300.0)));
+    }
+}
@@ -2,8 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{ColorU, FontKey, FontRenderMode, FontSize, GlyphDimensions};
-use api::{FontInstanceFlags, FontVariation, NativeFontHandle};
+use api::{ColorU, FontKey, FontRenderMode, FontSize, FontTemplate, GlyphDimensions};
+use api::{FontInstanceFlags, FontVariation, NativeFontHandle, PathCommand};
 use core_foundation::{array::{CFArray, CFArrayRef}, data::CFData};
 use core_foundation::base::TCFType;
 use core_foundation::dictionary::CFDictionary;
@@ -13,7 +13,7 @@ use core_graphics::base::{kCGImageAlphaNoneSkipFirst, kCGImageAlphaPremultiplied
 use core_graphics::base::{kCGBitmapByteOrder32Little};
 use core_graphics::color_space::CGColorSpace;
 use core_graphics::context::CGContext;
-use core_graphics::context::{CGBlendMode, CGTextDrawingMode};
+use core_graphics::context::{CGBlendMode, CGLineJoin, CGTextDrawingMode};
 use core_graphics::font::{CGFont, CGGlyph};
 use core_graphics::geometry::{CGAffineTransform, CGPoint, CGSize};
 use core_graphics::geometry::{CG_AFFINE_TRANSFORM_IDENTITY, CGRect};
@@ -150,6 +150,7 @@ fn get_glyph_metrics(
     x_offset: f64,
     y_offset: f64,
     extra_width: f64,
+    stroke_outset: f64,
 ) -> GlyphMetrics {
     let mut bounds = ct_font.get_bounding_rects_for_glyphs(kCTFontDefaultOrientation, &[glyph]);
 
@@ -198,10 +199,12 @@ fn get_glyph_metrics(
     // Note that this outset is to allow room for LCD smoothed glyphs. However, the correct outset
     // is not currently known, as CG dilates the outlines by some percentage.
     // This is taken from Skia.
-    left -= 1;
-    bottom -= 1;
-    right += 1;
-    top += 1;
+    // Strokes extend up to half of their width further.
+    let outset = 1 + stroke_outset.ceil() as i32;
+    left -= outset;
+    bottom -= outset;
+    right += outset;
+    top += outset;
 
     let width = right - left;
     let height = top - bottom;
@@ -226,6 +229,7 @@ extern {
     static kCTFontVariationAttribute: CFStringRef;
 
     fn CTFontCopyVariationAxes(font: CTFontRef) -> CFArrayRef;
+    fn CTGetCoreTextVersion() -> u32;
 }
 
 fn new_ct_font_with_variations(desc_or_font: &DescOrFont, size: f64, variations: &[FontVariation]) -> CTFont {
@@ -516,6 +520,7 @@ impl FontContext {
                     x_offset,
                     y_offset,
                     extra_strikes as f64 * pixel_step,
+                    font.get_stroke_width() * y_scale * 0.5,
                 );
                 if metrics.rasterized_width == 0 || metrics.rasterized_height == 0 {
                     None
@@ -568,6 +573,23 @@ impl FontContext {
         }
     }
 
+    pub fn has_color_layers(_template: &FontTemplate) -> bool {
+        false
+    }
+
+    pub fn has_glyph_outlines() -> bool {
+        false
+    }
+
+    /// The version of Core Text, as glyphs rasterized by other versions may differ.
+    pub fn rasterizer_version() -> String {
+        format!("Core Text {:#x}", unsafe { CTGetCoreTextVersion() })
+    }
+
+    pub fn get_glyph_outline(&mut self, _font: &FontInstance, _key: &GlyphKey) -> Option<Vec<PathCommand>> {
+        None
+    }
+
     pub fn prepare_font(font: &mut FontInstance) {
         // Sanitize the render mode for font smoothing. If font smoothing is supported,
         // then we just need to ensure the render mode is limited to what is supported.
@@ -626,6 +648,10 @@ impl FontContext {
         } else {
             GlyphType::Vector
         };
+        // Bitmap glyphs have no outline to stroke.
+        if glyph_type == GlyphType::Bitmap && font.is_stroked() {
+            return Err(GlyphRasterError::LoadFailed);
+        }
 
         let (mut shape, (x_offset, y_offset)) = match glyph_type {
             GlyphType::Bitmap => (FontTransform::identity(), (0.0, 0.0)),
@@ -677,6 +703,7 @@ impl FontContext {
             x_offset,
             y_offset,
             extra_strikes as f64 * pixel_step,
+            font.get_stroke_width() * y_scale * 0.5,
         );
         if metrics.rasterized_width == 0 || metrics.rasterized_height == 0 {
             return Err(GlyphRasterError::LoadFailed);
@@ -761,7 +788,14 @@ impl FontContext {
 
             // Set the text color and draw the glyphs.
             cg_context.set_rgb_fill_color(text_color, text_color, text_color, 1.0);
-            cg_context.set_text_drawing_mode(CGTextDrawingMode::CGTextFill);
+            if font.is_stroked() {
+                cg_context.set_rgb_stroke_color(text_color, text_color, text_color, 1.0);
+                cg_context.set_line_width(font.get_stroke_width() * y_scale);
+                cg_context.set_line_join(CGLineJoin::CGLineJoinRound);
+                cg_context.set_text_drawing_mode(CGTextDrawingMode::CGTextStroke);
+            } else {
+                cg_context.set_text_drawing_mode(CGTextDrawingMode::CGTextFill);
+            }
 
             // CG Origin is bottom left, WR is top left. Need -y offset
             let mut draw_origin = CGPoint {
@@ -0,0 +1,1159 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! COLR color glyphs.
+//!
+//! FreeType only rasterizes bitmap color glyphs (CBDT, sbix), so the COLR and
+//! CPAL tables are parsed and painted here, and FreeType is only used to load
+//! the outlines of the individual glyphs. Both the layers of COLR version 0
+//! and the paint graphs of version 1 (gradients, transforms and composite
+//! modes) are supported. Variable paints are drawn at the default instance of
+//! the font, their deltas are ignored.
+//!
+//! The paint graph of a glyph is first parsed into a `Paint` tree, with the
+//! palette colors resolved. It is then painted in device space into
+//! premultiplied floating point layers, one per node that needs compositing.
+
+use api::{ColorF, PathCommand};
+use api::units::LayoutPoint;
+use euclid::{Angle, vec2};
+use euclid::default::{Box2D, Point2D, Transform2D};
+use crate::path::{fill_edges, flatten_path, map_command, PathEdge, PATH_TOLERANCE};
+use std::cmp::Ordering;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Paint graphs can share nodes and reference other glyphs, so bound the
+/// amount of work a single glyph may cause.
+const MAX_PAINT_DEPTH: usize = 64;
+const MAX_PAINT_NODES: usize = 4096;
+
+/// The largest width or height of a painted color glyph, in pixels.
+const MAX_COLOR_GLYPH_SIZE: i32 = 4096;
+
+/// The number of sample rows per pixel when rasterizing outlines. Coverage is
+/// exact horizontally.
+const SUBSAMPLES: usize = 4;
+
+/// The palette index that refers to the text color.
+const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+
+/// Whether the sfnt font, or member `index` of a font collection, has a table
+/// with the given tag. Only the table directory is read.
+pub fn has_sfnt_table<R: Read + Seek>(reader: &mut R, index: u32, tag: &[u8; 4]) -> io::Result<bool> {
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    if &header[0 .. 4] == b"ttcf" {
+        let mut offset = [0u8; 4];
+        reader.seek(SeekFrom::Start(12 + 4 * index as u64))?;
+        reader.read_exact(&mut offset)?;
+        reader.seek(SeekFrom::Start(u32::from_be_bytes(offset) as u64))?;
+        reader.read_exact(&mut header)?;
+    }
+
+    let num_tables = u16::from_be_bytes([header[4], header[5]]);
+    let mut record = [0u8; 16];
+    for _ in 0 .. num_tables {
+        reader.read_exact(&mut record)?;
+        if &record[0 .. 4] == tag {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Provides the outlines of the glyphs that color glyphs are painted with.
+pub trait OutlineSource {
+    /// The outline of a glyph, in font units with y pointing up.
+    fn glyph_outline(&mut self, glyph: u16) -> Option<Vec<PathCommand>>;
+}
+
+/// Big-endian reads from a font table, which fail when out of bounds.
+#[derive(Clone, Copy)]
+struct Table<'a>(&'a [u8]);
+
+impl<'a> Table<'a> {
+    fn u8(&self, offset: usize) -> Option<u8> {
+        self.0.get(offset).cloned()
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.0.get(offset .. offset.checked_add(2)?)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u24(&self, offset: usize) -> Option<usize> {
+        let bytes = self.0.get(offset .. offset.checked_add(3)?)?;
+        Some((bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.0.get(offset .. offset.checked_add(4)?)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn fword(&self, offset: usize) -> Option<f32> {
+        Some(self.u16(offset)? as i16 as f32)
+    }
+
+    fn point(&self, offset: usize) -> Option<Point2D<f32>> {
+        Some(Point2D::new(self.fword(offset)?, self.fword(offset + 2)?))
+    }
+
+    fn f2dot14(&self, offset: usize) -> Option<f32> {
+        Some(self.u16(offset)? as i16 as f32 / 16384.0)
+    }
+
+    fn fixed(&self, offset: usize) -> Option<f32> {
+        Some(self.u32(offset)? as i32 as f32 / 65536.0)
+    }
+}
+
+/// A premultiplied RGBA color.
+type Rgba = [f32; 4];
+
+const TRANSPARENT: Rgba = [0.0; 4];
+
+fn premultiply(color: ColorF) -> Rgba {
+    [color.r * color.a, color.g * color.a, color.b * color.a, color.a]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Extend {
+    Pad,
+    Repeat,
+    Reflect,
+}
+
+/// The stops of a gradient.
+#[derive(Clone, Debug)]
+struct ColorLine {
+    extend: Extend,
+    /// Sorted by offset, with premultiplied colors.
+    stops: Vec<(f32, Rgba)>,
+}
+
+impl ColorLine {
+    fn color_at(&self, t: f32) -> Rgba {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return TRANSPARENT,
+        };
+        if !t.is_finite() {
+            return TRANSPARENT;
+        }
+
+        // Extend the range of the stops.
+        let range = last.0 - first.0;
+        let t = if range <= 0.0 {
+            t
+        } else {
+            match self.extend {
+                Extend::Pad => t,
+                Extend::Repeat => first.0 + (t - first.0).rem_euclid(range),
+                Extend::Reflect => {
+                    let u = ((t - first.0) / range).rem_euclid(2.0);
+                    first.0 + if u > 1.0 { 2.0 - u } else { u } * range
+                }
+            }
+        };
+
+        if t <= first.0 {
+            return first.1;
+        }
+        if t >= last.0 {
+            return last.1;
+        }
+        for pair in self.stops.windows(2) {
+            let ((t0, c0), (t1, c1)) = (pair[0], pair[1]);
+            if t <= t1 {
+                let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 1.0 };
+                return [
+                    c0[0] + (c1[0] - c0[0]) * f,
+                    c0[1] + (c1[1] - c0[1]) * f,
+                    c0[2] + (c1[2] - c0[2]) * f,
+                    c0[3] + (c1[3] - c0[3]) * f,
+                ];
+            }
+        }
+        last.1
+    }
+}
+
+/// The composite modes of PaintComposite, in the order of the specification.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CompositeMode {
+    Clear,
+    Src,
+    Dest,
+    SrcOver,
+    DestOver,
+    SrcIn,
+    DestIn,
+    SrcOut,
+    DestOut,
+    SrcAtop,
+    DestAtop,
+    Xor,
+    Plus,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Multiply,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl CompositeMode {
+    fn from_u8(mode: u8) -> Option<Self> {
+        use CompositeMode::*;
+        const MODES: [CompositeMode; 28] = [
+            Clear, Src, Dest, SrcOver, DestOver, SrcIn, DestIn, SrcOut, DestOut,
+            SrcAtop, DestAtop, Xor, Plus, Screen, Overlay, Darken, Lighten,
+            ColorDodge, ColorBurn, HardLight, SoftLight, Difference, Exclusion,
+            Multiply, Hue, Saturation, Color, Luminosity,
+        ];
+        MODES.get(mode as usize).cloned()
+    }
+}
+
+/// A node of a parsed paint graph, in font units with y pointing up.
+#[derive(Clone, Debug)]
+enum Paint {
+    Layers(Vec<Paint>),
+    Solid(Rgba),
+    LinearGradient {
+        line: ColorLine,
+        p0: Point2D<f32>,
+        p1: Point2D<f32>,
+        p2: Point2D<f32>,
+    },
+    RadialGradient {
+        line: ColorLine,
+        c0: Point2D<f32>,
+        r0: f32,
+        c1: Point2D<f32>,
+        r1: f32,
+    },
+    SweepGradient {
+        line: ColorLine,
+        center: Point2D<f32>,
+        /// In counter-clockwise degrees.
+        start_angle: f32,
+        end_angle: f32,
+    },
+    Glyph {
+        glyph: u16,
+        paint: Box<Paint>,
+    },
+    Transform {
+        transform: Transform2D<f32>,
+        paint: Box<Paint>,
+    },
+    Composite {
+        mode: CompositeMode,
+        source: Box<Paint>,
+        backdrop: Box<Paint>,
+    },
+}
+
+/// The COLR and CPAL tables of a font.
+pub struct ColorGlyphs {
+    colr: Vec<u8>,
+    cpal: Vec<u8>,
+}
+
+impl ColorGlyphs {
+    pub fn new(colr: Vec<u8>, cpal: Vec<u8>) -> Option<Self> {
+        match Table(&colr).u16(0)? {
+            0 | 1 => Some(ColorGlyphs { colr, cpal }),
+            version => {
+                debug!("Unsupported COLR version {}", version);
+                None
+            }
+        }
+    }
+
+    fn parser(&self, foreground: ColorF) -> Parser<'_> {
+        Parser {
+            colr: Table(&self.colr),
+            cpal: Table(&self.cpal),
+            foreground: premultiply(foreground),
+            nodes: 0,
+        }
+    }
+
+    pub fn has_glyph(&self, glyph: u32) -> bool {
+        let parser = self.parser(ColorF::BLACK);
+        parser.base_paint_offset(glyph).is_some() || parser.base_layers(glyph).is_some()
+    }
+
+    /// The pixel bounds of a color glyph, with y pointing up. The transform
+    /// maps font units to device pixels.
+    pub fn bounds(
+        &self,
+        glyph: u32,
+        transform: &Transform2D<f32>,
+        outlines: &mut dyn OutlineSource,
+    ) -> Option<Box2D<i32>> {
+        let mut parser = self.parser(ColorF::BLACK);
+        let bounds = match parser.clip_box(glyph) {
+            Some(clip_box) => transform.outer_transformed_box(&clip_box),
+            None => paint_bounds(&parser.glyph_paint(glyph)?, transform, outlines)?,
+        };
+        if bounds.is_empty() || !bounds.min.x.is_finite() || !bounds.max.x.is_finite() ||
+           !bounds.min.y.is_finite() || !bounds.max.y.is_finite() {
+            return None;
+        }
+
+        let bounds = Box2D::new(
+            Point2D::new(bounds.min.x.floor() as i32, bounds.min.y.floor() as i32),
+            Point2D::new(bounds.max.x.ceil() as i32, bounds.max.y.ceil() as i32),
+        );
+        if bounds.width() > MAX_COLOR_GLYPH_SIZE || bounds.height() > MAX_COLOR_GLYPH_SIZE {
+            warn!("Color glyph {} is too large ({:?})", glyph, bounds);
+            return None;
+        }
+        Some(bounds)
+    }
+
+    /// Paint a color glyph into premultiplied BGRA8 pixels covering `bounds`,
+    /// as returned by `bounds`. Layers that use the foreground color are
+    /// painted with the given color.
+    pub fn rasterize(
+        &self,
+        glyph: u32,
+        foreground: ColorF,
+        transform: &Transform2D<f32>,
+        bounds: &Box2D<i32>,
+        outlines: &mut dyn OutlineSource,
+    ) -> Option<Vec<u8>> {
+        let paint = self.parser(foreground).glyph_paint(glyph)?;
+
+        // Map device space to the pixels of the bitmap, with y pointing down.
+        let to_bitmap = Transform2D::new(1.0, 0.0, 0.0, -1.0, -bounds.min.x as f32, bounds.max.y as f32);
+        let mut canvas = Canvas {
+            width: bounds.width() as usize,
+            height: bounds.height() as usize,
+            outlines,
+        };
+        let pixels = canvas.render(&paint, &transform.then(&to_bitmap));
+
+        let mut bytes = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels {
+            let a = pixel[3].max(0.0).min(1.0);
+            let channel = |c: f32| (c.max(0.0).min(a) * 255.0 + 0.5) as u8;
+            bytes.extend_from_slice(&[channel(pixel[2]), channel(pixel[1]), channel(pixel[0]), channel(a)]);
+        }
+        Some(bytes)
+    }
+}
+
+struct Parser<'a> {
+    colr: Table<'a>,
+    cpal: Table<'a>,
+    foreground: Rgba,
+    /// The number of paint nodes parsed so far.
+    nodes: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Find a record in an array sorted by glyph id.
+    fn find_glyph_record(&self, start: usize, count: usize, size: usize, glyph: u32) -> Option<usize> {
+        let (mut low, mut high) = (0, count);
+        while low < high {
+            let mid = (low + high) / 2;
+            let record = start + mid * size;
+            match (self.colr.u16(record)? as u32).cmp(&glyph) {
+                Ordering::Less => low = mid + 1,
+                Ordering::Greater => high = mid,
+                Ordering::Equal => return Some(record),
+            }
+        }
+        None
+    }
+
+    /// The offset of the version 1 paint of a base glyph.
+    fn base_paint_offset(&self, glyph: u32) -> Option<usize> {
+        if self.colr.u16(0)? < 1 {
+            return None;
+        }
+        let list = self.colr.u32(14)? as usize;
+        if list == 0 {
+            return None;
+        }
+        let count = self.colr.u32(list)? as usize;
+        let record = self.find_glyph_record(list + 4, count, 6, glyph)?;
+        Some(list + self.colr.u32(record + 2)? as usize)
+    }
+
+    /// The first layer record and number of layers of a version 0 base glyph.
+    fn base_layers(&self, glyph: u32) -> Option<(usize, usize)> {
+        let count = self.colr.u16(2)? as usize;
+        let records = self.colr.u32(4)? as usize;
+        let record = self.find_glyph_record(records, count, 6, glyph)?;
+        let first = self.colr.u16(record + 2)? as usize;
+        let num = self.colr.u16(record + 4)? as usize;
+        Some((self.colr.u32(8)? as usize + first * 4, num))
+    }
+
+    /// The clip box of a glyph, in font units.
+    fn clip_box(&self, glyph: u32) -> Option<Box2D<f32>> {
+        if self.colr.u16(0)? < 1 {
+            return None;
+        }
+        let list = self.colr.u32(22)? as usize;
+        if list == 0 {
+            return None;
+        }
+        let count = self.colr.u32(list + 1)? as usize;
+        for i in 0 .. count {
+            let record = list + 5 + i * 7;
+            let start = self.colr.u16(record)? as u32;
+            let end = self.colr.u16(record + 2)? as u32;
+            if glyph < start {
+                break;
+            }
+            if glyph <= end {
+                let clip_box = list + self.colr.u24(record + 4)?;
+                return Some(Box2D::new(self.colr.point(clip_box + 1)?, self.colr.point(clip_box + 5)?));
+            }
+        }
+        None
+    }
+
+    fn palette_color(&self, index: u16, alpha: f32) -> Rgba {
+        let color = if index == FOREGROUND_PALETTE_INDEX {
+            Some(self.foreground)
+        } else {
+            self.cpal_color(index)
+        };
+        let [r, g, b, a] = color.unwrap_or(TRANSPARENT);
+        let alpha = alpha.max(0.0).min(1.0);
+        [r * alpha, g * alpha, b * alpha, a * alpha]
+    }
+
+    /// A color of the first palette.
+    fn cpal_color(&self, index: u16) -> Option<Rgba> {
+        if index >= self.cpal.u16(2)? {
+            return None;
+        }
+        let records = self.cpal.u32(8)? as usize;
+        let first = self.cpal.u16(12)? as usize;
+        let record = records + (first + index as usize) * 4;
+        let channel = |i| Some(self.cpal.u8(record + i)? as f32 / 255.0);
+        Some(premultiply(ColorF::new(channel(2)?, channel(1)?, channel(0)?, channel(3)?)))
+    }
+
+    /// The paint graph of a glyph, from version 1 if present, or else from
+    /// the layers of version 0.
+    fn glyph_paint(&mut self, glyph: u32) -> Option<Paint> {
+        if let Some(offset) = self.base_paint_offset(glyph) {
+            return self.paint(offset, 0);
+        }
+
+        let (records, num) = self.base_layers(glyph)?;
+        let mut layers = Vec::with_capacity(num);
+        for i in 0 .. num {
+            let record = records + i * 4;
+            layers.push(Paint::Glyph {
+                glyph: self.colr.u16(record)?,
+                paint: Box::new(Paint::Solid(self.palette_color(self.colr.u16(record + 2)?, 1.0))),
+            });
+        }
+        Some(Paint::Layers(layers))
+    }
+
+    fn color_line(&self, offset: usize, variable: bool) -> Option<ColorLine> {
+        let extend = match self.colr.u8(offset)? {
+            1 => Extend::Repeat,
+            2 => Extend::Reflect,
+            _ => Extend::Pad,
+        };
+        let count = self.colr.u16(offset + 1)? as usize;
+        let size = if variable { 10 } else { 6 };
+        let mut stops = Vec::with_capacity(count);
+        for i in 0 .. count {
+            let stop = offset + 3 + i * size;
+            let color = self.palette_color(self.colr.u16(stop + 2)?, self.colr.f2dot14(stop + 4)?);
+            stops.push((self.colr.f2dot14(stop)?, color));
+        }
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        Some(ColorLine { extend, stops })
+    }
+
+    /// Parse the child paint at the Offset24 at `field` of the paint at `offset`.
+    fn child(&mut self, offset: usize, field: usize, depth: usize) -> Option<Box<Paint>> {
+        let child = offset + self.colr.u24(offset + field)?;
+        Some(Box::new(self.paint(child, depth + 1)?))
+    }
+
+    fn paint(&mut self, offset: usize, depth: usize) -> Option<Paint> {
+        if depth > MAX_PAINT_DEPTH || self.nodes >= MAX_PAINT_NODES {
+            debug!("COLR paint graph is too complex");
+            return None;
+        }
+        self.nodes += 1;
+
+        let t = self.colr;
+        let format = t.u8(offset)?;
+        // Variable formats share the layout of the preceding format, with
+        // their variation indices at the end.
+        let variable = format % 2 == 1;
+        let transformed = |transform: Transform2D<f32>, paint| Paint::Transform { transform, paint };
+        let around = |center: Point2D<f32>, transform: Transform2D<f32>| {
+            Transform2D::translation(-center.x, -center.y)
+                .then(&transform)
+                .then_translate(center.to_vector())
+        };
+
+        Some(match format {
+            1 => {
+                let num = t.u8(offset + 1)? as usize;
+                let first = t.u32(offset + 2)? as usize;
+                let list = t.u32(18)? as usize;
+                if list == 0 || first + num > t.u32(list)? as usize {
+                    return None;
+                }
+                let mut layers = Vec::with_capacity(num);
+                for i in first .. first + num {
+                    let layer = list + t.u32(list + 4 + i * 4)? as usize;
+                    layers.push(self.paint(layer, depth + 1)?);
+                }
+                Paint::Layers(layers)
+            }
+            2 | 3 => Paint::Solid(self.palette_color(t.u16(offset + 1)?, t.f2dot14(offset + 3)?)),
+            4 | 5 => Paint::LinearGradient {
+                line: self.color_line(offset + t.u24(offset + 1)?, variable)?,
+                p0: t.point(offset + 4)?,
+                p1: t.point(offset + 8)?,
+                p2: t.point(offset + 12)?,
+            },
+            6 | 7 => Paint::RadialGradient {
+                line: self.color_line(offset + t.u24(offset + 1)?, variable)?,
+                c0: t.point(offset + 4)?,
+                r0: t.u16(offset + 8)? as f32,
+                c1: t.point(offset + 10)?,
+                r1: t.u16(offset + 14)? as f32,
+            },
+            8 | 9 => Paint::SweepGradient {
+                line: self.color_line(offset + t.u24(offset + 1)?, variable)?,
+                center: t.point(offset + 4)?,
+                start_angle: t.f2dot14(offset + 8)? * 180.0,
+                end_angle: t.f2dot14(offset + 10)? * 180.0,
+            },
+            10 => Paint::Glyph {
+                paint: self.child(offset, 1, depth)?,
+                glyph: t.u16(offset + 4)?,
+            },
+            11 => {
+                let glyph = t.u16(offset + 1)? as u32;
+                let paint = self.base_paint_offset(glyph)?;
+                self.paint(paint, depth + 1)?
+            }
+            12 | 13 => {
+                let affine = offset + t.u24(offset + 4)?;
+                let transform = Transform2D::new(
+                    t.fixed(affine)?,
+                    t.fixed(affine + 4)?,
+                    t.fixed(affine + 8)?,
+                    t.fixed(affine + 12)?,
+                    t.fixed(affine + 16)?,
+                    t.fixed(affine + 20)?,
+                );
+                transformed(transform, self.child(offset, 1, depth)?)
+            }
+            14 | 15 => {
+                let transform = Transform2D::translation(t.fword(offset + 4)?, t.fword(offset + 6)?);
+                transformed(transform, self.child(offset, 1, depth)?)
+            }
+            16 | 17 => {
+                let transform = Transform2D::scale(t.f2dot14(offset + 4)?, t.f2dot14(offset + 6)?);
+                transformed(transform, self.child(offset, 1, depth)?)
+            }
+            18 | 19 => {
+                let scale = Transform2D::scale(t.f2dot14(offset + 4)?, t.f2dot14(offset + 6)?);
+                transformed(around(t.point(offset + 8)?, scale), self.child(offset, 1, depth)?)
+            }
+            20 | 21 => {
+                let scale = t.f2dot14(offset + 4)?;
+                transformed(Transform2D::scale(scale, scale), self.child(offset, 1, depth)?)
+            }
+            22 | 23 => {
+                let scale = t.f2dot14(offset + 4)?;
+                let transform = around(t.point(offset + 6)?, Transform2D::scale(scale, scale));
+                transformed(transform, self.child(offset, 1, depth)?)
+            }
+            24 | 25 => {
+                let rotation = Transform2D::rotation(Angle::degrees(t.f2dot14(offset + 4)? * 180.0));
+                transformed(rotation, self.child(offset, 1, depth)?)
+            }
+            26 | 27 => {
+                let rotation = Transform2D::rotation(Angle::degrees(t.f2dot14(offset + 4)? * 180.0));
+                transformed(around(t.point(offset + 6)?, rotation), self.child(offset, 1, depth)?)
+            }
+            28 ..= 31 => {
+                // Both angles are counter-clockwise, so a positive x skew
+                // leans vertical lines to the left.
+                let skew_x = Angle::degrees(t.f2dot14(offset + 4)? * 180.0).radians.tan();
+                let skew_y = Angle::degrees(t.f2dot14(offset + 6)? * 180.0).radians.tan();
+                let mut transform = Transform2D::new(1.0, skew_y, -skew_x, 1.0, 0.0, 0.0);
+                if format >= 30 {
+                    transform = around(t.point(offset + 8)?, transform);
+                }
+                transformed(transform, self.child(offset, 1, depth)?)
+            }
+            32 => Paint::Composite {
+                source: self.child(offset, 1, depth)?,
+                mode: CompositeMode::from_u8(t.u8(offset + 4)?)?,
+                backdrop: self.child(offset, 5, depth)?,
+            },
+            _ => {
+                debug!("Unsupported COLR paint format {}", format);
+                return None;
+            }
+        })
+    }
+}
+
+/// The bounds of a paint in device space, or None if it is unbounded.
+fn paint_bounds(
+    paint: &Paint,
+    transform: &Transform2D<f32>,
+    outlines: &mut dyn OutlineSource,
+) -> Option<Box2D<f32>> {
+    let empty = Box2D::new(Point2D::new(f32::MAX, f32::MAX), Point2D::new(f32::MIN, f32::MIN));
+    match *paint {
+        Paint::Glyph { glyph, .. } => {
+            // The control points of the outline bound its curves.
+            let mut bounds = empty;
+            let mut add = |p: LayoutPoint| {
+                let p = transform.transform_point(p.to_untyped());
+                bounds.min = bounds.min.min(p);
+                bounds.max = bounds.max.max(p);
+            };
+            for command in outlines.glyph_outline(glyph).unwrap_or_default() {
+                match command {
+                    PathCommand::MoveTo(p) | PathCommand::LineTo(p) => add(p),
+                    PathCommand::QuadTo(a, b) => {
+                        add(a);
+                        add(b);
+                    }
+                    PathCommand::CubicTo(a, b, c) => {
+                        add(a);
+                        add(b);
+                        add(c);
+                    }
+                    PathCommand::Close => {}
+                }
+            }
+            Some(bounds)
+        }
+        Paint::Layers(ref layers) => {
+            let mut bounds = empty;
+            for layer in layers {
+                bounds = bounds.union(&paint_bounds(layer, transform, outlines)?);
+            }
+            Some(bounds)
+        }
+        Paint::Transform { transform: ref inner, ref paint } => {
+            paint_bounds(paint, &inner.then(transform), outlines)
+        }
+        Paint::Composite { ref source, ref backdrop, .. } => {
+            let source = paint_bounds(source, transform, outlines)?;
+            Some(source.union(&paint_bounds(backdrop, transform, outlines)?))
+        }
+        Paint::Solid(..) |
+        Paint::LinearGradient { .. } |
+        Paint::RadialGradient { .. } |
+        Paint::SweepGradient { .. } => None,
+    }
+}
+
+/// Paints a paint graph into layers of premultiplied pixels.
+struct Canvas<'a> {
+    width: usize,
+    height: usize,
+    outlines: &'a mut dyn OutlineSource,
+}
+
+impl<'a> Canvas<'a> {
+    /// Render a paint, with `transform` mapping font units to the pixels of
+    /// the canvas.
+    fn render(&mut self, paint: &Paint, transform: &Transform2D<f32>) -> Vec<Rgba> {
+        let len = self.width * self.height;
+        match *paint {
+            Paint::Layers(ref layers) => {
+                let mut pixels = vec![TRANSPARENT; len];
+                for layer in layers {
+                    let src = self.render(layer, transform);
+                    for (dst, src) in pixels.iter_mut().zip(src) {
+                        *dst = composite(CompositeMode::SrcOver, src, *dst);
+                    }
+                }
+                pixels
+            }
+            Paint::Solid(color) => vec![color; len],
+            Paint::LinearGradient { ref line, p0, p1, p2 } => {
+                // The color lines are parallel to p0p2, so project p1 onto the
+                // normal of p0p2 to get the direction of the gradient.
+                let normal = vec2((p2 - p0).y, -(p2 - p0).x);
+                let p1 = if normal.square_length() > 0.0 {
+                    p0 + normal * ((p1 - p0).dot(normal) / normal.square_length())
+                } else {
+                    p1
+                };
+                let dir = p1 - p0;
+                if dir.square_length() <= 0.0 {
+                    return vec![TRANSPARENT; len];
+                }
+                self.fill(transform, |p| line.color_at((p - p0).dot(dir) / dir.square_length()))
+            }
+            Paint::RadialGradient { ref line, c0, r0, c1, r1 } => {
+                self.fill(transform, |p| match radial_gradient_offset(p, c0, r0, c1, r1) {
+                    Some(t) => line.color_at(t),
+                    None => TRANSPARENT,
+                })
+            }
+            Paint::SweepGradient { ref line, center, start_angle, end_angle } => {
+                if start_angle == end_angle {
+                    return vec![TRANSPARENT; len];
+                }
+                self.fill(transform, |p| {
+                    let angle = (p.y - center.y).atan2(p.x - center.x).to_degrees().rem_euclid(360.0);
+                    line.color_at((angle - start_angle) / (end_angle - start_angle))
+                })
+            }
+            Paint::Glyph { glyph, ref paint } => {
+                let coverage = self.glyph_coverage(glyph, transform);
+                let mut pixels = match **paint {
+                    Paint::Solid(color) => vec![color; len],
+                    ref paint => self.render(paint, transform),
+                };
+                for (pixel, coverage) in pixels.iter_mut().zip(coverage) {
+                    for c in pixel.iter_mut() {
+                        *c *= coverage;
+                    }
+                }
+                pixels
+            }
+            Paint::Transform { transform: ref inner, ref paint } => {
+                self.render(paint, &inner.then(transform))
+            }
+            Paint::Composite { mode, ref source, ref backdrop } => {
+                let mut pixels = self.render(backdrop, transform);
+                let src = self.render(source, transform);
+                for (dst, src) in pixels.iter_mut().zip(src) {
+                    *dst = composite(mode, src, *dst);
+                }
+                pixels
+            }
+        }
+    }
+
+    /// Evaluate a color for the center of each pixel, in font units.
+    fn fill<F: Fn(Point2D<f32>) -> Rgba>(&self, transform: &Transform2D<f32>, color: F) -> Vec<Rgba> {
+        let inverse = match transform.inverse() {
+            Some(inverse) => inverse,
+            None => return vec![TRANSPARENT; self.width * self.height],
+        };
+        let mut pixels = Vec::with_capacity(self.width * self.height);
+        for y in 0 .. self.height {
+            for x in 0 .. self.width {
+                let p = inverse.transform_point(Point2D::new(x as f32 + 0.5, y as f32 + 0.5));
+                pixels.push(color(p));
+            }
+        }
+        pixels
+    }
+
+    fn glyph_coverage(&mut self, glyph: u16, transform: &Transform2D<f32>) -> Vec<f32> {
+        let map = |p: LayoutPoint| LayoutPoint::from_untyped(transform.transform_point(p.to_untyped()));
+        let commands = self.outlines.glyph_outline(glyph).unwrap_or_default().into_iter().map(|command| {
+            map_command(command, &map)
+        });
+        let mut edges = Vec::new();
+        fill_edges(&flatten_path(commands, PATH_TOLERANCE), &mut edges);
+        fill_coverage(&edges, self.width, self.height)
+    }
+}
+
+/// The offset along a two point conical gradient for a point, which is the
+/// largest t for which the point is on the circle interpolated between the
+/// two circles, with a positive radius.
+fn radial_gradient_offset(p: Point2D<f32>, c0: Point2D<f32>, r0: f32, c1: Point2D<f32>, r1: f32) -> Option<f32> {
+    let cd = c1 - c0;
+    let pd = p - c0;
+    let dr = r1 - r0;
+    let a = cd.square_length() - dr * dr;
+    let b = pd.dot(cd) + r0 * dr;
+    let c = pd.square_length() - r0 * r0;
+    let valid = |t: f32| r0 + t * dr >= 0.0;
+
+    if a.abs() < 1e-6 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = c / (2.0 * b);
+        return if valid(t) { Some(t) } else { None };
+    }
+
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (t0, t1) = ((b + root) / a, (b - root) / a);
+    let (high, low) = if t0 > t1 { (t0, t1) } else { (t1, t0) };
+    if valid(high) {
+        Some(high)
+    } else if valid(low) {
+        Some(low)
+    } else {
+        None
+    }
+}
+
+/// Rasterize the coverage of a set of edges with the nonzero fill rule.
+fn fill_coverage(edges: &[PathEdge], width: usize, height: usize) -> Vec<f32> {
+    let rows = height * SUBSAMPLES;
+    let mut crossings: Vec<Vec<(f32, i32)>> = vec![Vec::new(); rows];
+    for edge in edges {
+        let (a, b) = (edge.from, edge.to);
+        if a.y == b.y || !a.x.is_finite() || !a.y.is_finite() || !b.x.is_finite() || !b.y.is_finite() {
+            continue;
+        }
+        let (top, bottom, winding) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+        // The rows whose sample centers are within [top, bottom).
+        let row_range = |y: f32| (y * SUBSAMPLES as f32 - 0.5).ceil().max(0.0).min(rows as f32) as usize;
+        let first = row_range(top.y);
+        for (i, crossings) in crossings[first .. row_range(bottom.y)].iter_mut().enumerate() {
+            let y = ((first + i) as f32 + 0.5) / SUBSAMPLES as f32;
+            let x = top.x + (bottom.x - top.x) * (y - top.y) / (bottom.y - top.y);
+            crossings.push((x, winding));
+        }
+    }
+
+    let mut coverage = vec![0.0; width * height];
+    let weight = 1.0 / SUBSAMPLES as f32;
+    for (row, crossings) in crossings.iter_mut().enumerate() {
+        crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        let line = &mut coverage[row / SUBSAMPLES * width .. (row / SUBSAMPLES + 1) * width];
+        let mut winding = 0;
+        for i in 0 .. crossings.len() {
+            winding += crossings[i].1;
+            if winding != 0 && i + 1 < crossings.len() {
+                add_span(line, crossings[i].0, crossings[i + 1].0, weight);
+            }
+        }
+    }
+    for c in &mut coverage {
+        *c = c.min(1.0);
+    }
+    coverage
+}
+
+/// Add the coverage of a horizontal span to a row of pixels.
+fn add_span(line: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let width = line.len() as f32;
+    let (x0, x1) = (x0.max(0.0).min(width), x1.max(0.0).min(width));
+    if x1 <= x0 {
+        return;
+    }
+    let (i0, i1) = (x0.floor() as usize, x1.floor() as usize);
+    if i0 == i1 {
+        line[i0] += (x1 - x0) * weight;
+        return;
+    }
+    line[i0] += (i0 as f32 + 1.0 - x0) * weight;
+    for c in &mut line[i0 + 1 .. i1] {
+        *c += weight;
+    }
+    if i1 < line.len() {
+        line[i1] += (x1 - i1 as f32) * weight;
+    }
+}
+
+fn unpremultiply(color: Rgba) -> [f32; 3] {
+    if color[3] > 0.0 {
+        [color[0] / color[3], color[1] / color[3], color[2] / color[3]]
+    } else {
+        [0.0; 3]
+    }
+}
+
+fn luminosity(c: [f32; 3]) -> f32 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+fn saturation(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_luminosity(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - luminosity(c);
+    let c = [c[0] + d, c[1] + d, c[2] + d];
+    let l = luminosity(c);
+    let (n, x) = (c[0].min(c[1]).min(c[2]), c[0].max(c[1]).max(c[2]));
+    let clip = |v: f32| {
+        let mut v = v;
+        if n < 0.0 {
+            v = l + (v - l) * l / (l - n);
+        }
+        if x > 1.0 {
+            v = l + (v - l) * (1.0 - l) / (x - l);
+        }
+        v
+    };
+    [clip(c[0]), clip(c[1]), clip(c[2])]
+}
+
+fn set_saturation(c: [f32; 3], s: f32) -> [f32; 3] {
+    let (n, x) = (c[0].min(c[1]).min(c[2]), c[0].max(c[1]).max(c[2]));
+    if x > n {
+        [(c[0] - n) * s / (x - n), (c[1] - n) * s / (x - n), (c[2] - n) * s / (x - n)]
+    } else {
+        [0.0; 3]
+    }
+}
+
+fn hard_light(cs: f32, cb: f32) -> f32 {
+    if cs <= 0.5 {
+        cb * 2.0 * cs
+    } else {
+        let cs = 2.0 * cs - 1.0;
+        cb + cs - cb * cs
+    }
+}
+
+/// Composite two premultiplied colors, as specified by the W3C Compositing
+/// and Blending specification.
+fn composite(mode: CompositeMode, src: Rgba, dst: Rgba) -> Rgba {
+    use CompositeMode::*;
+
+    let (sa, da) = (src[3], dst[3]);
+    let porter_duff = |fs: f32, fd: f32| [
+        src[0] * fs + dst[0] * fd,
+        src[1] * fs + dst[1] * fd,
+        src[2] * fs + dst[2] * fd,
+        src[3] * fs + dst[3] * fd,
+    ];
+    let separable = |f: fn(f32, f32) -> f32| {
+        let (cs, cb) = (unpremultiply(src), unpremultiply(dst));
+        [f(cs[0], cb[0]), f(cs[1], cb[1]), f(cs[2], cb[2])]
+    };
+
+    let blended = match mode {
+        Clear => return TRANSPARENT,
+        Src => return src,
+        Dest => return dst,
+        SrcOver => return porter_duff(1.0, 1.0 - sa),
+        DestOver => return porter_duff(1.0 - da, 1.0),
+        SrcIn => return porter_duff(da, 0.0),
+        DestIn => return porter_duff(0.0, sa),
+        SrcOut => return porter_duff(1.0 - da, 0.0),
+        DestOut => return porter_duff(0.0, 1.0 - sa),
+        SrcAtop => return porter_duff(da, 1.0 - sa),
+        DestAtop => return porter_duff(1.0 - da, sa),
+        Xor => return porter_duff(1.0 - da, 1.0 - sa),
+        Plus => {
+            let [r, g, b, a] = porter_duff(1.0, 1.0);
+            return [r.min(1.0), g.min(1.0), b.min(1.0), a.min(1.0)];
+        }
+        Screen => separable(|cs, cb| cb + cs - cb * cs),
+        Overlay => separable(|cs, cb| hard_light(cb, cs)),
+        Darken => separable(|cs, cb| cs.min(cb)),
+        Lighten => separable(|cs, cb| cs.max(cb)),
+        ColorDodge => separable(|cs, cb| {
+            if cb <= 0.0 {
+                0.0
+            } else if cs >= 1.0 {
+                1.0
+            } else {
+                (cb / (1.0 - cs)).min(1.0)
+            }
+        }),
+        ColorBurn => separable(|cs, cb| {
+            if cb >= 1.0 {
+                1.0
+            } else if cs <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - cb) / cs).min(1.0)
+            }
+        }),
+        HardLight => separable(hard_light),
+        SoftLight => separable(|cs, cb| {
+            if cs <= 0.5 {
+                cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+            } else {
+                let d = if cb <= 0.25 { ((16.0 * cb - 12.0) * cb + 4.0) * cb } else { cb.sqrt() };
+                cb + (2.0 * cs - 1.0) * (d - cb)
+            }
+        }),
+        Difference => separable(|cs, cb| (cs - cb).abs()),
+        Exclusion => separable(|cs, cb| cs + cb - 2.0 * cs * cb),
+        Multiply => separable(|cs, cb| cs * cb),
+        Hue => {
+            let (cs, cb) = (unpremultiply(src), unpremultiply(dst));
+            set_luminosity(set_saturation(cs, saturation(cb)), luminosity(cb))
+        }
+        Saturation => {
+            let (cs, cb) = (unpremultiply(src), unpremultiply(dst));
+            set_luminosity(set_saturation(cb, saturation(cs)), luminosity(cb))
+        }
+        Color => {
+            let (cs, cb) = (unpremultiply(src), unpremultiply(dst));
+            set_luminosity(cs, luminosity(cb))
+        }
+        Luminosity => {
+            let (cs, cb) = (unpremultiply(src), unpremultiply(dst));
+            set_luminosity(cb, luminosity(cs))
+        }
+    };
+
+    let mix = |i: usize| (1.0 - da) * src[i] + (1.0 - sa) * dst[i] + sa * da * blended[i];
+    [mix(0), mix(1), mix(2), sa + da - sa * da]
+}
+
+#[cfg(test)]
+struct Squares;
+
+#[cfg(test)]
+impl OutlineSource for Squares {
+    /// Glyph n is a square from (n - 1, n - 1) to (9 - n, 9 - n).
+    fn glyph_outline(&mut self, glyph: u16) -> Option<Vec<PathCommand>> {
+        let (a, b) = (glyph as f32 - 1.0, 9.0 - glyph as f32);
+        Some(vec![
+            PathCommand::MoveTo(LayoutPoint::new(a, a)),
+            PathCommand::LineTo(LayoutPoint::new(b, a)),
+            PathCommand::LineTo(LayoutPoint::new(b, b)),
+            PathCommand::LineTo(LayoutPoint::new(a, b)),
+            PathCommand::Close,
+        ])
+    }
+}
+
+#[cfg(test)]
+fn assert_rgba_eq(result: Rgba, expected: Rgba) {
+    for i in 0 .. 4 {
+        assert!((result[i] - expected[i]).abs() < 0.001, "{:?} != {:?}", result, expected);
+    }
+}
+
+#[test]
+fn test_composite_modes() {
+    let red = [0.5, 0.0, 0.0, 0.5];
+    let blue = [0.0, 0.0, 1.0, 1.0];
+
+    assert_rgba_eq(composite(CompositeMode::SrcOver, red, blue), [0.5, 0.0, 0.5, 1.0]);
+    assert_rgba_eq(composite(CompositeMode::DestOver, red, blue), blue);
+    assert_rgba_eq(composite(CompositeMode::SrcIn, red, blue), red);
+    assert_rgba_eq(composite(CompositeMode::DestOut, red, blue), [0.0, 0.0, 0.5, 0.5]);
+    assert_rgba_eq(composite(CompositeMode::Xor, red, blue), [0.0, 0.0, 0.5, 0.5]);
+    assert_rgba_eq(composite(CompositeMode::Plus, red, blue), [0.5, 0.0, 1.0, 1.0]);
+
+    // Blend modes only apply where both colors are opaque.
+    let white = [1.0; 4];
+    let gray = [0.5, 0.5, 0.5, 1.0];
+    assert_rgba_eq(composite(CompositeMode::Multiply, gray, white), gray);
+    assert_rgba_eq(composite(CompositeMode::Screen, gray, gray), [0.75, 0.75, 0.75, 1.0]);
+    assert_rgba_eq(composite(CompositeMode::Difference, white, gray), gray);
+    assert_rgba_eq(composite(CompositeMode::Multiply, red, blue), [0.0, 0.0, 0.5, 1.0]);
+    assert_rgba_eq(composite(CompositeMode::Saturation, gray, blue), [0.11, 0.11, 0.11, 1.0]);
+    assert_rgba_eq(composite(CompositeMode::Color, gray, blue), [0.11, 0.11, 0.11, 1.0]);
+    assert_rgba_eq(composite(CompositeMode::Luminosity, blue, gray), [0.11, 0.11, 0.11, 1.0]);
+}
+
+#[test]
+fn test_color_line_extend() {
+    let mut line = ColorLine {
+        extend: Extend::Pad,
+        stops: vec![(0.25, [0.0; 4]), (0.75, [1.0; 4])],
+    };
+    assert_rgba_eq(line.color_at(0.0), [0.0; 4]);
+    assert_rgba_eq(line.color_at(0.5), [0.5; 4]);
+    assert_rgba_eq(line.color_at(1.0), [1.0; 4]);
+
+    line.extend = Extend::Repeat;
+    assert_rgba_eq(line.color_at(0.875), [0.25; 4]);
+    assert_rgba_eq(line.color_at(0.125), [0.75; 4]);
+
+    line.extend = Extend::Reflect;
+    assert_rgba_eq(line.color_at(0.875), [0.75; 4]);
+    assert_rgba_eq(line.color_at(0.125), [0.25; 4]);
+}
+
+#[test]
+fn test_radial_gradient_offset() {
+    let c = Point2D::new(0.0, 0.0);
+    assert_eq!(radial_gradient_offset(Point2D::new(5.0, 0.0), c, 0.0, c, 10.0), Some(0.5));
+    assert_eq!(radial_gradient_offset(Point2D::new(0.0, 15.0), c, 10.0, c, 20.0), Some(0.5));
+    // Points outside of the cone of a focal gradient are not painted.
+    let c1 = Point2D::new(10.0, 0.0);
+    assert_eq!(radial_gradient_offset(Point2D::new(-5.0, 0.0), c, 0.0, c1, 5.0), None);
+}
+
+#[test]
+fn test_layers() {
+    let mut colr = Vec::new();
+    let mut push = |bytes: &[u8]| colr.extend_from_slice(bytes);
+    // Version 1 header.
+    push(&[0, 1, 0, 1, 0, 0, 0, 34, 0, 0, 0, 40, 0, 2]);
+    push(&[0, 0, 0, 48, 0, 0, 0, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    // Version 0 base glyph 3, with layers of glyphs 1 and 2 in the palette
+    // colors 0 and 1.
+    push(&[0, 3, 0, 0, 0, 2]);
+    push(&[0, 1, 0, 0, 0, 2, 0, 1]);
+    // Version 1 base glyph 4, with PaintColrLayers.
+    push(&[0, 0, 0, 1, 0, 4, 0, 0, 0, 10]);
+    push(&[1, 2, 0, 0, 0, 0]);
+    // The layers, glyph 1 in the foreground color and glyph 2 in palette
+    // color 1.
+    push(&[0, 0, 0, 2, 0, 0, 0, 12, 0, 0, 0, 23]);
+    push(&[10, 0, 0, 6, 0, 1, 2, 0xFF, 0xFF, 0x40, 0]);
+    push(&[10, 0, 0, 6, 0, 2, 2, 0, 1, 0x40, 0]);
+
+    // One palette with opaque red and blue, as BGRA.
+    let cpal = vec![0, 0, 0, 2, 0, 1, 0, 2, 0, 0, 0, 14, 0, 0, 0, 0, 255, 255, 255, 0, 0, 255];
+
+    let glyphs = ColorGlyphs::new(colr, cpal).unwrap();
+    assert!(glyphs.has_glyph(3) && glyphs.has_glyph(4) && !glyphs.has_glyph(1));
+
+    let transform = Transform2D::identity();
+    let bounds = glyphs.bounds(3, &transform, &mut Squares).unwrap();
+    assert_eq!(bounds, Box2D::new(Point2D::new(0, 0), Point2D::new(8, 8)));
+
+    let pixel = |bytes: &[u8], x: usize, y: usize| bytes[(y * 8 + x) * 4 .. (y * 8 + x) * 4 + 4].to_vec();
+    let green = ColorF::new(0.0, 1.0, 0.0, 1.0);
+    let v0 = glyphs.rasterize(3, green, &transform, &bounds, &mut Squares).unwrap();
+    assert_eq!(pixel(&v0, 0, 0), [0, 0, 255, 255]);
+    assert_eq!(pixel(&v0, 4, 4), [255, 0, 0, 255]);
+
+    let v1 = glyphs.rasterize(4, green, &transform, &bounds, &mut Squares).unwrap();
+    assert_eq!(pixel(&v1, 1, 7), [0, 255, 0, 255]);
+    assert_eq!(pixel(&v1, 3, 3), [255, 0, 0, 255]);
+}
+
+#[test]
+fn test_coverage() {
+    let square = Squares.glyph_outline(2).unwrap().into_iter().map(|command| match command {
+        PathCommand::MoveTo(p) => PathCommand::MoveTo(p + vec2(0.5, 0.0)),
+        PathCommand::LineTo(p) => PathCommand::LineTo(p + vec2(0.5, 0.0)),
+        command => command,
+    });
+    let mut edges = Vec::new();
+    fill_edges(&flatten_path(square, PATH_TOLERANCE), &mut edges);
+    let coverage = fill_coverage(&edges, 8, 8);
+    assert_eq!(&coverage[8 .. 16], &[0.0, 0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5]);
+    assert!(coverage[.. 8].iter().all(|&c| c == 0.0));
+}
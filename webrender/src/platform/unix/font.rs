@@ -2,9 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{ColorU, GlyphDimensions, FontKey, FontRenderMode};
+use api::{ColorF, ColorU, GlyphDimensions, FontKey, FontRenderMode, FontTemplate};
 use api::{FontInstancePlatformOptions, FontLCDFilter, FontHinting};
-use api::{FontInstanceFlags, FontVariation, NativeFontHandle};
+use api::{FontInstanceFlags, FontVariation, NativeFontHandle, PathCommand};
+use api::units::LayoutPoint;
+use euclid::default::{Box2D, Transform2D};
 use freetype::freetype::{FT_BBox, FT_Outline_Translate, FT_Pixel_Mode, FT_Render_Mode};
 use freetype::freetype::{FT_Done_Face, FT_Error, FT_Get_Char_Index, FT_Int32};
 use freetype::freetype::{FT_Done_FreeType, FT_Library_SetLcdFilter, FT_Pos};
@@ -17,12 +19,15 @@ use freetype::freetype::{FT_Err_Unimplemented_Feature, FT_MulFix, FT_Outline_Emb
 use freetype::freetype::{FT_LOAD_COLOR, FT_LOAD_DEFAULT, FT_LOAD_FORCE_AUTOHINT};
 use freetype::freetype::{FT_LOAD_IGNORE_GLOBAL_ADVANCE_WIDTH, FT_LOAD_NO_AUTOHINT};
 use freetype::freetype::{FT_LOAD_NO_BITMAP, FT_LOAD_NO_HINTING};
+use freetype::freetype::{FT_LOAD_IGNORE_TRANSFORM, FT_LOAD_NO_SCALE, FT_Outline};
 use freetype::freetype::{FT_FACE_FLAG_SCALABLE, FT_FACE_FLAG_FIXED_SIZES};
-use freetype::freetype::{FT_FACE_FLAG_MULTIPLE_MASTERS};
+use freetype::freetype::{FT_FACE_FLAG_MULTIPLE_MASTERS, FT_Bool, FT_Int};
 use freetype::succeeded;
-use crate::glyph_rasterizer::{FontInstance, GlyphFormat, GlyphKey};
+use crate::glyph_rasterizer::{FontInstance, FontTransform, GlyphFormat, GlyphKey};
 use crate::glyph_rasterizer::{GlyphRasterError, GlyphRasterResult, RasterizedGlyph};
 use crate::internal_types::{FastHashMap, ResourceCacheError};
+use crate::path::map_command;
+use super::colr::{ColorGlyphs, OutlineSource, has_sfnt_table};
 #[cfg(any(not(target_os = "android"), feature = "no_static_freetype"))]
 use libc::{dlsym, RTLD_DEFAULT};
 use libc::free;
@@ -30,6 +35,8 @@ use std::{cmp, mem, ptr, slice};
 use std::cmp::max;
 use std::collections::hash_map::Entry;
 use std::ffi::CString;
+use std::fs::File;
+use std::io::{BufReader, Cursor};
 use std::sync::Arc;
 
 // These constants are not present in the freetype
@@ -41,6 +48,17 @@ const FT_LOAD_TARGET_MONO: FT_UInt   = 2 << 16;
 const FT_LOAD_TARGET_LCD: FT_UInt    = 3 << 16;
 const FT_LOAD_TARGET_LCD_V: FT_UInt  = 4 << 16;
 
+// FT_Stroker_LineCap and FT_Stroker_LineJoin values.
+const FT_STROKER_LINECAP_BUTT: FT_UInt = 0;
+const FT_STROKER_LINEJOIN_ROUND: FT_UInt = 0;
+
+#[repr(C)]
+struct FT_StrokerRec {
+    _private: [u8; 0],
+}
+
+type FT_Stroker = *mut FT_StrokerRec;
+
 #[repr(C)]
 struct FT_Var_Axis {
     pub name: *mut FT_String,
@@ -108,6 +126,33 @@ ft_dyn_fn!(FT_Get_Var_Design_Coordinates(face: FT_Face, num_vals: FT_UInt, vals:
 
 extern "C" {
     fn FT_GlyphSlot_Embolden(slot: FT_GlyphSlot);
+    fn FT_Outline_New(
+        library: FT_Library,
+        num_points: FT_UInt,
+        num_contours: FT_Int,
+        outline: *mut FT_Outline,
+    ) -> FT_Error;
+    fn FT_Outline_Done(library: FT_Library, outline: *mut FT_Outline) -> FT_Error;
+    fn FT_Stroker_New(library: FT_Library, stroker: *mut FT_Stroker) -> FT_Error;
+    fn FT_Stroker_Set(
+        stroker: FT_Stroker,
+        radius: FT_Fixed,
+        line_cap: FT_UInt,
+        line_join: FT_UInt,
+        miter_limit: FT_Fixed,
+    );
+    fn FT_Stroker_ParseOutline(stroker: FT_Stroker, outline: *mut FT_Outline, opened: FT_Bool) -> FT_Error;
+    fn FT_Stroker_GetCounts(stroker: FT_Stroker, num_points: *mut FT_UInt, num_contours: *mut FT_UInt) -> FT_Error;
+    fn FT_Stroker_Export(stroker: FT_Stroker, outline: *mut FT_Outline);
+    fn FT_Stroker_Done(stroker: FT_Stroker);
+    fn FT_Library_Version(library: FT_Library, major: *mut FT_Int, minor: *mut FT_Int, patch: *mut FT_Int);
+    fn FT_Load_Sfnt_Table(
+        face: FT_Face,
+        tag: FT_ULong,
+        offset: FT_Long,
+        buffer: *mut u8,
+        length: *mut FT_ULong,
+    ) -> FT_Error;
 }
 
 // Custom version of FT_GlyphSlot_Embolden to be less aggressive with outline
@@ -150,6 +195,66 @@ pub extern "C" fn mozilla_glyphslot_embolden_less(slot: FT_GlyphSlot) {
     slot_.metrics.horiBearingY += strength;
 }
 
+// The stroke of the outline of a glyph slot, which replaces the outline in
+// the slot until dropped.
+struct StrokedOutline {
+    lib: FT_Library,
+    slot: FT_GlyphSlot,
+    // The original outline of the slot while the stroke is swapped in.
+    outline: FT_Outline,
+}
+
+impl StrokedOutline {
+    fn new(lib: FT_Library, slot: FT_GlyphSlot, width: f64) -> Option<Self> {
+        unsafe {
+            if (*slot).format != FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                return None;
+            }
+
+            let mut stroker: FT_Stroker = ptr::null_mut();
+            if !succeeded(FT_Stroker_New(lib, &mut stroker)) {
+                return None;
+            }
+            // Round joins keep the stroke within half of its width of the outline.
+            FT_Stroker_Set(
+                stroker,
+                (width * 0.5 * 64.0 + 0.5) as FT_Fixed,
+                FT_STROKER_LINECAP_BUTT,
+                FT_STROKER_LINEJOIN_ROUND,
+                0,
+            );
+
+            let mut outline: FT_Outline = mem::zeroed();
+            let (mut num_points, mut num_contours) = (0, 0);
+            let stroked = succeeded(FT_Stroker_ParseOutline(stroker, &mut (*slot).outline, 0)) &&
+                succeeded(FT_Stroker_GetCounts(stroker, &mut num_points, &mut num_contours)) &&
+                succeeded(FT_Outline_New(lib, num_points, num_contours as FT_Int, &mut outline));
+            if stroked {
+                // The stroker appends to the outline.
+                outline.n_points = 0;
+                outline.n_contours = 0;
+                FT_Stroker_Export(stroker, &mut outline);
+            }
+            FT_Stroker_Done(stroker);
+            if !stroked {
+                return None;
+            }
+
+            mem::swap(&mut (*slot).outline, &mut outline);
+            Some(StrokedOutline { lib, slot, outline })
+        }
+    }
+}
+
+impl Drop for StrokedOutline {
+    fn drop(&mut self) {
+        unsafe {
+            mem::swap(&mut (*self.slot).outline, &mut self.outline);
+            FT_Outline_Done(self.lib, &mut self.outline);
+        }
+    }
+}
+
 enum FontFile {
     Pathname(CString),
     Data(Arc<Vec<u8>>),
@@ -162,6 +267,8 @@ struct FontFace {
     index: u32,
     face: FT_Face,
     mm_var: *mut FT_MM_Var,
+    // The COLR and CPAL tables, if the font has color layers.
+    color_glyphs: Option<ColorGlyphs>,
 }
 
 impl Drop for FontFace {
@@ -213,6 +320,110 @@ fn new_ft_face(font_key: &FontKey, lib: FT_Library, file: &FontFile, index: u32)
     }
 }
 
+fn load_sfnt_table(face: FT_Face, tag: &[u8; 4]) -> Option<Vec<u8>> {
+    let tag = u32::from_be_bytes(*tag) as FT_ULong;
+    let mut length: FT_ULong = 0;
+    unsafe {
+        if !succeeded(FT_Load_Sfnt_Table(face, tag, 0, ptr::null_mut(), &mut length)) || length == 0 {
+            return None;
+        }
+        let mut data = vec![0u8; length as usize];
+        if !succeeded(FT_Load_Sfnt_Table(face, tag, 0, data.as_mut_ptr(), &mut length)) {
+            return None;
+        }
+        Some(data)
+    }
+}
+
+fn load_color_glyphs(face: FT_Face) -> Option<ColorGlyphs> {
+    let colr = load_sfnt_table(face, b"COLR")?;
+    ColorGlyphs::new(colr, load_sfnt_table(face, b"CPAL").unwrap_or_default())
+}
+
+// Convert a FreeType outline to path commands, in the units of the outline.
+fn outline_to_path(outline: &FT_Outline) -> Vec<PathCommand> {
+    let mut commands = Vec::new();
+    if outline.n_points <= 0 || outline.n_contours <= 0 {
+        return commands;
+    }
+
+    let (points, tags, contours) = unsafe { (
+        slice::from_raw_parts(outline.points, outline.n_points as usize),
+        slice::from_raw_parts(outline.tags as *const u8, outline.n_points as usize),
+        slice::from_raw_parts(outline.contours, outline.n_contours as usize),
+    ) };
+    let point = |i: usize| LayoutPoint::new(points[i].x as f32, points[i].y as f32);
+
+    let mut start = 0;
+    for &end in contours {
+        let end = end as usize;
+        if end < start || end >= points.len() {
+            break;
+        }
+        let len = end - start + 1;
+
+        // Start at an on-curve point, or between the first and last points
+        // if all of them are conic control points.
+        let (origin, first) = match (0 .. len).find(|&i| tags[start + i] & 1 != 0) {
+            Some(i) => (point(start + i), i + 1),
+            None => (point(end).lerp(point(start), 0.5), 0),
+        };
+        commands.push(PathCommand::MoveTo(origin));
+
+        let mut conic = None;
+        let mut cubic = Vec::new();
+        for i in first .. first + len {
+            let index = start + i % len;
+            let p = point(index);
+            if tags[index] & 1 != 0 {
+                if let Some(ctrl) = conic.take() {
+                    commands.push(PathCommand::QuadTo(ctrl, p));
+                } else if cubic.len() == 2 {
+                    commands.push(PathCommand::CubicTo(cubic[0], cubic[1], p));
+                } else {
+                    commands.push(PathCommand::LineTo(p));
+                }
+                cubic.clear();
+            } else if tags[index] & 2 != 0 {
+                cubic.push(p);
+            } else {
+                // Consecutive conic control points have an implicit on-curve
+                // point between them.
+                if let Some(ctrl) = conic {
+                    commands.push(PathCommand::QuadTo(ctrl, ctrl.lerp(p, 0.5)));
+                }
+                conic = Some(p);
+            }
+        }
+        if let Some(ctrl) = conic {
+            commands.push(PathCommand::QuadTo(ctrl, origin));
+        }
+        commands.push(PathCommand::Close);
+
+        start = end + 1;
+    }
+    commands
+}
+
+// Loads the unscaled outlines of the glyphs that COLR glyphs are painted with.
+struct FaceOutlines(FT_Face);
+
+impl OutlineSource for FaceOutlines {
+    fn glyph_outline(&mut self, glyph: u16) -> Option<Vec<PathCommand>> {
+        unsafe {
+            let load_flags = FT_LOAD_NO_SCALE | FT_LOAD_IGNORE_TRANSFORM;
+            if !succeeded(FT_Load_Glyph(self.0, glyph as FT_UInt, load_flags as FT_Int32)) {
+                return None;
+            }
+            let slot = (*self.0).glyph;
+            if (*slot).format != FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                return None;
+            }
+            Some(outline_to_path(&(*slot).outline))
+        }
+    }
+}
+
 pub struct FontContext {
     lib: FT_Library,
     faces: FastHashMap<FontKey, FontFace>,
@@ -350,7 +561,8 @@ impl FontContext {
         if !self.faces.contains_key(font_key) {
             let file = FontFile::Data(bytes);
             if let Some(face) = new_ft_face(font_key, self.lib, &file, index) {
-                self.faces.insert(*font_key, FontFace { file, index, face, mm_var: ptr::null_mut() });
+                let color_glyphs = load_color_glyphs(face);
+                self.faces.insert(*font_key, FontFace { file, index, face, mm_var: ptr::null_mut(), color_glyphs });
             }
         }
     }
@@ -361,7 +573,8 @@ impl FontContext {
             let file = FontFile::Pathname(cstr);
             let index = native_font_handle.index;
             if let Some(face) = new_ft_face(font_key, self.lib, &file, index) {
-                self.faces.insert(*font_key, FontFace { file, index, face, mm_var: ptr::null_mut() });
+                let color_glyphs = load_color_glyphs(face);
+                self.faces.insert(*font_key, FontFace { file, index, face, mm_var: ptr::null_mut(), color_glyphs });
             }
         }
     }
@@ -433,6 +646,31 @@ impl FontContext {
         }
     }
 
+    // Get the transform FreeType applies to the outlines of scalable fonts, after
+    // scaling them to the requested size, and the offset for synthetic italics.
+    fn get_outline_transform(
+        font: &FontInstance,
+        x_scale: f64,
+        y_scale: f64,
+        req_size: f64,
+    ) -> (FontTransform, (f64, f64)) {
+        let mut shape = font.transform.invert_scale(x_scale, y_scale);
+        if font.flags.contains(FontInstanceFlags::FLIP_X) {
+            shape = shape.flip_x();
+        }
+        if font.flags.contains(FontInstanceFlags::FLIP_Y) {
+            shape = shape.flip_y();
+        }
+        if font.flags.contains(FontInstanceFlags::TRANSPOSE) {
+            shape = shape.swap_xy();
+        }
+        if font.synthetic_italics.is_enabled() {
+            font.synthesize_italics(shape, y_scale * req_size)
+        } else {
+            (shape, (0.0, 0.0))
+        }
+    }
+
     fn load_glyph(&mut self, font: &FontInstance, glyph: &GlyphKey) -> Option<(FT_GlyphSlot, f32)> {
         let face = self.get_ft_face(font)?;
 
@@ -484,23 +722,7 @@ impl FontContext {
             unsafe { FT_Set_Transform(face, ptr::null_mut(), ptr::null_mut()) };
             self.choose_bitmap_size(face, req_size * y_scale)
         } else {
-            let mut shape = font.transform.invert_scale(x_scale, y_scale);
-            if font.flags.contains(FontInstanceFlags::FLIP_X) {
-                shape = shape.flip_x();
-            }
-            if font.flags.contains(FontInstanceFlags::FLIP_Y) {
-                shape = shape.flip_y();
-            }
-            if font.flags.contains(FontInstanceFlags::TRANSPOSE) {
-                shape = shape.swap_xy();
-            }
-            let (mut tx, mut ty) = (0.0, 0.0);
-            if font.synthetic_italics.is_enabled() {
-                let (shape_, (tx_, ty_)) = font.synthesize_italics(shape, y_scale * req_size);
-                shape = shape_;
-                tx = tx_;
-                ty = ty_;
-            };
+            let (shape, (tx, ty)) = Self::get_outline_transform(font, x_scale, y_scale, req_size);
             let mut ft_shape = FT_Matrix {
                 xx: (shape.scale_x * 65536.0) as FT_Fixed,
                 xy: (shape.skew_x * -65536.0) as FT_Fixed,
@@ -578,6 +800,19 @@ impl FontContext {
         }
     }
 
+    // Replace the outline of a loaded glyph with its stroke, if the font is
+    // stroked, until the result is dropped. Bitmap glyphs can't be stroked.
+    fn stroke_glyph(&self, slot: FT_GlyphSlot, font: &FontInstance) -> Result<Option<StrokedOutline>, GlyphRasterError> {
+        if !font.is_stroked() {
+            return Ok(None);
+        }
+        let (x_scale, y_scale) = font.transform.compute_scale().unwrap_or((1.0, 1.0));
+        let width = font.get_stroke_width() * (x_scale * y_scale).sqrt();
+        StrokedOutline::new(self.lib, slot, width)
+            .map(Some)
+            .ok_or(GlyphRasterError::LoadFailed)
+    }
+
     fn pad_bounding_box(&self, font: &FontInstance, cbox: &mut FT_BBox) {
         // Apply extra pixel of padding for subpixel AA, due to the filter.
         if font.render_mode == FontRenderMode::Subpixel {
@@ -730,8 +965,135 @@ impl FontContext {
         font: &FontInstance,
         key: &GlyphKey,
     ) -> Option<GlyphDimensions> {
-        let slot = self.load_glyph(font, key);
-        slot.and_then(|(slot, scale)| self.get_glyph_dimensions_impl(slot, &font, key, scale, true))
+        let dimensions = {
+            let (slot, scale) = self.load_glyph(font, key)?;
+            let _stroke = self.stroke_glyph(slot, font).ok()?;
+            self.get_glyph_dimensions_impl(slot, &font, key, scale, true)?
+        };
+        // Color glyphs cover their layers rather than their own outline.
+        match self.get_color_glyph_bounds(font, key) {
+            Some(bounds) => Some(GlyphDimensions {
+                left: bounds.min.x,
+                top: bounds.max.y,
+                width: bounds.width(),
+                height: bounds.height(),
+                ..dimensions
+            }),
+            None => Some(dimensions),
+        }
+    }
+
+    /// Whether the font has COLR color glyphs. Their layers may be painted with
+    /// the text color, so glyphs of such fonts are cached for each color.
+    pub fn has_color_layers(template: &FontTemplate) -> bool {
+        let result = match *template {
+            FontTemplate::Raw(ref bytes, index) => {
+                has_sfnt_table(&mut Cursor::new(&bytes[..]), index, b"COLR")
+            }
+            FontTemplate::Native(ref handle) => {
+                File::open(&handle.path)
+                    .and_then(|file| has_sfnt_table(&mut BufReader::new(file), handle.index, b"COLR"))
+            }
+        };
+        result.unwrap_or(false)
+    }
+
+    // Get the transform from font units to device pixels, with y pointing up,
+    // that FreeType would apply to the outline of a glyph.
+    fn get_font_units_transform(face: FT_Face, font: &FontInstance, key: &GlyphKey) -> Option<Transform2D<f32>> {
+        let units_per_em = unsafe { (*face).units_per_EM };
+        if units_per_em == 0 {
+            return None;
+        }
+        let (x_scale, y_scale) = font.transform.compute_scale().unwrap_or((1.0, 1.0));
+        let req_size = font.size.to_f64_px();
+        let (shape, (tx, ty)) = Self::get_outline_transform(font, x_scale, y_scale, req_size);
+        let sx = req_size * x_scale / units_per_em as f64;
+        let sy = req_size * y_scale / units_per_em as f64;
+        let (dx, dy) = font.get_subpx_offset(key);
+        Some(Transform2D::new(
+            (shape.scale_x as f64 * sx) as f32,
+            (-shape.skew_y as f64 * sx) as f32,
+            (-shape.skew_x as f64 * sy) as f32,
+            (shape.scale_y as f64 * sy) as f32,
+            (tx + dx) as f32,
+            -(ty + dy) as f32,
+        ))
+    }
+
+    /// Whether `get_glyph_outline` can return outlines on this platform.
+    pub fn has_glyph_outlines() -> bool {
+        true
+    }
+
+    /// The version of FreeType, as glyphs rasterized by other versions may differ.
+    pub fn rasterizer_version() -> String {
+        let mut lib: FT_Library = ptr::null_mut();
+        let (mut major, mut minor, mut patch) = (0, 0, 0);
+        unsafe {
+            if succeeded(FT_Init_FreeType(&mut lib)) {
+                FT_Library_Version(lib, &mut major, &mut minor, &mut patch);
+                FT_Done_FreeType(lib);
+            }
+        }
+        format!("FreeType {}.{}.{}", major, minor, patch)
+    }
+
+    /// Get the outline of a glyph in pixels, with y pointing up, for rasterizing
+    /// it into a distance field. Color glyphs have no single outline.
+    pub fn get_glyph_outline(&mut self, font: &FontInstance, key: &GlyphKey) -> Option<Vec<PathCommand>> {
+        let face = self.get_ft_face(font)?;
+        if let Some(ref color_glyphs) = self.faces.get(&font.font_key)?.color_glyphs {
+            if color_glyphs.has_glyph(key.index()) {
+                return None;
+            }
+        }
+        let transform = Self::get_font_units_transform(face, font, key)?;
+        let map = |p: LayoutPoint| LayoutPoint::from_untyped(transform.transform_point(p.to_untyped()));
+        let outline = FaceOutlines(face).glyph_outline(key.index() as u16)?;
+        Some(outline.into_iter().map(|command| map_command(command, &map)).collect())
+    }
+
+    // Get the pixel bounds of a glyph, with y pointing up, if the font has color
+    // layers for it.
+    fn get_color_glyph_bounds(&mut self, font: &FontInstance, key: &GlyphKey) -> Option<Box2D<i32>> {
+        // Strokes follow the outline of the glyph itself rather than its layers.
+        if font.is_stroked() {
+            return None;
+        }
+        let face = self.get_ft_face(font)?;
+        let color_glyphs = self.faces.get(&font.font_key)?.color_glyphs.as_ref()?;
+        if !color_glyphs.has_glyph(key.index()) {
+            return None;
+        }
+        let transform = Self::get_font_units_transform(face, font, key)?;
+        color_glyphs.bounds(key.index(), &transform, &mut FaceOutlines(face))
+    }
+
+    // Paint a glyph from the color layers of the font. FreeType only loads the
+    // outlines of the layers.
+    fn rasterize_color_glyph(&mut self, font: &FontInstance, key: &GlyphKey) -> Option<RasterizedGlyph> {
+        let mut bounds = self.get_color_glyph_bounds(font, key)?;
+        if font.use_texture_padding() {
+            bounds = bounds.inflate(1, 1);
+        }
+
+        let face = self.get_ft_face(font)?;
+        let color_glyphs = self.faces.get(&font.font_key)?.color_glyphs.as_ref()?;
+        let transform = Self::get_font_units_transform(face, font, key)?;
+        // The alpha of the text color is applied when drawing the glyph.
+        let foreground = ColorF::from(ColorU { a: 255, ..font.color });
+        let bytes = color_glyphs.rasterize(key.index(), foreground, &transform, &bounds, &mut FaceOutlines(face))?;
+
+        Some(RasterizedGlyph {
+            left: bounds.min.x as f32,
+            top: bounds.max.y as f32,
+            width: bounds.width(),
+            height: bounds.height(),
+            scale: 1.0,
+            format: GlyphFormat::ColorBitmap,
+            bytes,
+        })
     }
 
     fn choose_bitmap_size(&self, face: FT_Face, requested_size: f64) -> FT_Error {
@@ -829,7 +1191,12 @@ impl FontContext {
     }
 
     pub fn rasterize_glyph(&mut self, font: &FontInstance, key: &GlyphKey) -> GlyphRasterResult {
+        if let Some(glyph) = self.rasterize_color_glyph(font, key) {
+            return Ok(glyph);
+        }
+
         let (slot, scale) = self.load_glyph(font, key).ok_or(GlyphRasterError::LoadFailed)?;
+        let _stroke = self.stroke_glyph(slot, font)?;
 
         // Get dimensions of the glyph, to see if we need to rasterize it.
         // Don't apply scaling to the dimensions, as the glyph cache needs to know the actual
@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use api::{FontInstanceFlags, FontKey, FontRenderMode, FontVariation};
-use api::{ColorU, GlyphDimensions, NativeFontHandle};
+use api::{ColorU, FontSize, FontTemplate, GlyphDimensions, NativeFontHandle, PathCommand};
 use dwrote;
 use crate::gamma_lut::ColorLut;
 use crate::glyph_rasterizer::{FontInstance, FontTransform, GlyphKey};
@@ -13,7 +13,9 @@ use crate::gamma_lut::GammaLut;
 use std::borrow::Borrow;
 use std::collections::hash_map::Entry;
 use std::hash::{Hash, Hasher};
+use std::{env, fs};
 use std::path::Path;
+use std::time::UNIX_EPOCH;
 use std::sync::{Arc, Mutex};
 use api::FontInstancePlatformOptions;
 use std::mem;
@@ -487,6 +489,29 @@ impl FontContext {
         bgra_pixels
     }
 
+    pub fn has_color_layers(_template: &FontTemplate) -> bool {
+        false
+    }
+
+    pub fn has_glyph_outlines() -> bool {
+        false
+    }
+
+    /// The version of DirectWrite, as glyphs rasterized by other versions may
+    /// differ. It has no version to query, so it is identified by the
+    /// modification time of its DLL, which OS updates replace.
+    pub fn rasterizer_version() -> String {
+        let modified = env::var_os("SystemRoot")
+            .and_then(|root| fs::metadata(Path::new(&root).join("System32").join("DWrite.dll")).ok())
+            .and_then(|metadata| metadata.modified().ok())
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+        format!("DirectWrite {}", modified.map_or(0, |modified| modified.as_secs()))
+    }
+
+    pub fn get_glyph_outline(&mut self, _font: &FontInstance, _key: &GlyphKey) -> Option<Vec<PathCommand>> {
+        None
+    }
+
     pub fn prepare_font(font: &mut FontInstance) {
         match font.render_mode {
             FontRenderMode::Mono => {
@@ -547,6 +572,16 @@ impl FontContext {
     }
 
     pub fn rasterize_glyph(&mut self, font: &FontInstance, key: &GlyphKey) -> GlyphRasterResult {
+        // Glyph run analysis only fills glyphs, so stroked glyphs fall back to
+        // the unstroked fill, as documented on `TextStroke`.
+        if font.is_stroked() {
+            let filled = FontInstance {
+                stroke_width: FontSize::zero(),
+                ..font.clone()
+            };
+            return self.rasterize_glyph(&filled, key);
+        }
+
         let (size, y_scale, bitmaps, transform) = Self::get_glyph_parameters(font, key);
         let (analysis, texture_type, bounds) = self.create_glyph_analysis(font, key, size, transform, bitmaps)
                                                    .or(Err(GlyphRasterError::LoadFailed))?;
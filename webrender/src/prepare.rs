@@ -195,6 +195,7 @@ fn prepare_prim_for_render(
                         prim_list,
                         pic_context_for_children,
                         pic_state_for_children,
+                        frame_context,
                         frame_state,
                     );
             }
@@ -577,7 +578,7 @@ fn prepare_interned_prim_for_render(
 
             // Update the template this instane references, which may refresh the GPU
             // cache with any shared template data.
-            yuv_image_data.update(common_data, frame_state);
+            yuv_image_data.update(common_data, frame_state, frame_context);
 
             write_segment(
                 *segment_instance_index,
@@ -585,7 +586,10 @@ fn prepare_interned_prim_for_render(
                 &mut scratch.segments,
                 &mut scratch.segment_instances,
                 |request| {
-                    yuv_image_data.write_prim_gpu_blocks(request);
+                    yuv_image_data.write_prim_gpu_blocks(
+                        request,
+                        frame_context.fb_config.hdr.unwrap_or_default().reference_white_nits,
+                    );
                 }
             );
         }
@@ -772,6 +776,29 @@ fn prepare_interned_prim_for_render(
             // TODO(gw): Consider whether it's worth doing segment building
             //           for gradient primitives.
         }
+        PrimitiveInstanceKind::Path { data_handle, .. } => {
+            profile_scope!("Path");
+            let prim_data = &mut data_stores.path[*data_handle];
+
+            // The mask is rasterized at the scale of the primitive's world transform,
+            // normalized to a power of 2 as for borders, so that scaled up paths stay
+            // sharp without re-rasterizing them as the scale animates.
+            let scale = frame_context
+                .spatial_tree
+                .get_world_transform(prim_spatial_node_index)
+                .scale_factors();
+            let world_scale = LayoutToWorldScale::new(
+                clamp_to_scale_factor(scale.0, false).max(clamp_to_scale_factor(scale.1, false))
+            );
+
+            // Update the template this instance references, which may refresh the GPU
+            // cache with any shared template data, and request the render task.
+            prim_data.update(frame_state, pic_context.surface_index, world_scale);
+
+            if prim_data.src_color.is_none() {
+                prim_instance.clear_visibility();
+            }
+        }
         PrimitiveInstanceKind::Picture { pic_index, segment_instance_index, .. } => {
             profile_scope!("Picture");
             let pic = &mut store.pictures[pic_index.0];
@@ -978,6 +1005,7 @@ fn update_clip_task_for_brush(
         PrimitiveInstanceKind::TextRun { .. } |
         PrimitiveInstanceKind::Clear { .. } |
         PrimitiveInstanceKind::LineDecoration { .. } |
+        PrimitiveInstanceKind::Path { .. } |
         PrimitiveInstanceKind::Backdrop { .. } => {
             return None;
         }
@@ -1123,6 +1151,8 @@ fn update_clip_task_for_brush(
                     device_pixel_scale,
                     &dirty_world_rect,
                     &mut data_stores.clip,
+                    &mut data_stores.clip_path,
+                    &data_stores.clip_text,
                     false,
                     instance.is_chased(),
                 );
@@ -1410,6 +1440,12 @@ fn write_brush_segment_description(
                 // image mask if it's non-repeating).
                 return false;
             }
+            ClipItemKind::Path { .. } |
+            ClipItemKind::Text { .. } => {
+                // Likewise for path and text clips, the coverage of each segment
+                // would require testing the path or the glyphs themselves.
+                return false;
+            }
         };
 
         segment_builder.push_clip_rect(local_clip_rect, radius, mode);
@@ -1482,6 +1518,7 @@ fn build_segments_if_needed(
         PrimitiveInstanceKind::CachedLinearGradient { .. } |
         PrimitiveInstanceKind::RadialGradient { .. } |
         PrimitiveInstanceKind::ConicGradient { .. } |
+        PrimitiveInstanceKind::Path { .. } |
         PrimitiveInstanceKind::LineDecoration { .. } |
         PrimitiveInstanceKind::Backdrop { .. } => {
             // These primitives don't support / need segments.
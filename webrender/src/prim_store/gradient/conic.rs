@@ -9,7 +9,7 @@
 //! Conic gradients are rendered via cached render tasks and composited with the image brush.
 
 use euclid::vec2;
-use api::{ExtendMode, GradientStop, PremultipliedColorF};
+use api::{ColorInterpolationSpace, ExtendMode, GradientStop, PremultipliedColorF};
 use api::units::*;
 use crate::scene_building::IsVisible;
 use crate::frame_builder::FrameBuildingState;
@@ -55,6 +55,7 @@ impl hash::Hash for ConicGradientParams {
 pub struct ConicGradientKey {
     pub common: PrimKeyCommonData,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub center: PointKey,
     pub params: ConicGradientParams,
     pub stretch_size: SizeKey,
@@ -71,6 +72,7 @@ impl ConicGradientKey {
         ConicGradientKey {
             common: info.into(),
             extend_mode: conic_grad.extend_mode,
+            interpolation_space: conic_grad.interpolation_space,
             center: conic_grad.center,
             params: conic_grad.params,
             stretch_size: conic_grad.stretch_size,
@@ -89,6 +91,7 @@ impl InternDebug for ConicGradientKey {}
 pub struct ConicGradientTemplate {
     pub common: PrimTemplateCommonData,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub center: DevicePoint,
     pub params: ConicGradientParams,
     pub task_size: DeviceIntSize,
@@ -154,6 +157,7 @@ impl From<ConicGradientKey> for ConicGradientTemplate {
             common,
             center: DevicePoint::new(item.center.x, item.center.y),
             extend_mode: item.extend_mode,
+            interpolation_space: item.interpolation_space,
             params: item.params,
             stretch_size,
             task_size: task_size.ceil().to_i32(),
@@ -203,6 +207,7 @@ impl ConicGradientTemplate {
         if let Some(mut request) = frame_state.gpu_cache.request(&mut self.stops_handle) {
             GradientGpuBlockBuilder::build(
                 false,
+                self.interpolation_space,
                 &mut request,
                 &self.stops,
             );
@@ -216,6 +221,7 @@ impl ConicGradientTemplate {
             end_offset: FloatKey(self.params.end_offset),
             angle: FloatKey(self.params.angle),
             extend_mode: self.extend_mode,
+            interpolation_space: self.interpolation_space,
             stops: self.stops.iter().map(|stop| (*stop).into()).collect(),
         };
 
@@ -261,6 +267,7 @@ pub type ConicGradientDataHandle = InternHandle<ConicGradient>;
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct ConicGradient {
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub center: PointKey,
     pub params: ConicGradientParams,
     pub stretch_size: SizeKey,
@@ -358,6 +365,7 @@ pub struct ConicGradientCacheKey {
     pub end_offset: FloatKey,
     pub angle: FloatKey,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub stops: Vec<GradientStopKey>,
 }
 
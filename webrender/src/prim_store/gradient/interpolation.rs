@@ -0,0 +1,309 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Gradient color interpolation spaces
+//!
+//! Specification: https://drafts.csswg.org/css-color-4/#interpolation
+//!
+//! Gradient stops are converted to the interpolation space, with the alpha
+//! premultiplied into every component except the hue, interpolated linearly
+//! and converted back to premultiplied sRGB.
+
+use api::{ColorF, ColorInterpolationSpace, HueInterpolationMethod, PremultipliedColorF};
+
+/// A color in an interpolation space. The last component is the alpha, the
+/// others are premultiplied by it, except for the hue of polar spaces.
+pub type InterpolationColor = [f32; 4];
+
+/// Hues of achromatic colors are powerless, and take the value of the hue of
+/// the other color when interpolating.
+const ACHROMATIC_EPSILON: f32 = 0.0001;
+
+/// The interpolation space, as understood by the shaders. Must match the
+/// INTERPOLATION_SPACE_* defines in cs_fast_linear_gradient.glsl.
+pub fn interpolation_space_as_int(space: ColorInterpolationSpace) -> i32 {
+    match space {
+        ColorInterpolationSpace::Srgb => 0,
+        ColorInterpolationSpace::SrgbLinear => 1,
+        ColorInterpolationSpace::Oklab => 2,
+        ColorInterpolationSpace::Oklch(..) => 3,
+        ColorInterpolationSpace::Hsl(..) => 4,
+    }
+}
+
+/// The index of the hue component of polar interpolation spaces.
+fn hue_index(space: ColorInterpolationSpace) -> Option<usize> {
+    match space {
+        ColorInterpolationSpace::Oklch(..) => Some(2),
+        ColorInterpolationSpace::Hsl(..) => Some(0),
+        _ => None,
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    let abs = c.abs();
+    if abs <= 0.04045 {
+        c / 12.92
+    } else {
+        ((abs + 0.055) / 1.055).powf(2.4).copysign(c)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let abs = c.abs();
+    if abs <= 0.0031308 {
+        c * 12.92
+    } else {
+        (1.055 * abs.powf(1.0 / 2.4) - 0.055).copysign(c)
+    }
+}
+
+fn linear_srgb_to_oklab(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+fn oklab_to_linear_srgb(lightness: f32, a: f32, b: f32) -> [f32; 3] {
+    let l = lightness + 0.3963377774 * a + 0.2158037573 * b;
+    let m = lightness - 0.1055613458 * a - 0.0638541728 * b;
+    let s = lightness - 0.0894841775 * a - 1.2914855480 * b;
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
+
+/// Returns the hue in degrees, or NaN if the color is achromatic.
+fn srgb_to_hsl(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) * 0.5;
+    let d = max - min;
+
+    if d.abs() < ACHROMATIC_EPSILON {
+        return [f32::NAN, 0.0, lightness];
+    }
+
+    let saturation = if lightness == 0.0 || lightness == 1.0 {
+        0.0
+    } else {
+        (max - lightness) / lightness.min(1.0 - lightness)
+    };
+
+    let hue = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+
+    [hue * 60.0, saturation, lightness]
+}
+
+fn hsl_to_srgb(hue: f32, saturation: f32, lightness: f32) -> [f32; 3] {
+    let a = saturation * lightness.min(1.0 - lightness);
+    let f = |n: f32| {
+        let k = (n + hue / 30.0).rem_euclid(12.0);
+        lightness - a * (k - 3.0).min(9.0 - k).min(1.0).max(-1.0)
+    };
+
+    [f(0.0), f(8.0), f(4.0)]
+}
+
+/// Convert a color to the given interpolation space. The hue of achromatic
+/// colors is NaN until `adjust_hues` resolves it.
+pub fn to_interpolation_space(
+    color: ColorF,
+    space: ColorInterpolationSpace,
+) -> InterpolationColor {
+    let components = match space {
+        ColorInterpolationSpace::Srgb => [color.r, color.g, color.b],
+        ColorInterpolationSpace::SrgbLinear => [
+            srgb_to_linear(color.r),
+            srgb_to_linear(color.g),
+            srgb_to_linear(color.b),
+        ],
+        ColorInterpolationSpace::Oklab => linear_srgb_to_oklab(
+            srgb_to_linear(color.r),
+            srgb_to_linear(color.g),
+            srgb_to_linear(color.b),
+        ),
+        ColorInterpolationSpace::Oklch(..) => {
+            let [l, a, b] = linear_srgb_to_oklab(
+                srgb_to_linear(color.r),
+                srgb_to_linear(color.g),
+                srgb_to_linear(color.b),
+            );
+            let chroma = (a * a + b * b).sqrt();
+            let hue = if chroma < ACHROMATIC_EPSILON {
+                f32::NAN
+            } else {
+                b.atan2(a).to_degrees().rem_euclid(360.0)
+            };
+            [l, chroma, hue]
+        }
+        ColorInterpolationSpace::Hsl(..) => srgb_to_hsl(color.r, color.g, color.b),
+    };
+
+    let mut result = [components[0], components[1], components[2], color.a];
+    let hue = hue_index(space);
+    for i in 0 .. 3 {
+        if Some(i) != hue {
+            result[i] *= color.a;
+        }
+    }
+
+    result
+}
+
+/// Convert a color from the given interpolation space back to premultiplied sRGB.
+pub fn from_interpolation_space(
+    color: InterpolationColor,
+    space: ColorInterpolationSpace,
+) -> PremultipliedColorF {
+    let alpha = color[3];
+    if alpha <= 0.0 {
+        return PremultipliedColorF::TRANSPARENT;
+    }
+
+    let hue = hue_index(space);
+    let mut c = [color[0], color[1], color[2]];
+    for i in 0 .. 3 {
+        if Some(i) != hue {
+            c[i] /= alpha;
+        }
+    }
+
+    let [r, g, b] = match space {
+        ColorInterpolationSpace::Srgb => c,
+        ColorInterpolationSpace::SrgbLinear => c,
+        ColorInterpolationSpace::Oklab => oklab_to_linear_srgb(c[0], c[1], c[2]),
+        ColorInterpolationSpace::Oklch(..) => {
+            let hue = c[2].to_radians();
+            oklab_to_linear_srgb(c[0], c[1] * hue.cos(), c[1] * hue.sin())
+        }
+        ColorInterpolationSpace::Hsl(..) => hsl_to_srgb(c[0], c[1], c[2]),
+    };
+
+    let [r, g, b] = match space {
+        ColorInterpolationSpace::SrgbLinear |
+        ColorInterpolationSpace::Oklab |
+        ColorInterpolationSpace::Oklch(..) => [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b)],
+        ColorInterpolationSpace::Srgb |
+        ColorInterpolationSpace::Hsl(..) => [r, g, b],
+    };
+
+    ColorF::new(
+        r.max(0.0).min(1.0),
+        g.max(0.0).min(1.0),
+        b.max(0.0).min(1.0),
+        alpha.min(1.0),
+    ).premultiplied()
+}
+
+/// Resolve missing hues and adjust the hue of `to` so that interpolating
+/// linearly between the two colors follows the requested arc.
+pub fn adjust_hues(
+    from: &mut InterpolationColor,
+    to: &mut InterpolationColor,
+    space: ColorInterpolationSpace,
+) {
+    let (index, method) = match space {
+        ColorInterpolationSpace::Oklch(method) => (2, method),
+        ColorInterpolationSpace::Hsl(method) => (0, method),
+        _ => return,
+    };
+
+    match (from[index].is_nan(), to[index].is_nan()) {
+        (true, true) => {
+            from[index] = 0.0;
+            to[index] = 0.0;
+        }
+        (true, false) => from[index] = to[index],
+        (false, true) => to[index] = from[index],
+        (false, false) => {}
+    }
+
+    let delta = to[index] - from[index];
+    match method {
+        HueInterpolationMethod::Shorter => {
+            if delta > 180.0 {
+                to[index] -= 360.0;
+            } else if delta < -180.0 {
+                to[index] += 360.0;
+            }
+        }
+        HueInterpolationMethod::Longer => {
+            if 0.0 < delta && delta < 180.0 {
+                to[index] -= 360.0;
+            } else if -180.0 < delta && delta <= 0.0 {
+                to[index] += 360.0;
+            }
+        }
+    }
+}
+
+pub fn lerp(from: &InterpolationColor, to: &InterpolationColor, t: f32) -> InterpolationColor {
+    [
+        from[0] + (to[0] - from[0]) * t,
+        from[1] + (to[1] - from[1]) * t,
+        from[2] + (to[2] - from[2]) * t,
+        from[3] + (to[3] - from[3]) * t,
+    ]
+}
+
+#[test]
+fn test_interpolation_space_round_trip() {
+    let spaces = [
+        ColorInterpolationSpace::SrgbLinear,
+        ColorInterpolationSpace::Oklab,
+        ColorInterpolationSpace::Oklch(HueInterpolationMethod::Shorter),
+        ColorInterpolationSpace::Hsl(HueInterpolationMethod::Shorter),
+    ];
+    let colors = [
+        ColorF::new(1.0, 0.0, 0.0, 1.0),
+        ColorF::new(0.2, 0.6, 0.9, 0.5),
+        ColorF::new(0.5, 0.5, 0.5, 1.0),
+    ];
+
+    for &space in &spaces {
+        for color in &colors {
+            let mut c = to_interpolation_space(*color, space);
+            let mut other = c;
+            adjust_hues(&mut c, &mut other, space);
+            let result = from_interpolation_space(c, space);
+            let expected = color.premultiplied();
+            assert!((result.r - expected.r).abs() < 0.001, "{:?} {:?}", space, color);
+            assert!((result.g - expected.g).abs() < 0.001, "{:?} {:?}", space, color);
+            assert!((result.b - expected.b).abs() < 0.001, "{:?} {:?}", space, color);
+            assert!((result.a - expected.a).abs() < 0.001, "{:?} {:?}", space, color);
+        }
+    }
+}
+
+#[test]
+fn test_hue_interpolation() {
+    let space = ColorInterpolationSpace::Hsl(HueInterpolationMethod::Shorter);
+    let mut from = [350.0, 1.0, 0.5, 1.0];
+    let mut to = [10.0, 1.0, 0.5, 1.0];
+    adjust_hues(&mut from, &mut to, space);
+    assert_eq!(lerp(&from, &to, 0.5)[0].rem_euclid(360.0), 0.0);
+
+    let space = ColorInterpolationSpace::Hsl(HueInterpolationMethod::Longer);
+    let mut from = [350.0, 1.0, 0.5, 1.0];
+    let mut to = [10.0, 1.0, 0.5, 1.0];
+    adjust_hues(&mut from, &mut to, space);
+    assert_eq!(lerp(&from, &to, 0.5)[0], 180.0);
+}
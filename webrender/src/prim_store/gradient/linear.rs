@@ -10,7 +10,7 @@
 
 use euclid::approxeq::ApproxEq;
 use euclid::{point2, vec2, size2};
-use api::{ExtendMode, GradientStop, LineOrientation, PremultipliedColorF, ColorF, ColorU};
+use api::{ColorInterpolationSpace, ExtendMode, GradientStop, LineOrientation, PremultipliedColorF, ColorU};
 use api::units::*;
 use crate::scene_building::IsVisible;
 use crate::frame_builder::FrameBuildingState;
@@ -28,6 +28,7 @@ use crate::render_task_cache::{RenderTaskCacheKeyKind, RenderTaskCacheKey, Rende
 use crate::picture::{SurfaceIndex};
 use crate::util::pack_as_float;
 use super::{stops_and_min_alpha, GradientStopKey, GradientGpuBlockBuilder, apply_gradient_local_clip};
+use super::{adjust_hues, interpolation_space_as_int, to_interpolation_space};
 use std::ops::{Deref, DerefMut};
 use std::mem::swap;
 
@@ -38,6 +39,7 @@ use std::mem::swap;
 pub struct LinearGradientKey {
     pub common: PrimKeyCommonData,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub start_point: PointKey,
     pub end_point: PointKey,
     pub stretch_size: SizeKey,
@@ -56,6 +58,7 @@ impl LinearGradientKey {
         LinearGradientKey {
             common: info.into(),
             extend_mode: linear_grad.extend_mode,
+            interpolation_space: linear_grad.interpolation_space,
             start_point: linear_grad.start_point,
             end_point: linear_grad.end_point,
             stretch_size: linear_grad.stretch_size,
@@ -76,6 +79,7 @@ impl InternDebug for LinearGradientKey {}
 pub struct LinearGradientTemplate {
     pub common: PrimTemplateCommonData,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub start_point: DevicePoint,
     pub end_point: DevicePoint,
     pub task_size: DeviceIntSize,
@@ -395,6 +399,7 @@ impl From<LinearGradientKey> for LinearGradientTemplate {
         LinearGradientTemplate {
             common,
             extend_mode: item.extend_mode,
+            interpolation_space: item.interpolation_space,
             start_point,
             end_point,
             task_size: task_size.ceil().to_i32(),
@@ -469,6 +474,7 @@ impl LinearGradientTemplate {
             if let Some(mut request) = frame_state.gpu_cache.request(&mut self.stops_handle) {
                 GradientGpuBlockBuilder::build(
                     self.reverse_stops,
+                    self.interpolation_space,
                     &mut request,
                     &self.stops,
                 );
@@ -496,6 +502,7 @@ impl LinearGradientTemplate {
                 color0: self.stops[0].color.into(),
                 color1: self.stops[1].color.into(),
                 orientation,
+                interpolation_space: self.interpolation_space,
             };
 
             frame_state.resource_cache.request_render_task(
@@ -523,6 +530,7 @@ impl LinearGradientTemplate {
                 end: PointKey { x: self.end_point.x, y: self.end_point.y },
                 scale: PointKey { x: self.scale.x, y: self.scale.y },
                 extend_mode: self.extend_mode,
+                interpolation_space: self.interpolation_space,
                 stops: self.stops.iter().map(|stop| (*stop).into()).collect(),
                 reversed_stops: self.reverse_stops,
             };
@@ -564,6 +572,7 @@ pub type LinearGradientDataHandle = InternHandle<LinearGradient>;
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct LinearGradient {
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub start_point: PointKey,
     pub end_point: PointKey,
     pub stretch_size: SizeKey,
@@ -637,18 +646,26 @@ pub struct FastLinearGradientTask {
     pub color0: ColorU,
     pub color1: ColorU,
     pub orientation: LineOrientation,
+    pub interpolation_space: ColorInterpolationSpace,
 }
 
 impl FastLinearGradientTask {
     pub fn to_instance(&self, target_rect: &DeviceIntRect) -> FastLinearGradientInstance {
+        // The colors are converted to the interpolation space here, and back
+        // to sRGB by the shader after interpolating.
+        let mut color0 = to_interpolation_space(self.color0.into(), self.interpolation_space);
+        let mut color1 = to_interpolation_space(self.color1.into(), self.interpolation_space);
+        adjust_hues(&mut color0, &mut color1, self.interpolation_space);
+
         FastLinearGradientInstance {
             task_rect: target_rect.to_f32(),
-            color0: ColorF::from(self.color0).premultiplied(),
-            color1: ColorF::from(self.color1).premultiplied(),
+            color0,
+            color1,
             axis_select: match self.orientation {
                 LineOrientation::Horizontal => 0.0,
                 LineOrientation::Vertical => 1.0,
             },
+            interpolation_space: interpolation_space_as_int(self.interpolation_space),
         }
     }
 }
@@ -664,9 +681,11 @@ pub type FastLinearGradientCacheKey = FastLinearGradientTask;
 #[derive(Clone, Debug)]
 pub struct FastLinearGradientInstance {
     pub task_rect: DeviceRect,
-    pub color0: PremultipliedColorF,
-    pub color1: PremultipliedColorF,
+    /// The colors in the interpolation space, see `to_interpolation_space`.
+    pub color0: [f32; 4],
+    pub color1: [f32; 4],
     pub axis_select: f32,
+    pub interpolation_space: i32,
 }
 
 #[derive(Debug)]
@@ -718,6 +737,7 @@ pub struct LinearGradientCacheKey {
     pub end: PointKey,
     pub scale: PointKey,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub stops: Vec<GradientStopKey>,
     pub reversed_stops: bool,
 }
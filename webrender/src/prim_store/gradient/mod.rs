@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{ColorF, ColorU, GradientStop, PremultipliedColorF};
+use api::{ColorF, ColorInterpolationSpace, ColorU, GradientStop, PremultipliedColorF};
 use api::units::{LayoutRect, LayoutSize, LayoutVector2D};
 use crate::gpu_cache::GpuDataRequest;
 use std::hash;
@@ -10,10 +10,12 @@ use std::hash;
 mod linear;
 mod radial;
 mod conic;
+mod interpolation;
 
 pub use linear::*;
 pub use radial::*;
 pub use conic::*;
+pub use interpolation::*;
 
 /// A hashable gradient stop that can be used in primitive keys.
 #[cfg_attr(feature = "capture", derive(Serialize))]
@@ -103,7 +105,7 @@ impl GradientDataEntry {
 }
 
 // TODO(gw): Tidy this up to be a free function / module?
-struct GradientGpuBlockBuilder {}
+pub struct GradientGpuBlockBuilder {}
 
 impl GradientGpuBlockBuilder {
     /// Generate a color ramp filling the indices in [start_idx, end_idx) and interpolating
@@ -153,6 +155,55 @@ impl GradientGpuBlockBuilder {
         step
     }
 
+    /// Generate a color ramp filling the indices in [start_idx, end_idx), interpolating
+    /// from start_color to end_color in the given color space.
+    ///
+    /// Ramps in other spaces than sRGB are not linear in sRGB, so they are approximated
+    /// with a linear ramp per entry.
+    fn fill_ramp(
+        start_idx: usize,
+        end_idx: usize,
+        start_color: &ColorF,
+        end_color: &ColorF,
+        interpolation_space: ColorInterpolationSpace,
+        entries: &mut [GradientDataEntry; GRADIENT_DATA_SIZE],
+        prev_step: &PremultipliedColorF,
+    ) -> PremultipliedColorF {
+        if interpolation_space == ColorInterpolationSpace::Srgb {
+            return GradientGpuBlockBuilder::fill_colors(
+                start_idx,
+                end_idx,
+                &start_color.premultiplied(),
+                &end_color.premultiplied(),
+                entries,
+                prev_step,
+            );
+        }
+
+        let mut from = to_interpolation_space(*start_color, interpolation_space);
+        let mut to = to_interpolation_space(*end_color, interpolation_space);
+        adjust_hues(&mut from, &mut to, interpolation_space);
+
+        let inv_steps = 1.0 / (end_idx - start_idx) as f32;
+        let mut cur_color = start_color.premultiplied();
+        let mut step = *prev_step;
+        for index in start_idx .. end_idx {
+            let t = (index + 1 - start_idx) as f32 * inv_steps;
+            let next_color = from_interpolation_space(lerp(&from, &to, t), interpolation_space);
+            step = GradientGpuBlockBuilder::fill_colors(
+                index,
+                index + 1,
+                &cur_color,
+                &next_color,
+                entries,
+                &step,
+            );
+            cur_color = next_color;
+        }
+
+        step
+    }
+
     /// Compute an index into the gradient entry table based on a gradient stop offset. This
     /// function maps offsets from [0, 1] to indices in [GRADIENT_DATA_TABLE_BEGIN, GRADIENT_DATA_TABLE_END].
     #[inline]
@@ -163,8 +214,9 @@ impl GradientGpuBlockBuilder {
     }
 
     // Build the gradient data from the supplied stops, reversing them if necessary.
-    fn build(
+    pub fn build(
         reverse_stops: bool,
+        interpolation_space: ColorInterpolationSpace,
         request: &mut GpuDataRequest,
         src_stops: &[GradientStop],
     ) {
@@ -176,11 +228,11 @@ impl GradientGpuBlockBuilder {
         let mut cur_color = match src_stops.next() {
             Some(stop) => {
                 debug_assert_eq!(stop.offset, 0.0);
-                stop.color.premultiplied()
+                stop.color
             }
             None => {
                 error!("Zero gradient stops found!");
-                PremultipliedColorF::BLACK
+                ColorF::BLACK
             }
         };
 
@@ -213,14 +265,14 @@ impl GradientGpuBlockBuilder {
         //     (0,0,0):(0,0,0) | (1,0,0):(-1,1,0) | (0,0,1):(0,1,-1) | (1,1,1):(0,0,0)
         //     ^ solid black     ^ red to green     ^ blue to green    ^ solid white
         let mut entries = [GradientDataEntry::white(); GRADIENT_DATA_SIZE];
-        let mut prev_step = cur_color;
+        let mut prev_step = cur_color.premultiplied();
         if reverse_stops {
             // Fill in the first entry (for reversed stops) with the first color stop
             prev_step = GradientGpuBlockBuilder::fill_colors(
                 GRADIENT_DATA_LAST_STOP,
                 GRADIENT_DATA_LAST_STOP + 1,
-                &cur_color,
-                &cur_color,
+                &cur_color.premultiplied(),
+                &cur_color.premultiplied(),
                 &mut entries,
                 &prev_step,
             );
@@ -230,15 +282,16 @@ impl GradientGpuBlockBuilder {
             // loop will then fill indices in [GRADIENT_DATA_TABLE_BEGIN, GRADIENT_DATA_TABLE_END).
             let mut cur_idx = GRADIENT_DATA_TABLE_END;
             for next in src_stops {
-                let next_color = next.color.premultiplied();
+                let next_color = next.color;
                 let next_idx = Self::get_index(1.0 - next.offset);
 
                 if next_idx < cur_idx {
-                    prev_step = GradientGpuBlockBuilder::fill_colors(
+                    prev_step = GradientGpuBlockBuilder::fill_ramp(
                         next_idx,
                         cur_idx,
                         &next_color,
                         &cur_color,
+                        interpolation_space,
                         &mut entries,
                         &prev_step,
                     );
@@ -255,8 +308,8 @@ impl GradientGpuBlockBuilder {
             GradientGpuBlockBuilder::fill_colors(
                 GRADIENT_DATA_FIRST_STOP,
                 GRADIENT_DATA_FIRST_STOP + 1,
-                &cur_color,
-                &cur_color,
+                &cur_color.premultiplied(),
+                &cur_color.premultiplied(),
                 &mut entries,
                 &prev_step,
             );
@@ -265,8 +318,8 @@ impl GradientGpuBlockBuilder {
             prev_step = GradientGpuBlockBuilder::fill_colors(
                 GRADIENT_DATA_FIRST_STOP,
                 GRADIENT_DATA_FIRST_STOP + 1,
-                &cur_color,
-                &cur_color,
+                &cur_color.premultiplied(),
+                &cur_color.premultiplied(),
                 &mut entries,
                 &prev_step,
             );
@@ -276,15 +329,16 @@ impl GradientGpuBlockBuilder {
             // loop will then fill indices in [GRADIENT_DATA_TABLE_BEGIN, GRADIENT_DATA_TABLE_END).
             let mut cur_idx = GRADIENT_DATA_TABLE_BEGIN;
             for next in src_stops {
-                let next_color = next.color.premultiplied();
+                let next_color = next.color;
                 let next_idx = Self::get_index(next.offset);
 
                 if next_idx > cur_idx {
-                    prev_step = GradientGpuBlockBuilder::fill_colors(
+                    prev_step = GradientGpuBlockBuilder::fill_ramp(
                         cur_idx,
                         next_idx,
                         &cur_color,
                         &next_color,
+                        interpolation_space,
                         &mut entries,
                         &prev_step,
                     );
@@ -301,8 +355,8 @@ impl GradientGpuBlockBuilder {
             GradientGpuBlockBuilder::fill_colors(
                 GRADIENT_DATA_LAST_STOP,
                 GRADIENT_DATA_LAST_STOP + 1,
-                &cur_color,
-                &cur_color,
+                &cur_color.premultiplied(),
+                &cur_color.premultiplied(),
                 &mut entries,
                 &prev_step,
             );
@@ -374,7 +428,7 @@ fn test_struct_sizes() {
     //     be done with care, and after checking if talos performance regresses badly.
     assert_eq!(mem::size_of::<LinearGradient>(), 72, "LinearGradient size changed");
     assert_eq!(mem::size_of::<LinearGradientTemplate>(), 152, "LinearGradientTemplate size changed");
-    assert_eq!(mem::size_of::<LinearGradientKey>(), 88, "LinearGradientKey size changed");
+    assert_eq!(mem::size_of::<LinearGradientKey>(), 96, "LinearGradientKey size changed");
 
     assert_eq!(mem::size_of::<RadialGradient>(), 72, "RadialGradient size changed");
     assert_eq!(mem::size_of::<RadialGradientTemplate>(), 152, "RadialGradientTemplate size changed");
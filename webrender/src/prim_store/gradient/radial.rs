@@ -9,7 +9,7 @@
 //! Radial gradients are rendered via cached render tasks and composited with the image brush.
 
 use euclid::{vec2, size2};
-use api::{ExtendMode, GradientStop, PremultipliedColorF, ColorU};
+use api::{ColorInterpolationSpace, ExtendMode, GradientStop, PremultipliedColorF, ColorU};
 use api::units::*;
 use crate::scene_building::IsVisible;
 use crate::frame_builder::FrameBuildingState;
@@ -58,6 +58,7 @@ impl hash::Hash for RadialGradientParams {
 pub struct RadialGradientKey {
     pub common: PrimKeyCommonData,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub center: PointKey,
     pub params: RadialGradientParams,
     pub stretch_size: SizeKey,
@@ -74,6 +75,7 @@ impl RadialGradientKey {
         RadialGradientKey {
             common: info.into(),
             extend_mode: radial_grad.extend_mode,
+            interpolation_space: radial_grad.interpolation_space,
             center: radial_grad.center,
             params: radial_grad.params,
             stretch_size: radial_grad.stretch_size,
@@ -93,6 +95,7 @@ impl InternDebug for RadialGradientKey {}
 pub struct RadialGradientTemplate {
     pub common: PrimTemplateCommonData,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub params: RadialGradientParams,
     pub center: DevicePoint,
     pub task_size: DeviceIntSize,
@@ -158,6 +161,7 @@ impl From<RadialGradientKey> for RadialGradientTemplate {
             common,
             center: DevicePoint::new(item.center.x, item.center.y),
             extend_mode: item.extend_mode,
+            interpolation_space: item.interpolation_space,
             params: item.params,
             stretch_size,
             task_size: task_size.ceil().to_i32(),
@@ -207,6 +211,7 @@ impl RadialGradientTemplate {
         if let Some(mut request) = frame_state.gpu_cache.request(&mut self.stops_handle) {
             GradientGpuBlockBuilder::build(
                 false,
+                self.interpolation_space,
                 &mut request,
                 &self.stops,
             );
@@ -221,6 +226,7 @@ impl RadialGradientTemplate {
             end_radius: FloatKey(self.params.end_radius),
             ratio_xy: FloatKey(self.params.ratio_xy),
             extend_mode: self.extend_mode,
+            interpolation_space: self.interpolation_space,
             stops: self.stops.iter().map(|stop| (*stop).into()).collect(),
         };
 
@@ -266,6 +272,7 @@ pub type RadialGradientDataHandle = InternHandle<RadialGradient>;
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct RadialGradient {
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub center: PointKey,
     pub params: RadialGradientParams,
     pub stretch_size: SizeKey,
@@ -363,6 +370,7 @@ pub struct RadialGradientCacheKey {
     pub end_radius: FloatKey,
     pub ratio_xy: FloatKey,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
     pub stops: Vec<GradientStopKey>,
 }
 
@@ -5,7 +5,7 @@
 use api::{
     AlphaType, ColorDepth, ColorF, ColorU, ExternalImageData, ExternalImageType,
     ImageKey as ApiImageKey, ImageBufferKind, ImageRendering, PremultipliedColorF,
-    RasterSpace, Shadow, YuvColorSpace, ColorRange, YuvFormat,
+    RasterSpace, Shadow, YuvColorSpace, ColorRange, YuvFormat, YuvTransferFunction,
 };
 use api::units::*;
 use crate::scene_building::{CreateShadow, IsVisible};
@@ -181,6 +181,17 @@ impl ImageData {
                     RenderTask::new_image(size, request)
                 );
 
+                // If the image is a snapshot rendered earlier in this frame,
+                // it must be copied into the texture cache before it is drawn.
+                if let Some(snapshot_task_id) = frame_state.resource_cache.get_snapshot_render_task(self.key) {
+                    add_child_render_task(
+                        parent_surface,
+                        snapshot_task_id,
+                        frame_state.surfaces,
+                        frame_state.rg_builder,
+                    );
+                }
+
                 // On some devices we cannot render from an ImageBufferKind::TextureExternal
                 // source using most shaders, so must peform a copy to a regular texture first.
                 let task_id = if frame_context.fb_config.external_images_require_copy
@@ -495,6 +506,7 @@ pub struct YuvImage {
     pub format: YuvFormat,
     pub color_space: YuvColorSpace,
     pub color_range: ColorRange,
+    pub transfer_function: YuvTransferFunction,
     pub image_rendering: ImageRendering,
 }
 
@@ -524,6 +536,7 @@ pub struct YuvImageData {
     pub format: YuvFormat,
     pub color_space: YuvColorSpace,
     pub color_range: ColorRange,
+    pub transfer_function: YuvTransferFunction,
     pub image_rendering: ImageRendering,
 }
 
@@ -536,6 +549,7 @@ impl From<YuvImage> for YuvImageData {
             format: image.format,
             color_space: image.color_space,
             color_range: image.color_range,
+            transfer_function: image.transfer_function,
             image_rendering: image.image_rendering,
         }
     }
@@ -550,6 +564,7 @@ impl YuvImageData {
         &mut self,
         common: &mut PrimTemplateCommonData,
         frame_state: &mut FrameBuildingState,
+        frame_context: &FrameBuildingContext,
     ) {
 
         self.src_yuv = [ None, None, None ];
@@ -576,7 +591,8 @@ impl YuvImageData {
         }
 
         if let Some(mut request) = frame_state.gpu_cache.request(&mut common.gpu_cache_handle) {
-            self.write_prim_gpu_blocks(&mut request);
+            let hdr = frame_context.fb_config.hdr.unwrap_or_default();
+            self.write_prim_gpu_blocks(&mut request, hdr.reference_white_nits);
         };
 
         // YUV images never have transparency
@@ -602,13 +618,18 @@ impl YuvImageData {
         }
     }
 
-    pub fn write_prim_gpu_blocks(&self, request: &mut GpuDataRequest) {
+    pub fn write_prim_gpu_blocks(&self, request: &mut GpuDataRequest, reference_white_nits: f32) {
         let ranged_color_space = self.color_space.with_range(self.color_range);
+        // The shader decodes PQ when given a reference white.
+        let pq_reference_white = match self.transfer_function {
+            YuvTransferFunction::Sdr => 0.0,
+            YuvTransferFunction::Pq => reference_white_nits,
+        };
         request.push([
             pack_as_float(self.color_depth.bit_depth()),
             pack_as_float(ranged_color_space as u32),
             pack_as_float(self.format as u32),
-            0.0
+            pq_reference_white,
         ]);
     }
 }
@@ -38,6 +38,7 @@ pub mod borders;
 pub mod gradient;
 pub mod image;
 pub mod line_dec;
+pub mod path;
 pub mod picture;
 pub mod text_run;
 pub mod interned;
@@ -49,6 +50,7 @@ use borders::{ImageBorderDataHandle, NormalBorderDataHandle};
 use gradient::{LinearGradientPrimitive, LinearGradientDataHandle, RadialGradientDataHandle, ConicGradientDataHandle};
 use image::{ImageDataHandle, ImageInstance, YuvImageDataHandle};
 use line_dec::LineDecorationDataHandle;
+use path::PathDataHandle;
 use picture::PictureDataHandle;
 use text_run::{TextRunDataHandle, TextRunPrimitive};
 
@@ -1053,6 +1055,11 @@ pub enum PrimitiveInstanceKind {
         data_handle: ConicGradientDataHandle,
         visible_tiles_range: GradientTileRange,
     },
+    /// A vector path, always rendered via a cached render task.
+    Path {
+        /// Handle to the common interned data for this primitive.
+        data_handle: PathDataHandle,
+    },
     /// Clear out a rect, used for special effects.
     Clear {
         /// Handle to the common interned data for this primitive.
@@ -1163,6 +1170,9 @@ impl PrimitiveInstance {
             PrimitiveInstanceKind::ConicGradient { data_handle, .. } => {
                 data_handle.uid()
             }
+            PrimitiveInstanceKind::Path { data_handle, .. } => {
+                data_handle.uid()
+            }
             PrimitiveInstanceKind::TextRun { data_handle, .. } => {
                 data_handle.uid()
             }
@@ -0,0 +1,559 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Vector paths
+//!
+//! Paths are flattened into a list of edges when the primitive is interned.
+//! The edges are uploaded to the GPU cache and rasterized by a cached render
+//! task (together with the brush, if it is a gradient), which is composited
+//! with the image brush.
+
+use api::{
+    ColorF, ColorInterpolationSpace, ColorU, ExtendMode, FillRule, GradientStop, LineCap, LineJoin,
+    PathBrush, PathCommand, PathStroke, PremultipliedColorF, RasterSpace, Shadow,
+    MAX_PATH_DASH_COUNT,
+};
+use api::units::*;
+use crate::scene_building::{CreateShadow, IsVisible};
+use crate::frame_builder::FrameBuildingState;
+use crate::gpu_cache::{GpuCache, GpuCacheHandle};
+use crate::intern;
+use crate::internal_types::LayoutPrimitiveInfo;
+use crate::path::{self, PathEdge};
+use crate::picture::SurfaceIndex;
+use crate::prim_store::{
+    FloatKey, InternablePrimitive, PointKey, PrimKey, PrimTemplateCommonData,
+    PrimitiveInstanceKind, PrimitiveStore, SizeKey,
+};
+use crate::prim_store::gradient::{GradientStopKey, GradientGpuBlockBuilder};
+use crate::render_task::{RenderTask, RenderTaskKind};
+use crate::render_task_cache::{RenderTaskCacheKey, RenderTaskCacheKeyKind, RenderTaskParent};
+use crate::render_task_graph::RenderTaskId;
+use euclid::Scale;
+use std::{cmp, ops::{Deref, DerefMut}};
+
+/// Maximum resolution in device pixels at which paths are rasterized.
+pub const MAX_PATH_RESOLUTION: u32 = 4096;
+
+/// A hashable path command, relative to the origin of the primitive.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Debug, Copy, Clone, Eq, Hash, MallocSizeOf, PartialEq)]
+pub enum PathCommandKey {
+    MoveTo(PointKey),
+    LineTo(PointKey),
+    QuadTo(PointKey, PointKey),
+    CubicTo(PointKey, PointKey, PointKey),
+    Close,
+}
+
+impl PathCommandKey {
+    pub fn new(command: PathCommand, offset: LayoutVector2D) -> Self {
+        match command {
+            PathCommand::MoveTo(p) => PathCommandKey::MoveTo((p - offset).into()),
+            PathCommand::LineTo(p) => PathCommandKey::LineTo((p - offset).into()),
+            PathCommand::QuadTo(c, p) => PathCommandKey::QuadTo(
+                (c - offset).into(),
+                (p - offset).into(),
+            ),
+            PathCommand::CubicTo(c1, c2, p) => PathCommandKey::CubicTo(
+                (c1 - offset).into(),
+                (c2 - offset).into(),
+                (p - offset).into(),
+            ),
+            PathCommand::Close => PathCommandKey::Close,
+        }
+    }
+}
+
+impl Into<PathCommand> for PathCommandKey {
+    fn into(self) -> PathCommand {
+        match self {
+            PathCommandKey::MoveTo(p) => PathCommand::MoveTo(p.into()),
+            PathCommandKey::LineTo(p) => PathCommand::LineTo(p.into()),
+            PathCommandKey::QuadTo(c, p) => PathCommand::QuadTo(c.into(), p.into()),
+            PathCommandKey::CubicTo(c1, c2, p) => PathCommand::CubicTo(c1.into(), c2.into(), p.into()),
+            PathCommandKey::Close => PathCommand::Close,
+        }
+    }
+}
+
+/// Hashable stroke parameters, for use during prim interning.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Debug, Clone, Eq, Hash, MallocSizeOf, PartialEq)]
+pub struct PathStrokeKey {
+    pub width: FloatKey,
+    pub line_join: LineJoin,
+    pub line_cap: LineCap,
+    pub miter_limit: FloatKey,
+    pub dashes: Vec<FloatKey>,
+    pub dash_offset: FloatKey,
+}
+
+impl From<PathStroke> for PathStrokeKey {
+    fn from(stroke: PathStroke) -> Self {
+        PathStrokeKey {
+            width: FloatKey(stroke.width),
+            line_join: stroke.line_join,
+            line_cap: stroke.line_cap,
+            miter_limit: FloatKey(stroke.miter_limit),
+            dashes: stroke.dashes().iter().map(|d| FloatKey(*d)).collect(),
+            dash_offset: FloatKey(stroke.dash_offset),
+        }
+    }
+}
+
+impl Into<PathStroke> for PathStrokeKey {
+    fn into(self) -> PathStroke {
+        let mut stroke = PathStroke::solid(self.width.0);
+        stroke.line_join = self.line_join;
+        stroke.line_cap = self.line_cap;
+        stroke.miter_limit = self.miter_limit.0;
+        for (dst, src) in stroke.dash_array.iter_mut().zip(self.dashes.iter()) {
+            *dst = src.0;
+        }
+        stroke.dash_count = self.dashes.len().min(MAX_PATH_DASH_COUNT) as u8;
+        stroke.dash_offset = self.dash_offset.0;
+        stroke
+    }
+}
+
+/// What a path is painted with. Positions are relative to the origin of the
+/// primitive. Gradient stops are stored separately in the key.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Debug, Clone, Eq, Hash, MallocSizeOf, PartialEq)]
+pub enum PathBrushKey {
+    Color(ColorU),
+    LinearGradient {
+        start_point: PointKey,
+        end_point: PointKey,
+        extend_mode: ExtendMode,
+        interpolation_space: ColorInterpolationSpace,
+    },
+    RadialGradient {
+        center: PointKey,
+        radius: SizeKey,
+        start_offset: FloatKey,
+        end_offset: FloatKey,
+        extend_mode: ExtendMode,
+        interpolation_space: ColorInterpolationSpace,
+    },
+    ConicGradient {
+        center: PointKey,
+        angle: FloatKey,
+        start_offset: FloatKey,
+        end_offset: FloatKey,
+        extend_mode: ExtendMode,
+        interpolation_space: ColorInterpolationSpace,
+    },
+}
+
+impl PathBrushKey {
+    pub fn new(brush: &PathBrush, offset: LayoutVector2D) -> Self {
+        match *brush {
+            PathBrush::Color(color) => PathBrushKey::Color(color.into()),
+            PathBrush::LinearGradient(ref gradient) => PathBrushKey::LinearGradient {
+                start_point: (gradient.start_point - offset).into(),
+                end_point: (gradient.end_point - offset).into(),
+                extend_mode: gradient.extend_mode,
+                interpolation_space: gradient.interpolation_space,
+            },
+            PathBrush::RadialGradient(ref gradient) => PathBrushKey::RadialGradient {
+                center: (gradient.center - offset).into(),
+                radius: gradient.radius.into(),
+                start_offset: FloatKey(gradient.start_offset),
+                end_offset: FloatKey(gradient.end_offset),
+                extend_mode: gradient.extend_mode,
+                interpolation_space: gradient.interpolation_space,
+            },
+            PathBrush::ConicGradient(ref gradient) => PathBrushKey::ConicGradient {
+                center: (gradient.center - offset).into(),
+                angle: FloatKey(gradient.angle),
+                start_offset: FloatKey(gradient.start_offset),
+                end_offset: FloatKey(gradient.end_offset),
+                extend_mode: gradient.extend_mode,
+                interpolation_space: gradient.interpolation_space,
+            },
+        }
+    }
+
+    /// The brush kind, must match the PATH_BRUSH_* defines in cs_path.glsl.
+    fn kind(&self) -> i32 {
+        match *self {
+            PathBrushKey::Color(..) => 0,
+            PathBrushKey::LinearGradient { .. } => 1,
+            PathBrushKey::RadialGradient { .. } => 2,
+            PathBrushKey::ConicGradient { .. } => 3,
+        }
+    }
+
+    fn extend_mode(&self) -> ExtendMode {
+        match *self {
+            PathBrushKey::Color(..) => ExtendMode::Clamp,
+            PathBrushKey::LinearGradient { extend_mode, .. } |
+            PathBrushKey::RadialGradient { extend_mode, .. } |
+            PathBrushKey::ConicGradient { extend_mode, .. } => extend_mode,
+        }
+    }
+
+    fn interpolation_space(&self) -> ColorInterpolationSpace {
+        match *self {
+            PathBrushKey::Color(..) => ColorInterpolationSpace::Srgb,
+            PathBrushKey::LinearGradient { interpolation_space, .. } |
+            PathBrushKey::RadialGradient { interpolation_space, .. } |
+            PathBrushKey::ConicGradient { interpolation_space, .. } => interpolation_space,
+        }
+    }
+
+    /// The brush parameters, as interpreted by cs_path.glsl.
+    fn params(&self) -> [f32; 8] {
+        match *self {
+            PathBrushKey::Color(..) => [0.0; 8],
+            PathBrushKey::LinearGradient { start_point, end_point, .. } => [
+                start_point.x, start_point.y, end_point.x, end_point.y,
+                0.0, 0.0, 0.0, 0.0,
+            ],
+            PathBrushKey::RadialGradient { center, radius, start_offset, end_offset, .. } => {
+                let radius: LayoutSize = radius.into();
+                [
+                    center.x, center.y, radius.width, radius.height,
+                    start_offset.0, end_offset.0, 0.0, 0.0,
+                ]
+            }
+            PathBrushKey::ConicGradient { center, angle, start_offset, end_offset, .. } => [
+                center.x, center.y, angle.0, 0.0,
+                start_offset.0, end_offset.0, 0.0, 0.0,
+            ],
+        }
+    }
+}
+
+/// Identifying key for a path.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Debug, Clone, Eq, Hash, MallocSizeOf, PartialEq)]
+pub struct PathPrim {
+    pub commands: Vec<PathCommandKey>,
+    pub fill_rule: FillRule,
+    pub stroke: Option<PathStrokeKey>,
+    pub brush: PathBrushKey,
+    pub stops: Vec<GradientStopKey>,
+}
+
+pub type PathKey = PrimKey<PathPrim>;
+
+impl PathKey {
+    pub fn new(
+        info: &LayoutPrimitiveInfo,
+        path: PathPrim,
+    ) -> Self {
+        PathKey {
+            common: info.into(),
+            kind: path,
+        }
+    }
+}
+
+impl intern::InternDebug for PathKey {}
+
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(MallocSizeOf)]
+pub struct PathTemplate {
+    pub common: PrimTemplateCommonData,
+    pub path: PathPrim,
+    /// The flattened edges of the path (or of its stroke outline), relative
+    /// to the origin of the primitive.
+    pub edges: Vec<PathEdge>,
+    pub edges_handle: GpuCacheHandle,
+    pub stops: Vec<GradientStop>,
+    pub stops_handle: GpuCacheHandle,
+    pub src_color: Option<RenderTaskId>,
+}
+
+impl Deref for PathTemplate {
+    type Target = PrimTemplateCommonData;
+    fn deref(&self) -> &Self::Target {
+        &self.common
+    }
+}
+
+impl DerefMut for PathTemplate {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.common
+    }
+}
+
+impl From<PathKey> for PathTemplate {
+    fn from(item: PathKey) -> Self {
+        let common = PrimTemplateCommonData::with_key_common(item.common);
+        let path = item.kind;
+
+        let edges = path::fit_edges(|tolerance| build_edges(&path, tolerance));
+
+        let stops = path.stops.iter().map(|stop| {
+            GradientStop {
+                offset: stop.offset,
+                color: stop.color.into(),
+            }
+        }).collect();
+
+        PathTemplate {
+            common,
+            path,
+            edges,
+            edges_handle: GpuCacheHandle::new(),
+            stops,
+            stops_handle: GpuCacheHandle::new(),
+            src_color: None,
+        }
+    }
+}
+
+fn build_edges(path: &PathPrim, tolerance: f32) -> Vec<PathEdge> {
+    let polylines = path::flatten_path(
+        path.commands.iter().map(|command| (*command).into()),
+        tolerance,
+    );
+
+    let mut edges = Vec::new();
+    match path.stroke {
+        Some(ref stroke) => {
+            path::stroke_edges(&polylines, &stroke.clone().into(), tolerance, &mut edges);
+        }
+        None => {
+            path::fill_edges(&polylines, &mut edges);
+        }
+    }
+
+    edges
+}
+
+impl PathTemplate {
+    /// Update the GPU cache for a given primitive template. This may be called multiple
+    /// times per frame, by each primitive reference that refers to this interned
+    /// template. The initial request call to the GPU cache ensures that work is only
+    /// done if the cache entry is invalid (due to first use or eviction).
+    pub fn update(
+        &mut self,
+        frame_state: &mut FrameBuildingState,
+        parent_surface: SurfaceIndex,
+        world_scale: LayoutToWorldScale,
+    ) {
+        if let Some(mut request) = frame_state.gpu_cache.request(&mut self.common.gpu_cache_handle) {
+            // Solid paths are rendered as an alpha mask and tinted by the image
+            // brush, gradients are rendered directly into the render task.
+            let color = match self.path.brush {
+                PathBrushKey::Color(color) => ColorF::from(color).premultiplied(),
+                _ => PremultipliedColorF::WHITE,
+            };
+            request.push(color);
+            request.push(PremultipliedColorF::WHITE);
+            request.push([
+                self.common.prim_rect.width(),
+                self.common.prim_rect.height(),
+                0.0,
+                0.0,
+            ]);
+        }
+
+        if self.edges.is_empty() {
+            self.src_color = None;
+            return;
+        }
+
+        if let Some(mut request) = frame_state.gpu_cache.request(&mut self.edges_handle) {
+            for edge in &self.edges {
+                request.push([edge.from.x, edge.from.y, edge.to.x, edge.to.y]);
+            }
+        }
+
+        if !self.stops.is_empty() {
+            if let Some(mut request) = frame_state.gpu_cache.request(&mut self.stops_handle) {
+                GradientGpuBlockBuilder::build(
+                    false,
+                    self.path.brush.interpolation_space(),
+                    &mut request,
+                    &self.stops,
+                );
+            }
+        }
+
+        let device_pixel_scale = frame_state.surfaces[parent_surface.0].device_pixel_scale;
+        let scale_factor = world_scale * device_pixel_scale;
+        let local_size = self.common.prim_rect.size();
+        let mut task_size = (local_size * scale_factor).ceil().to_i32();
+        if task_size.width > MAX_PATH_RESOLUTION as i32 ||
+           task_size.height > MAX_PATH_RESOLUTION as i32 {
+            let max_extent = cmp::max(task_size.width, task_size.height);
+            let task_scale_factor = Scale::new(MAX_PATH_RESOLUTION as f32 / max_extent as f32);
+            task_size = (local_size * scale_factor * task_scale_factor).ceil().to_i32();
+        }
+
+        if task_size.width <= 0 || task_size.height <= 0 {
+            self.src_color = None;
+            return;
+        }
+
+        let cache_key = PathCacheKey {
+            size: task_size,
+            local_size: local_size.into(),
+            path: self.path.clone(),
+        };
+
+        let task_id = frame_state.resource_cache.request_render_task(
+            RenderTaskCacheKey {
+                size: task_size,
+                kind: RenderTaskCacheKeyKind::Path(cache_key),
+            },
+            frame_state.gpu_cache,
+            frame_state.rg_builder,
+            None,
+            false,
+            RenderTaskParent::Surface(parent_surface),
+            frame_state.surfaces,
+            |rg_builder| {
+                rg_builder.add().init(RenderTask::new_dynamic(
+                    task_size,
+                    RenderTaskKind::Path(PathTask {
+                        local_size,
+                        fill_rule: match self.path.stroke {
+                            Some(..) => FillRule::Nonzero,
+                            None => self.path.fill_rule,
+                        },
+                        edges: self.edges_handle,
+                        edge_count: self.edges.len() as i32,
+                        brush_kind: self.path.brush.kind(),
+                        brush_params: self.path.brush.params(),
+                        extend_mode: self.path.brush.extend_mode(),
+                        stops: self.stops_handle,
+                    }),
+                ))
+            }
+        );
+
+        self.src_color = Some(task_id);
+    }
+}
+
+pub type PathDataHandle = intern::Handle<PathPrim>;
+
+impl intern::Internable for PathPrim {
+    type Key = PathKey;
+    type StoreData = PathTemplate;
+    type InternData = ();
+    const PROFILE_COUNTER: usize = crate::profiler::INTERNED_PATHS;
+}
+
+impl InternablePrimitive for PathPrim {
+    fn into_key(
+        self,
+        info: &LayoutPrimitiveInfo,
+    ) -> PathKey {
+        PathKey::new(info, self)
+    }
+
+    fn make_instance_kind(
+        _key: PathKey,
+        data_handle: PathDataHandle,
+        _: &mut PrimitiveStore,
+        _reference_frame_relative_offset: LayoutVector2D,
+    ) -> PrimitiveInstanceKind {
+        PrimitiveInstanceKind::Path {
+            data_handle,
+        }
+    }
+}
+
+impl CreateShadow for PathPrim {
+    fn create_shadow(
+        &self,
+        shadow: &Shadow,
+        _: bool,
+        _: RasterSpace,
+    ) -> Self {
+        PathPrim {
+            commands: self.commands.clone(),
+            fill_rule: self.fill_rule,
+            stroke: self.stroke.clone(),
+            brush: PathBrushKey::Color(shadow.color.into()),
+            stops: Vec::new(),
+        }
+    }
+}
+
+impl IsVisible for PathPrim {
+    fn is_visible(&self) -> bool {
+        match self.brush {
+            PathBrushKey::Color(color) => color.a > 0,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct PathTask {
+    pub local_size: LayoutSize,
+    pub fill_rule: FillRule,
+    pub edges: GpuCacheHandle,
+    pub edge_count: i32,
+    pub brush_kind: i32,
+    pub brush_params: [f32; 8],
+    pub extend_mode: ExtendMode,
+    pub stops: GpuCacheHandle,
+}
+
+impl PathTask {
+    pub fn to_instance(&self, target_rect: &DeviceIntRect, gpu_cache: &mut GpuCache) -> PathInstance {
+        let p = &self.brush_params;
+        PathInstance {
+            task_rect: target_rect.to_f32(),
+            local_size: self.local_size,
+            brush_params0: [p[0], p[1], p[2], p[3]],
+            brush_params1: [p[4], p[5], p[6], p[7]],
+            edges_address: self.edges.as_int(gpu_cache),
+            edge_count: self.edge_count,
+            fill_rule: self.fill_rule as i32,
+            brush_kind: self.brush_kind,
+            extend_mode: self.extend_mode as i32,
+            gradient_stops_address: match self.brush_kind {
+                0 => 0,
+                _ => self.stops.as_int(gpu_cache),
+            },
+        }
+    }
+}
+
+/// The per-instance shader input of a path render task.
+///
+/// Must match the PATH instance description in renderer/vertex.rs.
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub struct PathInstance {
+    pub task_rect: DeviceRect,
+    pub local_size: LayoutSize,
+    pub brush_params0: [f32; 4],
+    pub brush_params1: [f32; 4],
+    pub edges_address: i32,
+    pub edge_count: i32,
+    pub fill_rule: i32,
+    pub brush_kind: i32,
+    pub extend_mode: i32,
+    pub gradient_stops_address: i32,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct PathCacheKey {
+    pub size: DeviceIntSize,
+    pub local_size: SizeKey,
+    pub path: PathPrim,
+}
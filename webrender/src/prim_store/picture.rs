@@ -4,7 +4,9 @@
 
 use api::{
     ColorU, MixBlendMode, FilterPrimitiveInput, FilterPrimitiveKind, ColorSpace,
-    PropertyBinding, PropertyBindingId, CompositeOperator,
+    PropertyBinding, PropertyBindingId, CompositeOperator, TurbulenceType,
+    MorphologyOperator, EdgeMode, ColorChannel, LightSource, ImageKey, ImageRendering,
+    SVGFE_CONVOLVE_VALUES_LIMIT,
 };
 use api::units::{Au, LayoutVector2D};
 use crate::scene_building::IsVisible;
@@ -14,7 +16,7 @@ use crate::intern::{Internable, InternDebug, Handle as InternHandle};
 use crate::internal_types::{LayoutPrimitiveInfo, Filter};
 use crate::picture::PictureCompositeMode;
 use crate::prim_store::{
-    PrimitiveInstanceKind, PrimitiveStore, VectorKey,
+    PrimitiveInstanceKind, PrimitiveStore, RectangleKey, VectorKey,
     InternablePrimitive,
 };
 
@@ -53,6 +55,51 @@ impl From<CompositeOperator> for CompositeOperatorKey {
     }
 }
 
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+#[derive(Debug, Clone, MallocSizeOf, PartialEq, Hash, Eq)]
+pub enum LightSourceKey {
+    Distant(Au, Au),
+    Point([Au; 3]),
+    Spot([Au; 3], [Au; 3], Au, Option<Au>),
+}
+
+impl From<LightSource> for LightSourceKey {
+    fn from(light: LightSource) -> Self {
+        match light {
+            LightSource::Distant(light) => {
+                LightSourceKey::Distant(
+                    Au::from_f32_px(light.azimuth),
+                    Au::from_f32_px(light.elevation),
+                )
+            }
+            LightSource::Point(light) => {
+                LightSourceKey::Point([
+                    Au::from_f32_px(light.x),
+                    Au::from_f32_px(light.y),
+                    Au::from_f32_px(light.z),
+                ])
+            }
+            LightSource::Spot(light) => {
+                LightSourceKey::Spot(
+                    [
+                        Au::from_f32_px(light.x),
+                        Au::from_f32_px(light.y),
+                        Au::from_f32_px(light.z),
+                    ],
+                    [
+                        Au::from_f32_px(light.points_at_x),
+                        Au::from_f32_px(light.points_at_y),
+                        Au::from_f32_px(light.points_at_z),
+                    ],
+                    Au::from_f32_px(light.specular_exponent),
+                    light.limiting_cone_angle.map(Au::from_f32_px),
+                )
+            }
+        }
+    }
+}
+
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 #[derive(Debug, Clone, MallocSizeOf, PartialEq, Hash, Eq)]
@@ -67,6 +114,27 @@ pub enum FilterPrimitiveKey {
     ComponentTransfer(ColorSpace, FilterPrimitiveInput, Vec<SFilterData>),
     Offset(ColorSpace, FilterPrimitiveInput, VectorKey),
     Composite(ColorSpace, FilterPrimitiveInput, FilterPrimitiveInput, CompositeOperatorKey),
+    /// Base frequencies are keyed on their bits, as typical values are far
+    /// smaller than the precision of an `Au`.
+    Turbulence(ColorSpace, (u32, u32), u32, i32, bool, TurbulenceType),
+    Morphology(ColorSpace, FilterPrimitiveInput, (Au, Au), MorphologyOperator),
+    /// Kernel weights, divisor and bias are keyed on their bits.
+    ConvolveMatrix(
+        ColorSpace,
+        FilterPrimitiveInput,
+        (u32, u32),
+        [u32; SVGFE_CONVOLVE_VALUES_LIMIT],
+        (u32, u32),
+        (u32, u32),
+        EdgeMode,
+        bool,
+    ),
+    DisplacementMap(ColorSpace, FilterPrimitiveInput, FilterPrimitiveInput, Au, ColorChannel, ColorChannel),
+    DiffuseLighting(ColorSpace, FilterPrimitiveInput, (Au, Au), ColorU, LightSourceKey),
+    SpecularLighting(ColorSpace, FilterPrimitiveInput, (Au, Au, Au), ColorU, LightSourceKey),
+    Tile(ColorSpace, FilterPrimitiveInput, RectangleKey),
+    Merge(ColorSpace, Vec<FilterPrimitiveInput>),
+    Image(ColorSpace, ImageKey, RectangleKey, ImageRendering),
 }
 
 /// Represents a hashable description of how a picture primitive
@@ -217,6 +285,73 @@ impl From<Option<PictureCompositeMode>> for PictureCompositeKey {
                             FilterPrimitiveKey::Offset(primitive.color_space, info.input, info.offset.into()),
                         FilterPrimitiveKind::Composite(info) =>
                             FilterPrimitiveKey::Composite(primitive.color_space, info.input1, info.input2, info.operator.into()),
+                        FilterPrimitiveKind::Turbulence(info) =>
+                            FilterPrimitiveKey::Turbulence(
+                                primitive.color_space,
+                                (info.base_frequency_x.to_bits(), info.base_frequency_y.to_bits()),
+                                info.num_octaves,
+                                info.seed,
+                                info.stitch_tiles,
+                                info.kind,
+                            ),
+                        FilterPrimitiveKind::Morphology(info) =>
+                            FilterPrimitiveKey::Morphology(
+                                primitive.color_space,
+                                info.input,
+                                (Au::from_f32_px(info.radius_x), Au::from_f32_px(info.radius_y)),
+                                info.operator,
+                            ),
+                        FilterPrimitiveKind::ConvolveMatrix(info) => {
+                            let mut kernel_bits = [0; SVGFE_CONVOLVE_VALUES_LIMIT];
+                            for (value, result) in info.kernel.iter().zip(kernel_bits.iter_mut()) {
+                                *result = value.to_bits();
+                            }
+                            FilterPrimitiveKey::ConvolveMatrix(
+                                primitive.color_space,
+                                info.input,
+                                (info.order_x, info.order_y),
+                                kernel_bits,
+                                (info.divisor.to_bits(), info.bias.to_bits()),
+                                (info.target_x, info.target_y),
+                                info.edge_mode,
+                                info.preserve_alpha,
+                            )
+                        }
+                        FilterPrimitiveKind::DisplacementMap(info) =>
+                            FilterPrimitiveKey::DisplacementMap(
+                                primitive.color_space,
+                                info.input1,
+                                info.input2,
+                                Au::from_f32_px(info.scale),
+                                info.x_channel_selector,
+                                info.y_channel_selector,
+                            ),
+                        FilterPrimitiveKind::DiffuseLighting(info) =>
+                            FilterPrimitiveKey::DiffuseLighting(
+                                primitive.color_space,
+                                info.input,
+                                (Au::from_f32_px(info.surface_scale), Au::from_f32_px(info.diffuse_constant)),
+                                info.lighting_color.into(),
+                                info.light.into(),
+                            ),
+                        FilterPrimitiveKind::SpecularLighting(info) =>
+                            FilterPrimitiveKey::SpecularLighting(
+                                primitive.color_space,
+                                info.input,
+                                (
+                                    Au::from_f32_px(info.surface_scale),
+                                    Au::from_f32_px(info.specular_constant),
+                                    Au::from_f32_px(info.specular_exponent),
+                                ),
+                                info.lighting_color.into(),
+                                info.light.into(),
+                            ),
+                        FilterPrimitiveKind::Tile(info) =>
+                            FilterPrimitiveKey::Tile(primitive.color_space, info.input, info.source_rect.into()),
+                        FilterPrimitiveKind::Merge(info) =>
+                            FilterPrimitiveKey::Merge(primitive.color_space, info.inputs().to_vec()),
+                        FilterPrimitiveKind::Image(info) =>
+                            FilterPrimitiveKey::Image(primitive.color_space, info.image_key, info.rect.into(), info.rendering),
                     }
                 }).collect())
             }
@@ -6,7 +6,7 @@ use api::{ColorF, FontInstanceFlags, GlyphInstance, RasterSpace, Shadow};
 use api::units::{LayoutToWorldTransform, LayoutVector2D, RasterPixelScale, DevicePixelScale};
 use crate::scene_building::{CreateShadow, IsVisible};
 use crate::frame_builder::FrameBuildingState;
-use crate::glyph_rasterizer::{FontInstance, FontTransform, GlyphKey, FONT_SIZE_LIMIT};
+use crate::glyph_rasterizer::{FontInstance, FontTransform, GlyphKey, FONT_SIZE_LIMIT, SDF_REFERENCE_SIZE};
 use crate::gpu_cache::GpuCache;
 use crate::intern;
 use crate::internal_types::LayoutPrimitiveInfo;
@@ -247,6 +247,7 @@ impl TextRunPrimitive {
         mut allow_subpixel: bool,
         raster_space: RasterSpace,
         root_scaling_factor: f32,
+        distance_field: bool,
         spatial_tree: &SpatialTree,
     ) -> bool {
         // If local raster space is specified, include that in the scale
@@ -275,7 +276,9 @@ impl TextRunPrimitive {
         // Only support transforms that can be coerced to simple 2D transforms.
         // Add texture padding to the rasterized glyph buffer when one anticipates
         // the glyph will need to be scaled when rendered.
-        let (use_subpixel_aa, transform_glyphs, texture_padding, oversized) = if raster_space != RasterSpace::Screen ||
+        let (use_subpixel_aa, transform_glyphs, texture_padding, oversized) = if distance_field {
+            (false, false, false, false)
+        } else if raster_space != RasterSpace::Screen ||
             transform.has_perspective_component() || !transform.has_2d_inverse()
         {
             (false, false, true, device_font_size > FONT_SIZE_LIMIT)
@@ -291,7 +294,10 @@ impl TextRunPrimitive {
             self.raster_scale = 1.0;
             FontTransform::from(transform)
         } else {
-            if oversized {
+            if distance_field {
+                device_font_size = SDF_REFERENCE_SIZE;
+                self.raster_scale = SDF_REFERENCE_SIZE / (font_size * dps);
+            } else if oversized {
                 // Font sizes larger than the limit need to be scaled, thus can't use subpixels.
                 // In this case we adjust the font size and raster space to ensure
                 // we rasterize at the limit, to minimize the amount of scaling.
@@ -324,6 +330,10 @@ impl TextRunPrimitive {
             // Don't touch the reference frame relative offset. We'll let the
             // shader do the snapping in device pixels.
             self.reference_frame_relative_offset
+        } else if distance_field {
+            // Snapping would make animated text jitter, and distance field glyphs
+            // look the same at any subpixel offset.
+            self.reference_frame_relative_offset
         } else {
             // TODO(dp): The SurfaceInfo struct needs to be updated to use RasterPixelScale
             //           rather than DevicePixelScale, however this is a large chunk of
@@ -348,6 +358,14 @@ impl TextRunPrimitive {
         if texture_padding {
             flags |= FontInstanceFlags::TEXTURE_PADDING;
         }
+        if distance_field {
+            // The same glyph is used at every subpixel offset.
+            flags.remove(FontInstanceFlags::SUBPIXEL_POSITION);
+        } else {
+            // The font can't be rasterized into distance fields, so its glyphs
+            // are rasterized normally at the device size.
+            flags.remove(FontInstanceFlags::DISTANCE_FIELD);
+        }
 
         // If the transform or device size is different, then the caller of
         // this method needs to know to rebuild the glyphs.
@@ -450,6 +468,13 @@ impl TextRunPrimitive {
             spatial_tree,
         );
 
+        // Distance field glyphs are rasterized once at a reference size, and the
+        // shader scales them to any size and transform. They are rasterized from
+        // outlines, so stroked fonts and fonts without outlines use the normal
+        // raster path.
+        let distance_field = specified_font.use_distance_field() &&
+            resource_cache.can_rasterize_distance_field(&specified_font.font_key);
+
         let cache_dirty = self.update_font_instance(
             specified_font,
             surface,
@@ -458,6 +483,7 @@ impl TextRunPrimitive {
             allow_subpixel,
             raster_space,
             root_scaling_factor,
+            distance_field,
             spatial_tree,
         );
 
@@ -71,9 +71,9 @@ static PROFILER_PRESETS: &'static[(&'static str, &'static str)] = &[
     // Texture cache allocation stats.
     (&"Texture cache stats", &"Atlas textures mem, Standalone textures mem, Picture tiles mem, Render targets mem, Depth targets mem, Atlas items mem,
         Texture cache standalone pressure, Texture cache eviction count, Texture cache youngest evicted, ,
-        Atlas RGBA8 linear pixels, Atlas RGBA8 glyphs pixels, Atlas A8 glyphs pixels, Atlas A8 pixels, Atlas A16 pixels, Atlas RGBA8 nearest pixels,
-        Atlas RGBA8 linear textures, Atlas RGBA8 glyphs textures, Atlas A8 glyphs textures, Atlas A8 textures, Atlas A16 textures, Atlas RGBA8 nearest textures,
-        Atlas RGBA8 linear pressure, Atlas RGBA8 glyphs pressure, Atlas A8 glyphs pressure, Atlas A8 pressure, Atlas A16 pressure, Atlas RGBA8 nearest pressure,"
+        Atlas RGBA8 linear pixels, Atlas RGBA8 glyphs pixels, Atlas A8 glyphs pixels, Atlas A8 pixels, Atlas A16 pixels, Atlas RGBA8 nearest pixels, Atlas RGBA16F linear pixels,
+        Atlas RGBA8 linear textures, Atlas RGBA8 glyphs textures, Atlas A8 glyphs textures, Atlas A8 textures, Atlas A16 textures, Atlas RGBA8 nearest textures, Atlas RGBA16F linear textures,
+        Atlas RGBA8 linear pressure, Atlas RGBA8 glyphs pressure, Atlas A8 glyphs pressure, Atlas A8 pressure, Atlas A16 pressure, Atlas RGBA8 nearest pressure, Atlas RGBA16F linear pressure,"
     ),
     // Graphs to investigate driver overhead of texture cache updates.
     (&"Texture upload perf", &"#Texture cache update,#Texture cache upload, ,#Staging CPU allocation,#Staging GPU allocation,#Staging CPU copy,#Staging GPU copy,#Upload time, ,#Upload copy batches,#Rasterized glyphs, ,#Cache texture creation,#Cache texture deletion"),
@@ -90,7 +90,7 @@ static PROFILER_PRESETS: &'static[(&'static str, &'static str)] = &[
     // Misc:
 
     (&"Memory", &"Image templates,Image templates mem,Font templates,Font templates mem,DisplayList mem,Picture tiles mem"),
-    (&"Interners", "Interned primitives,Interned clips,Interned pictures,Interned text runs,Interned normal borders,Interned image borders,Interned images,Interned YUV images,Interned line decorations,Interned linear gradients,Interned radial gradients,Interned conic gradients,Interned filter data,Interned backdrops"),
+    (&"Interners", "Interned primitives,Interned clips,Interned pictures,Interned text runs,Interned normal borders,Interned image borders,Interned images,Interned YUV images,Interned line decorations,Interned linear gradients,Interned radial gradients,Interned conic gradients,Interned filter data,Interned backdrops,Interned paths,Interned clip paths,Interned clip texts"),
     // Gpu sampler queries (need the pref gfx.webrender.debug.gpu-sampler-queries).
     (&"GPU samplers", &"Alpha targets samplers,Transparent pass samplers,Opaque pass samplers,Total samplers"),
 ];
@@ -229,7 +229,16 @@ pub const INTERNED_POLYGONS: usize = 99;
 
 pub const DEPTH_TARGETS_MEM: usize = 100;
 
-pub const NUM_PROFILER_EVENTS: usize = 101;
+pub const INTERNED_PATHS: usize = 101;
+pub const INTERNED_CLIP_PATHS: usize = 102;
+
+pub const ATLAS_RGBA16F_LINEAR_PIXELS: usize = 103;
+pub const ATLAS_RGBA16F_LINEAR_TEXTURES: usize = 104;
+pub const ATLAS_COLOR16F_PRESSURE: usize = 105;
+
+pub const INTERNED_CLIP_TEXTS: usize = 106;
+
+pub const NUM_PROFILER_EVENTS: usize = 107;
 
 pub struct Profiler {
     counters: Vec<Counter>,
@@ -383,6 +392,15 @@ impl Profiler {
             int("Interned polygons", "", INTERNED_POLYGONS, Expected::none()),
 
             float("Depth targets mem", "MB", DEPTH_TARGETS_MEM, Expected::none()),
+
+            int("Interned paths", "", INTERNED_PATHS, Expected::none()),
+            int("Interned clip paths", "", INTERNED_CLIP_PATHS, Expected::none()),
+
+            int("Atlas RGBA16F linear pixels", "px", ATLAS_RGBA16F_LINEAR_PIXELS, expected(0..1_000_000)),
+            int("Atlas RGBA16F linear textures", "", ATLAS_RGBA16F_LINEAR_TEXTURES, expected(0..2)),
+            float("Atlas RGBA16F linear pressure", "", ATLAS_COLOR16F_PRESSURE, expected(0.0..1.0)),
+
+            int("Interned clip texts", "", INTERNED_CLIP_TEXTS, Expected::none()),
         ];
 
         let mut counters = Vec::with_capacity(profile_counters.len());
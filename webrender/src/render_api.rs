@@ -16,10 +16,13 @@ use crate::api::channel::{Sender, single_msg_channel, unbounded_channel};
 use crate::api::{ColorF, BuiltDisplayList, IdNamespace, ExternalScrollId};
 use crate::api::{SharedFontInstanceMap, FontKey, FontInstanceKey, NativeFontHandle};
 use crate::api::{BlobImageData, BlobImageKey, ImageData, ImageDescriptor, ImageKey, Epoch, QualitySettings};
+use crate::api::SnapshotImageKey;
 use crate::api::{BlobImageParams, BlobImageRequest, BlobImageResult, AsyncBlobImageRasterizer, BlobImageHandler};
 use crate::api::{DocumentId, PipelineId, PropertyBindingId, PropertyBindingKey, ExternalEvent};
+use crate::api::{Animation, RgbColorSpace};
 use crate::api::{HitTestResult, HitTesterRequest, ApiHitTester, PropertyValue, DynamicProperties};
 use crate::api::{ScrollClamping, TileSize, NotificationRequest, DebugFlags, ScrollNodeState};
+use crate::api::ScrollBehavior;
 use crate::api::{GlyphDimensionRequest, GlyphIndexRequest, GlyphIndex, GlyphDimensions};
 use crate::api::{FontInstanceOptions, FontInstancePlatformOptions, FontVariation};
 use crate::api::DEFAULT_TILE_SIZE;
@@ -58,6 +61,18 @@ pub enum ResourceUpdate {
     DeleteBlobImage(BlobImageKey),
     /// See `AddBlobImage::visible_area`.
     SetBlobImageVisibleArea(BlobImageKey, DeviceIntRect),
+    /// Creates a snapshot image resource, which stacking contexts can be
+    /// rendered into.
+    ///
+    /// Must be matched with a `DeleteSnapshotImage` at some point to release
+    /// the texture memory it uses.
+    AddSnapshotImage(SnapshotImageKey),
+    /// Delete an existing snapshot image resource.
+    ///
+    /// It is invalid to continue referring to the snapshot in any display list
+    /// in the transaction that contains the `DeleteSnapshotImage` message and
+    /// subsequent transactions.
+    DeleteSnapshotImage(SnapshotImageKey),
     /// See `AddFont`.
     AddFont(AddFont),
     /// Deletes an already existing font resource.
@@ -98,6 +113,8 @@ impl fmt::Debug for ResourceUpdate {
             ResourceUpdate::DeleteImage(..) => f.write_str("ResourceUpdate::DeleteImage"),
             ResourceUpdate::DeleteBlobImage(..) => f.write_str("ResourceUpdate::DeleteBlobImage"),
             ResourceUpdate::SetBlobImageVisibleArea(..) => f.write_str("ResourceUpdate::SetBlobImageVisibleArea"),
+            ResourceUpdate::AddSnapshotImage(..) => f.write_str("ResourceUpdate::AddSnapshotImage"),
+            ResourceUpdate::DeleteSnapshotImage(..) => f.write_str("ResourceUpdate::DeleteSnapshotImage"),
             ResourceUpdate::AddFont(..) => f.write_str("ResourceUpdate::AddFont"),
             ResourceUpdate::DeleteFont(..) => f.write_str("ResourceUpdate::DeleteFont"),
             ResourceUpdate::AddFontInstance(..) => f.write_str("ResourceUpdate::AddFontInstance"),
@@ -346,6 +363,48 @@ impl Transaction {
         self.frame_ops.push(FrameMsg::ScrollNodeWithId(origin, id, clamp));
     }
 
+    /// Scrolls the node identified by the given external scroll id to the given
+    /// scroll position, as in `scroll_node_with_id`, clamped to the scroll range.
+    /// Smooth scrolls are animated on the render backend every time a frame is
+    /// generated, until they complete or are interrupted by another scroll of the
+    /// same node.
+    pub fn scroll_to(
+        &mut self,
+        id: ExternalScrollId,
+        origin: LayoutPoint,
+        behavior: ScrollBehavior,
+    ) {
+        self.frame_ops.push(FrameMsg::ScrollNodeTo(origin, id, behavior));
+    }
+
+    /// Scrolls the node identified by the given external scroll id by the least
+    /// amount needed to make `rect` visible. The rect is in the coordinate space
+    /// of the content of the scroll frame.
+    pub fn scroll_into_view(
+        &mut self,
+        id: ExternalScrollId,
+        rect: LayoutRect,
+        behavior: ScrollBehavior,
+    ) {
+        self.frame_ops.push(FrameMsg::ScrollIntoView(rect, id, behavior));
+    }
+
+    /// Starts momentum scrolling the node identified by the given external scroll
+    /// id, typically at the end of a touch gesture. The velocity is in layout pixels
+    /// per second, positive values scrolling towards the end of the scroll range.
+    /// Nodes with snap areas come to rest at a snap position.
+    pub fn fling(&mut self, id: ExternalScrollId, velocity: LayoutVector2D) {
+        self.frame_ops.push(FrameMsg::Fling(velocity, id));
+    }
+
+    /// Notifies that the user stopped scrolling the node identified by the given
+    /// external scroll id. If the node was overscrolled, it goes back to the edge
+    /// of its scroll range, as described by its `OverscrollBehavior`. Otherwise it
+    /// moves to its closest snap position, if it has snap areas.
+    pub fn end_scroll_gesture(&mut self, id: ExternalScrollId) {
+        self.frame_ops.push(FrameMsg::EndScrollGesture(id));
+    }
+
     /// Set the current quality / performance settings for this document.
     pub fn set_quality_settings(&mut self, settings: QualitySettings) {
         self.scene_ops.push(SceneMsg::SetQualitySettings { settings });
@@ -391,6 +450,40 @@ impl Transaction {
         self.frame_ops.push(FrameMsg::AppendDynamicTransformProperties(transforms));
     }
 
+    /// Start animating a property binding, replacing any animation of the same
+    /// binding. The animation is sampled by the render backend every time a frame
+    /// is generated, so frames need to keep being generated while it runs, which
+    /// is requested through [request_animation_frame()][request]. Its completion
+    /// is reported through [animation_finished()][finished].
+    ///
+    /// [request]: trait.RenderNotifier.html#method.request_animation_frame
+    /// [finished]: trait.RenderNotifier.html#method.animation_finished
+    pub fn add_animation(&mut self, animation: Animation) {
+        self.frame_ops.push(FrameMsg::AddAnimation(animation));
+    }
+
+    /// Pause the animation of a property binding, which keeps its current value.
+    pub fn pause_animation(&mut self, id: PropertyBindingId) {
+        self.frame_ops.push(FrameMsg::PauseAnimation(id));
+    }
+
+    /// Resume a paused animation from the point at which it was paused.
+    pub fn resume_animation(&mut self, id: PropertyBindingId) {
+        self.frame_ops.push(FrameMsg::ResumeAnimation(id));
+    }
+
+    /// Move the animation of a property binding to the given time, in milliseconds
+    /// since the animation was added, delay included.
+    pub fn seek_animation(&mut self, id: PropertyBindingId, time: f32) {
+        self.frame_ops.push(FrameMsg::SeekAnimation(id, time));
+    }
+
+    /// Remove the animation of a property binding. The binding goes back to the value
+    /// supplied through the dynamic properties, if any. No completion is reported.
+    pub fn cancel_animation(&mut self, id: PropertyBindingId) {
+        self.frame_ops.push(FrameMsg::CancelAnimation(id));
+    }
+
     /// Consumes this object and just returns the frame ops.
     pub fn get_frame_ops(self) -> Vec<FrameMsg> {
         self.frame_ops
@@ -501,6 +594,16 @@ impl Transaction {
         self.resource_updates.push(ResourceUpdate::SetBlobImageVisibleArea(key, area));
     }
 
+    /// See `ResourceUpdate::AddSnapshotImage`.
+    pub fn add_snapshot_image(&mut self, key: SnapshotImageKey) {
+        self.resource_updates.push(ResourceUpdate::AddSnapshotImage(key));
+    }
+
+    /// See `ResourceUpdate::DeleteSnapshotImage`.
+    pub fn delete_snapshot_image(&mut self, key: SnapshotImageKey) {
+        self.resource_updates.push(ResourceUpdate::DeleteSnapshotImage(key));
+    }
+
     /// See `ResourceUpdate::AddFont`.
     pub fn add_raw_font(&mut self, key: FontKey, bytes: Vec<u8>, index: u32) {
         self.resource_updates
@@ -796,6 +899,14 @@ pub enum FrameMsg {
     ///
     ScrollNodeWithId(LayoutPoint, ExternalScrollId, ScrollClamping),
     ///
+    ScrollNodeTo(LayoutPoint, ExternalScrollId, ScrollBehavior),
+    ///
+    ScrollIntoView(LayoutRect, ExternalScrollId, ScrollBehavior),
+    ///
+    Fling(LayoutVector2D, ExternalScrollId),
+    ///
+    EndScrollGesture(ExternalScrollId),
+    ///
     GetScrollNodeState(Sender<Vec<ScrollNodeState>>),
     ///
     UpdateDynamicProperties(DynamicProperties),
@@ -803,6 +914,16 @@ pub enum FrameMsg {
     AppendDynamicTransformProperties(Vec<PropertyValue<LayoutTransform>>),
     ///
     SetIsTransformAsyncZooming(bool, PropertyBindingId),
+    ///
+    AddAnimation(Animation),
+    ///
+    PauseAnimation(PropertyBindingId),
+    ///
+    ResumeAnimation(PropertyBindingId),
+    ///
+    SeekAnimation(PropertyBindingId, f32),
+    ///
+    CancelAnimation(PropertyBindingId),
 }
 
 impl fmt::Debug for SceneMsg {
@@ -825,10 +946,19 @@ impl fmt::Debug for FrameMsg {
             FrameMsg::HitTest(..) => "FrameMsg::HitTest",
             FrameMsg::RequestHitTester(..) => "FrameMsg::RequestHitTester",
             FrameMsg::ScrollNodeWithId(..) => "FrameMsg::ScrollNodeWithId",
+            FrameMsg::ScrollNodeTo(..) => "FrameMsg::ScrollNodeTo",
+            FrameMsg::ScrollIntoView(..) => "FrameMsg::ScrollIntoView",
+            FrameMsg::Fling(..) => "FrameMsg::Fling",
+            FrameMsg::EndScrollGesture(..) => "FrameMsg::EndScrollGesture",
             FrameMsg::GetScrollNodeState(..) => "FrameMsg::GetScrollNodeState",
             FrameMsg::UpdateDynamicProperties(..) => "FrameMsg::UpdateDynamicProperties",
             FrameMsg::AppendDynamicTransformProperties(..) => "FrameMsg::AppendDynamicTransformProperties",
             FrameMsg::SetIsTransformAsyncZooming(..) => "FrameMsg::SetIsTransformAsyncZooming",
+            FrameMsg::AddAnimation(..) => "FrameMsg::AddAnimation",
+            FrameMsg::PauseAnimation(..) => "FrameMsg::PauseAnimation",
+            FrameMsg::ResumeAnimation(..) => "FrameMsg::ResumeAnimation",
+            FrameMsg::SeekAnimation(..) => "FrameMsg::SeekAnimation",
+            FrameMsg::CancelAnimation(..) => "FrameMsg::CancelAnimation",
         })
     }
 }
@@ -876,6 +1006,18 @@ pub struct CapturedDocument {
     pub root_pipeline_id: Option<PipelineId>,
 }
 
+/// The display lists of a document in a capture, as read back by
+/// `read_capture_scenes` without loading the capture into a renderer.
+#[cfg(feature = "replay")]
+pub struct CapturedScene {
+    ///
+    pub document_id: DocumentId,
+    ///
+    pub root_pipeline_id: Option<PipelineId>,
+    /// The display list of each pipeline of the scene.
+    pub display_lists: Vec<(PipelineId, BuiltDisplayList)>,
+}
+
 /// Update of the state of built-in debugging facilities.
 #[derive(Clone)]
 pub enum DebugCommand {
@@ -906,6 +1048,9 @@ pub enum DebugCommand {
     SimulateLongSceneBuild(u32),
     /// Set an override tile size to use for picture caches
     SetPictureTileSize(Option<DeviceIntSize>),
+    /// Change the output color space. Only scenes built after the change
+    /// are converted to the new color space.
+    SetOutputColorSpace(RgbColorSpace),
 }
 
 /// Message sent by the `RenderApi` to the render backend thread.
@@ -1137,6 +1282,11 @@ impl RenderApi {
         BlobImageKey(self.generate_image_key())
     }
 
+    /// Creates a `SnapshotImageKey`.
+    pub fn generate_snapshot_image_key(&self) -> SnapshotImageKey {
+        SnapshotImageKey(self.generate_image_key())
+    }
+
     /// A Gecko-specific notification mechanism to get some code executed on the
     /// `Renderer`'s thread, mostly replaced by `NotificationHandler`. You should
     /// probably use the latter instead.
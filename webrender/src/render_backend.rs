@@ -15,12 +15,13 @@ use api::{NotificationRequest, Checkpoint, QualitySettings};
 use api::{PrimitiveKeyKind};
 use api::units::*;
 use api::channel::{single_msg_channel, Sender, Receiver};
+use crate::animation::AnimationStore;
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::render_api::CaptureBits;
 #[cfg(feature = "replay")]
-use crate::render_api::CapturedDocument;
+use crate::render_api::{CapturedDocument, CapturedScene};
 use crate::render_api::{MemoryReport, TransactionMsg, ResourceUpdate, ApiMsg, FrameMsg, ClearCache, DebugCommand};
-use crate::clip::{ClipIntern, PolygonIntern, ClipStoreScratchBuffer};
+use crate::clip::{ClipIntern, ClipPathIntern, ClipTextIntern, PolygonIntern, ClipStoreScratchBuffer};
 use crate::filterdata::FilterDataIntern;
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::capture::CaptureConfig;
@@ -58,7 +59,7 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{UNIX_EPOCH, SystemTime};
 use std::{mem, u32};
-#[cfg(feature = "capture")]
+#[cfg(any(feature = "capture", feature = "replay"))]
 use std::path::PathBuf;
 #[cfg(feature = "replay")]
 use crate::frame_builder::Frame;
@@ -335,6 +336,10 @@ impl DataStores {
                 let prim_data = &self.conic_grad[data_handle];
                 &prim_data.common
             }
+            PrimitiveInstanceKind::Path { data_handle, .. } => {
+                let prim_data = &self.path[data_handle];
+                &prim_data.common
+            }
             PrimitiveInstanceKind::TextRun { data_handle, .. }  => {
                 let prim_data = &self.text_run[data_handle];
                 &prim_data.common
@@ -419,6 +424,10 @@ struct Document {
     /// without requiring the scene to be re-built.
     dynamic_properties: SceneProperties,
 
+    /// Keyframe animations driving some of the dynamic properties, sampled every
+    /// time a frame is generated.
+    animations: AnimationStore,
+
     /// Track whether the last built frame is up to date or if it will need to be re-built
     /// before rendering again.
     frame_is_valid: bool,
@@ -469,6 +478,7 @@ impl Document {
             hit_tester: None,
             shared_hit_tester: Arc::new(SharedHitTester::new()),
             dynamic_properties: SceneProperties::new(),
+            animations: AnimationStore::new(),
             frame_is_valid: false,
             hit_tester_is_valid: false,
             rendered_frame_is_valid: false,
@@ -531,6 +541,55 @@ impl Document {
                     ..DocumentOps::nop()
                 };
             }
+            FrameMsg::ScrollNodeTo(origin, id, behavior) => {
+                profile_scope!("ScrollNodeTo");
+
+                let now = precise_time_ns();
+                if self.scene.spatial_tree.scroll_node_to(origin, id, behavior, now) {
+                    self.hit_tester_is_valid = false;
+                    self.frame_is_valid = false;
+                }
+
+                return DocumentOps {
+                    scroll: true,
+                    ..DocumentOps::nop()
+                };
+            }
+            FrameMsg::ScrollIntoView(rect, id, behavior) => {
+                profile_scope!("ScrollIntoView");
+
+                let now = precise_time_ns();
+                if self.scene.spatial_tree.scroll_node_into_view(rect, id, behavior, now) {
+                    self.hit_tester_is_valid = false;
+                    self.frame_is_valid = false;
+                }
+
+                return DocumentOps {
+                    scroll: true,
+                    ..DocumentOps::nop()
+                };
+            }
+            FrameMsg::Fling(velocity, id) => {
+                profile_scope!("Fling");
+
+                if self.scene.spatial_tree.fling_node(velocity, id, precise_time_ns()) {
+                    self.hit_tester_is_valid = false;
+                    self.frame_is_valid = false;
+                }
+
+                return DocumentOps {
+                    scroll: true,
+                    ..DocumentOps::nop()
+                };
+            }
+            FrameMsg::EndScrollGesture(id) => {
+                profile_scope!("EndScrollGesture");
+
+                if self.scene.spatial_tree.end_scroll_gesture(id, precise_time_ns()) {
+                    self.hit_tester_is_valid = false;
+                    self.frame_is_valid = false;
+                }
+            }
             FrameMsg::GetScrollNodeState(tx) => {
                 profile_scope!("GetScrollNodeState");
                 tx.send(self.scene.spatial_tree.get_scroll_node_state()).unwrap();
@@ -551,6 +610,21 @@ impl Document {
                     }
                 }
             }
+            FrameMsg::AddAnimation(animation) => {
+                self.animations.add(animation, precise_time_ns());
+            }
+            FrameMsg::PauseAnimation(id) => {
+                self.animations.pause(id, precise_time_ns());
+            }
+            FrameMsg::ResumeAnimation(id) => {
+                self.animations.resume(id, precise_time_ns());
+            }
+            FrameMsg::SeekAnimation(id, time) => {
+                self.animations.seek(id, time, precise_time_ns());
+            }
+            FrameMsg::CancelAnimation(id) => {
+                self.animations.cancel(id);
+            }
         }
 
         DocumentOps::nop()
@@ -713,6 +787,11 @@ impl Document {
             resource_cache,
         );
 
+        self.dynamic_properties.set_color_spaces(
+            mem::replace(&mut built_scene.color_binding_spaces, FastHashMap::default()),
+            built_scene.config.output_color_space,
+        );
+
         let old_scrolling_states = self.scene.spatial_tree.drain();
         self.scene = built_scene;
         self.scratch.recycle(recycler);
@@ -745,6 +824,43 @@ struct PlainRenderBackend {
     resource_sequence_id: u32,
 }
 
+/// Reads the resources and the scene of each document of a capture taken with
+/// `CaptureBits::SCENE`, so that tools can convert captures to other formats.
+/// External images can't be read back and are left out of the resources.
+#[cfg(feature = "replay")]
+pub fn read_capture_scenes(root: PathBuf) -> (Vec<ResourceUpdate>, Vec<CapturedScene>) {
+    let mut config = CaptureConfig::new(root, CaptureBits::all());
+    let backend = config.deserialize_for_frame::<PlainRenderBackend, _>("backend")
+        .expect("Unable to open backend.ron");
+
+    config.resource_id = backend.resource_sequence_id;
+    let resources = config.deserialize_for_resource::<PlainResources, _>("plain-resources")
+        .expect("Unable to open plain-resources.ron")
+        .into_resource_updates(&config);
+
+    let mut scenes = Vec::new();
+    for id in backend.documents.keys() {
+        let scene_name = format!("scene-{}-{}", id.namespace_id.0, id.id);
+        let scene = config.deserialize_for_scene::<Scene, _>(&scene_name)
+            .expect(&format!("Unable to open {}.ron", scene_name));
+
+        let mut display_lists: Vec<_> = scene.pipelines
+            .into_iter()
+            .map(|(pipeline_id, pipeline)| (pipeline_id, pipeline.display_list.into_display_list()))
+            .collect();
+        display_lists.sort_by_key(|&(pipeline_id, _)| (pipeline_id.0, pipeline_id.1));
+
+        scenes.push(CapturedScene {
+            document_id: *id,
+            root_pipeline_id: scene.root_pipeline_id,
+            display_lists,
+        });
+    }
+    scenes.sort_by_key(|scene| (scene.document_id.namespace_id.0, scene.document_id.id));
+
+    (resources, scenes)
+}
+
 /// The render backend is responsible for transforming high level display lists into
 /// GPU-friendly work which is then submitted to the renderer in the form of a frame::Frame.
 ///
@@ -889,6 +1005,8 @@ impl RenderBackend {
 
         self.documents.clear();
 
+        self.resource_cache.save_glyph_disk_cache();
+
         self.notifier.shut_down();
 
         if let Some(ref sampler) = self.sampler {
@@ -1070,6 +1188,14 @@ impl RenderBackend {
 
                         return RenderBackendStatus::Continue;
                     }
+                    DebugCommand::SetOutputColorSpace(color_space) => {
+                        self.frame_config.output_color_space = color_space;
+                        self.update_frame_builder_config();
+
+                        // The renderer converts the clear color and the
+                        // compositor surfaces.
+                        ResultMsg::DebugCommand(option)
+                    }
                     #[cfg(feature = "capture")]
                     DebugCommand::SaveCapture(root, bits) => {
                         let output = self.save_capture(root, bits);
@@ -1431,6 +1557,26 @@ impl RenderBackend {
             &mut doc.profile,
         );
 
+        if requested_frame {
+            if doc.scene.spatial_tree.tick_scroll_animations(precise_time_ns()) {
+                doc.frame_is_valid = false;
+                doc.hit_tester_is_valid = false;
+                scroll = true;
+            }
+
+            let mut finished = Vec::new();
+            let properties = doc.animations.sample(precise_time_ns(), &mut finished);
+            doc.dynamic_properties.set_animated_properties(properties);
+
+            for id in finished {
+                self.notifier.animation_finished(document_id, id);
+            }
+        }
+
+        for (id, event) in doc.scene.spatial_tree.take_overscroll_events() {
+            self.notifier.overscroll(document_id, id, event);
+        }
+
         if doc.dynamic_properties.flush_pending_updates() {
             doc.frame_is_valid = false;
             doc.hit_tester_is_valid = false;
@@ -1572,6 +1718,10 @@ impl RenderBackend {
                 doc.rendered_frame_is_valid = true;
             }
             self.notifier.new_frame_ready(document_id, scroll, render_frame, frame_build_time);
+
+            if doc.animations.is_active() {
+                self.notifier.request_animation_frame(document_id);
+            }
         }
 
         if !doc.hit_tester_is_valid {
@@ -1896,6 +2046,7 @@ impl RenderBackend {
                         stamp: FrameStamp::first(id),
                         frame_builder: FrameBuilder::new(),
                         dynamic_properties: properties,
+                        animations: AnimationStore::new(),
                         hit_tester: None,
                         shared_hit_tester: Arc::new(SharedHitTester::new()),
                         frame_is_valid: false,
@@ -4,7 +4,7 @@
 
 
 use api::units::*;
-use api::{ColorF, ImageFormat, LineOrientation, BorderStyle};
+use api::{ColorF, ImageFormat, LineOrientation, BorderStyle, MorphologyOperator, RgbColorSpace};
 use crate::batch::{AlphaBatchBuilder, AlphaBatchContainer, BatchTextures};
 use crate::batch::{ClipBatcher, BatchBuilder};
 use crate::spatial_tree::{SpatialTree, ROOT_SPATIAL_NODE_INDEX};
@@ -13,7 +13,8 @@ use crate::composite::CompositeState;
 use crate::frame_builder::{FrameGlobalResources};
 use crate::gpu_cache::{GpuCache, GpuCacheAddress};
 use crate::gpu_types::{BorderInstance, SvgFilterInstance, BlurDirection, BlurInstance, PrimitiveHeaders, ScalingInstance};
-use crate::gpu_types::{TransformPalette, ZBufferIdGenerator};
+use crate::gpu_types::{TextMaskInstance, TransformPalette, ZBufferIdGenerator};
+use crate::glyph_rasterizer::GlyphFormat;
 use crate::internal_types::{FastHashMap, TextureSource, CacheTextureId};
 use crate::picture::{SliceId, SurfaceInfo, ResolvedSurfaceTexture, TileCacheInstance};
 use crate::prim_store::{PrimitiveStore, DeferredResolve, PrimitiveScratchBuffer};
@@ -21,9 +22,10 @@ use crate::prim_store::gradient::{
     FastLinearGradientInstance, LinearGradientInstance, RadialGradientInstance,
     ConicGradientInstance,
 };
+use crate::prim_store::path::PathInstance;
 use crate::render_backend::DataStores;
 use crate::render_task::{RenderTaskKind, RenderTaskAddress};
-use crate::render_task::{RenderTask, ScalingTask, SvgFilterInfo};
+use crate::render_task::{RenderTask, ScalingTask, SvgFilterInfo, SvgLightSource, TextMaskTask};
 use crate::render_task_graph::{RenderTaskGraph, RenderTaskId};
 use crate::resource_cache::ResourceCache;
 
@@ -53,6 +55,8 @@ pub struct RenderTargetContext<'a, 'rc> {
     pub use_advanced_blending: bool,
     pub break_advanced_blend_batches: bool,
     pub batch_lookback_count: usize,
+    /// The color space images are converted to when they are drawn.
+    pub output_color_space: RgbColorSpace,
     pub spatial_tree: &'a SpatialTree,
     pub data_stores: &'a DataStores,
     pub surfaces: &'a [SurfaceInfo],
@@ -403,12 +407,14 @@ impl RenderTarget for ColorRenderTarget {
             RenderTaskKind::Image(..) |
             RenderTaskKind::Cached(..) |
             RenderTaskKind::ClipRegion(..) |
+            RenderTaskKind::TextMask(..) |
             RenderTaskKind::Border(..) |
             RenderTaskKind::CacheMask(..) |
             RenderTaskKind::FastLinearGradient(..) |
             RenderTaskKind::LinearGradient(..) |
             RenderTaskKind::RadialGradient(..) |
             RenderTaskKind::ConicGradient(..) |
+            RenderTaskKind::Path(..) |
             RenderTaskKind::LineDecoration(..) => {
                 panic!("Should not be added to color target!");
             }
@@ -453,6 +459,8 @@ pub struct AlphaRenderTarget {
     pub vertical_blurs: FastHashMap<TextureSource, Vec<BlurInstance>>,
     pub horizontal_blurs: FastHashMap<TextureSource, Vec<BlurInstance>>,
     pub scalings: FastHashMap<TextureSource, Vec<ScalingInstance>>,
+    // List of glyphs to draw into text masks, by glyph cache texture.
+    pub text_masks: FastHashMap<TextureSource, Vec<TextMaskInstance>>,
     pub zero_clears: Vec<RenderTaskId>,
     pub one_clears: Vec<RenderTaskId>,
     pub texture_id: CacheTextureId,
@@ -470,6 +478,7 @@ impl RenderTarget for AlphaRenderTarget {
             vertical_blurs: FastHashMap::default(),
             horizontal_blurs: FastHashMap::default(),
             scalings: FastHashMap::default(),
+            text_masks: FastHashMap::default(),
             zero_clears: Vec::new(),
             one_clears: Vec::new(),
             texture_id,
@@ -505,6 +514,7 @@ impl RenderTarget for AlphaRenderTarget {
             RenderTaskKind::LinearGradient(..) |
             RenderTaskKind::RadialGradient(..) |
             RenderTaskKind::ConicGradient(..) |
+            RenderTaskKind::Path(..) |
             RenderTaskKind::SvgFilter(..) => {
                 panic!("BUG: should not be added to alpha target!");
             }
@@ -539,6 +549,7 @@ impl RenderTarget for AlphaRenderTarget {
                     ctx.spatial_tree,
                     transforms,
                     &ctx.data_stores.clip,
+                    &ctx.data_stores.clip_path,
                     task_info.actual_rect,
                     &ctx.screen_world_rect,
                     task_info.device_pixel_scale,
@@ -566,6 +577,16 @@ impl RenderTarget for AlphaRenderTarget {
                     region_task.device_pixel_scale.0,
                 );
             }
+            RenderTaskKind::TextMask(ref task_info) => {
+                self.zero_clears.push(task_id);
+                add_text_mask_instances(
+                    task_info,
+                    &mut self.text_masks,
+                    target_rect,
+                    ctx,
+                    gpu_cache,
+                );
+            }
             RenderTaskKind::Scaling(ref info) => {
                 add_scaling_instances(
                     info,
@@ -608,6 +629,7 @@ pub struct TextureCacheRenderTarget {
     pub linear_gradients: Vec<LinearGradientInstance>,
     pub radial_gradients: Vec<RadialGradientInstance>,
     pub conic_gradients: Vec<ConicGradientInstance>,
+    pub paths: Vec<PathInstance>,
 }
 
 impl TextureCacheRenderTarget {
@@ -624,6 +646,7 @@ impl TextureCacheRenderTarget {
             linear_gradients: vec![],
             radial_gradients: vec![],
             conic_gradients: vec![],
+            paths: vec![],
         }
     }
 
@@ -703,11 +726,15 @@ impl TextureCacheRenderTarget {
             RenderTaskKind::ConicGradient(ref task_info) => {
                 self.conic_gradients.push(task_info.to_instance(&target_rect, gpu_cache));
             }
+            RenderTaskKind::Path(ref task_info) => {
+                self.paths.push(task_info.to_instance(&target_rect, gpu_cache));
+            }
             RenderTaskKind::Image(..) |
             RenderTaskKind::Cached(..) |
             RenderTaskKind::VerticalBlur(..) |
             RenderTaskKind::Picture(..) |
             RenderTaskKind::ClipRegion(..) |
+            RenderTaskKind::TextMask(..) |
             RenderTaskKind::CacheMask(..) |
             RenderTaskKind::Readback(..) |
             RenderTaskKind::Scaling(..) |
@@ -741,6 +768,47 @@ fn add_blur_instances(
         .push(instance);
 }
 
+fn add_text_mask_instances(
+    task: &TextMaskTask,
+    instances: &mut FastHashMap<TextureSource, Vec<TextMaskInstance>>,
+    target_rect: DeviceIntRect,
+    ctx: &RenderTargetContext,
+    gpu_cache: &mut GpuCache,
+) {
+    let text = &ctx.data_stores.clip_text[task.text_handle];
+    let scale = LayoutToDeviceScale::new(task.scale);
+    let task_origin = target_rect.min.to_f32().to_vector();
+
+    let mut fetch_buffer = Vec::new();
+    ctx.resource_cache.fetch_glyphs(
+        task.font.clone(),
+        &task.glyph_keys,
+        &mut fetch_buffer,
+        gpu_cache,
+        |texture, glyph_format, glyphs| {
+            let alpha_channel = (glyph_format == GlyphFormat::ColorBitmap) as i32;
+            let instances = instances.entry(texture).or_insert(Vec::new());
+
+            for glyph in glyphs {
+                // Glyphs are rasterized without a subpixel offset, so snap
+                // their origins to whole pixels of the mask.
+                let point = LayoutPoint::from(text.glyphs[glyph.index_in_text_run as usize].point);
+                let origin = (point * scale + DeviceVector::new(0.5, 0.5)).floor() + task_origin;
+                let rect = DeviceRect::from_origin_and_size(
+                    origin + glyph.offset.to_vector() * glyph.scale,
+                    glyph.size.to_f32() * glyph.scale,
+                );
+
+                instances.push(TextMaskInstance {
+                    target_rect: rect,
+                    resource_address: glyph.uv_rect_address,
+                    alpha_channel,
+                });
+            }
+        },
+    );
+}
+
 fn add_scaling_instances(
     task: &ScalingTask,
     instances: &mut FastHashMap<TextureSource, Vec<ScalingInstance>>,
@@ -796,10 +864,19 @@ fn add_svg_filter_instances(
         SvgFilterInfo::ComponentTransfer(..) => 8,
         SvgFilterInfo::Identity => 9,
         SvgFilterInfo::Composite(..) => 10,
+        SvgFilterInfo::Turbulence(..) => 11,
+        SvgFilterInfo::Morphology(..) => 12,
+        SvgFilterInfo::ConvolveMatrix(..) => 13,
+        SvgFilterInfo::DisplacementMap(..) => 14,
+        SvgFilterInfo::DiffuseLighting(..) => 15,
+        SvgFilterInfo::SpecularLighting(..) => 16,
+        SvgFilterInfo::Tile(..) => 17,
+        SvgFilterInfo::Image(..) => 18,
     };
 
     let input_count = match filter {
-        SvgFilterInfo::Flood(..) => 0,
+        SvgFilterInfo::Flood(..) |
+        SvgFilterInfo::Turbulence(..) => 0,
 
         SvgFilterInfo::LinearToSrgb |
         SvgFilterInfo::SrgbToLinear |
@@ -807,12 +884,19 @@ fn add_svg_filter_instances(
         SvgFilterInfo::ColorMatrix(..) |
         SvgFilterInfo::Offset(..) |
         SvgFilterInfo::ComponentTransfer(..) |
-        SvgFilterInfo::Identity => 1,
+        SvgFilterInfo::Identity |
+        SvgFilterInfo::Morphology(..) |
+        SvgFilterInfo::ConvolveMatrix(..) |
+        SvgFilterInfo::DiffuseLighting(..) |
+        SvgFilterInfo::SpecularLighting(..) |
+        SvgFilterInfo::Tile(..) |
+        SvgFilterInfo::Image(..) => 1,
 
         // Not techincally a 2 input filter, but we have 2 inputs here: original content & blurred content.
         SvgFilterInfo::DropShadow(..) |
         SvgFilterInfo::Blend(..) |
-        SvgFilterInfo::Composite(..) => 2,
+        SvgFilterInfo::Composite(..) |
+        SvgFilterInfo::DisplacementMap(..) => 2,
     };
 
     let generic_int = match filter {
@@ -824,6 +908,18 @@ fn add_svg_filter_instances(
               data.a_func.to_int()) as u16),
         SvgFilterInfo::Composite(operator) =>
             operator.as_int() as u16,
+        SvgFilterInfo::Morphology(operator, _) => match operator {
+            MorphologyOperator::Erode => 0,
+            MorphologyOperator::Dilate => 1,
+        },
+        SvgFilterInfo::DisplacementMap(_, x_channel, y_channel) =>
+            (*x_channel as u16) | (*y_channel as u16) << 4,
+        SvgFilterInfo::DiffuseLighting(info) |
+        SvgFilterInfo::SpecularLighting(info) => match info.light {
+            SvgLightSource::Distant(..) => 0,
+            SvgLightSource::Point(..) => 1,
+            SvgLightSource::Spot { .. } => 2,
+        },
         SvgFilterInfo::LinearToSrgb |
         SvgFilterInfo::SrgbToLinear |
         SvgFilterInfo::Flood(..) |
@@ -831,7 +927,11 @@ fn add_svg_filter_instances(
         SvgFilterInfo::ColorMatrix(..) |
         SvgFilterInfo::DropShadow(..) |
         SvgFilterInfo::Offset(..) |
-        SvgFilterInfo::Identity => 0,
+        SvgFilterInfo::Identity |
+        SvgFilterInfo::Turbulence(..) |
+        SvgFilterInfo::ConvolveMatrix(..) |
+        SvgFilterInfo::Tile(..) |
+        SvgFilterInfo::Image(..) => 0,
     };
 
     let instance = SvgFilterInstance {
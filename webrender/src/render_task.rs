@@ -4,13 +4,16 @@
 
 use api::{CompositeOperator, FilterPrimitive, FilterPrimitiveInput, FilterPrimitiveKind};
 use api::{LineStyle, LineOrientation, ClipMode, MixBlendMode, ColorF, ColorSpace};
+use api::{ColorChannel, ConvolveMatrixPrimitive, LightSource, MorphologyOperator, TurbulenceType};
+use api::{EdgeMode, ExternalImageType, ImageBufferKind};
 use api::units::*;
 use crate::batch::BatchFilter;
-use crate::clip::{ClipDataStore, ClipItemKind, ClipStore, ClipNodeRange};
+use crate::clip::{ClipDataStore, ClipItemKind, ClipStore, ClipNodeRange, ClipTextDataHandle};
 use crate::spatial_tree::SpatialNodeIndex;
 use crate::filterdata::SFilterData;
 use crate::frame_builder::FrameBuilderConfig;
 use crate::gpu_cache::{GpuCache, GpuCacheAddress, GpuCacheHandle};
+use crate::glyph_rasterizer::{FontInstance, GlyphKey};
 use crate::gpu_types::{BorderInstance, ImageSource, UvRectKind};
 use crate::internal_types::{CacheTextureId, FastHashMap, TextureSource, Swizzle};
 use crate::picture::{ResolvedSurfaceTexture, SurfaceInfo};
@@ -19,6 +22,7 @@ use crate::prim_store::gradient::{
     FastLinearGradientTask, RadialGradientTask,
     ConicGradientTask, LinearGradientTask,
 };
+use crate::prim_store::path::PathTask;
 use crate::resource_cache::{ResourceCache, ImageRequest};
 use std::{usize, f32, i32, u32};
 use crate::render_target::RenderTargetKind;
@@ -30,6 +34,13 @@ const FLOATS_PER_RENDER_TASK_INFO: usize = 8;
 pub const MAX_RENDER_TASK_SIZE: i32 = 16384;
 pub const MAX_BLUR_STD_DEVIATION: f32 = 4.0;
 pub const MIN_DOWNSCALING_RT_SIZE: i32 = 8;
+/// Octaves past this contribute less than the precision of an 8 bit channel.
+pub const MAX_TURBULENCE_OCTAVES: u32 = 10;
+/// The largest morphology radius sampled by the shader, in device pixels.
+pub const MAX_MORPHOLOGY_RADIUS: i32 = 100;
+/// The offset applied to noise coordinates by the turbulence algorithm, to
+/// keep them positive.
+const TURBULENCE_PERLIN_N: i32 = 4096;
 
 fn render_task_sanity_check(size: &DeviceIntSize) {
     if size.width > MAX_RENDER_TASK_SIZE ||
@@ -163,6 +174,18 @@ pub struct ClipRegionTask {
     pub clear_to_one: bool,
 }
 
+#[derive(Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct TextMaskTask {
+    /// The glyphs of the text clip.
+    pub text_handle: ClipTextDataHandle,
+    /// The number of device pixels per local pixel of the mask.
+    pub scale: f32,
+    pub font: FontInstance,
+    pub glyph_keys: Vec<GlyphKey>,
+}
+
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
 pub struct PictureTask {
@@ -254,6 +277,125 @@ pub enum SvgFilterInfo {
     Composite(CompositeOperator),
     // TODO: This is used as a hack to ensure that a blur task's input is always in the blur's previous pass.
     Identity,
+    Turbulence(SvgTurbulenceInfo),
+    /// A single pass of a morphology filter, with the radius (in device
+    /// pixels) along the axis that is processed by this pass.
+    Morphology(MorphologyOperator, DeviceIntVector2D),
+    ConvolveMatrix(Box<ConvolveMatrixPrimitive>),
+    /// The scale in device pixels, and the channels of the displacement map
+    /// that move pixels along the x and y axes.
+    DisplacementMap(f32, ColorChannel, ColorChannel),
+    DiffuseLighting(Box<SvgLightingInfo>),
+    SpecularLighting(Box<SvgLightingInfo>),
+    /// The repeated area of the input, in device pixels relative to the task.
+    Tile(DeviceRect),
+    /// The area the image is drawn into, in device pixels relative to the task.
+    Image(DeviceRect),
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct SvgTurbulenceInfo {
+    pub kind: TurbulenceType,
+    pub seed: i32,
+    pub num_octaves: u32,
+    /// The base frequency of the noise, per device pixel.
+    pub base_frequency: DeviceVector2D,
+    /// The position of the task origin, in device pixels.
+    pub origin: DevicePoint,
+    /// The width, height and wrapping points of the noise lattice, if the
+    /// noise is stitched at the edges of the task.
+    pub stitch: Option<[i32; 4]>,
+    /// The lattice and gradient tables, which only depend on the seed and are
+    /// kept in the GPU cache across frames.
+    pub tables_handle: GpuCacheHandle,
+}
+
+/// A light source, with positions in device pixels relative to the task origin.
+#[derive(Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub enum SvgLightSource {
+    /// The normalized direction towards the light.
+    Distant([f32; 3]),
+    Point([f32; 3]),
+    Spot {
+        position: [f32; 3],
+        /// The normalized direction the light points at.
+        direction: [f32; 3],
+        specular_exponent: f32,
+        /// The cosine of the limiting cone angle, or -1 if the light isn't
+        /// restricted to a cone.
+        cos_cone_angle: f32,
+    },
+}
+
+impl SvgLightSource {
+    fn new(
+        light: &LightSource,
+        content_origin: DevicePoint,
+        device_pixel_scale: DevicePixelScale,
+    ) -> Self {
+        let to_device = |x: f32, y: f32, z: f32| {
+            [
+                x * device_pixel_scale.0 - content_origin.x,
+                y * device_pixel_scale.0 - content_origin.y,
+                z * device_pixel_scale.0,
+            ]
+        };
+
+        match *light {
+            LightSource::Distant(ref light) => {
+                let azimuth = light.azimuth.to_radians();
+                let elevation = light.elevation.to_radians();
+                SvgLightSource::Distant([
+                    azimuth.cos() * elevation.cos(),
+                    azimuth.sin() * elevation.cos(),
+                    elevation.sin(),
+                ])
+            }
+            LightSource::Point(ref light) => {
+                SvgLightSource::Point(to_device(light.x, light.y, light.z))
+            }
+            LightSource::Spot(ref light) => {
+                let position = to_device(light.x, light.y, light.z);
+                let points_at = to_device(light.points_at_x, light.points_at_y, light.points_at_z);
+                let mut direction = [
+                    points_at[0] - position[0],
+                    points_at[1] - position[1],
+                    points_at[2] - position[2],
+                ];
+                let length = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+                if length != 0.0 {
+                    for d in &mut direction {
+                        *d /= length;
+                    }
+                }
+
+                SvgLightSource::Spot {
+                    position,
+                    direction,
+                    specular_exponent: light.specular_exponent,
+                    cos_cone_angle: light.limiting_cone_angle
+                        .map_or(-1.0, |angle| angle.abs().min(180.0).to_radians().cos()),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct SvgLightingInfo {
+    /// The surface scale, in device pixels.
+    pub surface_scale: f32,
+    /// The diffuse or specular constant.
+    pub lighting_constant: f32,
+    pub specular_exponent: f32,
+    pub color: ColorF,
+    pub light: SvgLightSource,
 }
 
 #[derive(Debug)]
@@ -289,6 +431,7 @@ pub enum RenderTaskKind {
     Picture(PictureTask),
     CacheMask(CacheMaskTask),
     ClipRegion(ClipRegionTask),
+    TextMask(TextMaskTask),
     VerticalBlur(BlurTask),
     HorizontalBlur(BlurTask),
     Readback(ReadbackTask),
@@ -300,6 +443,7 @@ pub enum RenderTaskKind {
     LinearGradient(LinearGradientTask),
     RadialGradient(RadialGradientTask),
     ConicGradient(ConicGradientTask),
+    Path(PathTask),
     SvgFilter(SvgFilterTask),
     #[cfg(test)]
     Test(RenderTargetKind),
@@ -321,6 +465,7 @@ impl RenderTaskKind {
             RenderTaskKind::Picture(..) => "Picture",
             RenderTaskKind::CacheMask(..) => "CacheMask",
             RenderTaskKind::ClipRegion(..) => "ClipRegion",
+            RenderTaskKind::TextMask(..) => "TextMask",
             RenderTaskKind::VerticalBlur(..) => "VerticalBlur",
             RenderTaskKind::HorizontalBlur(..) => "HorizontalBlur",
             RenderTaskKind::Readback(..) => "Readback",
@@ -332,6 +477,7 @@ impl RenderTaskKind {
             RenderTaskKind::LinearGradient(..) => "LinearGradient",
             RenderTaskKind::RadialGradient(..) => "RadialGradient",
             RenderTaskKind::ConicGradient(..) => "ConicGradient",
+            RenderTaskKind::Path(..) => "Path",
             RenderTaskKind::SvgFilter(..) => "SvgFilter",
             #[cfg(test)]
             RenderTaskKind::Test(..) => "Test",
@@ -348,6 +494,7 @@ impl RenderTaskKind {
             RenderTaskKind::LinearGradient(..) |
             RenderTaskKind::RadialGradient(..) |
             RenderTaskKind::ConicGradient(..) |
+            RenderTaskKind::Path(..) |
             RenderTaskKind::Picture(..) |
             RenderTaskKind::Blit(..) |
             RenderTaskKind::SvgFilter(..) => {
@@ -355,6 +502,7 @@ impl RenderTaskKind {
             }
 
             RenderTaskKind::ClipRegion(..) |
+            RenderTaskKind::TextMask(..) |
             RenderTaskKind::CacheMask(..) => {
                 RenderTargetKind::Alpha
             }
@@ -547,9 +695,31 @@ impl RenderTaskKind {
                         }
                     ));
                 }
+                ClipItemKind::Text { ref rect, handle, ref mut source } => {
+                    let source = source
+                        .as_mut()
+                        .expect("bug: text clip glyphs not requested");
+
+                    // Draw the glyphs into their own mask at the scale they were
+                    // rasterized at, which the clip mask then samples like an image.
+                    // The task keeps a copy of the font, since another instance of
+                    // the clip may be rasterized at a different scale this frame.
+                    let task_id = rg_builder.add().init(RenderTask::new_dynamic(
+                        source.task_size(rect),
+                        RenderTaskKind::TextMask(TextMaskTask {
+                            text_handle: handle,
+                            scale: source.scale,
+                            font: source.font.clone(),
+                            glyph_keys: source.glyph_keys.clone(),
+                        }),
+                    ));
+                    rg_builder.add_dependency(clip_task_id, task_id);
+                    source.render_task = Some(task_id);
+                }
                 ClipItemKind::Rectangle { .. } |
                 ClipItemKind::RoundedRectangle { .. } |
-                ClipItemKind::Image { .. } => {}
+                ClipItemKind::Image { .. } |
+                ClipItemKind::Path { .. } => {}
             }
         }
 
@@ -616,6 +786,8 @@ impl RenderTaskKind {
             RenderTaskKind::LinearGradient(..) |
             RenderTaskKind::RadialGradient(..) |
             RenderTaskKind::ConicGradient(..) |
+            RenderTaskKind::Path(..) |
+            RenderTaskKind::TextMask(..) |
             RenderTaskKind::Blit(..) => {
                 [0.0; 4]
             }
@@ -625,6 +797,8 @@ impl RenderTaskKind {
                 match task.info {
                     SvgFilterInfo::Opacity(opacity) => [opacity, 0.0, 0.0, 0.0],
                     SvgFilterInfo::Offset(offset) => [offset.x, offset.y, 0.0, 0.0],
+                    SvgFilterInfo::Morphology(_, radius) => [radius.x as f32, radius.y as f32, 0.0, 0.0],
+                    SvgFilterInfo::DisplacementMap(scale, ..) => [scale, 0.0, 0.0, 0.0],
                     _ => [0.0; 4]
                 }
             }
@@ -684,12 +858,167 @@ impl RenderTaskKind {
                         }
                     }
                 }
+                SvgFilterInfo::Turbulence(ref info) => {
+                    let tables_address = gpu_cache.get_address(&info.tables_handle);
+                    let handle = filter_task.extra_gpu_cache_handle.get_or_insert_with(GpuCacheHandle::new);
+                    if let Some(mut request) = gpu_cache.request(handle) {
+                        request.push([
+                            info.base_frequency.x,
+                            info.base_frequency.y,
+                            info.origin.x,
+                            info.origin.y,
+                        ]);
+                        let stitch = info.stitch.unwrap_or([0; 4]);
+                        request.push([
+                            stitch[0] as f32,
+                            stitch[1] as f32,
+                            stitch[2] as f32,
+                            stitch[3] as f32,
+                        ]);
+                        request.push([
+                            info.num_octaves as f32,
+                            match info.kind {
+                                TurbulenceType::FractalNoise => 0.0,
+                                TurbulenceType::Turbulence => 1.0,
+                            },
+                            if info.stitch.is_some() { 1.0 } else { 0.0 },
+                            0.0,
+                        ]);
+                        request.push([
+                            tables_address.u as f32,
+                            tables_address.v as f32,
+                            0.0,
+                            0.0,
+                        ]);
+                    }
+                }
+                SvgFilterInfo::ConvolveMatrix(ref info) => {
+                    let handle = filter_task.extra_gpu_cache_handle.get_or_insert_with(GpuCacheHandle::new);
+                    if let Some(mut request) = gpu_cache.request(handle) {
+                        let kernel_len = (info.order_x * info.order_y) as usize;
+                        let kernel = &info.kernel[.. kernel_len];
+                        let mut divisor = info.divisor;
+                        if divisor == 0.0 {
+                            divisor = kernel.iter().sum();
+                            if divisor == 0.0 {
+                                divisor = 1.0;
+                            }
+                        }
+
+                        request.push([
+                            info.order_x as f32,
+                            info.order_y as f32,
+                            info.target_x as f32,
+                            info.target_y as f32,
+                        ]);
+                        request.push([
+                            divisor,
+                            info.bias,
+                            match info.edge_mode {
+                                EdgeMode::Duplicate => 0.0,
+                                EdgeMode::Wrap => 1.0,
+                                EdgeMode::None => 2.0,
+                            },
+                            if info.preserve_alpha { 1.0 } else { 0.0 },
+                        ]);
+                        for values in info.kernel.chunks(4) {
+                            let mut block = [0.0; 4];
+                            block[.. values.len()].copy_from_slice(values);
+                            request.push(block);
+                        }
+                    }
+                }
+                SvgFilterInfo::DiffuseLighting(ref info) |
+                SvgFilterInfo::SpecularLighting(ref info) => {
+                    let handle = filter_task.extra_gpu_cache_handle.get_or_insert_with(GpuCacheHandle::new);
+                    if let Some(mut request) = gpu_cache.request(handle) {
+                        request.push([
+                            info.surface_scale,
+                            info.lighting_constant,
+                            info.specular_exponent,
+                            0.0,
+                        ]);
+                        request.push(info.color.to_array());
+                        match info.light {
+                            SvgLightSource::Distant(direction) |
+                            SvgLightSource::Point(direction) => {
+                                request.push([direction[0], direction[1], direction[2], 0.0]);
+                                request.push([0.0; 4]);
+                            }
+                            SvgLightSource::Spot { position, direction, specular_exponent, cos_cone_angle } => {
+                                request.push([position[0], position[1], position[2], specular_exponent]);
+                                request.push([direction[0], direction[1], direction[2], cos_cone_angle]);
+                            }
+                        }
+                    }
+                }
+                SvgFilterInfo::Tile(rect) |
+                SvgFilterInfo::Image(rect) => {
+                    let handle = filter_task.extra_gpu_cache_handle.get_or_insert_with(GpuCacheHandle::new);
+                    if let Some(mut request) = gpu_cache.request(handle) {
+                        request.push([rect.min.x, rect.min.y, rect.max.x, rect.max.y]);
+                    }
+                }
                 _ => {},
             }
         }
     }
 }
 
+/// Generates the lattice selector and the gradients (one set per color channel)
+/// used by turbulence filters, following the reference implementation of the
+/// feTurbulence element in the SVG specification.
+///
+/// The specification duplicates both tables so that indices up to twice the
+/// lattice size can be used directly. We only generate the first half, and
+/// the shader wraps the indices instead.
+fn turbulence_tables(seed: i32) -> ([u8; 256], Vec<[f32; 2]>) {
+    const RAND_M: i64 = 2147483647;
+    const RAND_A: i64 = 16807;
+    const RAND_Q: i64 = 127773;
+    const RAND_R: i64 = 2836;
+    const B_SIZE: i64 = 256;
+
+    fn random(seed: i64) -> i64 {
+        let result = RAND_A * (seed % RAND_Q) - RAND_R * (seed / RAND_Q);
+        if result <= 0 { result + RAND_M } else { result }
+    }
+
+    let mut seed = seed as i64;
+    if seed <= 0 {
+        seed = -(seed % (RAND_M - 1)) + 1;
+    }
+    if seed > RAND_M - 1 {
+        seed = RAND_M - 1;
+    }
+
+    let mut lattice = [0u8; 256];
+    let mut gradients = vec![[0.0f32; 2]; 4 * B_SIZE as usize];
+    for channel in 0 .. 4 {
+        for i in 0 .. B_SIZE as usize {
+            lattice[i] = i as u8;
+            let gradient = &mut gradients[channel * B_SIZE as usize + i];
+            for component in gradient.iter_mut() {
+                seed = random(seed);
+                *component = ((seed % (B_SIZE + B_SIZE)) - B_SIZE) as f32 / B_SIZE as f32;
+            }
+            let length = (gradient[0] * gradient[0] + gradient[1] * gradient[1]).sqrt();
+            if length != 0.0 {
+                gradient[0] /= length;
+                gradient[1] /= length;
+            }
+        }
+    }
+
+    for i in (1 .. B_SIZE as usize).rev() {
+        seed = random(seed);
+        let j = (seed % B_SIZE) as usize;
+        lattice.swap(i, j);
+    }
+
+    (lattice, gradients)
+}
+
 /// In order to avoid duplicating the down-scaling and blur passes when a picture has several blurs,
 /// we use a local (primitive-level) cache of the render tasks generated for a single shadowed primitive
 /// in a single frame.
@@ -995,6 +1324,10 @@ impl RenderTask {
         uv_rect_kind: UvRectKind,
         original_task_id: RenderTaskId,
         device_pixel_scale: DevicePixelScale,
+        content_origin: DevicePoint,
+        resource_cache: &mut ResourceCache,
+        gpu_cache: &mut GpuCache,
+        extra_gpu_data_handles: &mut SmallVec<[GpuCacheHandle; 1]>,
     ) -> RenderTaskId {
 
         if filter_primitives.is_empty() {
@@ -1275,6 +1608,338 @@ impl RenderTask {
                         rg_builder,
                     )
                 }
+                FilterPrimitiveKind::Turbulence(ref info) => {
+                    let mut base_frequency = LayoutVector2D::new(info.base_frequency_x, info.base_frequency_y);
+
+                    // When stitching, the frequencies are adjusted so that the
+                    // task contains a whole number of noise periods.
+                    let stitch = if info.stitch_tiles {
+                        let tile_origin = content_origin / device_pixel_scale;
+                        let tile_size = content_size.to_f32() / device_pixel_scale;
+                        let adjust = |frequency: f32, tile_size: f32| {
+                            if frequency == 0.0 || tile_size <= 0.0 {
+                                return frequency;
+                            }
+                            let lo = (tile_size * frequency).floor() / tile_size;
+                            let hi = (tile_size * frequency).ceil() / tile_size;
+                            if lo != 0.0 && frequency / lo < hi / frequency { lo } else { hi }
+                        };
+                        base_frequency.x = adjust(base_frequency.x, tile_size.width);
+                        base_frequency.y = adjust(base_frequency.y, tile_size.height);
+
+                        let width = (tile_size.width * base_frequency.x + 0.5) as i32;
+                        let height = (tile_size.height * base_frequency.y + 0.5) as i32;
+                        Some([
+                            width,
+                            height,
+                            (tile_origin.x * base_frequency.x + (TURBULENCE_PERLIN_N + width) as f32) as i32,
+                            (tile_origin.y * base_frequency.y + (TURBULENCE_PERLIN_N + height) as f32) as i32,
+                        ])
+                    } else {
+                        None
+                    };
+
+                    // The tables are stored in a handle owned by the picture, so
+                    // that they are only uploaded once rather than every frame.
+                    if extra_gpu_data_handles.len() < filter_primitives.len() {
+                        extra_gpu_data_handles.resize(filter_primitives.len(), GpuCacheHandle::new());
+                    }
+                    let tables_handle = &mut extra_gpu_data_handles[cur_index];
+                    if let Some(mut request) = gpu_cache.request(tables_handle) {
+                        let (lattice, gradients) = turbulence_tables(info.seed);
+                        for values in lattice.chunks(4) {
+                            request.push([
+                                values[0] as f32,
+                                values[1] as f32,
+                                values[2] as f32,
+                                values[3] as f32,
+                            ]);
+                        }
+                        for values in gradients.chunks(2) {
+                            request.push([values[0][0], values[0][1], values[1][0], values[1][1]]);
+                        }
+                    }
+
+                    RenderTask::new_svg_filter_primitive(
+                        smallvec![],
+                        content_size,
+                        uv_rect_kind,
+                        SvgFilterInfo::Turbulence(SvgTurbulenceInfo {
+                            kind: info.kind,
+                            seed: info.seed,
+                            num_octaves: info.num_octaves.min(MAX_TURBULENCE_OCTAVES),
+                            base_frequency: DeviceVector2D::new(
+                                base_frequency.x / device_pixel_scale.0,
+                                base_frequency.y / device_pixel_scale.0,
+                            ),
+                            origin: content_origin,
+                            stitch,
+                            tables_handle: *tables_handle,
+                        }),
+                        rg_builder,
+                    )
+                }
+                FilterPrimitiveKind::Morphology(ref info) => {
+                    let input_task_id = get_task_input(
+                        &info.input,
+                        filter_primitives,
+                        rg_builder,
+                        cur_index,
+                        &outputs,
+                        original_task_id,
+                        primitive.color_space
+                    );
+
+                    // The radius is clamped in layout space when sanitizing, but
+                    // scaling can still grow it, so clamp it again in device space.
+                    let radius_x = ((info.radius_x * device_pixel_scale.0).round() as i32)
+                        .min(MAX_MORPHOLOGY_RADIUS);
+                    let radius_y = ((info.radius_y * device_pixel_scale.0).round() as i32)
+                        .min(MAX_MORPHOLOGY_RADIUS);
+
+                    // A radius of zero disables the filter. Otherwise, the filter
+                    // is separable, so it is applied with a pass along each axis.
+                    if radius_x <= 0 || radius_y <= 0 {
+                        input_task_id
+                    } else {
+                        let horizontal_task_id = RenderTask::new_svg_filter_primitive(
+                            smallvec![input_task_id],
+                            content_size,
+                            uv_rect_kind,
+                            SvgFilterInfo::Morphology(
+                                info.operator,
+                                DeviceIntVector2D::new(radius_x, 0),
+                            ),
+                            rg_builder,
+                        );
+
+                        RenderTask::new_svg_filter_primitive(
+                            smallvec![horizontal_task_id],
+                            content_size,
+                            uv_rect_kind,
+                            SvgFilterInfo::Morphology(
+                                info.operator,
+                                DeviceIntVector2D::new(0, radius_y),
+                            ),
+                            rg_builder,
+                        )
+                    }
+                }
+                FilterPrimitiveKind::ConvolveMatrix(ref info) => {
+                    let input_task_id = get_task_input(
+                        &info.input,
+                        filter_primitives,
+                        rg_builder,
+                        cur_index,
+                        &outputs,
+                        original_task_id,
+                        primitive.color_space
+                    );
+
+                    RenderTask::new_svg_filter_primitive(
+                        smallvec![input_task_id],
+                        content_size,
+                        uv_rect_kind,
+                        SvgFilterInfo::ConvolveMatrix(Box::new(*info)),
+                        rg_builder,
+                    )
+                }
+                FilterPrimitiveKind::DisplacementMap(ref info) => {
+                    let input_1_task_id = get_task_input(
+                        &info.input1,
+                        filter_primitives,
+                        rg_builder,
+                        cur_index,
+                        &outputs,
+                        original_task_id,
+                        primitive.color_space
+                    );
+                    let input_2_task_id = get_task_input(
+                        &info.input2,
+                        filter_primitives,
+                        rg_builder,
+                        cur_index,
+                        &outputs,
+                        original_task_id,
+                        primitive.color_space
+                    );
+
+                    RenderTask::new_svg_filter_primitive(
+                        smallvec![input_1_task_id, input_2_task_id],
+                        content_size,
+                        uv_rect_kind,
+                        SvgFilterInfo::DisplacementMap(
+                            info.scale * device_pixel_scale.0,
+                            info.x_channel_selector,
+                            info.y_channel_selector,
+                        ),
+                        rg_builder,
+                    )
+                }
+                FilterPrimitiveKind::DiffuseLighting(ref info) => {
+                    let input_task_id = get_task_input(
+                        &info.input,
+                        filter_primitives,
+                        rg_builder,
+                        cur_index,
+                        &outputs,
+                        original_task_id,
+                        primitive.color_space
+                    );
+
+                    RenderTask::new_svg_filter_primitive(
+                        smallvec![input_task_id],
+                        content_size,
+                        uv_rect_kind,
+                        SvgFilterInfo::DiffuseLighting(Box::new(SvgLightingInfo {
+                            surface_scale: info.surface_scale * device_pixel_scale.0,
+                            lighting_constant: info.diffuse_constant,
+                            specular_exponent: 1.0,
+                            color: info.lighting_color,
+                            light: SvgLightSource::new(&info.light, content_origin, device_pixel_scale),
+                        })),
+                        rg_builder,
+                    )
+                }
+                FilterPrimitiveKind::SpecularLighting(ref info) => {
+                    let input_task_id = get_task_input(
+                        &info.input,
+                        filter_primitives,
+                        rg_builder,
+                        cur_index,
+                        &outputs,
+                        original_task_id,
+                        primitive.color_space
+                    );
+
+                    RenderTask::new_svg_filter_primitive(
+                        smallvec![input_task_id],
+                        content_size,
+                        uv_rect_kind,
+                        SvgFilterInfo::SpecularLighting(Box::new(SvgLightingInfo {
+                            surface_scale: info.surface_scale * device_pixel_scale.0,
+                            lighting_constant: info.specular_constant,
+                            specular_exponent: info.specular_exponent,
+                            color: info.lighting_color,
+                            light: SvgLightSource::new(&info.light, content_origin, device_pixel_scale),
+                        })),
+                        rg_builder,
+                    )
+                }
+                FilterPrimitiveKind::Tile(ref info) => {
+                    let input_task_id = get_task_input(
+                        &info.input,
+                        filter_primitives,
+                        rg_builder,
+                        cur_index,
+                        &outputs,
+                        original_task_id,
+                        primitive.color_space
+                    );
+
+                    let source_rect = info.source_rect * LayoutToWorldScale::new(1.0) * device_pixel_scale;
+                    if source_rect.is_empty() {
+                        RenderTask::new_svg_filter_primitive(
+                            smallvec![],
+                            content_size,
+                            uv_rect_kind,
+                            SvgFilterInfo::Flood(ColorF::TRANSPARENT),
+                            rg_builder,
+                        )
+                    } else {
+                        RenderTask::new_svg_filter_primitive(
+                            smallvec![input_task_id],
+                            content_size,
+                            uv_rect_kind,
+                            SvgFilterInfo::Tile(source_rect.translate(-content_origin.to_vector())),
+                            rg_builder,
+                        )
+                    }
+                }
+                FilterPrimitiveKind::Merge(ref info) => {
+                    // Merging is the same as compositing each input over the
+                    // previous ones, so it doesn't need a dedicated shader.
+                    let mut merge_task_id = None;
+                    for input in info.inputs() {
+                        let input_task_id = get_task_input(
+                            input,
+                            filter_primitives,
+                            rg_builder,
+                            cur_index,
+                            &outputs,
+                            original_task_id,
+                            primitive.color_space
+                        );
+
+                        merge_task_id = Some(match merge_task_id {
+                            Some(backdrop_task_id) => {
+                                RenderTask::new_svg_filter_primitive(
+                                    smallvec![input_task_id, backdrop_task_id],
+                                    content_size,
+                                    uv_rect_kind,
+                                    SvgFilterInfo::Composite(CompositeOperator::Over),
+                                    rg_builder,
+                                )
+                            }
+                            None => input_task_id,
+                        });
+                    }
+
+                    merge_task_id.unwrap_or_else(|| {
+                        RenderTask::new_svg_filter_primitive(
+                            smallvec![],
+                            content_size,
+                            uv_rect_kind,
+                            SvgFilterInfo::Flood(ColorF::TRANSPARENT),
+                            rg_builder,
+                        )
+                    })
+                }
+                FilterPrimitiveKind::Image(ref info) => {
+                    let request = ImageRequest {
+                        key: info.image_key,
+                        rendering: info.rendering,
+                        tile: None,
+                    };
+
+                    // Tiled images and external textures that can't be sampled
+                    // as a regular texture are not supported, and render nothing.
+                    let is_supported = !info.rect.is_empty() && match resource_cache.get_image_properties(info.image_key) {
+                        Some(properties) => {
+                            properties.tiling.is_none() && match properties.external_image {
+                                Some(ref external_image) => matches!(
+                                    external_image.image_type,
+                                    ExternalImageType::TextureHandle(ImageBufferKind::Texture2D) |
+                                    ExternalImageType::Buffer
+                                ),
+                                None => true,
+                            }
+                        }
+                        None => false,
+                    };
+
+                    if is_supported {
+                        let size = resource_cache.request_image(request, gpu_cache);
+                        let image_task_id = rg_builder.add().init(RenderTask::new_image(size, request));
+
+                        let rect = info.rect * LayoutToWorldScale::new(1.0) * device_pixel_scale;
+                        RenderTask::new_svg_filter_primitive(
+                            smallvec![image_task_id],
+                            content_size,
+                            uv_rect_kind,
+                            SvgFilterInfo::Image(rect.translate(-content_origin.to_vector())),
+                            rg_builder,
+                        )
+                    } else {
+                        RenderTask::new_svg_filter_primitive(
+                            smallvec![],
+                            content_size,
+                            uv_rect_kind,
+                            SvgFilterInfo::Flood(ColorF::TRANSPARENT),
+                            rg_builder,
+                        )
+                    }
+                }
             };
             outputs.push(render_task_id);
         }
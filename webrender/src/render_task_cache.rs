@@ -18,6 +18,7 @@ use crate::prim_store::gradient::{
     ConicGradientCacheKey,
 };
 use crate::prim_store::line_dec::LineDecorationCacheKey;
+use crate::prim_store::path::PathCacheKey;
 use crate::resource_cache::CacheItem;
 use std::{mem, usize, f32, i32};
 use crate::texture_cache::{TextureCache, TextureCacheHandle, Eviction, TargetShader};
@@ -52,6 +53,7 @@ pub enum RenderTaskCacheKeyKind {
     LinearGradient(LinearGradientCacheKey),
     RadialGradient(RadialGradientCacheKey),
     ConicGradient(ConicGradientCacheKey),
+    Path(PathCacheKey),
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
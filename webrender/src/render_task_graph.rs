@@ -299,6 +299,7 @@ impl RenderTaskGraphBuilder {
         resource_cache: &mut ResourceCache,
         gpu_cache: &mut GpuCache,
         deferred_resolves: &mut Vec<DeferredResolve>,
+        color_target_format: ImageFormat,
     ) -> RenderTaskGraph {
         // Copy the render tasks over to the immutable graph output
         let task_count = self.tasks.len();
@@ -477,7 +478,7 @@ impl RenderTaskGraphBuilder {
                             };
 
                             let format = match kind {
-                                RenderTargetKind::Color => ImageFormat::RGBA8,
+                                RenderTargetKind::Color => color_target_format,
                                 RenderTargetKind::Alpha => ImageFormat::R8,
                             };
 
@@ -821,10 +822,10 @@ pub struct RenderPass {
 
 impl RenderPass {
     /// Creates an intermediate off-screen pass.
-    pub fn new(src: &Pass) -> Self {
+    pub fn new(src: &Pass, color_target_format: ImageFormat) -> Self {
         RenderPass {
             color: RenderTargetList::new(
-                ImageFormat::RGBA8,
+                color_target_format,
             ),
             alpha: RenderTargetList::new(
                 ImageFormat::R8,
@@ -1066,7 +1067,7 @@ impl RenderTaskGraphBuilder {
         gc.prepare_for_frames();
         gc.begin_frame(frame_stamp);
 
-        let g = self.end_frame(&mut rc, &mut gc, &mut Vec::new());
+        let g = self.end_frame(&mut rc, &mut gc, &mut Vec::new(), ImageFormat::RGBA8);
         g.print();
 
         assert_eq!(g.passes.len(), pass_count);
@@ -41,7 +41,7 @@ use api::CrashAnnotator;
 use api::ExternalImageId;
 use api::{ExternalImageSource, ExternalImageType, FontRenderMode, ImageFormat};
 use api::{PipelineId, ImageRendering, Checkpoint, NotificationRequest};
-use api::{VoidPtrToSizeFn, PremultipliedColorF};
+use api::{VoidPtrToSizeFn, PremultipliedColorF, RgbColorSpace};
 use api::{RenderNotifier, ImageBufferKind, SharedFontInstanceMap};
 #[cfg(feature = "replay")]
 use api::ExternalImage;
@@ -54,6 +54,7 @@ use crate::render_api::{RenderApiSender, DebugCommand, FrameMsg, MemoryReport};
 use crate::batch::{AlphaBatchContainer, BatchKind, BatchFeatures, BatchTextures, BrushBatchKind, ClipBatchList};
 #[cfg(any(feature = "capture", feature = "replay"))]
 use crate::capture::{CaptureConfig, ExternalCaptureImage, PlainExternalImage};
+use crate::color_space::{convert_color, encode_color_space_conversion};
 use crate::composite::{CompositeState, CompositeTileSurface, ResolvedExternalSurface, CompositorSurfaceTransform};
 use crate::composite::{CompositorKind, Compositor, NativeTileId, CompositeFeatures, CompositeSurfaceFormat, ResolvedExternalSurfaceColorData};
 use crate::composite::{CompositorConfig, NativeSurfaceOperationDetails, NativeSurfaceId, NativeSurfaceOperation};
@@ -68,7 +69,7 @@ use crate::device::query::{GpuSampler, GpuTimer};
 use crate::device::FBOId;
 use crate::debug_item::DebugItem;
 use crate::frame_builder::{Frame, ChasePrimitive, FrameBuilderConfig};
-use crate::glyph_cache::GlyphCache;
+use crate::glyph_cache::{GlyphCache, GlyphDiskCacheOptions};
 use crate::glyph_rasterizer::{GlyphFormat, GlyphRasterizer};
 use crate::gpu_cache::{GpuCacheUpdate, GpuCacheUpdateList};
 use crate::gpu_cache::{GpuCacheDebugChunk, GpuCacheDebugCmd};
@@ -95,6 +96,7 @@ use crate::render_target::{AlphaRenderTarget, ColorRenderTarget, PictureCacheTar
 use crate::render_target::{RenderTarget, TextureCacheRenderTarget};
 use crate::render_target::{RenderTargetKind, BlitJob};
 use crate::texture_cache::{TextureCache, TextureCacheConfig};
+use crate::tone_map::HdrOptions;
 use crate::tile_cache::PictureCacheDebugInfo;
 use crate::util::drain_filter;
 use crate::rectangle_occlusion as occlusion;
@@ -216,6 +218,14 @@ const GPU_TAG_CACHE_CONIC_GRADIENT: GpuProfileTag = GpuProfileTag {
     label: "C_ConicGradient",
     color: debug_colors::BROWN,
 };
+const GPU_TAG_CACHE_PATH: GpuProfileTag = GpuProfileTag {
+    label: "C_Path",
+    color: debug_colors::OLIVEDRAB,
+};
+const GPU_TAG_CACHE_TEXT_MASK: GpuProfileTag = GpuProfileTag {
+    label: "C_TextMask",
+    color: debug_colors::DARKOLIVEGREEN,
+};
 const GPU_TAG_SETUP_TARGET: GpuProfileTag = GpuProfileTag {
     label: "target init",
     color: debug_colors::SLATEGREY,
@@ -323,7 +333,8 @@ impl From<GlyphFormat> for ShaderColorMode {
         match format {
             GlyphFormat::Alpha |
             GlyphFormat::TransformedAlpha |
-            GlyphFormat::Bitmap => ShaderColorMode::Alpha,
+            GlyphFormat::Bitmap |
+            GlyphFormat::Sdf => ShaderColorMode::Alpha,
             GlyphFormat::Subpixel | GlyphFormat::TransformedSubpixel => {
                 panic!("Subpixel glyph formats must be handled separately.");
             }
@@ -418,6 +429,9 @@ pub struct CpuProfile {
     pub backend_time_ns: u64,
     pub composite_time_ns: u64,
     pub draw_calls: usize,
+    pub texture_uploads: usize,
+    /// The number of picture cache tiles that were invalidated and rendered.
+    pub rendered_picture_tiles: usize,
 }
 
 impl CpuProfile {
@@ -426,12 +440,16 @@ impl CpuProfile {
         backend_time_ns: u64,
         composite_time_ns: u64,
         draw_calls: usize,
+        texture_uploads: usize,
+        rendered_picture_tiles: usize,
     ) -> CpuProfile {
         CpuProfile {
             frame_id,
             backend_time_ns,
             composite_time_ns,
             draw_calls,
+            texture_uploads,
+            rendered_picture_tiles,
         }
     }
 }
@@ -771,6 +789,7 @@ pub struct Renderer {
 
     max_recorded_profiles: usize,
 
+    /// The color the framebuffer is cleared to, in sRGB.
     clear_color: ColorF,
     enable_clear_scissor: bool,
     enable_advanced_blend_barriers: bool,
@@ -859,6 +878,14 @@ pub struct Renderer {
 
     current_compositor_kind: CompositorKind,
 
+    /// The color space compositor surfaces and the clear color are
+    /// converted to.
+    output_color_space: RgbColorSpace,
+
+    /// Set when rendering in HDR mode. The composite pass then tone maps
+    /// the picture cache tiles and compositor surfaces.
+    hdr: Option<HdrOptions>,
+
     /// Maintains a set of allocated native composite surfaces. This allows any
     /// currently allocated surfaces to be cleaned up as soon as deinit() is
     /// called (the normal bookkeeping for native surfaces exists in the
@@ -1155,6 +1182,31 @@ impl Renderer {
             }
         };
 
+        let hdr = match options.hdr {
+            Some(..) if !device.get_capabilities().supports_color_buffer_float => {
+                warn!("HDR rendering requires float render targets, falling back to SDR");
+                None
+            }
+            // SWGL stores RGBA16F targets as RGBA32F, which it can't draw into.
+            Some(..) if is_software => {
+                warn!("HDR rendering is not supported by Software WebRender, falling back to SDR");
+                None
+            }
+            // Native compositor surfaces are presented directly by the OS
+            // compositor, so there is no composite pass to tone map in.
+            Some(..) if matches!(compositor_kind, CompositorKind::Native { .. }) => {
+                warn!("HDR rendering is not supported by the native compositor, falling back to SDR");
+                None
+            }
+            // The tone mapped output goes above 1.0, which would be clamped
+            // by a fixed point framebuffer.
+            Some(..) if !device.draw_framebuffer_is_float() => {
+                warn!("HDR rendering requires a float framebuffer, falling back to SDR");
+                None
+            }
+            hdr => hdr,
+        };
+
         let config = FrameBuilderConfig {
             default_font_render_mode,
             dual_source_blending_is_enabled: true,
@@ -1175,6 +1227,8 @@ impl Renderer {
             force_invalidation: false,
             is_software,
             low_quality_pinch_zoom: options.low_quality_pinch_zoom,
+            output_color_space: options.output_color_space,
+            hdr,
         };
         info!("WR {:?}", config);
 
@@ -1199,6 +1253,17 @@ impl Renderer {
                     .build();
                 Arc::new(worker.unwrap())
             });
+        // Software WebRender rasterizes large draw calls in parallel on the
+        // workers, in bands of rows. The number of threads can be limited with
+        // RAYON_NUM_THREADS to compare performance.
+        #[cfg(feature = "swgl")]
+        {
+            if is_software {
+                if let Some(swgl) = swgl::Context::current() {
+                    swgl.set_rasterize_workers(Some(workers.clone()));
+                }
+            }
+        }
         let sampler = options.sampler;
         let namespace_alloc_by_client = options.namespace_alloc_by_client;
 
@@ -1209,7 +1274,9 @@ impl Renderer {
         let rb_thread_name = format!("WRRenderBackend#{}", options.renderer_id.unwrap_or(0));
         let scene_thread_name = format!("WRSceneBuilder#{}", options.renderer_id.unwrap_or(0));
         let lp_scene_thread_name = format!("WRSceneBuilderLP#{}", options.renderer_id.unwrap_or(0));
-        let glyph_rasterizer = GlyphRasterizer::new(workers, device.get_capabilities().supports_r8_texture_upload)?;
+        let can_use_r8_glyphs = device.get_capabilities().supports_r8_texture_upload;
+        let glyph_rasterizer = GlyphRasterizer::new(workers, can_use_r8_glyphs)?;
+        let glyph_disk_cache = options.glyph_disk_cache.take();
 
         let (scene_builder_channels, scene_tx) =
             SceneBuilderThreadChannels::new(api_tx.clone());
@@ -1269,6 +1336,7 @@ impl Renderer {
         } else {
             TextureFilter::Nearest
         };
+        let picture_texture_format = config.color_target_format();
 
         let rb_scene_tx = scene_tx.clone();
         let rb_font_instances = font_instances.clone();
@@ -1285,9 +1353,15 @@ impl Renderer {
                 swizzle_settings,
                 &texture_cache_config,
                 picture_texture_filter,
+                picture_texture_format,
             );
 
-            let glyph_cache = GlyphCache::new();
+            let glyph_cache = match glyph_disk_cache {
+                Some(ref disk_cache_options) => {
+                    GlyphCache::with_disk_cache(disk_cache_options, can_use_r8_glyphs)
+                }
+                None => GlyphCache::new(),
+            };
 
             let mut resource_cache = ResourceCache::new(
                 texture_cache,
@@ -1391,6 +1465,8 @@ impl Renderer {
             force_redraw: true,
             compositor_config: options.compositor_config,
             current_compositor_kind: compositor_kind,
+            output_color_space: options.output_color_space,
+            hdr,
             allocated_native_surfaces: FastHashSet::default(),
             debug_overlay_state: DebugOverlayState::new(),
             buffer_damage_tracker: BufferDamageTracker::default(),
@@ -1444,10 +1520,16 @@ impl Renderer {
         self.device.required_pbo_stride().num_bytes(format).get()
     }
 
+    /// Set the color the framebuffer is cleared to. The color is specified
+    /// in sRGB and converted to the output color space.
     pub fn set_clear_color(&mut self, color: ColorF) {
         self.clear_color = color;
     }
 
+    fn output_clear_color(&self) -> ColorF {
+        convert_color(self.clear_color, RgbColorSpace::Srgb, self.output_color_space)
+    }
+
     pub fn flush_pipeline_info(&mut self) -> PipelineInfo {
         mem::replace(&mut self.pipeline_info, PipelineInfo::default())
     }
@@ -1640,6 +1722,9 @@ impl Renderer {
             DebugCommand::SetPictureTileSize(_) => {
                 panic!("Should be handled by render backend");
             }
+            DebugCommand::SetOutputColorSpace(color_space) => {
+                self.output_color_space = color_space;
+            }
             DebugCommand::SaveCapture(..) |
             DebugCommand::LoadCapture(..) |
             DebugCommand::StartCaptureSequence(..) |
@@ -2026,6 +2111,8 @@ impl Renderer {
                 (self.profile.get_or(profiler::FRAME_BUILDING_TIME, 0.0) * 1000000.0) as u64,
                 (self.profile.get_or(profiler::RENDERER_TIME, 0.0) * 1000000.0) as u64,
                 self.profile.get_or(profiler::DRAW_CALLS, 0.0) as usize,
+                self.profile.get_or(profiler::TEXTURE_UPLOADS, 0.0) as usize,
+                self.profile.get_or(profiler::RENDERED_PICTURE_TILES, 0.0) as usize,
             );
             self.cpu_profiles.push_back(cpu_profile);
         }
@@ -3027,7 +3114,7 @@ impl Renderer {
 
                     ( textures, instance )
                 },
-                ResolvedExternalSurfaceColorData::Rgb{ ref plane, .. } => {
+                ResolvedExternalSurfaceColorData::Rgb{ ref plane, color_space, .. } => {
                     self.shaders
                         .borrow_mut()
                         .get_composite_shader(
@@ -3050,6 +3137,8 @@ impl Renderer {
                         ZBufferId(0),
                         uv_rect,
                         CompositorTransform::identity(),
+                    ).with_color_space_conversion(
+                        encode_color_space_conversion(color_space, self.output_color_space),
                     );
 
                     ( textures, instance )
@@ -3072,6 +3161,38 @@ impl Renderer {
         self.gpu_profiler.finish_sampler(opaque_sampler);
     }
 
+    /// Bind the composite shader for the given surface format, buffer kind,
+    /// features and texture size. In HDR mode, rgba surfaces are tone mapped.
+    fn bind_composite_shader(
+        &mut self,
+        params: (CompositeSurfaceFormat, ImageBufferKind, CompositeFeatures, Option<DeviceSize>),
+        projection: &default::Transform3D<f32>,
+    ) {
+        let mut shaders = self.shaders.borrow_mut();
+        match (params.0, self.hdr) {
+            (CompositeSurfaceFormat::Rgba, Some(hdr)) => {
+                let shader = shaders.get_composite_tone_map_shader(params.1);
+                shader.bind(
+                    &mut self.device,
+                    projection,
+                    params.3,
+                    &mut self.renderer_errors
+                );
+                shader.set_tone_map(&mut self.device, hdr.shader_params());
+            }
+            _ => {
+                shaders
+                    .get_composite_shader(params.0, params.1, params.2)
+                    .bind(
+                        &mut self.device,
+                        projection,
+                        params.3,
+                        &mut self.renderer_errors
+                    );
+            }
+        }
+    }
+
     /// Draw a list of tiles to the framebuffer
     fn draw_tile_list<'a, I: Iterator<Item = &'a occlusion::Item>>(
         &mut self,
@@ -3090,18 +3211,7 @@ impl Renderer {
         let mut current_textures = BatchTextures::empty();
         let mut instances = Vec::new();
 
-        self.shaders
-            .borrow_mut()
-            .get_composite_shader(
-                current_shader_params.0,
-                current_shader_params.1,
-                current_shader_params.2,
-            ).bind(
-                &mut self.device,
-                projection,
-                None,
-                &mut self.renderer_errors
-            );
+        self.bind_composite_shader(current_shader_params, projection);
 
         for item in tiles_iter {
             let tile = &composite_state.tiles[item.key];
@@ -3191,7 +3301,7 @@ impl Renderer {
                                 ),
                             )
                         },
-                        ResolvedExternalSurfaceColorData::Rgb { ref plane, .. } => {
+                        ResolvedExternalSurfaceColorData::Rgb { ref plane, color_space, .. } => {
                             let uv_rect = self.texture_resolver.get_uv_rect(&plane.texture, plane.uv_rect);
                             let instance = CompositeInstance::new_rgb(
                                 tile_rect,
@@ -3200,6 +3310,8 @@ impl Renderer {
                                 tile.z_id,
                                 uv_rect,
                                 transform,
+                            ).with_color_space_conversion(
+                                encode_color_space_conversion(color_space, self.output_color_space),
                             );
                             let features = instance.get_rgb_features();
                             (
@@ -3254,16 +3366,7 @@ impl Renderer {
             }
 
             if shader_params != current_shader_params {
-                self.shaders
-                    .borrow_mut()
-                    .get_composite_shader(shader_params.0, shader_params.1, shader_params.2)
-                    .bind(
-                        &mut self.device,
-                        projection,
-                        shader_params.3,
-                        &mut self.renderer_errors
-                    );
-
+                self.bind_composite_shader(shader_params, projection);
                 current_shader_params = shader_params;
             }
 
@@ -3361,7 +3464,7 @@ impl Renderer {
         }
 
         // Clear the framebuffer
-        let clear_color = Some(self.clear_color.to_array());
+        let clear_color = Some(self.output_clear_color().to_array());
 
         match partial_present_mode {
             Some(PartialPresentMode::Single { dirty_rect }) => {
@@ -3639,6 +3742,23 @@ impl Renderer {
             );
         }
 
+        // draw path clips
+        if !list.paths.is_empty() {
+            let _gm2 = self.gpu_profiler.start_marker("clip paths");
+            self.shaders.borrow_mut().cs_clip_path.bind(
+                &mut self.device,
+                projection,
+                None,
+                &mut self.renderer_errors,
+            );
+            self.draw_instanced_batch(
+                &list.paths,
+                VertexArrayKind::ClipPath,
+                &BatchTextures::empty(),
+                stats,
+            );
+        }
+
         // draw box-shadow clips
         for (mask_texture_id, items) in list.box_shadows.iter() {
             let _gm2 = self.gpu_profiler.start_marker("box-shadows");
@@ -3818,6 +3938,33 @@ impl Renderer {
             stats,
         );
 
+        // Draw any text masks for this target. Glyphs of a text clip can
+        // overlap, so their coverage is combined with max blending.
+        if !target.text_masks.is_empty() {
+            let _timer = self.gpu_profiler.start_timer(GPU_TAG_CACHE_TEXT_MASK);
+
+            self.set_blend(true, FramebufferKind::Other);
+            self.device.set_blend_mode_max();
+
+            self.shaders.borrow_mut().cs_text_mask.bind(
+                &mut self.device,
+                projection,
+                None,
+                &mut self.renderer_errors,
+            );
+
+            for (texture, instances) in &target.text_masks {
+                self.draw_instanced_batch(
+                    instances,
+                    VertexArrayKind::TextMask,
+                    &BatchTextures::composite_rgb(*texture),
+                    stats,
+                );
+            }
+
+            self.set_blend(false, FramebufferKind::Other);
+        }
+
         // Draw the clip items into the tiled alpha mask.
         {
             let _timer = self.gpu_profiler.start_timer(GPU_TAG_CACHE_CLIP);
@@ -4096,6 +4243,27 @@ impl Renderer {
             );
         }
 
+        // Draw any paths for this target.
+        if !target.paths.is_empty() {
+            let _timer = self.gpu_profiler.start_timer(GPU_TAG_CACHE_PATH);
+
+            self.set_blend(false, FramebufferKind::Other);
+
+            self.shaders.borrow_mut().cs_path.bind(
+                &mut self.device,
+                &projection,
+                None,
+                &mut self.renderer_errors,
+            );
+
+            self.draw_instanced_batch(
+                &target.paths,
+                VertexArrayKind::Path,
+                &BatchTextures::empty(),
+                stats,
+            );
+        }
+
         // Draw any blurs for this target.
         if !target.horizontal_blurs.is_empty() {
             let _timer = self.gpu_profiler.start_timer(GPU_TAG_BLUR);
@@ -4469,7 +4637,7 @@ impl Renderer {
             // composition as surfaces are updated.
             if device_size.is_some() {
                 frame.composite_state.composite_native(
-                    self.clear_color,
+                    self.output_clear_color(),
                     &results.dirty_rects,
                     &mut **compositor,
                 );
@@ -5369,6 +5537,8 @@ pub struct RendererOptions {
     pub enable_subpixel_aa: bool,
     /// Enable sub-pixel anti-aliasing if it requires a slow implementation.
     pub force_subpixel_aa: bool,
+    /// The color the framebuffer is cleared to, in sRGB. It is converted to
+    /// the output color space.
     pub clear_color: ColorF,
     pub enable_clear_scissor: bool,
     pub max_internal_texture_size: Option<i32>,
@@ -5434,6 +5604,22 @@ pub struct RendererOptions {
     /// items, if the zoom factor is relatively small, bilinear filtering should
     /// make the result look quite close to the high-quality zoom, except for glyphs.
     pub low_quality_pinch_zoom: bool,
+    /// The color space of the output. Colors and images specified in other
+    /// color spaces are converted to it. The output is assumed to be sRGB
+    /// by default.
+    pub output_color_space: RgbColorSpace,
+    /// Render in HDR: intermediate targets and picture cache tiles are
+    /// RGBA16F so that content can go above the reference white, and the
+    /// composite pass tone maps the result. Only supported with the draw
+    /// compositor, and ignored if the GPU can't render to float targets.
+    ///
+    /// The tone mapped output goes above 1.0, so the framebuffer bound when
+    /// the renderer is created must have a floating point color buffer, such
+    /// as an RGBA16F window surface. HDR is ignored otherwise.
+    pub hdr: Option<HdrOptions>,
+    /// Keep rasterized glyphs on disk between runs, so that they don't need
+    /// rasterizing again on startup.
+    pub glyph_disk_cache: Option<GlyphDiskCacheOptions>,
 }
 
 impl RendererOptions {
@@ -5502,6 +5688,9 @@ impl Default for RendererOptions {
             enable_instancing: true,
             reject_software_rasterizer: false,
             low_quality_pinch_zoom: false,
+            output_color_space: RgbColorSpace::Srgb,
+            hdr: None,
+            glyph_disk_cache: None,
         }
     }
 }
@@ -5676,7 +5865,7 @@ impl Renderer {
         {
             let mut png_data;
             let (data_ref, format) = match texture.get_format() {
-                ImageFormat::RGBAF32 => {
+                ImageFormat::RGBAF32 | ImageFormat::RGBA16F | ImageFormat::RGB10A2 => {
                     png_data = vec![0; (rect_size.width * rect_size.height * 4) as usize];
                     device.read_pixels_into(rect, ImageFormat::RGBA8, &mut png_data);
                     (&png_data, ImageFormat::RGBA8)
@@ -63,6 +63,7 @@ const DEBUG_OVERDRAW_FEATURE: &str = "DEBUG_OVERDRAW";
 const DITHERING_FEATURE: &str = "DITHERING";
 const DUAL_SOURCE_FEATURE: &str = "DUAL_SOURCE_BLENDING";
 const FAST_PATH_FEATURE: &str = "FAST_PATH";
+const TONE_MAP_FEATURE: &str = "TONE_MAP";
 
 pub(crate) enum ShaderKind {
     Primitive,
@@ -160,6 +161,14 @@ impl LazilyCompiledShader {
         }
     }
 
+    /// Sets the uToneMap uniform of a composite shader with the
+    /// TONE_MAP feature. The shader must be bound first.
+    pub fn set_tone_map(&self, device: &mut Device, params: [f32; 4]) {
+        if let Some(ref program) = self.program {
+            device.set_shader_tone_map(program, params);
+        }
+    }
+
     fn get_internal(
         &mut self,
         device: &mut Device,
@@ -236,10 +245,13 @@ impl LazilyCompiledShader {
                 VertexArrayKind::LinearGradient => &desc::LINEAR_GRADIENT,
                 VertexArrayKind::RadialGradient => &desc::RADIAL_GRADIENT,
                 VertexArrayKind::ConicGradient => &desc::CONIC_GRADIENT,
+                VertexArrayKind::Path => &desc::PATH,
+                VertexArrayKind::TextMask => &desc::TEXT_MASK,
                 VertexArrayKind::Blur => &desc::BLUR,
                 VertexArrayKind::ClipImage => &desc::CLIP_IMAGE,
                 VertexArrayKind::ClipRect => &desc::CLIP_RECT,
                 VertexArrayKind::ClipBoxShadow => &desc::CLIP_BOX_SHADOW,
+                VertexArrayKind::ClipPath => &desc::CLIP_PATH,
                 VertexArrayKind::VectorStencil => &desc::VECTOR_STENCIL,
                 VertexArrayKind::VectorCover => &desc::VECTOR_COVER,
                 VertexArrayKind::Border => &desc::BORDER,
@@ -452,6 +464,7 @@ impl BrushShader {
 pub struct TextShader {
     simple: LazilyCompiledShader,
     glyph_transform: LazilyCompiledShader,
+    sdf: LazilyCompiledShader,
     debug_overdraw: LazilyCompiledShader,
 }
 
@@ -490,6 +503,20 @@ impl TextShader {
             &shader_list,
         )?;
 
+        let mut sdf_features = features.to_vec();
+        sdf_features.push("SDF");
+        sdf_features.push("ALPHA_PASS");
+        sdf_features.push("TEXTURE_2D");
+
+        let sdf = LazilyCompiledShader::new(
+            ShaderKind::Text,
+            name,
+            &sdf_features,
+            device,
+            precache_flags,
+            &shader_list,
+        )?;
+
         let mut debug_overdraw_features = features.to_vec();
         debug_overdraw_features.push("DEBUG_OVERDRAW");
         debug_overdraw_features.push("TEXTURE_2D");
@@ -503,7 +530,7 @@ impl TextShader {
             &shader_list,
         )?;
 
-        Ok(TextShader { simple, glyph_transform, debug_overdraw })
+        Ok(TextShader { simple, glyph_transform, sdf, debug_overdraw })
     }
 
     pub fn get(
@@ -519,12 +546,14 @@ impl TextShader {
             GlyphFormat::ColorBitmap => &mut self.simple,
             GlyphFormat::TransformedAlpha |
             GlyphFormat::TransformedSubpixel => &mut self.glyph_transform,
+            GlyphFormat::Sdf => &mut self.sdf,
         }
     }
 
     fn deinit(self, device: &mut Device) {
         self.simple.deinit(device);
         self.glyph_transform.deinit(device);
+        self.sdf.deinit(device);
         self.debug_overdraw.deinit(device);
     }
 }
@@ -565,6 +594,8 @@ pub struct Shaders {
     pub cs_linear_gradient: LazilyCompiledShader,
     pub cs_radial_gradient: LazilyCompiledShader,
     pub cs_conic_gradient: LazilyCompiledShader,
+    pub cs_path: LazilyCompiledShader,
+    pub cs_text_mask: LazilyCompiledShader,
     pub cs_svg_filter: LazilyCompiledShader,
 
     // Brush shaders
@@ -585,6 +616,7 @@ pub struct Shaders {
     pub cs_clip_rectangle_fast: LazilyCompiledShader,
     pub cs_clip_box_shadow: LazilyCompiledShader,
     pub cs_clip_image: LazilyCompiledShader,
+    pub cs_clip_path: LazilyCompiledShader,
 
     // The are "primitive shaders". These shaders draw and blend
     // final results on screen. They are aware of tile boundaries.
@@ -615,6 +647,10 @@ pub struct Shaders {
     pub composite_rgba_fast_path: Vec<Option<LazilyCompiledShader>>,
     // The same set of composite shaders but with WR_FEATURE_YUV added.
     pub composite_yuv: Vec<Option<LazilyCompiledShader>>,
+    // The rgba composite shaders with WR_FEATURE_TONE_MAP added, used for
+    // all rgba composites in HDR mode. Only compiled when RendererOptions::hdr
+    // is set.
+    pub composite_rgba_tone_map: Vec<Option<LazilyCompiledShader>>,
 }
 
 impl Shaders {
@@ -777,6 +813,15 @@ impl Shaders {
             &shader_list,
         )?;
 
+        let cs_clip_path = LazilyCompiledShader::new(
+            ShaderKind::ClipCache(VertexArrayKind::ClipPath),
+            "cs_clip_path",
+            &[],
+            device,
+            options.precache_flags,
+            &shader_list,
+        )?;
+
         let mut cs_scale = Vec::new();
         let scale_shader_num = IMAGE_BUFFER_KINDS.len();
         // PrimitiveShader is not clonable. Use push() to initialize the vec.
@@ -913,12 +958,14 @@ impl Shaders {
         let mut composite_yuv = Vec::new();
         let mut composite_rgba = Vec::new();
         let mut composite_rgba_fast_path = Vec::new();
+        let mut composite_rgba_tone_map = Vec::new();
         // PrimitiveShader is not clonable. Use push() to initialize the vec.
         for _ in 0 .. yuv_shader_num {
             brush_yuv_image.push(None);
             composite_yuv.push(None);
             composite_rgba.push(None);
             composite_rgba_fast_path.push(None);
+            composite_rgba_tone_map.push(None);
         }
         for image_buffer_kind in &IMAGE_BUFFER_KINDS {
             if has_platform_support(*image_buffer_kind, &gl_type) {
@@ -988,6 +1035,19 @@ impl Shaders {
                 composite_rgba[index] = Some(composite_rgba_shader);
                 composite_rgba_fast_path[index] = Some(composite_rgba_fast_path_shader);
 
+                if options.hdr.is_some() {
+                    rgba_features.push(TONE_MAP_FEATURE);
+                    let composite_rgba_tone_map_shader = LazilyCompiledShader::new(
+                        ShaderKind::Composite,
+                        "composite",
+                        &rgba_features,
+                        device,
+                        options.precache_flags,
+                        &shader_list,
+                    )?;
+                    composite_rgba_tone_map[index] = Some(composite_rgba_tone_map_shader);
+                }
+
                 yuv_features.clear();
                 rgba_features.clear();
                 fast_path_features.clear();
@@ -1039,6 +1099,24 @@ impl Shaders {
             &shader_list,
         )?;
 
+        let cs_path = LazilyCompiledShader::new(
+            ShaderKind::Cache(VertexArrayKind::Path),
+            "cs_path",
+            &[],
+            device,
+            options.precache_flags,
+            &shader_list,
+        )?;
+
+        let cs_text_mask = LazilyCompiledShader::new(
+            ShaderKind::Cache(VertexArrayKind::TextMask),
+            "cs_text_mask",
+            &[],
+            device,
+            options.precache_flags,
+            &shader_list,
+        )?;
+
         let cs_border_segment = LazilyCompiledShader::new(
             ShaderKind::Cache(VertexArrayKind::Border),
             "cs_border_segment",
@@ -1066,6 +1144,8 @@ impl Shaders {
             cs_linear_gradient,
             cs_radial_gradient,
             cs_conic_gradient,
+            cs_path,
+            cs_text_mask,
             cs_border_solid,
             cs_scale,
             cs_svg_filter,
@@ -1082,6 +1162,7 @@ impl Shaders {
             cs_clip_rectangle_fast,
             cs_clip_box_shadow,
             cs_clip_image,
+            cs_clip_path,
             ps_text_run,
             ps_text_run_dual_source,
             ps_split_composite,
@@ -1089,6 +1170,7 @@ impl Shaders {
             composite_rgba,
             composite_rgba_fast_path,
             composite_yuv,
+            composite_rgba_tone_map,
         })
     }
 
@@ -1127,6 +1209,16 @@ impl Shaders {
         }
     }
 
+    pub fn get_composite_tone_map_shader(
+        &mut self,
+        buffer_kind: ImageBufferKind,
+    ) -> &mut LazilyCompiledShader {
+        let shader_index = Self::get_compositing_shader_index(buffer_kind);
+        self.composite_rgba_tone_map[shader_index]
+            .as_mut()
+            .expect("bug: tone map shader requested without RendererOptions::hdr")
+    }
+
     pub fn get_scale_shader(
         &mut self,
         buffer_kind: ImageBufferKind,
@@ -1245,6 +1337,7 @@ impl Shaders {
         self.cs_clip_rectangle_fast.deinit(device);
         self.cs_clip_box_shadow.deinit(device);
         self.cs_clip_image.deinit(device);
+        self.cs_clip_path.deinit(device);
         self.ps_text_run.deinit(device);
         if let Some(shader) = self.ps_text_run_dual_source {
             shader.deinit(device);
@@ -1269,6 +1362,8 @@ impl Shaders {
         self.cs_linear_gradient.deinit(device);
         self.cs_radial_gradient.deinit(device);
         self.cs_conic_gradient.deinit(device);
+        self.cs_path.deinit(device);
+        self.cs_text_mask.deinit(device);
         self.cs_line_decoration.deinit(device);
         self.cs_border_segment.deinit(device);
         self.ps_split_composite.deinit(device);
@@ -1289,6 +1384,11 @@ impl Shaders {
                 shader.deinit(device);
             }
         }
+        for shader in self.composite_rgba_tone_map {
+            if let Some(shader) = shader {
+                shader.deinit(device);
+            }
+        }
     }
 }
 
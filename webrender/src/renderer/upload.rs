@@ -131,8 +131,11 @@ pub fn upload_to_texture_cache(
                 }
             };
 
+            // The staging textures and buffers only support 8 bit formats, so
+            // shared textures in other formats are uploaded directly.
             let use_batch_upload = renderer.device.use_batched_texture_uploads() &&
                 texture.flags().contains(TextureFlags::IS_SHARED_TEXTURE_CACHE) &&
+                UploadTexturePool::supports_format(texture.get_format()) &&
                 rect.width() <= BATCH_UPLOAD_TEXTURE_SIZE.width &&
                 rect.height() <= BATCH_UPLOAD_TEXTURE_SIZE.height;
 
@@ -609,6 +612,14 @@ impl UploadTexturePool {
         }
     }
 
+    /// Whether staging textures can be created for the given format.
+    pub fn supports_format(format: ImageFormat) -> bool {
+        match format {
+            ImageFormat::RGBA8 | ImageFormat::BGRA8 | ImageFormat::R8 => true,
+            _ => false,
+        }
+    }
+
     fn format_index(&self, format: ImageFormat) -> usize {
         match format {
             ImageFormat::RGBA8 => 0,
@@ -126,6 +126,11 @@ pub mod desc {
                 count: 1,
                 kind: VertexAttributeKind::F32,
             },
+            VertexAttribute {
+                name: "aInterpolationSpace",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
         ],
     };
 
@@ -269,6 +274,66 @@ pub mod desc {
         ],
     };
 
+    pub const PATH: VertexDescriptor = VertexDescriptor {
+        vertex_attributes: &[VertexAttribute {
+            name: "aPosition",
+            count: 2,
+            kind: VertexAttributeKind::U8Norm,
+        }],
+        instance_attributes: &[
+            VertexAttribute {
+                name: "aTaskRect",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aLocalSize",
+                count: 2,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aBrushParams0",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aBrushParams1",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aEdgesAddress",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+            VertexAttribute {
+                name: "aEdgeCount",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+            VertexAttribute {
+                name: "aFillRule",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+            VertexAttribute {
+                name: "aBrushKind",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+            VertexAttribute {
+                name: "aExtendMode",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+            VertexAttribute {
+                name: "aGradientStopsAddress",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+        ],
+    };
+
     pub const BORDER: VertexDescriptor = VertexDescriptor {
         vertex_attributes: &[VertexAttribute {
             name: "aPosition",
@@ -324,6 +389,31 @@ pub mod desc {
         ],
     };
 
+    pub const TEXT_MASK: VertexDescriptor = VertexDescriptor {
+        vertex_attributes: &[VertexAttribute {
+            name: "aPosition",
+            count: 2,
+            kind: VertexAttributeKind::U8Norm,
+        }],
+        instance_attributes: &[
+            VertexAttribute {
+                name: "aTaskRect",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aResourceAddress",
+                count: 2,
+                kind: VertexAttributeKind::U16,
+            },
+            VertexAttribute {
+                name: "aAlphaChannel",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+        ],
+    };
+
     pub const SCALE: VertexDescriptor = VertexDescriptor {
         vertex_attributes: &[VertexAttribute {
             name: "aPosition",
@@ -535,6 +625,58 @@ pub mod desc {
         ],
     };
 
+    pub const CLIP_PATH: VertexDescriptor = VertexDescriptor {
+        vertex_attributes: &[VertexAttribute {
+            name: "aPosition",
+            count: 2,
+            kind: VertexAttributeKind::U8Norm,
+        }],
+        instance_attributes: &[
+            // common clip attributes
+            VertexAttribute {
+                name: "aClipDeviceArea",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aClipOrigins",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aDevicePixelScale",
+                count: 1,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aTransformIds",
+                count: 2,
+                kind: VertexAttributeKind::I32,
+            },
+            // specific clip attributes
+            VertexAttribute {
+                name: "aClipLocalRect",
+                count: 4,
+                kind: VertexAttributeKind::F32,
+            },
+            VertexAttribute {
+                name: "aEdgesAddress",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+            VertexAttribute {
+                name: "aEdgeCount",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+            VertexAttribute {
+                name: "aFillRule",
+                count: 1,
+                kind: VertexAttributeKind::I32,
+            },
+        ],
+    };
+
     pub const GPU_CACHE_UPDATE: VertexDescriptor = VertexDescriptor {
         vertex_attributes: &[
             VertexAttribute {
@@ -767,6 +909,7 @@ pub enum VertexArrayKind {
     ClipImage,
     ClipRect,
     ClipBoxShadow,
+    ClipPath,
     VectorStencil,
     VectorCover,
     Border,
@@ -776,6 +919,8 @@ pub enum VertexArrayKind {
     LinearGradient,
     RadialGradient,
     ConicGradient,
+    Path,
+    TextMask,
     Resolve,
     SvgFilter,
     Composite,
@@ -992,6 +1137,7 @@ pub struct RendererVAOs {
     clip_rect_vao: VAO,
     clip_box_shadow_vao: VAO,
     clip_image_vao: VAO,
+    clip_path_vao: VAO,
     border_vao: VAO,
     line_vao: VAO,
     scale_vao: VAO,
@@ -999,6 +1145,8 @@ pub struct RendererVAOs {
     linear_gradient_vao: VAO,
     radial_gradient_vao: VAO,
     conic_gradient_vao: VAO,
+    path_vao: VAO,
+    text_mask_vao: VAO,
     resolve_vao: VAO,
     svg_filter_vao: VAO,
     composite_vao: VAO,
@@ -1038,6 +1186,7 @@ impl RendererVAOs {
             clip_box_shadow_vao: device
                 .create_vao_with_new_instances(&desc::CLIP_BOX_SHADOW, &prim_vao),
             clip_image_vao: device.create_vao_with_new_instances(&desc::CLIP_IMAGE, &prim_vao),
+            clip_path_vao: device.create_vao_with_new_instances(&desc::CLIP_PATH, &prim_vao),
             border_vao: device.create_vao_with_new_instances(&desc::BORDER, &prim_vao),
             scale_vao: device.create_vao_with_new_instances(&desc::SCALE, &prim_vao),
             line_vao: device.create_vao_with_new_instances(&desc::LINE, &prim_vao),
@@ -1045,6 +1194,8 @@ impl RendererVAOs {
             linear_gradient_vao: device.create_vao_with_new_instances(&desc::LINEAR_GRADIENT, &prim_vao),
             radial_gradient_vao: device.create_vao_with_new_instances(&desc::RADIAL_GRADIENT, &prim_vao),
             conic_gradient_vao: device.create_vao_with_new_instances(&desc::CONIC_GRADIENT, &prim_vao),
+            path_vao: device.create_vao_with_new_instances(&desc::PATH, &prim_vao),
+            text_mask_vao: device.create_vao_with_new_instances(&desc::TEXT_MASK, &prim_vao),
             resolve_vao: device.create_vao_with_new_instances(&desc::RESOLVE, &prim_vao),
             svg_filter_vao: device.create_vao_with_new_instances(&desc::SVG_FILTER, &prim_vao),
             composite_vao: device.create_vao_with_new_instances(&desc::COMPOSITE, &prim_vao),
@@ -1059,10 +1210,13 @@ impl RendererVAOs {
         device.delete_vao(self.clip_rect_vao);
         device.delete_vao(self.clip_box_shadow_vao);
         device.delete_vao(self.clip_image_vao);
+        device.delete_vao(self.clip_path_vao);
         device.delete_vao(self.fast_linear_gradient_vao);
         device.delete_vao(self.linear_gradient_vao);
         device.delete_vao(self.radial_gradient_vao);
         device.delete_vao(self.conic_gradient_vao);
+        device.delete_vao(self.path_vao);
+        device.delete_vao(self.text_mask_vao);
         device.delete_vao(self.blur_vao);
         device.delete_vao(self.line_vao);
         device.delete_vao(self.border_vao);
@@ -1081,6 +1235,7 @@ impl ops::Index<VertexArrayKind> for RendererVAOs {
             VertexArrayKind::ClipImage => &self.clip_image_vao,
             VertexArrayKind::ClipRect => &self.clip_rect_vao,
             VertexArrayKind::ClipBoxShadow => &self.clip_box_shadow_vao,
+            VertexArrayKind::ClipPath => &self.clip_path_vao,
             VertexArrayKind::Blur => &self.blur_vao,
             VertexArrayKind::VectorStencil | VertexArrayKind::VectorCover => unreachable!(),
             VertexArrayKind::Border => &self.border_vao,
@@ -1090,6 +1245,8 @@ impl ops::Index<VertexArrayKind> for RendererVAOs {
             VertexArrayKind::LinearGradient => &self.linear_gradient_vao,
             VertexArrayKind::RadialGradient => &self.radial_gradient_vao,
             VertexArrayKind::ConicGradient => &self.conic_gradient_vao,
+            VertexArrayKind::Path => &self.path_vao,
+            VertexArrayKind::TextMask => &self.text_mask_vao,
             VertexArrayKind::Resolve => &self.resolve_vao,
             VertexArrayKind::SvgFilter => &self.svg_filter_vao,
             VertexArrayKind::Composite => &self.composite_vao,
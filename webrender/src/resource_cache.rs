@@ -6,9 +6,9 @@ use api::{BlobImageResources, BlobImageRequest, RasterizedBlobImage, ImageFormat
 use api::{DebugFlags, FontInstanceKey, FontKey, FontTemplate, GlyphIndex};
 use api::{ExternalImageData, ExternalImageType, ExternalImageId, BlobImageResult, FontInstanceData};
 use api::{DirtyRect, GlyphDimensions, IdNamespace, DEFAULT_TILE_SIZE};
-use api::{ImageData, ImageDescriptor, ImageKey, ImageRendering, TileSize};
-use api::{BlobImageKey, VoidPtrToSizeFn};
-use api::{SharedFontInstanceMap, BaseFontInstance};
+use api::{ImageData, ImageDescriptor, ImageDescriptorFlags, ImageKey, ImageRendering, TileSize};
+use api::{BlobImageKey, SnapshotImageKey, VoidPtrToSizeFn};
+use api::{SharedFontInstanceMap, BaseFontInstance, RgbColorSpace};
 use api::units::*;
 use crate::{render_api::{ClearCache, AddFont, ResourceUpdate, MemoryReport}, util::WeakTable};
 use crate::image_tiling::{compute_tile_size, compute_tile_range};
@@ -29,6 +29,8 @@ use crate::internal_types::{CacheTextureId, FastHashMap, FastHashSet, TextureSou
 use crate::picture::SurfaceInfo;
 use crate::profiler::{self, TransactionProfile, bytes_to_mb};
 use crate::render_backend::{FrameId, FrameStamp};
+use crate::render_target::RenderTargetKind;
+use crate::render_task::{BlitTask, RenderTask, RenderTaskKind, RenderTaskLocation, StaticRenderTaskSurface};
 use crate::render_task_graph::{RenderTaskId, RenderTaskGraphBuilder};
 use crate::render_task_cache::{RenderTaskCache, RenderTaskCacheKey, RenderTaskParent};
 use crate::render_task_cache::{RenderTaskCacheEntry, RenderTaskCacheEntryHandle};
@@ -112,6 +114,9 @@ pub enum CachedImageData {
     /// An image owned by the embedding, and referenced by WebRender. This may
     /// take the form of a texture or a heap-allocated buffer.
     External(ExternalImageData),
+    /// An image rendered by WebRender from a stacking context, directly into
+    /// the texture cache.
+    Snapshot,
 }
 
 impl From<ImageData> for CachedImageData {
@@ -144,6 +149,16 @@ impl CachedImageData {
             },
             CachedImageData::Blob => true,
             CachedImageData::Raw(_) => true,
+            CachedImageData::Snapshot => true,
+        }
+    }
+
+    /// Returns true if this represents a snapshot.
+    #[inline]
+    pub fn is_snapshot(&self) -> bool {
+        match *self {
+            CachedImageData::Snapshot => true,
+            _ => false,
         }
     }
 }
@@ -491,6 +506,10 @@ pub struct ResourceCache {
 
     /// A pool of render targets for use by the render task graph
     render_target_pool: Vec<RenderTarget>,
+
+    /// The tasks rendering snapshot images this frame, which images showing
+    /// the snapshots depend on.
+    snapshot_render_tasks: FastHashMap<ImageKey, RenderTaskId>,
 }
 
 impl ResourceCache {
@@ -525,6 +544,7 @@ impl ResourceCache {
             image_templates_memory: 0,
             font_templates_memory: 0,
             render_target_pool: Vec::new(),
+            snapshot_render_tasks: FastHashMap::default(),
         }
     }
 
@@ -568,6 +588,8 @@ impl ResourceCache {
         let size_check = descriptor.size.width > limit || descriptor.size.height > limit;
         match *data {
             CachedImageData::Raw(_) | CachedImageData::Blob => size_check,
+            // Snapshots are rendered into a single texture cache entry.
+            CachedImageData::Snapshot => false,
             CachedImageData::External(info) => {
                 // External handles already represent existing textures so it does
                 // not make sense to tile them into smaller ones.
@@ -670,6 +692,12 @@ impl ResourceCache {
                 ResourceUpdate::DeleteBlobImage(img) => {
                     self.delete_image_template(img.as_image());
                 }
+                ResourceUpdate::AddSnapshotImage(key) => {
+                    self.add_snapshot_image(key);
+                }
+                ResourceUpdate::DeleteSnapshotImage(key) => {
+                    self.delete_image_template(key.as_image());
+                }
                 ResourceUpdate::DeleteFont(font) => {
                     self.delete_font_template(font);
                     profile.set(profiler::FONT_TEMPLATES, self.resources.font_templates.len());
@@ -748,11 +776,13 @@ impl ResourceCache {
             self.resources.weak_fonts.insert(Arc::downgrade(font));
         }
         self.glyph_rasterizer.add_font(font_key, template.clone());
+        self.cached_glyphs.add_font(font_key, &template);
         self.resources.font_templates.insert(font_key, template);
     }
 
     pub fn delete_font_template(&mut self, font_key: FontKey) {
         self.glyph_rasterizer.delete_font(font_key);
+        self.cached_glyphs.delete_font(font_key);
         if let Some(FontTemplate::Raw(data, _)) = self.resources.font_templates.remove(&font_key) {
             self.font_templates_memory -= data.len();
         }
@@ -797,6 +827,124 @@ impl ResourceCache {
         self.resources.image_templates.insert(image_key, resource);
     }
 
+    /// Register a snapshot image. It has no contents until a stacking context
+    /// is first rendered into it.
+    pub fn add_snapshot_image(&mut self, key: SnapshotImageKey) {
+        let descriptor = ImageDescriptor::new(
+            0,
+            0,
+            self.texture_cache.shared_color_expected_format(),
+            ImageDescriptorFlags::empty(),
+        );
+
+        self.add_image_template(
+            key.as_image(),
+            descriptor,
+            CachedImageData::Snapshot,
+            &DeviceIntRect::zero(),
+            None,
+        );
+    }
+
+    /// Copy the output of a render task into a snapshot image. Images that
+    /// are requested after this call in the current frame depend on the
+    /// returned copy task. The render task is rendered in `color_space`.
+    pub fn render_as_image(
+        &mut self,
+        image_key: ImageKey,
+        size: DeviceIntSize,
+        color_space: RgbColorSpace,
+        source: RenderTaskId,
+        rg_builder: &mut RenderTaskGraphBuilder,
+        gpu_cache: &mut GpuCache,
+    ) -> Option<RenderTaskId> {
+        debug_assert_eq!(self.state, State::AddResources);
+
+        let template = match self.resources.image_templates.get_mut(image_key) {
+            Some(template) if template.data.is_snapshot() => template,
+            _ => {
+                warn!("ERROR: Trying to render into a non-snapshot image key");
+                debug!("key={:?}", image_key);
+                return None;
+            }
+        };
+
+        if size.is_empty() {
+            return None;
+        }
+
+        if size.width > self.texture_cache.max_texture_size() ||
+            size.height > self.texture_cache.max_texture_size() {
+            warn!("Dropping snapshot, image:(w:{},h:{}) is too big for hardware!",
+                  size.width, size.height);
+            return None;
+        }
+
+        // Snapshots have a single cache entry.
+        let entry = match *self.cached_images.entry(image_key).or_insert_with(|| {
+            ImageResult::UntiledAuto(CachedImageInfo {
+                texture_cache_handle: TextureCacheHandle::invalid(),
+                dirty_rect: DirtyRect::All,
+                manual_eviction: false,
+            })
+        }) {
+            ImageResult::UntiledAuto(ref mut entry) => entry,
+            _ => {
+                warn!("Dropping snapshot, image {:?} is cached as a tiled image", image_key);
+                return None;
+            }
+        };
+
+        // Images showing the snapshot are invalidated each time it is rendered.
+        template.descriptor.size = size;
+        template.descriptor.color_space = color_space;
+        template.visible_rect = size.into();
+        template.generation = ImageGeneration(template.generation.0 + 1);
+        let descriptor = template.descriptor;
+
+        // Snapshots live in the texture cache until they are deleted.
+        self.texture_cache.request(&entry.texture_cache_handle, gpu_cache);
+        self.texture_cache.update(
+            &mut entry.texture_cache_handle,
+            descriptor,
+            TextureFilter::Linear,
+            None,
+            [0.0; 4],
+            DirtyRect::All,
+            gpu_cache,
+            None,
+            UvRectKind::Rect,
+            Eviction::Manual,
+            TargetShader::Default,
+        );
+        entry.dirty_rect = DirtyRect::empty();
+        entry.manual_eviction = true;
+
+        let (texture_id, uv_rect, _, _, _) =
+            self.texture_cache.get_cache_location(&entry.texture_cache_handle);
+
+        let task_id = rg_builder.add().init(RenderTask::new(
+            RenderTaskLocation::Static {
+                surface: StaticRenderTaskSurface::TextureCache {
+                    texture: texture_id,
+                    target_kind: RenderTargetKind::Color,
+                },
+                rect: uv_rect,
+            },
+            RenderTaskKind::Blit(BlitTask { source }),
+        ));
+        rg_builder.add_dependency(task_id, source);
+
+        self.snapshot_render_tasks.insert(image_key, task_id);
+
+        Some(task_id)
+    }
+
+    /// Return the task rendering a snapshot image this frame, if any.
+    pub fn get_snapshot_render_task(&self, image_key: ImageKey) -> Option<RenderTaskId> {
+        self.snapshot_render_tasks.get(&image_key).cloned()
+    }
+
     pub fn update_image_template(
         &mut self,
         image_key: ImageKey,
@@ -927,6 +1075,21 @@ impl ResourceCache {
             return size;
         }
 
+        // Snapshots are rendered into the texture cache by the render task
+        // graph, and only need to be kept alive here.
+        if template.data.is_snapshot() {
+            return match self.cached_images.try_get(&request.key) {
+                Some(&ImageResult::UntiledAuto(ref entry)) => {
+                    if self.texture_cache.request(&entry.texture_cache_handle, gpu_cache) {
+                        DeviceIntSize::zero()
+                    } else {
+                        size
+                    }
+                }
+                _ => DeviceIntSize::zero(),
+            };
+        }
+
         let side_size =
             template.tiling.map_or(cmp::max(template.descriptor.size.width, template.descriptor.size.height),
                                    |tile_size| tile_size as i32);
@@ -1067,6 +1230,10 @@ impl ResourceCache {
         }
     }
 
+    pub fn can_rasterize_distance_field(&self, font_key: &FontKey) -> bool {
+        self.glyph_rasterizer.can_rasterize_distance_field(font_key)
+    }
+
     pub fn request_glyphs(
         &mut self,
         mut font: FontInstance,
@@ -1163,7 +1330,14 @@ impl ResourceCache {
     #[inline]
     pub fn get_cached_image(&self, request: ImageRequest) -> Result<CacheItem, ()> {
         debug_assert_eq!(self.state, State::QueryResources);
+        if self.cached_images.try_get(&request.key).is_none() {
+            // Snapshots that were never rendered have no cache entry.
+            return Err(());
+        }
         let image_info = self.get_image_info(request)?;
+        if !self.texture_cache.is_allocated(&image_info.texture_cache_handle) {
+            return Err(());
+        }
         Ok(self.get_texture_cache_item(&image_info.texture_cache_handle))
     }
 
@@ -1200,8 +1374,8 @@ impl ResourceCache {
                     // external buffer uses resource_cache.
                     ExternalImageType::Buffer => None,
                 },
-                // raw and blob image are all using resource_cache.
-                CachedImageData::Raw(..) | CachedImageData::Blob => None,
+                // raw, blob and snapshot images are all using resource_cache.
+                CachedImageData::Raw(..) | CachedImageData::Blob | CachedImageData::Snapshot => None,
             };
 
             ImageProperties {
@@ -1224,6 +1398,7 @@ impl ResourceCache {
             &mut self.glyph_rasterizer,
         );
         self.cached_render_tasks.begin_frame(&mut self.texture_cache);
+        self.snapshot_render_tasks.clear();
         self.current_frame_id = stamp.frame_id();
 
         // pop the old frame and push a new one
@@ -1266,6 +1441,9 @@ impl ResourceCache {
                     // Arc, and the external image data is small.
                     updates.push((image_template.data.clone(), None));
                 }
+                CachedImageData::Snapshot => {
+                    unreachable!("Snapshots are not uploaded");
+                }
                 CachedImageData::Blob => {
                     let blob_image = self.rasterized_blob_images.get_mut(&BlobImageKey(request.key)).unwrap();
                     let img = &blob_image[&request.tile.unwrap()];
@@ -1521,6 +1699,12 @@ impl ResourceCache {
         }
     }
 
+    /// Write the glyphs rasterized so far to the persistent glyph cache, if
+    /// there is one.
+    pub fn save_glyph_disk_cache(&mut self) {
+        self.cached_glyphs.save_disk_cache();
+    }
+
     pub fn clear_namespace(&mut self, namespace: IdNamespace) {
         self.clear_images(|k| k.0 == namespace);
 
@@ -1528,6 +1712,7 @@ impl ResourceCache {
 
         for &key in self.resources.font_templates.keys().filter(|key| key.0 == namespace) {
             self.glyph_rasterizer.delete_font(key);
+            self.cached_glyphs.delete_font(key);
         }
         self.resources
             .font_templates
@@ -1567,7 +1752,7 @@ impl ResourceCache {
         for (_, image) in self.resources.image_templates.images.iter() {
             report.images += match image.data {
                 CachedImageData::Raw(ref v) => unsafe { op(v.as_ptr() as *const c_void) },
-                CachedImageData::Blob | CachedImageData::External(..) => 0,
+                CachedImageData::Blob | CachedImageData::External(..) | CachedImageData::Snapshot => 0,
             }
         }
 
@@ -1749,6 +1934,10 @@ struct PlainFontTemplate {
     index: u32,
 }
 
+/// The data path of snapshot image templates in captures.
+#[cfg(any(feature = "capture", feature = "replay"))]
+const SNAPSHOT_CAPTURE_PATH: &str = "snapshot";
+
 #[cfg(any(feature = "capture", feature = "replay"))]
 #[cfg_attr(feature = "capture", derive(Serialize))]
 #[cfg_attr(feature = "replay", derive(Deserialize))]
@@ -1768,6 +1957,73 @@ pub struct PlainResources {
     image_templates: FastHashMap<ImageKey, PlainImageTemplate>,
 }
 
+#[cfg(feature = "replay")]
+impl PlainResources {
+    /// Turns the resources back into the updates that added them, reading the
+    /// font and image files from the capture. External images are skipped.
+    pub fn into_resource_updates(self, config: &CaptureConfig) -> Vec<ResourceUpdate> {
+        use std::{fs, path::Path};
+        use api::FontInstanceOptions;
+        use crate::render_api::{AddFontInstance, AddImage};
+
+        let root = config.resource_root();
+        let mut raw_map = FastHashMap::<String, Arc<Vec<u8>>>::default();
+        let mut read = |path: String| -> Arc<Vec<u8>> {
+            match raw_map.entry(path) {
+                Entry::Occupied(e) => e.get().clone(),
+                Entry::Vacant(e) => {
+                    let file_path = if Path::new(e.key()).is_absolute() {
+                        PathBuf::from(e.key())
+                    } else {
+                        root.join(e.key())
+                    };
+                    let buffer = fs::read(file_path)
+                        .expect(&format!("Unable to open {}", e.key()));
+                    e.insert(Arc::new(buffer)).clone()
+                }
+            }
+        };
+
+        let mut updates = Vec::new();
+        for (key, template) in self.font_templates {
+            updates.push(ResourceUpdate::AddFont(AddFont::Raw(key, read(template.data), template.index)));
+        }
+
+        for (key, instance) in self.font_instances {
+            updates.push(ResourceUpdate::AddFontInstance(AddFontInstance {
+                key,
+                font_key: instance.font_key,
+                glyph_size: instance.size.to_f32_px(),
+                options: Some(FontInstanceOptions {
+                    render_mode: instance.render_mode,
+                    flags: instance.flags,
+                    bg_color: instance.bg_color,
+                    synthetic_italics: instance.synthetic_italics,
+                }),
+                platform_options: instance.platform_options,
+                variations: instance.variations.clone(),
+            }));
+        }
+
+        for (key, template) in self.image_templates {
+            if template.data == SNAPSHOT_CAPTURE_PATH {
+                updates.push(ResourceUpdate::AddSnapshotImage(SnapshotImageKey(key)));
+            } else if config.deserialize_for_resource::<PlainExternalImage, _>(&template.data).is_some() {
+                warn!("Skipping external image {:?}", key);
+            } else {
+                updates.push(ResourceUpdate::AddImage(AddImage {
+                    key,
+                    descriptor: template.descriptor,
+                    data: ImageData::new_shared(read(template.data)),
+                    tiling: template.tiling,
+                }));
+            }
+        }
+
+        updates
+    }
+}
+
 #[cfg(feature = "capture")]
 #[derive(Serialize)]
 pub struct PlainCacheRef<'a> {
@@ -1908,6 +2164,10 @@ impl ResourceCache {
                         .unwrap();
                     other_paths.insert(key, short_path);
                 }
+                CachedImageData::Snapshot => {
+                    // Snapshots are rendered again when the capture is replayed.
+                    other_paths.insert(key, SNAPSHOT_CAPTURE_PATH.to_string());
+                }
                 CachedImageData::External(ref ext) => {
                     let short_path = format!("externals/{}", external_images.len() + 1);
                     other_paths.insert(key, short_path.clone());
@@ -2018,6 +2278,7 @@ impl ResourceCache {
                     self.texture_cache.swizzle_settings(),
                     &TextureCacheConfig::DEFAULT,
                     self.texture_cache.picture_texture_filter(),
+                    self.texture_cache.picture_texture_format(),
                 );
             }
         }
@@ -2062,6 +2323,7 @@ impl ResourceCache {
         let mut external_images = Vec::new();
         for (key, template) in resources.image_templates {
             let data = match config.deserialize_for_resource::<PlainExternalImage, _>(&template.data) {
+                _ if template.data == SNAPSHOT_CAPTURE_PATH => CachedImageData::Snapshot,
                 Some(plain) => {
                     let ext_data = plain.external;
                     external_images.push(plain);
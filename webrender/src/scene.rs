@@ -4,17 +4,20 @@
 
 use api::{BuiltDisplayList, DisplayListWithCache, ColorF, DynamicProperties, Epoch, FontRenderMode};
 use api::{PipelineId, PropertyBinding, PropertyBindingId, PropertyValue, MixBlendMode, StackingContext};
+use api::RgbColorSpace;
 use api::units::*;
 use malloc_size_of::{MallocSizeOf, MallocSizeOfOps};
 use crate::render_api::MemoryReport;
 use crate::composite::CompositorKind;
 use crate::clip::{ClipStore, ClipStoreStats};
+use crate::color_space::convert_color;
 use crate::spatial_tree::SpatialTree;
 use crate::frame_builder::{ChasePrimitive, FrameBuilderConfig};
 use crate::hit_test::{HitTester, HitTestingScene, HitTestingSceneStats};
 use crate::internal_types::FastHashMap;
 use crate::prim_store::{PrimitiveStore, PrimitiveStoreStats, PictureIndex};
 use crate::tile_cache::TileCacheConfig;
+use std::mem;
 use std::sync::Arc;
 
 /// Stores a map of the animated property bindings for the current display list. These
@@ -28,6 +31,11 @@ pub struct SceneProperties {
     color_properties: FastHashMap<PropertyBindingId, ColorF>,
     current_properties: DynamicProperties,
     pending_properties: Option<DynamicProperties>,
+    animated_properties: DynamicProperties,
+    pending_animated_properties: Option<DynamicProperties>,
+    color_spaces: FastHashMap<PropertyBindingId, RgbColorSpace>,
+    output_color_space: RgbColorSpace,
+    color_spaces_changed: bool,
 }
 
 impl SceneProperties {
@@ -38,6 +46,11 @@ impl SceneProperties {
             color_properties: FastHashMap::default(),
             current_properties: DynamicProperties::default(),
             pending_properties: None,
+            animated_properties: DynamicProperties::default(),
+            pending_animated_properties: None,
+            color_spaces: FastHashMap::default(),
+            output_color_space: RgbColorSpace::Srgb,
+            color_spaces_changed: false,
         }
     }
 
@@ -57,6 +70,26 @@ impl SceneProperties {
         self.pending_properties = Some(pending_properties);
     }
 
+    /// Set the values sampled from the animations of this document. They take
+    /// precedence over the values supplied by `set_properties`.
+    pub fn set_animated_properties(&mut self, properties: DynamicProperties) {
+        self.pending_animated_properties = Some(properties);
+    }
+
+    /// Set the color spaces the values of the color bindings are specified
+    /// in, and the color space they are converted to.
+    pub fn set_color_spaces(
+        &mut self,
+        color_spaces: FastHashMap<PropertyBindingId, RgbColorSpace>,
+        output_color_space: RgbColorSpace,
+    ) {
+        if color_spaces != self.color_spaces || output_color_space != self.output_color_space {
+            self.color_spaces = color_spaces;
+            self.output_color_space = output_color_space;
+            self.color_spaces_changed = true;
+        }
+    }
+
     /// Flush any pending updates to the scene properties. Returns
     /// true if the properties have changed since the last flush
     /// was called. This code allows properties to be changed by
@@ -66,31 +99,50 @@ impl SceneProperties {
     /// saving implications, allowing a frame build to be skipped
     /// if the properties haven't changed in many cases.
     pub fn flush_pending_updates(&mut self) -> bool {
-        let mut properties_changed = false;
+        let mut properties_changed = mem::replace(&mut self.color_spaces_changed, false);
 
         if let Some(ref pending_properties) = self.pending_properties {
             if *pending_properties != self.current_properties {
-                self.transform_properties.clear();
-                self.float_properties.clear();
-                self.color_properties.clear();
+                self.current_properties = pending_properties.clone();
+                properties_changed = true;
+            }
+        }
+
+        if let Some(animated_properties) = self.pending_animated_properties.take() {
+            if animated_properties != self.animated_properties {
+                self.animated_properties = animated_properties;
+                properties_changed = true;
+            }
+        }
 
-                for property in &pending_properties.transforms {
+        if properties_changed {
+            self.transform_properties.clear();
+            self.float_properties.clear();
+            self.color_properties.clear();
+
+            // Animated values are inserted last so that they override the
+            // values supplied for the same bindings.
+            for properties in &[&self.current_properties, &self.animated_properties] {
+                for property in &properties.transforms {
                     self.transform_properties
                         .insert(property.key.id, property.value);
                 }
 
-                for property in &pending_properties.floats {
+                for property in &properties.floats {
                     self.float_properties
                         .insert(property.key.id, property.value);
                 }
 
-                for property in &pending_properties.colors {
-                    self.color_properties
-                        .insert(property.key.id, property.value);
+                for property in &properties.colors {
+                    let color_space = self.color_spaces
+                        .get(&property.key.id)
+                        .cloned()
+                        .unwrap_or(RgbColorSpace::Srgb);
+                    self.color_properties.insert(
+                        property.key.id,
+                        convert_color(property.value, color_space, self.output_color_space),
+                    );
                 }
-
-                self.current_properties = pending_properties.clone();
-                properties_changed = true;
             }
         }
 
@@ -276,6 +328,8 @@ pub struct BuiltScene {
     pub hit_testing_scene: Arc<HitTestingScene>,
     pub tile_cache_config: TileCacheConfig,
     pub tile_cache_pictures: Vec<PictureIndex>,
+    /// The color space of the values of each animated color binding.
+    pub color_binding_spaces: FastHashMap<PropertyBindingId, RgbColorSpace>,
 }
 
 impl BuiltScene {
@@ -291,6 +345,7 @@ impl BuiltScene {
             hit_testing_scene: Arc::new(HitTestingScene::new(&HitTestingSceneStats::empty())),
             tile_cache_config: TileCacheConfig::new(0),
             tile_cache_pictures: Vec::new(),
+            color_binding_spaces: FastHashMap::default(),
             config: FrameBuilderConfig {
                 default_font_render_mode: FontRenderMode::Mono,
                 dual_source_blending_is_enabled: true,
@@ -311,6 +366,8 @@ impl BuiltScene {
                 force_invalidation: false,
                 is_software: false,
                 low_quality_pinch_zoom: false,
+                output_color_space: RgbColorSpace::Srgb,
+                hdr: None,
             },
         }
     }
@@ -36,20 +36,22 @@
 //!
 
 use api::{AlphaType, BorderDetails, BorderDisplayItem, BuiltDisplayListIter, PrimitiveFlags};
-use api::{ClipId, ColorF, CommonItemProperties, ComplexClipRegion, ComponentTransferFuncType, RasterSpace};
+use api::{ClipId, ColorF, ColorInterpolationSpace, CommonItemProperties, ComplexClipRegion, ComponentTransferFuncType, RasterSpace};
 use api::{DisplayItem, DisplayItemRef, ExtendMode, ExternalScrollId, FilterData, SharedFontInstanceMap};
-use api::{FilterOp, FilterPrimitive, FontInstanceKey, FontSize, GlyphInstance, GlyphOptions, GradientStop};
+use api::{FilterOp, FilterPrimitive, FilterPrimitiveKind, FontInstanceKey, FontSize, GlyphInstance, GlyphOptions, GradientStop};
 use api::{IframeDisplayItem, ImageKey, ImageRendering, ItemRange, ColorDepth, QualitySettings};
 use api::{LineOrientation, LineStyle, NinePatchBorderSource, PipelineId, MixBlendMode, StackingContextFlags};
-use api::{PropertyBinding, ReferenceFrameKind, ScrollFrameDisplayItem, ScrollSensitivity};
-use api::{Shadow, SpaceAndClipInfo, SpatialId, StickyFrameDisplayItem, ImageMask, ItemTag};
+use api::{OverscrollBehavior, PropertyBinding, PropertyBindingId, ReferenceFrameKind, ScrollFrameDisplayItem, ScrollSensitivity};
+use api::{ScrollSnapArea, Shadow, SpaceAndClipInfo, SpatialId, SnapshotImageKey, StickyFrameDisplayItem, ImageMask, ItemTag};
 use api::{ClipMode, PrimitiveKeyKind, TransformStyle, YuvColorSpace, ColorRange, YuvData, TempFilterData};
-use api::{ReferenceTransformBinding, Rotation, FillRule};
+use api::{TextPaintOrder, YuvTransferFunction};
+use api::{ReferenceTransformBinding, Rotation, FillRule, PathBrush, PathCommand, PathStroke, RgbColorSpace};
 use api::units::*;
 use crate::image_tiling::simplify_repeated_primitive;
 use crate::clip::{ClipChainId, ClipItemKey, ClipStore, ClipItemKeyKind};
 use crate::clip::{ClipInternData, ClipNodeKind, ClipInstance, SceneClipInstance};
-use crate::clip::{PolygonDataHandle};
+use crate::clip::{ClipGlyphKey, ClipPathKey, ClipTextKey, PolygonDataHandle};
+use crate::color_space::convert_color;
 use crate::spatial_tree::{ROOT_SPATIAL_NODE_INDEX, SpatialTree, SpatialNodeIndex, StaticCoordinateSystemId};
 use crate::frame_builder::{ChasePrimitive, FrameBuilderConfig};
 use crate::glyph_rasterizer::FontInstance;
@@ -71,6 +73,7 @@ use crate::prim_store::gradient::{
 };
 use crate::prim_store::image::{Image, YuvImage};
 use crate::prim_store::line_dec::{LineDecoration, LineDecorationCacheKey, get_line_decoration_size};
+use crate::prim_store::path::{PathBrushKey, PathCommandKey, PathPrim};
 use crate::prim_store::picture::{Picture, PictureCompositeKey, PictureKey};
 use crate::prim_store::text_run::TextRun;
 use crate::render_backend::SceneView;
@@ -510,9 +513,18 @@ pub struct SceneBuilder<'a> {
     /// edge cases (e.g. SVG filter) where we can accept slightly incorrect
     /// behaviour in favour of getting the common case right.
     snap_to_device: SpaceSnapper,
+
+    /// The color space of the values of each animated color binding.
+    color_binding_spaces: FastHashMap<PropertyBindingId, RgbColorSpace>,
 }
 
 impl<'a> SceneBuilder<'a> {
+    /// Convert a color of an item without a color space, which is in sRGB,
+    /// to the output color space.
+    pub fn output_color(&self, color: ColorF) -> ColorF {
+        convert_color(color, RgbColorSpace::Srgb, self.config.output_color_space)
+    }
+
     pub fn build(
         scene: &Scene,
         font_instances: SharedFontInstanceMap,
@@ -529,7 +541,8 @@ impl<'a> SceneBuilder<'a> {
 
         let background_color = root_pipeline
             .background_color
-            .and_then(|color| if color.a > 0.0 { Some(color) } else { None });
+            .and_then(|color| if color.a > 0.0 { Some(color) } else { None })
+            .map(|color| convert_color(color, RgbColorSpace::Srgb, frame_builder_config.output_color_space));
 
         let spatial_tree = SpatialTree::new();
 
@@ -560,6 +573,7 @@ impl<'a> SceneBuilder<'a> {
             quality_settings: view.quality_settings,
             tile_cache_builder: TileCacheBuilder::new(),
             snap_to_device,
+            color_binding_spaces: FastHashMap::default(),
         };
 
         builder.build_all(&root_pipeline);
@@ -584,6 +598,7 @@ impl<'a> SceneBuilder<'a> {
             config: builder.config,
             tile_cache_config,
             tile_cache_pictures,
+            color_binding_spaces: builder.color_binding_spaces,
         }
     }
 
@@ -659,9 +674,9 @@ impl<'a> SceneBuilder<'a> {
                         }
 
                         let composition_operations = CompositeOps::new(
-                            filter_ops_for_compositing(item.filters()),
+                            filter_ops_for_compositing(item.filters(), self.config.output_color_space),
                             filter_datas_for_compositing(item.filter_datas()),
-                            filter_primitives_for_compositing(item.filter_primitives()),
+                            filter_primitives_for_compositing(item.filter_primitives(), self.config.output_color_space),
                             info.stacking_context.mix_blend_mode_for_compositing(),
                         );
 
@@ -673,6 +688,7 @@ impl<'a> SceneBuilder<'a> {
                             info.stacking_context.clip_id,
                             info.stacking_context.raster_space,
                             info.stacking_context.flags,
+                            info.stacking_context.snapshot,
                             bc.pipeline_id,
                         );
 
@@ -881,6 +897,7 @@ impl<'a> SceneBuilder<'a> {
     fn build_scroll_frame(
         &mut self,
         info: &ScrollFrameDisplayItem,
+        snap_areas: ItemRange<ScrollSnapArea>,
         parent_node_index: SpatialNodeIndex,
         pipeline_id: PipelineId,
     ) {
@@ -900,7 +917,7 @@ impl<'a> SceneBuilder<'a> {
             &clip_rect,
         );
 
-        self.add_scroll_frame(
+        let node_index = self.add_scroll_frame(
             info.scroll_frame_id,
             parent_node_index,
             info.external_id,
@@ -910,7 +927,19 @@ impl<'a> SceneBuilder<'a> {
             info.scroll_sensitivity,
             ScrollFrameKind::Explicit,
             info.external_scroll_offset,
+            info.overscroll_behavior,
         );
+
+        if !snap_areas.is_empty() {
+            self.spatial_tree.set_scroll_snap_areas(
+                node_index,
+                info.snap_strictness,
+                snap_areas.iter().map(|area| ScrollSnapArea {
+                    rect: area.rect.translate(current_offset),
+                    ..area
+                }),
+            );
+        }
     }
 
     fn push_iframe(
@@ -974,6 +1003,7 @@ impl<'a> SceneBuilder<'a> {
                 is_root_pipeline,
             },
             LayoutVector2D::zero(),
+            OverscrollBehavior::None,
         );
 
         Some((bounds.size(), pipeline.display_list.iter()))
@@ -1076,7 +1106,7 @@ impl<'a> SceneBuilder<'a> {
                     info.image_key,
                     info.image_rendering,
                     info.alpha_type,
-                    info.color,
+                    self.output_color(info.color),
                 );
             }
             DisplayItem::RepeatingImage(ref info) => {
@@ -1102,7 +1132,7 @@ impl<'a> SceneBuilder<'a> {
                     info.image_key,
                     info.image_rendering,
                     info.alpha_type,
-                    info.color,
+                    self.output_color(info.color),
                 );
             }
             DisplayItem::YuvImage(ref info) => {
@@ -1121,6 +1151,7 @@ impl<'a> SceneBuilder<'a> {
                     info.color_depth,
                     info.color_space,
                     info.color_range,
+                    info.transfer_function,
                     info.image_rendering,
                 );
             }
@@ -1138,14 +1169,22 @@ impl<'a> SceneBuilder<'a> {
                     &info.bounds,
                 );
 
+                let color = self.output_color(info.color);
+                let glyph_options = info.glyph_options.map(|mut options| {
+                    if let Some(ref mut stroke) = options.stroke {
+                        stroke.color = self.output_color(stroke.color);
+                    }
+                    options
+                });
+
                 self.add_text(
                     spatial_node_index,
                     clip_chain_id,
                     &layout,
                     &info.font_key,
-                    &info.color,
+                    &color,
                     item.glyphs(),
-                    info.glyph_options,
+                    glyph_options,
                 );
             }
             DisplayItem::Rectangle(ref info) => {
@@ -1156,13 +1195,21 @@ impl<'a> SceneBuilder<'a> {
                     &info.bounds,
                 );
 
+                if let PropertyBinding::Binding(key, _) = info.color {
+                    self.color_binding_spaces.insert(key.id, info.color_space);
+                }
+
                 self.add_primitive(
                     spatial_node_index,
                     clip_chain_id,
                     &layout,
                     Vec::new(),
                     PrimitiveKeyKind::Rectangle {
-                        color: info.color.into(),
+                        color: convert_color_binding(
+                            info.color,
+                            info.color_space,
+                            self.config.output_color_space,
+                        ).into(),
                     },
                 );
             }
@@ -1214,7 +1261,7 @@ impl<'a> SceneBuilder<'a> {
                     &layout,
                     info.wavy_line_thickness,
                     info.orientation,
-                    info.color,
+                    self.output_color(info.color),
                     info.style,
                 );
             }
@@ -1236,7 +1283,11 @@ impl<'a> SceneBuilder<'a> {
                     info.tile_size,
                 );
 
-                let mut stops = read_gradient_stops(item.gradient_stops());
+                let mut stops = read_gradient_stops(
+                    item.gradient_stops(),
+                    info.gradient.color_space,
+                    self.config.output_color_space,
+                );
                 let mut start = info.gradient.start_point;
                 let mut end = info.gradient.end_point;
                 let flags = layout.flags;
@@ -1258,6 +1309,7 @@ impl<'a> SceneBuilder<'a> {
                             end,
                             stops.to_vec(),
                             ExtendMode::Clamp,
+                            info.gradient.interpolation_space,
                             rect.size(),
                             LayoutSize::zero(),
                             None,
@@ -1280,6 +1332,7 @@ impl<'a> SceneBuilder<'a> {
                         end,
                         stops,
                         info.gradient.extend_mode,
+                        info.gradient.interpolation_space,
                         tile_size,
                         info.tile_spacing,
                         None,
@@ -1308,7 +1361,11 @@ impl<'a> SceneBuilder<'a> {
 
                 let mut center = info.gradient.center;
 
-                let stops = read_gradient_stops(item.gradient_stops());
+                let stops = read_gradient_stops(
+                    item.gradient_stops(),
+                    info.gradient.color_space,
+                    self.config.output_color_space,
+                );
 
                 let mut tile_size = process_repeat_size(
                     &layout.rect,
@@ -1358,6 +1415,7 @@ impl<'a> SceneBuilder<'a> {
                         info.gradient.radius.width / info.gradient.radius.height,
                         stops,
                         info.gradient.extend_mode,
+                        info.gradient.interpolation_space,
                         tile_size,
                         tile_spacing,
                         None,
@@ -1405,8 +1463,13 @@ impl<'a> SceneBuilder<'a> {
                         info.gradient.angle,
                         info.gradient.start_offset,
                         info.gradient.end_offset,
-                        item.gradient_stops(),
+                        read_gradient_stops(
+                            item.gradient_stops(),
+                            info.gradient.color_space,
+                            self.config.output_color_space,
+                        ),
                         info.gradient.extend_mode,
+                        info.gradient.interpolation_space,
                         tile_size,
                         info.tile_spacing,
                         None,
@@ -1421,6 +1484,26 @@ impl<'a> SceneBuilder<'a> {
                     );
                 }
             }
+            DisplayItem::Path(ref info) => {
+                profile_scope!("path");
+
+                let (layout, _, spatial_node_index, clip_chain_id) = self.process_common_properties_with_bounds(
+                    &info.common,
+                    &info.bounds,
+                );
+
+                self.add_path(
+                    spatial_node_index,
+                    clip_chain_id,
+                    &layout,
+                    info.bounds.min.to_vector(),
+                    item.path_commands(),
+                    info.fill_rule,
+                    info.stroke,
+                    &info.brush,
+                    item.gradient_stops(),
+                );
+            }
             DisplayItem::BoxShadow(ref info) => {
                 profile_scope!("box_shadow");
 
@@ -1434,7 +1517,7 @@ impl<'a> SceneBuilder<'a> {
                     clip_chain_id,
                     &layout,
                     &info.offset,
-                    info.color,
+                    self.output_color(info.color),
                     info.blur_radius,
                     info.spread_radius,
                     info.border_radius,
@@ -1476,6 +1559,37 @@ impl<'a> SceneBuilder<'a> {
                     item.points(),
                 );
             }
+            DisplayItem::PathClip(ref info) => {
+                profile_scope!("path_clip");
+
+                let parent_space = self.get_space(info.parent_space_and_clip.spatial_id);
+                let current_offset = self.current_offset(parent_space);
+
+                self.add_path_clip_node(
+                    info.id,
+                    &info.parent_space_and_clip,
+                    &info.rect,
+                    current_offset,
+                    item.path_commands(),
+                    info.fill_rule,
+                );
+            }
+            DisplayItem::TextClip(ref info) => {
+                profile_scope!("text_clip");
+
+                let parent_space = self.get_space(info.parent_space_and_clip.spatial_id);
+                let current_offset = self.current_offset(parent_space);
+
+                self.add_text_clip_node(
+                    info.id,
+                    &info.parent_space_and_clip,
+                    &info.rect,
+                    current_offset,
+                    &info.font_key,
+                    item.glyphs(),
+                    info.glyph_options,
+                );
+            }
             DisplayItem::RoundedRectClip(ref info) => {
                 profile_scope!("rounded_clip");
 
@@ -1526,6 +1640,7 @@ impl<'a> SceneBuilder<'a> {
                 let parent_space = self.get_space(info.parent_space_and_clip.spatial_id);
                 self.build_scroll_frame(
                     info,
+                    item.snap_areas(),
                     parent_space,
                     pipeline_id,
                 );
@@ -1547,9 +1662,9 @@ impl<'a> SceneBuilder<'a> {
                     None,
                 );
 
-                let filters = filter_ops_for_compositing(item.filters());
+                let filters = filter_ops_for_compositing(item.filters(), self.config.output_color_space);
                 let filter_datas = filter_datas_for_compositing(item.filter_datas());
-                let filter_primitives = filter_primitives_for_compositing(item.filter_primitives());
+                let filter_primitives = filter_primitives_for_compositing(item.filter_primitives(), self.config.output_color_space);
 
                 self.add_backdrop_filter(
                     spatial_node_index,
@@ -1591,7 +1706,10 @@ impl<'a> SceneBuilder<'a> {
                 );
 
                 self.push_shadow(
-                    info.shadow,
+                    Shadow {
+                        color: self.output_color(info.shadow.color),
+                        ..info.shadow
+                    },
                     spatial_node_index,
                     clip_chain_id,
                     info.should_inflate,
@@ -1858,6 +1976,7 @@ impl<'a> SceneBuilder<'a> {
         clip_id: Option<ClipId>,
         requested_raster_space: RasterSpace,
         flags: StackingContextFlags,
+        snapshot: Option<SnapshotImageKey>,
         pipeline_id: PipelineId,
     ) -> StackingContextInfo {
         profile_scope!("push_stacking_context");
@@ -1948,6 +2067,11 @@ impl<'a> SceneBuilder<'a> {
             }
         }
 
+        // Snapshots are copied out of the surface of the stacking context.
+        if snapshot.is_some() {
+            blit_reason |= BlitReason::SNAPSHOT;
+        }
+
         let is_redundant = FlattenedStackingContext::is_redundant(
             flags,
             &context_3d,
@@ -2028,6 +2152,7 @@ impl<'a> SceneBuilder<'a> {
                 is_redundant,
                 is_backdrop_root: flags.contains(StackingContextFlags::IS_BACKDROP_ROOT),
                 flags,
+                snapshot,
             });
         }
 
@@ -2072,7 +2197,10 @@ impl<'a> SceneBuilder<'a> {
         // cache. This means that we get caching and correct scrolling invalidation for
         // root level blend containers. For these cases, the readbacks of the backdrop
         // are handled by doing partial reads of the picture cache tiles during rendering.
+        // Snapshots are copied out of a single render task, so snapshotted blend
+        // containers fall back to a simple surface.
         if stacking_context.flags.contains(StackingContextFlags::IS_BLEND_CONTAINER) &&
+           stacking_context.snapshot.is_none() &&
            self.sc_stack.is_empty() &&
            self.tile_cache_builder.can_add_container_tile_cache() &&
            self.spatial_tree.get_static_coordinate_system_id(stacking_context.spatial_node_index) == StaticCoordinateSystemId::ROOT
@@ -2122,7 +2250,10 @@ impl<'a> SceneBuilder<'a> {
                         stacking_context.prim_flags,
                         stacking_context.prim_list,
                         stacking_context.spatial_node_index,
-                        PictureOptions::default(),
+                        PictureOptions {
+                            snapshot: stacking_context.snapshot,
+                            ..PictureOptions::default()
+                        },
                     ))
                 );
 
@@ -2161,7 +2292,10 @@ impl<'a> SceneBuilder<'a> {
                             stacking_context.prim_flags,
                             stacking_context.prim_list,
                             stacking_context.spatial_node_index,
-                            PictureOptions::default(),
+                            PictureOptions {
+                                snapshot: stacking_context.snapshot,
+                                ..PictureOptions::default()
+                            },
                         ))
                     );
 
@@ -2400,6 +2534,7 @@ impl<'a> SceneBuilder<'a> {
                 is_root_pipeline: true,
             },
             LayoutVector2D::zero(),
+            OverscrollBehavior::None,
         );
     }
 
@@ -2456,6 +2591,157 @@ impl<'a> SceneBuilder<'a> {
         );
     }
 
+    fn add_path_clip_node(
+        &mut self,
+        new_node_id: ClipId,
+        space_and_clip: &SpaceAndClipInfo,
+        rect: &LayoutRect,
+        current_offset: LayoutVector2D,
+        commands: ItemRange<PathCommand>,
+        fill_rule: FillRule,
+    ) {
+        let spatial_node_index = self.id_to_index_mapper.get_spatial_node_index(space_and_clip.spatial_id);
+
+        let snapped_rect = self.snap_rect(
+            &rect.translate(current_offset),
+            spatial_node_index,
+        );
+
+        // The path commands are made relative to the origin of the clip rect,
+        // so that translated copies of a clip share the same interned path.
+        let origin = rect.min.to_vector();
+        let path = ClipPathKey {
+            commands: commands.iter().map(|command| PathCommandKey::new(command, origin)).collect(),
+            fill_rule,
+        };
+
+        let path_handle = self
+            .interners
+            .clip_path
+            .intern(&path, || (&path).into());
+
+        let item = ClipItemKey {
+            kind: ClipItemKeyKind::path(snapped_rect, path_handle),
+        };
+
+        let handle = self
+            .interners
+            .clip
+            .intern(&item, || {
+                ClipInternData {
+                    clip_node_kind: ClipNodeKind::Complex,
+                }
+            });
+
+        let instance = SceneClipInstance {
+            key: item,
+            clip: ClipInstance::new(handle, spatial_node_index),
+        };
+
+        self.clip_store.register_clip_template(
+            new_node_id,
+            space_and_clip.clip_id,
+            &[instance],
+        );
+    }
+
+    fn add_text_clip_node(
+        &mut self,
+        new_node_id: ClipId,
+        space_and_clip: &SpaceAndClipInfo,
+        rect: &LayoutRect,
+        current_offset: LayoutVector2D,
+        font_instance_key: &FontInstanceKey,
+        glyph_range: ItemRange<GlyphInstance>,
+        glyph_options: Option<GlyphOptions>,
+    ) {
+        let font = {
+            let instance_map = self.font_instances.lock().unwrap();
+            match instance_map.get(font_instance_key) {
+                Some(font_instance) if font_instance.size > FontSize::zero() => {
+                    let mut render_mode = self.config
+                        .default_font_render_mode
+                        .limit_by(font_instance.render_mode);
+                    let mut flags = font_instance.flags;
+                    if let Some(options) = glyph_options {
+                        render_mode = render_mode.limit_by(options.render_mode);
+                        flags |= options.flags;
+                    }
+
+                    Some(FontInstance::new(
+                        Arc::clone(font_instance),
+                        ColorF::WHITE.into(),
+                        render_mode,
+                        flags,
+                    ))
+                }
+                _ => None,
+            }
+        };
+
+        // Without a usable font there is no coverage, so the clip still has
+        // to be registered, but clips everything out.
+        let font = match font {
+            Some(font) => font,
+            None => {
+                warn!("Unknown or empty font instance for text clip");
+                debug!("key={:?}", font_instance_key);
+                self.add_rect_clip_node(new_node_id, space_and_clip, &LayoutRect::zero());
+                return;
+            }
+        };
+
+        let spatial_node_index = self.id_to_index_mapper.get_spatial_node_index(space_and_clip.spatial_id);
+
+        let snapped_rect = self.snap_rect(
+            &rect.translate(current_offset),
+            spatial_node_index,
+        );
+
+        // Like path clips, the glyphs are made relative to the origin of the
+        // clip rect, so that translated copies of a clip share the same text.
+        let origin = rect.min.to_vector();
+        let text = ClipTextKey {
+            font,
+            glyphs: glyph_range
+                .iter()
+                .map(|glyph| ClipGlyphKey {
+                    index: glyph.index,
+                    point: (glyph.point - origin).into(),
+                })
+                .collect(),
+        };
+
+        let text_handle = self
+            .interners
+            .clip_text
+            .intern(&text, || text.clone().into());
+
+        let item = ClipItemKey {
+            kind: ClipItemKeyKind::text(snapped_rect, text_handle),
+        };
+
+        let handle = self
+            .interners
+            .clip
+            .intern(&item, || {
+                ClipInternData {
+                    clip_node_kind: ClipNodeKind::Complex,
+                }
+            });
+
+        let instance = SceneClipInstance {
+            key: item,
+            clip: ClipInstance::new(handle, spatial_node_index),
+        };
+
+        self.clip_store.register_clip_template(
+            new_node_id,
+            space_and_clip.clip_id,
+            &[instance],
+        );
+    }
+
     /// Add a new rectangle clip, positioned by the spatial node in the `space_and_clip`.
     pub fn add_rect_clip_node(
         &mut self,
@@ -2547,6 +2833,7 @@ impl<'a> SceneBuilder<'a> {
         scroll_sensitivity: ScrollSensitivity,
         frame_kind: ScrollFrameKind,
         external_scroll_offset: LayoutVector2D,
+        overscroll_behavior: OverscrollBehavior,
     ) -> SpatialNodeIndex {
         let node_index = self.spatial_tree.add_scroll_frame(
             parent_node_index,
@@ -2557,6 +2844,7 @@ impl<'a> SceneBuilder<'a> {
             scroll_sensitivity,
             frame_kind,
             external_scroll_offset,
+            overscroll_behavior,
         );
         self.id_to_index_mapper.add_spatial_node(new_node_id, node_index);
         node_index
@@ -2650,6 +2938,13 @@ impl<'a> SceneBuilder<'a> {
                                     blur_is_noop,
                                 )
                             }
+                            ShadowItem::Path(ref pending_path) => {
+                                self.create_shadow_prim(
+                                    &pending_shadow,
+                                    pending_path,
+                                    blur_is_noop,
+                                )
+                            }
                             _ => {
                                 continue;
                             }
@@ -2757,6 +3052,11 @@ impl<'a> SceneBuilder<'a> {
                         pending_text_run,
                     )
                 },
+                ShadowItem::Path(pending_path) => {
+                    self.add_shadow_prim_to_draw_list(
+                        pending_path,
+                    )
+                },
             }
         }
 
@@ -2933,6 +3233,53 @@ impl<'a> SceneBuilder<'a> {
         );
     }
 
+    pub fn add_path(
+        &mut self,
+        spatial_node_index: SpatialNodeIndex,
+        clip_chain_id: ClipChainId,
+        info: &LayoutPrimitiveInfo,
+        origin: LayoutVector2D,
+        commands: ItemRange<PathCommand>,
+        fill_rule: FillRule,
+        stroke: Option<PathStroke>,
+        brush: &PathBrush,
+        gradient_stops: ItemRange<GradientStop>,
+    ) {
+        // Path commands and brush positions are made relative to the
+        // origin of the item, so that translated copies of a path share
+        // the same interned template and render task.
+        let stops = match *brush {
+            PathBrush::Color(..) => Vec::new(),
+            PathBrush::LinearGradient(ref gradient) => {
+                read_gradient_stops(gradient_stops, gradient.color_space, self.config.output_color_space)
+            }
+            PathBrush::RadialGradient(ref gradient) => {
+                read_gradient_stops(gradient_stops, gradient.color_space, self.config.output_color_space)
+            }
+            PathBrush::ConicGradient(ref gradient) => {
+                read_gradient_stops(gradient_stops, gradient.color_space, self.config.output_color_space)
+            }
+        };
+        let brush = match *brush {
+            PathBrush::Color(color) => PathBrushKey::new(&PathBrush::Color(self.output_color(color)), origin),
+            _ => PathBrushKey::new(brush, origin),
+        };
+
+        self.add_primitive(
+            spatial_node_index,
+            clip_chain_id,
+            info,
+            Vec::new(),
+            PathPrim {
+                commands: commands.iter().map(|command| PathCommandKey::new(command, origin)).collect(),
+                fill_rule,
+                stroke: stroke.map(Into::into),
+                brush,
+                stops,
+            },
+        );
+    }
+
     pub fn add_border(
         &mut self,
         spatial_node_index: SpatialNodeIndex,
@@ -2978,8 +3325,13 @@ impl<'a> SceneBuilder<'a> {
                             &info,
                             gradient.start_point,
                             gradient.end_point,
-                            read_gradient_stops(gradient_stops),
+                            read_gradient_stops(
+                                gradient_stops,
+                                gradient.color_space,
+                                self.config.output_color_space,
+                            ),
                             gradient.extend_mode,
+                            gradient.interpolation_space,
                             LayoutSize::new(border.height as f32, border.width as f32),
                             LayoutSize::zero(),
                             Some(Box::new(nine_patch)),
@@ -3003,8 +3355,13 @@ impl<'a> SceneBuilder<'a> {
                             gradient.start_offset * gradient.radius.width,
                             gradient.end_offset * gradient.radius.width,
                             gradient.radius.width / gradient.radius.height,
-                            read_gradient_stops(gradient_stops),
+                            read_gradient_stops(
+                                gradient_stops,
+                                gradient.color_space,
+                                self.config.output_color_space,
+                            ),
                             gradient.extend_mode,
+                            gradient.interpolation_space,
                             LayoutSize::new(border.height as f32, border.width as f32),
                             LayoutSize::zero(),
                             Some(Box::new(nine_patch)),
@@ -3025,8 +3382,13 @@ impl<'a> SceneBuilder<'a> {
                             gradient.angle,
                             gradient.start_offset,
                             gradient.end_offset,
-                            gradient_stops,
+                            read_gradient_stops(
+                                gradient_stops,
+                                gradient.color_space,
+                                self.config.output_color_space,
+                            ),
                             gradient.extend_mode,
+                            gradient.interpolation_space,
                             LayoutSize::new(border.height as f32, border.width as f32),
                             LayoutSize::zero(),
                             Some(Box::new(nine_patch)),
@@ -3061,6 +3423,7 @@ impl<'a> SceneBuilder<'a> {
         end_point: LayoutPoint,
         stops: Vec<GradientStopKey>,
         extend_mode: ExtendMode,
+        interpolation_space: ColorInterpolationSpace,
         stretch_size: LayoutSize,
         mut tile_spacing: LayoutSize,
         nine_patch: Option<Box<NinePatchDescriptor>>,
@@ -3109,6 +3472,7 @@ impl<'a> SceneBuilder<'a> {
 
         Some(LinearGradient {
             extend_mode,
+            interpolation_space,
             start_point: sp.into(),
             end_point: ep.into(),
             stretch_size: stretch_size.into(),
@@ -3129,6 +3493,7 @@ impl<'a> SceneBuilder<'a> {
         ratio_xy: f32,
         stops: Vec<GradientStopKey>,
         extend_mode: ExtendMode,
+        interpolation_space: ColorInterpolationSpace,
         stretch_size: LayoutSize,
         mut tile_spacing: LayoutSize,
         nine_patch: Option<Box<NinePatchDescriptor>>,
@@ -3144,6 +3509,7 @@ impl<'a> SceneBuilder<'a> {
 
         RadialGradient {
             extend_mode,
+            interpolation_space,
             center: center.into(),
             params,
             stretch_size: stretch_size.into(),
@@ -3160,8 +3526,9 @@ impl<'a> SceneBuilder<'a> {
         angle: f32,
         start_offset: f32,
         end_offset: f32,
-        stops: ItemRange<GradientStop>,
+        stops: Vec<GradientStopKey>,
         extend_mode: ExtendMode,
+        interpolation_space: ColorInterpolationSpace,
         stretch_size: LayoutSize,
         mut tile_spacing: LayoutSize,
         nine_patch: Option<Box<NinePatchDescriptor>>,
@@ -3169,15 +3536,9 @@ impl<'a> SceneBuilder<'a> {
         let mut prim_rect = info.rect;
         simplify_repeated_primitive(&stretch_size, &mut tile_spacing, &mut prim_rect);
 
-        let stops = stops.iter().map(|stop| {
-            GradientStopKey {
-                offset: stop.offset,
-                color: stop.color.into(),
-            }
-        }).collect();
-
         ConicGradient {
             extend_mode,
+            interpolation_space,
             center: center.into(),
             params: ConicGradientParams { angle, start_offset, end_offset },
             stretch_size: stretch_size.into(),
@@ -3199,7 +3560,7 @@ impl<'a> SceneBuilder<'a> {
     ) {
         let offset = self.current_offset(spatial_node_index);
 
-        let text_run = {
+        let (text_run, stroke_run) = {
             let instance_map = self.font_instances.lock().unwrap();
             let font_instance = match instance_map.get(font_instance_key) {
                 Some(instance) => instance,
@@ -3239,7 +3600,7 @@ impl<'a> SceneBuilder<'a> {
             //           hash will match and we won't end up creating a new
             //           primitive template.
             let prim_offset = prim_info.rect.min.to_vector() - offset;
-            let glyphs = glyph_range
+            let glyphs: Vec<GlyphInstance> = glyph_range
                 .iter()
                 .map(|glyph| {
                     GlyphInstance {
@@ -3256,21 +3617,66 @@ impl<'a> SceneBuilder<'a> {
                 .cloned()
                 .unwrap();
 
-            TextRun {
+            // Strokes are drawn as a separate run of stroked glyphs. They extend
+            // past the outlines of the glyphs, so the run gets bounds outset by
+            // half of the stroke width.
+            let stroke_run = glyph_options
+                .and_then(|options| options.stroke)
+                .filter(|stroke| stroke.width > 0.0)
+                .map(|stroke| {
+                    let outset = 0.5 * stroke.width;
+                    let mut info = *prim_info;
+                    info.rect = info.rect.inflate(outset, outset);
+                    let glyphs = glyphs
+                        .iter()
+                        .map(|glyph| {
+                            GlyphInstance {
+                                index: glyph.index,
+                                point: glyph.point + LayoutVector2D::new(outset, outset),
+                            }
+                        })
+                        .collect();
+                    let font = FontInstance {
+                        color: stroke.color.into(),
+                        stroke_width: FontSize::from_f32_px(stroke.width / font_instance.size.to_f32_px()),
+                        ..font.clone()
+                    };
+                    let run = TextRun {
+                        glyphs: Arc::new(glyphs),
+                        font,
+                        shadow: false,
+                        requested_raster_space,
+                    };
+                    (info, run, stroke.paint_order)
+                });
+
+            let text_run = TextRun {
                 glyphs: Arc::new(glyphs),
                 font,
                 shadow: false,
                 requested_raster_space,
-            }
+            };
+
+            (text_run, stroke_run)
         };
 
-        self.add_primitive(
-            spatial_node_index,
-            clip_chain_id,
-            prim_info,
-            Vec::new(),
-            text_run,
-        );
+        let mut runs = vec![(*prim_info, text_run)];
+        if let Some((info, run, paint_order)) = stroke_run {
+            match paint_order {
+                TextPaintOrder::FillStroke => runs.push((info, run)),
+                TextPaintOrder::StrokeFill => runs.insert(0, (info, run)),
+            }
+        }
+
+        for (info, run) in runs {
+            self.add_primitive(
+                spatial_node_index,
+                clip_chain_id,
+                &info,
+                Vec::new(),
+                run,
+            );
+        }
     }
 
     pub fn add_image(
@@ -3317,6 +3723,7 @@ impl<'a> SceneBuilder<'a> {
         color_depth: ColorDepth,
         color_space: YuvColorSpace,
         color_range: ColorRange,
+        transfer_function: YuvTransferFunction,
         image_rendering: ImageRendering,
     ) {
         let format = yuv_data.get_format();
@@ -3337,6 +3744,7 @@ impl<'a> SceneBuilder<'a> {
                 format,
                 color_space,
                 color_range,
+                transfer_function,
                 image_rendering,
             },
         );
@@ -3710,6 +4118,9 @@ struct FlattenedStackingContext {
 
     /// Flags identifying the type of container (among other things) this stacking context is
     flags: StackingContextFlags,
+
+    /// The snapshot image the surface of this stacking context is copied into, if any.
+    snapshot: Option<SnapshotImageKey>,
 }
 
 impl FlattenedStackingContext {
@@ -3828,6 +4239,7 @@ pub enum ShadowItem {
     NormalBorder(PendingPrimitive<NormalBorderPrim>),
     Primitive(PendingPrimitive<PrimitiveKeyKind>),
     TextRun(PendingPrimitive<TextRun>),
+    Path(PendingPrimitive<PathPrim>),
 }
 
 impl From<PendingPrimitive<Image>> for ShadowItem {
@@ -3860,6 +4272,12 @@ impl From<PendingPrimitive<TextRun>> for ShadowItem {
     }
 }
 
+impl From<PendingPrimitive<PathPrim>> for ShadowItem {
+    fn from(path: PendingPrimitive<PathPrim>) -> Self {
+        ShadowItem::Path(path)
+    }
+}
+
 fn create_prim_instance(
     pic_index: PictureIndex,
     composite_mode_key: PictureCompositeKey,
@@ -3887,11 +4305,24 @@ fn create_prim_instance(
 
 fn filter_ops_for_compositing(
     input_filters: ItemRange<FilterOp>,
+    output_color_space: RgbColorSpace,
 ) -> Vec<Filter> {
     // TODO(gw): Now that we resolve these later on,
     //           we could probably make it a bit
     //           more efficient than cloning these here.
-    input_filters.iter().map(|filter| filter.into()).collect()
+    input_filters.iter().map(|filter| {
+        let filter = match filter {
+            FilterOp::DropShadow(shadow) => FilterOp::DropShadow(Shadow {
+                color: convert_color(shadow.color, RgbColorSpace::Srgb, output_color_space),
+                ..shadow
+            }),
+            FilterOp::Flood(color) => {
+                FilterOp::Flood(convert_color(color, RgbColorSpace::Srgb, output_color_space))
+            }
+            filter => filter,
+        };
+        filter.into()
+    }).collect()
 }
 
 fn filter_datas_for_compositing(
@@ -3920,12 +4351,31 @@ fn filter_datas_for_compositing(
 
 fn filter_primitives_for_compositing(
     input_filter_primitives: ItemRange<FilterPrimitive>,
+    output_color_space: RgbColorSpace,
 ) -> Vec<FilterPrimitive> {
     // Resolve these in the flattener?
     // TODO(gw): Now that we resolve these later on,
     //           we could probably make it a bit
     //           more efficient than cloning these here.
-    input_filter_primitives.iter().map(|primitive| primitive).collect()
+    let convert = |color| convert_color(color, RgbColorSpace::Srgb, output_color_space);
+    input_filter_primitives.iter().map(|mut primitive| {
+        match primitive.kind {
+            FilterPrimitiveKind::Flood(ref mut flood) => {
+                flood.color = convert(flood.color);
+            }
+            FilterPrimitiveKind::DropShadow(ref mut drop_shadow) => {
+                drop_shadow.shadow.color = convert(drop_shadow.shadow.color);
+            }
+            FilterPrimitiveKind::DiffuseLighting(ref mut lighting) => {
+                lighting.lighting_color = convert(lighting.lighting_color);
+            }
+            FilterPrimitiveKind::SpecularLighting(ref mut lighting) => {
+                lighting.lighting_color = convert(lighting.lighting_color);
+            }
+            _ => {}
+        }
+        primitive
+    }).collect()
 }
 
 fn process_repeat_size(
@@ -3955,11 +4405,35 @@ fn process_repeat_size(
     )
 }
 
-fn read_gradient_stops(stops: ItemRange<GradientStop>) -> Vec<GradientStopKey> {
+/// Read the gradient stops of an item, converting their colors from the
+/// color space of the gradient to the output color space.
+fn read_gradient_stops(
+    stops: ItemRange<GradientStop>,
+    color_space: RgbColorSpace,
+    output_color_space: RgbColorSpace,
+) -> Vec<GradientStopKey> {
     stops.iter().map(|stop| {
         GradientStopKey {
             offset: stop.offset,
-            color: stop.color.into(),
+            color: convert_color(stop.color, color_space, output_color_space).into(),
         }
     }).collect()
 }
+
+/// Convert a rectangle color to the output color space. Only the default value
+/// of a binding is converted here, the animated values are converted by
+/// `SceneProperties` using the color space recorded for the binding.
+fn convert_color_binding(
+    color: PropertyBinding<ColorF>,
+    color_space: RgbColorSpace,
+    output_color_space: RgbColorSpace,
+) -> PropertyBinding<ColorF> {
+    match color {
+        PropertyBinding::Value(value) => {
+            PropertyBinding::Value(convert_color(value, color_space, output_color_space))
+        }
+        PropertyBinding::Binding(key, value) => {
+            PropertyBinding::Binding(key, convert_color(value, color_space, output_color_space))
+        }
+    }
+}
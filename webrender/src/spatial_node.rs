@@ -4,8 +4,10 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use api::{ExternalScrollId, PipelineId, PropertyBinding, PropertyBindingId, ReferenceFrameKind, ScrollClamping, ScrollLocation};
-use api::{TransformStyle, ScrollSensitivity, StickyOffsetBounds};
+use api::{OverscrollBehavior, TransformStyle, ScrollSensitivity, StickyOffsetBounds, TimingFunction};
+use api::{ScrollSnapAlignment, ScrollSnapArea, ScrollSnapStrictness, ScrollSnapTarget};
 use api::units::*;
+use crate::animation::{apply_timing_function, spring};
 use crate::spatial_tree::{CoordinateSystem, SpatialNodeIndex, TransformUpdateState};
 use crate::spatial_tree::{CoordinateSystemId, StaticCoordinateSystemId};
 use euclid::{Vector2D, SideOffsets2D};
@@ -160,6 +162,7 @@ impl SpatialNode {
         scroll_sensitivity: ScrollSensitivity,
         frame_kind: ScrollFrameKind,
         external_scroll_offset: LayoutVector2D,
+        overscroll_behavior: OverscrollBehavior,
         static_coordinate_system_id: StaticCoordinateSystemId,
     ) -> Self {
         let node_type = SpatialNodeType::ScrollFrame(ScrollFrameInfo::new(
@@ -172,6 +175,7 @@ impl SpatialNode {
                 external_id,
                 frame_kind,
                 external_scroll_offset,
+                overscroll_behavior,
             )
         );
 
@@ -246,35 +250,170 @@ impl SpatialNode {
             }
         };
 
-        let normalized_offset = match clamp {
-            ScrollClamping::ToContentBounds => {
-                let scrollable_size = scrolling.scrollable_size;
-                let scrollable_width = scrollable_size.width;
-                let scrollable_height = scrollable_size.height;
+        let old_offset = scrolling.offset;
 
-                if scrollable_height <= 0. && scrollable_width <= 0. {
+        match clamp {
+            ScrollClamping::ToContentBounds => {
+                if scrolling.scrollable_size.width <= 0. && scrolling.scrollable_size.height <= 0. {
                     return false;
                 }
 
-                let origin = LayoutPoint::new(origin.x.max(0.0), origin.y.max(0.0));
-                LayoutVector2D::new(
-                    (-origin.x).max(-scrollable_width).min(0.0),
-                    (-origin.y).max(-scrollable_height).min(0.0),
-                )
+                let clamped_origin = scrolling.clamp_origin(origin);
+                let excess = *origin - clamped_origin;
+                let overscroll = match scrolling.overscroll_behavior {
+                    OverscrollBehavior::None => LayoutVector2D::zero(),
+                    OverscrollBehavior::RubberBand { .. } => LayoutVector2D::new(
+                        rubber_band(excess.x, scrolling.viewport_rect.width()),
+                        rubber_band(excess.y, scrolling.viewport_rect.height()),
+                    ),
+                    OverscrollBehavior::Glow => excess,
+                };
+                scrolling.set_origin_and_overscroll(clamped_origin, overscroll);
             }
-            ScrollClamping::NoClamping => LayoutPoint::zero() - *origin,
-        };
+            ScrollClamping::NoClamping => {
+                scrolling.set_origin_and_overscroll(*origin, LayoutVector2D::zero());
+            }
+        }
 
-        let new_offset = normalized_offset - scrolling.external_scroll_offset;
+        scrolling.offset != old_offset
+    }
 
-        if new_offset == scrolling.offset {
+    /// How far this node is scrolled past the edges of its scroll range.
+    pub fn overscroll(&self) -> LayoutVector2D {
+        match self.node_type {
+            SpatialNodeType::ScrollFrame(ref scrolling) => scrolling.overscroll,
+            _ => LayoutVector2D::zero(),
+        }
+    }
+
+    /// Bring this node back within its scroll range, once the scroll gesture
+    /// that overscrolled it has ended. Returns true if the scroll offset changed
+    /// or the content started springing back.
+    pub fn release_overscroll(&mut self, now_ns: u64) -> bool {
+        match self.node_type {
+            SpatialNodeType::ScrollFrame(ref mut scrolling) => {
+                let old_offset = scrolling.offset;
+                scrolling.release_overscroll(now_ns);
+                scrolling.offset != old_offset || scrolling.animation.is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// Start animating the scroll origin of this node, replacing any in-flight
+    /// scroll animation.
+    pub fn start_scroll_animation(&mut self, animation: ScrollAnimation) {
+        match self.node_type {
+            SpatialNodeType::ScrollFrame(ref mut scrolling) => {
+                scrolling.animation = Some(animation);
+                scrolling.snap_target = None;
+            }
+            _ => warn!("Tried to scroll a non-scroll node."),
+        }
+    }
+
+    pub fn cancel_scroll_animation(&mut self) {
+        if let SpatialNodeType::ScrollFrame(ref mut scrolling) = self.node_type {
+            scrolling.animation = None;
+            scrolling.snap_target = None;
+        }
+    }
+
+    /// Start moving this node to the snap position closest to where it would
+    /// come to rest if it was flung with the given velocity. `snap_areas` are
+    /// the snap areas of the whole spatial tree. Returns false if the node has
+    /// no snap position in reach.
+    pub fn start_snapping(
+        &mut self,
+        snap_areas: &[ScrollSnapArea],
+        velocity: LayoutVector2D,
+        now_ns: u64,
+    ) -> bool {
+        let scrolling = match self.node_type {
+            SpatialNodeType::ScrollFrame(ref mut scrolling) => scrolling,
+            _ => return false,
+        };
+
+        let range = scrolling.snap_areas;
+        let areas = &snap_areas[range.first as usize .. (range.first + range.count) as usize];
+        if areas.is_empty() {
             return false;
         }
 
-        scrolling.offset = new_offset;
+        let from = scrolling.scroll_origin();
+        let destination = ScrollAnimation::Fling { from, velocity, start_time_ns: now_ns }.destination(from);
+        let (to, target) = match scrolling.snap_origin(&destination, areas) {
+            Some(snap) => snap,
+            None => return false,
+        };
+
+        // Flings settle down over a few time constants, so keep a similar
+        // pace when they snap.
+        let duration = if velocity == LayoutVector2D::zero() {
+            SNAP_DURATION
+        } else {
+            3.0 * FLING_TIME_CONSTANT
+        };
+
+        scrolling.animation = if to != from {
+            Some(ScrollAnimation::Smooth {
+                from,
+                to,
+                start_time_ns: now_ns,
+                duration,
+                easing: TimingFunction::EASE_OUT,
+            })
+        } else {
+            None
+        };
+        scrolling.snap_target = Some(target);
+
         true
     }
 
+    /// Advance the in-flight scroll animation of this node, if any. Returns
+    /// true if the scroll offset changed.
+    pub fn tick_scroll_animation(&mut self, now_ns: u64) -> bool {
+        let animation = match self.node_type {
+            SpatialNodeType::ScrollFrame(ScrollFrameInfo { animation: Some(animation), .. }) => animation,
+            _ => return false,
+        };
+
+        if let ScrollAnimation::SpringBack { from, start_time_ns, stiffness, damping } = animation {
+            let (overscroll, finished) = spring_back(from, start_time_ns, stiffness, damping, now_ns);
+            return match self.node_type {
+                SpatialNodeType::ScrollFrame(ref mut scrolling) => {
+                    let old_offset = scrolling.offset;
+                    scrolling.set_origin_and_overscroll(scrolling.scroll_origin(), overscroll);
+                    if finished {
+                        scrolling.animation = None;
+                    }
+                    scrolling.offset != old_offset
+                }
+                _ => false,
+            };
+        }
+
+        let (origin, mut finished) = animation.sample(now_ns);
+        let mut changed = self.set_scroll_origin(&origin, ScrollClamping::ToContentBounds);
+
+        // Flings stop early once they reach the edges of the scroll range.
+        if let (ScrollAnimation::Fling { velocity, .. }, SpatialNodeType::ScrollFrame(ref scrolling)) =
+            (animation, &self.node_type) {
+            let current = scrolling.scroll_origin();
+            let pinned_x = velocity.x == 0.0 || (current.x - origin.x).abs() > FLING_STOP_DISTANCE;
+            let pinned_y = velocity.y == 0.0 || (current.y - origin.y).abs() > FLING_STOP_DISTANCE;
+            finished |= pinned_x && pinned_y;
+        }
+
+        if finished {
+            // Flings and smooth scrolls that ended past the edges bounce back.
+            changed |= self.release_overscroll(now_ns);
+        }
+
+        changed
+    }
+
     pub fn mark_uninvertible(
         &mut self,
         state: &TransformUpdateState,
@@ -645,6 +784,8 @@ impl SpatialNode {
             _ => return false,
         };
 
+        scrolling.animation = None;
+
         let delta = match scroll_location {
             ScrollLocation::Delta(delta) => delta,
             ScrollLocation::Start => {
@@ -668,23 +809,16 @@ impl SpatialNode {
             }
         };
 
-        let scrollable_width = scrolling.scrollable_size.width;
-        let scrollable_height = scrolling.scrollable_size.height;
-        let original_layer_scroll_offset = scrolling.offset;
-
-        if scrollable_width > 0. {
-            scrolling.offset.x = (scrolling.offset.x + delta.x)
-                .min(0.0)
-                .max(-scrollable_width);
-        }
-
-        if scrollable_height > 0. {
-            scrolling.offset.y = (scrolling.offset.y + delta.y)
-                .min(0.0)
-                .max(-scrollable_height);
-        }
+        // Only scroll along the axes with a scroll range.
+        let delta = LayoutVector2D::new(
+            if scrolling.scrollable_size.width > 0. { delta.x } else { 0.0 },
+            if scrolling.scrollable_size.height > 0. { delta.y } else { 0.0 },
+        );
 
-        scrolling.offset != original_layer_scroll_offset
+        // Continue from the distance already scrolled past the edges, so that
+        // deltas overscroll the same way as setting the scroll origin.
+        let origin = scrolling.scroll_origin() + scrolling.unresisted_overscroll() - delta;
+        self.set_scroll_origin(&origin, ScrollClamping::ToContentBounds)
     }
 
     pub fn scroll_offset(&self) -> LayoutVector2D {
@@ -817,6 +951,33 @@ pub struct ScrollFrameInfo {
     /// display list), `external_scroll_offset` would remain at (0,10) and
     /// `offset` would change to (0,-20).
     pub offset: LayoutVector2D,
+
+    /// The in-flight smooth scroll or fling of this scroll frame, if any.
+    pub animation: Option<ScrollAnimation>,
+
+    /// How this frame reacts to being scrolled past the edges of its scroll range.
+    pub overscroll_behavior: OverscrollBehavior,
+
+    /// How far this frame is scrolled past the edges of its scroll range. For
+    /// rubber band overscroll, this is the resisted distance by which the
+    /// content is moved, and it is included in `offset`.
+    pub overscroll: LayoutVector2D,
+
+    pub snap_strictness: ScrollSnapStrictness,
+
+    /// The snap areas of this frame, in `SpatialTree::snap_areas`.
+    pub snap_areas: ScrollSnapAreaRange,
+
+    /// The snap areas this frame snapped to at the end of the last scroll
+    /// gesture, if any.
+    pub snap_target: Option<ScrollSnapTarget>,
+}
+
+/// A range of snap areas in `SpatialTree::snap_areas`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ScrollSnapAreaRange {
+    pub first: u32,
+    pub count: u32,
 }
 
 /// Manages scrolling offset.
@@ -828,6 +989,7 @@ impl ScrollFrameInfo {
         external_id: ExternalScrollId,
         frame_kind: ScrollFrameKind,
         external_scroll_offset: LayoutVector2D,
+        overscroll_behavior: OverscrollBehavior,
     ) -> ScrollFrameInfo {
         ScrollFrameInfo {
             viewport_rect,
@@ -837,7 +999,156 @@ impl ScrollFrameInfo {
             external_id,
             frame_kind,
             external_scroll_offset,
+            animation: None,
+            overscroll_behavior,
+            overscroll: LayoutVector2D::zero(),
+            snap_strictness: ScrollSnapStrictness::Proximity,
+            snap_areas: ScrollSnapAreaRange::default(),
+            snap_target: None,
+        }
+    }
+
+    /// The current scroll origin, as passed to `SpatialNode::set_scroll_origin`,
+    /// not including overscroll.
+    pub fn scroll_origin(&self) -> LayoutPoint {
+        LayoutPoint::zero() - (self.offset + self.external_scroll_offset) - self.applied_overscroll()
+    }
+
+    /// The part of the overscroll that moves the content.
+    pub fn applied_overscroll(&self) -> LayoutVector2D {
+        match self.overscroll_behavior {
+            OverscrollBehavior::RubberBand { .. } => self.overscroll,
+            OverscrollBehavior::None | OverscrollBehavior::Glow => LayoutVector2D::zero(),
+        }
+    }
+
+    /// The distance scrolled past the edges of the scroll range that results
+    /// in the current overscroll.
+    fn unresisted_overscroll(&self) -> LayoutVector2D {
+        match self.overscroll_behavior {
+            OverscrollBehavior::RubberBand { .. } => LayoutVector2D::new(
+                unresisted_rubber_band(self.overscroll.x, self.viewport_rect.width()),
+                unresisted_rubber_band(self.overscroll.y, self.viewport_rect.height()),
+            ),
+            OverscrollBehavior::None | OverscrollBehavior::Glow => self.overscroll,
+        }
+    }
+
+    /// Clamp a scroll origin to the scroll range.
+    pub fn clamp_origin(&self, origin: &LayoutPoint) -> LayoutPoint {
+        LayoutPoint::new(
+            origin.x.max(0.0).min(self.scrollable_size.width.max(0.0)),
+            origin.y.max(0.0).min(self.scrollable_size.height.max(0.0)),
+        )
+    }
+
+    fn set_origin_and_overscroll(&mut self, origin: LayoutPoint, overscroll: LayoutVector2D) {
+        self.overscroll = overscroll;
+        self.offset = LayoutPoint::zero() - (origin + self.applied_overscroll()) - self.external_scroll_offset;
+    }
+
+    /// Start bringing the content back within the scroll range.
+    fn release_overscroll(&mut self, now_ns: u64) {
+        self.animation = None;
+        if self.overscroll == LayoutVector2D::zero() {
+            return;
+        }
+
+        match self.overscroll_behavior {
+            OverscrollBehavior::RubberBand { stiffness, damping } => {
+                self.animation = Some(ScrollAnimation::SpringBack {
+                    from: self.overscroll,
+                    start_time_ns: now_ns,
+                    stiffness,
+                    damping,
+                });
+            }
+            OverscrollBehavior::None | OverscrollBehavior::Glow => {
+                self.set_origin_and_overscroll(self.scroll_origin(), LayoutVector2D::zero());
+            }
+        }
+    }
+
+    /// Compute the offset corresponding to a scroll origin, or None if the
+    /// origin is clamped and this frame can't scroll.
+    pub fn offset_for_origin(
+        &self,
+        origin: &LayoutPoint,
+        clamp: ScrollClamping,
+    ) -> Option<LayoutVector2D> {
+        let normalized_offset = match clamp {
+            ScrollClamping::ToContentBounds => {
+                let scrollable_width = self.scrollable_size.width;
+                let scrollable_height = self.scrollable_size.height;
+
+                if scrollable_height <= 0. && scrollable_width <= 0. {
+                    return None;
+                }
+
+                LayoutPoint::zero() - self.clamp_origin(origin)
+            }
+            ScrollClamping::NoClamping => LayoutPoint::zero() - *origin,
+        };
+
+        Some(normalized_offset - self.external_scroll_offset)
+    }
+
+    /// The scroll origin that brings `rect`, in the space of the content of
+    /// this frame, into view with the least amount of scrolling. Rects larger
+    /// than the viewport are aligned to its start.
+    pub fn origin_revealing_rect(&self, rect: &LayoutRect) -> LayoutPoint {
+        let origin = self.scroll_origin();
+        let visible_rect = self.viewport_rect.translate(origin.to_vector() + self.external_scroll_offset);
+
+        let reveal = |origin: f32, min: f32, max: f32, visible_min: f32, visible_max: f32| {
+            if min < visible_min || max - min > visible_max - visible_min {
+                origin + min - visible_min
+            } else if max > visible_max {
+                origin + max - visible_max
+            } else {
+                origin
+            }
+        };
+
+        LayoutPoint::new(
+            reveal(origin.x, rect.min.x, rect.max.x, visible_rect.min.x, visible_rect.max.x),
+            reveal(origin.y, rect.min.y, rect.max.y, visible_rect.min.y, visible_rect.max.y),
+        )
+    }
+
+    /// The origin closest to `origin` at which the viewport is aligned with
+    /// one of `areas`, along each axis where there is such an origin, and the
+    /// snap areas it is aligned with. Returns None if no axis snaps.
+    pub fn snap_origin(
+        &self,
+        origin: &LayoutPoint,
+        areas: &[ScrollSnapArea],
+    ) -> Option<(LayoutPoint, ScrollSnapTarget)> {
+        let origin = self.clamp_origin(origin);
+        let viewport_rect = self.viewport_rect.translate(self.external_scroll_offset);
+
+        let (x, target_x) = snap_axis(
+            origin.x,
+            viewport_rect.min.x,
+            viewport_rect.max.x,
+            self.scrollable_size.width,
+            self.snap_strictness,
+            areas.iter().map(|area| (area.align_x, area.rect.min.x, area.rect.max.x)),
+        );
+        let (y, target_y) = snap_axis(
+            origin.y,
+            viewport_rect.min.y,
+            viewport_rect.max.y,
+            self.scrollable_size.height,
+            self.snap_strictness,
+            areas.iter().map(|area| (area.align_y, area.rect.min.y, area.rect.max.y)),
+        );
+
+        if target_x.is_none() && target_y.is_none() {
+            return None;
         }
+
+        Some((LayoutPoint::new(x, y), ScrollSnapTarget { x: target_x, y: target_y }))
     }
 
     pub fn sensitive_to_input_events(&self) -> bool {
@@ -851,7 +1162,7 @@ impl ScrollFrameInfo {
         self,
         old_scroll_info: &ScrollFrameInfo
     ) -> ScrollFrameInfo {
-        ScrollFrameInfo {
+        let mut info = ScrollFrameInfo {
             viewport_rect: self.viewport_rect,
             offset: old_scroll_info.offset,
             scroll_sensitivity: self.scroll_sensitivity,
@@ -859,6 +1170,213 @@ impl ScrollFrameInfo {
             external_id: self.external_id,
             frame_kind: self.frame_kind,
             external_scroll_offset: self.external_scroll_offset,
+            animation: old_scroll_info.animation,
+            overscroll_behavior: old_scroll_info.overscroll_behavior,
+            overscroll: old_scroll_info.overscroll,
+            snap_strictness: self.snap_strictness,
+            snap_areas: self.snap_areas,
+            snap_target: old_scroll_info.snap_target,
+        };
+
+        // Drop the overscroll if the new display list changed how it behaves.
+        if self.overscroll_behavior != old_scroll_info.overscroll_behavior {
+            info.set_origin_and_overscroll(info.scroll_origin(), LayoutVector2D::zero());
+            info.overscroll_behavior = self.overscroll_behavior;
+            if let Some(ScrollAnimation::SpringBack { .. }) = info.animation {
+                info.animation = None;
+            }
+        }
+
+        info
+    }
+}
+
+/// The time constant of the exponential decay of fling velocities, in milliseconds.
+const FLING_TIME_CONSTANT: f32 = 325.0;
+
+/// Flings stop once they get this close to their resting position, in pixels.
+const FLING_STOP_DISTANCE: f32 = 0.5;
+
+/// Rubber band overscroll springs stop once they get this close to the edge
+/// of the scroll range, in pixels.
+const OVERSCROLL_STOP_DISTANCE: f32 = 0.5;
+
+/// How strongly rubber band overscroll is resisted.
+const RUBBER_BAND_COEFFICIENT: f32 = 0.55;
+
+/// The overscroll of a rubber band springing back to the edge of the scroll
+/// range, and whether it has come to rest.
+fn spring_back(
+    from: LayoutVector2D,
+    start_time_ns: u64,
+    stiffness: f32,
+    damping: f32,
+    now_ns: u64,
+) -> (LayoutVector2D, bool) {
+    let overscroll_at = |time_ns: u64| {
+        let elapsed = time_ns.saturating_sub(start_time_ns) as f32 / 1_000_000_000.0;
+        from * (1.0 - spring(1.0, stiffness, damping, 0.0, elapsed))
+    };
+
+    // Oscillating springs cross the edge before they settle, so also check the
+    // overscroll a little later.
+    let overscroll = overscroll_at(now_ns);
+    if overscroll.length() < OVERSCROLL_STOP_DISTANCE &&
+       overscroll_at(now_ns + 50_000_000).length() < OVERSCROLL_STOP_DISTANCE {
+        return (LayoutVector2D::zero(), true);
+    }
+
+    (overscroll, false)
+}
+
+/// The duration of the animation to the snap position at the end of a scroll
+/// gesture without momentum, in milliseconds.
+const SNAP_DURATION: f32 = 250.0;
+
+/// Proximity snapping only snaps to positions within this fraction of the
+/// viewport size from where the scroll frame would rest.
+const SNAP_PROXIMITY: f32 = 0.3;
+
+/// The snap position closest to `origin` along one axis, and the index of the
+/// snap area it aligns with. The viewport extent is given for a zero scroll
+/// origin, and `areas` yields the alignment and extent of each snap area.
+fn snap_axis<I>(
+    origin: f32,
+    viewport_min: f32,
+    viewport_max: f32,
+    scroll_range: f32,
+    strictness: ScrollSnapStrictness,
+    areas: I,
+) -> (f32, Option<usize>)
+where
+    I: Iterator<Item = (ScrollSnapAlignment, f32, f32)>,
+{
+    let threshold = match strictness {
+        ScrollSnapStrictness::Mandatory => f32::INFINITY,
+        ScrollSnapStrictness::Proximity => (viewport_max - viewport_min) * SNAP_PROXIMITY,
+    };
+
+    let mut best: Option<(f32, usize)> = None;
+    for (index, (alignment, min, max)) in areas.enumerate() {
+        let position = match alignment {
+            ScrollSnapAlignment::None => continue,
+            ScrollSnapAlignment::Start => min - viewport_min,
+            ScrollSnapAlignment::Center => (min + max - viewport_min - viewport_max) * 0.5,
+            ScrollSnapAlignment::End => max - viewport_max,
+        };
+        let position = position.max(0.0).min(scroll_range.max(0.0));
+
+        let distance = (position - origin).abs();
+        let is_closer = match best {
+            Some((best_position, _)) => distance < (best_position - origin).abs(),
+            None => distance <= threshold,
+        };
+        if is_closer {
+            best = Some((position, index));
+        }
+    }
+
+    match best {
+        Some((position, index)) => (position, Some(index)),
+        None => (origin, None),
+    }
+}
+
+/// The distance by which the content moves when it is scrolled `distance`
+/// past an edge of the scroll range, for a viewport of the given dimension.
+fn rubber_band(distance: f32, dimension: f32) -> f32 {
+    if dimension <= 0.0 {
+        return 0.0;
+    }
+
+    let resisted = (1.0 - 1.0 / (distance.abs() * RUBBER_BAND_COEFFICIENT / dimension + 1.0)) * dimension;
+    resisted.copysign(distance)
+}
+
+/// The inverse of `rubber_band`: the distance scrolled past an edge that moves
+/// the content by `resisted`.
+fn unresisted_rubber_band(resisted: f32, dimension: f32) -> f32 {
+    if dimension <= 0.0 {
+        return 0.0;
+    }
+
+    // The content never moves by the whole dimension.
+    let ratio = (resisted.abs() / dimension).min(0.999);
+    let distance = ratio / (1.0 - ratio) * dimension / RUBBER_BAND_COEFFICIENT;
+    distance.copysign(resisted)
+}
+
+/// An in-flight scroll animation, advanced by the render backend every time a
+/// frame is generated. Positions are scroll origins, as passed to
+/// `SpatialNode::set_scroll_origin`.
+#[derive(Copy, Clone, Debug)]
+pub enum ScrollAnimation {
+    /// A smooth scroll to a destination.
+    Smooth {
+        from: LayoutPoint,
+        to: LayoutPoint,
+        start_time_ns: u64,
+        /// In milliseconds.
+        duration: f32,
+        easing: TimingFunction,
+    },
+    /// Momentum scrolling, decelerating exponentially from an initial velocity
+    /// in pixels per second.
+    Fling {
+        from: LayoutPoint,
+        velocity: LayoutVector2D,
+        start_time_ns: u64,
+    },
+    /// A rubber band overscroll springing back to the edge of the scroll range.
+    SpringBack {
+        /// The overscroll when the spring was released.
+        from: LayoutVector2D,
+        start_time_ns: u64,
+        stiffness: f32,
+        damping: f32,
+    },
+}
+
+impl ScrollAnimation {
+    /// The origin the animation comes to rest at, ignoring the scroll range,
+    /// given the current scroll origin.
+    pub fn destination(&self, origin: LayoutPoint) -> LayoutPoint {
+        match *self {
+            ScrollAnimation::Smooth { to, .. } => to,
+            ScrollAnimation::Fling { from, velocity, .. } => {
+                from + velocity * (FLING_TIME_CONSTANT / 1000.0)
+            }
+            ScrollAnimation::SpringBack { .. } => origin,
+        }
+    }
+
+    /// The origin at the given time, and whether the animation is finished.
+    fn sample(&self, now_ns: u64) -> (LayoutPoint, bool) {
+        match *self {
+            ScrollAnimation::SpringBack { .. } => {
+                unreachable!("Spring back animations only affect the overscroll");
+            }
+            ScrollAnimation::Smooth { from, to, start_time_ns, duration, ref easing } => {
+                let elapsed = now_ns.saturating_sub(start_time_ns) as f32 / 1_000_000.0;
+                if elapsed >= duration {
+                    return (to, true);
+                }
+
+                let progress = apply_timing_function(easing, elapsed / duration, duration);
+                (from.lerp(to, progress), false)
+            }
+            ScrollAnimation::Fling { from, velocity, start_time_ns } => {
+                let elapsed = now_ns.saturating_sub(start_time_ns) as f32 / 1_000_000.0;
+                let decay = (-elapsed / FLING_TIME_CONSTANT).exp();
+                let total = velocity * (FLING_TIME_CONSTANT / 1000.0);
+                let remaining = total * decay;
+
+                if remaining.length() < FLING_STOP_DISTANCE {
+                    return (from + total, true);
+                }
+
+                (from + total - remaining, false)
+            }
         }
     }
 }
@@ -952,6 +1470,7 @@ fn test_cst_perspective_relative_scroll() {
         ScrollSensitivity::Script,
         ScrollFrameKind::Explicit,
         LayoutVector2D::zero(),
+        OverscrollBehavior::None,
     );
 
     let scroll_frame_2 = cst.add_scroll_frame(
@@ -963,6 +1482,7 @@ fn test_cst_perspective_relative_scroll() {
         ScrollSensitivity::Script,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 50.0),
+        OverscrollBehavior::None,
     );
 
     let ref_frame = cst.add_reference_frame(
@@ -987,3 +1507,57 @@ fn test_cst_perspective_relative_scroll() {
     assert!(scroll_offset.x.approx_eq(&0.0));
     assert!(scroll_offset.y.approx_eq(&0.0));
 }
+
+#[test]
+fn test_delta_scroll_overscroll() {
+    use crate::spatial_tree::SpatialTree;
+
+    let mut cst = SpatialTree::new();
+    let pipeline_id = PipelineId::dummy();
+    let ext_scroll_id = ExternalScrollId(1, pipeline_id);
+
+    let root = cst.add_reference_frame(
+        None,
+        TransformStyle::Flat,
+        PropertyBinding::Value(LayoutTransform::identity()),
+        ReferenceFrameKind::Transform {
+            is_2d_scale_translation: true,
+            should_snap: false,
+        },
+        LayoutVector2D::zero(),
+        pipeline_id,
+    );
+
+    let scroll_frame = cst.add_scroll_frame(
+        root,
+        ext_scroll_id,
+        pipeline_id,
+        &LayoutRect::from_size(LayoutSize::new(100.0, 100.0)),
+        &LayoutSize::new(100.0, 500.0),
+        ScrollSensitivity::ScriptAndInputEvents,
+        ScrollFrameKind::Explicit,
+        LayoutVector2D::zero(),
+        OverscrollBehavior::RUBBER_BAND,
+    );
+    let index = scroll_frame.0 as usize;
+
+    // Deltas past the top of the range overscroll, with increasing resistance.
+    assert!(cst.spatial_nodes[index].scroll(ScrollLocation::Delta(LayoutVector2D::new(0.0, 50.0))));
+    let first = cst.spatial_nodes[index].overscroll();
+    assert!(first.y < 0.0 && first.y > -50.0);
+
+    assert!(cst.spatial_nodes[index].scroll(ScrollLocation::Delta(LayoutVector2D::new(0.0, 50.0))));
+    let second = cst.spatial_nodes[index].overscroll();
+    assert!(second.y < first.y && second.y - first.y > -(first.y.abs()));
+    assert_eq!(cst.spatial_nodes[index].scroll_offset(), -second);
+
+    // Scrolling back reduces the overscroll.
+    assert!(cst.spatial_nodes[index].scroll(ScrollLocation::Delta(LayoutVector2D::new(0.0, -50.0))));
+    assert!((cst.spatial_nodes[index].overscroll().y - first.y).abs() < 0.01);
+
+    // The content springs back once the gesture ends.
+    assert!(cst.end_scroll_gesture(ext_scroll_id, 0));
+    assert!(cst.tick_scroll_animations(2_000_000_000));
+    assert_eq!(cst.spatial_nodes[index].overscroll(), LayoutVector2D::zero());
+    assert_eq!(cst.spatial_nodes[index].scroll_offset(), LayoutVector2D::zero());
+}
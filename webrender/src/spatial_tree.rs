@@ -2,15 +2,17 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{ExternalScrollId, PropertyBinding, ReferenceFrameKind, TransformStyle};
-use api::{PipelineId, ScrollClamping, ScrollNodeState, ScrollSensitivity};
+use api::{ExternalScrollId, OverscrollBehavior, OverscrollEvent, PropertyBinding, ReferenceFrameKind, TransformStyle};
+use api::{PipelineId, ScrollBehavior, ScrollClamping, ScrollNodeState, ScrollSensitivity};
+use api::{ScrollSnapArea, ScrollSnapStrictness};
 use api::units::*;
 use euclid::Transform3D;
 use crate::gpu_types::TransformPalette;
 use crate::internal_types::{FastHashMap, FastHashSet};
 use crate::print_tree::{PrintableTree, PrintTree, PrintTreePrinter};
 use crate::scene::SceneProperties;
-use crate::spatial_node::{ScrollFrameInfo, SpatialNode, SpatialNodeType, StickyFrameInfo, ScrollFrameKind};
+use crate::spatial_node::{ScrollAnimation, ScrollFrameInfo, ScrollSnapAreaRange, SpatialNode, SpatialNodeType};
+use crate::spatial_node::{StickyFrameInfo, ScrollFrameKind};
 use std::{ops, u32};
 use crate::util::{FastTransform, LayoutToWorldFastTransform, MatrixHelpers, ScaleOffset, scale_factors};
 
@@ -121,6 +123,12 @@ pub struct SpatialTree {
 
     pub pending_scroll_offsets: FastHashMap<ExternalScrollId, (LayoutPoint, ScrollClamping)>,
 
+    /// Changes in the overscroll of scroll frames, to be reported to the embedder.
+    overscroll_events: Vec<(ExternalScrollId, OverscrollEvent)>,
+
+    /// The snap areas of all scroll frames, in the space of their items.
+    pub snap_areas: Vec<ScrollSnapArea>,
+
     /// A set of pipelines which should be discarded the next time this
     /// tree is drained.
     pub pipelines_to_discard: FastHashSet<PipelineId>,
@@ -232,6 +240,8 @@ impl SpatialTree {
             spatial_nodes: Vec::new(),
             coord_systems: Vec::new(),
             pending_scroll_offsets: FastHashMap::default(),
+            overscroll_events: Vec::new(),
+            snap_areas: Vec::new(),
             pipelines_to_discard: FastHashSet::default(),
             nodes_to_update: Vec::new(),
             next_static_coord_system_id: 0,
@@ -443,9 +453,16 @@ impl SpatialTree {
         let mut result = vec![];
         for node in &self.spatial_nodes {
             if let SpatialNodeType::ScrollFrame(info) = node.node_type {
+                let animation_target = info.animation.and_then(|animation| {
+                    let destination = animation.destination(info.scroll_origin());
+                    info.offset_for_origin(&destination, ScrollClamping::ToContentBounds)
+                });
                 result.push(ScrollNodeState {
                     id: info.external_id,
-                    scroll_offset: info.offset - info.external_scroll_offset,
+                    scroll_offset: info.offset + info.applied_overscroll() - info.external_scroll_offset,
+                    animation_target: animation_target.map(|offset| offset - info.external_scroll_offset),
+                    overscroll: info.overscroll,
+                    snap_target: info.snap_target,
                 })
             }
         }
@@ -480,7 +497,11 @@ impl SpatialTree {
     ) -> bool {
         for node in &mut self.spatial_nodes {
             if node.matches_external_id(id) {
-                return node.set_scroll_origin(&origin, clamp);
+                let old_overscroll = node.overscroll();
+                node.cancel_scroll_animation();
+                let changed = node.set_scroll_origin(&origin, clamp);
+                push_overscroll_events(&mut self.overscroll_events, id, old_overscroll, node.overscroll());
+                return changed;
             }
         }
 
@@ -488,6 +509,147 @@ impl SpatialTree {
         false
     }
 
+    /// Scroll a node to the given origin, either instantly or by starting a
+    /// smooth scroll animation. Returns true if the node changed position.
+    pub fn scroll_node_to(
+        &mut self,
+        origin: LayoutPoint,
+        id: ExternalScrollId,
+        behavior: ScrollBehavior,
+        now_ns: u64,
+    ) -> bool {
+        let (duration, easing) = match behavior {
+            ScrollBehavior::Smooth { duration, easing } if duration > 0.0 => (duration, easing),
+            _ => return self.scroll_node(origin, id, ScrollClamping::ToContentBounds),
+        };
+
+        let node = match self.spatial_nodes.iter_mut().find(|node| node.matches_external_id(id)) {
+            Some(node) => node,
+            // There is nothing to animate from before the node exists.
+            None => return self.scroll_node(origin, id, ScrollClamping::ToContentBounds),
+        };
+
+        if let SpatialNodeType::ScrollFrame(ref info) = node.node_type {
+            let from = info.scroll_origin();
+            node.start_scroll_animation(ScrollAnimation::Smooth {
+                from,
+                to: origin,
+                start_time_ns: now_ns,
+                duration,
+                easing,
+            });
+        }
+
+        false
+    }
+
+    /// Scroll a node so that `rect`, in the space of its content, becomes
+    /// visible. Returns true if the node changed position.
+    pub fn scroll_node_into_view(
+        &mut self,
+        rect: LayoutRect,
+        id: ExternalScrollId,
+        behavior: ScrollBehavior,
+        now_ns: u64,
+    ) -> bool {
+        let origin = self.spatial_nodes.iter().find_map(|node| match node.node_type {
+            SpatialNodeType::ScrollFrame(ref info) if info.external_id == id => {
+                Some(info.origin_revealing_rect(&rect))
+            }
+            _ => None,
+        });
+
+        match origin {
+            Some(origin) => self.scroll_node_to(origin, id, behavior, now_ns),
+            None => {
+                warn!("Tried to scroll an unknown node {:?} into view.", id);
+                false
+            }
+        }
+    }
+
+    /// Start momentum scrolling a node with the given initial velocity, in
+    /// pixels per second towards the end of the scroll range. Overscrolled
+    /// nodes go back to the edge of their scroll range instead. Returns true
+    /// if the node changed position or started moving.
+    pub fn fling_node(
+        &mut self,
+        velocity: LayoutVector2D,
+        id: ExternalScrollId,
+        now_ns: u64,
+    ) -> bool {
+        for node in &mut self.spatial_nodes {
+            if let SpatialNodeType::ScrollFrame(ref info) = node.node_type {
+                if info.external_id == id {
+                    if info.overscroll != LayoutVector2D::zero() {
+                        break;
+                    }
+
+                    let from = info.scroll_origin();
+                    if !node.start_snapping(&self.snap_areas, velocity, now_ns) {
+                        node.start_scroll_animation(ScrollAnimation::Fling {
+                            from,
+                            velocity,
+                            start_time_ns: now_ns,
+                        });
+                    }
+                    return true;
+                }
+            }
+        }
+
+        self.end_scroll_gesture(id, now_ns)
+    }
+
+    /// Notify that the user stopped scrolling a node, which brings it back
+    /// within its scroll range if it was overscrolled, or moves it to its
+    /// closest snap position otherwise. Returns true if the node changed
+    /// position or started snapping.
+    pub fn end_scroll_gesture(
+        &mut self,
+        id: ExternalScrollId,
+        now_ns: u64,
+    ) -> bool {
+        for node in &mut self.spatial_nodes {
+            if node.matches_external_id(id) {
+                let old_overscroll = node.overscroll();
+                if old_overscroll == LayoutVector2D::zero() {
+                    // Snapping replaces any in-flight animation, which keeps
+                    // running when there is nothing to snap to.
+                    return node.start_snapping(&self.snap_areas, LayoutVector2D::zero(), now_ns);
+                }
+
+                let changed = node.release_overscroll(now_ns);
+                push_overscroll_events(&mut self.overscroll_events, id, old_overscroll, node.overscroll());
+                return changed;
+            }
+        }
+
+        false
+    }
+
+    /// Advance the in-flight scroll animations. Returns true if any node
+    /// changed position.
+    pub fn tick_scroll_animations(&mut self, now_ns: u64) -> bool {
+        let mut changed = false;
+        for node in &mut self.spatial_nodes {
+            let id = match node.node_type {
+                SpatialNodeType::ScrollFrame(ScrollFrameInfo { external_id, animation: Some(..), .. }) => external_id,
+                _ => continue,
+            };
+
+            let old_overscroll = node.overscroll();
+            changed |= node.tick_scroll_animation(now_ns);
+            push_overscroll_events(&mut self.overscroll_events, id, old_overscroll, node.overscroll());
+        }
+        changed
+    }
+
+    /// Take the overscroll changes since the last call.
+    pub fn take_overscroll_events(&mut self) -> Vec<(ExternalScrollId, OverscrollEvent)> {
+        std::mem::replace(&mut self.overscroll_events, Vec::new())
+    }
+
     pub fn update_tree(
         &mut self,
         scene_properties: &SceneProperties,
@@ -578,6 +740,7 @@ impl SpatialTree {
         scroll_sensitivity: ScrollSensitivity,
         frame_kind: ScrollFrameKind,
         external_scroll_offset: LayoutVector2D,
+        overscroll_behavior: OverscrollBehavior,
     ) -> SpatialNodeIndex {
         // Scroll frames are only 2d translations - they can't introduce a new static coord system
         let static_coordinate_system_id = self.get_static_coordinate_system_id(parent_index);
@@ -591,11 +754,37 @@ impl SpatialTree {
             scroll_sensitivity,
             frame_kind,
             external_scroll_offset,
+            overscroll_behavior,
             static_coordinate_system_id,
         );
         self.add_spatial_node(node)
     }
 
+    /// Set the snap areas of a scroll frame, see `ScrollSnapArea`.
+    pub fn set_scroll_snap_areas<I>(
+        &mut self,
+        index: SpatialNodeIndex,
+        strictness: ScrollSnapStrictness,
+        areas: I,
+    )
+    where
+        I: IntoIterator<Item = ScrollSnapArea>,
+    {
+        let first = self.snap_areas.len();
+        self.snap_areas.extend(areas);
+
+        match self.spatial_nodes[index.0 as usize].node_type {
+            SpatialNodeType::ScrollFrame(ref mut info) => {
+                info.snap_strictness = strictness;
+                info.snap_areas = ScrollSnapAreaRange {
+                    first: first as u32,
+                    count: (self.snap_areas.len() - first) as u32,
+                };
+            }
+            _ => warn!("Tried to set the snap areas of a non-scroll node."),
+        }
+    }
+
     pub fn add_reference_frame(
         &mut self,
         parent_index: Option<SpatialNodeIndex>,
@@ -875,6 +1064,27 @@ impl PrintableTree for SpatialTree {
     }
 }
 
+fn push_overscroll_events(
+    events: &mut Vec<(ExternalScrollId, OverscrollEvent)>,
+    id: ExternalScrollId,
+    old_overscroll: LayoutVector2D,
+    new_overscroll: LayoutVector2D,
+) {
+    if old_overscroll == new_overscroll {
+        return;
+    }
+
+    if old_overscroll == LayoutVector2D::zero() {
+        events.push((id, OverscrollEvent::Started));
+    }
+
+    if new_overscroll == LayoutVector2D::zero() {
+        events.push((id, OverscrollEvent::Ended));
+    } else {
+        events.push((id, OverscrollEvent::Changed(new_overscroll)));
+    }
+}
+
 #[cfg(test)]
 fn add_reference_frame(
     cst: &mut SpatialTree,
@@ -1165,6 +1375,7 @@ fn test_find_scroll_root_simple() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     assert_eq!(st.find_scroll_root(scroll), scroll);
@@ -1196,6 +1407,7 @@ fn test_find_scroll_root_sub_scroll_frame() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     let sub_scroll = st.add_scroll_frame(
@@ -1207,6 +1419,7 @@ fn test_find_scroll_root_sub_scroll_frame() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     assert_eq!(st.find_scroll_root(sub_scroll), root_scroll);
@@ -1238,6 +1451,7 @@ fn test_find_scroll_root_not_scrollable() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     let sub_scroll = st.add_scroll_frame(
@@ -1249,6 +1463,7 @@ fn test_find_scroll_root_not_scrollable() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     assert_eq!(st.find_scroll_root(sub_scroll), sub_scroll);
@@ -1280,6 +1495,7 @@ fn test_find_scroll_root_too_small() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     let sub_scroll = st.add_scroll_frame(
@@ -1291,6 +1507,7 @@ fn test_find_scroll_root_too_small() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     assert_eq!(st.find_scroll_root(sub_scroll), sub_scroll);
@@ -1323,6 +1540,7 @@ fn test_find_scroll_root_perspective() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     let perspective = st.add_reference_frame(
@@ -1345,6 +1563,7 @@ fn test_find_scroll_root_perspective() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     assert_eq!(st.find_scroll_root(sub_scroll), root_scroll);
@@ -1377,6 +1596,7 @@ fn test_find_scroll_root_2d_scale() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     let scale = st.add_reference_frame(
@@ -1400,7 +1620,161 @@ fn test_find_scroll_root_2d_scale() {
         ScrollSensitivity::ScriptAndInputEvents,
         ScrollFrameKind::Explicit,
         LayoutVector2D::new(0.0, 0.0),
+        OverscrollBehavior::None,
     );
 
     assert_eq!(st.find_scroll_root(sub_scroll), sub_scroll);
 }
+
+/// Tests that smooth scrolls and scrolls into view are animated to their destination.
+#[test]
+fn test_smooth_scroll() {
+    use api::TimingFunction;
+
+    let mut st = SpatialTree::new();
+    let id = ExternalScrollId(1, PipelineId::dummy());
+
+    let root = add_reference_frame(
+        &mut st,
+        None,
+        LayoutTransform::identity(),
+        LayoutVector2D::zero(),
+    );
+
+    st.add_scroll_frame(
+        root,
+        id,
+        PipelineId::dummy(),
+        &LayoutRect::from_size(LayoutSize::new(400.0, 400.0)),
+        &LayoutSize::new(400.0, 1000.0),
+        ScrollSensitivity::ScriptAndInputEvents,
+        ScrollFrameKind::Explicit,
+        LayoutVector2D::zero(),
+        OverscrollBehavior::None,
+    );
+
+    let behavior = ScrollBehavior::Smooth {
+        duration: 100.0,
+        easing: TimingFunction::Linear,
+    };
+    assert!(!st.scroll_node_to(LayoutPoint::new(0.0, 200.0), id, behavior, 0));
+    assert_eq!(st.get_scroll_node_state()[0].animation_target, Some(LayoutVector2D::new(0.0, -200.0)));
+
+    assert!(st.tick_scroll_animations(50_000_000));
+    assert_eq!(st.get_scroll_node_state()[0].scroll_offset, LayoutVector2D::new(0.0, -100.0));
+
+    assert!(st.tick_scroll_animations(100_000_000));
+    let state = &st.get_scroll_node_state()[0];
+    assert_eq!(state.scroll_offset, LayoutVector2D::new(0.0, -200.0));
+    assert_eq!(state.animation_target, None);
+
+    // The bottom of the rect is aligned with the bottom of the viewport.
+    let rect = LayoutRect::new(LayoutPoint::new(0.0, 700.0), LayoutPoint::new(100.0, 800.0));
+    assert!(st.scroll_node_into_view(rect, id, ScrollBehavior::Instant, 0));
+    assert_eq!(st.get_scroll_node_state()[0].scroll_offset, LayoutVector2D::new(0.0, -400.0));
+
+    // Flings are clamped to the scroll range.
+    st.fling_node(LayoutVector2D::new(0.0, 10000.0), id, 0);
+    assert!(st.tick_scroll_animations(1_000_000_000));
+    let state = &st.get_scroll_node_state()[0];
+    assert_eq!(state.scroll_offset, LayoutVector2D::new(0.0, -600.0));
+    assert_eq!(state.animation_target, None);
+}
+
+#[test]
+fn test_rubber_band_overscroll() {
+    let mut st = SpatialTree::new();
+    let id = ExternalScrollId(1, PipelineId::dummy());
+
+    let root = add_reference_frame(
+        &mut st,
+        None,
+        LayoutTransform::identity(),
+        LayoutVector2D::zero(),
+    );
+
+    st.add_scroll_frame(
+        root,
+        id,
+        PipelineId::dummy(),
+        &LayoutRect::from_size(LayoutSize::new(400.0, 400.0)),
+        &LayoutSize::new(400.0, 1000.0),
+        ScrollSensitivity::ScriptAndInputEvents,
+        ScrollFrameKind::Explicit,
+        LayoutVector2D::zero(),
+        OverscrollBehavior::RUBBER_BAND,
+    );
+
+    // Scrolling past the top of the range moves the content by less than the
+    // scrolled distance.
+    assert!(st.scroll_node(LayoutPoint::new(0.0, -100.0), id, ScrollClamping::ToContentBounds));
+    let state = &st.get_scroll_node_state()[0];
+    assert_eq!(state.scroll_offset, LayoutVector2D::zero());
+    assert!(state.overscroll.y < 0.0 && state.overscroll.y > -100.0);
+    assert_eq!(
+        st.take_overscroll_events(),
+        vec![(id, OverscrollEvent::Started), (id, OverscrollEvent::Changed(state.overscroll))],
+    );
+
+    // The content springs back once the gesture ends.
+    assert!(st.end_scroll_gesture(id, 0));
+    assert!(st.tick_scroll_animations(2_000_000_000));
+    assert_eq!(st.get_scroll_node_state()[0].overscroll, LayoutVector2D::zero());
+    assert_eq!(st.take_overscroll_events(), vec![(id, OverscrollEvent::Ended)]);
+}
+
+#[test]
+fn test_scroll_snapping() {
+    use api::{ScrollSnapAlignment, ScrollSnapTarget};
+
+    let mut st = SpatialTree::new();
+    let id = ExternalScrollId(1, PipelineId::dummy());
+
+    let root = add_reference_frame(
+        &mut st,
+        None,
+        LayoutTransform::identity(),
+        LayoutVector2D::zero(),
+    );
+
+    let scroll = st.add_scroll_frame(
+        root,
+        id,
+        PipelineId::dummy(),
+        &LayoutRect::from_size(LayoutSize::new(400.0, 400.0)),
+        &LayoutSize::new(400.0, 1600.0),
+        ScrollSensitivity::ScriptAndInputEvents,
+        ScrollFrameKind::Explicit,
+        LayoutVector2D::zero(),
+        OverscrollBehavior::None,
+    );
+
+    // Four pages, snapping to their top edge.
+    let pages = (0 .. 4).map(|i| ScrollSnapArea::new(
+        LayoutRect::from_origin_and_size(LayoutPoint::new(0.0, i as f32 * 400.0), LayoutSize::new(400.0, 400.0)),
+        ScrollSnapAlignment::None,
+        ScrollSnapAlignment::Start,
+    ));
+    st.set_scroll_snap_areas(scroll, ScrollSnapStrictness::Mandatory, pages);
+
+    // The frame snaps to the closest page once the gesture ends.
+    st.scroll_node(LayoutPoint::new(0.0, 500.0), id, ScrollClamping::ToContentBounds);
+    assert!(st.end_scroll_gesture(id, 0));
+    let state = &st.get_scroll_node_state()[0];
+    assert_eq!(state.animation_target, Some(LayoutVector2D::new(0.0, -400.0)));
+    assert_eq!(state.snap_target, Some(ScrollSnapTarget { x: None, y: Some(1) }));
+
+    assert!(st.tick_scroll_animations(1_000_000_000));
+    assert_eq!(st.get_scroll_node_state()[0].scroll_offset, LayoutVector2D::new(0.0, -400.0));
+
+    // Flings snap to the page closest to where they would come to rest.
+    assert!(st.fling_node(LayoutVector2D::new(0.0, 1000.0), id, 0));
+    assert!(st.tick_scroll_animations(2_000_000_000));
+    let state = &st.get_scroll_node_state()[0];
+    assert_eq!(state.scroll_offset, LayoutVector2D::new(0.0, -800.0));
+    assert_eq!(state.snap_target, Some(ScrollSnapTarget { x: None, y: Some(2) }));
+
+    // Scrolling again forgets the snap target.
+    st.scroll_node(LayoutPoint::new(0.0, 700.0), id, ScrollClamping::ToContentBounds);
+    assert_eq!(st.get_scroll_node_state()[0].snap_target, None);
+}
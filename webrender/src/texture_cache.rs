@@ -288,11 +288,12 @@ enum BudgetType {
     SharedAlpha8,
     SharedAlpha8Glyphs,
     SharedAlpha16,
+    SharedColor16F,
     Standalone,
 }
 
 impl BudgetType {
-    pub const COUNT: usize = 7;
+    pub const COUNT: usize = 8;
 
     pub const VALUES: [BudgetType; BudgetType::COUNT] = [
         BudgetType::SharedColor8Linear,
@@ -301,6 +302,7 @@ impl BudgetType {
         BudgetType::SharedAlpha8,
         BudgetType::SharedAlpha8Glyphs,
         BudgetType::SharedAlpha16,
+        BudgetType::SharedColor16F,
         BudgetType::Standalone,
     ];
 
@@ -311,6 +313,7 @@ impl BudgetType {
         profiler::ATLAS_ALPHA8_PRESSURE,
         profiler::ATLAS_ALPHA8_GLYPHS_PRESSURE,
         profiler::ATLAS_ALPHA16_PRESSURE,
+        profiler::ATLAS_COLOR16F_PRESSURE,
         profiler::ATLAS_STANDALONE_PRESSURE,
     ];
 
@@ -330,6 +333,7 @@ struct SharedTextures {
     alpha16_linear: AllocatorList<SlabAllocator, TextureParameters>,
     color8_linear: AllocatorList<ShelfAllocator, TextureParameters>,
     color8_glyphs: AllocatorList<ShelfAllocator, TextureParameters>,
+    color16f_linear: AllocatorList<ShelfAllocator, TextureParameters>,
     bytes_per_texture_of_type: [i32 ; BudgetType::COUNT],
 }
 
@@ -442,6 +446,23 @@ impl SharedTextures {
             color_formats.internal.bytes_per_pixel() *
             config.color8_nearest_texture_size * config.color8_nearest_texture_size;
 
+        // Used for half-float (typically HDR or wide gamut) images. Other high
+        // bit depth formats always get standalone textures.
+        let color16f_linear = AllocatorList::new(
+            config.color16f_linear_texture_size,
+            ShelfAllocatorOptions {
+                alignment: size2(16, 16),
+                .. ShelfAllocatorOptions::default()
+            },
+            TextureParameters {
+                formats: TextureFormatPair::from(ImageFormat::RGBA16F),
+                filter: TextureFilter::Linear,
+            },
+        );
+        bytes_per_texture_of_type[BudgetType::SharedColor16F as usize] =
+            ImageFormat::RGBA16F.bytes_per_pixel() *
+            config.color16f_linear_texture_size * config.color16f_linear_texture_size;
+
         Self {
             alpha8_linear,
             alpha8_glyphs,
@@ -449,6 +470,7 @@ impl SharedTextures {
             color8_linear,
             color8_glyphs,
             color8_nearest,
+            color16f_linear,
             bytes_per_texture_of_type,
         }
     }
@@ -465,6 +487,7 @@ impl SharedTextures {
         self.color8_linear.clear(texture_dealloc_cb);
         self.color8_nearest.clear(texture_dealloc_cb);
         self.color8_glyphs.clear(texture_dealloc_cb);
+        self.color16f_linear.clear(texture_dealloc_cb);
     }
 
     /// Returns a mutable borrow for the shared texture array matching the parameters.
@@ -500,6 +523,10 @@ impl SharedTextures {
                     _ => panic!("Unexpected filter {:?}", filter),
                 }
             }
+            ImageFormat::RGBA16F => {
+                assert_eq!(filter, TextureFilter::Linear);
+                (&mut self.color16f_linear, BudgetType::SharedColor16F)
+            }
             _ => panic!("Unexpected format {:?}", external_format),
         }
     }
@@ -533,18 +560,22 @@ struct PictureTextures {
     allocated_texture_count: usize,
     /// Texture filter to use for picture cache textures
     filter: TextureFilter,
+    /// Format of the picture cache textures, RGBA16F when rendering HDR
+    format: ImageFormat,
 }
 
 impl PictureTextures {
     fn new(
         default_tile_size: DeviceIntSize,
         filter: TextureFilter,
+        format: ImageFormat,
     ) -> Self {
         PictureTextures {
             textures: Vec::new(),
             default_tile_size,
             allocated_texture_count: 0,
             filter,
+            format,
         }
     }
 
@@ -580,7 +611,7 @@ impl PictureTextures {
                 target: ImageBufferKind::Texture2D,
                 width: tile_size.width,
                 height: tile_size.height,
-                format: ImageFormat::RGBA8,
+                format: self.format,
                 filter: self.filter,
                 is_shared_cache: false,
                 has_depth: true,
@@ -607,7 +638,7 @@ impl PictureTextures {
                 size: tile_size,
             },
             uv_rect_handle: GpuCacheHandle::new(),
-            input_format: ImageFormat::RGBA8,
+            input_format: self.format,
             filter: self.filter,
             swizzle: Swizzle::default(),
             texture_id,
@@ -706,6 +737,7 @@ pub struct TextureCacheConfig {
     pub alpha8_texture_size: i32,
     pub alpha8_glyph_texture_size: i32,
     pub alpha16_texture_size: i32,
+    pub color16f_linear_texture_size: i32,
 }
 
 impl TextureCacheConfig {
@@ -716,6 +748,7 @@ impl TextureCacheConfig {
         alpha8_texture_size: 1024,
         alpha8_glyph_texture_size: 2048,
         alpha16_texture_size: 512,
+        color16f_linear_texture_size: 1024,
     };
 }
 
@@ -805,6 +838,7 @@ impl TextureCache {
         swizzle: Option<SwizzleSettings>,
         config: &TextureCacheConfig,
         picture_texture_filter: TextureFilter,
+        picture_texture_format: ImageFormat,
     ) -> Self {
         let pending_updates = TextureUpdateList::new();
 
@@ -822,6 +856,7 @@ impl TextureCache {
             picture_textures: PictureTextures::new(
                 default_picture_tile_size,
                 picture_texture_filter,
+                picture_texture_format,
             ),
             max_texture_size,
             tiling_threshold,
@@ -855,6 +890,7 @@ impl TextureCache {
             None,
             &TextureCacheConfig::DEFAULT,
             TextureFilter::Nearest,
+            ImageFormat::RGBA8,
         );
         let mut now = FrameStamp::first(DocumentId::new(IdNamespace(1), 1));
         now.advance();
@@ -935,6 +971,7 @@ impl TextureCache {
         self.shared_textures.color8_linear.release_empty_textures(callback);
         self.shared_textures.color8_nearest.release_empty_textures(callback);
         self.shared_textures.color8_glyphs.release_empty_textures(callback);
+        self.shared_textures.color16f_linear.release_empty_textures(callback);
 
         for budget in BudgetType::iter() {
             let threshold = self.get_eviction_threshold(budget);
@@ -954,6 +991,8 @@ impl TextureCache {
         profile.set(profiler::ATLAS_RGBA8_NEAREST_TEXTURES, self.shared_textures.color8_nearest.allocated_textures());
         profile.set(profiler::ATLAS_RGBA8_GLYPHS_PIXELS, self.shared_textures.color8_glyphs.allocated_space());
         profile.set(profiler::ATLAS_RGBA8_GLYPHS_TEXTURES, self.shared_textures.color8_glyphs.allocated_textures());
+        profile.set(profiler::ATLAS_RGBA16F_LINEAR_PIXELS, self.shared_textures.color16f_linear.allocated_space());
+        profile.set(profiler::ATLAS_RGBA16F_LINEAR_TEXTURES, self.shared_textures.color16f_linear.allocated_textures());
 
         self.picture_textures.update_profile(profile);
 
@@ -964,6 +1003,7 @@ impl TextureCache {
             BudgetType::SharedAlpha8,
             BudgetType::SharedAlpha8Glyphs,
             BudgetType::SharedAlpha16,
+            BudgetType::SharedColor16F,
         ].iter().map(|b| self.bytes_allocated[*b as usize]).sum();
 
         profile.set(profiler::ATLAS_ITEMS_MEM, profiler::bytes_to_mb(shared_bytes));
@@ -1052,6 +1092,11 @@ impl TextureCache {
         self.picture_textures.filter
     }
 
+    #[cfg(feature = "replay")]
+    pub fn picture_texture_format(&self) -> ImageFormat {
+        self.picture_textures.format
+    }
+
     pub fn pending_updates(&mut self) -> TextureUpdateList {
         mem::replace(&mut self.pending_updates, TextureUpdateList::new())
     }
@@ -1294,7 +1339,9 @@ impl TextureCache {
         // shared textures as possible, to minimize the number of draw calls.
         // Ideally we only want one or two textures per type.
         let expected_texture_count = match budget_type {
-            BudgetType::SharedColor8Nearest | BudgetType::SharedAlpha16 => {
+            BudgetType::SharedColor8Nearest |
+            BudgetType::SharedAlpha16 |
+            BudgetType::SharedColor16F => {
                 // These types are only rarely used, we don't want more than
                 // one of each.
                 1
@@ -1549,6 +1596,18 @@ impl TextureCache {
             allowed_in_shared_cache = false;
         }
 
+        // Only linearly sampled half-float images have a shared texture. The
+        // other high bit depth formats are rare enough to always be standalone.
+        match descriptor.format {
+            ImageFormat::RGBA16F if filter == TextureFilter::Nearest => {
+                allowed_in_shared_cache = false;
+            }
+            ImageFormat::R16F | ImageFormat::RGB10A2 => {
+                allowed_in_shared_cache = false;
+            }
+            _ => {}
+        }
+
         allowed_in_shared_cache
     }
 
@@ -1795,6 +1854,9 @@ impl TextureCacheUpdate {
             CachedImageData::Blob => {
                 panic!("The vector image should have been rasterized.");
             }
+            CachedImageData::Snapshot => {
+                panic!("Snapshot images are rendered, not uploaded.");
+            }
             CachedImageData::External(ext_image) => match ext_image.image_type {
                 ExternalImageType::TextureHandle(_) => {
                     panic!("External texture handle should not go through texture_cache.");
@@ -1864,7 +1926,7 @@ mod test_texture_cache {
         use crate::gpu_cache::GpuCache;
         use crate::device::TextureFilter;
         use crate::gpu_types::UvRectKind;
-        use api::{ImageDescriptor, ImageDescriptorFlags, ImageFormat, DirtyRect};
+        use api::{ImageDescriptor, ImageDescriptorFlags, ImageFormat, DirtyRect, RgbColorSpace};
         use api::units::*;
         use euclid::size2;
         let mut gpu_cache = GpuCache::new_for_testing();
@@ -1898,6 +1960,7 @@ mod test_texture_cache {
                     format: ImageFormat::BGRA8,
                     flags: ImageDescriptorFlags::empty(),
                     offset: 0,
+                    color_space: RgbColorSpace::Srgb,
                 },
                 TextureFilter::Linear,
                 None,
@@ -1922,4 +1985,27 @@ mod test_texture_cache {
         let bytes_at_end = texture_cache.total_allocated_bytes_for_testing();
         assert_eq!(bytes_at_end, bytes_at_start);
     }
+
+    #[test]
+    fn check_high_bit_depth_formats() {
+        use crate::texture_cache::TextureCache;
+        use crate::device::TextureFilter;
+        use api::{ImageDescriptor, ImageDescriptorFlags, ImageFormat, RgbColorSpace};
+        use euclid::size2;
+        let texture_cache = TextureCache::new_for_testing(2048, ImageFormat::BGRA8);
+
+        let descriptor = |format| ImageDescriptor {
+            size: size2(64, 64),
+            stride: None,
+            format,
+            flags: ImageDescriptorFlags::empty(),
+            offset: 0,
+            color_space: RgbColorSpace::Srgb,
+        };
+
+        assert!(texture_cache.is_allowed_in_shared_cache(TextureFilter::Linear, &descriptor(ImageFormat::RGBA16F)));
+        assert!(!texture_cache.is_allowed_in_shared_cache(TextureFilter::Nearest, &descriptor(ImageFormat::RGBA16F)));
+        assert!(!texture_cache.is_allowed_in_shared_cache(TextureFilter::Linear, &descriptor(ImageFormat::R16F)));
+        assert!(!texture_cache.is_allowed_in_shared_cache(TextureFilter::Linear, &descriptor(ImageFormat::RGB10A2)));
+    }
 }
@@ -2,9 +2,10 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use api::{ColorF, PrimitiveFlags, QualitySettings};
+use api::{ColorF, PrimitiveFlags, QualitySettings, RgbColorSpace};
 use api::units::*;
 use crate::clip::{ClipChainId, ClipNodeKind, ClipStore, ClipInstance};
+use crate::color_space::convert_color;
 use crate::frame_builder::FrameBuilderConfig;
 use crate::internal_types::{FastHashMap, FastHashSet};
 use crate::picture::{PrimitiveList, PictureCompositeMode, PictureOptions, PicturePrimitive, SliceId};
@@ -395,7 +396,9 @@ impl TileCacheBuilder {
                     let slice_flags = self.force_new_tile_cache.unwrap_or(SliceFlags::empty());
 
                     let background_color = if slice == 0 {
-                        config.background_color
+                        config.background_color.map(|color| {
+                            convert_color(color, RgbColorSpace::Srgb, config.output_color_space)
+                        })
                     } else {
                         None
                     };
@@ -0,0 +1,221 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! HDR rendering options and tone mapping.
+//!
+//! In HDR mode, render targets and picture cache tiles are RGBA16F and hold
+//! extended sRGB values, where 1.0 is the reference white and brighter content
+//! goes above it. The composite pass decodes the tiles to linear light, maps
+//! them to the range of the display with one of the `ToneMapOperator`s and
+//! encodes the result with the sRGB transfer function again, so that the
+//! output is still 1.0 at the reference white and at most
+//! `max_display_nits / reference_white_nits`. Keeping the values above 1.0
+//! requires a floating point framebuffer.
+//!
+//! The math below mirrors hdr.glsl.
+
+/// How the compositor maps content brighter than the display can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub enum ToneMapOperator {
+    /// Clamp each channel to the peak of the display.
+    Clip,
+    /// Extended Reinhard, mapping the peak of the content to the peak of
+    /// the display. This compresses all of the range, including SDR content.
+    Reinhard,
+    /// The EETF from ITU-R BT.2390, which leaves everything below a knee
+    /// untouched and rolls off the highlights in the PQ domain.
+    Bt2390,
+}
+
+/// Configuration of the HDR mode, see `RendererOptions::hdr`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "capture", derive(Serialize))]
+#[cfg_attr(feature = "replay", derive(Deserialize))]
+pub struct HdrOptions {
+    /// The luminance of SDR white, in nits. SDR content and PQ inputs are
+    /// scaled so that this maps to 1.0.
+    pub reference_white_nits: f32,
+    /// The peak luminance of the display, in nits.
+    pub max_display_nits: f32,
+    /// The peak luminance of the content, in nits. Tone mapping operators
+    /// map it to `max_display_nits`.
+    pub max_content_nits: f32,
+    pub tone_map: ToneMapOperator,
+}
+
+impl Default for HdrOptions {
+    fn default() -> Self {
+        HdrOptions {
+            // ITU-R BT.2408 reference white.
+            reference_white_nits: 203.0,
+            max_display_nits: 1000.0,
+            max_content_nits: PQ_MAX_NITS,
+            tone_map: ToneMapOperator::Bt2390,
+        }
+    }
+}
+
+const PQ_M1: f32 = 0.1593017578125;
+const PQ_M2: f32 = 78.84375;
+const PQ_C1: f32 = 0.8359375;
+const PQ_C2: f32 = 18.8515625;
+const PQ_C3: f32 = 18.6875;
+const PQ_MAX_NITS: f32 = 10000.0;
+
+/// The SMPTE ST 2084 EOTF, from PQ code values to nits.
+fn pq_to_nits(e: f32) -> f32 {
+    let p = e.max(0.0).min(1.0).powf(1.0 / PQ_M2);
+    PQ_MAX_NITS * ((p - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * p)).powf(1.0 / PQ_M1)
+}
+
+/// The inverse of `pq_to_nits`.
+fn nits_to_pq(nits: f32) -> f32 {
+    let y = (nits / PQ_MAX_NITS).max(0.0).min(1.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+}
+
+impl ToneMapOperator {
+    /// The operator, as understood by the shaders. Must match the
+    /// TONE_MAP_* defines in hdr.glsl.
+    fn as_int(self) -> i32 {
+        match self {
+            ToneMapOperator::Clip => 0,
+            ToneMapOperator::Reinhard => 1,
+            ToneMapOperator::Bt2390 => 2,
+        }
+    }
+}
+
+impl HdrOptions {
+    /// The value of the uToneMap uniform of the composite shaders.
+    pub(crate) fn shader_params(&self) -> [f32; 4] {
+        [
+            self.tone_map.as_int() as f32,
+            self.reference_white_nits,
+            self.max_display_nits,
+            self.max_content_nits,
+        ]
+    }
+
+    /// Tone map a linear color, where 1.0 is the reference white, the same
+    /// way the compositor does.
+    pub fn tone_map(&self, color: [f32; 3]) -> [f32; 3] {
+        let display_peak = self.max_display_nits / self.reference_white_nits;
+        let content_peak = (self.max_content_nits / self.reference_white_nits).max(display_peak);
+
+        if self.tone_map == ToneMapOperator::Clip {
+            return [
+                color[0].min(display_peak),
+                color[1].min(display_peak),
+                color[2].min(display_peak),
+            ];
+        }
+
+        // The other operators scale the color by the ratio the brightest
+        // channel is mapped with, to preserve the hue.
+        let max_channel = color[0].max(color[1]).max(color[2]);
+        if max_channel <= 0.0 {
+            return color;
+        }
+
+        let mapped = match self.tone_map {
+            ToneMapOperator::Clip => unreachable!(),
+            ToneMapOperator::Reinhard => {
+                let x = max_channel / display_peak;
+                let white = content_peak / display_peak;
+                x * (1.0 + x / (white * white)) / (1.0 + x) * display_peak
+            }
+            ToneMapOperator::Bt2390 => {
+                let content_max_pq = nits_to_pq(content_peak * self.reference_white_nits);
+                let max_lum = nits_to_pq(self.max_display_nits) / content_max_pq;
+                let knee = (1.5 * max_lum - 0.5).max(0.0);
+                let mut e = (nits_to_pq(max_channel * self.reference_white_nits) / content_max_pq).min(1.0);
+                if e > knee && max_lum < 1.0 {
+                    let t = (e - knee) / (1.0 - knee);
+                    let t2 = t * t;
+                    let t3 = t2 * t;
+                    e = (2.0 * t3 - 3.0 * t2 + 1.0) * knee +
+                        (t3 - 2.0 * t2 + t) * (1.0 - knee) +
+                        (-2.0 * t3 + 3.0 * t2) * max_lum;
+                }
+                pq_to_nits(e * content_max_pq) / self.reference_white_nits
+            }
+        };
+
+        let scale = mapped.min(display_peak) / max_channel;
+        [color[0] * scale, color[1] * scale, color[2] * scale]
+    }
+}
+
+#[cfg(test)]
+fn options(tone_map: ToneMapOperator) -> HdrOptions {
+    HdrOptions {
+        reference_white_nits: 100.0,
+        max_display_nits: 400.0,
+        max_content_nits: 4000.0,
+        tone_map,
+    }
+}
+
+#[test]
+fn test_pq() {
+    assert!((nits_to_pq(100.0) - 0.5081).abs() < 0.001);
+    assert!((nits_to_pq(PQ_MAX_NITS) - 1.0).abs() < 0.001);
+    assert_eq!(nits_to_pq(0.0), PQ_C1.powf(PQ_M2));
+
+    for &nits in &[0.5, 80.0, 203.0, 1000.0, 4000.0] {
+        let round_trip = pq_to_nits(nits_to_pq(nits));
+        assert!((round_trip - nits).abs() / nits < 0.001, "{} != {}", round_trip, nits);
+    }
+}
+
+#[test]
+fn test_clip() {
+    let hdr = options(ToneMapOperator::Clip);
+    assert_eq!(hdr.tone_map([0.5, 2.0, 10.0]), [0.5, 2.0, 4.0]);
+}
+
+#[test]
+fn test_reinhard() {
+    let hdr = options(ToneMapOperator::Reinhard);
+
+    // The peak of the content maps to the peak of the display.
+    let [r, g, b] = hdr.tone_map([40.0, 20.0, 0.0]);
+    assert!((r - 4.0).abs() < 0.001 && (g - 2.0).abs() < 0.001 && b == 0.0);
+
+    // The curve is monotonic and compresses everything.
+    let mut last = 0.0;
+    for i in 1 .. 100 {
+        let [y, _, _] = hdr.tone_map([i as f32 * 0.4, 0.0, 0.0]);
+        assert!(y > last && y < i as f32 * 0.4);
+        last = y;
+    }
+}
+
+#[test]
+fn test_bt2390() {
+    let hdr = options(ToneMapOperator::Bt2390);
+
+    // Values below the knee are left untouched.
+    let [r, g, b] = hdr.tone_map([1.0, 0.5, 0.25]);
+    assert!((r - 1.0).abs() < 0.001 && (g - 0.5).abs() < 0.001 && (b - 0.25).abs() < 0.001);
+
+    // The peak of the content maps to the peak of the display, and the
+    // highlights are rolled off monotonically, give or take the precision
+    // of the PQ curve.
+    let [peak, _, _] = hdr.tone_map([40.0, 0.0, 0.0]);
+    assert!((peak - 4.0).abs() < 0.01, "{}", peak);
+    let mut last = 0.0;
+    for i in 1 .. 100 {
+        let [y, _, _] = hdr.tone_map([i as f32 * 0.4, 0.0, 0.0]);
+        assert!(y > last - 0.001 && y <= 4.0);
+        last = y;
+    }
+
+    // Nothing is mapped when the display is as bright as the content.
+    let hdr = HdrOptions { max_display_nits: 4000.0, ..hdr };
+    assert!((hdr.tone_map([30.0, 0.0, 0.0])[0] - 30.0).abs() < 0.01);
+}
@@ -409,6 +409,8 @@ pub fn update_primitive_visibility(
                         surface.device_pixel_scale,
                         &world_culling_rect,
                         &mut frame_state.data_stores.clip,
+                        &mut frame_state.data_stores.clip_path,
+                        &frame_state.data_stores.clip_text,
                         true,
                         prim_instance.is_chased(),
                     );
@@ -508,6 +510,7 @@ pub fn update_primitive_visibility(
                         PrimitiveInstanceKind::CachedLinearGradient { .. } => debug_colors::PINK,
                         PrimitiveInstanceKind::RadialGradient { .. } => debug_colors::PINK,
                         PrimitiveInstanceKind::ConicGradient { .. } => debug_colors::PINK,
+                        PrimitiveInstanceKind::Path { .. } => debug_colors::OLIVEDRAB,
                         PrimitiveInstanceKind::Clear { .. } => debug_colors::CYAN,
                         PrimitiveInstanceKind::Backdrop { .. } => debug_colors::MEDIUMAQUAMARINE,
                     };
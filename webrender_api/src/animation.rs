@@ -0,0 +1,203 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Declarative animations of property bindings.
+//!
+//! Instead of supplying new `DynamicProperties` every frame, an animation can
+//! be registered for a property binding through `Transaction::add_animation`.
+//! The render backend then samples it each time a frame is generated.
+//!
+//! The timing model follows the Web Animations specification:
+//! https://drafts.csswg.org/web-animations-1/#timing-model
+
+use crate::color::ColorF;
+use crate::units::LayoutTransform;
+use crate::{PropertyBindingId, PropertyBindingKey};
+
+/// Where the jumps of a `TimingFunction::Steps` happen.
+///
+/// Specification: https://drafts.csswg.org/css-easing/#step-easing-functions
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum StepPosition {
+    /// The first jump happens when the animation begins.
+    JumpStart,
+    /// The last jump happens when the animation ends.
+    JumpEnd,
+    /// There is no jump at either end of the animation.
+    JumpNone,
+    /// There is a jump at both ends of the animation.
+    JumpBoth,
+}
+
+/// The easing applied to the progress of each iteration of an animation.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum TimingFunction {
+    ///
+    Linear,
+    /// A cubic bezier curve from (0, 0) to (1, 1) with the given control points.
+    /// The x coordinates must be in the [0, 1] range.
+    CubicBezier {
+        ///
+        x1: f32,
+        ///
+        y1: f32,
+        ///
+        x2: f32,
+        ///
+        y2: f32,
+    },
+    /// A step function dividing the iteration into `count` intervals.
+    Steps {
+        ///
+        count: u32,
+        ///
+        position: StepPosition,
+    },
+    /// A damped spring going from 0 to 1. The spring is simulated over the
+    /// duration of the iteration, in seconds, and snaps to its final value
+    /// at the end of the iteration.
+    Spring {
+        ///
+        mass: f32,
+        ///
+        stiffness: f32,
+        ///
+        damping: f32,
+        /// The initial velocity, in progress units per second.
+        initial_velocity: f32,
+    },
+}
+
+impl TimingFunction {
+    /// The CSS `ease` timing function.
+    pub const EASE: Self = TimingFunction::CubicBezier { x1: 0.25, y1: 0.1, x2: 0.25, y2: 1.0 };
+    /// The CSS `ease-in` timing function.
+    pub const EASE_IN: Self = TimingFunction::CubicBezier { x1: 0.42, y1: 0.0, x2: 1.0, y2: 1.0 };
+    /// The CSS `ease-out` timing function.
+    pub const EASE_OUT: Self = TimingFunction::CubicBezier { x1: 0.0, y1: 0.0, x2: 0.58, y2: 1.0 };
+    /// The CSS `ease-in-out` timing function.
+    pub const EASE_IN_OUT: Self = TimingFunction::CubicBezier { x1: 0.42, y1: 0.0, x2: 0.58, y2: 1.0 };
+}
+
+/// Whether iterations of an animation play forwards or backwards.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AnimationDirection {
+    ///
+    Normal,
+    ///
+    Reverse,
+    /// Even iterations play forwards and odd iterations backwards.
+    Alternate,
+    /// Even iterations play backwards and odd iterations forwards.
+    AlternateReverse,
+}
+
+/// Whether the animation affects its property before it starts and after it
+/// finishes. Outside of the filled intervals, the property takes the value
+/// supplied through `DynamicProperties`, or the value of the binding in the
+/// display list.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AnimationFillMode {
+    ///
+    None,
+    /// The first value is applied during the delay.
+    Backwards,
+    /// The last value is applied once the animation has finished.
+    Forwards,
+    ///
+    Both,
+}
+
+/// The number of times an animation repeats.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AnimationIterations {
+    /// A number of iterations, which may be fractional.
+    Count(f32),
+    ///
+    Infinite,
+}
+
+/// The value of an animated property at a given point of an iteration.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Keyframe<T> {
+    /// The position of the keyframe in the iteration, between 0 and 1.
+    pub offset: f32,
+    ///
+    pub value: T,
+}
+
+impl<T> Keyframe<T> {
+    ///
+    pub fn new(offset: f32, value: T) -> Self {
+        Keyframe { offset, value }
+    }
+}
+
+/// The property binding an animation drives, and its keyframes.
+///
+/// Transforms are interpolated by decomposing them into translation, rotation,
+/// skew, scale and perspective components, as described in
+/// https://drafts.csswg.org/css-transforms-2/#interpolation-of-3d-matrices
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AnimatedProperty {
+    ///
+    Float(PropertyBindingKey<f32>, Vec<Keyframe<f32>>),
+    ///
+    Color(PropertyBindingKey<ColorF>, Vec<Keyframe<ColorF>>),
+    ///
+    Transform(PropertyBindingKey<LayoutTransform>, Vec<Keyframe<LayoutTransform>>),
+}
+
+impl AnimatedProperty {
+    /// The id of the animated property binding, which also identifies the
+    /// animation.
+    pub fn id(&self) -> PropertyBindingId {
+        match *self {
+            AnimatedProperty::Float(key, _) => key.id,
+            AnimatedProperty::Color(key, _) => key.id,
+            AnimatedProperty::Transform(key, _) => key.id,
+        }
+    }
+}
+
+/// A keyframe animation of a property binding.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Animation {
+    ///
+    pub property: AnimatedProperty,
+    /// The duration of a single iteration, in milliseconds.
+    pub duration: f32,
+    /// The time between the registration of the animation and the start of
+    /// its first iteration, in milliseconds.
+    pub delay: f32,
+    ///
+    pub iterations: AnimationIterations,
+    ///
+    pub direction: AnimationDirection,
+    ///
+    pub fill_mode: AnimationFillMode,
+    ///
+    pub timing_function: TimingFunction,
+}
+
+impl Animation {
+    /// Create an animation playing its keyframes once, linearly and without
+    /// delay.
+    pub fn new(property: AnimatedProperty, duration: f32) -> Self {
+        Animation {
+            property,
+            duration,
+            delay: 0.0,
+            iterations: AnimationIterations::Count(1.0),
+            direction: AnimationDirection::Normal,
+            fill_mode: AnimationFillMode::None,
+            timing_function: TimingFunction::Linear,
+        }
+    }
+
+    /// The id of the animated property binding.
+    pub fn id(&self) -> PropertyBindingId {
+        self.property.id()
+    }
+}
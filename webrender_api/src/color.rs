@@ -158,3 +158,27 @@ impl From<ColorU> for ColorF {
         }
     }
 }
+
+/// The RGB color space colors and images are specified in, or that the
+/// renderer outputs to.
+///
+/// Colors are converted from the color space of their item to the output
+/// color space of the renderer. All of these spaces share the D65 white point.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum RgbColorSpace {
+    /// sRGB (IEC 61966-2-1).
+    Srgb = 0,
+    /// Display P3: the DCI-P3 primaries with the sRGB transfer function.
+    DisplayP3 = 1,
+    /// Adobe RGB (1998).
+    AdobeRgb = 2,
+    /// ITU-R BT.2020, with the BT.2020 (SDR) transfer function.
+    Rec2020 = 3,
+}
+
+impl Default for RgbColorSpace {
+    fn default() -> Self {
+        RgbColorSpace::Srgb
+    }
+}
@@ -8,8 +8,8 @@ use std::ops::Not;
 // local imports
 use crate::font;
 use crate::{PipelineId, PropertyBinding};
-use crate::color::ColorF;
-use crate::image::{ColorDepth, ImageKey};
+use crate::color::{ColorF, RgbColorSpace};
+use crate::image::{ColorDepth, ImageKey, SnapshotImageKey};
 use crate::units::*;
 use std::hash::{Hash, Hasher};
 
@@ -124,6 +124,7 @@ pub enum DisplayItem {
     HitTest(HitTestDisplayItem),
     Text(TextDisplayItem),
     Line(LineDisplayItem),
+    Path(PathDisplayItem),
     Border(BorderDisplayItem),
     BoxShadow(BoxShadowDisplayItem),
     PushShadow(PushShadowDisplayItem),
@@ -139,6 +140,8 @@ pub enum DisplayItem {
     RectClip(RectClipDisplayItem),
     RoundedRectClip(RoundedRectClipDisplayItem),
     ImageMaskClip(ImageMaskClipDisplayItem),
+    PathClip(PathClipDisplayItem),
+    TextClip(TextClipDisplayItem),
     ClipChain(ClipChainItem),
 
     // Spaces and Frames that content can be scoped under.
@@ -176,6 +179,7 @@ pub enum DebugDisplayItem {
     HitTest(HitTestDisplayItem),
     Text(TextDisplayItem, Vec<font::GlyphInstance>),
     Line(LineDisplayItem),
+    Path(PathDisplayItem, Vec<PathCommand>),
     Border(BorderDisplayItem),
     BoxShadow(BoxShadowDisplayItem),
     PushShadow(PushShadowDisplayItem),
@@ -190,9 +194,11 @@ pub enum DebugDisplayItem {
     ImageMaskClip(ImageMaskClipDisplayItem),
     RoundedRectClip(RoundedRectClipDisplayItem),
     RectClip(RectClipDisplayItem),
+    PathClip(PathClipDisplayItem, Vec<PathCommand>),
+    TextClip(TextClipDisplayItem, Vec<font::GlyphInstance>),
     ClipChain(ClipChainItem, Vec<ClipId>),
 
-    ScrollFrame(ScrollFrameDisplayItem),
+    ScrollFrame(ScrollFrameDisplayItem, Vec<ScrollSnapArea>),
     StickyFrame(StickyFrameDisplayItem),
     Iframe(IframeDisplayItem),
     PushReferenceFrame(ReferenceFrameDisplayListItem),
@@ -231,6 +237,29 @@ pub struct RoundedRectClipDisplayItem {
     pub clip: ComplexClipRegion,
 }
 
+/// A clip to the interior of a path, filled according to `fill_rule`. The
+/// path commands are in the same space as `rect`, and anything outside of
+/// `rect` is clipped out.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct PathClipDisplayItem {
+    pub id: ClipId,
+    pub parent_space_and_clip: SpaceAndClipInfo,
+    pub rect: LayoutRect,
+    pub fill_rule: FillRule,
+} // IMPLICIT commands: Vec<PathCommand>
+
+/// A clip to the coverage of a run of glyphs, as a text item with the same
+/// font and glyphs would draw them. The glyphs are in the same space as
+/// `rect`, and anything outside of `rect` is clipped out.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct TextClipDisplayItem {
+    pub id: ClipId,
+    pub parent_space_and_clip: SpaceAndClipInfo,
+    pub rect: LayoutRect,
+    pub font_key: font::FontInstanceKey,
+    pub glyph_options: Option<font::GlyphOptions>,
+} // IMPLICIT: glyphs: Vec<font::GlyphInstance>
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct ClipDisplayItem {
     pub id: ClipId,
@@ -296,6 +325,83 @@ pub enum ScrollSensitivity {
     Script,
 }
 
+/// How a scroll frame reacts to being scrolled past the edges of its scroll
+/// range. Overscroll only happens for clamped scrolls.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub enum OverscrollBehavior {
+    /// Scrolling stops at the edges.
+    None,
+    /// Scrolling past the edges is increasingly resisted, and the content
+    /// springs back once the scroll gesture ends.
+    RubberBand {
+        /// The stiffness of the spring, for a unit mass.
+        stiffness: f32,
+        /// The damping of the spring, for a unit mass.
+        damping: f32,
+    },
+    /// Scrolling stops at the edges, and the overscroll distance is reported
+    /// to the embedder, which can draw an effect such as a glow.
+    Glow,
+}
+
+impl OverscrollBehavior {
+    /// A critically damped rubber band.
+    pub const RUBBER_BAND: Self = OverscrollBehavior::RubberBand {
+        stiffness: 100.0,
+        damping: 20.0,
+    };
+}
+
+/// Whether a scroll frame always rests on one of its snap positions, once a
+/// scroll gesture ends.
+///
+/// Specification: https://drafts.csswg.org/css-scroll-snap-1/#scroll-snap-type
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub enum ScrollSnapStrictness {
+    /// The scroll frame snaps to the nearest snap position.
+    Mandatory,
+    /// The scroll frame only snaps to snap positions close to where it rests.
+    Proximity,
+}
+
+/// How a snap area is aligned with the viewport of its scroll frame, along
+/// one axis.
+///
+/// Specification: https://drafts.csswg.org/css-scroll-snap-1/#scroll-snap-align
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub enum ScrollSnapAlignment {
+    /// The snap area doesn't snap along this axis.
+    None,
+    /// The start edge of the snap area is aligned with the start edge of the
+    /// viewport.
+    Start,
+    /// The center of the snap area is aligned with the center of the viewport.
+    Center,
+    /// The end edge of the snap area is aligned with the end edge of the
+    /// viewport.
+    End,
+}
+
+/// An area of the content of a scroll frame that the viewport can snap to.
+/// Snap points are snap areas of zero size.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct ScrollSnapArea {
+    /// In the coordinate space of the items of the scroll frame.
+    pub rect: LayoutRect,
+    pub align_x: ScrollSnapAlignment,
+    pub align_y: ScrollSnapAlignment,
+}
+
+impl ScrollSnapArea {
+    pub fn new(
+        rect: LayoutRect,
+        align_x: ScrollSnapAlignment,
+        align_y: ScrollSnapAlignment,
+    ) -> Self {
+        ScrollSnapArea { rect, align_x, align_y }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct ScrollFrameDisplayItem {
     /// The id of the clip this scroll frame creates
@@ -315,7 +421,9 @@ pub struct ScrollFrameDisplayItem {
     /// should be added to those display item coordinates in order to get a
     /// normalized value that is consistent across display lists.
     pub external_scroll_offset: LayoutVector2D,
-}
+    pub overscroll_behavior: OverscrollBehavior,
+    pub snap_strictness: ScrollSnapStrictness,
+} // IMPLICIT snap_areas: Vec<ScrollSnapArea>
 
 /// A solid or an animating color to draw (may not actually be a rectangle due to complex clips)
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
@@ -323,6 +431,8 @@ pub struct RectangleDisplayItem {
     pub common: CommonItemProperties,
     pub bounds: LayoutRect,
     pub color: PropertyBinding<ColorF>,
+    /// The color space `color` is specified in.
+    pub color_space: RgbColorSpace,
 }
 
 /// Clears all colors from the area, making it possible to cut holes in the window.
@@ -379,6 +489,100 @@ pub enum LineStyle {
     Wavy,
 }
 
+/// The maximum number of entries in a path stroke's dash array.
+pub const MAX_PATH_DASH_COUNT: usize = 8;
+
+/// A single segment of a vector path, in the local space of the item.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub enum PathCommand {
+    /// Start a new sub-path at the given point.
+    MoveTo(LayoutPoint),
+    /// Straight line from the current point.
+    LineTo(LayoutPoint),
+    /// Quadratic bézier curve with one control point, then the end point.
+    QuadTo(LayoutPoint, LayoutPoint),
+    /// Cubic bézier curve with two control points, then the end point.
+    CubicTo(LayoutPoint, LayoutPoint, LayoutPoint),
+    /// Close the current sub-path with a straight line to its start point.
+    Close,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize, Eq, Hash, PeekPoke)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, MallocSizeOf, PartialEq, Serialize, Eq, Hash, PeekPoke)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+/// Describes how the outline of a path is stroked. Behaves as the SVG
+/// stroke-* properties of the same names.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct PathStroke {
+    pub width: f32,
+    pub line_join: LineJoin,
+    pub line_cap: LineCap,
+    pub miter_limit: f32,
+    /// Alternating dash and gap lengths. Only the first `dash_count`
+    /// entries are used; a count of zero means a solid stroke.
+    pub dash_array: [f32; MAX_PATH_DASH_COUNT],
+    pub dash_count: u8,
+    pub dash_offset: f32,
+}
+
+impl PathStroke {
+    pub fn solid(width: f32) -> Self {
+        PathStroke {
+            width,
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+            miter_limit: 4.0,
+            dash_array: [0.0; MAX_PATH_DASH_COUNT],
+            dash_count: 0,
+            dash_offset: 0.0,
+        }
+    }
+
+    pub fn dashes(&self) -> &[f32] {
+        &self.dash_array[.. (self.dash_count as usize).min(MAX_PATH_DASH_COUNT)]
+    }
+}
+
+/// What a path is painted with. Gradient brushes take their stops from
+/// the preceding `SetGradientStops`, as for the gradient display items, but
+/// their positions are in the same space as the path commands.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub enum PathBrush {
+    Color(ColorF),
+    LinearGradient(Gradient),
+    RadialGradient(RadialGradient),
+    ConicGradient(ConicGradient),
+}
+
+/// A filled or stroked vector path. If `stroke` is `None` the interior of
+/// the path is filled according to `fill_rule`, otherwise its outline is
+/// stroked.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct PathDisplayItem {
+    pub common: CommonItemProperties,
+    /// The local space bounds of the path, including the stroke. Commands
+    /// outside of these bounds are clipped.
+    pub bounds: LayoutRect,
+    pub fill_rule: FillRule,
+    pub stroke: Option<PathStroke>,
+    pub brush: PathBrush,
+} // IMPLICIT commands: Vec<PathCommand>
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct TextDisplayItem {
     pub common: CommonItemProperties,
@@ -618,11 +822,38 @@ pub enum ExtendMode {
     Repeat,
 }
 
+/// How the hue of a polar color space is interpolated between two colors.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Hash, Eq, MallocSizeOf, PartialEq, Serialize, Deserialize, PeekPoke)]
+pub enum HueInterpolationMethod {
+    /// Take the shorter arc between the two hues.
+    Shorter,
+    /// Take the longer arc between the two hues.
+    Longer,
+}
+
+/// The color space in which gradient stops are interpolated.
+///
+/// Colors are always interpolated with premultiplied alpha. The hue of polar
+/// color spaces is not premultiplied.
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Hash, Eq, MallocSizeOf, PartialEq, Serialize, Deserialize, PeekPoke)]
+pub enum ColorInterpolationSpace {
+    Srgb,
+    SrgbLinear,
+    Oklab,
+    Oklch(HueInterpolationMethod),
+    Hsl(HueInterpolationMethod),
+}
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
 pub struct Gradient {
     pub start_point: LayoutPoint,
     pub end_point: LayoutPoint,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
+    /// The color space the colors of the stops are specified in.
+    pub color_space: RgbColorSpace,
 } // IMPLICIT: stops: Vec<GradientStop>
 
 impl Gradient {
@@ -664,6 +895,9 @@ pub struct RadialGradient {
     pub start_offset: f32,
     pub end_offset: f32,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
+    /// The color space the colors of the stops are specified in.
+    pub color_space: RgbColorSpace,
 } // IMPLICIT stops: Vec<GradientStop>
 
 impl RadialGradient {
@@ -682,6 +916,9 @@ pub struct ConicGradient {
     pub start_offset: f32,
     pub end_offset: f32,
     pub extend_mode: ExtendMode,
+    pub interpolation_space: ColorInterpolationSpace,
+    /// The color space the colors of the stops are specified in.
+    pub color_space: RgbColorSpace,
 } // IMPLICIT stops: Vec<GradientStop>
 
 impl ConicGradient {
@@ -840,6 +1077,9 @@ pub struct StackingContext {
     pub clip_id: Option<ClipId>,
     pub raster_space: RasterSpace,
     pub flags: StackingContextFlags,
+    /// If set, the contents of the stacking context are also rendered into
+    /// this snapshot image, before its filters and blend mode are applied.
+    pub snapshot: Option<SnapshotImageKey>,
 }
 // IMPLICIT: filters: Vec<FilterOp>, filter_datas: Vec<FilterData>, filter_primitives: Vec<FilterPrimitive>
 
@@ -1082,6 +1322,246 @@ pub struct CompositePrimitive {
     pub operator: CompositeOperator,
 }
 
+/// The kind of noise generated by a turbulence filter primitive.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum TurbulenceType {
+    FractalNoise,
+    Turbulence,
+}
+
+/// Generates Perlin noise, as described by the SVG feTurbulence element.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct TurbulencePrimitive {
+    pub base_frequency_x: f32,
+    pub base_frequency_y: f32,
+    pub num_octaves: u32,
+    pub seed: i32,
+    pub stitch_tiles: bool,
+    pub kind: TurbulenceType,
+}
+
+impl TurbulencePrimitive {
+    pub fn sanitize(&mut self) {
+        self.base_frequency_x = self.base_frequency_x.max(0.0);
+        self.base_frequency_y = self.base_frequency_y.max(0.0);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum MorphologyOperator {
+    Erode,
+    Dilate,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct MorphologyPrimitive {
+    pub input: FilterPrimitiveInput,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub operator: MorphologyOperator,
+}
+
+/// The maximum radius of a morphology filter primitive, in layout pixels.
+pub const SVGFE_MORPHOLOGY_RADIUS_LIMIT: f32 = 100.0;
+
+impl MorphologyPrimitive {
+    pub fn sanitize(&mut self) {
+        // `max` also replaces NaN with zero, which disables the filter.
+        self.radius_x = self.radius_x.max(0.0).min(SVGFE_MORPHOLOGY_RADIUS_LIMIT);
+        self.radius_y = self.radius_y.max(0.0).min(SVGFE_MORPHOLOGY_RADIUS_LIMIT);
+    }
+}
+
+/// The maximum number of kernel values of a convolve matrix filter primitive,
+/// which limits its order to 5x5.
+pub const SVGFE_CONVOLVE_VALUES_LIMIT: usize = 25;
+
+/// Determines how a convolve matrix filter primitive extends its input
+/// when the kernel reaches past its edges.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum EdgeMode {
+    Duplicate,
+    Wrap,
+    None,
+}
+
+/// cbindgen:derive-eq=false
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct ConvolveMatrixPrimitive {
+    pub input: FilterPrimitiveInput,
+    pub order_x: u32,
+    pub order_y: u32,
+    /// The kernel values in row-major order. Only the first
+    /// `order_x * order_y` values are used.
+    pub kernel: [f32; SVGFE_CONVOLVE_VALUES_LIMIT],
+    /// If zero, the sum of the kernel values is used instead (or one,
+    /// if that is also zero).
+    pub divisor: f32,
+    pub bias: f32,
+    pub target_x: u32,
+    pub target_y: u32,
+    pub edge_mode: EdgeMode,
+    pub preserve_alpha: bool,
+}
+
+impl ConvolveMatrixPrimitive {
+    pub fn sanitize(&mut self) {
+        self.order_x = self.order_x.max(1).min(5);
+        self.order_y = self.order_y.max(1).min(5);
+        while self.order_x * self.order_y > SVGFE_CONVOLVE_VALUES_LIMIT as u32 {
+            self.order_y -= 1;
+        }
+        self.target_x = self.target_x.min(self.order_x - 1);
+        self.target_y = self.target_y.min(self.order_y - 1);
+    }
+}
+
+/// A color channel of an image, used to select which channel of the
+/// displacement map moves pixels along each axis.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum ColorChannel {
+    Red = 0,
+    Green = 1,
+    Blue = 2,
+    Alpha = 3,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct DisplacementMapPrimitive {
+    /// The image that is displaced.
+    pub input1: FilterPrimitiveInput,
+    /// The displacement map.
+    pub input2: FilterPrimitiveInput,
+    pub scale: f32,
+    pub x_channel_selector: ColorChannel,
+    pub y_channel_selector: ColorChannel,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct DistantLight {
+    /// Direction angle for the light source on the XY plane, in degrees.
+    pub azimuth: f32,
+    /// Direction angle for the light source from the XY plane towards
+    /// the Z axis, in degrees.
+    pub elevation: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct PointLight {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct SpotLight {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub points_at_x: f32,
+    pub points_at_y: f32,
+    pub points_at_z: f32,
+    pub specular_exponent: f32,
+    /// The angle of the cone of light, in degrees. If `None`, the light
+    /// is not restricted to a cone.
+    pub limiting_cone_angle: Option<f32>,
+}
+
+/// The light source of a lighting filter primitive. Positions are in the
+/// local space of the filtered content.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub enum LightSource {
+    Distant(DistantLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct DiffuseLightingPrimitive {
+    pub input: FilterPrimitiveInput,
+    pub surface_scale: f32,
+    pub diffuse_constant: f32,
+    pub lighting_color: ColorF,
+    pub light: LightSource,
+}
+
+impl DiffuseLightingPrimitive {
+    pub fn sanitize(&mut self) {
+        self.diffuse_constant = self.diffuse_constant.max(0.0);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct SpecularLightingPrimitive {
+    pub input: FilterPrimitiveInput,
+    pub surface_scale: f32,
+    pub specular_constant: f32,
+    pub specular_exponent: f32,
+    pub lighting_color: ColorF,
+    pub light: LightSource,
+}
+
+impl SpecularLightingPrimitive {
+    pub fn sanitize(&mut self) {
+        self.specular_constant = self.specular_constant.max(0.0);
+        self.specular_exponent = self.specular_exponent.min(128.0).max(1.0);
+    }
+}
+
+/// Repeats the `source_rect` area of its input across the whole filter region.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct TilePrimitive {
+    pub input: FilterPrimitiveInput,
+    pub source_rect: LayoutRect,
+}
+
+/// The maximum number of inputs of a merge filter primitive.
+pub const SVGFE_MERGE_INPUTS_LIMIT: usize = 16;
+
+/// Composites its inputs on top of each other, in order, using the
+/// source-over operator.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct MergePrimitive {
+    pub inputs: [FilterPrimitiveInput; SVGFE_MERGE_INPUTS_LIMIT],
+    pub input_count: u32,
+}
+
+impl MergePrimitive {
+    pub fn sanitize(&mut self) {
+        self.input_count = self.input_count.min(SVGFE_MERGE_INPUTS_LIMIT as u32);
+    }
+
+    /// The inputs that are actually used by this primitive.
+    pub fn inputs(&self) -> &[FilterPrimitiveInput] {
+        &self.inputs[.. self.input_count as usize]
+    }
+}
+
+/// Draws an image into `rect`, in the local space of the filtered content.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize, PeekPoke)]
+pub struct ImagePrimitive {
+    pub image_key: ImageKey,
+    pub rect: LayoutRect,
+    pub rendering: ImageRendering,
+}
+
 /// See: https://github.com/eqrion/cbindgen/issues/9
 /// cbindgen:derive-eq=false
 #[repr(C)]
@@ -1099,6 +1579,16 @@ pub enum FilterPrimitiveKind {
     ComponentTransfer(ComponentTransferPrimitive),
     Offset(OffsetPrimitive),
     Composite(CompositePrimitive),
+    Turbulence(TurbulencePrimitive),
+    Morphology(MorphologyPrimitive),
+    /// cbindgen:derive-eq=false
+    ConvolveMatrix(ConvolveMatrixPrimitive),
+    DisplacementMap(DisplacementMapPrimitive),
+    DiffuseLighting(DiffuseLightingPrimitive),
+    SpecularLighting(SpecularLightingPrimitive),
+    Tile(TilePrimitive),
+    Merge(MergePrimitive),
+    Image(ImagePrimitive),
 }
 
 impl Default for FilterPrimitiveKind {
@@ -1112,6 +1602,12 @@ impl FilterPrimitiveKind {
         match self {
             FilterPrimitiveKind::Flood(flood) => flood.sanitize(),
             FilterPrimitiveKind::Opacity(opacity) => opacity.sanitize(),
+            FilterPrimitiveKind::Turbulence(turbulence) => turbulence.sanitize(),
+            FilterPrimitiveKind::Morphology(morphology) => morphology.sanitize(),
+            FilterPrimitiveKind::ConvolveMatrix(convolve) => convolve.sanitize(),
+            FilterPrimitiveKind::DiffuseLighting(lighting) => lighting.sanitize(),
+            FilterPrimitiveKind::SpecularLighting(lighting) => lighting.sanitize(),
+            FilterPrimitiveKind::Merge(merge) => merge.sanitize(),
 
             // No sanitization needed.
             FilterPrimitiveKind::Identity(..) |
@@ -1121,6 +1617,9 @@ impl FilterPrimitiveKind {
             FilterPrimitiveKind::Composite(..) |
             FilterPrimitiveKind::Blur(..) |
             FilterPrimitiveKind::DropShadow(..) |
+            FilterPrimitiveKind::DisplacementMap(..) |
+            FilterPrimitiveKind::Tile(..) |
+            FilterPrimitiveKind::Image(..) |
             // Component transfer's filter data is sanitized separately.
             FilterPrimitiveKind::ComponentTransfer(..) => {}
         }
@@ -1333,6 +1832,7 @@ pub struct YuvImageDisplayItem {
     pub color_depth: ColorDepth,
     pub color_space: YuvColorSpace,
     pub color_range: ColorRange,
+    pub transfer_function: YuvTransferFunction,
     pub image_rendering: ImageRendering,
 }
 
@@ -1352,6 +1852,18 @@ pub enum ColorRange {
     Full = 1,
 }
 
+/// The transfer function the RGB values of a YUV image are encoded with.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
+pub enum YuvTransferFunction {
+    /// The values are displayed as they are, like any other SDR content.
+    Sdr = 0,
+    /// SMPTE ST 2084 (PQ), usually paired with `YuvColorSpace::Rec2020`.
+    /// Decoding it produces values above 1.0, which are only preserved
+    /// when the renderer is in HDR mode.
+    Pq = 1,
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, MallocSizeOf, PartialEq, Serialize, PeekPoke)]
 pub enum YuvRangedColorSpace {
@@ -1693,6 +2205,8 @@ impl DisplayItem {
             DisplayItem::RectClip(..) => "rect_clip",
             DisplayItem::RoundedRectClip(..) => "rounded_rect_clip",
             DisplayItem::ImageMaskClip(..) => "image_mask_clip",
+            DisplayItem::PathClip(..) => "path_clip",
+            DisplayItem::TextClip(..) => "text_clip",
             DisplayItem::ClipChain(..) => "clip_chain",
             DisplayItem::ConicGradient(..) => "conic_gradient",
             DisplayItem::Gradient(..) => "gradient",
@@ -1700,6 +2214,7 @@ impl DisplayItem {
             DisplayItem::Image(..) => "image",
             DisplayItem::RepeatingImage(..) => "repeating_image",
             DisplayItem::Line(..) => "line",
+            DisplayItem::Path(..) => "path",
             DisplayItem::PopAllShadows => "pop_all_shadows",
             DisplayItem::PopReferenceFrame => "pop_reference_frame",
             DisplayItem::PopStackingContext => "pop_stacking_context",
@@ -1739,8 +2254,15 @@ macro_rules! impl_default_for_enums {
 impl_default_for_enums! {
     DisplayItem => PopStackingContext,
     ScrollSensitivity => ScriptAndInputEvents,
+    OverscrollBehavior => None,
+    ScrollSnapStrictness => Proximity,
+    ScrollSnapAlignment => None,
     LineOrientation => Vertical,
     LineStyle => Solid,
+    PathCommand => Close,
+    LineJoin => Miter,
+    LineCap => Butt,
+    PathBrush => Color(ColorF::BLACK),
     RepeatMode => Stretch,
     NinePatchBorderSource => Image(ImageKey::default()),
     BorderDetails => Normal(NormalBorder::default()),
@@ -1748,6 +2270,8 @@ impl_default_for_enums! {
     BorderStyle => None,
     BoxShadowClipMode => Outset,
     ExtendMode => Clamp,
+    HueInterpolationMethod => Shorter,
+    ColorInterpolationSpace => Srgb,
     FilterOp => Identity,
     ComponentTransferFuncType => Identity,
     ClipMode => Clip,
@@ -1766,9 +2290,15 @@ impl_default_for_enums! {
     YuvColorSpace => Rec601,
     YuvRangedColorSpace => Rec601Narrow,
     ColorRange => Limited,
+    YuvTransferFunction => Sdr,
     YuvData => NV12(ImageKey::default(), ImageKey::default()),
     YuvFormat => NV12,
     FilterPrimitiveInput => Original,
     ColorSpace => Srgb,
-    CompositeOperator => Over
+    CompositeOperator => Over,
+    TurbulenceType => FractalNoise,
+    MorphologyOperator => Erode,
+    EdgeMode => Duplicate,
+    ColorChannel => Alpha,
+    LightSource => Distant(DistantLight::default())
 }
@@ -33,10 +33,16 @@ impl From<DisplayItemRef<'_, '_>> for CachedDisplayItem {
         let item = item_ref.item();
 
         match item {
-            DisplayItem::Text(..) => CachedDisplayItem {
+            DisplayItem::Text(..) |
+            DisplayItem::TextClip(..) => CachedDisplayItem {
                 item: *item,
                 data: item_ref.glyphs().bytes().to_vec(),
             },
+            DisplayItem::Path(..) |
+            DisplayItem::PathClip(..) => CachedDisplayItem {
+                item: *item,
+                data: item_ref.path_commands().bytes().to_vec(),
+            },
             _ => CachedDisplayItem {
                 item: *item,
                 data: Vec::new(),
@@ -22,9 +22,9 @@ use crate::display_item as di;
 use crate::display_item_cache::*;
 use crate::{PipelineId, PropertyBinding};
 use crate::gradient_builder::GradientBuilder;
-use crate::color::ColorF;
+use crate::color::{ColorF, RgbColorSpace};
 use crate::font::{FontInstanceKey, GlyphInstance, GlyphOptions};
-use crate::image::{ColorDepth, ImageKey};
+use crate::image::{ColorDepth, ImageKey, SnapshotImageKey};
 use crate::units::*;
 
 
@@ -173,6 +173,13 @@ impl DisplayListWithCache {
         }
     }
 
+    /// Returns the display list without its item cache. Lists deserialized
+    /// from a capture have their cached items written inline, so nothing is
+    /// lost for them.
+    pub fn into_display_list(self) -> BuiltDisplayList {
+        self.display_list
+    }
+
     pub fn update(&mut self, display_list: BuiltDisplayList) {
         self.cache.update(&display_list);
         self.display_list = display_list;
@@ -234,11 +241,13 @@ pub struct BuiltDisplayListIter<'a> {
     cur_item: di::DisplayItem,
     cur_stops: ItemRange<'a, di::GradientStop>,
     cur_glyphs: ItemRange<'a, GlyphInstance>,
+    cur_path_commands: ItemRange<'a, di::PathCommand>,
     cur_filters: ItemRange<'a, di::FilterOp>,
     cur_filter_data: Vec<TempFilterData<'a>>,
     cur_filter_primitives: ItemRange<'a, di::FilterPrimitive>,
     cur_clip_chain_items: ItemRange<'a, di::ClipId>,
     cur_points: ItemRange<'a, LayoutPoint>,
+    cur_snap_areas: ItemRange<'a, di::ScrollSnapArea>,
     peeking: Peek,
     /// Should just be initialized but never populated in release builds
     debug_stats: DebugStats,
@@ -336,10 +345,18 @@ impl<'a, 'b> DisplayItemRef<'a, 'b> {
         self.iter.cur_points
     }
 
+    pub fn snap_areas(&self) -> ItemRange<di::ScrollSnapArea> {
+        self.iter.cur_snap_areas
+    }
+
     pub fn glyphs(&self) -> ItemRange<GlyphInstance> {
         self.iter.glyphs()
     }
 
+    pub fn path_commands(&self) -> ItemRange<di::PathCommand> {
+        self.iter.path_commands()
+    }
+
     pub fn gradient_stops(&self) -> ItemRange<di::GradientStop> {
         self.iter.gradient_stops()
     }
@@ -460,11 +477,26 @@ impl BuiltDisplayList {
                     v,
                     item.iter.cur_clip_chain_items.iter().collect()
                 ),
-                Real::ScrollFrame(v) => Debug::ScrollFrame(v),
+                Real::ScrollFrame(v) => Debug::ScrollFrame(
+                    v,
+                    item.iter.cur_snap_areas.iter().collect()
+                ),
                 Real::Text(v) => Debug::Text(
                     v,
                     item.iter.cur_glyphs.iter().collect()
                 ),
+                Real::Path(v) => Debug::Path(
+                    v,
+                    item.iter.cur_path_commands.iter().collect()
+                ),
+                Real::PathClip(v) => Debug::PathClip(
+                    v,
+                    item.iter.cur_path_commands.iter().collect()
+                ),
+                Real::TextClip(v) => Debug::TextClip(
+                    v,
+                    item.iter.cur_glyphs.iter().collect()
+                ),
                 Real::SetFilterOps => Debug::SetFilterOps(
                     item.iter.cur_filters.iter().collect()
                 ),
@@ -561,11 +593,13 @@ impl<'a> BuiltDisplayListIter<'a> {
             cur_item: di::DisplayItem::PopStackingContext,
             cur_stops: ItemRange::default(),
             cur_glyphs: ItemRange::default(),
+            cur_path_commands: ItemRange::default(),
             cur_filters: ItemRange::default(),
             cur_filter_data: Vec::new(),
             cur_filter_primitives: ItemRange::default(),
             cur_clip_chain_items: ItemRange::default(),
             cur_points: ItemRange::default(),
+            cur_snap_areas: ItemRange::default(),
             peeking: Peek::NotPeeking,
             debug_stats: DebugStats {
                 last_addr: data.as_ptr() as usize,
@@ -607,6 +641,10 @@ impl<'a> BuiltDisplayListIter<'a> {
         self.cached_item_range_or(self.cur_glyphs)
     }
 
+    pub fn path_commands(&self) -> ItemRange<di::PathCommand> {
+        self.cached_item_range_or(self.cur_path_commands)
+    }
+
     pub fn gradient_stops(&self) -> ItemRange<di::GradientStop> {
         self.cached_item_range_or(self.cur_stops)
     }
@@ -634,6 +672,7 @@ impl<'a> BuiltDisplayListIter<'a> {
         self.cur_stops = ItemRange::default();
         self.cur_clip_chain_items = ItemRange::default();
         self.cur_points = ItemRange::default();
+        self.cur_snap_areas = ItemRange::default();
         self.cur_filters = ItemRange::default();
         self.cur_filter_primitives = ItemRange::default();
         self.cur_filter_data.clear();
@@ -715,10 +754,26 @@ impl<'a> BuiltDisplayListIter<'a> {
                 self.cur_clip_chain_items = skip_slice::<di::ClipId>(&mut self.data);
                 self.debug_stats.log_slice("clip_chain.clip_ids", &self.cur_clip_chain_items);
             }
+            ScrollFrame(_) => {
+                self.cur_snap_areas = skip_slice::<di::ScrollSnapArea>(&mut self.data);
+                self.debug_stats.log_slice("scroll_frame.snap_areas", &self.cur_snap_areas);
+            }
             Text(_) => {
                 self.cur_glyphs = skip_slice::<GlyphInstance>(&mut self.data);
                 self.debug_stats.log_slice("text.glyphs", &self.cur_glyphs);
             }
+            Path(_) => {
+                self.cur_path_commands = skip_slice::<di::PathCommand>(&mut self.data);
+                self.debug_stats.log_slice("path.commands", &self.cur_path_commands);
+            }
+            PathClip(_) => {
+                self.cur_path_commands = skip_slice::<di::PathCommand>(&mut self.data);
+                self.debug_stats.log_slice("path_clip.commands", &self.cur_path_commands);
+            }
+            TextClip(_) => {
+                self.cur_glyphs = skip_slice::<GlyphInstance>(&mut self.data);
+                self.debug_stats.log_slice("text_clip.glyphs", &self.cur_glyphs);
+            }
             ReuseItems(key) => {
                 match self.cache {
                     Some(cache) => {
@@ -869,7 +924,8 @@ impl<'de> Deserialize<'de> for BuiltDisplayList {
                     DisplayListBuilder::push_iter_impl(&mut temp, clip_chain_ids);
                     Real::ClipChain(v)
                 }
-                Debug::ScrollFrame(v) => {
+                Debug::ScrollFrame(v, snap_areas) => {
+                    DisplayListBuilder::push_iter_impl(&mut temp, snap_areas);
                     total_spatial_nodes += 1;
                     total_clip_nodes += 1;
                     Real::ScrollFrame(v)
@@ -882,6 +938,18 @@ impl<'de> Deserialize<'de> for BuiltDisplayList {
                     DisplayListBuilder::push_iter_impl(&mut temp, glyphs);
                     Real::Text(v)
                 },
+                Debug::Path(v, commands) => {
+                    DisplayListBuilder::push_iter_impl(&mut temp, commands);
+                    Real::Path(v)
+                },
+                Debug::PathClip(v, commands) => {
+                    DisplayListBuilder::push_iter_impl(&mut temp, commands);
+                    Real::PathClip(v)
+                },
+                Debug::TextClip(v, glyphs) => {
+                    DisplayListBuilder::push_iter_impl(&mut temp, glyphs);
+                    Real::TextClip(v)
+                },
                 Debug::Iframe(v) => {
                     total_clip_nodes += 1;
                     Real::Iframe(v)
@@ -1219,12 +1287,12 @@ impl DisplayListBuilder {
         bounds: LayoutRect,
         color: ColorF,
     ) {
-        let item = di::DisplayItem::Rectangle(di::RectangleDisplayItem {
-            common: *common,
-            color: PropertyBinding::Value(color),
+        self.push_rect_with_color_space(
+            common,
             bounds,
-        });
-        self.push_item(&item);
+            PropertyBinding::Value(color),
+            RgbColorSpace::Srgb,
+        );
     }
 
     pub fn push_rect_with_animation(
@@ -1232,11 +1300,26 @@ impl DisplayListBuilder {
         common: &di::CommonItemProperties,
         bounds: LayoutRect,
         color: PropertyBinding<ColorF>,
+    ) {
+        self.push_rect_with_color_space(common, bounds, color, RgbColorSpace::Srgb);
+    }
+
+    /// Push a rectangle whose color is specified in the given color space.
+    ///
+    /// Animated colors supplied through `DynamicProperties` for the binding
+    /// are specified in the same color space.
+    pub fn push_rect_with_color_space(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        color: PropertyBinding<ColorF>,
+        color_space: RgbColorSpace,
     ) {
         let item = di::DisplayItem::Rectangle(di::RectangleDisplayItem {
             common: *common,
             color,
             bounds,
+            color_space,
         });
         self.push_item(&item);
     }
@@ -1286,6 +1369,31 @@ impl DisplayListBuilder {
         self.push_item(&item);
     }
 
+    /// Pushes a vector path. The path is filled using `fill_rule`, or
+    /// stroked if `stroke` is supplied. Gradient brushes must be created with
+    /// the `create_*gradient` methods immediately before this call, since
+    /// those store the stops in anticipation.
+    pub fn push_path(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        commands: &[di::PathCommand],
+        fill_rule: di::FillRule,
+        stroke: Option<di::PathStroke>,
+        brush: di::PathBrush,
+    ) {
+        let item = di::DisplayItem::Path(di::PathDisplayItem {
+            common: *common,
+            bounds,
+            fill_rule,
+            stroke,
+            brush,
+        });
+
+        self.push_item(&item);
+        self.push_iter(commands);
+    }
+
     pub fn push_image(
         &mut self,
         common: &di::CommonItemProperties,
@@ -1342,6 +1450,29 @@ impl DisplayListBuilder {
         color_space: di::YuvColorSpace,
         color_range: di::ColorRange,
         image_rendering: di::ImageRendering,
+    ) {
+        self.push_yuv_image_with_transfer_function(
+            common,
+            bounds,
+            yuv_data,
+            color_depth,
+            color_space,
+            color_range,
+            di::YuvTransferFunction::Sdr,
+            image_rendering,
+        );
+    }
+
+    pub fn push_yuv_image_with_transfer_function(
+        &mut self,
+        common: &di::CommonItemProperties,
+        bounds: LayoutRect,
+        yuv_data: di::YuvData,
+        color_depth: ColorDepth,
+        color_space: di::YuvColorSpace,
+        color_range: di::ColorRange,
+        transfer_function: di::YuvTransferFunction,
+        image_rendering: di::ImageRendering,
     ) {
         let item = di::DisplayItem::YuvImage(di::YuvImageDisplayItem {
             common: *common,
@@ -1350,6 +1481,7 @@ impl DisplayListBuilder {
             color_depth,
             color_space,
             color_range,
+            transfer_function,
             image_rendering,
         });
         self.push_item(&item);
@@ -1618,6 +1750,44 @@ impl DisplayListBuilder {
         filter_primitives: &[di::FilterPrimitive],
         raster_space: di::RasterSpace,
         flags: di::StackingContextFlags,
+    ) {
+        self.push_stacking_context_with_snapshot(
+            origin,
+            spatial_id,
+            prim_flags,
+            clip_id,
+            transform_style,
+            mix_blend_mode,
+            filters,
+            filter_datas,
+            filter_primitives,
+            raster_space,
+            flags,
+            None,
+        );
+    }
+
+    /// Push a stacking context whose contents are also rendered into the
+    /// given snapshot image, which image display items can then refer to.
+    ///
+    /// The snapshot covers the visible area of the stacking context, and is
+    /// only updated on frames where the stacking context is rendered. Images
+    /// drawn after the stacking context in the same frame show its new
+    /// contents.
+    pub fn push_stacking_context_with_snapshot(
+        &mut self,
+        origin: LayoutPoint,
+        spatial_id: di::SpatialId,
+        prim_flags: di::PrimitiveFlags,
+        clip_id: Option<di::ClipId>,
+        transform_style: di::TransformStyle,
+        mix_blend_mode: di::MixBlendMode,
+        filters: &[di::FilterOp],
+        filter_datas: &[di::FilterData],
+        filter_primitives: &[di::FilterPrimitive],
+        raster_space: di::RasterSpace,
+        flags: di::StackingContextFlags,
+        snapshot: Option<SnapshotImageKey>,
     ) {
         self.push_filters(filters, filter_datas, filter_primitives);
 
@@ -1631,6 +1801,7 @@ impl DisplayListBuilder {
                 clip_id,
                 raster_space,
                 flags,
+                snapshot,
             },
         });
 
@@ -1758,6 +1929,54 @@ impl DisplayListBuilder {
         clip_rect: LayoutRect,
         scroll_sensitivity: di::ScrollSensitivity,
         external_scroll_offset: LayoutVector2D,
+    ) -> di::SpaceAndClipInfo {
+        self.define_scroll_frame_with_overscroll(
+            parent_space_and_clip,
+            external_id,
+            content_rect,
+            clip_rect,
+            scroll_sensitivity,
+            external_scroll_offset,
+            di::OverscrollBehavior::None,
+        )
+    }
+
+    pub fn define_scroll_frame_with_overscroll(
+        &mut self,
+        parent_space_and_clip: &di::SpaceAndClipInfo,
+        external_id: di::ExternalScrollId,
+        content_rect: LayoutRect,
+        clip_rect: LayoutRect,
+        scroll_sensitivity: di::ScrollSensitivity,
+        external_scroll_offset: LayoutVector2D,
+        overscroll_behavior: di::OverscrollBehavior,
+    ) -> di::SpaceAndClipInfo {
+        self.define_scroll_frame_with_snapping(
+            parent_space_and_clip,
+            external_id,
+            content_rect,
+            clip_rect,
+            scroll_sensitivity,
+            external_scroll_offset,
+            overscroll_behavior,
+            di::ScrollSnapStrictness::Proximity,
+            &[],
+        )
+    }
+
+    /// Define a scroll frame which snaps to the given areas of its content
+    /// once a scroll gesture ends.
+    pub fn define_scroll_frame_with_snapping(
+        &mut self,
+        parent_space_and_clip: &di::SpaceAndClipInfo,
+        external_id: di::ExternalScrollId,
+        content_rect: LayoutRect,
+        clip_rect: LayoutRect,
+        scroll_sensitivity: di::ScrollSensitivity,
+        external_scroll_offset: LayoutVector2D,
+        overscroll_behavior: di::OverscrollBehavior,
+        snap_strictness: di::ScrollSnapStrictness,
+        snap_areas: &[di::ScrollSnapArea],
     ) -> di::SpaceAndClipInfo {
         let clip_id = self.generate_clip_index();
         let scroll_frame_id = self.generate_spatial_index();
@@ -1770,9 +1989,12 @@ impl DisplayListBuilder {
             external_id,
             scroll_sensitivity,
             external_scroll_offset,
+            overscroll_behavior,
+            snap_strictness,
         });
 
         self.push_item(&item);
+        self.push_iter(snap_areas);
 
         di::SpaceAndClipInfo {
             spatial_id: scroll_frame_id,
@@ -1822,6 +2044,53 @@ impl DisplayListBuilder {
         id
     }
 
+    /// Defines a clip to the interior of a path. Commands are in the same
+    /// space as `rect`, and anything outside of `rect` is clipped out.
+    pub fn define_clip_path(
+        &mut self,
+        parent_space_and_clip: &di::SpaceAndClipInfo,
+        rect: LayoutRect,
+        commands: &[di::PathCommand],
+        fill_rule: di::FillRule,
+    ) -> di::ClipId {
+        let id = self.generate_clip_index();
+        let item = di::DisplayItem::PathClip(di::PathClipDisplayItem {
+            id,
+            parent_space_and_clip: *parent_space_and_clip,
+            rect,
+            fill_rule,
+        });
+
+        self.push_item(&item);
+        self.push_iter(commands);
+        id
+    }
+
+    /// Defines a clip to the coverage of a run of glyphs, e.g. to fill text
+    /// with a gradient or an image. Glyphs are in the same space as `rect`,
+    /// and anything outside of `rect` is clipped out.
+    pub fn define_clip_text(
+        &mut self,
+        parent_space_and_clip: &di::SpaceAndClipInfo,
+        rect: LayoutRect,
+        glyphs: &[GlyphInstance],
+        font_key: FontInstanceKey,
+        glyph_options: Option<GlyphOptions>,
+    ) -> di::ClipId {
+        let id = self.generate_clip_index();
+        let item = di::DisplayItem::TextClip(di::TextClipDisplayItem {
+            id,
+            parent_space_and_clip: *parent_space_and_clip,
+            rect,
+            font_key,
+            glyph_options,
+        });
+
+        self.push_item(&item);
+        self.push_iter(glyphs);
+        id
+    }
+
     pub fn define_clip_rect(
         &mut self,
         parent_space_and_clip: &di::SpaceAndClipInfo,
@@ -20,7 +20,7 @@ use std::collections::HashMap;
 // local imports
 use crate::IdNamespace;
 use crate::channel::Sender;
-use crate::color::ColorU;
+use crate::color::{ColorF, ColorU};
 use crate::units::LayoutPoint;
 
 /// Hashable floating-point storage for font size.
@@ -338,11 +338,77 @@ impl Hash for FontVariation {
     }
 }
 
+/// Whether the fill or the stroke of text is painted first, as the SVG
+/// paint-order property.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, Hash, Eq, PartialEq, PartialOrd, Ord, Serialize, PeekPoke)]
+pub enum TextPaintOrder {
+    FillStroke,
+    StrokeFill,
+}
+
+impl Default for TextPaintOrder {
+    fn default() -> Self {
+        TextPaintOrder::FillStroke
+    }
+}
+
+/// Outlines the glyphs of a text item, as `-webkit-text-stroke`. The stroke
+/// is centered on the outlines of the glyphs, and painted in addition to
+/// the fill, which can be made transparent to only paint the stroke.
+///
+/// Strokes are rasterized with FreeType and Core Text. DirectWrite and bitmap
+/// glyphs have no stroked rasterization, so on Windows, and for bitmap fonts,
+/// only the fill is painted. Callers that need a stroke there should draw it
+/// themselves, e.g. as a path.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PeekPoke)]
+pub struct TextStroke {
+    /// The width of the stroke, in layout pixels.
+    pub width: f32,
+    pub color: ColorF,
+    pub paint_order: TextPaintOrder,
+}
+
+impl TextStroke {
+    fn to_bits(&self) -> (u32, [u32; 4], TextPaintOrder) {
+        let ColorF { r, g, b, a } = self.color;
+        (self.width.to_bits(), [r.to_bits(), g.to_bits(), b.to_bits(), a.to_bits()], self.paint_order)
+    }
+}
+
+impl PartialEq for TextStroke {
+    fn eq(&self, other: &TextStroke) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl Eq for TextStroke {}
+
+impl PartialOrd for TextStroke {
+    fn partial_cmp(&self, other: &TextStroke) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TextStroke {
+    fn cmp(&self, other: &TextStroke) -> Ordering {
+        self.to_bits().cmp(&other.to_bits())
+    }
+}
+
+impl Hash for TextStroke {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state);
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Deserialize, Hash, Eq, PartialEq, PartialOrd, Ord, Serialize, PeekPoke)]
 pub struct GlyphOptions {
     pub render_mode: FontRenderMode,
     pub flags: FontInstanceFlags,
+    pub stroke: Option<TextStroke>,
 }
 
 impl Default for GlyphOptions {
@@ -350,6 +416,7 @@ impl Default for GlyphOptions {
         GlyphOptions {
             render_mode: FontRenderMode::Subpixel,
             flags: FontInstanceFlags::empty(),
+            stroke: None,
         }
     }
 }
@@ -367,6 +434,7 @@ bitflags! {
         const FLIP_Y            = 1 << 6;
         const SUBPIXEL_POSITION = 1 << 7;
         const VERTICAL          = 1 << 8;
+        const DISTANCE_FIELD    = 1 << 9;
 
         // Internal flags
         const TRANSFORM_GLYPHS  = 1 << 12;
@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::color::RgbColorSpace;
 use crate::display_item as di;
 use crate::units::*;
 
@@ -11,6 +12,8 @@ use crate::units::*;
 /// Each gradient needs at least two stops.
 pub struct GradientBuilder {
     stops: Vec<di::GradientStop>,
+    interpolation_space: di::ColorInterpolationSpace,
+    color_space: RgbColorSpace,
 }
 
 impl GradientBuilder {
@@ -18,12 +21,29 @@ impl GradientBuilder {
     pub fn new() -> Self {
         GradientBuilder {
             stops: Vec::new(),
+            interpolation_space: di::ColorInterpolationSpace::Srgb,
+            color_space: RgbColorSpace::Srgb,
         }
     }
 
     /// Create a gradient builder with a list of stops.
     pub fn with_stops(stops: Vec<di::GradientStop>) -> GradientBuilder {
-        GradientBuilder { stops }
+        GradientBuilder {
+            stops,
+            interpolation_space: di::ColorInterpolationSpace::Srgb,
+            color_space: RgbColorSpace::Srgb,
+        }
+    }
+
+    /// Set the color space the stops are interpolated in. Defaults to sRGB.
+    pub fn set_interpolation_space(&mut self, interpolation_space: di::ColorInterpolationSpace) {
+        self.interpolation_space = interpolation_space;
+    }
+
+    /// Set the color space the colors of the stops are specified in.
+    /// Defaults to sRGB.
+    pub fn set_color_space(&mut self, color_space: RgbColorSpace) {
+        self.color_space = color_space;
     }
 
     /// Push an additional stop for the gradient.
@@ -55,6 +75,8 @@ impl GradientBuilder {
             start_point: start_point + start_to_end * start_offset,
             end_point: start_point + start_to_end * end_offset,
             extend_mode,
+            interpolation_space: self.interpolation_space,
+            color_space: self.color_space,
         }
     }
 
@@ -84,6 +106,8 @@ impl GradientBuilder {
                 start_offset: 0.0,
                 end_offset: 1.0,
                 extend_mode,
+                interpolation_space: self.interpolation_space,
+                color_space: self.color_space,
             };
         }
 
@@ -96,6 +120,8 @@ impl GradientBuilder {
             start_offset,
             end_offset,
             extend_mode,
+            interpolation_space: self.interpolation_space,
+            color_space: self.color_space,
         }
     }
 
@@ -115,6 +141,8 @@ impl GradientBuilder {
             start_offset,
             end_offset,
             extend_mode,
+            interpolation_space: self.interpolation_space,
+            color_space: self.color_space,
         }
     }
 
@@ -9,7 +9,7 @@ use peek_poke::PeekPoke;
 use std::ops::{Add, Sub};
 use std::sync::Arc;
 // local imports
-use crate::{IdNamespace, TileSize};
+use crate::{IdNamespace, RgbColorSpace, TileSize};
 use crate::display_item::ImageRendering;
 use crate::font::{FontInstanceKey, FontInstanceData, FontKey, FontTemplate};
 use crate::units::*;
@@ -55,6 +55,20 @@ impl BlobImageKey {
     }
 }
 
+/// An opaque identifier describing a snapshot image registered with WebRender.
+/// The contents of a snapshot image are rendered by WebRender from a stacking
+/// context, and the snapshot can be used as an image in display items.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize, PeekPoke)]
+pub struct SnapshotImageKey(pub ImageKey);
+
+impl SnapshotImageKey {
+    /// Interpret this snapshot image as an image for a display item.
+    pub fn as_image(self) -> ImageKey {
+        self.0
+    }
+}
+
 /// An arbitrary identifier for an external image provided by the
 /// application. It must be a unique identifier for each external
 /// image.
@@ -168,6 +182,13 @@ pub enum ImageFormat {
     RGBAI32 = 7,
     /// Four channels, byte storage.
     RGBA8 = 8,
+    /// Four channels, half-float storage.
+    RGBA16F = 9,
+    /// One channel, half-float storage.
+    R16F = 10,
+    /// Three 10-bit color channels and a 2-bit alpha channel, packed in
+    /// 32 bits with red in the least significant bits.
+    RGB10A2 = 11,
 }
 
 impl ImageFormat {
@@ -182,6 +203,9 @@ impl ImageFormat {
             ImageFormat::RG16 => 4,
             ImageFormat::RGBAI32 => 16,
             ImageFormat::RGBA8 => 4,
+            ImageFormat::RGBA16F => 8,
+            ImageFormat::R16F => 2,
+            ImageFormat::RGB10A2 => 4,
         }
     }
 }
@@ -264,6 +288,9 @@ pub struct ImageDescriptor {
     pub offset: i32,
     /// Various bool flags related to this descriptor.
     pub flags: ImageDescriptorFlags,
+    /// The color space of the image data. Images are converted to the output
+    /// color space of the renderer when they are drawn.
+    pub color_space: RgbColorSpace,
 }
 
 impl ImageDescriptor {
@@ -280,6 +307,7 @@ impl ImageDescriptor {
             stride: None,
             offset: 0,
             flags,
+            color_space: RgbColorSpace::Srgb,
         }
     }
 
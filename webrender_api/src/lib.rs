@@ -39,6 +39,7 @@ extern crate time;
 extern crate malloc_size_of;
 extern crate peek_poke;
 
+mod animation;
 pub mod channel;
 mod color;
 mod display_item;
@@ -49,6 +50,7 @@ mod gradient_builder;
 mod image;
 pub mod units;
 
+pub use crate::animation::*;
 pub use crate::color::*;
 pub use crate::display_item::*;
 pub use crate::display_item_cache::DisplayItemCache;
@@ -211,6 +213,16 @@ pub trait RenderNotifier: Send {
     fn external_event(&self, _evt: ExternalEvent) {
         unimplemented!()
     }
+    /// Notify the thread containing the `Renderer` that the animation of a property
+    /// binding, registered with `Transaction::add_animation`, has finished.
+    fn animation_finished(&self, _: DocumentId, _: PropertyBindingId) {}
+    /// Notify the thread containing the `Renderer` that the document has animations
+    /// in progress. Animations only advance when frames are generated, so another
+    /// frame should be generated, for example on the next vsync.
+    fn request_animation_frame(&self, _: DocumentId) {}
+    /// Notify the thread containing the `Renderer` that the overscroll of a scroll
+    /// frame changed, for example to implement pull-to-refresh.
+    fn overscroll(&self, _: DocumentId, _: ExternalScrollId, _: OverscrollEvent) {}
     /// Notify the thread containing the `Renderer` that the render backend has been
     /// shut down.
     fn shut_down(&self) {}
@@ -279,6 +291,38 @@ pub trait ApiHitTester: Send + Sync {
     /// of hit results will contain all display items that match, ordered from
     /// front to back.
     fn hit_test(&self, pipeline_id: Option<PipelineId>, point: WorldPoint) -> HitTestResult;
+
+    /// Does a hit test on display items in the specified document, returning every
+    /// item that intersects the given rect, ordered from front to back. Items are
+    /// filtered by pipeline as for `hit_test`, and clipped as for `hit_test_polygon`.
+    ///
+    /// The default implementation hit tests the corners of the rect with
+    /// `hit_test_polygon`.
+    fn hit_test_rect(&self, pipeline_id: Option<PipelineId>, rect: WorldRect) -> HitTestRegionResult {
+        let points = [
+            rect.min,
+            WorldPoint::new(rect.max.x, rect.min.y),
+            rect.max,
+            WorldPoint::new(rect.min.x, rect.max.y),
+        ];
+        self.hit_test_polygon(pipeline_id, &points)
+    }
+
+    /// Does a hit test on display items in the specified document, returning every
+    /// item that intersects the given polygon, ordered from front to back. The
+    /// polygon may be concave, and is filled using the even-odd rule. Polygons
+    /// with no area, and items that only touch the polygon along an edge, are not
+    /// hit. Items are filtered by pipeline as for `hit_test`.
+    ///
+    /// Unlike `hit_test`, clips are approximated by their bounding rects, and clip
+    /// outs are ignored, so an item can be hit outside its clip, or where it is
+    /// clipped out.
+    ///
+    /// The default implementation, for hit testers that don't support region
+    /// queries, hits no items. WebRender's own hit tester implements it.
+    fn hit_test_polygon(&self, _pipeline_id: Option<PipelineId>, _points: &[WorldPoint]) -> HitTestRegionResult {
+        HitTestRegionResult::default()
+    }
 }
 
 /// A hit tester requested to the render backend thread but not necessarily ready yet.
@@ -322,6 +366,48 @@ pub struct HitTestResult {
     pub items: Vec<HitTestItem>,
 }
 
+/// How much of a display item lies within the area of a region hit-test query.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum HitTestCoverage {
+    /// The item is entirely inside the area.
+    Full,
+    /// The item is partly outside the area.
+    Partial,
+}
+
+/// Describe an item that intersects the area of a region hit-test query.
+///
+/// Rounded corners and paths of the clips of the item are approximated by their
+/// bounding rect.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct HitTestRegionItem {
+    /// The pipeline that the display item that was hit belongs to.
+    pub pipeline: PipelineId,
+
+    /// The tag of the hit display item. Display items sharing a tag are reported
+    /// as a single item.
+    pub tag: ItemTag,
+
+    /// Whether the clipped display item lies entirely inside the area of the
+    /// query, or only partly. Items sharing a tag are partly covered if any of
+    /// them is.
+    pub coverage: HitTestCoverage,
+
+    /// The bounding box of the clipped display item, in world space.
+    pub world_bounds: WorldRect,
+
+    /// The scroll frames the display item belongs to, from the innermost to the
+    /// outermost.
+    pub scroll_ids: Vec<ExternalScrollId>,
+}
+
+/// Returned by `ApiHitTester::hit_test_rect` and `ApiHitTester::hit_test_polygon`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct HitTestRegionResult {
+    /// List of items that intersect the area of the hit-test query.
+    pub items: Vec<HitTestRegionItem>,
+}
+
 impl Drop for NotificationRequest {
     fn drop(&mut self) {
         if let Some(ref mut handler) = self.handler {
@@ -582,6 +668,37 @@ pub struct ScrollNodeState {
     pub id: ExternalScrollId,
     ///
     pub scroll_offset: LayoutVector2D,
+    /// The scroll offset that an in-flight smooth scroll or fling is heading
+    /// to, if any.
+    pub animation_target: Option<LayoutVector2D>,
+    /// How far the scroll frame is scrolled past the edges of its scroll range,
+    /// not included in `scroll_offset`.
+    pub overscroll: LayoutVector2D,
+    /// The snap areas the scroll frame snapped to, or is snapping to, at the
+    /// end of the last scroll gesture.
+    pub snap_target: Option<ScrollSnapTarget>,
+}
+
+/// The snap areas a scroll frame is aligned with, as indices in the list of
+/// snap areas of its `ScrollFrameDisplayItem`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScrollSnapTarget {
+    /// The snap area aligned horizontally, if any.
+    pub x: Option<usize>,
+    /// The snap area aligned vertically, if any.
+    pub y: Option<usize>,
+}
+
+/// A change in the overscroll of a scroll frame, see `OverscrollBehavior`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverscrollEvent {
+    /// The scroll frame was scrolled past an edge of its scroll range.
+    Started,
+    /// The overscroll distance changed, positive values being past the end
+    /// of the scroll range.
+    Changed(LayoutVector2D),
+    /// The scroll frame came back to rest within its scroll range.
+    Ended,
 }
 
 ///
@@ -595,6 +712,20 @@ pub enum ScrollLocation {
     End,
 }
 
+/// How programmatic scrolls reach their destination.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ScrollBehavior {
+    /// Jump to the destination.
+    Instant,
+    /// Animate to the destination on the render backend.
+    Smooth {
+        /// In milliseconds.
+        duration: f32,
+        ///
+        easing: TimingFunction,
+    },
+}
+
 /// Crash annotations included in crash reports.
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -67,6 +67,7 @@ pub fn get_shader_features(flags: ShaderFeatureFlags) -> ShaderFeatures {
     shaders.insert("cs_clip_rectangle", vec![String::new(), "FAST_PATH".to_string()]);
     shaders.insert("cs_clip_image", vec!["TEXTURE_2D".to_string()]);
     shaders.insert("cs_clip_box_shadow", vec!["TEXTURE_2D".to_string()]);
+    shaders.insert("cs_clip_path", vec![String::new()]);
 
     // Cache shaders
     shaders.insert("cs_blur", vec!["ALPHA_TARGET".to_string(), "COLOR_TARGET".to_string()]);
@@ -77,6 +78,8 @@ pub fn get_shader_features(flags: ShaderFeatureFlags) -> ShaderFeatures {
         "cs_border_segment",
         "cs_border_solid",
         "cs_svg_filter",
+        "cs_path",
+        "cs_text_mask",
     ] {
         shaders.insert(name, vec![String::new()]);
     }
@@ -199,6 +202,16 @@ pub fn get_shader_features(flags: ShaderFeatureFlags) -> ShaderFeatures {
         list.add("FAST_PATH");
         composite_features.push(list.finish());
     }
+
+    // Tone mapping composite shaders, for HDR mode
+    for texture_type in &composite_texture_types {
+        let mut list = FeatureList::new();
+        if !texture_type.is_empty() {
+            list.add(texture_type);
+        }
+        list.add("TONE_MAP");
+        composite_features.push(list.finish());
+    }
     shaders.insert("composite", composite_features);
 
     // Prim shaders
@@ -215,6 +228,7 @@ pub fn get_shader_features(flags: ShaderFeatureFlags) -> ShaderFeatures {
         let mut alpha_list = list.with("ALPHA_PASS");
         text_features.push(alpha_list.finish());
         text_features.push(alpha_list.with("GLYPH_TRANSFORM").finish());
+        text_features.push(alpha_list.with("SDF").finish());
         text_features.push(list.with("DEBUG_OVERDRAW").finish());
     }
     shaders.insert("ps_text_run", text_features);
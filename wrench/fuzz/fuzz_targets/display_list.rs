@@ -0,0 +1,128 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Renders a display list generated from the fuzzer input with SWGL, so that
+// libFuzzer can guide the scenes towards new paths through scene and frame
+// building. The scenes are the same as `wrench fuzz` generates.
+
+#![no_main]
+
+#[macro_use]
+extern crate serde;
+
+// Minimizing crashes is left to libFuzzer, so the editing functions are unused.
+#[allow(dead_code)]
+#[path = "../../src/fuzz_scene.rs"]
+mod fuzz_scene;
+
+use crate::fuzz_scene::FuzzScene;
+use libfuzzer_sys::fuzz_target;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use webrender::api::*;
+use webrender::api::units::*;
+use webrender::render_api::*;
+
+const SIZE: i32 = 512;
+
+struct Notifier {
+    tx: Sender<()>,
+}
+
+impl RenderNotifier for Notifier {
+    fn clone(&self) -> Box<dyn RenderNotifier> {
+        Box::new(Notifier {
+            tx: self.tx.clone(),
+        })
+    }
+
+    fn wake_up(&self, _composite_needed: bool) {}
+
+    fn new_frame_ready(&self,
+                       _: DocumentId,
+                       _scrolled: bool,
+                       _composite_needed: bool,
+                       _render_time: Option<u64>) {
+        self.tx.send(()).unwrap();
+    }
+}
+
+/// A renderer that is created for the first input, and reused for the rest.
+/// Each input renders in a fresh document, and the caches are cleared after
+/// it, so that a crash doesn't depend on the inputs before it.
+struct Fuzzer {
+    _context: swgl::Context,
+    renderer: webrender::Renderer,
+    api: RenderApi,
+    rx: Receiver<()>,
+}
+
+impl Fuzzer {
+    fn new() -> Self {
+        let context = swgl::Context::create();
+        context.make_current();
+        context.init_default_framebuffer(0, 0, SIZE, SIZE, 0, std::ptr::null_mut());
+
+        let (tx, rx) = channel();
+        let opts = webrender::RendererOptions {
+            testing: true,
+            clear_caches_with_quads: false,
+            allow_advanced_blend_equation: true,
+            ..Default::default()
+        };
+        let (renderer, sender) = webrender::Renderer::new(
+            Rc::new(context.clone()),
+            Box::new(Notifier { tx }),
+            opts,
+            None,
+        ).unwrap();
+
+        let api = sender.create_api();
+
+        Fuzzer {
+            _context: context,
+            renderer,
+            api,
+            rx,
+        }
+    }
+
+    fn render(&mut self, scene: &FuzzScene) {
+        let mut builder = DisplayListBuilder::new(PipelineId(0, 0));
+        scene.build(&mut builder);
+
+        let document_id = self.api.add_document(DeviceIntSize::new(SIZE, SIZE));
+
+        let mut txn = Transaction::new();
+        txn.set_root_pipeline(PipelineId(0, 0));
+        txn.set_display_list(
+            Epoch(0),
+            Some(ColorF::new(1.0, 1.0, 1.0, 1.0)),
+            LayoutSize::new(SIZE as f32, SIZE as f32),
+            builder.finalize(),
+            false,
+        );
+        txn.generate_frame(0);
+        self.api.send_transaction(document_id, txn);
+
+        self.rx.recv().unwrap();
+        self.renderer.update();
+        self.renderer.render(DeviceIntSize::new(SIZE, SIZE), 0).unwrap();
+
+        self.api.delete_document(document_id);
+        self.api.send_debug_cmd(DebugCommand::ClearCaches(ClearCache::all()));
+    }
+}
+
+thread_local! {
+    static FUZZER: RefCell<Option<Fuzzer>> = RefCell::new(None);
+}
+
+fuzz_target!(|data: &[u8]| {
+    let scene = FuzzScene::generate(data);
+    FUZZER.with(|fuzzer| {
+        fuzzer.borrow_mut().get_or_insert_with(Fuzzer::new).render(&scene);
+    });
+});
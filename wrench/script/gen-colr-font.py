@@ -0,0 +1,205 @@
+# This Source Code Form is subject to the terms of the Mozilla Public
+# License, v. 2.0. If a copy of the MPL was not distributed with this
+# file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+# Generates reftests/text/colr.ttf, a font with COLR color glyphs made of
+# squares, so that the reftests can compare them to rects.
+#
+# Glyphs, at 1000 units per em:
+#   1: a square from 100 to 900
+#   2: a square from 300 to 700
+#   3: a rect from (100, 300) to (900, 700)
+#   4: COLRv0 layers, glyph 1 in red and glyph 2 in blue
+#   5: COLRv1 layers, glyph 1 in the text color and glyph 2 in green
+#   6: COLRv1 glyph 1 in red, scaled by 0.5 around the center
+#   7: COLRv1 glyph 3 in blue, rotated by 90 degrees around the center
+#   8: COLRv1 glyph 1 in red, with glyph 2 cut out of it with DEST_OUT
+
+import struct
+
+UNITS_PER_EM = 1000
+RED, BLUE, GREEN = 0, 1, 2
+FOREGROUND = 0xFFFF
+
+
+def rect_glyph(x0, y0, x1, y1):
+    points = [(x0, y0), (x0, y1), (x1, y1), (x1, y0)]
+    data = struct.pack(">hhhhhH", 1, x0, y0, x1, y1, 3) + struct.pack(">H", 0)
+    data += bytes([1] * len(points))
+    last = (0, 0)
+    xs, ys = b"", b""
+    for (x, y) in points:
+        xs += struct.pack(">h", x - last[0])
+        ys += struct.pack(">h", y - last[1])
+        last = (x, y)
+    return data + xs + ys
+
+
+OUTLINES = [
+    b"",
+    rect_glyph(100, 100, 900, 900),
+    rect_glyph(300, 300, 700, 700),
+    rect_glyph(100, 300, 900, 700),
+]
+NUM_GLYPHS = 9
+LEFT_SIDE_BEARINGS = [0, 100, 300, 100, 0, 0, 0, 0, 0]
+
+
+def f2dot14(value):
+    return int(round(value * 16384))
+
+
+def offset24(value):
+    return struct.pack(">I", value)[1:]
+
+
+def paint_solid(palette_index):
+    return struct.pack(">BHh", 2, palette_index, f2dot14(1.0))
+
+
+def paint_glyph(glyph, paint):
+    return bytes([10]) + offset24(6) + struct.pack(">H", glyph) + paint
+
+
+def paint_scale_around(scale, cx, cy, paint):
+    return bytes([22]) + offset24(10) + struct.pack(">hhh", f2dot14(scale), cx, cy) + paint
+
+
+def paint_rotate_around(turns, cx, cy, paint):
+    # Angles are in multiples of 180 degrees.
+    return bytes([26]) + offset24(10) + struct.pack(">hhh", f2dot14(turns * 2), cx, cy) + paint
+
+
+def paint_composite(mode, source, backdrop):
+    return (bytes([32]) + offset24(8) + bytes([mode]) + offset24(8 + len(source)) +
+            source + backdrop)
+
+
+def colr():
+    base_glyphs = [(4, 0, 2)]
+    layers = [(1, RED), (2, BLUE)]
+
+    layer_list = [
+        paint_glyph(1, paint_solid(FOREGROUND)),
+        paint_glyph(2, paint_solid(GREEN)),
+    ]
+    base_paints = [
+        (5, struct.pack(">BBI", 1, 2, 0)),
+        (6, paint_scale_around(0.5, 500, 500, paint_glyph(1, paint_solid(RED)))),
+        (7, paint_rotate_around(0.25, 500, 500, paint_glyph(3, paint_solid(BLUE)))),
+        (8, paint_composite(8, paint_glyph(2, paint_solid(RED)), paint_glyph(1, paint_solid(RED)))),
+    ]
+
+    header_size = 34
+    base_glyphs_offset = header_size
+    layers_offset = base_glyphs_offset + 6 * len(base_glyphs)
+    base_glyph_list_offset = layers_offset + 4 * len(layers)
+
+    base_glyph_list = struct.pack(">I", len(base_paints))
+    offset = 4 + 6 * len(base_paints)
+    paints = b""
+    for (glyph, paint) in base_paints:
+        base_glyph_list += struct.pack(">HI", glyph, offset + len(paints))
+        paints += paint
+    base_glyph_list += paints
+
+    layer_list_offset = base_glyph_list_offset + len(base_glyph_list)
+    layer_data = struct.pack(">I", len(layer_list))
+    offset = 4 + 4 * len(layer_list)
+    paints = b""
+    for paint in layer_list:
+        layer_data += struct.pack(">I", offset + len(paints))
+        paints += paint
+    layer_data += paints
+
+    data = struct.pack(">HHIIHIIIII", 1, len(base_glyphs), base_glyphs_offset, layers_offset,
+                       len(layers), base_glyph_list_offset, layer_list_offset, 0, 0, 0)
+    for record in base_glyphs:
+        data += struct.pack(">HHH", *record)
+    for record in layers:
+        data += struct.pack(">HH", *record)
+    return data + base_glyph_list + layer_data
+
+
+def cpal():
+    # Red, blue and green, as BGRA.
+    colors = [(0, 0, 255, 255), (255, 0, 0, 255), (0, 255, 0, 255)]
+    data = struct.pack(">HHHHIH", 0, len(colors), 1, len(colors), 14, 0)
+    for color in colors:
+        data += bytes(color)
+    return data
+
+
+def name():
+    records = [(1, "COLR Test"), (2, "Regular"), (4, "COLR Test"), (6, "COLRTest")]
+    strings = b""
+    data = struct.pack(">HHH", 0, len(records), 6 + 12 * len(records))
+    for (name_id, string) in records:
+        encoded = string.encode("utf-16-be")
+        data += struct.pack(">HHHHHH", 3, 1, 0x409, name_id, len(encoded), len(strings))
+        strings += encoded
+    return data + strings
+
+
+def tables():
+    glyf = b""
+    loca = []
+    for glyph in range(NUM_GLYPHS):
+        loca.append(len(glyf))
+        glyf += OUTLINES[glyph] if glyph < len(OUTLINES) else b""
+    loca.append(len(glyf))
+
+    return {
+        b"COLR": colr(),
+        b"CPAL": cpal(),
+        b"OS/2": struct.pack(">HhHHHhhhhhhhhhhh10B4I4sHHHhhhHH2IhhHHH", 4, 1000, 400, 5, 0,
+                             650, 600, 0, 75, 650, 600, 0, 350, 50, 250, 0, *([0] * 10),
+                             0, 0, 0, 0, b"NONE", 0x40, 0, 0xFFFF, 1000, 0, 0, 1000, 0,
+                             1, 0, 500, 1000, 0, 32, 0),
+        b"cmap": struct.pack(">HHHHI12H", 0, 1, 3, 1, 12, 4, 24, 0, 2, 2, 0, 0,
+                             0xFFFF, 0, 0xFFFF, 1, 0),
+        b"glyf": glyf,
+        b"head": struct.pack(">IIIIHHqqhhhhHHhhh", 0x00010000, 0x00010000, 0, 0x5F0F3CF5,
+                             0x000B, UNITS_PER_EM, 0, 0, 100, 100, 900, 900, 0, 8, 2, 1, 0),
+        b"hhea": struct.pack(">IhhhHhhhhhhhhhhhH", 0x00010000, 1000, 0, 0, 1000, 100, 100,
+                             900, 1, 0, 0, 0, 0, 0, 0, 0, NUM_GLYPHS),
+        b"hmtx": b"".join(struct.pack(">Hh", 1000, lsb) for lsb in LEFT_SIDE_BEARINGS),
+        b"loca": struct.pack(">%dI" % len(loca), *loca),
+        b"maxp": struct.pack(">IHHHHHHHHHHHHHH", 0x00010000, NUM_GLYPHS, 4, 1, 0, 0,
+                             2, 0, 0, 0, 0, 0, 0, 0, 0),
+        b"name": name(),
+        b"post": struct.pack(">IIhhIIIII", 0x00030000, 0, -75, 50, 0, 0, 0, 0, 0),
+    }
+
+
+def checksum(data):
+    data += b"\0" * (-len(data) % 4)
+    return sum(struct.unpack(">%dI" % (len(data) // 4), data)) & 0xFFFFFFFF
+
+
+def font():
+    entries = sorted(tables().items())
+    num_tables = len(entries)
+    search_range = 16
+    while search_range * 2 <= num_tables * 16:
+        search_range *= 2
+    entry_selector = search_range.bit_length() - 5
+    data = struct.pack(">IHHHH", 0x00010000, num_tables, search_range, entry_selector,
+                       num_tables * 16 - search_range)
+
+    offset = 12 + 16 * num_tables
+    body = b""
+    head_offset = None
+    for (tag, table) in entries:
+        if tag == b"head":
+            head_offset = offset + len(body)
+        data += tag + struct.pack(">III", checksum(table), offset + len(body), len(table))
+        body += table + b"\0" * (-len(table) % 4)
+    data += body
+
+    adjustment = (0xB1B0AFBA - checksum(data)) & 0xFFFFFFFF
+    return data[:head_offset + 8] + struct.pack(">I", adjustment) + data[head_offset + 12:]
+
+
+with open("../reftests/text/colr.ttf", "wb") as font_file:
+    font_file.write(font())
@@ -0,0 +1,231 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Renders generated display lists to find crashes in scene and frame
+// building. As wrench aborts on panics, the scenes render in a child wrench
+// process; when it crashes, the crashing scene is minimized by replaying
+// smaller versions of it in more child processes, and written out as YAML.
+
+use crate::NotifierEvent;
+use crate::WindowWrapper;
+use crate::fuzz_scene::{seed_bytes, FuzzScene};
+use crate::wrench::Wrench;
+use crate::yaml_frame_writer::YamlFrameWriter;
+use serde_json;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use std::sync::mpsc::Receiver;
+use webrender::api::*;
+
+/// The number of bytes each seed expands to, which is plenty for a scene of
+/// the maximum size.
+const CASE_SIZE: usize = 4096;
+
+/// Printed by the child process before it renders each scene, so that the
+/// parent knows which one crashed.
+const CASE_PREFIX: &str = "FUZZ CASE ";
+
+pub struct FuzzHarness<'a> {
+    wrench: &'a mut Wrench,
+    window: &'a mut WindowWrapper,
+    rx: Receiver<NotifierEvent>,
+    frame_number: u32,
+}
+
+impl<'a> FuzzHarness<'a> {
+    pub fn new(
+        wrench: &'a mut Wrench,
+        window: &'a mut WindowWrapper,
+        rx: Receiver<NotifierEvent>
+    ) -> Self {
+        FuzzHarness {
+            wrench,
+            window,
+            rx,
+            frame_number: 0,
+        }
+    }
+
+    /// Renders the scenes of `iterations` seeds, starting at `seed`.
+    pub fn run(mut self, seed: u64, iterations: u64) {
+        for seed in seed .. seed + iterations {
+            println!("{}{}", CASE_PREFIX, seed);
+            let scene = FuzzScene::generate(&seed_bytes(seed, CASE_SIZE));
+            self.render(&scene);
+        }
+    }
+
+    /// Renders a scene written by `write_scene`.
+    pub fn replay(mut self, filename: &Path) {
+        let mut json = String::new();
+        File::open(filename)
+            .and_then(|mut file| file.read_to_string(&mut json))
+            .unwrap_or_else(|_| panic!("Unable to read {:?}", filename));
+        let scene: FuzzScene = serde_json::from_str(&json).expect("Invalid fuzz scene");
+        self.render(&scene);
+    }
+
+    fn render(&mut self, scene: &FuzzScene) {
+        let mut builder = DisplayListBuilder::new(self.wrench.root_pipeline_id);
+        scene.build(&mut builder);
+
+        self.frame_number += 1;
+        self.wrench.send_lists(self.frame_number, vec![builder.finalize()], &HashMap::new());
+
+        self.rx.recv().unwrap();
+        self.wrench.render();
+        self.window.swap_buffers();
+    }
+}
+
+/// Fuzzes `iterations` seeds starting at `seed` in child processes, and
+/// writes the minimized scenes that crash to `output_dir`. Returns the number
+/// of crashes.
+pub fn run(seed: u64, iterations: u64, output_dir: &Path) -> usize {
+    let end = seed + iterations;
+    let mut next_seed = seed;
+    let mut crashes = 0;
+
+    while next_seed < end {
+        let output = child_command()
+            .args(&["--child", "--seed", &next_seed.to_string()])
+            .args(&["--iterations", &(end - next_seed).to_string()])
+            .output()
+            .expect("Unable to run wrench");
+        if output.status.success() {
+            break;
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let crashed_seed: u64 = match stdout.lines().filter(|line| line.starts_with(CASE_PREFIX)).last() {
+            Some(line) => line[CASE_PREFIX.len() ..].parse().unwrap(),
+            None => {
+                println!("FUZZ ERROR | wrench exited with {} before rendering a scene\n{}", output.status, stderr);
+                return crashes + 1;
+            }
+        };
+        let signature = crash_signature(&stderr, output.status);
+        println!("FUZZ CRASH | seed {} | {}", crashed_seed, signature);
+
+        fs::create_dir_all(output_dir).unwrap();
+        let scene = FuzzScene::generate(&seed_bytes(crashed_seed, CASE_SIZE));
+        let scene = minimize(scene, &signature, &output_dir.join("candidate.json"));
+        let name = format!("crash-{}", crashed_seed);
+        write_scene(&scene, &output_dir.join(&name).with_extension("json"));
+        write_yaml(&scene, &output_dir.join(&name).with_extension("yaml"));
+        println!(
+            "FUZZ CRASH | seed {} | minimized to {} items in {}",
+            crashed_seed,
+            scene.item_count(),
+            output_dir.join(&name).with_extension("yaml").display(),
+        );
+
+        crashes += 1;
+        next_seed = crashed_seed + 1;
+    }
+
+    println!("FUZZ INFO | {} scenes, {} crashes", iterations, crashes);
+    crashes
+}
+
+/// Removes items from a crashing scene, and replaces containers with their
+/// children, as long as it still crashes the same way.
+fn minimize(mut scene: FuzzScene, signature: &str, candidate_path: &Path) -> FuzzScene {
+    if !crashes(&scene, signature, candidate_path) {
+        println!("FUZZ WARNING | the scene doesn't crash on its own, so it can't be minimized");
+        return scene;
+    }
+
+    loop {
+        let mut reduced = false;
+        let mut index = 0;
+        while index < scene.item_count() {
+            let candidate = scene.without_item(index);
+            if crashes(&candidate, signature, candidate_path) {
+                scene = candidate;
+                reduced = true;
+                continue;
+            }
+            if let Some(candidate) = scene.with_item_unwrapped(index) {
+                if crashes(&candidate, signature, candidate_path) {
+                    scene = candidate;
+                    reduced = true;
+                    continue;
+                }
+            }
+            index += 1;
+        }
+        if !reduced {
+            break;
+        }
+    }
+
+    let _ = fs::remove_file(candidate_path);
+    scene
+}
+
+fn crashes(scene: &FuzzScene, signature: &str, candidate_path: &Path) -> bool {
+    write_scene(scene, candidate_path);
+    let output = child_command()
+        .args(&["--replay", candidate_path.to_str().unwrap()])
+        .output()
+        .expect("Unable to run wrench");
+    !output.status.success() &&
+        crash_signature(&String::from_utf8_lossy(&output.stderr), output.status) == signature
+}
+
+/// Identifies a crash by where it panicked, or by the exit status if it
+/// didn't.
+fn crash_signature(stderr: &str, status: ExitStatus) -> String {
+    match stderr.lines().find_map(|line| line.find("panicked at ").map(|i| &line[i ..])) {
+        Some(panic) => panic.trim_end_matches(':').to_owned(),
+        None => format!("exited with {}", status),
+    }
+}
+
+/// A command to run wrench with the same global arguments as this process,
+/// and the fuzz subcommand without its options. The child always renders with
+/// SWGL, so that crashes reproduce the same way as under `cargo fuzz`.
+fn child_command() -> Command {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command.arg("--software");
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" | "--iterations" | "--output" | "--replay" => {
+                args.next();
+            }
+            "--child" | "--software" => {}
+            _ if arg.starts_with("--seed=") ||
+                 arg.starts_with("--iterations=") ||
+                 arg.starts_with("--output=") ||
+                 arg.starts_with("--replay=") => {}
+            _ => {
+                command.arg(arg);
+            }
+        }
+    }
+    command
+}
+
+fn write_scene(scene: &FuzzScene, path: &Path) {
+    let json = serde_json::to_string_pretty(scene).unwrap();
+    let mut file = File::create(path).unwrap_or_else(|_| panic!("Unable to create {:?}", path));
+    file.write_all(json.as_bytes()).unwrap();
+}
+
+fn write_yaml(scene: &FuzzScene, path: &Path) {
+    let pipeline_id = PipelineId(0, 0);
+    let mut builder = DisplayListBuilder::new(pipeline_id);
+    scene.build(&mut builder);
+
+    let mut writer = YamlFrameWriter::new(path);
+    writer.set_display_lists(&[builder.finalize()], &HashMap::new());
+    writer.finish_write(pipeline_id);
+}
@@ -0,0 +1,616 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Generates arbitrary but well-typed display lists for fuzzing, from a byte
+// string. Pushes and pops are always balanced and every clip and spatial id
+// refers to a node that was defined before it, so that crashes point at scene
+// or frame building rather than at the generator.
+//
+// This module only depends on WebRender, as the cargo-fuzz target in
+// `wrench/fuzz` includes it too.
+
+use std::mem;
+use webrender::api::*;
+use webrender::api::units::*;
+use webrender::euclid::Angle;
+
+/// How deeply stacking contexts, reference frames, scroll frames and clips
+/// nest.
+const MAX_DEPTH: usize = 5;
+/// The maximum number of items in a scene, counting nested ones.
+const MAX_ITEMS: usize = 48;
+
+/// Coordinates that tend to hit edge cases.
+const SPECIAL_COORDS: [f32; 8] = [0.0, -0.5, -64.0, 0.25, 4096.0, 16384.0, 1.0e6, -1.0e6];
+
+const BLEND_MODES: [MixBlendMode; 16] = [
+    MixBlendMode::Normal,
+    MixBlendMode::Multiply,
+    MixBlendMode::Screen,
+    MixBlendMode::Overlay,
+    MixBlendMode::Darken,
+    MixBlendMode::Lighten,
+    MixBlendMode::ColorDodge,
+    MixBlendMode::ColorBurn,
+    MixBlendMode::HardLight,
+    MixBlendMode::SoftLight,
+    MixBlendMode::Difference,
+    MixBlendMode::Exclusion,
+    MixBlendMode::Hue,
+    MixBlendMode::Saturation,
+    MixBlendMode::Color,
+    MixBlendMode::Luminosity,
+];
+
+const LINE_STYLES: [LineStyle; 4] = [
+    LineStyle::Solid,
+    LineStyle::Dotted,
+    LineStyle::Dashed,
+    LineStyle::Wavy,
+];
+
+const BORDER_STYLES: [BorderStyle; 10] = [
+    BorderStyle::None,
+    BorderStyle::Solid,
+    BorderStyle::Double,
+    BorderStyle::Dotted,
+    BorderStyle::Dashed,
+    BorderStyle::Hidden,
+    BorderStyle::Groove,
+    BorderStyle::Ridge,
+    BorderStyle::Inset,
+    BorderStyle::Outset,
+];
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum FuzzTransform {
+    Translate(f32, f32),
+    Scale(f32, f32),
+    Rotate(f32),
+    /// A rotation around the x axis, which can show the back face.
+    RotateX(f32),
+    Perspective(f32),
+}
+
+impl FuzzTransform {
+    fn to_transform(&self) -> LayoutTransform {
+        match *self {
+            FuzzTransform::Translate(x, y) => LayoutTransform::translation(x, y, 0.0),
+            FuzzTransform::Scale(x, y) => LayoutTransform::scale(x, y, 1.0),
+            FuzzTransform::Rotate(degrees) => {
+                LayoutTransform::rotation(0.0, 0.0, 1.0, Angle::degrees(degrees))
+            }
+            FuzzTransform::RotateX(degrees) => {
+                LayoutTransform::rotation(1.0, 0.0, 0.0, Angle::degrees(degrees))
+            }
+            FuzzTransform::Perspective(distance) => LayoutTransform::perspective(distance),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum FuzzItem {
+    Rect {
+        rect: LayoutRect,
+        color: ColorF,
+    },
+    ClearRect {
+        rect: LayoutRect,
+    },
+    Line {
+        rect: LayoutRect,
+        orientation: LineOrientation,
+        style: LineStyle,
+        thickness: f32,
+        color: ColorF,
+    },
+    Border {
+        rect: LayoutRect,
+        widths: [f32; 4],
+        style: BorderStyle,
+        radius: f32,
+        color: ColorF,
+    },
+    BoxShadow {
+        rect: LayoutRect,
+        offset: LayoutVector2D,
+        blur: f32,
+        spread: f32,
+        radius: f32,
+        clip_mode: BoxShadowClipMode,
+        color: ColorF,
+    },
+    Gradient {
+        rect: LayoutRect,
+        start: LayoutPoint,
+        end: LayoutPoint,
+        stops: Vec<(f32, ColorF)>,
+        extend_mode: ExtendMode,
+        tile_size: LayoutSize,
+    },
+    StackingContext {
+        origin: LayoutPoint,
+        transform_style: TransformStyle,
+        blend_mode: MixBlendMode,
+        filters: Vec<FilterOp>,
+        children: Vec<FuzzItem>,
+    },
+    ReferenceFrame {
+        origin: LayoutPoint,
+        transform_style: TransformStyle,
+        transform: FuzzTransform,
+        children: Vec<FuzzItem>,
+    },
+    ScrollFrame {
+        clip_rect: LayoutRect,
+        content_size: LayoutSize,
+        offset: LayoutVector2D,
+        children: Vec<FuzzItem>,
+    },
+    Clip {
+        rect: LayoutRect,
+        radius: f32,
+        mode: ClipMode,
+        children: Vec<FuzzItem>,
+    },
+}
+
+impl FuzzItem {
+    fn children(&self) -> Option<&Vec<FuzzItem>> {
+        match *self {
+            FuzzItem::StackingContext { ref children, .. } |
+            FuzzItem::ReferenceFrame { ref children, .. } |
+            FuzzItem::ScrollFrame { ref children, .. } |
+            FuzzItem::Clip { ref children, .. } => Some(children),
+            _ => None,
+        }
+    }
+
+    fn children_mut(&mut self) -> Option<&mut Vec<FuzzItem>> {
+        match *self {
+            FuzzItem::StackingContext { ref mut children, .. } |
+            FuzzItem::ReferenceFrame { ref mut children, .. } |
+            FuzzItem::ScrollFrame { ref mut children, .. } |
+            FuzzItem::Clip { ref mut children, .. } => Some(children),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FuzzScene {
+    pub items: Vec<FuzzItem>,
+}
+
+/// Expands a seed into the bytes of a fuzz case, so that runs are
+/// reproducible.
+pub fn seed_bytes(seed: u64, len: usize) -> Vec<u8> {
+    // splitmix64
+    let mut state = seed;
+    let mut bytes = Vec::with_capacity(len + 8);
+    while bytes.len() < len {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        bytes.extend_from_slice(&(z ^ (z >> 31)).to_le_bytes());
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+/// Draws choices from the input bytes. Once they run out, every choice is
+/// zero, which picks the simplest option.
+struct Generator<'a> {
+    data: &'a [u8],
+    pos: usize,
+    items_left: usize,
+}
+
+impl<'a> Generator<'a> {
+    fn is_done(&self) -> bool {
+        self.pos >= self.data.len() || self.items_left == 0
+    }
+
+    fn byte(&mut self) -> u8 {
+        let byte = self.data.get(self.pos).cloned().unwrap_or(0);
+        self.pos += 1;
+        byte
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        let value = (self.byte() as usize) << 8 | self.byte() as usize;
+        value % n
+    }
+
+    fn chance(&mut self, one_in: usize) -> bool {
+        self.below(one_in) == 0
+    }
+
+    fn float(&mut self, max: f32) -> f32 {
+        let value = (self.byte() as u32) << 8 | self.byte() as u32;
+        value as f32 / 65535.0 * max
+    }
+
+    fn choose<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.below(values.len())]
+    }
+
+    fn coord(&mut self) -> f32 {
+        if self.chance(16) {
+            self.choose(&SPECIAL_COORDS)
+        } else {
+            self.float(1000.0) - 100.0
+        }
+    }
+
+    fn length(&mut self, max: f32) -> f32 {
+        if self.chance(16) {
+            self.choose(&[0.0, -10.0, 0.01, 1.0e6])
+        } else {
+            self.float(max)
+        }
+    }
+
+    fn point(&mut self) -> LayoutPoint {
+        LayoutPoint::new(self.coord(), self.coord())
+    }
+
+    fn vector(&mut self) -> LayoutVector2D {
+        LayoutVector2D::new(self.coord(), self.coord())
+    }
+
+    fn size(&mut self) -> LayoutSize {
+        LayoutSize::new(self.length(500.0), self.length(500.0))
+    }
+
+    /// A rect, which may be empty or inverted.
+    fn rect(&mut self) -> LayoutRect {
+        let min = self.point();
+        LayoutRect::new(min, min + self.size().to_vector())
+    }
+
+    fn color(&mut self) -> ColorF {
+        ColorU::new(self.byte(), self.byte(), self.byte(), self.byte()).into()
+    }
+
+    fn transform_style(&mut self) -> TransformStyle {
+        if self.chance(4) { TransformStyle::Preserve3D } else { TransformStyle::Flat }
+    }
+
+    fn transform(&mut self) -> FuzzTransform {
+        match self.below(5) {
+            0 => FuzzTransform::Translate(self.coord(), self.coord()),
+            1 => FuzzTransform::Scale(self.length(4.0), self.length(4.0)),
+            2 => FuzzTransform::Rotate(self.float(720.0) - 360.0),
+            3 => FuzzTransform::RotateX(self.float(360.0)),
+            _ => FuzzTransform::Perspective(self.length(1000.0)),
+        }
+    }
+
+    fn filter(&mut self) -> FilterOp {
+        match self.below(7) {
+            0 => {
+                let opacity = self.float(1.0);
+                FilterOp::Opacity(PropertyBinding::Value(opacity), opacity)
+            }
+            1 => {
+                let (x, y) = (self.length(50.0), self.length(50.0));
+                FilterOp::Blur(x, y)
+            }
+            2 => FilterOp::Grayscale(self.float(1.0)),
+            3 => FilterOp::HueRotate(self.float(360.0)),
+            4 => FilterOp::Invert(self.float(1.0)),
+            5 => FilterOp::Flood(self.color()),
+            _ => FilterOp::DropShadow(Shadow {
+                offset: self.vector(),
+                color: self.color(),
+                blur_radius: self.length(50.0),
+            }),
+        }
+    }
+
+    fn items(&mut self, depth: usize) -> Vec<FuzzItem> {
+        let count = if depth == 0 { MAX_ITEMS } else { self.below(5) };
+        let mut items = Vec::new();
+        while items.len() < count && !self.is_done() {
+            self.items_left -= 1;
+            items.push(self.item(depth));
+        }
+        items
+    }
+
+    fn item(&mut self, depth: usize) -> FuzzItem {
+        // Containers only nest up to MAX_DEPTH.
+        let kinds = if depth < MAX_DEPTH { 10 } else { 6 };
+        match self.below(kinds) {
+            0 => FuzzItem::Rect {
+                rect: self.rect(),
+                color: self.color(),
+            },
+            1 => FuzzItem::ClearRect {
+                rect: self.rect(),
+            },
+            2 => FuzzItem::Line {
+                rect: self.rect(),
+                orientation: if self.chance(2) { LineOrientation::Vertical } else { LineOrientation::Horizontal },
+                style: self.choose(&LINE_STYLES),
+                thickness: self.length(20.0),
+                color: self.color(),
+            },
+            3 => FuzzItem::Border {
+                rect: self.rect(),
+                widths: [self.length(50.0), self.length(50.0), self.length(50.0), self.length(50.0)],
+                style: self.choose(&BORDER_STYLES),
+                radius: self.length(100.0),
+                color: self.color(),
+            },
+            4 => FuzzItem::BoxShadow {
+                rect: self.rect(),
+                offset: self.vector(),
+                blur: self.length(100.0),
+                spread: self.length(100.0) - 20.0,
+                radius: self.length(100.0),
+                clip_mode: if self.chance(2) { BoxShadowClipMode::Inset } else { BoxShadowClipMode::Outset },
+                color: self.color(),
+            },
+            5 => {
+                let rect = self.rect();
+                let (start, end) = (self.point(), self.point());
+                // Offsets are sorted, but may repeat for hard stops.
+                let mut offsets: Vec<f32> = (0 .. 2 + self.below(4)).map(|_| self.float(1.0)).collect();
+                offsets.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let stops = offsets.into_iter().map(|offset| (offset, self.color())).collect();
+                FuzzItem::Gradient {
+                    rect,
+                    start,
+                    end,
+                    stops,
+                    extend_mode: if self.chance(2) { ExtendMode::Repeat } else { ExtendMode::Clamp },
+                    tile_size: if self.chance(4) { self.size() } else { rect.size() },
+                }
+            }
+            6 => FuzzItem::StackingContext {
+                origin: self.point(),
+                transform_style: self.transform_style(),
+                blend_mode: self.choose(&BLEND_MODES),
+                filters: (0 .. self.below(3)).map(|_| self.filter()).collect(),
+                children: self.items(depth + 1),
+            },
+            7 => FuzzItem::ReferenceFrame {
+                origin: self.point(),
+                transform_style: self.transform_style(),
+                transform: self.transform(),
+                children: self.items(depth + 1),
+            },
+            8 => FuzzItem::ScrollFrame {
+                clip_rect: self.rect(),
+                content_size: self.size(),
+                offset: self.vector(),
+                children: self.items(depth + 1),
+            },
+            _ => FuzzItem::Clip {
+                rect: self.rect(),
+                radius: self.length(100.0),
+                mode: if self.chance(4) { ClipMode::ClipOut } else { ClipMode::Clip },
+                children: self.items(depth + 1),
+            },
+        }
+    }
+}
+
+/// Pushes the items of a scene, keeping track of the spaces and clips they
+/// are in.
+struct SceneBuilder<'a> {
+    builder: &'a mut DisplayListBuilder,
+    next_scroll_id: u64,
+}
+
+impl<'a> SceneBuilder<'a> {
+    fn push_items(&mut self, items: &[FuzzItem], space_and_clip: SpaceAndClipInfo) {
+        for item in items {
+            self.push_item(item, space_and_clip);
+        }
+    }
+
+    fn push_item(&mut self, item: &FuzzItem, space_and_clip: SpaceAndClipInfo) {
+        let pipeline_id = self.builder.pipeline_id;
+        let common = |rect: LayoutRect| CommonItemProperties::new(rect, space_and_clip);
+
+        match *item {
+            FuzzItem::Rect { rect, color } => {
+                self.builder.push_rect(&common(rect), rect, color);
+            }
+            FuzzItem::ClearRect { rect } => {
+                self.builder.push_clear_rect(&common(rect), rect);
+            }
+            FuzzItem::Line { rect, orientation, style, thickness, color } => {
+                self.builder.push_line(&common(rect), &rect, thickness, orientation, &color, style);
+            }
+            FuzzItem::Border { rect, widths, style, radius, color } => {
+                let side = BorderSide { color, style };
+                let details = BorderDetails::Normal(NormalBorder {
+                    left: side,
+                    right: side,
+                    top: side,
+                    bottom: side,
+                    radius: BorderRadius::uniform(radius),
+                    do_aa: true,
+                });
+                let widths = LayoutSideOffsets::new(widths[0], widths[1], widths[2], widths[3]);
+                self.builder.push_border(&common(rect), rect, widths, details);
+            }
+            FuzzItem::BoxShadow { rect, offset, blur, spread, radius, clip_mode, color } => {
+                let clip_rect = rect.inflate(blur + spread.abs(), blur + spread.abs()).translate(offset);
+                self.builder.push_box_shadow(
+                    &common(clip_rect.union(&rect)),
+                    rect,
+                    offset,
+                    color,
+                    blur,
+                    spread,
+                    BorderRadius::uniform(radius),
+                    clip_mode,
+                );
+            }
+            FuzzItem::Gradient { rect, start, end, ref stops, extend_mode, tile_size } => {
+                let stops = stops
+                    .iter()
+                    .map(|&(offset, color)| GradientStop { offset, color })
+                    .collect();
+                let gradient = self.builder.create_gradient(start, end, stops, extend_mode);
+                self.builder.push_gradient(&common(rect), rect, gradient, tile_size, LayoutSize::zero());
+            }
+            FuzzItem::StackingContext { origin, transform_style, blend_mode, ref filters, ref children } => {
+                self.builder.push_stacking_context(
+                    origin,
+                    space_and_clip.spatial_id,
+                    PrimitiveFlags::default(),
+                    None,
+                    transform_style,
+                    blend_mode,
+                    filters,
+                    &[],
+                    &[],
+                    RasterSpace::Screen,
+                    StackingContextFlags::empty(),
+                );
+                self.push_items(children, space_and_clip);
+                self.builder.pop_stacking_context();
+            }
+            FuzzItem::ReferenceFrame { origin, transform_style, ref transform, ref children } => {
+                let kind = match *transform {
+                    FuzzTransform::Perspective(..) => ReferenceFrameKind::Perspective {
+                        scrolling_relative_to: None,
+                    },
+                    _ => ReferenceFrameKind::Transform {
+                        is_2d_scale_translation: false,
+                        should_snap: false,
+                    },
+                };
+                let spatial_id = self.builder.push_reference_frame(
+                    origin,
+                    space_and_clip.spatial_id,
+                    transform_style,
+                    PropertyBinding::Value(transform.to_transform()),
+                    kind,
+                );
+                self.push_items(children, SpaceAndClipInfo { spatial_id, clip_id: space_and_clip.clip_id });
+                self.builder.pop_reference_frame();
+            }
+            FuzzItem::ScrollFrame { clip_rect, content_size, offset, ref children } => {
+                let id = ExternalScrollId(self.next_scroll_id, pipeline_id);
+                self.next_scroll_id += 1;
+                let inner = self.builder.define_scroll_frame(
+                    &space_and_clip,
+                    id,
+                    LayoutRect::from_origin_and_size(clip_rect.min, content_size),
+                    clip_rect,
+                    ScrollSensitivity::ScriptAndInputEvents,
+                    offset,
+                );
+                self.push_items(children, inner);
+            }
+            FuzzItem::Clip { rect, radius, mode, ref children } => {
+                let clip_id = self.builder.define_clip_rounded_rect(
+                    &space_and_clip,
+                    ComplexClipRegion::new(rect, BorderRadius::uniform(radius), mode),
+                );
+                self.push_items(children, SpaceAndClipInfo { spatial_id: space_and_clip.spatial_id, clip_id });
+            }
+        }
+    }
+}
+
+enum Edit {
+    Remove,
+    /// Replaces a container with its children.
+    Unwrap,
+}
+
+/// Applies an edit to the item at `index`, counting nested items in
+/// pre-order. Returns None if there is no such item, and whether the edit
+/// applied otherwise.
+fn edit_items(items: &mut Vec<FuzzItem>, index: &mut usize, edit: &Edit) -> Option<bool> {
+    let mut i = 0;
+    while i < items.len() {
+        if *index == 0 {
+            return Some(match *edit {
+                Edit::Remove => {
+                    items.remove(i);
+                    true
+                }
+                Edit::Unwrap => match items[i].children_mut() {
+                    Some(children) => {
+                        let children = mem::take(children);
+                        items.splice(i ..= i, children);
+                        true
+                    }
+                    None => false,
+                },
+            });
+        }
+        *index -= 1;
+        if let Some(children) = items[i].children_mut() {
+            if let Some(applied) = edit_items(children, index, edit) {
+                return Some(applied);
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+fn count_items(items: &[FuzzItem]) -> usize {
+    items.iter().map(|item| 1 + item.children().map_or(0, |c| count_items(c))).sum()
+}
+
+impl FuzzScene {
+    pub fn generate(data: &[u8]) -> FuzzScene {
+        let mut generator = Generator {
+            data,
+            pos: 0,
+            items_left: MAX_ITEMS,
+        };
+        FuzzScene {
+            items: generator.items(0),
+        }
+    }
+
+    /// Pushes the scene onto a display list, in the root scroll frame of
+    /// its pipeline.
+    pub fn build(&self, builder: &mut DisplayListBuilder) {
+        let root = SpaceAndClipInfo::root_scroll(builder.pipeline_id);
+        let mut scene_builder = SceneBuilder {
+            builder,
+            // 0 is the root scroll frame.
+            next_scroll_id: 1,
+        };
+        scene_builder.push_items(&self.items, root);
+    }
+
+    /// The number of items in the scene, counting nested ones.
+    pub fn item_count(&self) -> usize {
+        count_items(&self.items)
+    }
+
+    /// The scene without the item at `index`, in pre-order.
+    pub fn without_item(&self, index: usize) -> FuzzScene {
+        let mut scene = self.clone();
+        let mut index = index;
+        edit_items(&mut scene.items, &mut index, &Edit::Remove);
+        scene
+    }
+
+    /// The scene with the container at `index` replaced by its children, or
+    /// None if the item isn't a container.
+    pub fn with_item_unwrapped(&self, index: usize) -> Option<FuzzScene> {
+        let mut scene = self.clone();
+        let mut index = index;
+        match edit_items(&mut scene.items, &mut index, &Edit::Unwrap) {
+            Some(true) => Some(scene),
+            _ => None,
+        }
+    }
+}
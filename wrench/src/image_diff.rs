@@ -0,0 +1,288 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Perceptual metrics of the difference between two images, which tolerate
+// small antialiasing changes better than counting differing pixels.
+
+use crate::png::{save, SaveSettings};
+use crate::reftest::{ReftestImage, ReftestImageComparison};
+use image::ImageFormat;
+use std::path::Path;
+
+/// The size of the windows SSIM is computed over, and the distance between
+/// them.
+const SSIM_WINDOW_SIZE: usize = 8;
+const SSIM_WINDOW_STEP: usize = 4;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ImageMetrics {
+    /// The peak signal to noise ratio over all channels, in dB. Infinite for
+    /// identical images.
+    pub psnr: f64,
+    /// The mean structural similarity of the luma, 1 for identical images.
+    pub ssim: f64,
+    /// The lowest structural similarity of any window, which a localized
+    /// difference lowers even when the mean barely changes.
+    pub min_ssim: f64,
+    /// The mean and maximum CIEDE2000 color difference of the pixels. A
+    /// difference below 1 is not perceptible.
+    pub mean_delta_e: f64,
+    pub max_delta_e: f64,
+}
+
+impl ImageMetrics {
+    /// Compares two images of the same size. The color metrics ignore alpha,
+    /// as rendered images are opaque.
+    pub fn compute(a: &ReftestImage, b: &ReftestImage) -> Self {
+        assert_eq!(a.size, b.size);
+        let width = a.size.width as usize;
+        let height = a.size.height as usize;
+        let (mean_delta_e, max_delta_e) = delta_e(&a.data, &b.data);
+        let (ssim, min_ssim) = ssim(&a.data, &b.data, width, height);
+        ImageMetrics {
+            psnr: psnr(&a.data, &b.data),
+            ssim,
+            min_ssim,
+            mean_delta_e,
+            max_delta_e,
+        }
+    }
+}
+
+/// Prints the differences between two PNG images, and optionally writes an
+/// image of them. Returns whether the images are identical.
+pub fn diff(first: &Path, second: &Path, output: Option<&Path>) -> bool {
+    let a = ReftestImage::load(first, ImageFormat::Png);
+    let b = ReftestImage::load(second, ImageFormat::Png);
+    if a.size != b.size {
+        println!("The images have different sizes: {:?} and {:?}", a.size, b.size);
+        return false;
+    }
+
+    let identical = match a.compare(&b) {
+        ReftestImageComparison::Equal => {
+            println!("The images are identical");
+            true
+        }
+        ReftestImageComparison::NotEqual { max_difference, count_different, .. } => {
+            let metrics = ImageMetrics::compute(&a, &b);
+            println!("max difference: {}, number of differing pixels: {}", max_difference, count_different);
+            println!("PSNR: {:.2} dB", metrics.psnr);
+            println!("SSIM: mean {:.5}, min {:.5}", metrics.ssim, metrics.min_ssim);
+            println!("CIEDE2000: mean {:.3}, max {:.3}", metrics.mean_delta_e, metrics.max_delta_e);
+            false
+        }
+    };
+
+    if let Some(output) = output {
+        let diff = a.create_diff_image(&b);
+        save(output, diff.data, diff.size, SaveSettings {
+            flip_vertical: true,
+            try_crop: false,
+        });
+    }
+    identical
+}
+
+fn psnr(a: &[u8], b: &[u8]) -> f64 {
+    let sum: f64 = a.iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| (x as f64 - y as f64) * (x as f64 - y as f64))
+        .sum();
+    let mse = sum / a.len() as f64;
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+}
+
+fn luma(data: &[u8]) -> Vec<f64> {
+    data.chunks(4)
+        .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+        .collect()
+}
+
+/// The start of each window along a side of `len` pixels, including a last
+/// window that ends at the edge.
+fn window_starts(len: usize, window: usize) -> Vec<usize> {
+    let mut starts: Vec<usize> = (0 ..= len - window).step_by(SSIM_WINDOW_STEP).collect();
+    if *starts.last().unwrap() != len - window {
+        starts.push(len - window);
+    }
+    starts
+}
+
+/// The mean and the minimum SSIM of the windows of the images.
+fn ssim(a: &[u8], b: &[u8], width: usize, height: usize) -> (f64, f64) {
+    if width == 0 || height == 0 {
+        return (1.0, 1.0);
+    }
+    let (a, b) = (luma(a), luma(b));
+    let c1 = (0.01 * 255.0) * (0.01 * 255.0);
+    let c2 = (0.03 * 255.0) * (0.03 * 255.0);
+    let window_width = SSIM_WINDOW_SIZE.min(width);
+    let window_height = SSIM_WINDOW_SIZE.min(height);
+    let n = (window_width * window_height) as f64;
+
+    let mut total = 0.0;
+    let mut min: f64 = 1.0;
+    let mut count = 0;
+    for y0 in window_starts(height, window_height) {
+        for x0 in window_starts(width, window_width) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in y0 .. y0 + window_height {
+                for x in x0 .. x0 + window_width {
+                    let (va, vb) = (a[y * width + x], b[y * width + x]);
+                    sum_a += va;
+                    sum_b += vb;
+                    sum_aa += va * va;
+                    sum_bb += vb * vb;
+                    sum_ab += va * vb;
+                }
+            }
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+            let window_ssim = ((2.0 * mean_a * mean_b + c1) * (2.0 * covariance + c2)) /
+                ((mean_a * mean_a + mean_b * mean_b + c1) * (var_a + var_b + c2));
+            total += window_ssim;
+            min = min.min(window_ssim);
+            count += 1;
+        }
+    }
+    (total / count as f64, min)
+}
+
+/// Converts an sRGB color to CIELAB, with a D65 white point.
+fn srgb_to_lab(pixel: &[u8]) -> [f64; 3] {
+    let linear = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    let (r, g, b) = (linear(pixel[0]), linear(pixel[1]), linear(pixel[2]));
+    let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+    let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+    let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+    let f = |t: f64| {
+        if t > 216.0 / 24389.0 { t.cbrt() } else { (24389.0 / 27.0 * t + 16.0) / 116.0 }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// The CIEDE2000 difference of two CIELAB colors.
+fn ciede2000(lab1: [f64; 3], lab2: [f64; 3]) -> f64 {
+    let [l1, a1, b1] = lab1;
+    let [l2, a2, b2] = lab2;
+    let pow25_7 = 25f64.powi(7);
+
+    let c_bar = ((a1 * a1 + b1 * b1).sqrt() + (a2 * a2 + b2 * b2).sqrt()) / 2.0;
+    let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt());
+    let (a1, a2) = ((1.0 + g) * a1, (1.0 + g) * a2);
+    let (c1, c2) = ((a1 * a1 + b1 * b1).sqrt(), (a2 * a2 + b2 * b2).sqrt());
+    let hue = |b: f64, a: f64| {
+        if a == 0.0 && b == 0.0 { 0.0 } else { b.atan2(a).to_degrees().rem_euclid(360.0) }
+    };
+    let (h1, h2) = (hue(b1, a1), hue(b2, a2));
+
+    let delta_l = l2 - l1;
+    let delta_c = c2 - c1;
+    let delta_h = if c1 * c2 == 0.0 {
+        0.0
+    } else if (h2 - h1).abs() <= 180.0 {
+        h2 - h1
+    } else if h2 - h1 > 180.0 {
+        h2 - h1 - 360.0
+    } else {
+        h2 - h1 + 360.0
+    };
+    let delta_h = 2.0 * (c1 * c2).sqrt() * (delta_h / 2.0).to_radians().sin();
+
+    let l_bar = (l1 + l2) / 2.0;
+    let c_bar = (c1 + c2) / 2.0;
+    let h_bar = if c1 * c2 == 0.0 {
+        h1 + h2
+    } else if (h1 - h2).abs() <= 180.0 {
+        (h1 + h2) / 2.0
+    } else if h1 + h2 < 360.0 {
+        (h1 + h2 + 360.0) / 2.0
+    } else {
+        (h1 + h2 - 360.0) / 2.0
+    };
+
+    let cos = |degrees: f64| degrees.to_radians().cos();
+    let t = 1.0 - 0.17 * cos(h_bar - 30.0) + 0.24 * cos(2.0 * h_bar) +
+        0.32 * cos(3.0 * h_bar + 6.0) - 0.20 * cos(4.0 * h_bar - 63.0);
+    let delta_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+    let r_c = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt();
+    let s_l = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+    let s_c = 1.0 + 0.045 * c_bar;
+    let s_h = 1.0 + 0.015 * c_bar * t;
+    let r_t = -(2.0 * delta_theta).to_radians().sin() * r_c;
+
+    let (l, c, h) = (delta_l / s_l, delta_c / s_c, delta_h / s_h);
+    (l * l + c * c + h * h + r_t * c * h).sqrt()
+}
+
+/// The mean and maximum CIEDE2000 difference of the pixels.
+fn delta_e(a: &[u8], b: &[u8]) -> (f64, f64) {
+    let mut total = 0.0;
+    let mut max: f64 = 0.0;
+    for (pa, pb) in a.chunks(4).zip(b.chunks(4)) {
+        if pa[.. 3] != pb[.. 3] {
+            let delta = ciede2000(srgb_to_lab(pa), srgb_to_lab(pb));
+            total += delta;
+            max = max.max(delta);
+        }
+    }
+    let count = (a.len() / 4).max(1);
+    (total / count as f64, max)
+}
+
+#[test]
+fn ciede2000_matches_reference_data() {
+    // Pairs from Sharma, Wu and Dalal, "The CIEDE2000 Color-Difference
+    // Formula: Implementation Notes, Supplementary Test Data, and
+    // Mathematical Observations".
+    let pairs = [
+        ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+        ([50.0, -1.0, 2.0], [50.0, 0.0, 0.0], 2.3669),
+        ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+        ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644),
+        ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373),
+    ];
+    for &(lab1, lab2, expected) in &pairs {
+        let delta = ciede2000(lab1, lab2);
+        assert!((delta - expected).abs() < 1e-4, "{} != {}", delta, expected);
+    }
+}
+
+#[test]
+fn identical_images() {
+    let data: Vec<u8> = (0 .. 16 * 12 * 4).map(|i| (i * 7 % 256) as u8).collect();
+    assert_eq!(psnr(&data, &data), f64::INFINITY);
+    let (mean, min) = ssim(&data, &data, 16, 12);
+    assert!((mean - 1.0).abs() < 1e-9);
+    assert!((min - 1.0).abs() < 1e-9);
+    assert_eq!(delta_e(&data, &data), (0.0, 0.0));
+}
+
+#[test]
+fn localized_difference_lowers_min_ssim() {
+    // A large, mostly unchanged image with a small changed block.
+    let (width, height) = (256, 256);
+    let a: Vec<u8> = (0 .. width * height * 4).map(|i| (i * 7 % 256) as u8).collect();
+    let mut b = a.clone();
+    for y in 100 .. 108 {
+        for x in 100 .. 108 {
+            let i = (y * width + x) * 4;
+            b[i .. i + 3].copy_from_slice(&[255, 255, 255]);
+        }
+    }
+    let (mean, min) = ssim(&a, &b, width, height);
+    assert!(mean > 0.995, "mean {}", mean);
+    assert!(min < 0.5, "min {}", min);
+}
@@ -14,15 +14,21 @@ extern crate tracy_rs;
 mod angle;
 mod blob;
 mod egl;
+mod fuzz;
+mod fuzz_scene;
+mod image_diff;
 mod parse_function;
 mod perf;
 mod png;
 mod premultiply;
 mod rawtest;
 mod reftest;
+mod reftest_report;
 mod test_invalidation;
+mod test_yaml_round_trip;
 mod wrench;
 mod yaml_frame_reader;
+mod yaml_frame_writer;
 mod yaml_helper;
 
 use gleam::gl;
@@ -32,6 +38,7 @@ use crate::perf::PerfHarness;
 use crate::png::save_flipped;
 use crate::rawtest::RawtestHarness;
 use crate::reftest::{ReftestHarness, ReftestOptions};
+use crate::reftest_report::ReftestReportPaths;
 use std::fs;
 #[cfg(feature = "headless")]
 use std::ffi::CString;
@@ -62,7 +69,8 @@ pub static mut CURRENT_FRAME_NUMBER: u32 = 0;
 pub struct HeadlessContext {
     width: i32,
     height: i32,
-    _context: osmesa_sys::OSMesaContext,
+    // The OSMesa context and its framebuffer, or None when rendering with SWGL.
+    _context: Option<osmesa_sys::OSMesaContext>,
     _buffer: Vec<u32>,
 }
 
@@ -108,7 +116,7 @@ impl HeadlessContext {
         HeadlessContext {
             width,
             height,
-            _context: context,
+            _context: Some(context),
             _buffer: buffer,
         }
     }
@@ -118,6 +126,23 @@ impl HeadlessContext {
         HeadlessContext { width, height }
     }
 
+    /// Creates a context for rendering with SWGL into its own framebuffer,
+    /// which neither needs OSMesa nor a windowing system.
+    #[cfg(feature = "headless")]
+    fn new_software(width: i32, height: i32) -> Self {
+        HeadlessContext {
+            width,
+            height,
+            _context: None,
+            _buffer: Vec::new(),
+        }
+    }
+
+    #[cfg(not(feature = "headless"))]
+    fn new_software(width: i32, height: i32) -> Self {
+        HeadlessContext::new(width, height)
+    }
+
     #[cfg(feature = "headless")]
     fn get_proc_address(s: &str) -> *const c_void {
         let c_str = CString::new(s).expect("Unable to create CString");
@@ -175,6 +200,44 @@ impl WindowWrapper {
     fn upload_software_to_native(&self) {
     }
 
+    /// Reads back the pixels of the given rect of the window in RGBA8 order.
+    /// With software rendering they are read directly from the framebuffer of
+    /// the SWGL context.
+    #[cfg(feature = "software")]
+    pub fn read_pixels_rgba8(
+        &self,
+        renderer: &mut webrender::Renderer,
+        rect: FramebufferIntRect,
+    ) -> Vec<u8> {
+        let swgl = match self.software_gl() {
+            Some(swgl) => swgl,
+            None => return renderer.read_pixels_rgba8(rect),
+        };
+        let (data_ptr, w, h, stride) = swgl.get_color_buffer(0, true);
+        assert!(!data_ptr.is_null(), "SWGL context has no framebuffer");
+        assert!(FramebufferIntRect::from_size(FramebufferIntSize::new(w, h)).contains_box(&rect));
+        let buffer = unsafe { slice::from_raw_parts(data_ptr as *const u8, stride as usize * h as usize) };
+        let mut pixels = Vec::with_capacity(rect.area() as usize * 4);
+        for y in rect.min.y .. rect.max.y {
+            let row = (y * stride) as usize;
+            let row = &buffer[row + rect.min.x as usize * 4 .. row + rect.max.x as usize * 4];
+            // SWGL stores pixels in BGRA8 order.
+            for bgra in row.chunks_exact(4) {
+                pixels.extend_from_slice(&[bgra[2], bgra[1], bgra[0], bgra[3]]);
+            }
+        }
+        pixels
+    }
+
+    #[cfg(not(feature = "software"))]
+    pub fn read_pixels_rgba8(
+        &self,
+        renderer: &mut webrender::Renderer,
+        rect: FramebufferIntRect,
+    ) -> Vec<u8> {
+        renderer.read_pixels_rgba8(rect)
+    }
+
     fn swap_buffers(&self) {
         match *self {
             WindowWrapper::WindowedContext(ref windowed_context, _, _) => {
@@ -411,7 +474,12 @@ fn make_window(
                 #[cfg(not(feature = "software"))]
                 _ => panic!(),
             };
-            WindowWrapper::Headless(HeadlessContext::new(size.width, size.height), gl, sw_ctx)
+            let context = if sw_ctx.is_some() {
+                HeadlessContext::new_software(size.width, size.height)
+            } else {
+                HeadlessContext::new(size.width, size.height)
+            };
+            WindowWrapper::Headless(context, gl, sw_ctx)
         }
     };
 
@@ -500,6 +568,14 @@ fn reftest<'a>(
     };
     let specific_reftest = subargs.value_of("REFTEST").map(|x| Path::new(x));
     let mut reftest_options = ReftestOptions::default();
+    reftest_options.reports = reftest_report_paths(subargs);
+    reftest_options.shard = subargs.value_of("shard").map(|s| {
+        let slash = s.find('/').expect("Shard must be specified as index/count");
+        let index: usize = s[.. slash].parse().expect("Invalid shard index");
+        let count: usize = s[slash + 1 ..].parse().expect("Invalid shard count");
+        assert!(index < count, "Shard index must be less than the shard count");
+        (index, count)
+    });
     if let Some(allow_max_diff) = subargs.value_of("fuzz_tolerance") {
         reftest_options.allow_max_difference = allow_max_diff.parse().unwrap_or(1);
         reftest_options.allow_num_differences = dim.width as usize * dim.height as usize;
@@ -510,6 +586,22 @@ fn reftest<'a>(
     num_failures
 }
 
+fn reftest_report_paths(subargs: &clap::ArgMatches) -> ReftestReportPaths {
+    ReftestReportPaths {
+        json: subargs.value_of("json").map(PathBuf::from),
+        junit: subargs.value_of("junit").map(PathBuf::from),
+        html: subargs.value_of("html").map(PathBuf::from),
+    }
+}
+
+/// The first seed and the number of seeds to fuzz.
+fn fuzz_range(subargs: &clap::ArgMatches) -> (u64, u64) {
+    let seed = subargs.value_of("seed").map_or(0, |s| s.parse().expect("Invalid seed"));
+    let iterations = subargs.value_of("iterations")
+        .map_or(1000, |s| s.parse().expect("Invalid number of iterations"));
+    (seed, iterations)
+}
+
 fn main() {
     #[cfg(feature = "env_logger")]
     env_logger::init();
@@ -560,6 +652,41 @@ fn main() {
         clap.get_matches()
     };
 
+    if let Some(subargs) = args.subcommand_matches("reftest") {
+        let jobs = subargs.value_of("jobs").map_or(1, |s| s.parse().expect("Invalid number of jobs"));
+        if jobs > 1 {
+            // Each job is a wrench process with its own renderer, so this
+            // one doesn't need a window.
+            let num_failures = reftest::run_parallel(jobs, &reftest_report_paths(subargs));
+            process::exit(num_failures as _);
+        }
+    } else if let Some(subargs) = args.subcommand_matches("diff") {
+        // Comparing images doesn't need a renderer.
+        let first_filename = subargs.value_of("first_filename").unwrap();
+        let second_filename = subargs.value_of("second_filename").unwrap();
+        let output = subargs.value_of("output").map(Path::new);
+        let identical = image_diff::diff(Path::new(first_filename), Path::new(second_filename), output);
+        process::exit(if identical { 0 } else { 1 });
+    } else if let Some(subargs) = args.subcommand_matches("capture_to_yaml") {
+        // Captures are read directly, without a renderer.
+        let input = Path::new(subargs.value_of("INPUT").unwrap());
+        let output = Path::new(subargs.value_of("OUTPUT").unwrap());
+        let unsupported = yaml_frame_writer::write_capture(input, output);
+        if !unsupported.is_empty() {
+            println!("Unsupported features: {}", unsupported.join(", "));
+        }
+        return;
+    } else if let Some(subargs) = args.subcommand_matches("fuzz") {
+        if !subargs.is_present("child") && !subargs.is_present("replay") {
+            // The scenes render in child wrench processes, so that this one
+            // survives their crashes and can minimize the crashing scenes.
+            let (seed, iterations) = fuzz_range(subargs);
+            let output_dir = Path::new(subargs.value_of("output").unwrap_or("fuzz-crashes"));
+            let num_crashes = fuzz::run(seed, iterations, output_dir);
+            process::exit(num_crashes as _);
+        }
+    }
+
     // handle some global arguments
     let res_path = args.value_of("shaders").map(|s| PathBuf::from(s));
     let size = args.value_of("size")
@@ -603,6 +730,23 @@ fn main() {
 
     let dump_shader_source = args.value_of("dump_shader_source").map(String::from);
 
+    let hdr = args.value_of("hdr").map(|tone_map| {
+        let mut hdr = webrender::HdrOptions::default();
+        hdr.tone_map = match tone_map {
+            "clip" => webrender::ToneMapOperator::Clip,
+            "reinhard" => webrender::ToneMapOperator::Reinhard,
+            "bt2390" => webrender::ToneMapOperator::Bt2390,
+            _ => panic!("Unexpected tone mapping operator {}", tone_map),
+        };
+        if let Some(nits) = args.value_of("hdr_reference_white") {
+            hdr.reference_white_nits = nits.parse().expect("invalid reference white");
+        }
+        if let Some(nits) = args.value_of("hdr_max_display_nits") {
+            hdr.max_display_nits = nits.parse().expect("invalid display peak");
+        }
+        hdr
+    });
+
     let mut events_loop = if args.is_present("headless") {
         None
     } else {
@@ -627,7 +771,10 @@ fn main() {
         }
     };
 
-    let software = args.is_present("software");
+    // Without OSMesa, headless rendering falls back to SWGL if available, so
+    // that it works without any GPU or windowing system.
+    let software = args.is_present("software") ||
+        (args.is_present("headless") && cfg!(feature = "software") && !cfg!(feature = "headless"));
 
     let mut window = make_window(
         size,
@@ -639,7 +786,7 @@ fn main() {
     );
     let dim = window.get_inner_size();
 
-    let needs_frame_notifier = ["perf", "reftest", "png", "rawtest", "test_invalidation"]
+    let needs_frame_notifier = ["perf", "reftest", "png", "rawtest", "test_invalidation", "test_yaml_round_trip", "fuzz"]
         .iter()
         .any(|s| args.subcommand_matches(s).is_some());
     let (notifier, rx) = if needs_frame_notifier {
@@ -665,6 +812,7 @@ fn main() {
         chase_primitive,
         dump_shader_source,
         notifier,
+        hdr,
     );
 
     if let Some(ui_str) = args.value_of("profiler_ui") {
@@ -748,11 +896,36 @@ fn main() {
         );
 
         harness.run();
+    } else if let Some(subargs) = args.subcommand_matches("test_yaml_round_trip") {
+        let dir = Path::new(subargs.value_of("INPUT").unwrap_or("reftests"));
+        let harness = test_yaml_round_trip::YamlRoundTripHarness::new(
+            &mut wrench,
+            &mut window,
+            rx.unwrap(),
+        );
+
+        let num_failures = harness.run(dir);
+        wrench.renderer.deinit();
+        process::exit(num_failures as _);
+    } else if let Some(subargs) = args.subcommand_matches("fuzz") {
+        let harness = fuzz::FuzzHarness::new(
+            &mut wrench,
+            &mut window,
+            rx.unwrap(),
+        );
+
+        match subargs.value_of("replay") {
+            Some(filename) => harness.replay(Path::new(filename)),
+            None => {
+                let (seed, iterations) = fuzz_range(subargs);
+                harness.run(seed, iterations);
+            }
+        }
     } else if let Some(subargs) = args.subcommand_matches("compare_perf") {
         let first_filename = subargs.value_of("first_filename").unwrap();
         let second_filename = subargs.value_of("second_filename").unwrap();
-        perf::compare(first_filename, second_filename);
-        return;
+        let num_regressions = perf::compare(first_filename, second_filename);
+        process::exit(num_regressions as _);
     } else if let Some(_) = args.subcommand_matches("test_init") {
         // Wrench::new() unwraps the Renderer initialization, so if
         // we reach this point then we have initialized successfully.
@@ -996,7 +1169,7 @@ fn render<'a>(
         None => {
             while body(wrench, vec![winit::Event::Awakened]) == winit::ControlFlow::Continue {}
             let fb_rect = FramebufferIntSize::new(size.width, size.height).into();
-            let pixels = wrench.renderer.read_pixels_rgba8(fb_rect);
+            let pixels = window.read_pixels_rgba8(&mut wrench.renderer, fb_rect);
             save_flipped("screenshot.png", pixels, size);
         }
         Some(ref mut events_loop) => {
@@ -4,6 +4,7 @@
 
 use crate::NotifierEvent;
 use crate::WindowWrapper;
+use crate::parse_function::parse_function;
 use serde_json;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
@@ -24,8 +25,97 @@ const COLOR_MAGENTA: &str = "\x1b[95m";
 const MIN_SAMPLE_COUNT: usize = 50;
 const SAMPLE_EXCLUDE_COUNT: usize = 10;
 
+// A timing regression only counts when the Mann-Whitney U test finds the new
+// samples larger than the old ones at this significance level.
+const SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// The measurements of a benchmark that can be compared and gated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Metric {
+    BackendTime,
+    CompositeTime,
+    /// The GPU time of the frame.
+    PaintTime,
+    DrawCalls,
+    TextureUploads,
+    RenderTargets,
+    /// The number of picture cache tiles invalidated per frame.
+    TileInvalidations,
+}
+
+const METRICS: [Metric; 7] = [
+    Metric::BackendTime,
+    Metric::CompositeTime,
+    Metric::PaintTime,
+    Metric::DrawCalls,
+    Metric::TextureUploads,
+    Metric::RenderTargets,
+    Metric::TileInvalidations,
+];
+
+impl Metric {
+    fn name(&self) -> &'static str {
+        match *self {
+            Metric::BackendTime => "backend-time",
+            Metric::CompositeTime => "composite-time",
+            Metric::PaintTime => "paint-time",
+            Metric::DrawCalls => "draw-calls",
+            Metric::TextureUploads => "texture-uploads",
+            Metric::RenderTargets => "render-targets",
+            Metric::TileInvalidations => "tile-invalidations",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Metric> {
+        METRICS.iter().cloned().find(|metric| metric.name() == name)
+    }
+
+    /// The timing samples of the metric, in ns, or None for counters.
+    fn samples<'a>(&self, test: &'a TestProfile) -> Option<&'a TestProfileRange> {
+        match *self {
+            Metric::BackendTime => Some(&test.backend_time_ns),
+            Metric::CompositeTime => Some(&test.composite_time_ns),
+            Metric::PaintTime => Some(&test.paint_time_ns),
+            _ => None,
+        }
+    }
+
+    /// The value of the metric, in ms for timings.
+    fn value(&self, test: &TestProfile) -> f64 {
+        match *self {
+            Metric::DrawCalls => test.draw_calls as f64,
+            Metric::TextureUploads => test.texture_uploads,
+            Metric::RenderTargets => test.render_targets,
+            Metric::TileInvalidations => test.tile_invalidations,
+            _ => self.samples(test).unwrap().median() as f64 / 1000000.0,
+        }
+    }
+
+    /// The values of the metric in an old and a new profile. Timings are
+    /// compared by their averages when either profile has no samples, as
+    /// an average can't be compared with a median.
+    fn values(&self, test0: &TestProfile, test1: &TestProfile) -> (f64, f64) {
+        match (self.samples(test0), self.samples(test1)) {
+            (Some(range0), Some(range1)) if range0.samples.is_empty() || range1.samples.is_empty() => {
+                (range0.avg as f64 / 1000000.0, range1.avg as f64 / 1000000.0)
+            }
+            _ => (self.value(test0), self.value(test1)),
+        }
+    }
+}
+
+/// The largest regression of a metric a benchmark allows, declared in the
+/// manifest as e.g. `threshold(paint-time,10)`.
+#[derive(Clone, Serialize, Deserialize)]
+struct Threshold {
+    metric: String,
+    /// The allowed increase of the metric, in percent.
+    percent: f64,
+}
+
 pub struct Benchmark {
     pub test: PathBuf,
+    thresholds: Vec<Threshold>,
 }
 
 pub struct BenchmarkManifest {
@@ -61,7 +151,22 @@ impl BenchmarkManifest {
                 }
                 Some(name) => {
                     let test = dir.join(name);
-                    benchmarks.push(Benchmark { test });
+                    let thresholds = items.map(|item| {
+                        let (function, args, _) = parse_function(item);
+                        assert_eq!(function, "threshold", "unknown benchmark option {}", item);
+                        assert_eq!(
+                            args.len(),
+                            2,
+                            "{} expects a metric and a percent, e.g. threshold(paint-time,10)",
+                            item,
+                        );
+                        assert!(Metric::from_name(args[0]).is_some(), "unknown metric {}", args[0]);
+                        Threshold {
+                            metric: args[0].to_string(),
+                            percent: args[1].parse().expect("invalid threshold"),
+                        }
+                    }).collect();
+                    benchmarks.push(Benchmark { test, thresholds });
                 }
                 _ => panic!(),
             };
@@ -78,6 +183,20 @@ struct TestProfileRange {
     min: u64,
     avg: u64,
     max: u64,
+    // Profiles written before these were added only have the above.
+    #[serde(default)]
+    p50: u64,
+    #[serde(default)]
+    p90: u64,
+    /// The samples, sorted.
+    #[serde(default)]
+    samples: Vec<u64>,
+}
+
+impl TestProfileRange {
+    fn median(&self) -> u64 {
+        if self.samples.is_empty() { self.avg } else { self.p50 }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -87,6 +206,15 @@ struct TestProfile {
     composite_time_ns: TestProfileRange,
     paint_time_ns: TestProfileRange,
     draw_calls: usize,
+    // The mean of these counters per frame.
+    #[serde(default)]
+    texture_uploads: f64,
+    #[serde(default)]
+    render_targets: f64,
+    #[serde(default)]
+    tile_invalidations: f64,
+    #[serde(default)]
+    thresholds: Vec<Threshold>,
 }
 
 impl TestProfile {
@@ -95,7 +223,10 @@ impl TestProfile {
         backend_time_ns min, avg, max,\
         composite_time_ns min, avg, max,\
         paint_time_ns min, avg, max,\
-        draw_calls\n".to_string()
+        draw_calls,\
+        texture_uploads,\
+        render_targets,\
+        tile_invalidations\n".to_string()
     }
 
     fn convert_to_csv(&self) -> String {
@@ -103,12 +234,15 @@ impl TestProfile {
                  {},{},{},\
                  {},{},{},\
                  {},{},{},\
-                 {}\n",
+                 {},{:.2},{:.2},{:.2}\n",
                 self.name,
                 self.backend_time_ns.min,   self.backend_time_ns.avg,   self.backend_time_ns.max,
                 self.composite_time_ns.min, self.composite_time_ns.avg, self.composite_time_ns.max,
                 self.paint_time_ns.min,     self.paint_time_ns.avg,     self.paint_time_ns.max,
-                self.draw_calls)
+                self.draw_calls,
+                self.texture_uploads,
+                self.render_targets,
+                self.tile_invalidations)
     }
 }
 
@@ -189,7 +323,8 @@ impl<'a> PerfHarness<'a> {
         let mut profile = Profile::new();
 
         for t in manifest.benchmarks {
-            let stats = self.render_yaml(t.test.as_path());
+            let mut stats = self.render_yaml(t.test.as_path());
+            stats.thresholds = t.thresholds;
             profile.add(stats);
         }
 
@@ -203,6 +338,7 @@ impl<'a> PerfHarness<'a> {
         // frame profiles. Then take the mean.
         let mut cpu_frame_profiles = Vec::new();
         let mut gpu_frame_profiles = Vec::new();
+        let mut render_targets = Vec::new();
 
         let mut debug_flags = DebugFlags::empty();
         debug_flags.set(DebugFlags::GPU_TIME_QUERIES | DebugFlags::GPU_SAMPLE_QUERIES, true);
@@ -215,12 +351,13 @@ impl<'a> PerfHarness<'a> {
         {
             reader.do_frame(self.wrench);
             self.rx.recv().unwrap();
-            self.wrench.render();
+            let results = self.wrench.render();
             self.window.swap_buffers();
             let (cpu_profiles, gpu_profiles) = self.wrench.get_frame_profiles();
             if frame_count >= self.warmup_frames {
                 cpu_frame_profiles.extend(cpu_profiles);
                 gpu_frame_profiles.extend(gpu_profiles);
+                render_targets.push(results.stats.color_target_count + results.stats.alpha_target_count);
             }
             frame_count = frame_count + 1;
         }
@@ -243,18 +380,26 @@ impl<'a> PerfHarness<'a> {
         let paint_time_ns = extract_sample(&mut gpu_frame_profiles, |a| a.paint_time_ns);
         let backend_time_ns = extract_sample(&mut cpu_frame_profiles, |a| a.backend_time_ns);
 
+        let texture_uploads = mean(cpu_frame_profiles.iter().map(|p| p.texture_uploads));
+        let tile_invalidations = mean(cpu_frame_profiles.iter().map(|p| p.rendered_picture_tiles));
+        let render_targets = mean(render_targets.into_iter());
+
         TestProfile {
             name: filename.to_str().unwrap().to_string(),
             composite_time_ns,
             paint_time_ns,
             backend_time_ns,
             draw_calls,
+            texture_uploads,
+            render_targets,
+            tile_invalidations,
+            thresholds: Vec::new(),
         }
     }
 }
 
-// returns min, average, max, after removing the lowest and highest SAMPLE_EXCLUDE_COUNT
-// samples (each).
+// returns min, average, max, percentiles and the samples, after removing the lowest and
+// highest SAMPLE_EXCLUDE_COUNT samples (each).
 fn extract_sample<F, T>(profiles: &mut [T], f: F) -> TestProfileRange
 where
     F: Fn(&T) -> u64,
@@ -266,8 +411,69 @@ where
     TestProfileRange {
         min: useful_samples[0],
         avg: total_time / useful_samples.len() as u64,
-        max: useful_samples[useful_samples.len()-1]
+        max: useful_samples[useful_samples.len()-1],
+        p50: percentile(useful_samples, 50),
+        p90: percentile(useful_samples, 90),
+        samples: useful_samples.to_vec(),
+    }
+}
+
+fn mean<I: Iterator<Item = usize>>(values: I) -> f64 {
+    let (count, total) = values.fold((0, 0), |(count, total), v| (count + 1, total + v));
+    if count == 0 { 0.0 } else { total as f64 / count as f64 }
+}
+
+// nearest-rank percentile of sorted samples.
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    let rank = (percent * sorted.len() + 99) / 100;
+    sorted[rank.max(1) - 1]
+}
+
+/// The one-sided p-value of the Mann-Whitney U test that the samples of `b`
+/// tend to be larger than those of `a`, using the normal approximation with
+/// a tie correction.
+fn mann_whitney_p(a: &[u64], b: &[u64]) -> f64 {
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let mut values: Vec<(u64, bool)> = a.iter().map(|&v| (v, false))
+        .chain(b.iter().map(|&v| (v, true)))
+        .collect();
+    values.sort_by_key(|&(v, _)| v);
+
+    // Tied values share the mean of their ranks.
+    let mut rank_sum_b = 0.0;
+    let mut tie_correction = 0.0;
+    let mut i = 0;
+    while i < values.len() {
+        let mut j = i;
+        while j < values.len() && values[j].0 == values[i].0 {
+            j += 1;
+        }
+        let rank = (i + j + 1) as f64 / 2.0;
+        rank_sum_b += rank * values[i .. j].iter().filter(|&&(_, in_b)| in_b).count() as f64;
+        let ties = (j - i) as f64;
+        tie_correction += ties * ties * ties - ties;
+        i = j;
     }
+
+    let n = n_a + n_b;
+    let u_b = rank_sum_b - n_b * (n_b + 1.0) / 2.0;
+    let variance = n_a * n_b / 12.0 * ((n + 1.0) - tie_correction / (n * (n - 1.0)));
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    // With a continuity correction.
+    let z = (u_b - n_a * n_b / 2.0 - 0.5) / variance.sqrt();
+    0.5 * erfc(z / 2f64.sqrt())
+}
+
+// Abramowitz and Stegun 7.1.26, accurate to 1.5e-7.
+fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    poly * (-x * x).exp()
 }
 
 fn select_color(base: f32, value: f32) -> &'static str {
@@ -281,13 +487,68 @@ fn select_color(base: f32, value: f32) -> &'static str {
     }
 }
 
-pub fn compare(first_filename: &str, second_filename: &str) {
+/// A metric that got worse than its threshold allows.
+struct Regression {
+    metric: Metric,
+    old: f64,
+    new: f64,
+    threshold: f64,
+    p_value: Option<f64>,
+}
+
+impl Regression {
+    fn change_percent(&self) -> f64 {
+        if self.old == 0.0 {
+            std::f64::INFINITY
+        } else {
+            (self.new - self.old) / self.old * 100.0
+        }
+    }
+}
+
+fn find_regressions(test0: &TestProfile, test1: &TestProfile) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+
+    // The thresholds come from the manifest of the newer run.
+    for threshold in &test1.thresholds {
+        let metric = match Metric::from_name(&threshold.metric) {
+            Some(metric) => metric,
+            None => continue,
+        };
+        let (old, new) = metric.values(test0, test1);
+
+        // Timings are noisy, so they also need to be significantly slower,
+        // when both runs recorded their samples.
+        let p_value = match (metric.samples(test0), metric.samples(test1)) {
+            (Some(range0), Some(range1)) if !range0.samples.is_empty() && !range1.samples.is_empty() => {
+                Some(mann_whitney_p(&range0.samples, &range1.samples))
+            }
+            _ => None,
+        };
+
+        let regression = Regression { metric, old, new, threshold: threshold.percent, p_value };
+        if new > old &&
+           regression.change_percent() > threshold.percent &&
+           p_value.map_or(true, |p| p < SIGNIFICANCE_LEVEL) {
+            regressions.push(regression);
+        }
+    }
+
+    regressions
+}
+
+/// Compares two profiles, and returns the number of regressions that exceed
+/// the thresholds of their benchmarks.
+pub fn compare(first_filename: &str, second_filename: &str) -> usize {
     let profile0 = Profile::load(first_filename);
     let profile1 = Profile::load(second_filename);
 
     let (set0, map0) = profile0.build_set_and_map_of_tests();
     let (set1, map1) = profile1.build_set_and_map_of_tests();
 
+    let mut common_tests: Vec<&String> = set0.intersection(&set1).collect();
+    common_tests.sort();
+
     print!("+------------------------------------------------");
     println!("+--------------+------------------+------------------+");
     print!("|  Test name                                     ");
@@ -307,15 +568,15 @@ pub fn compare(first_filename: &str, second_filename: &str) {
         );
     }
 
-    for test_name in set0.intersection(&set1) {
+    for &test_name in &common_tests {
         let test0 = &map0[test_name];
         let test1 = &map1[test_name];
 
-        let composite_time0 = test0.composite_time_ns.avg as f32 / 1000000.0;
-        let composite_time1 = test1.composite_time_ns.avg as f32 / 1000000.0;
+        let (composite_time0, composite_time1) = Metric::CompositeTime.values(test0, test1);
+        let (composite_time0, composite_time1) = (composite_time0 as f32, composite_time1 as f32);
 
-        let paint_time0 = test0.paint_time_ns.avg as f32 / 1000000.0;
-        let paint_time1 = test1.paint_time_ns.avg as f32 / 1000000.0;
+        let (paint_time0, paint_time1) = Metric::PaintTime.values(test0, test1);
+        let (paint_time0, paint_time1) = (paint_time0 as f32, paint_time1 as f32);
 
         let draw_calls_color = if test0.draw_calls == test1.draw_calls {
             COLOR_DEFAULT
@@ -349,4 +610,189 @@ pub fn compare(first_filename: &str, second_filename: &str) {
 
     print!("+------------------------------------------------");
     println!("+--------------+------------------+------------------+");
+    println!("Times are medians, or averages when a profile has no samples. Per frame counters:");
+    print!("+------------------------------------------------");
+    println!("+------------------+------------------+------------------+");
+    print!("|  Test name                                     ");
+    println!("| Texture uploads  | Render targets   | Invalidated tiles|");
+    print!("+------------------------------------------------");
+    println!("+------------------+------------------+------------------+");
+
+    for &test_name in &common_tests {
+        let test0 = &map0[test_name];
+        let test1 = &map1[test_name];
+
+        print!("| {:47}", test_name);
+        for &metric in &[Metric::TextureUploads, Metric::RenderTargets, Metric::TileInvalidations] {
+            let (value0, value1) = metric.values(test0, test1);
+            let (value0, value1) = (value0 as f32, value1 as f32);
+            print!(
+                "|{}{:18}{}",
+                select_color(value0, value1),
+                format!(" {:.1} -> {:.1}", value0, value1),
+                COLOR_DEFAULT,
+            );
+        }
+        println!("|");
+    }
+
+    print!("+------------------------------------------------");
+    println!("+------------------+------------------+------------------+");
+
+    let mut num_regressions = 0;
+    for &test_name in &common_tests {
+        for regression in find_regressions(&map0[test_name], &map1[test_name]) {
+            let p_value = regression.p_value.map_or(String::new(), |p| format!(", p = {:.4}", p));
+            println!(
+                "{}REGRESSION{} | {} | {} {:.2} -> {:.2} (+{:.1}%, allowed {}%{})",
+                COLOR_RED,
+                COLOR_DEFAULT,
+                test_name,
+                regression.metric.name(),
+                regression.old,
+                regression.new,
+                regression.change_percent(),
+                regression.threshold,
+                p_value,
+            );
+            num_regressions += 1;
+        }
+    }
+    println!("{} regressions over the thresholds", num_regressions);
+
+    num_regressions
+}
+
+#[cfg(test)]
+fn test_profile(paint_time_ns: TestProfileRange, draw_calls: usize, thresholds: &[(&str, f64)]) -> TestProfile {
+    let no_samples = TestProfileRange { min: 0, avg: 0, max: 0, p50: 0, p90: 0, samples: Vec::new() };
+    TestProfile {
+        name: "test".to_string(),
+        backend_time_ns: no_samples.clone(),
+        composite_time_ns: no_samples,
+        paint_time_ns,
+        draw_calls,
+        texture_uploads: 0.0,
+        render_targets: 0.0,
+        tile_invalidations: 0.0,
+        thresholds: thresholds.iter().map(|&(metric, percent)| {
+            Threshold { metric: metric.to_string(), percent }
+        }).collect(),
+    }
+}
+
+#[cfg(test)]
+fn sampled_range(mut samples: Vec<u64>) -> TestProfileRange {
+    samples.sort();
+    TestProfileRange {
+        min: samples[0],
+        avg: samples.iter().sum::<u64>() / samples.len() as u64,
+        max: samples[samples.len() - 1],
+        p50: percentile(&samples, 50),
+        p90: percentile(&samples, 90),
+        samples,
+    }
+}
+
+#[test]
+fn percentile_is_nearest_rank() {
+    assert_eq!(percentile(&[7], 0), 7);
+    assert_eq!(percentile(&[7], 50), 7);
+    assert_eq!(percentile(&[7], 100), 7);
+    assert_eq!(percentile(&[1, 2, 3], 50), 2);
+    assert_eq!(percentile(&[10, 20, 30, 40], 0), 10);
+    assert_eq!(percentile(&[10, 20, 30, 40], 50), 20);
+    assert_eq!(percentile(&[10, 20, 30, 40], 51), 30);
+    assert_eq!(percentile(&[10, 20, 30, 40], 90), 40);
+    assert_eq!(percentile(&[10, 20, 30, 40], 100), 40);
+}
+
+#[test]
+fn erfc_matches_reference_values() {
+    let values = [
+        (0.0, 1.0),
+        (0.5, 0.4795001221869535),
+        (1.0, 0.15729920705028513),
+        (3.0, 2.209049699858544e-5),
+        (-1.0, 1.842700792949715),
+    ];
+    for &(x, expected) in &values {
+        assert!((erfc(x) - expected).abs() < 1e-6, "erfc({}) = {} != {}", x, erfc(x), expected);
+    }
+}
+
+#[test]
+fn mann_whitney_matches_reference_values() {
+    // The asymptotic p-values with continuity and tie corrections, as given by
+    // scipy.stats.mannwhitneyu(b, a, alternative="greater").
+    let cases: [(&[u64], &[u64], f64); 4] = [
+        (&[1, 2, 3, 4, 5], &[6, 7, 8, 9, 10], 0.006092890177672409),
+        (&[6, 7, 8, 9, 10], &[1, 2, 3, 4, 5], 0.9966923245172357),
+        (&[1, 2, 2, 3], &[2, 3, 3, 4], 0.08601685446091148),
+        (&[10, 20, 30], &[10, 20, 30], 0.5902615116112394),
+    ];
+    for &(a, b, expected) in &cases {
+        let p = mann_whitney_p(a, b);
+        assert!((p - expected).abs() < 1e-6, "p({:?}, {:?}) = {} != {}", a, b, p, expected);
+    }
+
+    // All values tied leaves no variance to test.
+    assert_eq!(mann_whitney_p(&[5, 5, 5], &[5, 5, 5]), 1.0);
+}
+
+#[test]
+fn regressions_compare_averages_without_samples() {
+    // A profile written before samples were recorded only has an average.
+    let old_range = TestProfileRange { min: 9000000, avg: 10000000, max: 11000000, p50: 0, p90: 0, samples: Vec::new() };
+    let old = test_profile(old_range, 10, &[]);
+
+    // The median is 20% slower, but the average only 2%, which is within the
+    // threshold as the averages are compared.
+    let mut samples = vec![12000000; 6];
+    samples.extend_from_slice(&[7500000; 4]);
+    let new = test_profile(sampled_range(samples), 10, &[("paint-time", 10.0)]);
+    assert_eq!(new.paint_time_ns.p50, 12000000);
+    assert_eq!(new.paint_time_ns.avg, 10200000);
+    assert_eq!(Metric::PaintTime.values(&old, &new), (10.0, 10.2));
+    assert!(find_regressions(&old, &new).is_empty());
+
+    // Without samples there's no significance test, so the threshold alone
+    // gates the regression.
+    let new = test_profile(sampled_range(vec![12000000; 10]), 10, &[("paint-time", 10.0)]);
+    let regressions = find_regressions(&old, &new);
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].metric, Metric::PaintTime);
+    assert_eq!((regressions[0].old, regressions[0].new), (10.0, 12.0));
+    assert!(regressions[0].p_value.is_none());
+
+    let new = test_profile(sampled_range(vec![12000000; 10]), 10, &[("paint-time", 25.0)]);
+    assert!(find_regressions(&old, &new).is_empty());
+}
+
+#[test]
+fn regressions_need_significant_samples() {
+    let old = test_profile(sampled_range((100 .. 120).map(|i| i * 100000).collect()), 10, &[]);
+
+    // Every new sample is slower.
+    let slower: Vec<u64> = (150 .. 170).map(|i| i * 100000).collect();
+    let new = test_profile(sampled_range(slower), 10, &[("paint-time", 10.0)]);
+    let regressions = find_regressions(&old, &new);
+    assert_eq!(regressions.len(), 1);
+    assert!(regressions[0].p_value.unwrap() < SIGNIFICANCE_LEVEL);
+
+    // A slower median from a few outliers isn't significant.
+    let mut noisy: Vec<u64> = (100 .. 120).map(|i| i * 100000).collect();
+    for sample in &mut noisy[9 .. 11] {
+        *sample *= 2;
+    }
+    let new = test_profile(sampled_range(noisy), 10, &[("paint-time", 1.0)]);
+    assert!(new.paint_time_ns.p50 > old.paint_time_ns.p50);
+    assert!(find_regressions(&old, &new).is_empty());
+
+    // Counters are gated by the threshold alone.
+    let new = test_profile(sampled_range((100 .. 120).map(|i| i * 100000).collect()), 12, &[("draw-calls", 10.0)]);
+    let regressions = find_regressions(&old, &new);
+    assert_eq!(regressions.len(), 1);
+    assert_eq!(regressions[0].metric, Metric::DrawCalls);
+    assert!(regressions[0].p_value.is_none());
 }
@@ -92,7 +92,7 @@ pub fn png(
         ReadSurface::Screen => {
             let dim = window.get_inner_size();
             let rect = FramebufferIntSize::new(dim.width, dim.height).into();
-            let data = wrench.renderer.read_pixels_rgba8(rect);
+            let data = window.read_pixels_rgba8(&mut wrench.renderer, rect);
             (dim, data, SaveSettings {
                 flip_vertical: true,
                 try_crop: true,
@@ -17,7 +17,8 @@ pub fn premultiply(data: &mut [u8]) {
     }
 }
 
-#[allow(unused)]
+/// The inverse of `premultiply`. Picks the largest color that premultiplies
+/// back to the same value, so that premultiplied images round trip exactly.
 pub fn unpremultiply(data: &mut [u8]) {
     for pixel in data.chunks_mut(4) {
         let a = pixel[3] as u32;
@@ -26,9 +27,9 @@ pub fn unpremultiply(data: &mut [u8]) {
         let mut r = pixel[0] as u32;
 
         if a > 0 {
-            r = r * 255 / a;
-            g = g * 255 / a;
-            b = b * 255 / a;
+            r = ((r * 255 + 126) / a).min(255);
+            g = ((g * 255 + 126) / a).min(255);
+            b = ((b * 255 + 126) / a).min(255);
         }
 
         pixel[3] = a as u8;
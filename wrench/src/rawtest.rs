@@ -53,7 +53,7 @@ impl<'a> RawtestHarness<'a> {
     fn render_and_get_pixels(&mut self, window_rect: FramebufferIntRect) -> Vec<u8> {
         self.rx.recv().unwrap();
         self.wrench.render();
-        self.wrench.renderer.read_pixels_rgba8(window_rect)
+        self.window.read_pixels_rgba8(&mut self.wrench.renderer, window_rect)
     }
 
     fn compare_pixels(&self, data1: Vec<u8>, data2: Vec<u8>, size: FramebufferIntSize) {
@@ -1428,6 +1428,32 @@ impl<'a> RawtestHarness<'a> {
 
         test_rounded_rectangle(WorldPoint::new(100., 100.), WorldSize::new(100., 100.), (0, 4));
         test_rounded_rectangle(WorldPoint::new(200., 100.), WorldSize::new(100., 100.), (0, 5));
+
+        let hit_tester = self.wrench.api.request_hit_tester(self.wrench.document_id).resolve();
+        let root_scroll_id = ExternalScrollId(0, self.wrench.root_pipeline_id);
+
+        // The normal rectangle is entirely inside the rect, the others only partly.
+        let result = hit_tester.hit_test_rect(
+            None,
+            WorldRect::new(WorldPoint::new(90., -10.), WorldPoint::new(210., 110.)),
+        );
+        let hits: Vec<_> = result.items.iter().map(|item| (item.tag, item.coverage)).collect();
+        assert_eq!(hits, vec![
+            ((0, 5), HitTestCoverage::Partial),
+            ((0, 4), HitTestCoverage::Partial),
+            ((0, 2), HitTestCoverage::Full),
+            ((0, 1), HitTestCoverage::Partial),
+        ]);
+        assert_eq!(result.items[2].world_bounds, WorldRect::new(WorldPoint::new(100., 0.), WorldPoint::new(200., 100.)));
+        assert_eq!(result.items[2].scroll_ids, vec![root_scroll_id]);
+
+        // A polygon in the top left corner only intersects the background.
+        let result = hit_tester.hit_test_polygon(
+            None,
+            &[WorldPoint::new(10., 10.), WorldPoint::new(60., 10.), WorldPoint::new(10., 60.)],
+        );
+        let hits: Vec<_> = result.items.iter().map(|item| (item.tag, item.coverage)).collect();
+        assert_eq!(hits, vec![((0, 1), HitTestCoverage::Partial)]);
     }
 
     fn test_clear_cache(&mut self) {
@@ -4,25 +4,30 @@
 
 use crate::{WindowWrapper, NotifierEvent};
 use base64;
+use crate::image_diff::ImageMetrics;
 use semver;
 use image::load as load_piston_image;
 use image::png::PNGEncoder;
 use image::{ColorType, ImageFormat};
 use crate::parse_function::parse_function;
 use crate::png::save_flipped;
-use std::{cmp, env};
+use crate::reftest_report::{ReftestFailure, ReftestFailureImages, ReftestFuzziness};
+use crate::reftest_report::{ReftestReport, ReftestReportPaths, ReftestResult};
+use std::{cmp, env, fs, process, thread};
 use std::fmt::{Display, Error, Formatter};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::mpsc::Receiver;
+use std::time::Instant;
 use webrender::RenderResults;
 use webrender::api::*;
 use webrender::render_api::*;
 use webrender::api::units::*;
 use crate::wrench::{Wrench, WrenchThing};
 use crate::yaml_frame_reader::YamlFrameReader;
+use crate::yaml_helper::StringEnum;
 
 
 const OPTION_DISABLE_SUBPX: &str = "disable-subpixel";
@@ -34,6 +39,10 @@ pub struct ReftestOptions {
     // These override values that are lower.
     pub allow_max_difference: usize,
     pub allow_num_differences: usize,
+    /// Only run the tests whose index is `index` modulo `count`, given as
+    /// `(index, count)`.
+    pub shard: Option<(usize, usize)>,
+    pub reports: ReftestReportPaths,
 }
 
 impl ReftestOptions {
@@ -41,6 +50,8 @@ impl ReftestOptions {
         ReftestOptions {
             allow_max_difference: 0,
             allow_num_differences: 0,
+            shard: None,
+            reports: ReftestReportPaths::default(),
         }
     }
 }
@@ -97,16 +108,74 @@ pub struct RefTestFuzzy {
     num_differences: usize,
 }
 
+/// A perceptual metric the test image must meet. When a test has any of
+/// these, they replace the pixel differences allowed by `fuzzy`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PerceptualThreshold {
+    /// The minimum SSIM of any window of the images, so that a localized
+    /// difference fails even if the rest of the images are identical.
+    Ssim(f64),
+    /// The minimum PSNR of the images, in dB.
+    Psnr(f64),
+    /// The maximum CIEDE2000 difference of any pixel.
+    DeltaE(f64),
+}
+
+impl PerceptualThreshold {
+    fn new(name: &str, value: f64) -> Self {
+        match name {
+            "ssim" => PerceptualThreshold::Ssim(value),
+            "psnr" => PerceptualThreshold::Psnr(value),
+            "delta-e" => PerceptualThreshold::DeltaE(value),
+            _ => panic!("unknown threshold {}", name),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            PerceptualThreshold::Ssim(..) => "ssim",
+            PerceptualThreshold::Psnr(..) => "psnr",
+            PerceptualThreshold::DeltaE(..) => "delta-e",
+        }
+    }
+
+    /// Returns a description of the failure if the metrics don't meet the
+    /// threshold.
+    fn check(&self, metrics: &ImageMetrics) -> Option<String> {
+        match *self {
+            PerceptualThreshold::Ssim(min) if metrics.min_ssim < min =>
+                Some(format!("minimum window ssim {:.5} < {}", metrics.min_ssim, min)),
+            PerceptualThreshold::Psnr(min) if metrics.psnr < min =>
+                Some(format!("psnr {:.2} dB < {}", metrics.psnr, min)),
+            PerceptualThreshold::DeltaE(max) if metrics.max_delta_e > max =>
+                Some(format!("delta-e {:.3} > {}", metrics.max_delta_e, max)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PerceptualThreshold {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            PerceptualThreshold::Ssim(value) |
+            PerceptualThreshold::Psnr(value) |
+            PerceptualThreshold::DeltaE(value) => write!(f, "{}({})", self.name(), value),
+        }
+    }
+}
+
 pub struct Reftest {
     op: ReftestOp,
     test: Vec<PathBuf>,
     reference: PathBuf,
     font_render_mode: Option<FontRenderMode>,
     fuzziness: Vec<RefTestFuzzy>,
+    thresholds: Vec<PerceptualThreshold>,
     extra_checks: Vec<ExtraCheck>,
     disable_dual_source_blending: bool,
     allow_mipmaps: bool,
     force_subpixel_aa_where_possible: Option<bool>,
+    output_color_space: RgbColorSpace,
 }
 
 impl Reftest {
@@ -116,94 +185,24 @@ impl Reftest {
         comparison: ReftestImageComparison,
         test: &ReftestImage,
         reference: &ReftestImage,
-    ) -> bool {
+    ) -> Result<(), ReftestFailure> {
         match comparison {
             ReftestImageComparison::Equal => {
-                true
+                Ok(())
             }
             ReftestImageComparison::NotEqual { difference_histogram, max_difference, count_different } => {
-                // Each entry in the sorted self.fuzziness list represents a bucket which
-                // allows at most num_differences pixels with a difference of at most
-                // max_difference -- but with the caveat that a difference which is small
-                // enough to be less than a max_difference of an earlier bucket, must be
-                // counted against that bucket.
-                //
-                // Thus the test will fail if the number of pixels with a difference
-                // > fuzzy[j-1].max_difference and <= fuzzy[j].max_difference
-                // exceeds fuzzy[j].num_differences.
-                //
-                // (For the first entry, consider fuzzy[j-1] to allow zero pixels of zero
-                // difference).
-                //
-                // For example, say we have this histogram of differences:
-                //
-                //       | [0] [1] [2] [3] [4] [5] [6] ... [255]
-                // ------+------------------------------------------
-                // Hist. |  0   3   2   1   6   2   0  ...   0
-                //
-                // Ie. image comparison found 3 pixels that differ by 1, 2 that differ by 2, etc.
-                // (Note that entry 0 is always zero, we don't count matching pixels.)
-                //
-                // First we calculate an inclusive prefix sum:
-                //
-                //       | [0] [1] [2] [3] [4] [5] [6] ... [255]
-                // ------+------------------------------------------
-                // Hist. |  0   3   2   1   6   2   0  ...   0
-                // Sum   |  0   3   5   6  12  14  14  ...  14
-                //
-                // Let's say the fuzzy statements are:
-                // Fuzzy( 2, 6 )    -- allow up to 6 pixels that differ by 2 or less
-                // Fuzzy( 4, 8 )    -- allow up to 8 pixels that differ by 4 or less _but_
-                //                     also by more than 2 (= by 3 or 4).
-                //
-                // The first  check is Sum[2] <= max 6  which passes: 5 <= 6.
-                // The second check is Sum[4] - Sum[2] <= max 8  which passes: 12-5 <= 8.
-                // Finally we check if there are any pixels that exceed the max difference (4)
-                // by checking Sum[255] - Sum[4] which shows there are 14-12 == 2 so we fail.
-
-                let prefix_sum = difference_histogram.iter()
-                                                     .scan(0, |sum, i| { *sum += i; Some(*sum) })
-                                                     .collect::<Vec<_>>();
-
-                // check each fuzzy statement for violations.
-                assert_eq!(0, difference_histogram[0]);
-                assert_eq!(0, prefix_sum[0]);
-
-                // loop invariant: this is the max_difference of the previous iteration's 'fuzzy'
-                let mut previous_max_diff = 0;
-
-                // loop invariant: this is the number of pixels to ignore as they have been counted
-                // against previous iterations' fuzzy statements.
-                let mut previous_sum_fail = 0;  // ==  prefix_sum[previous_max_diff]
-
-                let mut is_failing = false;
-                let mut fail_text = String::new();
-
-                for fuzzy in &self.fuzziness {
-                    let fuzzy_max_difference = cmp::min(255, fuzzy.max_difference);
-                    let num_differences = prefix_sum[fuzzy_max_difference] - previous_sum_fail;
-                    if num_differences > fuzzy.num_differences {
-                        fail_text.push_str(
-                            &format!("{} differences > {} and <= {} (allowed {}); ",
-                                     num_differences,
-                                     previous_max_diff, fuzzy_max_difference,
-                                     fuzzy.num_differences));
-                        is_failing = true;
-                    }
-                    previous_max_diff = fuzzy_max_difference;
-                    previous_sum_fail = prefix_sum[previous_max_diff];
-                }
-                // do we have any pixels with a difference above the highest allowed
-                // max difference? if so, we fail the test:
-                let num_differences = prefix_sum[255] - previous_sum_fail;
-                if num_differences > 0 {
-                    fail_text.push_str(
-                        &format!("{} num_differences > {} and <= {} (allowed {}); ",
-                                num_differences,
-                                previous_max_diff, 255,
-                                0));
-                    is_failing = true;
-                }
+                let (is_failing, fail_text, metrics) = if self.thresholds.is_empty() {
+                    let (is_failing, fail_text) = self.check_fuzziness(&difference_histogram);
+                    (is_failing, fail_text, None)
+                } else {
+                    let metrics = ImageMetrics::compute(test, reference);
+                    let fail_text: String = self.thresholds
+                        .iter()
+                        .filter_map(|threshold| threshold.check(&metrics))
+                        .map(|text| format!("{}; ", text))
+                        .collect();
+                    (!fail_text.is_empty(), fail_text, Some(metrics))
+                };
 
                 if is_failing {
                     println!(
@@ -216,25 +215,140 @@ impl Reftest {
                         count_different,
                         fail_text,
                     );
-                    println!("REFTEST   IMAGE 1 (TEST): {}", test.clone().create_data_uri());
-                    println!(
-                        "REFTEST   IMAGE 2 (REFERENCE): {}",
-                        reference.clone().create_data_uri()
-                    );
+                    let images = ReftestFailureImages {
+                        test: test.clone().create_data_uri(),
+                        reference: reference.clone().create_data_uri(),
+                        diff: test.create_diff_image(reference).create_data_uri(),
+                    };
+                    println!("REFTEST   IMAGE 1 (TEST): {}", images.test);
+                    println!("REFTEST   IMAGE 2 (REFERENCE): {}", images.reference);
                     println!("REFTEST TEST-END | {}", self);
 
-                    false
+                    Err(ReftestFailure {
+                        message: format!("image comparison: {}", fail_text.trim_end_matches("; ")),
+                        max_difference: Some(max_difference),
+                        count_different: Some(count_different),
+                        metrics,
+                        images: Some(images),
+                    })
                 } else {
-                    true
+                    Ok(())
                 }
             }
         }
     }
 
+    /// Checks a histogram of pixel differences against the fuzziness allowed
+    /// by the manifest, and returns whether it fails and why.
+    fn check_fuzziness(&self, difference_histogram: &[usize]) -> (bool, String) {
+        // Each entry in the sorted self.fuzziness list represents a bucket which
+        // allows at most num_differences pixels with a difference of at most
+        // max_difference -- but with the caveat that a difference which is small
+        // enough to be less than a max_difference of an earlier bucket, must be
+        // counted against that bucket.
+        //
+        // Thus the test will fail if the number of pixels with a difference
+        // > fuzzy[j-1].max_difference and <= fuzzy[j].max_difference
+        // exceeds fuzzy[j].num_differences.
+        //
+        // (For the first entry, consider fuzzy[j-1] to allow zero pixels of zero
+        // difference).
+        //
+        // For example, say we have this histogram of differences:
+        //
+        //       | [0] [1] [2] [3] [4] [5] [6] ... [255]
+        // ------+------------------------------------------
+        // Hist. |  0   3   2   1   6   2   0  ...   0
+        //
+        // Ie. image comparison found 3 pixels that differ by 1, 2 that differ by 2, etc.
+        // (Note that entry 0 is always zero, we don't count matching pixels.)
+        //
+        // First we calculate an inclusive prefix sum:
+        //
+        //       | [0] [1] [2] [3] [4] [5] [6] ... [255]
+        // ------+------------------------------------------
+        // Hist. |  0   3   2   1   6   2   0  ...   0
+        // Sum   |  0   3   5   6  12  14  14  ...  14
+        //
+        // Let's say the fuzzy statements are:
+        // Fuzzy( 2, 6 )    -- allow up to 6 pixels that differ by 2 or less
+        // Fuzzy( 4, 8 )    -- allow up to 8 pixels that differ by 4 or less _but_
+        //                     also by more than 2 (= by 3 or 4).
+        //
+        // The first  check is Sum[2] <= max 6  which passes: 5 <= 6.
+        // The second check is Sum[4] - Sum[2] <= max 8  which passes: 12-5 <= 8.
+        // Finally we check if there are any pixels that exceed the max difference (4)
+        // by checking Sum[255] - Sum[4] which shows there are 14-12 == 2 so we fail.
+
+        let prefix_sum = difference_histogram.iter()
+                                             .scan(0, |sum, i| { *sum += i; Some(*sum) })
+                                             .collect::<Vec<_>>();
+
+        // check each fuzzy statement for violations.
+        assert_eq!(0, difference_histogram[0]);
+        assert_eq!(0, prefix_sum[0]);
+
+        // loop invariant: this is the max_difference of the previous iteration's 'fuzzy'
+        let mut previous_max_diff = 0;
+
+        // loop invariant: this is the number of pixels to ignore as they have been counted
+        // against previous iterations' fuzzy statements.
+        let mut previous_sum_fail = 0;  // ==  prefix_sum[previous_max_diff]
+
+        let mut is_failing = false;
+        let mut fail_text = String::new();
+
+        for fuzzy in &self.fuzziness {
+            let fuzzy_max_difference = cmp::min(255, fuzzy.max_difference);
+            let num_differences = prefix_sum[fuzzy_max_difference] - previous_sum_fail;
+            if num_differences > fuzzy.num_differences {
+                fail_text.push_str(
+                    &format!("{} differences > {} and <= {} (allowed {}); ",
+                             num_differences,
+                             previous_max_diff, fuzzy_max_difference,
+                             fuzzy.num_differences));
+                is_failing = true;
+            }
+            previous_max_diff = fuzzy_max_difference;
+            previous_sum_fail = prefix_sum[previous_max_diff];
+        }
+        // do we have any pixels with a difference above the highest allowed
+        // max difference? if so, we fail the test:
+        let num_differences = prefix_sum[255] - previous_sum_fail;
+        if num_differences > 0 {
+            fail_text.push_str(
+                &format!("{} num_differences > {} and <= {} (allowed {}); ",
+                        num_differences,
+                        previous_max_diff, 255,
+                        0));
+            is_failing = true;
+        }
+
+        (is_failing, fail_text)
+    }
+
     /// Report details of the negative case
-    fn report_unexpected_equality(&self) {
+    fn report_unexpected_equality(&self) -> ReftestFailure {
         println!("REFTEST TEST-UNEXPECTED-FAIL | {} | image comparison", self);
         println!("REFTEST TEST-END | {}", self);
+        ReftestFailure::new("image comparison: the images are unexpectedly equal".to_owned())
+    }
+
+    fn result(&self, time: f64, failure: Option<ReftestFailure>) -> ReftestResult {
+        ReftestResult {
+            name: self.to_string(),
+            suite: self.reference.parent().map_or(String::new(), |dir| dir.display().to_string()),
+            time,
+            fuzziness: self.fuzziness
+                .iter()
+                .map(|fuzzy| ReftestFuzziness {
+                    max_difference: fuzzy.max_difference,
+                    num_differences: fuzzy.num_differences,
+                })
+                .collect(),
+            thresholds: self.thresholds.iter().map(|threshold| threshold.to_string()).collect(),
+            failure,
+        }
     }
 }
 
@@ -269,6 +383,17 @@ pub enum ReftestImageComparison {
 }
 
 impl ReftestImage {
+    pub fn load(filename: &Path, format: ImageFormat) -> ReftestImage {
+        let file = BufReader::new(File::open(filename).unwrap());
+        let img_raw = load_piston_image(file, format).unwrap();
+        let img = img_raw.flipv().to_rgba();
+        let size = img.dimensions();
+        ReftestImage {
+            data: img.into_raw(),
+            size: DeviceIntSize::new(size.0 as i32, size.1 as i32),
+        }
+    }
+
     pub fn compare(&self, other: &ReftestImage) -> ReftestImageComparison {
         assert_eq!(self.size, other.size);
         assert_eq!(self.data.len(), other.data.len());
@@ -308,6 +433,28 @@ impl ReftestImage {
         }
     }
 
+    /// Makes an image highlighting the pixels that differ from `other` in
+    /// red, brighter for larger differences, over a faded copy of `other`.
+    pub fn create_diff_image(&self, other: &ReftestImage) -> ReftestImage {
+        assert_eq!(self.size, other.size);
+        let mut data = Vec::with_capacity(self.data.len());
+        for (a, b) in self.data.chunks(4).zip(other.data.chunks(4)) {
+            if a == b {
+                let luma = (b[0] as u32 * 2 + b[1] as u32 * 5 + b[2] as u32) / 8;
+                let faded = (192 + luma / 4) as u8;
+                data.extend_from_slice(&[faded, faded, faded, 255]);
+            } else {
+                let pixel_max = a.iter()
+                    .zip(b.iter())
+                    .map(|(x, y)| (*x as isize - *y as isize).abs() as usize)
+                    .max()
+                    .unwrap();
+                data.extend_from_slice(&[(128 + pixel_max / 2) as u8, 0, 0, 255]);
+            }
+        }
+        ReftestImage { data, size: self.size }
+    }
+
     pub fn create_data_uri(mut self) -> String {
         let width = self.size.width;
         let height = self.size.height;
@@ -360,12 +507,14 @@ impl ReftestManifest {
             let tokens: Vec<&str> = s.split_whitespace().collect();
 
             let mut fuzziness = Vec::new();
+            let mut thresholds: Vec<PerceptualThreshold> = Vec::new();
             let mut op = None;
             let mut font_render_mode = None;
             let mut extra_checks = vec![];
             let mut disable_dual_source_blending = false;
             let mut allow_mipmaps = false;
             let mut force_subpixel_aa_where_possible = None;
+            let mut output_color_space = RgbColorSpace::Srgb;
 
             let mut parse_command = |token: &str| -> bool {
                 match token {
@@ -411,6 +560,23 @@ impl ReftestManifest {
                         assert!(fuzziness.is_empty()); // if this fires, consider fuzzy-range instead
                         fuzziness.push(RefTestFuzzy { max_difference, num_differences });
                     }
+                    function if ["ssim", "psnr", "delta-e"].iter().any(|name| {
+                                    function.starts_with(&format!("{}(", name)) ||
+                                    function.starts_with(&format!("{}-if(", name))
+                                }) => {
+                        let (name, mut args, _) = parse_function(function);
+                        if name.ends_with("-if") {
+                            if !environment.parse_condition(args.remove(0)).expect("unknown condition") {
+                                return true;
+                            }
+                        }
+                        let threshold = PerceptualThreshold::new(
+                            name.trim_end_matches("-if"),
+                            args[0].parse().unwrap(),
+                        );
+                        thresholds.retain(|t| t.name() != threshold.name());
+                        thresholds.push(threshold);
+                    }
                     function if function.starts_with("draw_calls(") => {
                         let (_, args, _) = parse_function(function);
                         extra_checks.push(ExtraCheck::DrawCalls(args[0].parse().unwrap()));
@@ -423,6 +589,11 @@ impl ReftestManifest {
                         let (_, args, _) = parse_function(function);
                         extra_checks.push(ExtraCheck::ColorTargets(args[0].parse().unwrap()));
                     }
+                    function if function.starts_with("output_color_space(") => {
+                        let (_, args, _) = parse_function(function);
+                        output_color_space = StringEnum::from_str(args[0])
+                            .expect("unknown output color space");
+                    }
                     options if options.starts_with("options(") => {
                         let (_, args, _) = parse_function(options);
                         if args.iter().any(|arg| arg == &OPTION_DISABLE_SUBPX) {
@@ -543,10 +714,12 @@ impl ReftestManifest {
                 reference,
                 font_render_mode,
                 fuzziness,
+                thresholds,
                 extra_checks,
                 disable_dual_source_blending,
                 allow_mipmaps,
                 force_subpixel_aa_where_possible,
+                output_color_space,
             });
         }
 
@@ -701,19 +874,38 @@ impl<'a> ReftestHarness<'a> {
 
     pub fn run(mut self, base_manifest: &Path, reftests: Option<&Path>, options: &ReftestOptions) -> usize {
         let manifest = ReftestManifest::new(base_manifest, &self.environment, options);
-        let reftests = manifest.find(reftests.unwrap_or(&PathBuf::new()));
+        let mut reftests = manifest.find(reftests.unwrap_or(&PathBuf::new()));
+        if let Some((index, count)) = options.shard {
+            reftests = reftests
+                .into_iter()
+                .enumerate()
+                .filter(|&(i, _)| i % count == index)
+                .map(|(_, t)| t)
+                .collect();
+        }
 
         let mut total_passing = 0;
         let mut failing = Vec::new();
+        let mut results = Vec::new();
 
         for t in reftests {
-            if self.run_reftest(t) {
-                total_passing += 1;
-            } else {
-                failing.push(t);
+            let start = Instant::now();
+            let outcome = self.run_reftest(t);
+            let time = start.elapsed().as_secs_f64();
+            match outcome {
+                Ok(()) => {
+                    total_passing += 1;
+                    results.push(t.result(time, None));
+                }
+                Err(failure) => {
+                    failing.push(t);
+                    results.push(t.result(time, Some(failure)));
+                }
             }
         }
 
+        ReftestReport::new(results).save(&options.reports);
+
         println!(
             "REFTEST INFO | {} passing, {} failing",
             total_passing,
@@ -731,7 +923,7 @@ impl<'a> ReftestHarness<'a> {
         failing.len()
     }
 
-    fn run_reftest(&mut self, t: &Reftest) -> bool {
+    fn run_reftest(&mut self, t: &Reftest) -> Result<(), ReftestFailure> {
         let test_name = t.to_string();
         println!("REFTEST {}", test_name);
         profile_scope!("wrench reftest", text: &test_name);
@@ -763,6 +955,14 @@ impl<'a> ReftestHarness<'a> {
                 );
         }
 
+        if t.output_color_space != RgbColorSpace::Srgb {
+            self.wrench
+                .api
+                .send_debug_cmd(
+                    DebugCommand::SetOutputColorSpace(t.output_color_space)
+                );
+        }
+
         let window_size = self.window.get_inner_size();
         let reference_image = match t.reference.extension().unwrap().to_str().unwrap() {
             "yaml" => None,
@@ -857,6 +1057,14 @@ impl<'a> ReftestHarness<'a> {
                 );
         }
 
+        if t.output_color_space != RgbColorSpace::Srgb {
+            self.wrench
+                .api
+                .send_debug_cmd(
+                    DebugCommand::SetOutputColorSpace(RgbColorSpace::Srgb)
+                );
+        }
+
         for extra_check in t.extra_checks.iter() {
             if !extra_check.run(&results) {
                 println!(
@@ -866,7 +1074,7 @@ impl<'a> ReftestHarness<'a> {
                     results,
                 );
                 println!("REFTEST TEST-END | {}", t);
-                return false;
+                return Err(ReftestFailure::new(format!("failing check: {:?}", extra_check)));
             }
         }
 
@@ -887,11 +1095,10 @@ impl<'a> ReftestHarness<'a> {
                 let comparison = test.compare(&reference);
                 match comparison {
                     ReftestImageComparison::Equal => {
-                        t.report_unexpected_equality();
-                        false
+                        Err(t.report_unexpected_equality())
                     }
                     ReftestImageComparison::NotEqual { .. } => {
-                        true
+                        Ok(())
                     }
                 }
             }
@@ -900,16 +1107,14 @@ impl<'a> ReftestHarness<'a> {
                 for test in images.drain(..) {
                     let comparison = test.compare(&reference);
 
-                    if !t.check_and_report_equality_failure(
+                    t.check_and_report_equality_failure(
                         comparison,
                         &test,
                         &reference,
-                    ) {
-                        return false;
-                    }
+                    )?;
                 }
 
-                true
+                Ok(())
             }
             ReftestOp::Inaccurate => {
                 // Ensure that at least one of the images doesn't match the reference
@@ -921,23 +1126,16 @@ impl<'a> ReftestHarness<'a> {
                 });
 
                 if all_same {
-                    t.report_unexpected_equality();
+                    Err(t.report_unexpected_equality())
+                } else {
+                    Ok(())
                 }
-
-                !all_same
             }
         }
     }
 
     fn load_image(&mut self, filename: &Path, format: ImageFormat) -> ReftestImage {
-        let file = BufReader::new(File::open(filename).unwrap());
-        let img_raw = load_piston_image(file, format).unwrap();
-        let img = img_raw.flipv().to_rgba();
-        let size = img.dimensions();
-        ReftestImage {
-            data: img.into_raw(),
-            size: DeviceIntSize::new(size.0 as i32, size.1 as i32),
-        }
+        ReftestImage::load(filename, format)
     }
 
     fn render_yaml(
@@ -970,7 +1168,7 @@ impl<'a> ReftestHarness<'a> {
             FramebufferIntPoint::new(0, window_size.height - size.height),
             FramebufferIntSize::new(size.width, size.height),
         );
-        let pixels = self.wrench.renderer.read_pixels_rgba8(rect);
+        let pixels = self.window.read_pixels_rgba8(&mut self.wrench.renderer, rect);
         self.window.swap_buffers();
 
         let write_debug_images = false;
@@ -987,3 +1185,88 @@ impl<'a> ReftestHarness<'a> {
         }
     }
 }
+
+/// Runs the reftests in `jobs` wrench processes at once, each rendering a
+/// shard of the tests with its own renderer, and returns the number of
+/// failing tests.
+pub fn run_parallel(jobs: usize, reports: &ReftestReportPaths) -> usize {
+    let exe = env::current_exe().expect("Unable to find the wrench executable");
+    let args = shard_args(env::args().skip(1));
+    let out_dir = env::temp_dir().join(format!("wrench-reftest-{}", process::id()));
+    fs::create_dir_all(&out_dir).unwrap();
+
+    let shards: Vec<_> = (0 .. jobs).map(|index| {
+        let json = out_dir.join(format!("shard-{}.json", index));
+        let child = Command::new(&exe)
+            .args(&args)
+            .arg("--shard")
+            .arg(format!("{}/{}", index, jobs))
+            .arg("--json")
+            .arg(&json)
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Unable to start wrench");
+        // Wait for each shard on its own thread, so that none of them blocks
+        // on a full stdout pipe.
+        (json, thread::spawn(move || child.wait_with_output()))
+    }).collect();
+
+    let mut shard_reports = Vec::new();
+    for (index, (json, waiter)) in shards.into_iter().enumerate() {
+        let output = waiter.join().unwrap().expect("Unable to run wrench");
+        // Print the output of each shard in one piece, to keep the lines of
+        // each test together.
+        std::io::stdout().write_all(&output.stdout).unwrap();
+
+        let report = ReftestReport::load(&json).unwrap_or_else(|| {
+            let name = format!("shard {}/{}", index, jobs);
+            let message = format!("wrench exited with {} without writing results", output.status);
+            println!("REFTEST TEST-UNEXPECTED-FAIL | {} | {}", name, message);
+            ReftestReport::new(vec![ReftestResult {
+                name,
+                suite: String::new(),
+                time: 0.0,
+                fuzziness: Vec::new(),
+                thresholds: Vec::new(),
+                failure: Some(ReftestFailure::new(message)),
+            }])
+        });
+        shard_reports.push(report);
+    }
+    let _ = fs::remove_dir_all(&out_dir);
+
+    let report = ReftestReport::merge(shard_reports);
+    println!(
+        "REFTEST INFO | {} passing, {} failing",
+        report.passing,
+        report.failing
+    );
+
+    if report.failing > 0 {
+        println!("\nReftests with unexpected results:");
+
+        for result in report.results.iter().filter(|r| r.failure.is_some()) {
+            println!("\t{}", result.name);
+        }
+    }
+
+    report.save(reports);
+    report.failing
+}
+
+/// The arguments of this process, without the options that only apply to
+/// the parent of a parallel run.
+fn shard_args(mut args: impl Iterator<Item = String>) -> Vec<String> {
+    const PARENT_OPTIONS: &[&str] = &["--jobs", "--shard", "--json", "--junit", "--html"];
+
+    let mut shard_args = Vec::new();
+    while let Some(arg) = args.next() {
+        if PARENT_OPTIONS.contains(&arg.as_str()) {
+            // Skip the value too.
+            args.next();
+        } else if !PARENT_OPTIONS.iter().any(|option| arg.starts_with(&format!("{}=", option))) {
+            shard_args.push(arg);
+        }
+    }
+    shard_args
+}
@@ -0,0 +1,311 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Machine readable and HTML reports of reftest results. Shards of a parallel
+// run write their results as JSON, which the parent process merges.
+
+use crate::image_diff::ImageMetrics;
+use serde_json;
+use std::fmt::Write as FmtWrite;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Where to write the reports of a reftest run.
+#[derive(Clone, Default)]
+pub struct ReftestReportPaths {
+    pub json: Option<PathBuf>,
+    pub junit: Option<PathBuf>,
+    pub html: Option<PathBuf>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReftestFuzziness {
+    pub max_difference: usize,
+    pub num_differences: usize,
+}
+
+/// The images of a failing reftest, as PNG data URIs.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReftestFailureImages {
+    pub test: String,
+    pub reference: String,
+    pub diff: String,
+}
+
+/// Why a reftest failed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReftestFailure {
+    pub message: String,
+    pub max_difference: Option<usize>,
+    pub count_different: Option<usize>,
+    /// The perceptual metrics of the images, for tests with thresholds.
+    pub metrics: Option<ImageMetrics>,
+    pub images: Option<ReftestFailureImages>,
+}
+
+impl ReftestFailure {
+    pub fn new(message: String) -> Self {
+        ReftestFailure {
+            message,
+            max_difference: None,
+            count_different: None,
+            metrics: None,
+            images: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReftestResult {
+    /// The manifest line of the test, e.g. "a.yaml == b.yaml".
+    pub name: String,
+    /// The directory of the manifest the test is in.
+    pub suite: String,
+    /// How long the test took, in seconds.
+    pub time: f64,
+    /// The fuzziness allowed by the manifest and the command line.
+    pub fuzziness: Vec<ReftestFuzziness>,
+    /// The perceptual thresholds of the test, e.g. "ssim(0.995)", which
+    /// replace the fuzziness when present.
+    pub thresholds: Vec<String>,
+    pub failure: Option<ReftestFailure>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct ReftestReport {
+    pub passing: usize,
+    pub failing: usize,
+    pub results: Vec<ReftestResult>,
+}
+
+impl ReftestReport {
+    pub fn new(results: Vec<ReftestResult>) -> Self {
+        let failing = results.iter().filter(|r| r.failure.is_some()).count();
+        ReftestReport {
+            passing: results.len() - failing,
+            failing,
+            results,
+        }
+    }
+
+    /// Merges the results of several shards, in the given order.
+    pub fn merge(reports: Vec<ReftestReport>) -> Self {
+        let results = reports.into_iter().flat_map(|report| report.results).collect();
+        ReftestReport::new(results)
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let mut string = String::new();
+        File::open(path).ok()?.read_to_string(&mut string).ok()?;
+        serde_json::from_str(&string).ok()
+    }
+
+    pub fn save(&self, paths: &ReftestReportPaths) {
+        if let Some(ref path) = paths.json {
+            let json = serde_json::to_string_pretty(self).unwrap();
+            write_file(path, &json);
+        }
+        if let Some(ref path) = paths.junit {
+            write_file(path, &self.to_junit());
+        }
+        if let Some(ref path) = paths.html {
+            write_file(path, &self.to_html());
+        }
+    }
+
+    fn total_time(&self) -> f64 {
+        self.results.iter().map(|r| r.time).sum()
+    }
+
+    fn to_junit(&self) -> String {
+        let mut xml = String::new();
+        writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+        writeln!(
+            xml,
+            r#"<testsuites name="reftest" tests="{}" failures="{}" time="{:.3}">"#,
+            self.results.len(),
+            self.failing,
+            self.total_time(),
+        ).unwrap();
+
+        // Group the tests by manifest directory, keeping the run order.
+        let mut suites: Vec<(&str, Vec<&ReftestResult>)> = Vec::new();
+        for result in &self.results {
+            match suites.iter_mut().find(|(suite, _)| *suite == result.suite) {
+                Some((_, results)) => results.push(result),
+                None => suites.push((&result.suite, vec![result])),
+            }
+        }
+
+        for (suite, results) in suites {
+            let failures = results.iter().filter(|r| r.failure.is_some()).count();
+            let time: f64 = results.iter().map(|r| r.time).sum();
+            writeln!(
+                xml,
+                r#"  <testsuite name="{}" tests="{}" failures="{}" time="{:.3}">"#,
+                escape(suite),
+                results.len(),
+                failures,
+                time,
+            ).unwrap();
+            for result in results {
+                write!(
+                    xml,
+                    r#"    <testcase classname="{}" name="{}" time="{:.3}""#,
+                    escape(suite),
+                    escape(&result.name),
+                    result.time,
+                ).unwrap();
+                match result.failure {
+                    Some(ref failure) => {
+                        writeln!(xml, ">").unwrap();
+                        writeln!(
+                            xml,
+                            r#"      <failure message="{}">{}</failure>"#,
+                            escape(&failure.message),
+                            escape(&allowed_text(result)),
+                        ).unwrap();
+                        writeln!(xml, "    </testcase>").unwrap();
+                    }
+                    None => writeln!(xml, "/>").unwrap(),
+                }
+            }
+            writeln!(xml, "  </testsuite>").unwrap();
+        }
+        writeln!(xml, "</testsuites>").unwrap();
+        xml
+    }
+
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        html.push_str(HTML_HEADER);
+        writeln!(
+            html,
+            "<h1>Reftest results</h1>\n<p>{} passing, {} failing, {:.1}s</p>",
+            self.passing,
+            self.failing,
+            self.total_time(),
+        ).unwrap();
+
+        for result in &self.results {
+            let failure = match result.failure {
+                Some(ref failure) => failure,
+                None => continue,
+            };
+            writeln!(html, "<div class=\"failure\">").unwrap();
+            writeln!(html, "<h2>{}</h2>", escape(&result.name)).unwrap();
+            writeln!(html, "<p>{}</p>", escape(&failure.message)).unwrap();
+            if let (Some(max_difference), Some(count_different)) = (failure.max_difference, failure.count_different) {
+                writeln!(
+                    html,
+                    "<p>Max difference: {}, differing pixels: {}</p>",
+                    max_difference,
+                    count_different,
+                ).unwrap();
+            }
+            if let Some(ref metrics) = failure.metrics {
+                writeln!(
+                    html,
+                    "<p>PSNR: {:.2} dB, SSIM mean: {:.5}, min: {:.5}, CIEDE2000 mean: {:.3}, max: {:.3}</p>",
+                    metrics.psnr,
+                    metrics.ssim,
+                    metrics.min_ssim,
+                    metrics.mean_delta_e,
+                    metrics.max_delta_e,
+                ).unwrap();
+            }
+            writeln!(html, "<p>Allowed: {}</p>", escape(&allowed_text(result))).unwrap();
+            if let Some(ref images) = failure.images {
+                writeln!(html, "<div class=\"images\">").unwrap();
+                for &(label, uri) in &[
+                    ("Reference", &images.reference),
+                    ("Test", &images.test),
+                    ("Difference", &images.diff),
+                ] {
+                    writeln!(
+                        html,
+                        "<figure><img src=\"{}\"><figcaption>{}</figcaption></figure>",
+                        uri,
+                        label,
+                    ).unwrap();
+                }
+                writeln!(html, "</div>").unwrap();
+            }
+            writeln!(html, "</div>").unwrap();
+        }
+
+        writeln!(html, "<details><summary>All tests</summary>\n<table>").unwrap();
+        writeln!(html, "<tr><th>Test</th><th>Result</th><th>Allowed</th><th>Time</th></tr>").unwrap();
+        for result in &self.results {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.3}s</td></tr>",
+                escape(&result.name),
+                if result.failure.is_some() { "FAIL" } else { "PASS" },
+                escape(&allowed_text(result)),
+                result.time,
+            ).unwrap();
+        }
+        writeln!(html, "</table>\n</details>\n</body>\n</html>").unwrap();
+        html
+    }
+}
+
+const HTML_HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Reftest results</title>
+<style>
+body { font-family: sans-serif; }
+.failure { border-top: 1px solid #ccc; }
+.images { display: flex; flex-wrap: wrap; }
+figure { margin: 8px; }
+img { border: 1px solid #888; image-rendering: pixelated; max-width: 100%; }
+td, th { padding: 2px 8px; text-align: left; }
+</style>
+</head>
+<body>
+"#;
+
+/// The differences the test allows.
+fn allowed_text(result: &ReftestResult) -> String {
+    if !result.thresholds.is_empty() {
+        return result.thresholds.join(" ");
+    }
+    if result.fuzziness.is_empty() {
+        return "exact match".to_owned();
+    }
+    let fuzzy: Vec<String> = result.fuzziness
+        .iter()
+        .map(|f| format!("fuzzy({},{})", f.max_difference, f.num_differences))
+        .collect();
+    fuzzy.join(" ")
+}
+
+/// Escapes text for XML and HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_file(path: &Path, contents: &str) {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).unwrap();
+    }
+    let mut file = File::create(path)
+        .unwrap_or_else(|_| panic!("Unable to create {:?}", path));
+    file.write_all(contents.as_bytes()).unwrap();
+}
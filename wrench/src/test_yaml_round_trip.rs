@@ -0,0 +1,190 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Checks that the YAML frame writer round trips: each YAML file is rendered,
+// written back out from the display lists and resources wrench sent, and the
+// written file must render the same.
+//
+// Scenes that use features the writer can't express fail, unless they are
+// listed in the KNOWN_UNSUPPORTED_LIST file of the input directory. Listed
+// scenes fail if they stop using unsupported features, so the list only
+// shrinks as the writer learns new features.
+
+use crate::NotifierEvent;
+use crate::WindowWrapper;
+use crate::reftest::{ReftestImage, ReftestImageComparison};
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use crate::wrench::{Wrench, WrenchThing};
+use crate::yaml_frame_reader::YamlFrameReader;
+use crate::yaml_frame_writer::YamlFrameWriter;
+use webrender::api::units::*;
+use yaml_rust::YamlLoader;
+
+/// The file listing the scenes, relative to the input directory, that are
+/// known to use features the writer can't express.
+const KNOWN_UNSUPPORTED_LIST: &str = "yaml-round-trip-unsupported.list";
+
+pub struct YamlRoundTripHarness<'a> {
+    wrench: &'a mut Wrench,
+    window: &'a mut WindowWrapper,
+    rx: Receiver<NotifierEvent>,
+}
+
+impl<'a> YamlRoundTripHarness<'a> {
+    pub fn new(
+        wrench: &'a mut Wrench,
+        window: &'a mut WindowWrapper,
+        rx: Receiver<NotifierEvent>
+    ) -> Self {
+        YamlRoundTripHarness {
+            wrench,
+            window,
+            rx,
+        }
+    }
+
+    /// Round trips every YAML scene under `dir`, and returns the number of
+    /// scenes that didn't render the same.
+    pub fn run(mut self, dir: &Path) -> usize {
+        let mut files = Vec::new();
+        collect_yaml_files(dir, &mut files);
+        files.sort();
+
+        let known_unsupported = read_known_unsupported(dir);
+        let out_dir = env::temp_dir().join("wrench-yaml-round-trip");
+        let (mut passing, mut failing, mut known_failing) = (0, 0, 0);
+
+        for (index, file) in files.iter().enumerate() {
+            if !is_scene(file) {
+                continue;
+            }
+
+            let out_path = out_dir.join(index.to_string()).join(file.file_name().unwrap());
+            self.wrench.frame_writer = Some(YamlFrameWriter::new(&out_path));
+            let original = self.render_yaml(file);
+            let mut writer = self.wrench.frame_writer.take().unwrap();
+            writer.finish_write(self.wrench.root_pipeline_id);
+
+            let is_known_unsupported = known_unsupported.iter().any(|known| known == file);
+            match (writer.unsupported_features().is_empty(), is_known_unsupported) {
+                (false, true) => {
+                    println!(
+                        "YAML ROUND TRIP TEST-KNOWN-FAIL | {} | {}",
+                        file.display(),
+                        writer.unsupported_features().join(", "),
+                    );
+                    known_failing += 1;
+                    continue;
+                }
+                (false, false) => {
+                    println!(
+                        "YAML ROUND TRIP TEST-UNEXPECTED-FAIL | {} | unsupported features: {}",
+                        file.display(),
+                        writer.unsupported_features().join(", "),
+                    );
+                    failing += 1;
+                    continue;
+                }
+                (true, true) => {
+                    println!(
+                        "YAML ROUND TRIP TEST-UNEXPECTED-PASS | {} | no unsupported features, \
+                         remove it from {}",
+                        file.display(),
+                        KNOWN_UNSUPPORTED_LIST,
+                    );
+                    failing += 1;
+                    continue;
+                }
+                (true, false) => {}
+            }
+
+            let written = self.render_yaml(&out_path);
+            match original.compare(&written) {
+                ReftestImageComparison::Equal => {
+                    passing += 1;
+                }
+                ReftestImageComparison::NotEqual { max_difference, count_different, .. } => {
+                    println!(
+                        "YAML ROUND TRIP TEST-UNEXPECTED-FAIL | {} | written to {} | \
+                         max difference: {}, number of differing pixels: {}",
+                        file.display(),
+                        out_path.display(),
+                        max_difference,
+                        count_different,
+                    );
+                    failing += 1;
+                }
+            }
+        }
+
+        println!(
+            "YAML ROUND TRIP INFO | {} passing, {} failing, {} known failing",
+            passing,
+            failing,
+            known_failing,
+        );
+        failing
+    }
+
+    fn render_yaml(&mut self, filename: &Path) -> ReftestImage {
+        let mut reader = YamlFrameReader::new(filename);
+        reader.do_frame(self.wrench);
+
+        self.wrench.api.flush_scene_builder();
+
+        // wait for the frame
+        self.rx.recv().unwrap();
+        self.wrench.render();
+
+        let size = self.window.get_inner_size();
+        let rect = FramebufferIntRect::from_size(FramebufferIntSize::new(size.width, size.height));
+        let pixels = self.window.read_pixels_rgba8(&mut self.wrench.renderer, rect);
+        self.window.swap_buffers();
+
+        reader.deinit(self.wrench);
+
+        ReftestImage { data: pixels, size }
+    }
+}
+
+/// Reads the scenes listed in the known unsupported list of `dir`, if any.
+fn read_known_unsupported(dir: &Path) -> Vec<PathBuf> {
+    let source = match fs::read_to_string(dir.join(KNOWN_UNSUPPORTED_LIST)) {
+        Ok(source) => source,
+        Err(..) => return Vec::new(),
+    };
+    source
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| dir.join(line))
+        .collect()
+}
+
+fn collect_yaml_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_yaml_files(&path, files);
+        } else if path.extension().map_or(false, |ext| ext == "yaml") {
+            files.push(path);
+        }
+    }
+}
+
+/// Whether a YAML file is a scene, rather than a file other scenes include.
+fn is_scene(path: &Path) -> bool {
+    let mut source = String::new();
+    if File::open(path).and_then(|mut file| file.read_to_string(&mut source)).is_err() {
+        return false;
+    }
+    match YamlLoader::load_from_str(&source) {
+        Ok(docs) => docs.first().map_or(false, |doc| !doc["root"].is_badvalue()),
+        Err(..) => false,
+    }
+}
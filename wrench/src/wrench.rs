@@ -21,6 +21,7 @@ use webrender::render_api::*;
 use webrender::api::units::*;
 use webrender::{DebugFlags, RenderResults, ShaderPrecacheFlags};
 use crate::{WindowWrapper, NotifierEvent};
+use crate::yaml_frame_writer::YamlFrameWriter;
 
 // TODO(gw): This descriptor matches what we currently support for fonts
 //           but is quite a mess. We should at least document and
@@ -216,6 +217,10 @@ pub struct Wrench {
     pub frame_start_sender: chase_lev::Worker<time::SteadyTime>,
 
     pub callbacks: Arc<Mutex<blob::BlobCallbacks>>,
+
+    /// Records the resources and display lists sent, so that the frame can
+    /// be written back as YAML.
+    pub frame_writer: Option<YamlFrameWriter>,
 }
 
 impl Wrench {
@@ -235,6 +240,7 @@ impl Wrench {
         chase_primitive: webrender::ChasePrimitive,
         dump_shader_source: Option<String>,
         notifier: Option<Box<dyn RenderNotifier>>,
+        hdr: Option<webrender::HdrOptions>,
     ) -> Self {
         println!("Shader override path: {:?}", shader_override_path);
 
@@ -266,6 +272,7 @@ impl Wrench {
             // SWGL doesn't support the GL_ALWAYS depth comparison function used by
             // `clear_caches_with_quads`, but scissored clears work well.
             clear_caches_with_quads: !window.is_software(),
+            hdr,
             ..Default::default()
         };
 
@@ -310,6 +317,8 @@ impl Wrench {
             frame_start_sender: timing_sender,
 
             callbacks,
+
+            frame_writer: None,
         };
 
         wrench.set_title("start");
@@ -320,10 +329,19 @@ impl Wrench {
         wrench
     }
 
+    /// Sends a transaction to the document, letting the frame writer see its
+    /// resource updates first.
+    pub fn send_transaction(&mut self, txn: Transaction) {
+        if let Some(ref mut writer) = self.frame_writer {
+            writer.update_resources(&txn.resource_updates);
+        }
+        self.api.send_transaction(self.document_id, txn);
+    }
+
     pub fn set_quality_settings(&mut self, settings: QualitySettings) {
         let mut txn = Transaction::new();
         txn.set_quality_settings(settings);
-        self.api.send_transaction(self.document_id, txn);
+        self.send_transaction(txn);
     }
 
     pub fn layout_simple_ascii(
@@ -422,7 +440,7 @@ impl Wrench {
         let key = self.api.generate_font_key();
         let mut txn = Transaction::new();
         txn.add_native_font(key, descriptor.clone());
-        self.api.send_transaction(self.document_id, txn);
+        self.send_transaction(txn);
         key
     }
 
@@ -513,7 +531,7 @@ impl Wrench {
         let key = self.api.generate_font_key();
         let mut txn = Transaction::new();
         txn.add_raw_font(key, bytes, index);
-        self.api.send_transaction(self.document_id, txn);
+        self.send_transaction(txn);
         key
     }
 
@@ -537,7 +555,7 @@ impl Wrench {
         }
         options.synthetic_italics = synthetic_italics;
         txn.add_font_instance(key, font_key, size, Some(options), None, Vec::new());
-        self.api.send_transaction(self.document_id, txn);
+        self.send_transaction(txn);
         key
     }
 
@@ -545,7 +563,7 @@ impl Wrench {
     pub fn delete_font_instance(&mut self, key: FontInstanceKey) {
         let mut txn = Transaction::new();
         txn.delete_font_instance(key);
-        self.api.send_transaction(self.document_id, txn);
+        self.send_transaction(txn);
     }
 
     pub fn update(&mut self, dim: DeviceIntSize) {
@@ -566,6 +584,10 @@ impl Wrench {
     ) {
         let root_background_color = Some(ColorF::new(1.0, 1.0, 1.0, 1.0));
 
+        if let Some(ref mut writer) = self.frame_writer {
+            writer.set_display_lists(&display_lists, scroll_offsets);
+        }
+
         let mut txn = Transaction::new();
         for display_list in display_lists {
             txn.set_display_list(
@@ -582,7 +604,7 @@ impl Wrench {
         }
 
         txn.generate_frame(0);
-        self.api.send_transaction(self.document_id, txn);
+        self.send_transaction(txn);
     }
 
     pub fn get_frame_profiles(
@@ -603,7 +625,7 @@ impl Wrench {
         self.begin_frame();
         let mut txn = Transaction::new();
         txn.generate_frame(0);
-        self.api.send_transaction(self.document_id, txn);
+        self.send_transaction(txn);
     }
 
     pub fn show_onscreen_help(&mut self) {
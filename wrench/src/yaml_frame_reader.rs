@@ -188,6 +188,69 @@ fn broadcast<T: Clone>(base_vals: &[T], num_items: usize) -> Vec<T> {
     vals
 }
 
+/// Parse a subset of SVG path data: M, L, Q, C and Z commands, absolute or
+/// relative, with coordinates separated by whitespace or commas.
+fn parse_path_commands(text: &str) -> Vec<PathCommand> {
+    let mut tokens = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty());
+    let mut commands = Vec::new();
+    // The current point, and the start of the current subpath that Z returns to.
+    let mut current = LayoutPoint::zero();
+    let mut subpath_start = LayoutPoint::zero();
+
+    while let Some(command) = tokens.next() {
+        // Relative commands are offset from the current point.
+        let origin = if command.chars().all(|c| c.is_ascii_lowercase()) {
+            current.to_vector()
+        } else {
+            LayoutVector2D::zero()
+        };
+        let mut point = || {
+            let mut coord = || {
+                tokens
+                    .next()
+                    .and_then(|token| token.parse::<f32>().ok())
+                    .expect("invalid path coordinate")
+            };
+            let x = coord();
+            let y = coord();
+            LayoutPoint::new(x, y) + origin
+        };
+
+        let command = match command {
+            "M" | "m" => {
+                let to = point();
+                subpath_start = to;
+                PathCommand::MoveTo(to)
+            }
+            "L" | "l" => PathCommand::LineTo(point()),
+            "Q" | "q" => {
+                let ctrl = point();
+                PathCommand::QuadTo(ctrl, point())
+            }
+            "C" | "c" => {
+                let ctrl1 = point();
+                let ctrl2 = point();
+                PathCommand::CubicTo(ctrl1, ctrl2, point())
+            }
+            "Z" | "z" => PathCommand::Close,
+            _ => panic!("unsupported path command {:?}", command),
+        };
+
+        current = match command {
+            PathCommand::MoveTo(to) |
+            PathCommand::LineTo(to) |
+            PathCommand::QuadTo(_, to) |
+            PathCommand::CubicTo(_, _, to) => to,
+            PathCommand::Close => subpath_start,
+        };
+        commands.push(command);
+    }
+
+    commands
+}
+
 enum CheckerboardKind {
     BlackGrey,
     BlackTransparent,
@@ -319,11 +382,101 @@ fn is_image_opaque(format: ImageFormat, bytes: &[u8]) -> bool {
         ImageFormat::RG16 => true,
         ImageFormat::R8 => false,
         ImageFormat::R16 => false,
+        ImageFormat::RGBA16F => {
+            bytes.chunks(8).all(|p| u16::from_ne_bytes([p[6], p[7]]) == f32_to_f16(1.0))
+        }
+        ImageFormat::R16F => false,
+        ImageFormat::RGB10A2 => {
+            bytes.chunks(4).all(|p| p[3] >> 6 == 0b11)
+        }
         ImageFormat::RGBAF32 |
         ImageFormat::RGBAI32 => unreachable!(),
     }
 }
 
+/// Converts a float to a half float, flushing values too small for a normal
+/// half float to zero.
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent <= 0 {
+        sign
+    } else if exponent >= 0x1f {
+        sign | 0x7c00
+    } else {
+        let half = sign | ((exponent as u16) << 10) | (mantissa >> 13) as u16;
+        // Round to nearest. A carry into the exponent is still correct.
+        half + ((mantissa >> 12) & 1) as u16
+    }
+}
+
+/// Packs premultiplied RGBA colors into the given high bit depth format.
+fn pack_pixels(format: ImageFormat, pixels: impl Iterator<Item = [f32; 4]>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for [r, g, b, a] in pixels {
+        match format {
+            ImageFormat::RGBA16F => {
+                for c in &[r, g, b, a] {
+                    bytes.extend_from_slice(&f32_to_f16(*c).to_ne_bytes());
+                }
+            }
+            ImageFormat::R16F => {
+                bytes.extend_from_slice(&f32_to_f16(r).to_ne_bytes());
+            }
+            ImageFormat::RGB10A2 => {
+                let unorm = |c: f32, max: f32| (c * max).round() as u32;
+                let packed = unorm(r, 1023.0) |
+                    unorm(g, 1023.0) << 10 |
+                    unorm(b, 1023.0) << 20 |
+                    unorm(a, 3.0) << 30;
+                bytes.extend_from_slice(&packed.to_ne_bytes());
+            }
+            _ => panic!("Unable to convert image data to {:?}", format),
+        }
+    }
+    bytes
+}
+
+/// Converts the data of an 8 bit BGRA or R8 image to the requested format,
+/// which allows exercising the high bit depth formats with the regular test
+/// images.
+pub fn convert_image(
+    descriptor: ImageDescriptor,
+    data: ImageData,
+    format: ImageFormat,
+) -> (ImageDescriptor, ImageData) {
+    if descriptor.format == format {
+        return (descriptor, data);
+    }
+    assert!(descriptor.stride.is_none() && descriptor.offset == 0);
+    let bytes = match data {
+        ImageData::Raw(ref bytes) => bytes,
+        ImageData::External(..) => panic!("Unable to convert external images"),
+    };
+
+    let unorm = |c: u8| c as f32 / 255.0;
+    let bytes = match descriptor.format {
+        ImageFormat::BGRA8 => pack_pixels(
+            format,
+            bytes.chunks(4).map(|p| [unorm(p[2]), unorm(p[1]), unorm(p[0]), unorm(p[3])]),
+        ),
+        ImageFormat::R8 => pack_pixels(
+            format,
+            bytes.iter().map(|&v| [unorm(v), 0.0, 0.0, 1.0]),
+        ),
+        other => panic!("Unable to convert {:?} images", other),
+    };
+
+    let mut flags = descriptor.flags;
+    flags.set(ImageDescriptorFlags::IS_OPAQUE, is_image_opaque(format, &bytes));
+    (
+        ImageDescriptor::new(descriptor.size.width, descriptor.size.height, format, flags),
+        ImageData::new(bytes),
+    )
+}
+
 pub struct YamlFrameReader {
     yaml_path: PathBuf,
     aux_dir: PathBuf,
@@ -341,7 +494,8 @@ pub struct YamlFrameReader {
     scroll_offsets: HashMap<ExternalScrollId, LayoutPoint>,
     next_external_scroll_id: u64,
 
-    image_map: HashMap<(PathBuf, Option<i64>), (ImageKey, LayoutSize)>,
+    image_map: HashMap<(PathBuf, Option<i64>, Option<ImageFormat>, RgbColorSpace), (ImageKey, LayoutSize)>,
+    snapshot_map: HashMap<String, SnapshotImageKey>,
 
     fonts: HashMap<FontDescriptor, FontKey>,
     font_instances: HashMap<(FontKey, FontSize, FontInstanceFlags, Option<ColorU>, SyntheticItalics), FontInstanceKey>,
@@ -381,6 +535,7 @@ impl YamlFrameReader {
             font_render_mode: None,
             allow_mipmaps: false,
             image_map: HashMap::new(),
+            snapshot_map: HashMap::new(),
             user_clip_id_map: HashMap::new(),
             user_spatial_id_map: HashMap::new(),
             clip_id_stack: Vec::new(),
@@ -405,7 +560,7 @@ impl YamlFrameReader {
             txn.delete_font(font);
         }
 
-        wrench.api.send_transaction(wrench.document_id, txn);
+        wrench.send_transaction(txn);
     }
 
     fn top_space_and_clip(&self) -> SpaceAndClipInfo {
@@ -637,7 +792,11 @@ impl YamlFrameReader {
         item: &Yaml,
         wrench: &mut Wrench,
     ) -> (ImageKey, LayoutSize) {
-        let key = (file.to_owned(), tiling);
+        let format = item["image-format"]
+            .as_str()
+            .map(|s| ImageFormat::from_str(s).expect("unknown image format"));
+        let color_space = item["color-space"].as_rgb_color_space().unwrap_or(RgbColorSpace::Srgb);
+        let key = (file.to_owned(), tiling, format, color_space);
         if let Some(k) = self.image_map.get(&key) {
             return *k;
         }
@@ -668,6 +827,14 @@ impl YamlFrameReader {
                         }
                         (ImageFormat::BGRA8, pixels)
                     }
+                    image::DynamicImage::ImageRgba16(ref buffer) => {
+                        let unorm = |c: u16| c as f32 / 65535.0;
+                        let pixels = buffer.as_raw().chunks(4).map(|p| {
+                            let a = unorm(p[3]);
+                            [unorm(p[0]) * a, unorm(p[1]) * a, unorm(p[2]) * a, a]
+                        });
+                        (ImageFormat::RGBA16F, pack_pixels(ImageFormat::RGBA16F, pixels))
+                    }
                     _ => panic!("We don't support whatever your crazy image type is, come on"),
                 };
                 let mut flags = ImageDescriptorFlags::empty();
@@ -752,6 +919,11 @@ impl YamlFrameReader {
                 }
             }
         };
+        let (mut descriptor, image_data) = match format {
+            Some(format) => convert_image(descriptor, image_data, format),
+            None => (descriptor, image_data),
+        };
+        descriptor.color_space = color_space;
         let tiling = tiling.map(|tile_size| tile_size as u16);
         let image_key = wrench.api.generate_image_key();
         let mut txn = Transaction::new();
@@ -783,7 +955,7 @@ impl YamlFrameReader {
             txn.add_image(image_key, descriptor, image_data, tiling);
         }
 
-        wrench.api.send_transaction(wrench.document_id, txn);
+        wrench.send_transaction(txn);
         let val = (
             image_key,
             LayoutSize::new(descriptor.size.width as f32, descriptor.size.height as f32),
@@ -792,6 +964,24 @@ impl YamlFrameReader {
         val
     }
 
+    fn add_or_get_snapshot(
+        &mut self,
+        name: &str,
+        wrench: &mut Wrench,
+    ) -> SnapshotImageKey {
+        if let Some(k) = self.snapshot_map.get(name) {
+            return *k;
+        }
+
+        let key = wrench.api.generate_snapshot_image_key();
+        let mut txn = Transaction::new();
+        txn.add_snapshot_image(key);
+        wrench.send_transaction(txn);
+
+        self.snapshot_map.insert(name.to_owned(), key);
+        key
+    }
+
     fn get_or_create_font(&mut self, desc: FontDescriptor, wrench: &mut Wrench) -> FontKey {
         let list_resources = self.list_resources;
         *self.fonts
@@ -909,7 +1099,14 @@ impl YamlFrameReader {
             ExtendMode::Clamp
         };
 
-        dl.create_gradient(start, end, stops, extend_mode)
+        let mut gradient = dl.create_gradient(start, end, stops, extend_mode);
+        gradient.interpolation_space = item["interpolation-space"]
+            .as_color_interpolation_space()
+            .unwrap_or(ColorInterpolationSpace::Srgb);
+        gradient.color_space = item["color-space"]
+            .as_rgb_color_space()
+            .unwrap_or(RgbColorSpace::Srgb);
+        gradient
     }
 
     fn to_radial_gradient(&mut self, dl: &mut DisplayListBuilder, item: &Yaml) -> RadialGradient {
@@ -936,7 +1133,14 @@ impl YamlFrameReader {
             ExtendMode::Clamp
         };
 
-        dl.create_radial_gradient(center, radius, stops, extend_mode)
+        let mut gradient = dl.create_radial_gradient(center, radius, stops, extend_mode);
+        gradient.interpolation_space = item["interpolation-space"]
+            .as_color_interpolation_space()
+            .unwrap_or(ColorInterpolationSpace::Srgb);
+        gradient.color_space = item["color-space"]
+            .as_rgb_color_space()
+            .unwrap_or(RgbColorSpace::Srgb);
+        gradient
     }
 
     fn to_conic_gradient(&mut self, dl: &mut DisplayListBuilder, item: &Yaml) -> ConicGradient {
@@ -963,7 +1167,14 @@ impl YamlFrameReader {
             ExtendMode::Clamp
         };
 
-        dl.create_conic_gradient(center, angle, stops, extend_mode)
+        let mut gradient = dl.create_conic_gradient(center, angle, stops, extend_mode);
+        gradient.interpolation_space = item["interpolation-space"]
+            .as_color_interpolation_space()
+            .unwrap_or(ColorInterpolationSpace::Srgb);
+        gradient.color_space = item["color-space"]
+            .as_rgb_color_space()
+            .unwrap_or(RgbColorSpace::Srgb);
+        gradient
     }
 
     fn handle_rect(
@@ -980,7 +1191,10 @@ impl YamlFrameReader {
 
         let bounds = self.resolve_rect(&item[bounds_key]);
         let color = self.resolve_colorf(&item["color"], ColorF::BLACK);
-        dl.push_rect(&info, bounds, color);
+        let color_space = item["color-space"]
+            .as_rgb_color_space()
+            .unwrap_or(RgbColorSpace::Srgb);
+        dl.push_rect_with_color_space(&info, bounds, PropertyBinding::Value(color), color_space);
     }
 
     fn handle_clear_rect(
@@ -1078,6 +1292,70 @@ impl YamlFrameReader {
         );
     }
 
+    fn handle_path(
+        &mut self,
+        dl: &mut DisplayListBuilder,
+        item: &Yaml,
+        info: &mut CommonItemProperties,
+    ) {
+        let bounds = item["bounds"].as_rect().expect("path must have bounds");
+        let commands = parse_path_commands(
+            item["commands"].as_str().expect("path must have commands"),
+        );
+        let fill_rule = item["fill-rule"]
+            .as_str()
+            .and_then(FillRule::from_str)
+            .unwrap_or(FillRule::Nonzero);
+
+        let stroke = if item["stroke"].is_badvalue() {
+            None
+        } else {
+            let yaml = &item["stroke"];
+            let mut stroke = PathStroke::solid(
+                yaml["width"].as_force_f32().expect("path stroke must have a width"),
+            );
+            if let Some(line_join) = yaml["join"].as_str().and_then(LineJoin::from_str) {
+                stroke.line_join = line_join;
+            }
+            if let Some(line_cap) = yaml["cap"].as_str().and_then(LineCap::from_str) {
+                stroke.line_cap = line_cap;
+            }
+            if let Some(miter_limit) = yaml["miter-limit"].as_force_f32() {
+                stroke.miter_limit = miter_limit;
+            }
+            if let Some(dashes) = yaml["dash-array"].as_vec_f32() {
+                assert!(dashes.len() <= MAX_PATH_DASH_COUNT, "too many path dashes");
+                stroke.dash_array[.. dashes.len()].copy_from_slice(&dashes);
+                stroke.dash_count = dashes.len() as u8;
+            }
+            stroke.dash_offset = yaml["dash-offset"].as_force_f32().unwrap_or(0.0);
+            Some(stroke)
+        };
+
+        // Gradient brushes must be created right before the path is pushed,
+        // so that their stops are associated with it.
+        let brush = if item["brush"].is_badvalue() {
+            PathBrush::Color(item["color"].as_colorf().unwrap_or(ColorF::BLACK))
+        } else {
+            let brush = &item["brush"];
+            match brush["type"].as_str() {
+                Some("gradient") => PathBrush::LinearGradient(self.to_gradient(dl, brush)),
+                Some("radial-gradient") => PathBrush::RadialGradient(self.to_radial_gradient(dl, brush)),
+                Some("conic-gradient") => PathBrush::ConicGradient(self.to_conic_gradient(dl, brush)),
+                _ => panic!("unknown path brush type {:?}", brush["type"]),
+            }
+        };
+
+        dl.push_path(
+            &info,
+            bounds,
+            &commands,
+            fill_rule,
+            stroke,
+            brush,
+        );
+    }
+
     fn handle_gradient(
         &mut self,
         dl: &mut DisplayListBuilder,
@@ -1377,10 +1655,20 @@ impl YamlFrameReader {
         item: &Yaml,
         info: &mut CommonItemProperties,
     ) {
-        // TODO(gw): Support other YUV color depth and spaces.
+        // TODO(gw): Support other YUV color depths.
         let color_depth = ColorDepth::Color8;
-        let color_space = YuvColorSpace::Rec709;
-        let color_range = ColorRange::Limited;
+        let color_space = item["yuv-color-space"]
+            .as_str()
+            .map(|s| YuvColorSpace::from_str(s).expect("unknown yuv color space"))
+            .unwrap_or(YuvColorSpace::Rec709);
+        let color_range = item["color-range"]
+            .as_str()
+            .map(|s| ColorRange::from_str(s).expect("unknown color range"))
+            .unwrap_or(ColorRange::Limited);
+        let transfer_function = item["transfer-function"]
+            .as_str()
+            .map(|s| YuvTransferFunction::from_str(s).expect("unknown transfer function"))
+            .unwrap_or(YuvTransferFunction::Sdr);
 
         let yuv_data = match item["format"].as_str().expect("no format supplied") {
             "planar" => {
@@ -1421,13 +1709,14 @@ impl YamlFrameReader {
             LayoutSize::new(bounds[2], bounds[3]),
         );
 
-        dl.push_yuv_image(
+        dl.push_yuv_image_with_transfer_function(
             &info,
             bounds,
             yuv_data,
             color_depth,
             color_space,
             color_range,
+            transfer_function,
             ImageRendering::Auto,
         );
     }
@@ -1439,17 +1728,26 @@ impl YamlFrameReader {
         item: &Yaml,
         info: &mut CommonItemProperties,
     ) {
-        let filename = &item[if item["type"].is_badvalue() {
-                                 "image"
-                             } else {
-                                 "src"
-                             }];
-        let tiling = item["tile-size"].as_i64();
-        let file = rsrc_path(filename, &self.aux_dir);
-        let (image_key, image_dims) =
-            self.add_or_get_image(&file, tiling, item, wrench);
-
         let bounds_raws = item["bounds"].as_vec_f32().unwrap();
+        let (image_key, image_dims) = match item["snapshot"].as_str() {
+            Some(name) => {
+                // Snapshots have no intrinsic size, the bounds must be given.
+                assert_eq!(bounds_raws.len(), 4, "snapshot images expect 4 values in bounds");
+                let key = self.add_or_get_snapshot(name, wrench);
+                (key.as_image(), LayoutSize::new(bounds_raws[2], bounds_raws[3]))
+            }
+            None => {
+                let filename = &item[if item["type"].is_badvalue() {
+                                         "image"
+                                     } else {
+                                         "src"
+                                     }];
+                let tiling = item["tile-size"].as_i64();
+                let file = rsrc_path(filename, &self.aux_dir);
+                self.add_or_get_image(&file, tiling, item, wrench)
+            }
+        };
+
         let bounds = if bounds_raws.len() == 2 {
             LayoutRect::from_origin_and_size(LayoutPoint::new(bounds_raws[0], bounds_raws[1]), image_dims)
         } else if bounds_raws.len() == 4 {
@@ -1505,15 +1803,14 @@ impl YamlFrameReader {
         }
     }
 
-    fn handle_text(
+    /// Get the font instance, glyphs and bounds of a text item, or of the
+    /// text of a clip.
+    fn to_glyph_run(
         &mut self,
-        dl: &mut DisplayListBuilder,
         wrench: &mut Wrench,
         item: &Yaml,
-        info: &mut CommonItemProperties,
-    ) {
+    ) -> (FontInstanceKey, Vec<GlyphInstance>, LayoutRect) {
         let size = item["size"].as_pt_to_f32().unwrap_or(16.0);
-        let color = item["color"].as_colorf().unwrap_or(ColorF::BLACK);
         let bg_color = item["bg-color"].as_colorf().map(|c| c.into());
         let synthetic_italics = if let Some(angle) = item["synthetic-italics"].as_f32() {
             SyntheticItalics::from_degrees(angle)
@@ -1539,6 +1836,9 @@ impl YamlFrameReader {
         if item["flip-y"].as_bool().unwrap_or(false) {
             flags |= FontInstanceFlags::FLIP_Y;
         }
+        if item["distance-field"].as_bool().unwrap_or(false) {
+            flags |= FontInstanceFlags::DISTANCE_FIELD;
+        }
 
         assert!(
             item["blur-radius"].is_badvalue(),
@@ -1617,13 +1917,42 @@ impl YamlFrameReader {
             (glyphs, bounds)
         };
 
+        (font_instance_key, glyphs, rect)
+    }
+
+    fn handle_text(
+        &mut self,
+        dl: &mut DisplayListBuilder,
+        wrench: &mut Wrench,
+        item: &Yaml,
+        info: &mut CommonItemProperties,
+    ) {
+        let color = item["color"].as_colorf().unwrap_or(ColorF::BLACK);
+        let (font_instance_key, glyphs, rect) = self.to_glyph_run(wrench, item);
+
+        let glyph_options = item["stroke-width"].as_f32().map(|width| {
+            let paint_order = match item["paint-order"].as_str() {
+                Some("stroke") => TextPaintOrder::StrokeFill,
+                Some("fill") | None => TextPaintOrder::FillStroke,
+                Some(order) => panic!("Unknown paint order {}", order),
+            };
+            GlyphOptions {
+                stroke: Some(TextStroke {
+                    width,
+                    color: item["stroke-color"].as_colorf().unwrap_or(ColorF::BLACK),
+                    paint_order,
+                }),
+                ..GlyphOptions::default()
+            }
+        });
+
         dl.push_text(
             &info,
             rect,
             &glyphs,
             font_instance_key,
             color,
-            None,
+            glyph_options,
         );
     }
 
@@ -1657,6 +1986,12 @@ impl YamlFrameReader {
     }
 
     fn get_item_type_from_yaml(item: &Yaml) -> &str {
+        // An explicit type wins over shorthands, so that e.g. a clip can have
+        // a "text" key for its glyphs.
+        if let Some(item_type) = item["type"].as_str() {
+            return item_type;
+        }
+
         let shorthands = [
             "rect",
             "image",
@@ -1674,7 +2009,7 @@ impl YamlFrameReader {
                 return shorthand;
             }
         }
-        item["type"].as_str().unwrap_or("unknown")
+        "unknown"
     }
 
     fn add_display_list_items_from_yaml(
@@ -1766,6 +2101,7 @@ impl YamlFrameReader {
                 "hit-test" => self.handle_hit_test(dl, item, &mut info),
                 "clear-rect" => self.handle_clear_rect(dl, item, &mut info),
                 "line" => self.handle_line(dl, item, &mut info),
+                "path" => self.handle_path(dl, item, &mut info),
                 "image" => self.handle_image(dl, wrench, item, &mut info),
                 "yuv-image" => self.handle_yuv_image(dl, wrench, item, &mut info),
                 "text" | "glyphs" => self.handle_text(dl, wrench, item, &mut info),
@@ -1785,7 +2121,7 @@ impl YamlFrameReader {
                 "reference-frame" => self.handle_reference_frame(dl, wrench, item),
                 "shadow" => self.handle_push_shadow(dl, item, &mut info),
                 "pop-all-shadows" => self.handle_pop_all_shadows(dl),
-                "backdrop-filter" => self.handle_backdrop_filter(dl, item, &mut info),
+                "backdrop-filter" => self.handle_backdrop_filter(dl, wrench, item, &mut info),
                 _ => println!("Skipping unknown item type: {:?}", item),
             }
 
@@ -1823,13 +2159,46 @@ impl YamlFrameReader {
             self.scroll_offsets.insert(external_id, LayoutPoint::new(size.x, size.y));
         }
 
-        let space_and_clip = dl.define_scroll_frame(
+        let overscroll_behavior = match yaml["overscroll"].as_str() {
+            Some("rubber-band") => OverscrollBehavior::RUBBER_BAND,
+            Some("glow") => OverscrollBehavior::Glow,
+            Some("none") | None => OverscrollBehavior::None,
+            Some(other) => panic!("Unknown overscroll behavior: {}", other),
+        };
+
+        let snap_strictness = match yaml["snap-type"].as_str() {
+            Some("mandatory") => ScrollSnapStrictness::Mandatory,
+            Some("proximity") | None => ScrollSnapStrictness::Proximity,
+            Some(other) => panic!("Unknown snap type: {}", other),
+        };
+
+        let snap_areas: Vec<ScrollSnapArea> = match yaml["snap-areas"].as_vec() {
+            Some(areas) => areas.iter().map(|area| {
+                let rect = area["rect"].as_rect().expect("snap area must have a rect");
+                let align = area["align"].as_vec_string().unwrap_or_else(|| vec!["start".to_owned(); 2]);
+                assert_eq!(align.len(), 2, "Snap area align must have an x and a y alignment: {:?}", align);
+                let parse_alignment = |alignment: &str| match alignment {
+                    "none" => ScrollSnapAlignment::None,
+                    "start" => ScrollSnapAlignment::Start,
+                    "center" => ScrollSnapAlignment::Center,
+                    "end" => ScrollSnapAlignment::End,
+                    _ => panic!("Unknown snap alignment: {}", alignment),
+                };
+                ScrollSnapArea::new(rect, parse_alignment(&align[0]), parse_alignment(&align[1]))
+            }).collect(),
+            None => Vec::new(),
+        };
+
+        let space_and_clip = dl.define_scroll_frame_with_snapping(
             &self.top_space_and_clip(),
             external_id,
             content_rect,
             clip_rect,
             ScrollSensitivity::ScriptAndInputEvents,
             external_scroll_offset,
+            overscroll_behavior,
+            snap_strictness,
+            &snap_areas,
         );
         if let Some(numeric_id) = numeric_id {
             self.add_spatial_id_mapping(numeric_id, space_and_clip.spatial_id);
@@ -1992,6 +2361,37 @@ impl YamlFrameReader {
             );
         }
 
+        if let Some(path) = yaml["path"].as_hash().map(|_| &yaml["path"]) {
+            let rect = path["bounds"]
+                .as_rect()
+                .or_else(|| yaml["bounds"].as_rect())
+                .expect("clip path must have bounds");
+            let commands = parse_path_commands(
+                path["commands"].as_str().expect("clip path must have commands"),
+            );
+            let fill_rule = path["fill-rule"]
+                .as_str()
+                .and_then(FillRule::from_str)
+                .unwrap_or(FillRule::Nonzero);
+            space_and_clip.clip_id = dl.define_clip_path(
+                &space_and_clip,
+                rect,
+                &commands,
+                fill_rule,
+            );
+        }
+
+        if let Some(text) = yaml["text"].as_hash().map(|_| &yaml["text"]) {
+            let (font_instance_key, glyphs, rect) = self.to_glyph_run(wrench, text);
+            space_and_clip.clip_id = dl.define_clip_text(
+                &space_and_clip,
+                rect,
+                &glyphs,
+                font_instance_key,
+                None,
+            );
+        }
+
         for complex_clip in complex_clips {
             space_and_clip.clip_id = dl.define_clip_rounded_rect(
                 &space_and_clip,
@@ -2140,7 +2540,7 @@ impl YamlFrameReader {
 
         let filters = yaml["filters"].as_vec_filter_op().unwrap_or(vec![]);
         let filter_datas = yaml["filter-datas"].as_vec_filter_data().unwrap_or(vec![]);
-        let filter_primitives = yaml["filter-primitives"].as_vec_filter_primitive().unwrap_or(vec![]);
+        let filter_primitives = self.to_filter_primitives(&yaml["filter-primitives"], wrench);
 
         let mut flags = StackingContextFlags::empty();
         if is_backdrop_root {
@@ -2150,7 +2550,11 @@ impl YamlFrameReader {
             flags |= StackingContextFlags::IS_BLEND_CONTAINER;
         }
 
-        dl.push_stacking_context(
+        let snapshot = yaml["snapshot"]
+            .as_str()
+            .map(|name| self.add_or_get_snapshot(name, wrench));
+
+        dl.push_stacking_context_with_snapshot(
             bounds.min,
             *self.spatial_id_stack.last().unwrap(),
             info.flags,
@@ -2162,6 +2566,7 @@ impl YamlFrameReader {
             &filter_primitives,
             raster_space,
             flags,
+            snapshot,
         );
 
         if !yaml["items"].is_badvalue() {
@@ -2176,9 +2581,37 @@ impl YamlFrameReader {
         }
     }
 
+    fn to_filter_primitives(
+        &mut self,
+        yaml: &Yaml,
+        wrench: &mut Wrench,
+    ) -> Vec<FilterPrimitive> {
+        let mut filter_primitives = yaml.as_vec_filter_primitive().unwrap_or(vec![]);
+
+        // Image primitives reference a file, which needs to be loaded to get
+        // an image key.
+        let items = match yaml.as_vec() {
+            Some(items) => items.clone(),
+            None => vec![yaml.clone()],
+        };
+        for (primitive, item) in filter_primitives.iter_mut().zip(items.iter()) {
+            if let FilterPrimitiveKind::Image(ref mut image) = primitive.kind {
+                let file = rsrc_path(&item["src"], &self.aux_dir);
+                let (image_key, image_dims) = self.add_or_get_image(&file, None, item, wrench);
+                image.image_key = image_key;
+                if image.rect.is_empty() {
+                    image.rect = LayoutRect::from_size(image_dims);
+                }
+            }
+        }
+
+        filter_primitives
+    }
+
     fn handle_backdrop_filter(
         &mut self,
         dl: &mut DisplayListBuilder,
+        wrench: &mut Wrench,
         item: &Yaml,
         info: &mut CommonItemProperties,
     ) {
@@ -2189,7 +2622,7 @@ impl YamlFrameReader {
 
         let filters = item["filters"].as_vec_filter_op().unwrap_or(vec![]);
         let filter_datas = item["filter-datas"].as_vec_filter_data().unwrap_or(vec![]);
-        let filter_primitives = item["filter-primitives"].as_vec_filter_primitive().unwrap_or(vec![]);
+        let filter_primitives = self.to_filter_primitives(&item["filter-primitives"], wrench);
 
         dl.push_backdrop_filter(
             &info,
@@ -0,0 +1,1657 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Writes the display lists and resources sent by wrench as a YAML file that
+// yaml_frame_reader.rs reads back into the same display lists. Images are
+// dumped as PNGs and fonts as files next to the YAML.
+
+use image::png::PNGEncoder;
+use image::ColorType;
+use crate::premultiply::{premultiply, unpremultiply};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::mem;
+use std::path::{Path, PathBuf};
+use webrender::api::*;
+use webrender::render_api::*;
+use webrender::api::units::*;
+use crate::yaml_frame_reader::convert_image;
+use crate::yaml_helper::StringEnum;
+use yaml_rust::{Yaml, YamlEmitter};
+use yaml_rust::yaml::Hash as Table;
+
+fn table_insert(table: &mut Table, key: &str, value: Yaml) {
+    table.insert(Yaml::String(key.to_owned()), value);
+}
+
+fn str_node(value: &str) -> Yaml {
+    Yaml::String(value.to_owned())
+}
+
+fn bool_node(value: bool) -> Yaml {
+    Yaml::Boolean(value)
+}
+
+fn i64_node(value: i64) -> Yaml {
+    Yaml::Integer(value)
+}
+
+fn f32_node(value: f32) -> Yaml {
+    Yaml::Real(value.to_string())
+}
+
+fn f32_vec_node(values: &[f32]) -> Yaml {
+    let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+    Yaml::String(values.join(" "))
+}
+
+/// Finds a value that the reader turns back into exactly `value` with
+/// `parse`, starting from the approximate inverse `guess`. Colors, font
+/// sizes and rect sizes are written in different units than they're stored
+/// in, and the conversion can be off by an ulp or two.
+fn exact_inverse(value: f32, guess: f32, parse: impl Fn(f32) -> f32) -> f32 {
+    if !guess.is_finite() {
+        return guess;
+    }
+    let bits = guess.to_bits() as i64;
+    for delta in [0, 1, -1, 2, -2].iter() {
+        let candidate = f32::from_bits((bits + delta) as u32);
+        if parse(candidate) == value {
+            return candidate;
+        }
+    }
+    guess
+}
+
+fn point_node(point: &LayoutPoint) -> Yaml {
+    f32_vec_node(&[point.x, point.y])
+}
+
+fn vector_node(vector: &LayoutVector2D) -> Yaml {
+    f32_vec_node(&[vector.x, vector.y])
+}
+
+fn size_node(size: &LayoutSize) -> Yaml {
+    f32_vec_node(&[size.width, size.height])
+}
+
+fn rect_node(rect: &LayoutRect) -> Yaml {
+    let width = exact_inverse(rect.max.x, rect.max.x - rect.min.x, |w| rect.min.x + w);
+    let height = exact_inverse(rect.max.y, rect.max.y - rect.min.y, |h| rect.min.y + h);
+    f32_vec_node(&[rect.min.x, rect.min.y, width, height])
+}
+
+fn color_node(color: &ColorF) -> Yaml {
+    let channel = |c: f32| exact_inverse(c, c * 255.0, |v| v / 255.0);
+    f32_vec_node(&[channel(color.r), channel(color.g), channel(color.b), color.a])
+}
+
+fn matrix4d_node(transform: &LayoutTransform) -> Yaml {
+    f32_vec_node(&transform.to_array())
+}
+
+fn radius_node(radius: &BorderRadius) -> Yaml {
+    let mut table = Table::new();
+    table_insert(&mut table, "top-left", size_node(&radius.top_left));
+    table_insert(&mut table, "top-right", size_node(&radius.top_right));
+    table_insert(&mut table, "bottom-left", size_node(&radius.bottom_left));
+    table_insert(&mut table, "bottom-right", size_node(&radius.bottom_right));
+    Yaml::Hash(table)
+}
+
+fn path_commands_node(commands: impl Iterator<Item = PathCommand>) -> Yaml {
+    let point = |p: LayoutPoint| format!("{} {}", p.x, p.y);
+    let commands: Vec<String> = commands.map(|command| match command {
+        PathCommand::MoveTo(p) => format!("M {}", point(p)),
+        PathCommand::LineTo(p) => format!("L {}", point(p)),
+        PathCommand::QuadTo(c, p) => format!("Q {} {}", point(c), point(p)),
+        PathCommand::CubicTo(c1, c2, p) => format!("C {} {} {}", point(c1), point(c2), point(p)),
+        PathCommand::Close => "Z".to_owned(),
+    }).collect();
+    Yaml::String(commands.join(" "))
+}
+
+fn border_style_str(style: BorderStyle) -> &'static str {
+    match style {
+        BorderStyle::None => "none",
+        BorderStyle::Solid => "solid",
+        BorderStyle::Double => "double",
+        BorderStyle::Dotted => "dotted",
+        BorderStyle::Dashed => "dashed",
+        BorderStyle::Hidden => "hidden",
+        BorderStyle::Groove => "groove",
+        BorderStyle::Ridge => "ridge",
+        BorderStyle::Inset => "inset",
+        BorderStyle::Outset => "outset",
+    }
+}
+
+fn repeat_mode_str(mode: RepeatMode) -> &'static str {
+    match mode {
+        RepeatMode::Stretch => "stretch",
+        RepeatMode::Repeat => "repeat",
+        RepeatMode::Round => "round",
+        RepeatMode::Space => "space",
+    }
+}
+
+fn snap_alignment_str(alignment: ScrollSnapAlignment) -> &'static str {
+    match alignment {
+        ScrollSnapAlignment::None => "none",
+        ScrollSnapAlignment::Start => "start",
+        ScrollSnapAlignment::Center => "center",
+        ScrollSnapAlignment::End => "end",
+    }
+}
+
+fn interpolation_space_str(space: ColorInterpolationSpace) -> &'static str {
+    match space {
+        ColorInterpolationSpace::Srgb => "srgb",
+        ColorInterpolationSpace::SrgbLinear => "srgb-linear",
+        ColorInterpolationSpace::Oklab => "oklab",
+        ColorInterpolationSpace::Oklch(HueInterpolationMethod::Shorter) => "oklch",
+        ColorInterpolationSpace::Oklch(HueInterpolationMethod::Longer) => "oklch longer",
+        ColorInterpolationSpace::Hsl(HueInterpolationMethod::Shorter) => "hsl",
+        ColorInterpolationSpace::Hsl(HueInterpolationMethod::Longer) => "hsl longer",
+    }
+}
+
+fn filter_input_node(input: FilterPrimitiveInput) -> Yaml {
+    match input {
+        FilterPrimitiveInput::Original => str_node("original"),
+        FilterPrimitiveInput::Previous => str_node("previous"),
+        FilterPrimitiveInput::OutputOfPrimitiveIndex(index) => i64_node(index as i64),
+    }
+}
+
+/// The clip rect the reader gives items without a "clip-rect".
+fn full_clip() -> LayoutRect {
+    let big_number = 1.0e30;
+    LayoutRect::from_origin_and_size(
+        LayoutPoint::new(-big_number / 2.0, -big_number / 2.0),
+        LayoutSize::new(big_number, big_number),
+    )
+}
+
+/// Converts a half float, treating subnormals as zero like the reader does
+/// when it packs them.
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        0 => sign,
+        0x1f => sign | 0x7f80_0000 | mantissa << 13,
+        _ => sign | (exponent + 127 - 15) << 23 | mantissa << 13,
+    };
+    f32::from_bits(bits)
+}
+
+struct CachedImage {
+    descriptor: ImageDescriptor,
+    data: ImageData,
+    tiling: Option<TileSize>,
+    /// The PNG the image was dumped to, relative to the YAML file.
+    path: Option<String>,
+}
+
+struct CachedFont {
+    font: AddFont,
+    /// The file the font was dumped to, or the native font file.
+    path: Option<String>,
+}
+
+/// The numeric ids given to the spatial nodes and clips of the pipeline
+/// being written, which the reader maps back to real ids.
+struct IdMap {
+    pipeline_id: PipelineId,
+    next_id: u64,
+    spatial_ids: HashMap<SpatialId, u64>,
+    clip_ids: HashMap<ClipId, u64>,
+}
+
+impl IdMap {
+    fn new(pipeline_id: PipelineId) -> Self {
+        IdMap {
+            pipeline_id,
+            // Zero is reserved for the root clip.
+            next_id: 1,
+            spatial_ids: HashMap::new(),
+            clip_ids: HashMap::new(),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+}
+
+pub struct YamlFrameWriter {
+    yaml_path: PathBuf,
+    /// The directory images and fonts are dumped to, and its name relative
+    /// to the YAML file.
+    rsrc_dir: PathBuf,
+    rsrc_prefix: String,
+    next_rsrc_id: u32,
+
+    images: HashMap<ImageKey, CachedImage>,
+    snapshots: HashMap<ImageKey, String>,
+    fonts: HashMap<FontKey, CachedFont>,
+    font_instances: HashMap<FontInstanceKey, AddFontInstance>,
+
+    display_lists: Vec<(PipelineId, BuiltDisplayList)>,
+    scroll_offsets: HashMap<ExternalScrollId, LayoutPoint>,
+
+    ids: IdMap,
+    unsupported: Vec<String>,
+}
+
+impl YamlFrameWriter {
+    pub fn new(yaml_path: &Path) -> YamlFrameWriter {
+        let stem = yaml_path.file_stem().unwrap().to_string_lossy().into_owned();
+        YamlFrameWriter {
+            yaml_path: yaml_path.to_owned(),
+            rsrc_dir: yaml_path.with_file_name(&stem),
+            rsrc_prefix: stem,
+            next_rsrc_id: 1,
+            images: HashMap::new(),
+            snapshots: HashMap::new(),
+            fonts: HashMap::new(),
+            font_instances: HashMap::new(),
+            display_lists: Vec::new(),
+            scroll_offsets: HashMap::new(),
+            ids: IdMap::new(PipelineId::dummy()),
+            unsupported: Vec::new(),
+        }
+    }
+
+    /// Features of the written frames that YAML can't express, so the
+    /// written file won't render the same.
+    pub fn unsupported_features(&self) -> &[String] {
+        &self.unsupported
+    }
+
+    fn unsupported(&mut self, feature: String) {
+        if !self.unsupported.contains(&feature) {
+            self.unsupported.push(feature);
+        }
+    }
+
+    /// Records the resources added by a transaction. Deletions are ignored,
+    /// resources stay around until the frame is written.
+    pub fn update_resources(&mut self, updates: &[ResourceUpdate]) {
+        for update in updates {
+            match *update {
+                ResourceUpdate::AddImage(ref img) => {
+                    self.images.insert(img.key, CachedImage {
+                        descriptor: img.descriptor,
+                        data: img.data.clone(),
+                        tiling: img.tiling,
+                        path: None,
+                    });
+                }
+                ResourceUpdate::UpdateImage(ref img) => {
+                    if let Some(cached) = self.images.get_mut(&img.key) {
+                        cached.descriptor = img.descriptor;
+                        cached.data = img.data.clone();
+                        cached.path = None;
+                    }
+                }
+                ResourceUpdate::AddSnapshotImage(key) => {
+                    self.snapshot_name(key.as_image());
+                }
+                ResourceUpdate::AddFont(ref font) => {
+                    let key = match *font {
+                        AddFont::Raw(key, ..) | AddFont::Native(key, ..) => key,
+                    };
+                    self.fonts.insert(key, CachedFont { font: font.clone(), path: None });
+                }
+                ResourceUpdate::AddFontInstance(ref instance) => {
+                    self.font_instances.insert(instance.key, instance.clone());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Records the display lists and scroll offsets of a frame, replacing
+    /// the previous lists of the same pipelines.
+    pub fn set_display_lists(
+        &mut self,
+        display_lists: &[(PipelineId, BuiltDisplayList)],
+        scroll_offsets: &HashMap<ExternalScrollId, LayoutPoint>,
+    ) {
+        for &(pipeline_id, ref display_list) in display_lists {
+            self.display_lists.retain(|&(id, _)| id != pipeline_id);
+            self.display_lists.push((pipeline_id, display_list.clone()));
+        }
+        self.scroll_offsets = scroll_offsets.clone();
+    }
+
+    /// Writes the last frame to the YAML file, with the root pipeline as the
+    /// root and the others as pipelines that iframes refer to.
+    pub fn finish_write(&mut self, root_pipeline_id: PipelineId) {
+        let display_lists = mem::take(&mut self.display_lists);
+
+        let mut pipelines = Vec::new();
+        let mut root = None;
+        for &(pipeline_id, ref display_list) in &display_lists {
+            let table = self.write_pipeline(pipeline_id, display_list);
+            if pipeline_id == root_pipeline_id {
+                root = Some(table);
+            } else {
+                let mut id = Table::new();
+                table_insert(&mut id, "id", Yaml::Array(vec![
+                    i64_node(pipeline_id.0 as i64),
+                    i64_node(pipeline_id.1 as i64),
+                ]));
+                id.extend(table);
+                pipelines.push(Yaml::Hash(id));
+            }
+        }
+        self.display_lists = display_lists;
+
+        let mut doc = Table::new();
+        if !pipelines.is_empty() {
+            table_insert(&mut doc, "pipelines", Yaml::Array(pipelines));
+        }
+        table_insert(&mut doc, "root", Yaml::Hash(root.unwrap_or_else(Table::new)));
+
+        let mut output = String::new();
+        YamlEmitter::new(&mut output).dump(&Yaml::Hash(doc)).unwrap();
+        output.push('\n');
+
+        if let Some(parent) = self.yaml_path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        let mut file = File::create(&self.yaml_path)
+            .unwrap_or_else(|_| panic!("Unable to create {:?}", self.yaml_path));
+        file.write_all(output.as_bytes()).unwrap();
+    }
+
+    fn write_pipeline(&mut self, pipeline_id: PipelineId, display_list: &BuiltDisplayList) -> Table {
+        self.ids = IdMap::new(pipeline_id);
+        let mut items = self.write_items(&mut display_list.iter());
+
+        // The reader always puts the pipeline in a stacking context, so a
+        // list that is a single such stacking context is unwrapped.
+        let is_root = items.len() == 1 && match items[0] {
+            Yaml::Hash(ref sc) => Self::is_root_stacking_context(sc),
+            _ => false,
+        };
+        let mut table = match items.pop() {
+            Some(Yaml::Hash(mut sc)) if is_root => {
+                sc.remove(&str_node("type"));
+                sc.remove(&str_node("clip-and-scroll"));
+                sc
+            }
+            last => {
+                items.extend(last);
+                let mut table = Table::new();
+                table_insert(&mut table, "items", Yaml::Array(items));
+                table
+            }
+        };
+
+        if let Some(offset) = self.scroll_offsets.get(&ExternalScrollId(0, pipeline_id)) {
+            table_insert(&mut table, "scroll-offset", point_node(offset));
+        }
+        table
+    }
+
+    fn is_root_stacking_context(table: &Table) -> bool {
+        let flag_keys = ["backface-visible", "scrollbar-container", "prefer-compositor-surface"];
+        table.get(&str_node("type")) == Some(&str_node("stacking-context")) &&
+            table.get(&str_node("clip-and-scroll")) == Some(&str_node("root-scroll-node")) &&
+            flag_keys.iter().all(|key| !table.contains_key(&str_node(key)))
+    }
+
+    /// Writes the items up to the end of the list or of the enclosing
+    /// stacking context or reference frame.
+    fn write_items(&mut self, iter: &mut BuiltDisplayListIter) -> Vec<Yaml> {
+        let mut items = Vec::new();
+        loop {
+            let mut table = Table::new();
+            let has_children = {
+                let base = match iter.next() {
+                    Some(base) => base,
+                    None => break,
+                };
+                match *base.item() {
+                    DisplayItem::PopStackingContext | DisplayItem::PopReferenceFrame => break,
+                    DisplayItem::PushStackingContext(ref item) => {
+                        self.write_stacking_context(&mut table, item, &base);
+                        true
+                    }
+                    DisplayItem::PushReferenceFrame(ref item) => {
+                        self.write_reference_frame(&mut table, item);
+                        true
+                    }
+                    ref item => {
+                        self.write_item(&mut table, item, &base);
+                        false
+                    }
+                }
+            };
+            if has_children {
+                let children = self.write_items(iter);
+                table_insert(&mut table, "items", Yaml::Array(children));
+            }
+            if !table.is_empty() {
+                items.push(Yaml::Hash(table));
+            }
+        }
+        items
+    }
+
+    fn write_item(&mut self, table: &mut Table, item: &DisplayItem, base: &DisplayItemRef) {
+        match *item {
+            DisplayItem::Rectangle(ref item) => {
+                self.write_common(table, "rect", &item.common);
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                let color = match item.color {
+                    PropertyBinding::Value(color) => color,
+                    PropertyBinding::Binding(_, color) => {
+                        self.unsupported("animated rect colors".to_owned());
+                        color
+                    }
+                };
+                table_insert(table, "color", color_node(&color));
+                if item.color_space != RgbColorSpace::Srgb {
+                    table_insert(table, "color-space", str_node(item.color_space.as_str()));
+                }
+            }
+            DisplayItem::ClearRectangle(ref item) => {
+                self.write_common(table, "clear-rect", &item.common);
+                table_insert(table, "bounds", rect_node(&item.bounds));
+            }
+            DisplayItem::HitTest(ref item) => {
+                // The reader intersects the bounds with the clip rect.
+                let common = CommonItemProperties { clip_rect: full_clip(), ..item.common };
+                self.write_common(table, "hit-test", &common);
+                table_insert(table, "bounds", rect_node(&item.common.clip_rect));
+                table_insert(table, "hit-testing-tag", Yaml::Array(vec![
+                    i64_node(item.tag.0 as i64),
+                    i64_node(item.tag.1 as i64),
+                ]));
+            }
+            DisplayItem::Text(ref item) => {
+                self.write_common(table, "text", &item.common);
+                self.write_glyph_run(table, item.font_key, base.glyphs(), &item.bounds);
+                table_insert(table, "color", color_node(&item.color));
+                if let Some(options) = item.glyph_options {
+                    if options.render_mode != GlyphOptions::default().render_mode ||
+                        !options.flags.is_empty() {
+                        self.unsupported("glyph options".to_owned());
+                    }
+                    if let Some(stroke) = options.stroke {
+                        table_insert(table, "stroke-width", f32_node(stroke.width));
+                        table_insert(table, "stroke-color", color_node(&stroke.color));
+                        if let TextPaintOrder::StrokeFill = stroke.paint_order {
+                            table_insert(table, "paint-order", str_node("stroke"));
+                        }
+                    }
+                }
+            }
+            DisplayItem::Line(ref item) => {
+                self.write_common(table, "line", &item.common);
+                table_insert(table, "bounds", rect_node(&item.area));
+                table_insert(table, "orientation", str_node(item.orientation.as_str()));
+                table_insert(table, "style", str_node(item.style.as_str()));
+                if let LineStyle::Wavy = item.style {
+                    table_insert(table, "thickness", f32_node(item.wavy_line_thickness));
+                }
+                table_insert(table, "color", color_node(&item.color));
+            }
+            DisplayItem::Path(ref item) => {
+                self.write_common(table, "path", &item.common);
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                table_insert(table, "commands", path_commands_node(base.path_commands().iter()));
+                table_insert(table, "fill-rule", str_node(item.fill_rule.as_str()));
+                if let Some(ref stroke) = item.stroke {
+                    let mut node = Table::new();
+                    table_insert(&mut node, "width", f32_node(stroke.width));
+                    table_insert(&mut node, "join", str_node(stroke.line_join.as_str()));
+                    table_insert(&mut node, "cap", str_node(stroke.line_cap.as_str()));
+                    table_insert(&mut node, "miter-limit", f32_node(stroke.miter_limit));
+                    if stroke.dash_count > 0 {
+                        table_insert(&mut node, "dash-array", f32_vec_node(stroke.dashes()));
+                    }
+                    if stroke.dash_offset != 0.0 {
+                        table_insert(&mut node, "dash-offset", f32_node(stroke.dash_offset));
+                    }
+                    table_insert(table, "stroke", Yaml::Hash(node));
+                }
+                let stops: Vec<GradientStop> = base.gradient_stops().iter().collect();
+                let mut brush = Table::new();
+                match item.brush {
+                    PathBrush::Color(ref color) => {
+                        table_insert(table, "color", color_node(color));
+                    }
+                    PathBrush::LinearGradient(ref gradient) => {
+                        table_insert(&mut brush, "type", str_node("gradient"));
+                        self.write_gradient(&mut brush, gradient, &stops);
+                    }
+                    PathBrush::RadialGradient(ref gradient) => {
+                        table_insert(&mut brush, "type", str_node("radial-gradient"));
+                        self.write_radial_gradient(&mut brush, gradient, &stops);
+                    }
+                    PathBrush::ConicGradient(ref gradient) => {
+                        table_insert(&mut brush, "type", str_node("conic-gradient"));
+                        self.write_conic_gradient(&mut brush, gradient, &stops);
+                    }
+                }
+                if !brush.is_empty() {
+                    table_insert(table, "brush", Yaml::Hash(brush));
+                }
+            }
+            DisplayItem::Border(ref item) => {
+                self.write_common(table, "border", &item.common);
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                let w = &item.widths;
+                table_insert(table, "width", f32_vec_node(&[w.top, w.left, w.bottom, w.right]));
+                match item.details {
+                    BorderDetails::Normal(ref border) => {
+                        table_insert(table, "border-type", str_node("normal"));
+                        let sides = [border.top, border.right, border.bottom, border.left];
+                        table_insert(table, "color", Yaml::Array(
+                            sides.iter().map(|side| color_node(&side.color)).collect(),
+                        ));
+                        table_insert(table, "style", Yaml::Array(
+                            sides.iter().map(|side| str_node(border_style_str(side.style))).collect(),
+                        ));
+                        table_insert(table, "radius", radius_node(&border.radius));
+                        if !border.do_aa {
+                            table_insert(table, "do_aa", bool_node(false));
+                        }
+                    }
+                    BorderDetails::NinePatch(ref border) => {
+                        let stops: Vec<GradientStop> = base.gradient_stops().iter().collect();
+                        match border.source {
+                            NinePatchBorderSource::Image(key) => {
+                                table_insert(table, "border-type", str_node("image"));
+                                self.write_image(table, "image-source", key, false);
+                            }
+                            NinePatchBorderSource::Gradient(ref gradient) => {
+                                table_insert(table, "border-type", str_node("gradient"));
+                                self.write_gradient(table, gradient, &stops);
+                            }
+                            NinePatchBorderSource::RadialGradient(ref gradient) => {
+                                table_insert(table, "border-type", str_node("radial-gradient"));
+                                self.write_radial_gradient(table, gradient, &stops);
+                            }
+                            NinePatchBorderSource::ConicGradient(ref gradient) => {
+                                table_insert(table, "border-type", str_node("conic-gradient"));
+                                self.write_conic_gradient(table, gradient, &stops);
+                            }
+                        }
+                        table_insert(table, "image-width", i64_node(border.width as i64));
+                        table_insert(table, "image-height", i64_node(border.height as i64));
+                        table_insert(table, "fill", bool_node(border.fill));
+                        let s = &border.slice;
+                        table_insert(table, "slice", Yaml::Array(
+                            [s.top, s.right, s.bottom, s.left].iter().map(|&v| i64_node(v as i64)).collect(),
+                        ));
+                        let o = &border.outset;
+                        table_insert(table, "outset", f32_vec_node(&[o.top, o.right, o.bottom, o.left]));
+                        table_insert(table, "repeat-horizontal", str_node(repeat_mode_str(border.repeat_horizontal)));
+                        table_insert(table, "repeat-vertical", str_node(repeat_mode_str(border.repeat_vertical)));
+                    }
+                }
+            }
+            DisplayItem::BoxShadow(ref item) => {
+                self.write_common(table, "box-shadow", &item.common);
+                table_insert(table, "bounds", rect_node(&item.box_bounds));
+                table_insert(table, "offset", vector_node(&item.offset));
+                table_insert(table, "color", color_node(&item.color));
+                table_insert(table, "blur-radius", f32_node(item.blur_radius));
+                table_insert(table, "spread-radius", f32_node(item.spread_radius));
+                table_insert(table, "border-radius", radius_node(&item.border_radius));
+                let clip_mode = match item.clip_mode {
+                    BoxShadowClipMode::Outset => "outset",
+                    BoxShadowClipMode::Inset => "inset",
+                };
+                table_insert(table, "clip-mode", str_node(clip_mode));
+            }
+            DisplayItem::PushShadow(ref item) => {
+                table_insert(table, "type", str_node("shadow"));
+                self.write_space_and_clip(table, &item.space_and_clip);
+                table_insert(table, "blur-radius", f32_node(item.shadow.blur_radius));
+                table_insert(table, "offset", vector_node(&item.shadow.offset));
+                table_insert(table, "color", color_node(&item.shadow.color));
+                if !item.should_inflate {
+                    self.unsupported("shadows that don't inflate".to_owned());
+                }
+            }
+            DisplayItem::PopAllShadows => {
+                table_insert(table, "type", str_node("pop-all-shadows"));
+            }
+            DisplayItem::Gradient(ref item) => {
+                self.write_common(table, "gradient", &item.common);
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                table_insert(table, "tile-size", size_node(&item.tile_size));
+                table_insert(table, "tile-spacing", size_node(&item.tile_spacing));
+                let stops: Vec<GradientStop> = base.gradient_stops().iter().collect();
+                self.write_gradient(table, &item.gradient, &stops);
+            }
+            DisplayItem::RadialGradient(ref item) => {
+                self.write_common(table, "radial-gradient", &item.common);
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                table_insert(table, "tile-size", size_node(&item.tile_size));
+                table_insert(table, "tile-spacing", size_node(&item.tile_spacing));
+                let stops: Vec<GradientStop> = base.gradient_stops().iter().collect();
+                self.write_radial_gradient(table, &item.gradient, &stops);
+            }
+            DisplayItem::ConicGradient(ref item) => {
+                self.write_common(table, "conic-gradient", &item.common);
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                table_insert(table, "tile-size", size_node(&item.tile_size));
+                table_insert(table, "tile-spacing", size_node(&item.tile_spacing));
+                let stops: Vec<GradientStop> = base.gradient_stops().iter().collect();
+                self.write_conic_gradient(table, &item.gradient, &stops);
+            }
+            DisplayItem::Image(ref item) => {
+                self.write_common(table, "image", &item.common);
+                self.write_image_item(table, &item.bounds, item.image_key, item.image_rendering, item.alpha_type, &item.color);
+            }
+            DisplayItem::RepeatingImage(ref item) => {
+                self.write_common(table, "image", &item.common);
+                self.write_image_item(table, &item.bounds, item.image_key, item.image_rendering, item.alpha_type, &item.color);
+                table_insert(table, "stretch-size", size_node(&item.stretch_size));
+                table_insert(table, "tile-spacing", size_node(&item.tile_spacing));
+            }
+            DisplayItem::YuvImage(ref item) => {
+                self.write_common(table, "yuv-image", &item.common);
+                match item.yuv_data {
+                    YuvData::NV12(y, uv) => {
+                        table_insert(table, "format", str_node("nv12"));
+                        self.write_image(table, "src-y", y, false);
+                        self.write_image(table, "src-uv", uv, false);
+                    }
+                    YuvData::PlanarYCbCr(y, u, v) => {
+                        table_insert(table, "format", str_node("planar"));
+                        self.write_image(table, "src-y", y, false);
+                        self.write_image(table, "src-u", u, false);
+                        self.write_image(table, "src-v", v, false);
+                    }
+                    YuvData::InterleavedYCbCr(yuv) => {
+                        table_insert(table, "format", str_node("interleaved"));
+                        self.write_image(table, "src", yuv, false);
+                    }
+                }
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                table_insert(table, "yuv-color-space", str_node(item.color_space.as_str()));
+                table_insert(table, "color-range", str_node(item.color_range.as_str()));
+                table_insert(table, "transfer-function", str_node(item.transfer_function.as_str()));
+                if item.color_depth != ColorDepth::Color8 {
+                    self.unsupported(format!("yuv color depth {:?}", item.color_depth));
+                }
+                if item.image_rendering != ImageRendering::Auto {
+                    self.unsupported("yuv image rendering".to_owned());
+                }
+            }
+            DisplayItem::BackdropFilter(ref item) => {
+                // The reader intersects the bounds with the clip rect.
+                let common = CommonItemProperties { clip_rect: full_clip(), ..item.common };
+                self.write_common(table, "backdrop-filter", &common);
+                table_insert(table, "bounds", rect_node(&item.common.clip_rect));
+                self.write_filters(table, base);
+            }
+            DisplayItem::RectClip(ref item) => {
+                self.write_clip(table, item.id, &item.parent_space_and_clip);
+                table_insert(table, "bounds", rect_node(&item.clip_rect));
+            }
+            DisplayItem::RoundedRectClip(ref item) => {
+                self.write_clip(table, item.id, &item.parent_space_and_clip);
+                let mut region = Table::new();
+                table_insert(&mut region, "rect", rect_node(&item.clip.rect));
+                table_insert(&mut region, "radius", radius_node(&item.clip.radii));
+                table_insert(&mut region, "clip-mode", str_node(item.clip.mode.as_str()));
+                table_insert(table, "complex", Yaml::Array(vec![Yaml::Hash(region)]));
+            }
+            DisplayItem::ImageMaskClip(ref item) => {
+                self.write_clip(table, item.id, &item.parent_space_and_clip);
+                let mut mask = Table::new();
+                self.write_image(&mut mask, "image", item.image_mask.image, true);
+                table_insert(&mut mask, "rect", rect_node(&item.image_mask.rect));
+                table_insert(&mut mask, "repeat", bool_node(item.image_mask.repeat));
+                table_insert(table, "image-mask", Yaml::Hash(mask));
+                if !base.points().is_empty() {
+                    self.unsupported("image mask clips with points".to_owned());
+                }
+            }
+            DisplayItem::PathClip(ref item) => {
+                self.write_clip(table, item.id, &item.parent_space_and_clip);
+                let mut path = Table::new();
+                table_insert(&mut path, "bounds", rect_node(&item.rect));
+                table_insert(&mut path, "commands", path_commands_node(base.path_commands().iter()));
+                table_insert(&mut path, "fill-rule", str_node(item.fill_rule.as_str()));
+                table_insert(table, "path", Yaml::Hash(path));
+            }
+            DisplayItem::TextClip(ref item) => {
+                self.write_clip(table, item.id, &item.parent_space_and_clip);
+                let mut text = Table::new();
+                self.write_glyph_run(&mut text, item.font_key, base.glyphs(), &item.rect);
+                table_insert(table, "text", Yaml::Hash(text));
+                if item.glyph_options.is_some() {
+                    self.unsupported("glyph options of text clips".to_owned());
+                }
+            }
+            DisplayItem::ClipChain(ref item) => {
+                table_insert(table, "type", str_node("clip-chain"));
+                let id = self.ids.next();
+                table_insert(table, "id", i64_node(id as i64));
+                let mut clips = Vec::new();
+                for clip_id in base.clip_chain_items().iter() {
+                    if !clip_id.is_root() {
+                        clips.push(self.clip_node(clip_id));
+                    }
+                }
+                table_insert(table, "clips", Yaml::Array(clips));
+                if let Some(parent) = item.parent {
+                    let parent = self.clip_node(ClipId::ClipChain(parent));
+                    table_insert(table, "parent", parent);
+                }
+                self.ids.clip_ids.insert(ClipId::ClipChain(item.id), id);
+            }
+            DisplayItem::ScrollFrame(ref item) => {
+                table_insert(table, "type", str_node("scroll-frame"));
+                self.write_space_and_clip(table, &item.parent_space_and_clip);
+                let id = self.ids.next();
+                table_insert(table, "id", i64_node(id as i64));
+                table_insert(table, "bounds", rect_node(&item.clip_rect));
+                table_insert(table, "content-size", size_node(&item.content_rect.size()));
+                if item.content_rect.min != item.clip_rect.min {
+                    self.unsupported("scroll frame content offsets".to_owned());
+                }
+                table_insert(table, "external-scroll-offset", vector_node(&item.external_scroll_offset));
+                if let Some(offset) = self.scroll_offsets.get(&item.external_id) {
+                    table_insert(table, "scroll-offset", point_node(offset));
+                }
+                match item.overscroll_behavior {
+                    OverscrollBehavior::None => {}
+                    OverscrollBehavior::Glow => table_insert(table, "overscroll", str_node("glow")),
+                    behavior if behavior == OverscrollBehavior::RUBBER_BAND => {
+                        table_insert(table, "overscroll", str_node("rubber-band"))
+                    }
+                    behavior => self.unsupported(format!("overscroll behavior {:?}", behavior)),
+                }
+                if let ScrollSnapStrictness::Mandatory = item.snap_strictness {
+                    table_insert(table, "snap-type", str_node("mandatory"));
+                }
+                let areas: Vec<Yaml> = base.snap_areas().iter().map(|area| {
+                    let mut node = Table::new();
+                    table_insert(&mut node, "rect", rect_node(&area.rect));
+                    table_insert(&mut node, "align", Yaml::Array(vec![
+                        str_node(snap_alignment_str(area.align_x)),
+                        str_node(snap_alignment_str(area.align_y)),
+                    ]));
+                    Yaml::Hash(node)
+                }).collect();
+                if !areas.is_empty() {
+                    table_insert(table, "snap-areas", Yaml::Array(areas));
+                }
+                self.ids.spatial_ids.insert(item.scroll_frame_id, id);
+                self.ids.clip_ids.insert(item.clip_id, id);
+            }
+            DisplayItem::StickyFrame(ref item) => {
+                table_insert(table, "type", str_node("sticky-frame"));
+                let parent = self.spatial_node(item.parent_spatial_id);
+                table_insert(table, "clip-and-scroll", parent);
+                let id = self.ids.next();
+                table_insert(table, "id", i64_node(id as i64));
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                let margins = [
+                    ("margin-top", item.margins.top),
+                    ("margin-right", item.margins.right),
+                    ("margin-bottom", item.margins.bottom),
+                    ("margin-left", item.margins.left),
+                ];
+                for &(key, margin) in &margins {
+                    if let Some(margin) = margin {
+                        table_insert(table, key, f32_node(margin));
+                    }
+                }
+                let offset_bounds = |bounds: &StickyOffsetBounds| {
+                    Yaml::Array(vec![f32_node(bounds.min), f32_node(bounds.max)])
+                };
+                table_insert(table, "vertical-offset-bounds", offset_bounds(&item.vertical_offset_bounds));
+                table_insert(table, "horizontal-offset-bounds", offset_bounds(&item.horizontal_offset_bounds));
+                table_insert(table, "previously-applied-offset", vector_node(&item.previously_applied_offset));
+                self.ids.spatial_ids.insert(item.id, id);
+            }
+            DisplayItem::Iframe(ref item) => {
+                table_insert(table, "type", str_node("iframe"));
+                self.write_space_and_clip(table, &item.space_and_clip);
+                if item.clip_rect != full_clip() {
+                    table_insert(table, "clip-rect", rect_node(&item.clip_rect));
+                }
+                table_insert(table, "bounds", rect_node(&item.bounds));
+                table_insert(table, "id", Yaml::Array(vec![
+                    i64_node(item.pipeline_id.0 as i64),
+                    i64_node(item.pipeline_id.1 as i64),
+                ]));
+                table_insert(table, "ignore_missing_pipeline", bool_node(item.ignore_missing_pipeline));
+            }
+            DisplayItem::ReuseItems(..) | DisplayItem::RetainedItems(..) => {
+                self.unsupported("cached display items".to_owned());
+            }
+            DisplayItem::SetGradientStops |
+            DisplayItem::SetFilterOps |
+            DisplayItem::SetFilterData |
+            DisplayItem::SetFilterPrimitives |
+            DisplayItem::SetPoints |
+            DisplayItem::PushStackingContext(..) |
+            DisplayItem::PushReferenceFrame(..) |
+            DisplayItem::PopStackingContext |
+            DisplayItem::PopReferenceFrame => unreachable!(),
+        }
+    }
+
+    fn write_stacking_context(
+        &mut self,
+        table: &mut Table,
+        item: &PushStackingContextDisplayItem,
+        base: &DisplayItemRef,
+    ) {
+        table_insert(table, "type", str_node("stacking-context"));
+        let spatial = self.spatial_node(item.spatial_id);
+        table_insert(table, "clip-and-scroll", spatial);
+        self.write_prim_flags(table, item.prim_flags);
+
+        let sc = &item.stacking_context;
+        table_insert(table, "bounds", f32_vec_node(&[item.origin.x, item.origin.y, 0.0, 0.0]));
+        if let Some(clip_id) = sc.clip_id {
+            let clip = self.clip_node(clip_id);
+            table_insert(table, "clip-node", clip);
+        }
+        if sc.transform_style != TransformStyle::Flat {
+            table_insert(table, "transform-style", str_node(sc.transform_style.as_str()));
+        }
+        if sc.mix_blend_mode != MixBlendMode::Normal {
+            table_insert(table, "mix-blend-mode", str_node(sc.mix_blend_mode.as_str()));
+        }
+        if let RasterSpace::Local(scale) = sc.raster_space {
+            table_insert(table, "raster-space", str_node(&format!("local({})", scale)));
+        }
+        if sc.flags.contains(StackingContextFlags::IS_BACKDROP_ROOT) {
+            table_insert(table, "backdrop-root", bool_node(true));
+        }
+        if sc.flags.contains(StackingContextFlags::IS_BLEND_CONTAINER) {
+            table_insert(table, "blend-container", bool_node(true));
+        }
+        if let Some(snapshot) = sc.snapshot {
+            let name = self.snapshot_name(snapshot.as_image());
+            table_insert(table, "snapshot", str_node(&name));
+        }
+        self.write_filters(table, base);
+    }
+
+    fn write_reference_frame(&mut self, table: &mut Table, item: &ReferenceFrameDisplayListItem) {
+        table_insert(table, "type", str_node("reference-frame"));
+        let parent = self.spatial_node(item.parent_spatial_id);
+        table_insert(table, "clip-and-scroll", parent);
+        let id = self.ids.next();
+        table_insert(table, "id", i64_node(id as i64));
+        table_insert(table, "bounds", f32_vec_node(&[item.origin.x, item.origin.y, 0.0, 0.0]));
+
+        let frame = &item.reference_frame;
+        if frame.transform_style != TransformStyle::Flat {
+            table_insert(table, "transform-style", str_node(frame.transform_style.as_str()));
+        }
+        let transform = match frame.transform {
+            ReferenceTransformBinding::Static { binding: PropertyBinding::Value(transform) } => transform,
+            ReferenceTransformBinding::Static { binding: PropertyBinding::Binding(_, transform) } => {
+                self.unsupported("animated transforms".to_owned());
+                transform
+            }
+            ReferenceTransformBinding::Computed { .. } => {
+                self.unsupported("computed reference frame transforms".to_owned());
+                LayoutTransform::identity()
+            }
+        };
+        match frame.kind {
+            ReferenceFrameKind::Transform { is_2d_scale_translation, should_snap } => {
+                if is_2d_scale_translation || should_snap {
+                    self.unsupported("snapped reference frames".to_owned());
+                }
+                table_insert(table, "transform", matrix4d_node(&transform));
+            }
+            ReferenceFrameKind::Perspective { scrolling_relative_to } => {
+                if scrolling_relative_to.is_some() {
+                    self.unsupported("scrolling relative perspectives".to_owned());
+                }
+                table_insert(table, "perspective", matrix4d_node(&transform));
+            }
+        }
+        self.ids.spatial_ids.insert(frame.id, id);
+    }
+
+    /// Writes the keys common to content items.
+    fn write_common(&mut self, table: &mut Table, item_type: &str, common: &CommonItemProperties) {
+        table_insert(table, "type", str_node(item_type));
+        self.write_space_and_clip(table, &SpaceAndClipInfo {
+            spatial_id: common.spatial_id,
+            clip_id: common.clip_id,
+        });
+        if common.clip_rect != full_clip() {
+            table_insert(table, "clip-rect", rect_node(&common.clip_rect));
+        }
+        self.write_prim_flags(table, common.flags);
+    }
+
+    fn write_prim_flags(&mut self, table: &mut Table, flags: PrimitiveFlags) {
+        if !flags.contains(PrimitiveFlags::IS_BACKFACE_VISIBLE) {
+            table_insert(table, "backface-visible", bool_node(false));
+        }
+        if flags.contains(PrimitiveFlags::IS_SCROLLBAR_CONTAINER) {
+            table_insert(table, "scrollbar-container", bool_node(true));
+        }
+        if flags.contains(PrimitiveFlags::PREFER_COMPOSITOR_SURFACE) {
+            table_insert(table, "prefer-compositor-surface", bool_node(true));
+        }
+        let other = flags - (PrimitiveFlags::IS_BACKFACE_VISIBLE |
+            PrimitiveFlags::IS_SCROLLBAR_CONTAINER |
+            PrimitiveFlags::PREFER_COMPOSITOR_SURFACE);
+        if !other.is_empty() {
+            self.unsupported(format!("primitive flags {:?}", other));
+        }
+    }
+
+    fn write_space_and_clip(&mut self, table: &mut Table, space_and_clip: &SpaceAndClipInfo) {
+        let spatial = self.spatial_node(space_and_clip.spatial_id);
+        let clip = self.clip_node(space_and_clip.clip_id);
+        table_insert(table, "clip-and-scroll", Yaml::Array(vec![spatial, clip]));
+    }
+
+    /// Writes the keys common to clips, which the reader chains to the
+    /// clip of their "clip-and-scroll".
+    fn write_clip(&mut self, table: &mut Table, clip_id: ClipId, parent: &SpaceAndClipInfo) {
+        table_insert(table, "type", str_node("clip"));
+        self.write_space_and_clip(table, parent);
+        let id = self.ids.next();
+        table_insert(table, "id", i64_node(id as i64));
+        self.ids.clip_ids.insert(clip_id, id);
+    }
+
+    fn spatial_node(&mut self, spatial_id: SpatialId) -> Yaml {
+        let pipeline_id = self.ids.pipeline_id;
+        if spatial_id == SpatialId::root_reference_frame(pipeline_id) {
+            return str_node("root-reference-frame");
+        }
+        if spatial_id == SpatialId::root_scroll_node(pipeline_id) {
+            return str_node("root-scroll-node");
+        }
+        match self.ids.spatial_ids.get(&spatial_id) {
+            Some(&id) => i64_node(id as i64),
+            None => {
+                self.unsupported(format!("undefined spatial node {:?}", spatial_id));
+                str_node("root-scroll-node")
+            }
+        }
+    }
+
+    fn clip_node(&mut self, clip_id: ClipId) -> Yaml {
+        if clip_id == ClipId::root(self.ids.pipeline_id) {
+            return str_node("root_clip");
+        }
+        match self.ids.clip_ids.get(&clip_id) {
+            Some(&id) => i64_node(id as i64),
+            None => {
+                self.unsupported(format!("undefined clip {:?}", clip_id));
+                str_node("root_clip")
+            }
+        }
+    }
+
+    fn write_filters(&mut self, table: &mut Table, base: &DisplayItemRef) {
+        let mut filters = Vec::new();
+        for filter in base.filters().iter() {
+            let filter = match filter {
+                FilterOp::Identity => "identity".to_owned(),
+                FilterOp::ComponentTransfer => "component-transfer".to_owned(),
+                FilterOp::Blur(width, height) => format!("blur({}, {})", width, height),
+                FilterOp::Brightness(amount) => format!("brightness({})", amount),
+                FilterOp::Contrast(amount) => format!("contrast({})", amount),
+                FilterOp::Grayscale(amount) => format!("grayscale({})", amount),
+                FilterOp::HueRotate(angle) => format!("hue-rotate({})", angle),
+                FilterOp::Invert(amount) => format!("invert({})", amount),
+                FilterOp::Opacity(binding, amount) => {
+                    if let PropertyBinding::Binding(..) = binding {
+                        self.unsupported("animated opacity".to_owned());
+                    }
+                    format!("opacity({})", amount)
+                }
+                FilterOp::Saturate(amount) => format!("saturate({})", amount),
+                FilterOp::Sepia(amount) => format!("sepia({})", amount),
+                FilterOp::SrgbToLinear => "srgb-to-linear".to_owned(),
+                FilterOp::LinearToSrgb => "linear-to-srgb".to_owned(),
+                FilterOp::DropShadow(shadow) => {
+                    format!("drop-shadow([{}, {}], {}, [{}])",
+                        shadow.offset.x,
+                        shadow.offset.y,
+                        shadow.blur_radius,
+                        Self::color_args(&shadow.color),
+                    )
+                }
+                FilterOp::ColorMatrix(matrix) => {
+                    let values: Vec<String> = matrix.iter().map(|v| v.to_string()).collect();
+                    format!("color-matrix({})", values.join(", "))
+                }
+                FilterOp::Flood(color) => format!("flood([{}])", Self::color_args(&color)),
+            };
+            filters.push(Yaml::String(filter));
+        }
+        if !filters.is_empty() {
+            table_insert(table, "filters", Yaml::Array(filters));
+        }
+
+        let filter_datas: Vec<Yaml> = base.filter_datas().iter().map(|data| {
+            let func_types = data.func_types.iter().map(|t| str_node(t.as_str())).collect();
+            let values = |range: &ItemRange<f32>| {
+                let values: Vec<f32> = range.iter().collect();
+                f32_vec_node(&values)
+            };
+            Yaml::Array(vec![
+                Yaml::Array(func_types),
+                values(&data.r_values),
+                values(&data.g_values),
+                values(&data.b_values),
+                values(&data.a_values),
+            ])
+        }).collect();
+        if !filter_datas.is_empty() {
+            table_insert(table, "filter-datas", Yaml::Array(filter_datas));
+        }
+
+        let mut primitives = Vec::new();
+        for primitive in base.filter_primitives().iter() {
+            let node = self.write_filter_primitive(&primitive);
+            primitives.push(Yaml::Hash(node));
+        }
+        if !primitives.is_empty() {
+            table_insert(table, "filter-primitives", Yaml::Array(primitives));
+        }
+    }
+
+    /// The comma separated color arguments of drop-shadow and flood filters.
+    fn color_args(color: &ColorF) -> String {
+        match color_node(color) {
+            Yaml::String(values) => values.split_whitespace().collect::<Vec<_>>().join(", "),
+            _ => unreachable!(),
+        }
+    }
+
+    fn write_filter_primitive(&mut self, primitive: &FilterPrimitive) -> Table {
+        let mut table = Table::new();
+        let t = &mut table;
+        match primitive.kind {
+            FilterPrimitiveKind::Identity(ref p) => {
+                table_insert(t, "type", str_node("identity"));
+                table_insert(t, "in", filter_input_node(p.input));
+            }
+            FilterPrimitiveKind::Blend(ref p) => {
+                table_insert(t, "type", str_node("blend"));
+                table_insert(t, "in1", filter_input_node(p.input1));
+                table_insert(t, "in2", filter_input_node(p.input2));
+                table_insert(t, "blend-mode", str_node(p.mode.as_str()));
+            }
+            FilterPrimitiveKind::Flood(ref p) => {
+                table_insert(t, "type", str_node("flood"));
+                table_insert(t, "color", color_node(&p.color));
+            }
+            FilterPrimitiveKind::Blur(ref p) => {
+                table_insert(t, "type", str_node("blur"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "width", f32_node(p.width));
+                table_insert(t, "height", f32_node(p.height));
+            }
+            FilterPrimitiveKind::Opacity(ref p) => {
+                table_insert(t, "type", str_node("opacity"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "opacity", f32_node(p.opacity));
+            }
+            FilterPrimitiveKind::ColorMatrix(ref p) => {
+                table_insert(t, "type", str_node("color-matrix"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "matrix", f32_vec_node(&p.matrix));
+            }
+            FilterPrimitiveKind::DropShadow(ref p) => {
+                table_insert(t, "type", str_node("drop-shadow"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "offset", vector_node(&p.shadow.offset));
+                table_insert(t, "color", color_node(&p.shadow.color));
+                table_insert(t, "radius", f32_node(p.shadow.blur_radius));
+            }
+            FilterPrimitiveKind::ComponentTransfer(ref p) => {
+                table_insert(t, "type", str_node("component-transfer"));
+                table_insert(t, "in", filter_input_node(p.input));
+            }
+            FilterPrimitiveKind::Offset(ref p) => {
+                table_insert(t, "type", str_node("offset"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "offset", vector_node(&p.offset));
+            }
+            FilterPrimitiveKind::Composite(ref p) => {
+                table_insert(t, "type", str_node("composite"));
+                table_insert(t, "in1", filter_input_node(p.input1));
+                table_insert(t, "in2", filter_input_node(p.input2));
+                let operator = match p.operator {
+                    CompositeOperator::Over => "over",
+                    CompositeOperator::In => "in",
+                    CompositeOperator::Out => "out",
+                    CompositeOperator::Atop => "atop",
+                    CompositeOperator::Xor => "xor",
+                    CompositeOperator::Lighter => "lighter",
+                    CompositeOperator::Arithmetic(k) => {
+                        table_insert(t, "k-values", f32_vec_node(&k));
+                        "arithmetic"
+                    }
+                };
+                table_insert(t, "operator", str_node(operator));
+            }
+            FilterPrimitiveKind::Turbulence(ref p) => {
+                table_insert(t, "type", str_node("turbulence"));
+                table_insert(t, "base-frequency", f32_vec_node(&[p.base_frequency_x, p.base_frequency_y]));
+                table_insert(t, "num-octaves", i64_node(p.num_octaves as i64));
+                table_insert(t, "seed", i64_node(p.seed as i64));
+                table_insert(t, "stitch-tiles", bool_node(p.stitch_tiles));
+                table_insert(t, "noise-type", str_node(p.kind.as_str()));
+            }
+            FilterPrimitiveKind::Morphology(ref p) => {
+                table_insert(t, "type", str_node("morphology"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "radius", f32_vec_node(&[p.radius_x, p.radius_y]));
+                table_insert(t, "operator", str_node(p.operator.as_str()));
+            }
+            FilterPrimitiveKind::ConvolveMatrix(ref p) => {
+                table_insert(t, "type", str_node("convolve-matrix"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "order", Yaml::Array(vec![
+                    i64_node(p.order_x as i64),
+                    i64_node(p.order_y as i64),
+                ]));
+                let count = (p.order_x * p.order_y) as usize;
+                table_insert(t, "kernel", f32_vec_node(&p.kernel[.. count]));
+                table_insert(t, "divisor", f32_node(p.divisor));
+                table_insert(t, "bias", f32_node(p.bias));
+                table_insert(t, "target", Yaml::Array(vec![
+                    i64_node(p.target_x as i64),
+                    i64_node(p.target_y as i64),
+                ]));
+                table_insert(t, "edge-mode", str_node(p.edge_mode.as_str()));
+                table_insert(t, "preserve-alpha", bool_node(p.preserve_alpha));
+            }
+            FilterPrimitiveKind::DisplacementMap(ref p) => {
+                table_insert(t, "type", str_node("displacement-map"));
+                table_insert(t, "in1", filter_input_node(p.input1));
+                table_insert(t, "in2", filter_input_node(p.input2));
+                table_insert(t, "scale", f32_node(p.scale));
+                table_insert(t, "x-channel", str_node(p.x_channel_selector.as_str()));
+                table_insert(t, "y-channel", str_node(p.y_channel_selector.as_str()));
+            }
+            FilterPrimitiveKind::DiffuseLighting(ref p) => {
+                table_insert(t, "type", str_node("diffuse-lighting"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "surface-scale", f32_node(p.surface_scale));
+                table_insert(t, "diffuse-constant", f32_node(p.diffuse_constant));
+                table_insert(t, "lighting-color", color_node(&p.lighting_color));
+                table_insert(t, "light", Self::light_node(&p.light));
+            }
+            FilterPrimitiveKind::SpecularLighting(ref p) => {
+                table_insert(t, "type", str_node("specular-lighting"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "surface-scale", f32_node(p.surface_scale));
+                table_insert(t, "specular-constant", f32_node(p.specular_constant));
+                table_insert(t, "specular-exponent", f32_node(p.specular_exponent));
+                table_insert(t, "lighting-color", color_node(&p.lighting_color));
+                table_insert(t, "light", Self::light_node(&p.light));
+            }
+            FilterPrimitiveKind::Tile(ref p) => {
+                table_insert(t, "type", str_node("tile"));
+                table_insert(t, "in", filter_input_node(p.input));
+                table_insert(t, "rect", rect_node(&p.source_rect));
+            }
+            FilterPrimitiveKind::Merge(ref p) => {
+                table_insert(t, "type", str_node("merge"));
+                let inputs = p.inputs[.. p.input_count as usize]
+                    .iter()
+                    .map(|input| filter_input_node(*input))
+                    .collect();
+                table_insert(t, "inputs", Yaml::Array(inputs));
+            }
+            FilterPrimitiveKind::Image(ref p) => {
+                table_insert(t, "type", str_node("image"));
+                self.write_image(t, "src", p.image_key, false);
+                // The image and the primitive share the "color-space" key.
+                if t.contains_key(&str_node("color-space")) {
+                    self.unsupported("image filter primitives with a color space".to_owned());
+                }
+                if p.rect.is_empty() {
+                    self.unsupported("empty image filter primitives".to_owned());
+                }
+                table_insert(t, "rect", rect_node(&p.rect));
+                table_insert(t, "rendering", str_node(p.rendering.as_str()));
+            }
+        }
+        table_insert(t, "color-space", str_node(primitive.color_space.as_str()));
+        table
+    }
+
+    fn light_node(light: &LightSource) -> Yaml {
+        let mut table = Table::new();
+        match *light {
+            LightSource::Distant(ref light) => {
+                table_insert(&mut table, "type", str_node("distant"));
+                table_insert(&mut table, "azimuth", f32_node(light.azimuth));
+                table_insert(&mut table, "elevation", f32_node(light.elevation));
+            }
+            LightSource::Point(ref light) => {
+                table_insert(&mut table, "type", str_node("point"));
+                table_insert(&mut table, "position", f32_vec_node(&[light.x, light.y, light.z]));
+            }
+            LightSource::Spot(ref light) => {
+                table_insert(&mut table, "type", str_node("spot"));
+                table_insert(&mut table, "position", f32_vec_node(&[light.x, light.y, light.z]));
+                table_insert(&mut table, "points-at", f32_vec_node(&[
+                    light.points_at_x,
+                    light.points_at_y,
+                    light.points_at_z,
+                ]));
+                table_insert(&mut table, "specular-exponent", f32_node(light.specular_exponent));
+                if let Some(angle) = light.limiting_cone_angle {
+                    table_insert(&mut table, "limiting-cone-angle", f32_node(angle));
+                }
+            }
+        }
+        Yaml::Hash(table)
+    }
+
+    fn write_stops(&mut self, table: &mut Table, stops: &[GradientStop], start: f32, end: f32) {
+        if stops.len() < 2 {
+            self.unsupported("gradients with less than two stops".to_owned());
+        }
+        let mut node = Vec::new();
+        for stop in stops {
+            // Radial and conic gradients store their stops normalized to the
+            // start and end offsets, which the reader computes again.
+            let offset = if start == 0.0 && end == 1.0 {
+                stop.offset
+            } else {
+                start + stop.offset * (end - start)
+            };
+            node.push(f32_node(offset));
+            node.push(color_node(&stop.color));
+        }
+        table_insert(table, "stops", Yaml::Array(node));
+    }
+
+    fn write_gradient_options(
+        table: &mut Table,
+        extend_mode: ExtendMode,
+        interpolation_space: ColorInterpolationSpace,
+        color_space: RgbColorSpace,
+    ) {
+        if let ExtendMode::Repeat = extend_mode {
+            table_insert(table, "repeat", bool_node(true));
+        }
+        if interpolation_space != ColorInterpolationSpace::Srgb {
+            table_insert(table, "interpolation-space", str_node(interpolation_space_str(interpolation_space)));
+        }
+        if color_space != RgbColorSpace::Srgb {
+            table_insert(table, "color-space", str_node(color_space.as_str()));
+        }
+    }
+
+    fn write_gradient(&mut self, table: &mut Table, gradient: &Gradient, stops: &[GradientStop]) {
+        table_insert(table, "start", point_node(&gradient.start_point));
+        table_insert(table, "end", point_node(&gradient.end_point));
+        self.write_stops(table, stops, 0.0, 1.0);
+        Self::write_gradient_options(table, gradient.extend_mode, gradient.interpolation_space, gradient.color_space);
+    }
+
+    fn write_radial_gradient(&mut self, table: &mut Table, gradient: &RadialGradient, stops: &[GradientStop]) {
+        table_insert(table, "center", point_node(&gradient.center));
+        table_insert(table, "radius", size_node(&gradient.radius));
+        self.write_stops(table, stops, gradient.start_offset, gradient.end_offset);
+        Self::write_gradient_options(table, gradient.extend_mode, gradient.interpolation_space, gradient.color_space);
+    }
+
+    fn write_conic_gradient(&mut self, table: &mut Table, gradient: &ConicGradient, stops: &[GradientStop]) {
+        table_insert(table, "center", point_node(&gradient.center));
+        table_insert(table, "angle", f32_node(gradient.angle));
+        self.write_stops(table, stops, gradient.start_offset, gradient.end_offset);
+        Self::write_gradient_options(table, gradient.extend_mode, gradient.interpolation_space, gradient.color_space);
+    }
+
+    fn write_image_item(
+        &mut self,
+        table: &mut Table,
+        bounds: &LayoutRect,
+        image_key: ImageKey,
+        rendering: ImageRendering,
+        alpha_type: AlphaType,
+        color: &ColorF,
+    ) {
+        table_insert(table, "bounds", rect_node(bounds));
+        if self.snapshots.contains_key(&image_key) {
+            let name = self.snapshot_name(image_key);
+            table_insert(table, "snapshot", str_node(&name));
+        } else {
+            self.write_image(table, "src", image_key, true);
+        }
+        if rendering != ImageRendering::Auto {
+            table_insert(table, "rendering", str_node(rendering.as_str()));
+        }
+        if let AlphaType::Alpha = alpha_type {
+            table_insert(table, "alpha-type", str_node("alpha"));
+        }
+        if *color != ColorF::WHITE {
+            self.unsupported("tinted images".to_owned());
+        }
+    }
+
+    fn snapshot_name(&mut self, image_key: ImageKey) -> String {
+        let count = self.snapshots.len();
+        self.snapshots
+            .entry(image_key)
+            .or_insert_with(|| format!("snapshot-{}", count + 1))
+            .clone()
+    }
+
+    /// Writes the path of an image under `key`, along with the keys the
+    /// reader takes its format, color space and tiling from.
+    fn write_image(&mut self, table: &mut Table, key: &str, image_key: ImageKey, allow_tiling: bool) {
+        let (path, format, color_space, tiling) = match self.image_path(image_key) {
+            Some(image) => image,
+            None => {
+                self.unsupported(format!("image {:?} without raw data", image_key));
+                (String::new(), None, RgbColorSpace::Srgb, None)
+            }
+        };
+        table_insert(table, key, str_node(&path));
+
+        // Items with several images share these keys.
+        let set = |table: &mut Table, key: &str, value: Yaml| -> bool {
+            match table.get(&str_node(key)) {
+                Some(existing) => *existing == value,
+                None => {
+                    table_insert(table, key, value);
+                    true
+                }
+            }
+        };
+        let mut consistent = true;
+        if let Some(format) = format {
+            consistent &= set(table, "image-format", str_node(format.as_str()));
+        }
+        if color_space != RgbColorSpace::Srgb {
+            consistent &= set(table, "color-space", str_node(color_space.as_str()));
+        }
+        if let Some(tile_size) = tiling {
+            if allow_tiling {
+                consistent &= set(table, "tile-size", i64_node(tile_size as i64));
+            } else {
+                self.unsupported("tiled images in borders, yuv images and filters".to_owned());
+            }
+        }
+        if !consistent {
+            self.unsupported("images with different formats in one item".to_owned());
+        }
+    }
+
+    /// Dumps an image as a PNG if it wasn't already, and returns its path
+    /// along with the format to convert it to, its color space and tiling.
+    fn image_path(
+        &mut self,
+        image_key: ImageKey,
+    ) -> Option<(String, Option<ImageFormat>, RgbColorSpace, Option<TileSize>)> {
+        let (descriptor, tiling) = {
+            let image = self.images.get(&image_key)?;
+            (image.descriptor, image.tiling)
+        };
+        let format = match descriptor.format {
+            ImageFormat::BGRA8 | ImageFormat::R8 => None,
+            format => Some(format),
+        };
+        if let Some(ref path) = self.images[&image_key].path {
+            return Some((path.clone(), format, descriptor.color_space, tiling));
+        }
+
+        let bytes = match self.images[&image_key].data {
+            ImageData::Raw(ref bytes) => bytes.clone(),
+            ImageData::External(..) => return None,
+        };
+        let (width, height) = (descriptor.size.width as usize, descriptor.size.height as usize);
+        let bpp = descriptor.format.bytes_per_pixel() as usize;
+        let row_bytes = width * bpp;
+        let stride = descriptor.stride.map_or(row_bytes, |stride| stride as usize);
+        let mut pixels = Vec::with_capacity(row_bytes * height);
+        for y in 0 .. height {
+            let start = descriptor.offset as usize + y * stride;
+            pixels.extend_from_slice(&bytes[start .. start + row_bytes]);
+        }
+
+        // Formats other than BGRA8 and R8 are written as an 8 bit image that
+        // the reader converts back, which only works if it was made that way.
+        let (base_format, pixels) = match descriptor.format {
+            ImageFormat::BGRA8 | ImageFormat::R8 => (descriptor.format, pixels),
+            format => {
+                let (base_format, base_pixels) = Self::to_8_bit(format, &pixels)?;
+                let base_descriptor = ImageDescriptor::new(
+                    width as i32,
+                    height as i32,
+                    base_format,
+                    descriptor.flags,
+                );
+                let (_, converted) = convert_image(base_descriptor, ImageData::new(base_pixels.clone()), format);
+                match converted {
+                    ImageData::Raw(ref converted) if **converted == pixels => {}
+                    _ => {
+                        self.unsupported(format!("{:?} images", format));
+                        return None;
+                    }
+                }
+                (base_format, base_pixels)
+            }
+        };
+
+        let (color_type, data) = match base_format {
+            ImageFormat::R8 => (ColorType::L8, pixels),
+            _ => {
+                let mut data = pixels.clone();
+                unpremultiply(&mut data);
+                let mut check = data.clone();
+                premultiply(&mut check);
+                if check != pixels {
+                    self.unsupported("images that aren't premultiplied".to_owned());
+                }
+                (ColorType::Rgba8, data)
+            }
+        };
+
+        fs::create_dir_all(&self.rsrc_dir).unwrap();
+        let name = format!("image-{}.png", self.next_rsrc_id);
+        self.next_rsrc_id += 1;
+        let encoder = PNGEncoder::new(File::create(self.rsrc_dir.join(&name)).unwrap());
+        encoder
+            .encode(&data, width as u32, height as u32, color_type)
+            .expect("Unable to encode PNG!");
+
+        let path = format!("{}/{}", self.rsrc_prefix, name);
+        self.images.get_mut(&image_key).unwrap().path = Some(path.clone());
+        Some((path, format, descriptor.color_space, tiling))
+    }
+
+    /// Reduces high bit depth pixels to the BGRA8 or R8 pixels they were
+    /// likely converted from.
+    fn to_8_bit(format: ImageFormat, pixels: &[u8]) -> Option<(ImageFormat, Vec<u8>)> {
+        let unorm = |v: f32| (v * 255.0).round().max(0.0).min(255.0) as u8;
+        let half = |p: &[u8]| f16_to_f32(u16::from_ne_bytes([p[0], p[1]]));
+        match format {
+            ImageFormat::RGBA16F => Some((ImageFormat::BGRA8, pixels.chunks(8).flat_map(|p| {
+                let [r, g, b, a] = [half(&p[0 ..]), half(&p[2 ..]), half(&p[4 ..]), half(&p[6 ..])];
+                vec![unorm(b), unorm(g), unorm(r), unorm(a)]
+            }).collect())),
+            ImageFormat::R16F => Some((ImageFormat::R8, pixels.chunks(2).map(|p| unorm(half(p))).collect())),
+            ImageFormat::RGB10A2 => Some((ImageFormat::BGRA8, pixels.chunks(4).flat_map(|p| {
+                let packed = u32::from_ne_bytes([p[0], p[1], p[2], p[3]]);
+                let channel = |shift: u32, max: u32| unorm(((packed >> shift) & max) as f32 / max as f32);
+                vec![channel(20, 1023), channel(10, 1023), channel(0, 1023), channel(30, 3)]
+            }).collect())),
+            _ => None,
+        }
+    }
+
+    /// Writes the keys of a run of glyphs, shared by text items and clips.
+    fn write_glyph_run(
+        &mut self,
+        table: &mut Table,
+        font_instance_key: FontInstanceKey,
+        glyphs: ItemRange<GlyphInstance>,
+        bounds: &LayoutRect,
+    ) {
+        match self.font_instances.get(&font_instance_key).cloned() {
+            Some(instance) => self.write_font_instance(table, &instance),
+            None => self.unsupported(format!("unknown font instance {:?}", font_instance_key)),
+        }
+
+        let mut indices = Vec::new();
+        let mut offsets = Vec::new();
+        for glyph in glyphs.iter() {
+            indices.push(i64_node(glyph.index as i64));
+            offsets.push(glyph.point.x);
+            offsets.push(glyph.point.y);
+        }
+        table_insert(table, "glyphs", Yaml::Array(indices));
+        table_insert(table, "offsets", f32_vec_node(&offsets));
+        table_insert(table, "bounds", rect_node(bounds));
+    }
+
+    fn write_font_instance(&mut self, table: &mut Table, instance: &AddFontInstance) {
+        match self.font_path(instance.font_key) {
+            Some((path, index)) => {
+                table_insert(table, "font", str_node(&path));
+                if index != 0 {
+                    table_insert(table, "font-index", i64_node(index as i64));
+                }
+            }
+            None => self.unsupported(format!("font {:?}", instance.font_key)),
+        }
+
+        let size = instance.glyph_size;
+        table_insert(table, "size", f32_node(exact_inverse(size, size * 12.0 / 16.0, |pt| pt * 16.0 / 12.0)));
+
+        if instance.platform_options.is_some() || !instance.variations.is_empty() {
+            self.unsupported("platform font options and variations".to_owned());
+        }
+        let options = instance.options.unwrap_or_default();
+        if options.render_mode != FontInstanceOptions::default().render_mode {
+            self.unsupported(format!("font render mode {:?}", options.render_mode));
+        }
+        let flag_keys = [
+            (FontInstanceFlags::SYNTHETIC_BOLD, "synthetic-bold"),
+            (FontInstanceFlags::EMBEDDED_BITMAPS, "embedded-bitmaps"),
+            (FontInstanceFlags::TRANSPOSE, "transpose"),
+            (FontInstanceFlags::FLIP_X, "flip-x"),
+            (FontInstanceFlags::FLIP_Y, "flip-y"),
+            (FontInstanceFlags::DISTANCE_FIELD, "distance-field"),
+        ];
+        let mut known = FontInstanceFlags::default();
+        for &(flag, key) in &flag_keys {
+            if options.flags.contains(flag) {
+                table_insert(table, key, bool_node(true));
+            }
+            known |= flag;
+        }
+        if options.flags | known != known || !options.flags.contains(FontInstanceFlags::default()) {
+            self.unsupported(format!("font instance flags {:?}", options.flags));
+        }
+        if options.bg_color.a != 0 {
+            let bg = options.bg_color;
+            table_insert(table, "bg-color", f32_vec_node(&[
+                bg.r as f32,
+                bg.g as f32,
+                bg.b as f32,
+                bg.a as f32 / 255.0,
+            ]));
+        }
+        if options.synthetic_italics.angle != 0 {
+            table_insert(table, "synthetic-italics", f32_node(options.synthetic_italics.to_degrees()));
+        }
+    }
+
+    /// Dumps a font if it wasn't already, and returns its path along with
+    /// the index of the face in it.
+    fn font_path(&mut self, font_key: FontKey) -> Option<(String, u32)> {
+        let (path, index) = {
+            let font = self.fonts.get(&font_key)?;
+            let index = match font.font {
+                AddFont::Raw(_, _, index) => index,
+                #[cfg(not(target_os = "macos"))]
+                AddFont::Native(_, ref handle) => handle.index,
+                #[cfg(target_os = "macos")]
+                AddFont::Native(..) => return None,
+            };
+            (font.path.clone(), index)
+        };
+        if let Some(path) = path {
+            return Some((path, index));
+        }
+
+        let path = match self.fonts[&font_key].font {
+            AddFont::Raw(_, ref bytes, _) => {
+                fs::create_dir_all(&self.rsrc_dir).unwrap();
+                let name = format!("font-{}.ttf", self.next_rsrc_id);
+                self.next_rsrc_id += 1;
+                let mut file = File::create(self.rsrc_dir.join(&name)).unwrap();
+                file.write_all(bytes).unwrap();
+                format!("{}/{}", self.rsrc_prefix, name)
+            }
+            // Native fonts are referred to by their absolute path.
+            #[cfg(not(target_os = "macos"))]
+            AddFont::Native(_, ref handle) => handle.path.to_string_lossy().into_owned(),
+            #[cfg(target_os = "macos")]
+            AddFont::Native(..) => unreachable!(),
+        };
+        self.fonts.get_mut(&font_key).unwrap().path = Some(path.clone());
+        Some((path, index))
+    }
+}
+
+/// Writes each document of a capture taken with `CaptureBits::SCENE` to a
+/// YAML file, and returns the features that couldn't be written. With more
+/// than one document, the document index is appended to the file name.
+pub fn write_capture(capture_dir: &Path, yaml_path: &Path) -> Vec<String> {
+    let (resources, scenes) = webrender::read_capture_scenes(capture_dir.to_owned());
+    let mut unsupported = Vec::new();
+
+    for (index, scene) in scenes.iter().enumerate() {
+        let path = if scenes.len() > 1 {
+            let stem = yaml_path.file_stem().unwrap().to_string_lossy();
+            yaml_path.with_file_name(format!("{}-{}.yaml", stem, index))
+        } else {
+            yaml_path.to_owned()
+        };
+        let root_pipeline_id = match scene.root_pipeline_id {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let mut writer = YamlFrameWriter::new(&path);
+        writer.update_resources(&resources);
+        writer.set_display_lists(&scene.display_lists, &HashMap::new());
+        writer.finish_write(root_pipeline_id);
+        println!("Wrote {}", path.display());
+
+        for feature in writer.unsupported_features() {
+            if !unsupported.contains(feature) {
+                unsupported.push(feature.clone());
+            }
+        }
+    }
+    unsupported
+}
@@ -42,6 +42,8 @@ pub trait YamlHelper {
     fn as_filter_primitive(&self) -> Option<FilterPrimitive>;
     fn as_vec_filter_primitive(&self) -> Option<Vec<FilterPrimitive>>;
     fn as_color_space(&self) -> Option<ColorSpace>;
+    fn as_color_interpolation_space(&self) -> Option<ColorInterpolationSpace>;
+    fn as_rgb_color_space(&self) -> Option<RgbColorSpace>;
 }
 
 fn string_to_color(color: &str) -> Option<ColorF> {
@@ -147,6 +149,12 @@ define_string_enum!(
 
 define_string_enum!(ClipMode, [Clip = "clip", ClipOut = "clip-out"]);
 
+define_string_enum!(FillRule, [Nonzero = "nonzero", Evenodd = "evenodd"]);
+
+define_string_enum!(LineJoin, [Miter = "miter", Round = "round", Bevel = "bevel"]);
+
+define_string_enum!(LineCap, [Butt = "butt", Round = "round", Square = "square"]);
+
 define_string_enum!(
     ComponentTransferFuncType,
     [
@@ -166,6 +174,121 @@ define_string_enum!(
     ]
 );
 
+define_string_enum!(
+    TurbulenceType,
+    [
+        FractalNoise = "fractal-noise",
+        Turbulence = "turbulence"
+    ]
+);
+
+define_string_enum!(MorphologyOperator, [Erode = "erode", Dilate = "dilate"]);
+
+define_string_enum!(
+    EdgeMode,
+    [
+        Duplicate = "duplicate",
+        Wrap = "wrap",
+        None = "none"
+    ]
+);
+
+define_string_enum!(
+    ColorChannel,
+    [
+        Red = "r",
+        Green = "g",
+        Blue = "b",
+        Alpha = "a"
+    ]
+);
+
+define_string_enum!(
+    ImageFormat,
+    [
+        R8 = "r8",
+        R16 = "r16",
+        BGRA8 = "bgra8",
+        RGBAF32 = "rgba-f32",
+        RG8 = "rg8",
+        RG16 = "rg16",
+        RGBAI32 = "rgba-i32",
+        RGBA8 = "rgba8",
+        RGBA16F = "rgba16f",
+        R16F = "r16f",
+        RGB10A2 = "rgb10a2"
+    ]
+);
+
+define_string_enum!(
+    RgbColorSpace,
+    [
+        Srgb = "srgb",
+        DisplayP3 = "display-p3",
+        AdobeRgb = "adobe-rgb",
+        Rec2020 = "rec2020"
+    ]
+);
+
+define_string_enum!(
+    YuvColorSpace,
+    [
+        Rec601 = "rec601",
+        Rec709 = "rec709",
+        Rec2020 = "rec2020",
+        Identity = "identity"
+    ]
+);
+
+define_string_enum!(ColorRange, [Limited = "limited", Full = "full"]);
+
+define_string_enum!(YuvTransferFunction, [Sdr = "sdr", Pq = "pq"]);
+
+define_string_enum!(
+    ImageRendering,
+    [
+        Auto = "auto",
+        CrispEdges = "crisp-edges",
+        Pixelated = "pixelated"
+    ]
+);
+
+fn as_light_source(yaml: &Yaml) -> LightSource {
+    let position = |key: &str| -> [f32; 3] {
+        let values = yaml[key].as_vec_f32().expect("Light positions must have 3 values");
+        assert!(values.len() == 3, "Light positions must have 3 values");
+        [values[0], values[1], values[2]]
+    };
+
+    match yaml["type"].as_str() {
+        Some("distant") => {
+            LightSource::Distant(DistantLight {
+                azimuth: yaml["azimuth"].as_f32().unwrap_or(0.0),
+                elevation: yaml["elevation"].as_f32().unwrap_or(0.0),
+            })
+        }
+        Some("point") => {
+            let [x, y, z] = position("position");
+            LightSource::Point(PointLight { x, y, z })
+        }
+        Some("spot") => {
+            let [x, y, z] = position("position");
+            let [points_at_x, points_at_y, points_at_z] = position("points-at");
+            LightSource::Spot(SpotLight {
+                x,
+                y,
+                z,
+                points_at_x,
+                points_at_y,
+                points_at_z,
+                specular_exponent: yaml["specular-exponent"].as_f32().unwrap_or(1.0),
+                limiting_cone_angle: yaml["limiting-cone-angle"].as_f32(),
+            })
+        }
+        _ => panic!("Invalid light source"),
+    }
+}
+
 // Rotate around `axis` by `degrees` angle
 fn make_rotation(
     origin: &LayoutPoint,
@@ -782,6 +905,126 @@ impl YamlHelper for Yaml {
                         operator,
                     })
                 }
+                "turbulence" => {
+                    let base_frequency = self["base-frequency"].as_vec_f32().unwrap();
+                    FilterPrimitiveKind::Turbulence(TurbulencePrimitive {
+                        base_frequency_x: base_frequency[0],
+                        base_frequency_y: *base_frequency.get(1).unwrap_or(&base_frequency[0]),
+                        num_octaves: self["num-octaves"].as_i64().unwrap_or(1) as u32,
+                        seed: self["seed"].as_i64().unwrap_or(0) as i32,
+                        stitch_tiles: self["stitch-tiles"].as_bool().unwrap_or(false),
+                        kind: self["noise-type"].as_str()
+                            .and_then(|x| StringEnum::from_str(x))
+                            .unwrap_or(TurbulenceType::Turbulence),
+                    })
+                }
+                "morphology" => {
+                    let radius = self["radius"].as_vec_f32().unwrap();
+                    FilterPrimitiveKind::Morphology(MorphologyPrimitive {
+                        input: self["in"].as_filter_input().unwrap(),
+                        radius_x: radius[0],
+                        radius_y: *radius.get(1).unwrap_or(&radius[0]),
+                        operator: self["operator"].as_str()
+                            .and_then(|x| StringEnum::from_str(x))
+                            .unwrap_or(MorphologyOperator::Erode),
+                    })
+                }
+                "convolve-matrix" => {
+                    let order = self["order"].as_vec_u32().unwrap();
+                    let order_x = order[0];
+                    let order_y = *order.get(1).unwrap_or(&order_x);
+                    let values = self["kernel"].as_vec_f32().unwrap();
+                    assert!(
+                        values.len() == (order_x * order_y) as usize,
+                        "The kernel must have order-x * order-y values",
+                    );
+                    assert!(
+                        values.len() <= SVGFE_CONVOLVE_VALUES_LIMIT,
+                        "Too many values for a convolve matrix filter primitive",
+                    );
+                    let mut kernel = [0.0; SVGFE_CONVOLVE_VALUES_LIMIT];
+                    kernel[.. values.len()].copy_from_slice(&values);
+                    let target = self["target"].as_vec_u32().unwrap_or(vec![order_x / 2, order_y / 2]);
+
+                    FilterPrimitiveKind::ConvolveMatrix(ConvolveMatrixPrimitive {
+                        input: self["in"].as_filter_input().unwrap(),
+                        order_x,
+                        order_y,
+                        kernel,
+                        divisor: self["divisor"].as_f32().unwrap_or(0.0),
+                        bias: self["bias"].as_f32().unwrap_or(0.0),
+                        target_x: target[0],
+                        target_y: target[1],
+                        edge_mode: self["edge-mode"].as_str()
+                            .and_then(|x| StringEnum::from_str(x))
+                            .unwrap_or(EdgeMode::Duplicate),
+                        preserve_alpha: self["preserve-alpha"].as_bool().unwrap_or(false),
+                    })
+                }
+                "displacement-map" => {
+                    FilterPrimitiveKind::DisplacementMap(DisplacementMapPrimitive {
+                        input1: self["in1"].as_filter_input().unwrap(),
+                        input2: self["in2"].as_filter_input().unwrap(),
+                        scale: self["scale"].as_f32().unwrap_or(0.0),
+                        x_channel_selector: self["x-channel"].as_str()
+                            .and_then(|x| StringEnum::from_str(x))
+                            .unwrap_or(ColorChannel::Alpha),
+                        y_channel_selector: self["y-channel"].as_str()
+                            .and_then(|x| StringEnum::from_str(x))
+                            .unwrap_or(ColorChannel::Alpha),
+                    })
+                }
+                "diffuse-lighting" => {
+                    FilterPrimitiveKind::DiffuseLighting(DiffuseLightingPrimitive {
+                        input: self["in"].as_filter_input().unwrap(),
+                        surface_scale: self["surface-scale"].as_f32().unwrap_or(1.0),
+                        diffuse_constant: self["diffuse-constant"].as_f32().unwrap_or(1.0),
+                        lighting_color: self["lighting-color"].as_colorf().unwrap_or(ColorF::WHITE),
+                        light: as_light_source(&self["light"]),
+                    })
+                }
+                "specular-lighting" => {
+                    FilterPrimitiveKind::SpecularLighting(SpecularLightingPrimitive {
+                        input: self["in"].as_filter_input().unwrap(),
+                        surface_scale: self["surface-scale"].as_f32().unwrap_or(1.0),
+                        specular_constant: self["specular-constant"].as_f32().unwrap_or(1.0),
+                        specular_exponent: self["specular-exponent"].as_f32().unwrap_or(1.0),
+                        lighting_color: self["lighting-color"].as_colorf().unwrap_or(ColorF::WHITE),
+                        light: as_light_source(&self["light"]),
+                    })
+                }
+                "tile" => {
+                    FilterPrimitiveKind::Tile(TilePrimitive {
+                        input: self["in"].as_filter_input().unwrap(),
+                        source_rect: self["rect"].as_rect().unwrap(),
+                    })
+                }
+                "merge" => {
+                    let inputs = self["inputs"].as_vec().unwrap();
+                    assert!(
+                        inputs.len() <= SVGFE_MERGE_INPUTS_LIMIT,
+                        "Too many inputs for a merge filter primitive",
+                    );
+                    let mut merge = MergePrimitive {
+                        input_count: inputs.len() as u32,
+                        ..MergePrimitive::default()
+                    };
+                    for (input, yaml) in merge.inputs.iter_mut().zip(inputs) {
+                        *input = yaml.as_filter_input().unwrap();
+                    }
+                    FilterPrimitiveKind::Merge(merge)
+                }
+                "image" => {
+                    // The image key is resolved by the frame reader, which
+                    // loads the image from the "src" file.
+                    FilterPrimitiveKind::Image(ImagePrimitive {
+                        image_key: ImageKey::DUMMY,
+                        rect: self["rect"].as_rect().unwrap_or(LayoutRect::zero()),
+                        rendering: self["rendering"].as_str()
+                            .and_then(|x| StringEnum::from_str(x))
+                            .unwrap_or(ImageRendering::Auto),
+                    })
+                }
                 _ => return None,
             };
 
@@ -805,4 +1048,28 @@ impl YamlHelper for Yaml {
     fn as_color_space(&self) -> Option<ColorSpace> {
         self.as_str().and_then(|x| StringEnum::from_str(x))
     }
+
+    fn as_rgb_color_space(&self) -> Option<RgbColorSpace> {
+        self.as_str().and_then(|x| StringEnum::from_str(x))
+    }
+
+    fn as_color_interpolation_space(&self) -> Option<ColorInterpolationSpace> {
+        // e.g. "oklab", or "oklch longer" to specify the hue interpolation method.
+        let mut words = self.as_str()?.split_whitespace();
+        let space = words.next()?;
+        let hue = match words.next() {
+            Some("shorter") | None => HueInterpolationMethod::Shorter,
+            Some("longer") => HueInterpolationMethod::Longer,
+            Some(method) => panic!("Invalid hue interpolation method: {}", method),
+        };
+
+        match space {
+            "srgb" => Some(ColorInterpolationSpace::Srgb),
+            "srgb-linear" => Some(ColorInterpolationSpace::SrgbLinear),
+            "oklab" => Some(ColorInterpolationSpace::Oklab),
+            "oklch" => Some(ColorInterpolationSpace::Oklch(hue)),
+            "hsl" => Some(ColorInterpolationSpace::Hsl(hue)),
+            _ => None,
+        }
+    }
 }